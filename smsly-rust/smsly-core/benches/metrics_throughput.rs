@@ -0,0 +1,144 @@
+//! Throughput of `SimpleMetrics` under concurrent writers.
+//!
+//! Run with `cargo bench -p smsly-core --bench metrics_throughput`. The single
+//! `Mutex<HashMap>` baseline mirrors the previous storage layout for comparison.
+
+use smsly_core::metrics::SimpleMetrics;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const TASKS: usize = 32;
+const OPS_PER_TASK: usize = 50_000;
+
+fn labels(task: usize) -> Option<HashMap<String, String>> {
+    Some(HashMap::from([
+        ("route".to_string(), format!("/v1/route/{}", task % 8)),
+        ("status".to_string(), "2xx".to_string()),
+    ]))
+}
+
+async fn run_sharded() -> Duration {
+    let metrics = Arc::new(SimpleMetrics::new(None));
+    let start = Instant::now();
+    let handles: Vec<_> = (0..TASKS)
+        .map(|task| {
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                for i in 0..OPS_PER_TASK {
+                    metrics.increment("bench_requests", 1, labels(task));
+                    metrics.observe(
+                        "bench_duration_seconds",
+                        (i % 100) as f64 / 1000.0,
+                        labels(task),
+                    );
+                }
+            })
+        })
+        .collect();
+    for h in handles {
+        h.await.unwrap();
+    }
+    start.elapsed()
+}
+
+async fn run_handles() -> Duration {
+    let metrics: &'static SimpleMetrics = Box::leak(Box::new(SimpleMetrics::new(None)));
+    let start = Instant::now();
+    let handles: Vec<_> = (0..TASKS)
+        .map(|task| {
+            let requests = metrics.counter("bench_requests", labels(task));
+            let duration = metrics.histogram("bench_duration_seconds", labels(task));
+            tokio::spawn(async move {
+                for i in 0..OPS_PER_TASK {
+                    requests.inc();
+                    duration.observe((i % 100) as f64 / 1000.0);
+                }
+            })
+        })
+        .collect();
+    for h in handles {
+        h.await.unwrap();
+    }
+    start.elapsed()
+}
+
+/// The previous storage layout: one mutex per metric kind.
+#[derive(Default)]
+struct MutexMetrics {
+    counters: Mutex<HashMap<String, i64>>,
+    histograms: Mutex<HashMap<String, Vec<f64>>>,
+}
+
+impl MutexMetrics {
+    fn make_key(name: &str, labels: &Option<HashMap<String, String>>) -> String {
+        let mut sorted: Vec<_> = labels.iter().flatten().collect();
+        sorted.sort_by_key(|a| a.0);
+        let label_str: Vec<String> = sorted.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        format!("{}{{{}}}", name, label_str.join(","))
+    }
+
+    fn increment(&self, name: &str, value: i64, labels: Option<HashMap<String, String>>) {
+        let key = Self::make_key(name, &labels);
+        *self.counters.lock().unwrap().entry(key).or_insert(0) += value;
+    }
+
+    fn observe(&self, name: &str, value: f64, labels: Option<HashMap<String, String>>) {
+        let key = Self::make_key(name, &labels);
+        self.histograms
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .push(value);
+    }
+}
+
+async fn run_single_mutex() -> Duration {
+    let metrics = Arc::new(MutexMetrics::default());
+    let start = Instant::now();
+    let handles: Vec<_> = (0..TASKS)
+        .map(|task| {
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                for i in 0..OPS_PER_TASK {
+                    metrics.increment("bench_requests", 1, labels(task));
+                    metrics.observe(
+                        "bench_duration_seconds",
+                        (i % 100) as f64 / 1000.0,
+                        labels(task),
+                    );
+                }
+            })
+        })
+        .collect();
+    for h in handles {
+        h.await.unwrap();
+    }
+    start.elapsed()
+}
+
+fn report(label: &str, elapsed: Duration) {
+    let ops = (TASKS * OPS_PER_TASK * 2) as f64;
+    println!(
+        "{:<14} {:>10.2?} {:>14.0} ops/s",
+        label,
+        elapsed,
+        ops / elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    println!(
+        "{} tasks x {} iterations (increment + observe)",
+        TASKS, OPS_PER_TASK
+    );
+    report("single mutex", runtime.block_on(run_single_mutex()));
+    report("sharded", runtime.block_on(run_sharded()));
+    report("typed handles", runtime.block_on(run_handles()));
+}
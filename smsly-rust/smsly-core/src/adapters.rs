@@ -1,15 +1,25 @@
+use crate::circuit_breaker::{CallPermit, CircuitRegistry, CircuitState};
+use crate::dlr::{self, DlrReason};
+use crate::inter_service_metrics::{record_call, status_from_code};
+use crate::metrics::GLOBAL_METRICS;
+use crate::providers::failed;
+use crate::retry::{classify_send, RetryClass};
+use crate::sender_id::SenderRewrites;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{info, warn};
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum MessageStatus {
+    #[default]
     Pending,
     Sent,
     Delivered,
@@ -17,9 +27,43 @@ pub enum MessageStatus {
     Rejected,
 }
 
-impl Default for MessageStatus {
-    fn default() -> Self {
-        Self::Pending
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Sms,
+    Mms,
+    Whatsapp,
+    Rcs,
+    Viber,
+    Email,
+    Push,
+}
+
+impl Channel {
+    pub fn supported_by(&self, adapter: &dyn BaseProviderAdapter) -> bool {
+        match self {
+            Channel::Sms => adapter.supports_sms(),
+            Channel::Mms => adapter.supports_mms(),
+            Channel::Whatsapp => adapter.supports_whatsapp(),
+            Channel::Rcs => adapter.supports_rcs(),
+            Channel::Viber => adapter.supports_viber(),
+            Channel::Email => adapter.supports_email(),
+            Channel::Push => adapter.supports_push(),
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Channel::Sms => "sms",
+            Channel::Mms => "mms",
+            Channel::Whatsapp => "whatsapp",
+            Channel::Rcs => "rcs",
+            Channel::Viber => "viber",
+            Channel::Email => "email",
+            Channel::Push => "push",
+        })
     }
 }
 
@@ -40,6 +84,192 @@ pub struct SendResult {
     pub segments: u32,
 }
 
+/// `OutboundSms::metadata` key for when the message stops being worth
+/// sending, in unix seconds; see `OutboundSms::with_validity`.
+pub const EXPIRES_AT: &str = "expires_at";
+
+/// `OutboundSms::metadata` key for the attachments of a message sent as
+/// MMS; see `OutboundSms::with_media`.
+pub const MEDIA_URLS: &str = "media_urls";
+
+/// One message in a batch send.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutboundSms {
+    pub to: String,
+    pub from: String,
+    pub body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, Value>>,
+}
+
+impl OutboundSms {
+    pub fn new(to: &str, from: &str, body: &str) -> Self {
+        Self {
+            to: to.to_string(),
+            from: from.to_string(),
+            body: body.to_string(),
+            metadata: None,
+        }
+    }
+
+    /// Expires the message `period` from now: sends after that fail with
+    /// error code `expired`, and providers that take a validity period are
+    /// given what remains of it (SMPP, Twilio, Infobip, Vonage), within the
+    /// limits each provider accepts.
+    pub fn with_validity(mut self, period: Duration) -> Self {
+        let expires_at = unix_now() + period.as_secs_f64();
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert(EXPIRES_AT.to_string(), Value::from(expires_at));
+        self
+    }
+
+    pub fn expires_at(&self) -> Option<f64> {
+        self.metadata.as_ref()?.get(EXPIRES_AT)?.as_f64()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        remaining_validity(self.metadata.as_ref()) == Some(Duration::ZERO)
+    }
+
+    /// Attachments for when the message goes out as MMS.
+    pub fn with_media<I: IntoIterator<Item = S>, S: Into<String>>(mut self, urls: I) -> Self {
+        let urls: Vec<Value> = urls
+            .into_iter()
+            .map(|url| Value::from(url.into()))
+            .collect();
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert(MEDIA_URLS.to_string(), Value::from(urls));
+        self
+    }
+
+    pub fn media_urls(&self) -> Vec<String> {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get(MEDIA_URLS))
+            .and_then(Value::as_array)
+            .map(|urls| {
+                urls.iter()
+                    .filter_map(|url| url.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn unix_now() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// Validity left for a message with this metadata: `None` if it never
+/// expires, zero once it has.
+pub fn remaining_validity(metadata: Option<&HashMap<String, Value>>) -> Option<Duration> {
+    let expires_at = metadata?.get(EXPIRES_AT)?.as_f64()?;
+    Some(Duration::try_from_secs_f64(expires_at - unix_now()).unwrap_or(Duration::ZERO))
+}
+
+/// Result for a message whose validity ran out before it was sent.
+pub fn expired_result() -> SendResult {
+    failed(
+        Some("expired".to_string()),
+        "Validity period elapsed before sending",
+        None,
+    )
+}
+
+/// A tappable chip under an RCS message or card.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RcsSuggestion {
+    /// Sends `text` back as the user's reply, with `postback_data` attached.
+    Reply { text: String, postback_data: String },
+    OpenUrl {
+        text: String,
+        postback_data: String,
+        url: String,
+    },
+    Dial {
+        text: String,
+        postback_data: String,
+        phone_number: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RcsMediaHeight {
+    Short,
+    #[default]
+    Medium,
+    Tall,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RcsMedia {
+    pub file_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    #[serde(default)]
+    pub height: RcsMediaHeight,
+}
+
+/// A rich card; at least one of title, description or media is required.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RcsCard {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<RcsMedia>,
+    /// Up to four per card.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<RcsSuggestion>,
+}
+
+/// Body of an RCS message. `suggestions` are the reply chips shown under
+/// the message (up to eleven).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RcsContent {
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        suggestions: Vec<RcsSuggestion>,
+    },
+    File {
+        file_url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thumbnail_url: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        suggestions: Vec<RcsSuggestion>,
+    },
+    RichCard {
+        card: RcsCard,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        suggestions: Vec<RcsSuggestion>,
+    },
+    /// Two to ten horizontally scrolling cards.
+    Carousel {
+        cards: Vec<RcsCard>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        suggestions: Vec<RcsSuggestion>,
+    },
+}
+
+impl RcsContent {
+    pub fn text(text: &str) -> Self {
+        RcsContent::Text {
+            text: text.to_string(),
+            suggestions: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WebhookEvent {
     pub provider_message_id: String,
@@ -47,6 +277,31 @@ pub struct WebhookEvent {
     pub timestamp: Option<f64>,
     pub error_code: Option<String>,
     pub error_message: Option<String>,
+    /// Provider-independent failure reason; see `dlr::normalize`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<DlrReason>,
+    pub raw_payload: Option<Value>,
+}
+
+/// An attachment on an inbound message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InboundMedia {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+}
+
+/// A mobile-originated message: a reply or a new message sent to one of
+/// our numbers, agents or sender IDs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboundMessage {
+    pub provider_message_id: Option<String>,
+    pub from: String,
+    pub to: String,
+    pub body: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub media: Vec<InboundMedia>,
+    pub timestamp: Option<f64>,
     pub raw_payload: Option<Value>,
 }
 
@@ -59,6 +314,11 @@ pub enum AdapterError {
 #[async_trait]
 pub trait BaseProviderAdapter: Send + Sync {
     fn name(&self) -> String;
+    /// False for adapters whose `send_sms` goes out over another channel
+    /// (RCS, Viber, email, push), so SMS routing skips them.
+    fn supports_sms(&self) -> bool {
+        true
+    }
     fn supports_mms(&self) -> bool {
         false
     }
@@ -68,6 +328,15 @@ pub trait BaseProviderAdapter: Send + Sync {
     fn supports_rcs(&self) -> bool {
         false
     }
+    fn supports_viber(&self) -> bool {
+        false
+    }
+    fn supports_email(&self) -> bool {
+        false
+    }
+    fn supports_push(&self) -> bool {
+        false
+    }
 
     async fn initialize(&self) {
         info!("Provider adapter initialized: {}", self.name());
@@ -85,6 +354,19 @@ pub trait BaseProviderAdapter: Send + Sync {
         metadata: Option<HashMap<String, Value>>,
     ) -> SendResult;
 
+    /// Sends many messages, returning results in input order. The default
+    /// sends one at a time; adapters with a bulk API should override it.
+    async fn send_sms_batch(&self, messages: Vec<OutboundSms>) -> Vec<SendResult> {
+        let mut results = Vec::with_capacity(messages.len());
+        for message in messages {
+            results.push(
+                self.send_sms(&message.to, &message.from, &message.body, message.metadata)
+                    .await,
+            );
+        }
+        results
+    }
+
     async fn send_mms(
         &self,
         _to: &str,
@@ -102,27 +384,418 @@ pub trait BaseProviderAdapter: Send + Sync {
         }
     }
 
+    /// Sends an RCS message as the business messaging agent `agent_id`.
+    async fn send_rcs(&self, _to: &str, _agent_id: &str, _content: RcsContent) -> SendResult {
+        SendResult {
+            success: false,
+            status: MessageStatus::Failed,
+            error_message: Some(format!("{} does not support RCS", self.name())),
+            ..Default::default()
+        }
+    }
+
+    /// WhatsApp from adapters that also send SMS; adapters whose `send_sms`
+    /// already goes out over WhatsApp don't need it.
+    async fn send_whatsapp(
+        &self,
+        _to: &str,
+        _from: &str,
+        _body: &str,
+        _metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        SendResult {
+            success: false,
+            status: MessageStatus::Failed,
+            error_message: Some(format!("{} does not support WhatsApp", self.name())),
+            ..Default::default()
+        }
+    }
+
+    /// Rejects by default: a provider accepts callbacks only once it
+    /// verifies them (see `providers::signature` for the usual schemes).
     async fn validate_webhook(&self, _headers: &HashMap<String, String>, _body: &[u8]) -> bool {
-        true
+        warn!(
+            "{} webhook rejected: no signature validation implemented",
+            self.name()
+        );
+        false
     }
 
     async fn parse_webhook(&self, _body: &[u8]) -> Result<WebhookEvent, String> {
         Err(format!("{} must implement webhook parsing", self.name()))
     }
 
+    /// Parses an inbound-message webhook. Callers check `validate_webhook`
+    /// first, as for delivery reports.
+    async fn parse_inbound(&self, _body: &[u8]) -> Result<InboundMessage, String> {
+        Err(format!("{} does not receive inbound messages", self.name()))
+    }
+
     async fn health_check(&self) -> bool {
         true
     }
 }
 
+/// Deployment-specific selection settings for a registered provider.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderProfile {
+    /// Lower sorts first, as with MX records.
+    pub priority: i32,
+    /// ISO 3166 alpha-2 codes the provider may send to; `None` allows all.
+    pub countries: Option<HashSet<String>>,
+}
+
+impl Default for ProviderProfile {
+    fn default() -> Self {
+        Self {
+            priority: 100,
+            countries: None,
+        }
+    }
+}
+
+impl ProviderProfile {
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_countries<I: IntoIterator<Item = S>, S: AsRef<str>>(
+        mut self,
+        countries: I,
+    ) -> Self {
+        self.countries = Some(
+            countries
+                .into_iter()
+                .map(|c| c.as_ref().to_uppercase())
+                .collect(),
+        );
+        self
+    }
+
+    pub fn allows_country(&self, country: &str) -> bool {
+        self.countries
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&country.to_uppercase()))
+    }
+}
+
+/// `record_call` status for a provider send: the HTTP status when the
+/// provider reported one as its error code, otherwise the provider's own
+/// failures are server errors and the recipient's client errors. Failures
+/// without a code never got a response.
+fn call_status(provider: &str, result: &SendResult) -> &'static str {
+    if result.success {
+        return "success";
+    }
+    match result.error_code.as_deref() {
+        None if result
+            .error_message
+            .as_deref()
+            .is_some_and(|message| message.contains("timed out")) =>
+        {
+            "timeout"
+        }
+        None => "exception",
+        Some(code) => match code.parse::<u16>() {
+            Ok(status @ 400..=599) => status_from_code(status),
+            _ if is_provider_failure(provider, result) => "server_error",
+            _ => "client_error",
+        },
+    }
+}
+
+/// Whether a failed send says something about the provider rather than the
+/// recipient: errors without a code (timeouts, connection failures) and
+/// codes that normalize to provider, network or unknown failures. Our own
+/// throttling doesn't count.
+fn is_provider_failure(provider: &str, result: &SendResult) -> bool {
+    if result.success || result.error_code.as_deref() == Some("throttled") {
+        return false;
+    }
+    match dlr::normalize(
+        provider,
+        result.error_code.as_deref(),
+        result.error_message.as_deref(),
+    ) {
+        Some(reason) => matches!(
+            reason,
+            DlrReason::ProviderError | DlrReason::NetworkError | DlrReason::Unknown
+        ),
+        None => true,
+    }
+}
+
 pub struct ProviderRegistry {
     adapters: RwLock<HashMap<String, Arc<Box<dyn BaseProviderAdapter>>>>,
+    profiles: RwLock<HashMap<String, ProviderProfile>>,
+    circuits: Arc<CircuitRegistry>,
+    sender_rewrites: SenderRewrites,
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ProviderRegistry {
     pub fn new() -> Self {
         Self {
             adapters: RwLock::new(HashMap::new()),
+            profiles: RwLock::new(HashMap::new()),
+            circuits: Arc::new(CircuitRegistry::new()),
+            sender_rewrites: SenderRewrites::default(),
+        }
+    }
+
+    /// Breakers for the providers, one per provider name, configured by
+    /// the registry's policies (e.g. `Settings::circuit_registry`). Share
+    /// the registry with `create_circuit_debug_router` to list them.
+    pub fn with_circuits(mut self, circuits: Arc<CircuitRegistry>) -> Self {
+        self.circuits = circuits;
+        self
+    }
+
+    pub fn circuits(&self) -> &Arc<CircuitRegistry> {
+        &self.circuits
+    }
+
+    /// Replacement senders `send_with_fallback` uses where a market refuses
+    /// the requested one.
+    pub fn with_sender_rewrites(mut self, rewrites: SenderRewrites) -> Self {
+        self.sender_rewrites = rewrites;
+        self
+    }
+
+    /// Whether `name`'s circuit is open. `find_capable`, and so
+    /// `send_with_fallback`, skip such providers, as does `RoutingEngine`;
+    /// `get` still returns them, e.g. for webhooks.
+    pub fn circuit_open(&self, name: &str) -> bool {
+        self.circuits.breaker(&name.to_lowercase()).state() == CircuitState::Open
+    }
+
+    /// Feeds a send outcome into the provider's breaker, if it lets a call
+    /// through. Failures that are the recipient's (invalid number,
+    /// opt-out, ...) don't count either way.
+    pub fn record_result(&self, name: &str, result: &SendResult) {
+        if let Ok(permit) = self.circuits.breaker(&name.to_lowercase()).try_acquire() {
+            Self::settle(permit, name, result);
+        }
+    }
+
+    fn settle(permit: CallPermit, name: &str, result: &SendResult) {
+        if result.success {
+            permit.success();
+        } else if is_provider_failure(&name.to_lowercase(), result) {
+            permit.failure();
+        }
+    }
+
+    pub async fn set_profile(&self, name: &str, profile: ProviderProfile) {
+        self.profiles
+            .write()
+            .await
+            .insert(name.to_lowercase(), profile);
+    }
+
+    pub async fn profile(&self, name: &str) -> ProviderProfile {
+        self.profiles
+            .read()
+            .await
+            .get(&name.to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Adapters that support `channel` and may send to `country`, ordered by
+    /// profile priority and then name. Providers with an open breaker are
+    /// left out.
+    pub async fn find_capable(
+        &self,
+        channel: Channel,
+        country: &str,
+    ) -> Vec<(String, Arc<Box<dyn BaseProviderAdapter>>)> {
+        let profiles = self.profiles.read().await;
+        let mut candidates: Vec<_> = self
+            .adapters()
+            .await
+            .into_iter()
+            .filter_map(|(name, adapter)| {
+                let profile = profiles.get(&name).cloned().unwrap_or_default();
+                (channel.supported_by(adapter.as_ref().as_ref())
+                    && profile.allows_country(country)
+                    && !self.circuit_open(&name))
+                .then_some((profile.priority, name, adapter))
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        candidates
+            .into_iter()
+            .map(|(_, name, adapter)| (name, adapter))
+            .collect()
+    }
+
+    /// Tries each channel in order (e.g. `[Viber, Sms]`) through every capable
+    /// adapter until one accepts the message, and returns that attempt, or
+    /// the last failed one. `None` if nothing could carry it. Each channel
+    /// goes through the adapter's method for it (see `send_on`); MMS takes
+    /// its attachments from `OutboundSms::with_media`. Every attempt is
+    /// recorded with `inter_service_metrics::record_call`, the provider
+    /// being the target.
+    ///
+    /// This only falls back on synchronous rejections; a message accepted on
+    /// the first channel but later undelivered has to be resent by the caller
+    /// once its delivery report arrives.
+    ///
+    /// SMS and MMS senders are checked with `sender_id::validate_sender`
+    /// first and rewritten per `with_sender_rewrites` where the market
+    /// refuses them; the rewrite is recorded under `sender_rewrite` in the
+    /// result's `raw_response`. A sender that is still refused skips the
+    /// channel with error code `invalid_sender`. An empty `from` leaves the
+    /// choice to the provider.
+    ///
+    /// Messages past their `expires_at` aren't sent; they fail on the first
+    /// channel with error code `expired`. Failures that no other provider
+    /// would get past, such as an invalid destination number (see
+    /// `retry::classify_send`), end the fallback too.
+    pub async fn send_with_fallback(
+        &self,
+        channels: &[Channel],
+        country: &str,
+        message: &OutboundSms,
+    ) -> Option<(Channel, String, SendResult)> {
+        if message.is_expired() {
+            warn!("Not sending expired message to {}", message.to);
+            GLOBAL_METRICS.increment("messages_expired", 1, None);
+            return channels
+                .first()
+                .map(|&channel| (channel, String::new(), expired_result()));
+        }
+        let mut last = None;
+        for &channel in channels {
+            let mut from = message.from.clone();
+            let mut rewrite = None;
+            if matches!(channel, Channel::Sms | Channel::Mms)
+                && !from.is_empty()
+                && !country.is_empty()
+            {
+                match self.sender_rewrites.apply(&from, country) {
+                    Ok((sender, rewritten)) => {
+                        if let Some(rewritten) = &rewritten {
+                            info!(
+                                "Sender {} rewritten to {} for {}: {}",
+                                rewritten.original, rewritten.rewritten, country, rewritten.reason
+                            );
+                        }
+                        from = sender.value;
+                        rewrite = rewritten;
+                    }
+                    Err(e) => {
+                        warn!("{} skipped: {}", channel, e);
+                        let result =
+                            failed(Some("invalid_sender".to_string()), e.to_string(), None);
+                        last = Some((channel, String::new(), result));
+                        continue;
+                    }
+                }
+            }
+            for (name, adapter) in self.find_capable(channel, country).await {
+                // Half-open circuits let only a few trial sends through.
+                let operation = format!("send_{}", channel);
+                let Ok(permit) = self.circuits.breaker(&name).try_acquire() else {
+                    record_call(&name, &operation, "circuit_open", Duration::ZERO);
+                    continue;
+                };
+                let start = Instant::now();
+                let mut result =
+                    Self::send_on(channel, adapter.as_ref().as_ref(), &from, message).await;
+                record_call(
+                    &name,
+                    &operation,
+                    call_status(&name, &result),
+                    start.elapsed(),
+                );
+                Self::settle(permit, &name, &result);
+                if let Some(rewrite) = &rewrite {
+                    let record = serde_json::to_value(rewrite).unwrap_or_default();
+                    result.raw_response = Some(match result.raw_response.take() {
+                        Some(Value::Object(mut map)) => {
+                            map.insert("sender_rewrite".to_string(), record);
+                            Value::Object(map)
+                        }
+                        Some(other) => {
+                            serde_json::json!({ "response": other, "sender_rewrite": record })
+                        }
+                        None => serde_json::json!({ "sender_rewrite": record }),
+                    });
+                }
+                if result.success {
+                    return Some((channel, name, result));
+                }
+                if classify_send(&name, &result) == RetryClass::NotRetryable {
+                    warn!(
+                        "{} send via {} failed permanently: {}",
+                        channel,
+                        name,
+                        result.error_message.as_deref().unwrap_or_default()
+                    );
+                    return Some((channel, name, result));
+                }
+                warn!(
+                    "{} send via {} failed, trying next: {}",
+                    channel,
+                    name,
+                    result.error_message.as_deref().unwrap_or_default()
+                );
+                last = Some((channel, name, result));
+            }
+        }
+        last
+    }
+
+    /// Sends `message` over `channel` from `from`. Adapters that don't send
+    /// SMS go through `send_sms`, which uses their own channel; the others
+    /// need the channel's method (`send_mms`, `send_whatsapp`) and fail
+    /// without one rather than send an SMS instead.
+    async fn send_on(
+        channel: Channel,
+        adapter: &dyn BaseProviderAdapter,
+        from: &str,
+        message: &OutboundSms,
+    ) -> SendResult {
+        let metadata = message.metadata.clone();
+        match channel {
+            Channel::Sms => {
+                adapter
+                    .send_sms(&message.to, from, &message.body, metadata)
+                    .await
+            }
+            Channel::Mms => {
+                let media_urls = message.media_urls();
+                if media_urls.is_empty() {
+                    return failed(None, "MMS needs media_urls", None);
+                }
+                let text = Some(message.body.as_str()).filter(|body| !body.is_empty());
+                adapter
+                    .send_mms(&message.to, from, text, media_urls, metadata)
+                    .await
+            }
+            _ if !adapter.supports_sms() => {
+                adapter
+                    .send_sms(&message.to, from, &message.body, metadata)
+                    .await
+            }
+            Channel::Whatsapp => {
+                adapter
+                    .send_whatsapp(&message.to, from, &message.body, metadata)
+                    .await
+            }
+            _ => failed(
+                None,
+                format!("{} has no {} send", adapter.name(), channel),
+                None,
+            ),
         }
     }
 
@@ -132,6 +805,23 @@ impl ProviderRegistry {
         self.adapters.write().await.insert(name, Arc::new(adapter));
     }
 
+    /// Registers under `name` rather than `adapter.name()`, e.g. for a second
+    /// account with the same provider. Replaces any adapter already there.
+    pub async fn register_as(&self, name: &str, adapter: Box<dyn BaseProviderAdapter>) {
+        let name = name.to_lowercase();
+        info!("Provider registered: {}", name);
+        self.adapters.write().await.insert(name, Arc::new(adapter));
+    }
+
+    pub async fn unregister(&self, name: &str) -> Option<Arc<Box<dyn BaseProviderAdapter>>> {
+        let removed = self.adapters.write().await.remove(&name.to_lowercase());
+        self.profiles.write().await.remove(&name.to_lowercase());
+        if removed.is_some() {
+            info!("Provider unregistered: {}", name);
+        }
+        removed
+    }
+
     pub async fn get(&self, name: &str) -> Result<Arc<Box<dyn BaseProviderAdapter>>, AdapterError> {
         let adapters = self.adapters.read().await;
         adapters
@@ -143,4 +833,42 @@ impl ProviderRegistry {
     pub async fn list(&self) -> Vec<String> {
         self.adapters.read().await.keys().cloned().collect()
     }
+
+    /// Snapshot of registered adapters, so callers don't hold the lock across awaits.
+    pub async fn adapters(&self) -> Vec<(String, Arc<Box<dyn BaseProviderAdapter>>)> {
+        self.adapters
+            .read()
+            .await
+            .iter()
+            .map(|(name, adapter)| (name.clone(), adapter.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn call_status_follows_http_status_and_error_kind() {
+        let ok = SendResult {
+            success: true,
+            ..Default::default()
+        };
+        assert_eq!(call_status("twilio", &ok), "success");
+
+        let http = |code: &str| failed(Some(code.to_string()), "Unknown error", None);
+        assert_eq!(call_status("twilio", &http("503")), "server_error");
+        assert_eq!(call_status("twilio", &http("401")), "client_error");
+
+        let invalid_number = failed(Some("21211".to_string()), "Invalid 'To' number", None);
+        assert_eq!(call_status("twilio", &invalid_number), "client_error");
+        let unknown = failed(Some("99999".to_string()), "Something broke", None);
+        assert_eq!(call_status("twilio", &unknown), "server_error");
+
+        let timeout = failed(None, "error sending request: operation timed out", None);
+        assert_eq!(call_status("twilio", &timeout), "timeout");
+        let refused = failed(None, "error sending request: connection refused", None);
+        assert_eq!(call_status("twilio", &refused), "exception");
+    }
 }
@@ -0,0 +1,541 @@
+//! Minimal gzip (RFC 1952) for archive objects.
+//!
+//! `compress` emits a single fixed-Huffman deflate block with greedy LZ77
+//! matching: well short of zlib's ratio, but JSONL of message rows is
+//! repetitive enough to shrink several times over, and any gzip reader
+//! opens the result. `decompress` handles all deflate block types.
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 32;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order of the code length code lengths in a dynamic block header.
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Writes the low `count` bits of `value`, least significant first.
+    fn bits(&mut self, value: u32, count: u32) {
+        self.acc |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, most significant bit first.
+    fn code(&mut self, code: u32, len: u32) {
+        self.bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+fn write_literal(w: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => w.code(0x30 + symbol, 8),
+        144..=255 => w.code(0x190 + symbol - 144, 9),
+        256..=279 => w.code(symbol - 256, 7),
+        _ => w.code(0xC0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(w: &mut BitWriter, length: usize, distance: usize) {
+    let li = LENGTH_BASE
+        .iter()
+        .rposition(|&b| b as usize <= length)
+        .unwrap_or(0);
+    write_literal(w, 257 + li as u16);
+    w.bits(
+        (length - LENGTH_BASE[li] as usize) as u32,
+        LENGTH_EXTRA[li] as u32,
+    );
+    let di = DIST_BASE
+        .iter()
+        .rposition(|&b| b as usize <= distance)
+        .unwrap_or(0);
+    w.code(di as u32, 5);
+    w.bits(
+        (distance - DIST_BASE[di] as usize) as u32,
+        DIST_EXTRA[di] as u32,
+    );
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let key = (data[pos] as u32) << 16 | (data[pos + 1] as u32) << 8 | data[pos + 2] as u32;
+    (key.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+fn insert(data: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize]) {
+    if pos + MIN_MATCH <= data.len() {
+        let h = hash(data, pos);
+        prev[pos] = head[h];
+        head[h] = pos;
+    }
+}
+
+/// Gzips `data`.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter {
+        out: vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff],
+        acc: 0,
+        count: 0,
+    };
+    // BFINAL, then BTYPE 01 (fixed Huffman).
+    w.bits(1, 1);
+    w.bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; data.len()];
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if pos + MIN_MATCH <= data.len() {
+            let mut candidate = head[hash(data, pos)];
+            let limit = (data.len() - pos).min(MAX_MATCH);
+            let mut chain = 0;
+            while candidate != usize::MAX && pos - candidate <= WINDOW && chain < MAX_CHAIN {
+                let len = data[candidate..]
+                    .iter()
+                    .zip(&data[pos..pos + limit])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = pos - candidate;
+                    if len == limit {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+        if best_len >= MIN_MATCH {
+            write_match(&mut w, best_len, best_dist);
+            for p in pos..pos + best_len {
+                insert(data, p, &mut head, &mut prev);
+            }
+            pos += best_len;
+        } else {
+            write_literal(&mut w, data[pos] as u16);
+            insert(data, pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+    write_literal(&mut w, 256);
+
+    let mut out = w.finish();
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        while self.count < count {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| "truncated deflate stream".to_string())?;
+            self.acc |= (byte as u32) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = self.acc & ((1u64 << count) - 1) as u32;
+        self.acc >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.acc = 0;
+        self.count = 0;
+    }
+}
+
+/// Canonical Huffman table as code counts per length plus symbols in
+/// code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, r: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= r.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+fn inflate_block(
+    r: &mut BitReader,
+    out: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = lit.decode(r)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let li = symbol - 257;
+                if li >= LENGTH_BASE.len() {
+                    return Err("invalid length symbol".to_string());
+                }
+                let length = LENGTH_BASE[li] as usize + r.bits(LENGTH_EXTRA[li] as u32)? as usize;
+                let di = dist.decode(r)? as usize;
+                if di >= DIST_BASE.len() {
+                    return Err("invalid distance symbol".to_string());
+                }
+                let distance = DIST_BASE[di] as usize + r.bits(DIST_EXTRA[di] as u32)? as usize;
+                if distance > out.len() {
+                    return Err("distance beyond start of output".to_string());
+                }
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+fn dynamic_tables(r: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let nlen = r.bits(5)? as usize + 257;
+    let ndist = r.bits(5)? as usize + 1;
+    let ncode = r.bits(4)? as usize + 4;
+    let mut clens = [0u8; 19];
+    for &i in &CLEN_ORDER[..ncode] {
+        clens[i] = r.bits(3)? as u8;
+    }
+    let clen = Huffman::new(&clens);
+    let mut lengths = Vec::with_capacity(nlen + ndist);
+    while lengths.len() < nlen + ndist {
+        let symbol = clen.decode(r)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (
+                *lengths
+                    .last()
+                    .ok_or_else(|| "repeat with no previous length".to_string())?,
+                3 + r.bits(2)? as usize,
+            ),
+            17 => (0, 3 + r.bits(3)? as usize),
+            _ => (0, 11 + r.bits(7)? as usize),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat));
+    }
+    if lengths.len() > nlen + ndist {
+        return Err("too many code lengths".to_string());
+    }
+    Ok((
+        Huffman::new(&lengths[..nlen]),
+        Huffman::new(&lengths[nlen..]),
+    ))
+}
+
+/// Gunzips a single-member gzip file, checking its CRC and length.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 18 || data[0] != 0x1f || data[1] != 0x8b || data[2] != 8 {
+        return Err("not a gzip file".to_string());
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    let skip_string = |pos: usize| -> Result<usize, String> {
+        data.get(pos..)
+            .unwrap_or_default()
+            .iter()
+            .position(|&b| b == 0)
+            .map(|end| pos + end + 1)
+            .ok_or_else(|| "truncated gzip header".to_string())
+    };
+    if flags & 0x04 != 0 {
+        let xlen = u16::from_le_bytes([data[pos], data[pos + 1]]) as usize;
+        pos += 2 + xlen;
+    }
+    if flags & 0x08 != 0 {
+        pos = skip_string(pos)?;
+    }
+    if flags & 0x10 != 0 {
+        pos = skip_string(pos)?;
+    }
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+    if pos + 8 > data.len() {
+        return Err("truncated gzip header".to_string());
+    }
+
+    let mut r = BitReader {
+        data: &data[..data.len() - 8],
+        pos,
+        acc: 0,
+        count: 0,
+    };
+    let mut out = Vec::with_capacity(data.len() * 4);
+    loop {
+        let last = r.bits(1)? == 1;
+        match r.bits(2)? {
+            0 => {
+                r.align();
+                let header = r
+                    .data
+                    .get(r.pos..r.pos + 4)
+                    .ok_or_else(|| "truncated stored block".to_string())?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if len != !u16::from_le_bytes([header[2], header[3]]) as usize {
+                    return Err("corrupt stored block length".to_string());
+                }
+                r.pos += 4;
+                let block = r
+                    .data
+                    .get(r.pos..r.pos + len)
+                    .ok_or_else(|| "truncated stored block".to_string())?;
+                out.extend_from_slice(block);
+                r.pos += len;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let lit = Huffman::new(&lengths);
+                let dist = Huffman::new(&[5u8; 30]);
+                inflate_block(&mut r, &mut out, &lit, &dist)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_tables(&mut r)?;
+                inflate_block(&mut r, &mut out, &lit, &dist)?;
+            }
+            _ => return Err("invalid deflate block type".to_string()),
+        }
+        if last {
+            break;
+        }
+    }
+
+    let trailer = &data[data.len() - 8..];
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out) || size != out.len() as u32 {
+        return Err("gzip checksum mismatch".to_string());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic bytes that deflate can't shrink.
+    fn noise(len: usize, mut seed: u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as u8
+            })
+            .collect()
+    }
+
+    fn round_trip(data: &[u8]) -> Vec<u8> {
+        let compressed = compress(data);
+        assert_eq!(decompress(&compressed).unwrap(), data);
+        compressed
+    }
+
+    fn rows() -> Vec<u8> {
+        (0..12)
+            .map(|i| {
+                format!(
+                    "{{\"id\":\"msg-{:03}\",\"status\":\"delivered\",\"to\":\"+1555010{}\"}}\n",
+                    i,
+                    i % 10
+                )
+            })
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    /// `gzip -9 -n` of `rows()`: one dynamic Huffman block.
+    const GZIP_9: &str = "1f8b08000000000002039dd23b0a84401084e1dc6374ea0ad36afbba8d30c3222882339a8877df09a5a262d39ffab2ba65f132c916bf95734e3e12d39cce98930feb728523f81cd39e43a966e634af9ee27e33a59822ab2956236b28d6206b29d622338a19b28e621db29e623db2816203b291622330fdef25cabfe40771c2f69a94020000";
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn decodes_gzip_9_output() {
+        let data = hex::decode(GZIP_9).unwrap();
+        let rows = rows();
+        assert_eq!(decompress(&data).unwrap(), rows);
+
+        let trailer = &data[data.len() - 8..];
+        assert_eq!(trailer[..4], 0x9AF6_C271u32.to_le_bytes());
+        assert_eq!(trailer[..4], crc32(&rows).to_le_bytes());
+        assert_eq!(trailer[4..], (rows.len() as u32).to_le_bytes());
+    }
+
+    #[test]
+    fn rejects_bad_trailer() {
+        let mut data = hex::decode(GZIP_9).unwrap();
+        let crc = data.len() - 8;
+        data[crc] ^= 1;
+        assert!(decompress(&data).is_err());
+
+        let mut data = hex::decode(GZIP_9).unwrap();
+        let size = data.len() - 4;
+        data[size] ^= 1;
+        assert!(decompress(&data).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_and_foreign_input() {
+        let data = compress(&rows());
+        assert!(decompress(&data[..data.len() / 2]).is_err());
+        assert!(decompress(b"PK\x03\x04 not gzip at all").is_err());
+        assert!(decompress(&[]).is_err());
+    }
+
+    #[test]
+    fn rejects_header_fields_past_the_end() {
+        // FEXTRA with a length past the end, then FNAME.
+        let mut data = vec![0x1f, 0x8b, 8, 0x04 | 0x08, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff];
+        data.extend_from_slice(&[0; 8]);
+        assert!(decompress(&data).is_err());
+    }
+
+    #[test]
+    fn round_trips_empty_input() {
+        let compressed = round_trip(b"");
+        assert_eq!(compressed.len(), 20);
+    }
+
+    #[test]
+    fn round_trips_incompressible_input() {
+        let data = noise(100_000, 0x9E37_79B9_7F4A_7C15);
+        let compressed = round_trip(&data);
+        // Fixed-Huffman literals cost 8 or 9 bits each.
+        assert!(compressed.len() < data.len() * 9 / 8 + 64);
+    }
+
+    #[test]
+    fn round_trips_long_runs() {
+        let mut data = vec![b'a'; 1 << 20];
+        data.extend(vec![0u8; 70_000]);
+        let compressed = round_trip(&data);
+        assert!(compressed.len() < data.len() / 100);
+    }
+
+    #[test]
+    fn round_trips_repeats_beyond_the_window() {
+        // A 40 KiB block repeated is too far back to match whole, so
+        // matches must stay within the 32 KiB window.
+        let block = noise(40 * 1024, 7);
+        let mut data = block.clone();
+        data.extend_from_slice(&block);
+        data.extend_from_slice(&block[..WINDOW]);
+        round_trip(&data);
+
+        // Exactly at the window edge is the furthest a match may reach.
+        let block = noise(WINDOW, 11);
+        let mut data = block.clone();
+        data.extend_from_slice(&block);
+        let compressed = round_trip(&data);
+        assert!(compressed.len() < data.len() * 3 / 4);
+    }
+
+    #[test]
+    fn round_trips_rows() {
+        let data: Vec<u8> = (0..50).flat_map(|_| rows()).collect();
+        let compressed = round_trip(&data);
+        assert!(compressed.len() < data.len() / 4);
+    }
+}
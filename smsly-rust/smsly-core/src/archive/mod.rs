@@ -0,0 +1,377 @@
+//! Archival of finished messages to object storage.
+//!
+//! The `Archiver` moves delivered and failed rows older than a cutoff out
+//! of Postgres in batches: each batch is deleted inside a transaction,
+//! written as one gzipped JSONL object (one `row_to_json` record per line)
+//! and only committed once the upload succeeded, so a failed upload leaves
+//! the rows in place. An index table maps each archived ID to its object
+//! for `fetch` and `find`.
+//!
+//! Objects are JSONL only; Parquet output is not implemented yet.
+
+pub mod gzip;
+#[cfg(feature = "aws")]
+pub mod s3;
+
+#[cfg(feature = "aws")]
+pub use s3::{S3Config, S3Store};
+
+use crate::database::quote_ident;
+use crate::metrics::GLOBAL_METRICS;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::postgres::PgPool;
+use sqlx::Row;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Default index table; include this in a service migration.
+pub const ARCHIVE_INDEX_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS message_archive_index (
+    message_id TEXT PRIMARY KEY,
+    object_key TEXT NOT NULL,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+"#;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error("Invalid archive config: {0}")]
+    Config(String),
+    #[error("Object storage error: {0}")]
+    Storage(String),
+    #[error("Corrupt archive object {key}: {reason}")]
+    Corrupt { key: String, reason: String },
+}
+
+/// Where archive objects are written and read back from.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> Result<(), ArchiveError>;
+    /// `None` if there is no such object.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, ArchiveError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    pub table: String,
+    pub id_column: String,
+    /// SQL type of `id_column`, for lookups that use its index.
+    pub id_type: String,
+    pub timestamp_column: String,
+    pub status_column: String,
+    /// Only rows in these (final) statuses are archived.
+    pub statuses: Vec<String>,
+    pub older_than: Duration,
+    /// Rows per object.
+    pub batch_size: i64,
+    /// Objects are written as `<prefix>/YYYY/MM/DD/<uuid>.jsonl.gz`.
+    pub key_prefix: String,
+    pub index_table: String,
+    pub gzip: bool,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            table: "messages".to_string(),
+            id_column: "id".to_string(),
+            id_type: "uuid".to_string(),
+            timestamp_column: "created_at".to_string(),
+            status_column: "status".to_string(),
+            statuses: vec!["delivered".to_string(), "failed".to_string()],
+            older_than: Duration::from_secs(90 * 86400),
+            batch_size: 5000,
+            key_prefix: "messages".to_string(),
+            index_table: "message_archive_index".to_string(),
+            gzip: true,
+        }
+    }
+}
+
+impl ArchiveConfig {
+    pub fn new(table: &str) -> Self {
+        Self {
+            table: table.to_string(),
+            key_prefix: table.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_id_column(mut self, column: &str, sql_type: &str) -> Self {
+        self.id_column = column.to_string();
+        self.id_type = sql_type.to_string();
+        self
+    }
+
+    pub fn with_timestamp_column(mut self, column: &str) -> Self {
+        self.timestamp_column = column.to_string();
+        self
+    }
+
+    pub fn with_status_column(mut self, column: &str, statuses: &[&str]) -> Self {
+        self.status_column = column.to_string();
+        self.statuses = statuses.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    pub fn with_older_than(mut self, age: Duration) -> Self {
+        self.older_than = age;
+        self
+    }
+
+    pub fn with_batch_size(mut self, size: i64) -> Self {
+        self.batch_size = size;
+        self
+    }
+
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.trim_matches('/').to_string();
+        self
+    }
+
+    pub fn with_index_table(mut self, table: &str) -> Self {
+        self.index_table = table.to_string();
+        self
+    }
+
+    pub fn with_gzip(mut self, gzip: bool) -> Self {
+        self.gzip = gzip;
+        self
+    }
+}
+
+/// Quoted identifiers for the archive queries, validated once.
+#[derive(Debug, Clone)]
+struct Columns {
+    table: String,
+    id: String,
+    id_type: String,
+    timestamp: String,
+    status: String,
+    index: String,
+}
+
+impl Columns {
+    fn new(config: &ArchiveConfig) -> Result<Self, ArchiveError> {
+        let quote = |name: &str| quote_ident(name).map_err(|e| ArchiveError::Config(e.to_string()));
+        if config.id_type.is_empty()
+            || !config
+                .id_type
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(ArchiveError::Config(format!(
+                "invalid id type {:?}",
+                config.id_type
+            )));
+        }
+        Ok(Self {
+            table: quote(&config.table)?,
+            id: quote(&config.id_column)?,
+            id_type: config.id_type.clone(),
+            timestamp: quote(&config.timestamp_column)?,
+            status: quote(&config.status_column)?,
+            index: quote(&config.index_table)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveReport {
+    pub archived: u64,
+    pub objects: Vec<String>,
+}
+
+pub struct Archiver {
+    pool: PgPool,
+    store: Arc<dyn ObjectStore>,
+    config: ArchiveConfig,
+    columns: Columns,
+}
+
+impl Archiver {
+    pub fn new(
+        pool: PgPool,
+        store: Arc<dyn ObjectStore>,
+        config: ArchiveConfig,
+    ) -> Result<Self, ArchiveError> {
+        Ok(Self {
+            columns: Columns::new(&config)?,
+            pool,
+            store,
+            config,
+        })
+    }
+
+    fn object_key(&self) -> String {
+        format!(
+            "{}/{}/{}.jsonl{}",
+            self.config.key_prefix,
+            Utc::now().format("%Y/%m/%d"),
+            Uuid::new_v4(),
+            if self.config.gzip { ".gz" } else { "" }
+        )
+    }
+
+    /// Archives one batch, returning the rows archived and their object.
+    /// Replicas can run this concurrently: candidate rows are claimed with
+    /// `SKIP LOCKED`.
+    pub async fn archive_batch(&self) -> Result<Option<(u64, String)>, ArchiveError> {
+        let c = &self.columns;
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query(&format!(
+            "DELETE FROM {table} AS t WHERE {id} IN ( \
+                 SELECT {id} FROM {table} \
+                 WHERE {status}::text = ANY($1) AND {ts} < now() - make_interval(secs => $2) \
+                 ORDER BY {ts} LIMIT $3 FOR UPDATE SKIP LOCKED) \
+             RETURNING t.{id}::text AS archive_id, row_to_json(t)::text AS record",
+            table = c.table,
+            id = c.id,
+            status = c.status,
+            ts = c.timestamp,
+        ))
+        .bind(&self.config.statuses)
+        .bind(self.config.older_than.as_secs_f64())
+        .bind(self.config.batch_size)
+        .fetch_all(&mut *tx)
+        .await?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        let mut ids = Vec::with_capacity(rows.len());
+        let mut body = Vec::new();
+        for row in &rows {
+            ids.push(row.try_get::<String, _>("archive_id")?);
+            body.extend_from_slice(row.try_get::<String, _>("record")?.as_bytes());
+            body.push(b'\n');
+        }
+        let key = self.object_key();
+        let (body, content_type) = if self.config.gzip {
+            (gzip::compress(&body), "application/gzip")
+        } else {
+            (body, "application/x-ndjson")
+        };
+        self.store.put(&key, body, content_type).await?;
+
+        sqlx::query(&format!(
+            "INSERT INTO {} (message_id, object_key) SELECT id, $2 FROM UNNEST($1::text[]) AS id \
+             ON CONFLICT (message_id) DO UPDATE SET object_key = EXCLUDED.object_key, \
+             archived_at = now()",
+            c.index
+        ))
+        .bind(&ids)
+        .bind(&key)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let count = ids.len() as u64;
+        GLOBAL_METRICS.increment("messages_archived", count as i64, None);
+        Ok(Some((count, key)))
+    }
+
+    /// Archives batches until no eligible rows are left.
+    pub async fn archive(&self) -> Result<ArchiveReport, ArchiveError> {
+        let mut report = ArchiveReport::default();
+        while let Some((count, key)) = self.archive_batch().await? {
+            report.archived += count;
+            report.objects.push(key);
+            if (count as i64) < self.config.batch_size {
+                break;
+            }
+        }
+        if report.archived > 0 {
+            info!(
+                "Archived {} rows from {} into {} objects",
+                report.archived,
+                self.config.table,
+                report.objects.len()
+            );
+        }
+        Ok(report)
+    }
+
+    /// An archived row, read back from its object.
+    pub async fn fetch(&self, id: &str) -> Result<Option<Value>, ArchiveError> {
+        let key: Option<String> = sqlx::query_scalar(&format!(
+            "SELECT object_key FROM {} WHERE message_id = $1",
+            self.columns.index
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(key) = key else {
+            return Ok(None);
+        };
+        let Some(object) = self.store.get(&key).await? else {
+            warn!("Archive object {} for {} is missing", key, id);
+            return Ok(None);
+        };
+        let corrupt = |reason: String| ArchiveError::Corrupt {
+            key: key.clone(),
+            reason,
+        };
+        let data = if key.ends_with(".gz") {
+            gzip::decompress(&object).map_err(corrupt)?
+        } else {
+            object
+        };
+        for line in data.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            let record: Value = serde_json::from_slice(line).map_err(|e| corrupt(e.to_string()))?;
+            let matches = match &record[&self.config.id_column] {
+                Value::String(s) => s == id,
+                Value::Null => false,
+                other => serde_json::from_str::<Value>(id).is_ok_and(|v| &v == other),
+            };
+            if matches {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The row as JSON from the live table, falling back to the archive,
+    /// so readers need not care whether a message was archived yet.
+    pub async fn find(&self, id: &str) -> Result<Option<Value>, ArchiveError> {
+        let live: Option<String> = sqlx::query_scalar(&format!(
+            "SELECT row_to_json(t)::text FROM {} AS t WHERE {} = $1::text::{}",
+            self.columns.table, self.columns.id, self.columns.id_type
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        match live {
+            Some(record) => {
+                serde_json::from_str(&record)
+                    .map(Some)
+                    .map_err(|e| ArchiveError::Corrupt {
+                        key: self.config.table.clone(),
+                        reason: e.to_string(),
+                    })
+            }
+            None => self.fetch(id).await,
+        }
+    }
+
+    /// Runs `archive` on `interval` until the task is aborted.
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.archive().await {
+                    warn!("Archival of {} failed: {}", self.config.table, e);
+                }
+            }
+        })
+    }
+}
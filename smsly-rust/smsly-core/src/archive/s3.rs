@@ -0,0 +1,191 @@
+use super::{ArchiveError, ObjectStore};
+use crate::providers::http_client;
+use crate::providers::sns::{host_header, sign_v4_with_headers, SigningScope};
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{Client, Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use std::env;
+
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub region: String,
+    pub bucket: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    /// Defaults to `https://s3.<region>.amazonaws.com`; point it at MinIO,
+    /// R2 or another S3-compatible store.
+    pub endpoint: String,
+    /// Address objects as `<endpoint>/<bucket>/<key>` rather than on a
+    /// `<bucket>.` subdomain; most S3-compatible stores need this.
+    pub path_style: bool,
+}
+
+impl S3Config {
+    pub fn new(region: &str, bucket: &str, access_key_id: &str, secret_access_key: &str) -> Self {
+        Self {
+            region: region.to_string(),
+            bucket: bucket.to_string(),
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+            session_token: None,
+            endpoint: format!("https://s3.{}.amazonaws.com", region),
+            path_style: false,
+        }
+    }
+
+    /// Reads the standard AWS credential variables, plus `AWS_S3_ENDPOINT`
+    /// for S3-compatible stores (which switches to path-style addressing).
+    pub fn from_env(bucket: &str) -> Option<Self> {
+        let non_empty = |key: &str| env::var(key).ok().filter(|v| !v.is_empty());
+        let region = non_empty("AWS_REGION").or_else(|| non_empty("AWS_DEFAULT_REGION"))?;
+        let mut config = Self::new(
+            &region,
+            bucket,
+            &non_empty("AWS_ACCESS_KEY_ID")?,
+            &non_empty("AWS_SECRET_ACCESS_KEY")?,
+        );
+        config.session_token = non_empty("AWS_SESSION_TOKEN");
+        if let Some(endpoint) = non_empty("AWS_S3_ENDPOINT") {
+            config = config.with_endpoint(&endpoint);
+        }
+        Some(config)
+    }
+
+    /// Also switches to path-style addressing.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.trim_end_matches('/').to_string();
+        self.path_style = true;
+        self
+    }
+
+    pub fn with_path_style(mut self, path_style: bool) -> Self {
+        self.path_style = path_style;
+        self
+    }
+}
+
+/// `ObjectStore` on S3 or an S3-compatible store, signed with SigV4.
+/// Keys should stick to letters, digits and `/-_.`, as the archiver's do.
+pub struct S3Store {
+    config: S3Config,
+    client: Client,
+}
+
+impl S3Store {
+    pub fn new(config: S3Config) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+
+    fn object_url(&self, key: &str) -> Result<Url, ArchiveError> {
+        let url = if self.config.path_style {
+            format!("{}/{}/{}", self.config.endpoint, self.config.bucket, key)
+        } else {
+            let endpoint = Url::parse(&self.config.endpoint)
+                .map_err(|e| ArchiveError::Storage(e.to_string()))?;
+            format!(
+                "{}://{}.{}/{}",
+                endpoint.scheme(),
+                self.config.bucket,
+                host_header(&endpoint).map_err(ArchiveError::Storage)?,
+                key
+            )
+        };
+        Url::parse(&url).map_err(|e| ArchiveError::Storage(e.to_string()))
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        key: &str,
+        content_type: Option<&str>,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response, ArchiveError> {
+        let url = self.object_url(key)?;
+        let host = host_header(&url).map_err(ArchiveError::Storage)?;
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+        let scope = SigningScope {
+            region: &self.config.region,
+            service: "s3",
+            access_key_id: &self.config.access_key_id,
+            secret_access_key: &self.config.secret_access_key,
+            session_token: self.config.session_token.as_deref(),
+        };
+        let mut signed = vec![("x-amz-content-sha256", payload_hash.as_str())];
+        if let Some(content_type) = content_type {
+            signed.push(("content-type", content_type));
+        }
+        let authorization = sign_v4_with_headers(
+            &scope,
+            method.as_str(),
+            &host,
+            url.path(),
+            &amz_date,
+            &signed,
+            &payload_hash,
+        );
+
+        let mut http = self
+            .client
+            .request(method, url)
+            .header("X-Amz-Content-Sha256", &payload_hash)
+            .header("X-Amz-Date", &amz_date)
+            .header("Authorization", authorization);
+        if let Some(content_type) = content_type {
+            http = http.header("Content-Type", content_type);
+        }
+        if let Some(token) = &self.config.session_token {
+            http = http.header("X-Amz-Security-Token", token);
+        }
+        http.body(body)
+            .send()
+            .await
+            .map_err(|e| ArchiveError::Storage(e.to_string()))
+    }
+}
+
+async fn storage_error(action: &str, key: &str, response: reqwest::Response) -> ArchiveError {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    let code = body
+        .split_once("<Code>")
+        .and_then(|(_, rest)| rest.split_once("</Code>"))
+        .map(|(code, _)| code)
+        .unwrap_or("UnknownError");
+    ArchiveError::Storage(format!(
+        "S3 {} {} failed ({}): {}",
+        action, key, status, code
+    ))
+}
+
+#[async_trait]
+impl ObjectStore for S3Store {
+    async fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> Result<(), ArchiveError> {
+        let response = self
+            .request(Method::PUT, key, Some(content_type), body)
+            .await?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(storage_error("PUT", key, response).await)
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, ArchiveError> {
+        let response = self.request(Method::GET, key, None, Vec::new()).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => response
+                .bytes()
+                .await
+                .map(|bytes| Some(bytes.to_vec()))
+                .map_err(|e| ArchiveError::Storage(e.to_string())),
+            _ => Err(storage_error("GET", key, response).await),
+        }
+    }
+}
@@ -0,0 +1,486 @@
+//! Numbers that must not be messaged.
+//!
+//! Entries are either global (regulator do-not-call lists, numbers reported
+//! for abuse) or scoped to one account (its opt-outs and suppressions).
+//! Postgres is the source of truth; Redis holds one set per scope so the
+//! send path can check a whole batch in a round trip with `check_many`.
+//! Numbers are stored as digits only.
+
+use crate::opt_out::{OptOutError, OptOutStore};
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+use sqlx::Row;
+use std::collections::HashSet;
+use thiserror::Error;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+/// Default blocklist table; include this in a service migration.
+pub const BLOCKLIST_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS blocklist (
+    scope TEXT NOT NULL,
+    msisdn TEXT NOT NULL,
+    reason TEXT,
+    source TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (scope, msisdn)
+);
+CREATE INDEX IF NOT EXISTS blocklist_msisdn_idx ON blocklist (msisdn);
+"#;
+
+/// Scope value of global entries.
+pub const GLOBAL_SCOPE: &str = "*";
+const IMPORT_BATCH: usize = 5000;
+
+#[derive(Error, Debug)]
+pub enum BlocklistError {
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    #[error("Import failed: {0}")]
+    Import(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlocklistScope {
+    Global,
+    Account(String),
+}
+
+impl BlocklistScope {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Global => GLOBAL_SCOPE,
+            Self::Account(account_id) => account_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockEntry {
+    pub scope: BlocklistScope,
+    pub msisdn: String,
+    /// E.g. `opt_out`, `dnc`, `abuse`.
+    pub reason: Option<String>,
+    /// Where the entry came from, e.g. a keyword or an import file.
+    pub source: Option<String>,
+}
+
+impl BlockEntry {
+    pub fn new(scope: BlocklistScope, msisdn: &str) -> Self {
+        Self {
+            scope,
+            msisdn: normalize(msisdn),
+            reason: None,
+            source: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: &str) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+}
+
+/// Which CSV column holds the number. Rows whose value has fewer than
+/// `min_digits` digits (headers, blanks, notes) are skipped.
+#[derive(Debug, Clone)]
+pub struct CsvImport {
+    pub column: usize,
+    pub delimiter: char,
+    pub min_digits: usize,
+}
+
+impl Default for CsvImport {
+    fn default() -> Self {
+        Self {
+            column: 0,
+            delimiter: ',',
+            min_digits: 6,
+        }
+    }
+}
+
+impl CsvImport {
+    pub fn with_column(mut self, column: usize) -> Self {
+        self.column = column;
+        self
+    }
+
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    /// Numbers newly blocked.
+    pub added: u64,
+    /// Numbers already on the list.
+    pub existing: u64,
+    /// Rows without a usable number.
+    pub skipped: u64,
+}
+
+fn normalize(msisdn: &str) -> String {
+    msisdn.chars().filter(char::is_ascii_digit).collect()
+}
+
+pub struct Blocklist {
+    pool: PgPool,
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    table: String,
+    key_prefix: String,
+}
+
+impl Blocklist {
+    pub fn new(pool: PgPool, client: Client) -> Self {
+        Self {
+            pool,
+            client,
+            conn: OnceCell::new(),
+            table: "blocklist".to_string(),
+            key_prefix: "smsly:blocklist".to_string(),
+        }
+    }
+
+    pub fn with_table(mut self, table: &str) -> Self {
+        self.table = table.to_string();
+        self
+    }
+
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    fn key(&self, scope: &str) -> String {
+        format!("{}:{}", self.key_prefix, scope)
+    }
+
+    pub async fn add(&self, entry: &BlockEntry) -> Result<bool, BlocklistError> {
+        let inserted = sqlx::query(&format!(
+            "INSERT INTO {} (scope, msisdn, reason, source) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT DO NOTHING",
+            self.table
+        ))
+        .bind(entry.scope.as_str())
+        .bind(&entry.msisdn)
+        .bind(&entry.reason)
+        .bind(&entry.source)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        let mut conn = self.conn().await?;
+        redis::cmd("SADD")
+            .arg(self.key(entry.scope.as_str()))
+            .arg(&entry.msisdn)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(inserted > 0)
+    }
+
+    pub async fn remove(
+        &self,
+        scope: &BlocklistScope,
+        msisdn: &str,
+    ) -> Result<bool, BlocklistError> {
+        let msisdn = normalize(msisdn);
+        let removed = sqlx::query(&format!(
+            "DELETE FROM {} WHERE scope = $1 AND msisdn = $2",
+            self.table
+        ))
+        .bind(scope.as_str())
+        .bind(&msisdn)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        let mut conn = self.conn().await?;
+        redis::cmd("SREM")
+            .arg(self.key(scope.as_str()))
+            .arg(&msisdn)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Blocks `msisdns` in `scope` in batches; numbers already blocked are
+    /// left as they were.
+    pub async fn import(
+        &self,
+        scope: &BlocklistScope,
+        msisdns: &[String],
+        reason: Option<&str>,
+        source: Option<&str>,
+    ) -> Result<ImportReport, BlocklistError> {
+        let mut report = ImportReport::default();
+        let mut batch = Vec::with_capacity(IMPORT_BATCH);
+        for msisdn in msisdns {
+            batch.push(normalize(msisdn));
+            if batch.len() == IMPORT_BATCH {
+                self.import_batch(scope, &mut batch, reason, source, &mut report)
+                    .await?;
+            }
+        }
+        self.import_batch(scope, &mut batch, reason, source, &mut report)
+            .await?;
+        Ok(report)
+    }
+
+    async fn import_batch(
+        &self,
+        scope: &BlocklistScope,
+        batch: &mut Vec<String>,
+        reason: Option<&str>,
+        source: Option<&str>,
+        report: &mut ImportReport,
+    ) -> Result<(), BlocklistError> {
+        batch.sort();
+        batch.dedup();
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let added = sqlx::query(&format!(
+            "INSERT INTO {} (scope, msisdn, reason, source) \
+             SELECT $1, msisdn, $3, $4 FROM UNNEST($2::text[]) AS msisdn \
+             ON CONFLICT DO NOTHING",
+            self.table
+        ))
+        .bind(scope.as_str())
+        .bind(&*batch)
+        .bind(reason)
+        .bind(source)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        let mut conn = self.conn().await?;
+        redis::cmd("SADD")
+            .arg(self.key(scope.as_str()))
+            .arg(&*batch)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        report.added += added;
+        report.existing += batch.len() as u64 - added;
+        batch.clear();
+        Ok(())
+    }
+
+    /// Imports a CSV export such as a regulator's do-not-call list,
+    /// recording `source` (e.g. the file name) on every entry.
+    pub async fn import_csv<R: AsyncBufRead + Unpin>(
+        &self,
+        scope: &BlocklistScope,
+        reader: R,
+        options: &CsvImport,
+        reason: Option<&str>,
+        source: Option<&str>,
+    ) -> Result<ImportReport, BlocklistError> {
+        let mut lines = reader.lines();
+        let mut numbers = Vec::with_capacity(IMPORT_BATCH);
+        let mut report = ImportReport::default();
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| BlocklistError::Import(e.to_string()))?
+        {
+            let number = line
+                .split(options.delimiter)
+                .nth(options.column)
+                .map(|field| normalize(field.trim().trim_matches('"')))
+                .unwrap_or_default();
+            if number.len() < options.min_digits {
+                report.skipped += 1;
+                continue;
+            }
+            numbers.push(number);
+            if numbers.len() == IMPORT_BATCH {
+                self.import_batch(scope, &mut numbers, reason, source, &mut report)
+                    .await?;
+            }
+        }
+        self.import_batch(scope, &mut numbers, reason, source, &mut report)
+            .await?;
+        info!(
+            "Blocklist import into {}: {} added, {} existing, {} skipped",
+            scope.as_str(),
+            report.added,
+            report.existing,
+            report.skipped
+        );
+        Ok(report)
+    }
+
+    /// Whether `msisdn` is blocked globally or for `account_id`.
+    pub async fn is_blocked(&self, account_id: &str, msisdn: &str) -> Result<bool, BlocklistError> {
+        Ok(!self
+            .check_many(account_id, &[msisdn.to_string()])
+            .await?
+            .is_empty())
+    }
+
+    /// The numbers among `msisdns` blocked globally or for `account_id`,
+    /// normalised to digits. Answered from Redis (6.2+ for `SMISMEMBER`),
+    /// or from Postgres if Redis is unreachable.
+    pub async fn check_many(
+        &self,
+        account_id: &str,
+        msisdns: &[String],
+    ) -> Result<HashSet<String>, BlocklistError> {
+        let numbers: Vec<String> = msisdns.iter().map(|m| normalize(m)).collect();
+        if numbers.is_empty() {
+            return Ok(HashSet::new());
+        }
+        match self.check_cached(account_id, &numbers).await {
+            Ok(blocked) => Ok(blocked),
+            Err(e) => {
+                warn!("Blocklist cache unavailable, checking Postgres: {}", e);
+                self.check_database(account_id, &numbers).await
+            }
+        }
+    }
+
+    async fn check_cached(
+        &self,
+        account_id: &str,
+        numbers: &[String],
+    ) -> RedisResult<HashSet<String>> {
+        let mut conn = self.conn().await?;
+        let (global, account): (Vec<bool>, Vec<bool>) = redis::pipe()
+            .cmd("SMISMEMBER")
+            .arg(self.key(GLOBAL_SCOPE))
+            .arg(numbers)
+            .cmd("SMISMEMBER")
+            .arg(self.key(account_id))
+            .arg(numbers)
+            .query_async(&mut conn)
+            .await?;
+        Ok(numbers
+            .iter()
+            .zip(global.into_iter().zip(account))
+            .filter(|(_, (global, account))| *global || *account)
+            .map(|(number, _)| number.clone())
+            .collect())
+    }
+
+    async fn check_database(
+        &self,
+        account_id: &str,
+        numbers: &[String],
+    ) -> Result<HashSet<String>, BlocklistError> {
+        let rows = sqlx::query(&format!(
+            "SELECT DISTINCT msisdn FROM {} WHERE scope IN ($1, $2) AND msisdn = ANY($3)",
+            self.table
+        ))
+        .bind(GLOBAL_SCOPE)
+        .bind(account_id)
+        .bind(numbers)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| row.try_get("msisdn").map_err(BlocklistError::from))
+            .collect()
+    }
+
+    /// Rebuilds the Redis set for `scope` from Postgres, e.g. after a
+    /// Redis flush. The old set is replaced in one step once loaded.
+    pub async fn rebuild_cache(&self, scope: &BlocklistScope) -> Result<u64, BlocklistError> {
+        let key = self.key(scope.as_str());
+        let staging = format!("{}:rebuild", key);
+        let mut conn = self.conn().await?;
+        redis::cmd("DEL")
+            .arg(&staging)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+
+        let mut loaded = 0u64;
+        let mut after = String::new();
+        loop {
+            let rows = sqlx::query(&format!(
+                "SELECT msisdn FROM {} WHERE scope = $1 AND msisdn > $2 ORDER BY msisdn LIMIT $3",
+                self.table
+            ))
+            .bind(scope.as_str())
+            .bind(&after)
+            .bind(IMPORT_BATCH as i64)
+            .fetch_all(&self.pool)
+            .await?;
+            let numbers: Vec<String> = rows
+                .iter()
+                .map(|row| row.try_get("msisdn"))
+                .collect::<Result<_, _>>()?;
+            let Some(last) = numbers.last() else {
+                break;
+            };
+            after = last.clone();
+            redis::cmd("SADD")
+                .arg(&staging)
+                .arg(&numbers)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+            loaded += numbers.len() as u64;
+        }
+
+        if loaded == 0 {
+            redis::cmd("DEL")
+                .arg(&key)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+        } else {
+            redis::cmd("RENAME")
+                .arg(&staging)
+                .arg(&key)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+        }
+        Ok(loaded)
+    }
+}
+
+/// Opt-outs recorded as account-scoped entries with reason `opt_out`, so
+/// `KeywordEngine` keeps the blocklist current. Opting in only lifts the
+/// account entry; global blocks stay.
+#[async_trait]
+impl OptOutStore for Blocklist {
+    async fn opt_out(&self, account_id: &str, msisdn: &str) -> Result<(), OptOutError> {
+        let entry = BlockEntry::new(BlocklistScope::Account(account_id.to_string()), msisdn)
+            .with_reason("opt_out")
+            .with_source("keyword");
+        self.add(&entry)
+            .await
+            .map(|_| ())
+            .map_err(|e| OptOutError::Store(e.to_string()))
+    }
+
+    async fn opt_in(&self, account_id: &str, msisdn: &str) -> Result<(), OptOutError> {
+        self.remove(&BlocklistScope::Account(account_id.to_string()), msisdn)
+            .await
+            .map(|_| ())
+            .map_err(|e| OptOutError::Store(e.to_string()))
+    }
+
+    async fn is_opted_out(&self, account_id: &str, msisdn: &str) -> Result<bool, OptOutError> {
+        self.is_blocked(account_id, msisdn)
+            .await
+            .map_err(|e| OptOutError::Store(e.to_string()))
+    }
+}
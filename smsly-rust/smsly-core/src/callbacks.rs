@@ -0,0 +1,800 @@
+//! Delivery status callbacks to customers.
+//!
+//! Status updates from providers (`WebhookEvent`s on `DELIVERY_REPORTS`)
+//! are turned into signed POSTs to each customer's callback URL. Pending
+//! callbacks live in Redis, so retries survive restarts and are shared by
+//! all replicas: each is retried with exponential backoff until it is
+//! accepted or runs out of attempts, when it moves to the account's
+//! dead-letter list for inspection and redelivery through
+//! `create_callback_router`.
+//!
+//! Endpoints failing `failure_threshold` times in a row are skipped for
+//! `open_for` (a `SharedCircuits` per endpoint), so one customer's dead
+//! server doesn't eat every attempt; their callbacks wait without using up
+//! attempts.
+//!
+//! Callback URLs must be public http(s) URLs (see `providers::url_guard`):
+//! others are dead-lettered when queued, and requests neither resolve to
+//! private addresses nor follow redirects.
+//!
+//! ```no_run
+//! # use smsly_core::callbacks::{CallbackConfig, CallbackDispatcher, CallbackResolver};
+//! # use smsly_core::messaging::{ConsumerConfig, MessageQueue, TopicConsumer, DELIVERY_REPORTS};
+//! # use smsly_core::shutdown::ShutdownSignal;
+//! # use std::sync::Arc;
+//! # async fn start(
+//! #     redis: redis::Client,
+//! #     resolver: Arc<dyn CallbackResolver>,
+//! #     queue: Arc<dyn MessageQueue>,
+//! #     pod: String,
+//! #     shutdown: ShutdownSignal,
+//! # ) {
+//! let dispatcher = Arc::new(CallbackDispatcher::new(redis, resolver, CallbackConfig::default()));
+//! let consumer = TopicConsumer::new(
+//!     queue,
+//!     DELIVERY_REPORTS,
+//!     "callbacks",
+//!     &pod,
+//!     ConsumerConfig::default(),
+//! );
+//! tokio::spawn(dispatcher.clone().run(shutdown.clone()));
+//! consumer
+//!     .run(
+//!         |event, _| {
+//!             let d = dispatcher.clone();
+//!             async move { d.handle_event(&event).await.map(|_| ()) }
+//!         },
+//!         shutdown,
+//!     )
+//!     .await;
+//! # }
+//! ```
+
+use crate::adapters::{MessageStatus, WebhookEvent};
+use crate::circuit_breaker::{SharedCircuitConfig, SharedCircuits};
+use crate::dlr::DlrReason;
+use crate::idempotency::AccountId;
+use crate::metrics::GLOBAL_METRICS;
+use crate::providers::signature::{
+    hmac_sha256, now_secs, verify_hmac_sha256, within_tolerance, SignatureEncoding,
+};
+use crate::providers::url_guard::{check_url, guarded_client};
+use crate::shutdown::ShutdownSignal;
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use chrono::Utc;
+use futures_util::future::join_all;
+use lazy_static::lazy_static;
+use rand::Rng;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult, Script};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// `t=<unix seconds>,v1=<hex HMAC-SHA256 of "<t>.<body>">`.
+pub const SIGNATURE_HEADER: &str = "Smsly-Signature";
+/// The callback's ID, the same on every attempt, for deduplication.
+pub const EVENT_ID_HEADER: &str = "Smsly-Event-Id";
+
+lazy_static! {
+    /// Returns up to ARGV[2] members of KEYS[1] scored at most ARGV[1],
+    /// rescoring them to ARGV[3] so other replicas skip them meanwhile.
+    static ref CLAIM_DUE: Script = Script::new(
+        r#"
+        local ids = redis.call("ZRANGEBYSCORE", KEYS[1], "-inf", ARGV[1], "LIMIT", 0, ARGV[2])
+        for _, id in ipairs(ids) do
+            redis.call("ZADD", KEYS[1], ARGV[3], id)
+        end
+        return ids
+    "#
+    );
+}
+
+#[derive(Error, Debug)]
+pub enum CallbackError {
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    #[error("Callback resolver failed: {0}")]
+    Resolver(String),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Where a status update should be reported.
+#[derive(Debug, Clone)]
+pub struct CallbackTarget {
+    pub account_id: String,
+    pub message_id: String,
+    pub url: String,
+}
+
+/// Looks up the message a provider update refers to and its owner's
+/// callback settings, usually from the messages and accounts tables.
+#[async_trait]
+pub trait CallbackResolver: Send + Sync {
+    /// `None` for unknown messages or accounts without a callback URL.
+    async fn resolve(&self, event: &WebhookEvent) -> Result<Option<CallbackTarget>, String>;
+    /// Looked up on every attempt, so rotated secrets apply to pending
+    /// callbacks too.
+    async fn signing_secret(&self, account_id: &str) -> Result<Option<String>, String>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusCallback {
+    pub id: String,
+    pub account_id: String,
+    pub message_id: String,
+    pub url: String,
+    pub status: MessageStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<DlrReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<f64>,
+    pub created_at: f64,
+    /// Failed deliveries so far.
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl StatusCallback {
+    pub fn new(target: CallbackTarget, event: &WebhookEvent) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            account_id: target.account_id,
+            message_id: target.message_id,
+            url: target.url,
+            status: event.status.clone(),
+            error_code: event.error_code.clone(),
+            error_message: event.error_message.clone(),
+            reason: event.reason,
+            timestamp: event.timestamp,
+            created_at: now_millis() as f64 / 1000.0,
+            attempts: 0,
+            last_error: None,
+        }
+    }
+
+    /// The body POSTed to the customer.
+    pub fn payload(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "message_id": self.message_id,
+            "status": self.status,
+            "error_code": self.error_code,
+            "error_message": self.error_message,
+            "reason": self.reason,
+            "timestamp": self.timestamp,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CallbackConfig {
+    pub key_prefix: String,
+    /// Deliveries tried before a callback is dead-lettered.
+    pub max_attempts: u32,
+    /// Delay after the first failure, doubling with each further one.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub request_timeout: Duration,
+    pub batch_size: usize,
+    pub poll_interval: Duration,
+    /// How long a claimed callback is hidden from other replicas.
+    pub lease: Duration,
+    /// Consecutive failures that open an endpoint's circuit.
+    pub failure_threshold: u32,
+    /// How long an open circuit skips its endpoint.
+    pub open_for: Duration,
+}
+
+impl Default for CallbackConfig {
+    fn default() -> Self {
+        Self {
+            key_prefix: "smsly:callbacks".to_string(),
+            max_attempts: 10,
+            base_delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(3600),
+            request_timeout: Duration::from_secs(10),
+            batch_size: 50,
+            poll_interval: Duration::from_secs(1),
+            lease: Duration::from_secs(60),
+            failure_threshold: 5,
+            open_for: Duration::from_secs(60),
+        }
+    }
+}
+
+impl CallbackConfig {
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    pub fn with_backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_delay = base;
+        self.max_delay = max;
+        self
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size;
+        self
+    }
+
+    pub fn with_circuit(mut self, failure_threshold: u32, open_for: Duration) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self.open_for = open_for;
+        self
+    }
+
+    /// Delay before retrying after `attempts` failures, jittered between
+    /// half and all of the exponential step.
+    fn retry_delay(&self, attempts: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(self.max_delay);
+        let millis = exp.as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(millis / 2..=millis.max(1)))
+    }
+}
+
+/// The `Smsly-Signature` value for `body` sent at `timestamp`.
+pub fn sign_callback(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut signed = format!("{}.", timestamp).into_bytes();
+    signed.extend_from_slice(body);
+    format!(
+        "t={},v1={}",
+        timestamp,
+        hex::encode(hmac_sha256(secret.as_bytes(), &signed))
+    )
+}
+
+/// Checks a `Smsly-Signature` header, as customers' receivers should,
+/// rejecting signatures older than `tolerance`.
+pub fn verify_callback(secret: &str, header: &str, body: &[u8], tolerance: Duration) -> bool {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", t)) => timestamp = t.parse::<i64>().ok(),
+            Some(("v1", sig)) => signatures.push(sig),
+            _ => {}
+        }
+    }
+    let Some(timestamp) = timestamp.filter(|t| within_tolerance(*t, tolerance)) else {
+        return false;
+    };
+    let mut signed = format!("{}.", timestamp).into_bytes();
+    signed.extend_from_slice(body);
+    signatures
+        .iter()
+        .any(|sig| verify_hmac_sha256(secret.as_bytes(), &signed, sig, SignatureEncoding::Hex))
+}
+
+fn now_millis() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+enum Outcome {
+    Delivered,
+    Failed(String),
+    /// Not attempted: the endpoint's circuit is open for this long.
+    CircuitOpen(Duration),
+    /// Not retryable, e.g. the account has no signing secret.
+    Rejected(String),
+}
+
+pub struct CallbackDispatcher {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    resolver: Arc<dyn CallbackResolver>,
+    http: reqwest::Client,
+    circuits: SharedCircuits,
+    config: CallbackConfig,
+}
+
+impl CallbackDispatcher {
+    pub fn new(
+        client: Client,
+        resolver: Arc<dyn CallbackResolver>,
+        config: CallbackConfig,
+    ) -> Self {
+        let circuits = SharedCircuits::new(
+            client.clone(),
+            SharedCircuitConfig::default()
+                .with_key_prefix(&format!("{}:circuit", config.key_prefix))
+                .with_failure_threshold(config.failure_threshold)
+                .with_open_for(config.open_for)
+                .with_failures_ttl(config.max_delay.max(config.open_for) * 2),
+        );
+        Self {
+            client,
+            conn: OnceCell::new(),
+            resolver,
+            http: guarded_client(config.request_timeout),
+            circuits,
+            config,
+        }
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    fn key(&self, suffix: &str) -> String {
+        format!("{}:{}", self.config.key_prefix, suffix)
+    }
+
+    fn dead_key(&self, account_id: &str) -> String {
+        self.key(&format!("dead:{}", account_id))
+    }
+
+    fn dead_index_key(&self, account_id: &str) -> String {
+        self.key(&format!("dead:{}:index", account_id))
+    }
+
+    /// Queues a callback for `event` if its message has an owner with a
+    /// callback URL; returns the callback ID. Callbacks to URLs failing
+    /// `check_url` are dead-lettered straight away.
+    pub async fn handle_event(
+        &self,
+        event: &WebhookEvent,
+    ) -> Result<Option<String>, CallbackError> {
+        let Some(target) = self
+            .resolver
+            .resolve(event)
+            .await
+            .map_err(CallbackError::Resolver)?
+        else {
+            return Ok(None);
+        };
+        let mut callback = StatusCallback::new(target, event);
+        if let Err(e) = check_url(&callback.url) {
+            callback.last_error = Some(e.to_string());
+            self.dead_letter(&callback).await?;
+            return Ok(Some(callback.id));
+        }
+        self.enqueue(&callback, Duration::ZERO).await?;
+        Ok(Some(callback.id))
+    }
+
+    async fn enqueue(
+        &self,
+        callback: &StatusCallback,
+        delay: Duration,
+    ) -> Result<(), CallbackError> {
+        let mut conn = self.conn().await?;
+        redis::pipe()
+            .atomic()
+            .cmd("HSET")
+            .arg(self.key("pending"))
+            .arg(&callback.id)
+            .arg(serde_json::to_string(callback)?)
+            .ignore()
+            .cmd("ZADD")
+            .arg(self.key("due"))
+            .arg(now_millis() + delay.as_millis() as i64)
+            .arg(&callback.id)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Callbacks waiting for delivery or a retry.
+    pub async fn pending_count(&self) -> Result<u64, CallbackError> {
+        let mut conn = self.conn().await?;
+        Ok(redis::cmd("ZCARD")
+            .arg(self.key("due"))
+            .query_async(&mut conn)
+            .await?)
+    }
+
+    /// Attempts every callback that is due, returning how many were
+    /// delivered.
+    pub async fn deliver_due(&self) -> Result<usize, CallbackError> {
+        let mut conn = self.conn().await?;
+        let now = now_millis();
+        let ids: Vec<String> = CLAIM_DUE
+            .key(self.key("due"))
+            .arg(now)
+            .arg(self.config.batch_size)
+            .arg(now + self.config.lease.as_millis() as i64)
+            .invoke_async(&mut conn)
+            .await?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let raw: Vec<Option<String>> = redis::cmd("HMGET")
+            .arg(self.key("pending"))
+            .arg(&ids)
+            .query_async(&mut conn)
+            .await?;
+
+        let mut callbacks = Vec::with_capacity(ids.len());
+        for (id, raw) in ids.iter().zip(raw) {
+            match raw.map(|raw| serde_json::from_str::<StatusCallback>(&raw)) {
+                Some(Ok(callback)) => callbacks.push(callback),
+                Some(Err(e)) => {
+                    error!("Dropping undecodable callback {}: {}", id, e);
+                    self.forget(id).await?;
+                }
+                None => self.forget(id).await?,
+            }
+        }
+
+        let outcomes = join_all(callbacks.iter().map(|callback| self.attempt(callback))).await;
+        let mut delivered = 0;
+        for (mut callback, outcome) in callbacks.into_iter().zip(outcomes) {
+            match outcome {
+                Outcome::Delivered => {
+                    delivered += 1;
+                    self.forget(&callback.id).await?;
+                    GLOBAL_METRICS.increment("callbacks_delivered", 1, None);
+                }
+                Outcome::CircuitOpen(wait) => self.reschedule(&callback, wait).await?,
+                Outcome::Failed(reason) => {
+                    callback.attempts += 1;
+                    callback.last_error = Some(reason);
+                    GLOBAL_METRICS.increment("callbacks_failed", 1, None);
+                    if callback.attempts >= self.config.max_attempts {
+                        self.dead_letter(&callback).await?;
+                    } else {
+                        let delay = self.config.retry_delay(callback.attempts);
+                        self.enqueue(&callback, delay).await?;
+                    }
+                }
+                Outcome::Rejected(reason) => {
+                    callback.last_error = Some(reason);
+                    self.dead_letter(&callback).await?;
+                }
+            }
+        }
+        Ok(delivered)
+    }
+
+    async fn attempt(&self, callback: &StatusCallback) -> Outcome {
+        // Also covers callbacks queued before the URL checks.
+        if let Err(e) = check_url(&callback.url) {
+            return Outcome::Rejected(e.to_string());
+        }
+        match self.circuits.open_for(&callback.url).await {
+            Ok(Some(wait)) => return Outcome::CircuitOpen(wait),
+            Ok(None) => {}
+            Err(e) => warn!("Circuit check for callback {} failed: {}", callback.id, e),
+        }
+        let secret = match self.resolver.signing_secret(&callback.account_id).await {
+            Ok(Some(secret)) => secret,
+            Ok(None) => {
+                return Outcome::Rejected("Account has no callback signing secret".to_string())
+            }
+            Err(e) => return Outcome::Failed(format!("Signing secret lookup failed: {}", e)),
+        };
+
+        let body = callback.payload().to_string();
+        let result = self
+            .http
+            .post(&callback.url)
+            .header("Content-Type", "application/json")
+            .header(EVENT_ID_HEADER, &callback.id)
+            .header(
+                SIGNATURE_HEADER,
+                sign_callback(&secret, now_secs(), body.as_bytes()),
+            )
+            .body(body)
+            .send()
+            .await;
+        let outcome = match result {
+            Ok(response) if response.status().is_success() => Outcome::Delivered,
+            Ok(response) => Outcome::Failed(format!("HTTP {}", response.status().as_u16())),
+            Err(e) => Outcome::Failed(e.to_string()),
+        };
+        let recorded = match &outcome {
+            Outcome::Delivered => self.circuits.record_success(&callback.url).await,
+            _ => match self.circuits.record_failure(&callback.url).await {
+                Ok(true) => {
+                    warn!("Callback circuit opened for {}", callback.url);
+                    GLOBAL_METRICS.increment("callback_circuits_opened", 1, None);
+                    Ok(())
+                }
+                Ok(false) => Ok(()),
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = recorded {
+            warn!(
+                "Failed to update circuit for callback {}: {}",
+                callback.id, e
+            );
+        }
+        outcome
+    }
+
+    async fn reschedule(
+        &self,
+        callback: &StatusCallback,
+        delay: Duration,
+    ) -> Result<(), CallbackError> {
+        let mut conn = self.conn().await?;
+        redis::cmd("ZADD")
+            .arg(self.key("due"))
+            .arg(now_millis() + delay.as_millis() as i64)
+            .arg(&callback.id)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn forget(&self, id: &str) -> Result<(), CallbackError> {
+        let mut conn = self.conn().await?;
+        redis::pipe()
+            .atomic()
+            .cmd("ZREM")
+            .arg(self.key("due"))
+            .arg(id)
+            .ignore()
+            .cmd("HDEL")
+            .arg(self.key("pending"))
+            .arg(id)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn dead_letter(&self, callback: &StatusCallback) -> Result<(), CallbackError> {
+        let mut conn = self.conn().await?;
+        redis::pipe()
+            .atomic()
+            .cmd("ZREM")
+            .arg(self.key("due"))
+            .arg(&callback.id)
+            .ignore()
+            .cmd("HDEL")
+            .arg(self.key("pending"))
+            .arg(&callback.id)
+            .ignore()
+            .cmd("HSET")
+            .arg(self.dead_key(&callback.account_id))
+            .arg(&callback.id)
+            .arg(serde_json::to_string(callback)?)
+            .ignore()
+            .cmd("ZADD")
+            .arg(self.dead_index_key(&callback.account_id))
+            .arg(now_millis())
+            .arg(&callback.id)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        warn!(
+            "Dead-lettered callback {} for {} after {} attempts: {}",
+            callback.id,
+            callback.account_id,
+            callback.attempts,
+            callback.last_error.as_deref().unwrap_or_default()
+        );
+        GLOBAL_METRICS.increment("callbacks_dead_lettered", 1, None);
+        Ok(())
+    }
+
+    /// An account's dead-lettered callbacks, most recent first.
+    pub async fn dead_letters(
+        &self,
+        account_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<StatusCallback>, CallbackError> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.conn().await?;
+        let ids: Vec<String> = redis::cmd("ZREVRANGE")
+            .arg(self.dead_index_key(account_id))
+            .arg(offset)
+            .arg(offset + limit - 1)
+            .query_async(&mut conn)
+            .await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let raw: Vec<Option<String>> = redis::cmd("HMGET")
+            .arg(self.dead_key(account_id))
+            .arg(&ids)
+            .query_async(&mut conn)
+            .await?;
+        raw.into_iter()
+            .flatten()
+            .map(|raw| serde_json::from_str(&raw).map_err(CallbackError::from))
+            .collect()
+    }
+
+    pub async fn dead_letter_count(&self, account_id: &str) -> Result<u64, CallbackError> {
+        let mut conn = self.conn().await?;
+        Ok(redis::cmd("ZCARD")
+            .arg(self.dead_index_key(account_id))
+            .query_async(&mut conn)
+            .await?)
+    }
+
+    async fn take_dead_letter(
+        &self,
+        account_id: &str,
+        id: &str,
+    ) -> Result<Option<StatusCallback>, CallbackError> {
+        let mut conn = self.conn().await?;
+        let (raw, _, _): (Option<String>, u32, u32) = redis::pipe()
+            .atomic()
+            .cmd("HGET")
+            .arg(self.dead_key(account_id))
+            .arg(id)
+            .cmd("HDEL")
+            .arg(self.dead_key(account_id))
+            .arg(id)
+            .cmd("ZREM")
+            .arg(self.dead_index_key(account_id))
+            .arg(id)
+            .query_async(&mut conn)
+            .await?;
+        raw.map(|raw| serde_json::from_str(&raw))
+            .transpose()
+            .map_err(CallbackError::from)
+    }
+
+    /// Queues a dead-lettered callback again with a fresh set of attempts;
+    /// false if there is no such callback.
+    pub async fn redeliver(&self, account_id: &str, id: &str) -> Result<bool, CallbackError> {
+        let Some(mut callback) = self.take_dead_letter(account_id, id).await? else {
+            return Ok(false);
+        };
+        callback.attempts = 0;
+        callback.last_error = None;
+        self.enqueue(&callback, Duration::ZERO).await?;
+        info!("Redelivering callback {} for {}", id, account_id);
+        Ok(true)
+    }
+
+    pub async fn discard(&self, account_id: &str, id: &str) -> Result<bool, CallbackError> {
+        Ok(self.take_dead_letter(account_id, id).await?.is_some())
+    }
+
+    /// Delivers due callbacks until `shutdown`.
+    pub async fn run(self: Arc<Self>, shutdown: ShutdownSignal) {
+        while !shutdown.is_triggered() {
+            let idle = match self.deliver_due().await {
+                Ok(delivered) => delivered == 0,
+                Err(e) => {
+                    error!("Callback delivery failed: {}", e);
+                    true
+                }
+            };
+            if idle {
+                tokio::select! {
+                    _ = tokio::time::sleep(self.config.poll_interval) => {}
+                    _ = shutdown.clone().wait() => {}
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PageQuery {
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_page_limit")]
+    limit: usize,
+}
+
+fn default_page_limit() -> usize {
+    50
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+fn internal_error(e: CallbackError) -> Response {
+    error!("Callback dead-letter request failed: {}", e);
+    error_response(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Callback store unavailable",
+    )
+}
+
+async fn list_dead_letters(
+    State(dispatcher): State<Arc<CallbackDispatcher>>,
+    account: Option<Extension<AccountId>>,
+    Query(page): Query<PageQuery>,
+) -> Response {
+    let Some(Extension(AccountId(account_id))) = account else {
+        return error_response(StatusCode::UNAUTHORIZED, "Unauthenticated");
+    };
+    let limit = page.limit.min(500);
+    let listed = tokio::try_join!(
+        dispatcher.dead_letters(&account_id, page.offset, limit),
+        dispatcher.dead_letter_count(&account_id)
+    );
+    match listed {
+        Ok((items, total)) => Json(json!({ "items": items, "total": total })).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn redeliver_dead_letter(
+    State(dispatcher): State<Arc<CallbackDispatcher>>,
+    account: Option<Extension<AccountId>>,
+    Path(id): Path<String>,
+) -> Response {
+    let Some(Extension(AccountId(account_id))) = account else {
+        return error_response(StatusCode::UNAUTHORIZED, "Unauthenticated");
+    };
+    match dispatcher.redeliver(&account_id, &id).await {
+        Ok(true) => StatusCode::ACCEPTED.into_response(),
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "Callback not found"),
+        Err(e) => internal_error(e),
+    }
+}
+
+async fn discard_dead_letter(
+    State(dispatcher): State<Arc<CallbackDispatcher>>,
+    account: Option<Extension<AccountId>>,
+    Path(id): Path<String>,
+) -> Response {
+    let Some(Extension(AccountId(account_id))) = account else {
+        return error_response(StatusCode::UNAUTHORIZED, "Unauthenticated");
+    };
+    match dispatcher.discard(&account_id, &id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "Callback not found"),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// Customer-facing dead-letter endpoints, scoped to the request's
+/// `AccountId`:
+///
+/// - `GET /callbacks/dead-letters?offset=&limit=`
+/// - `POST /callbacks/dead-letters/:id/redeliver`
+/// - `DELETE /callbacks/dead-letters/:id`
+pub fn create_callback_router(dispatcher: Arc<CallbackDispatcher>) -> Router {
+    Router::new()
+        .route("/callbacks/dead-letters", get(list_dead_letters))
+        .route(
+            "/callbacks/dead-letters/:id/redeliver",
+            post(redeliver_dead_letter),
+        )
+        .route("/callbacks/dead-letters/:id", delete(discard_dead_letter))
+        .with_state(dispatcher)
+}
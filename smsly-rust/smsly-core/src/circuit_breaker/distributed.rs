@@ -0,0 +1,194 @@
+//! Circuit state shared across replicas.
+//!
+//! Without this, each replica has to see a provider fail `minimum_calls`
+//! times before it stops calling it. A `CircuitSync` pushes its breaker's
+//! outcomes into a Redis window shared by every replica using the same
+//! breaker name, and opens the shared circuit when the combined failure
+//! rate crosses the threshold or any replica opens its own. Breakers keep
+//! deciding locally between syncs, so calls never wait on Redis and keep
+//! being protected by local counts if Redis is unreachable.
+//!
+//! Each shared opening starts a new epoch, so outcomes counted before it
+//! don't reopen the circuit once it recovers.
+
+use super::{CircuitBreaker, WindowStats};
+use crate::shutdown::ShutdownSignal;
+use chrono::Utc;
+use lazy_static::lazy_static;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult, Script};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+lazy_static! {
+    /// Adds this replica's outcomes (ARGV[4], ARGV[5]) to the current
+    /// epoch's bucket, then opens the shared circuit for ARGV[9] ms if this
+    /// replica opened (ARGV[6]) or the window over the last ARGV[3] buckets
+    /// has at least ARGV[7] calls failing at rate ARGV[8]. Returns the open
+    /// key's PTTL and the window's successes and failures.
+    static ref SYNC: Script = Script::new(
+        r#"
+        local base = KEYS[1]
+        local open_key = base .. ":open"
+        local bucket_ms = tonumber(ARGV[2])
+        local buckets = tonumber(ARGV[3])
+        local current = math.floor(tonumber(ARGV[1]) / bucket_ms)
+        local epoch = redis.call("GET", base .. ":epoch") or "0"
+
+        local ds, df = tonumber(ARGV[4]), tonumber(ARGV[5])
+        if ds + df > 0 then
+            local key = base .. ":" .. epoch .. ":" .. current
+            redis.call("HINCRBY", key, "s", ds)
+            redis.call("HINCRBY", key, "f", df)
+            redis.call("PEXPIRE", key, bucket_ms * (buckets + 1))
+        end
+
+        local s, f = 0, 0
+        if redis.call("EXISTS", open_key) == 0 then
+            local trip = ARGV[6] == "1"
+            if not trip then
+                for b = current - buckets + 1, current do
+                    local v = redis.call("HMGET", base .. ":" .. epoch .. ":" .. b, "s", "f")
+                    s = s + (tonumber(v[1]) or 0)
+                    f = f + (tonumber(v[2]) or 0)
+                end
+                trip = s + f >= tonumber(ARGV[7]) and f / (s + f) >= tonumber(ARGV[8])
+            end
+            if trip then
+                redis.call("INCR", base .. ":epoch")
+                redis.call("SET", open_key, "1", "PX", ARGV[9])
+            end
+        end
+        return {redis.call("PTTL", open_key), s, f}
+    "#
+    );
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    pub key_prefix: String,
+    /// How often outcomes are pushed and the shared state read; bounds how
+    /// long other replicas keep calling after one opens.
+    pub interval: Duration,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            key_prefix: "smsly:circuit".to_string(),
+            interval: Duration::from_millis(500),
+        }
+    }
+}
+
+impl SyncConfig {
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+pub struct CircuitSync {
+    breaker: CircuitBreaker,
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    config: SyncConfig,
+    /// A local opening that a failed sync couldn't publish yet.
+    open_pending: AtomicBool,
+    cluster: Mutex<WindowStats>,
+}
+
+impl CircuitSync {
+    pub fn new(breaker: &CircuitBreaker, client: Client, config: SyncConfig) -> Self {
+        Self {
+            breaker: breaker.clone(),
+            client,
+            conn: OnceCell::new(),
+            config,
+            open_pending: AtomicBool::new(false),
+            cluster: Mutex::new(WindowStats::default()),
+        }
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    /// Outcomes across all replicas as of the last sync; zero while the
+    /// shared circuit is open.
+    pub fn cluster_stats(&self) -> WindowStats {
+        *self.cluster.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub async fn sync_once(&self) -> RedisResult<()> {
+        let (delta, opened) = self.breaker.take_unsynced();
+        let opened = opened || self.open_pending.swap(false, Ordering::Relaxed);
+        let config = self.breaker.config();
+        let bucket_ms = config.bucket_width().as_millis() as u64;
+
+        let result: RedisResult<(i64, u32, u32)> = async {
+            let mut conn = self.conn().await?;
+            SYNC.key(format!(
+                "{}:{}",
+                self.config.key_prefix,
+                self.breaker.name()
+            ))
+            .arg(Utc::now().timestamp_millis())
+            .arg(bucket_ms)
+            .arg(config.window_buckets)
+            .arg(delta.successes)
+            .arg(delta.failures)
+            .arg(if opened { "1" } else { "0" })
+            .arg(config.minimum_calls)
+            .arg(config.failure_rate)
+            .arg(config.open_duration.as_millis() as u64)
+            .invoke_async(&mut conn)
+            .await
+        }
+        .await;
+        let (open_ttl, successes, failures) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                if opened {
+                    self.open_pending.store(true, Ordering::Relaxed);
+                }
+                return Err(e);
+            }
+        };
+
+        *self.cluster.lock().unwrap_or_else(|e| e.into_inner()) = WindowStats {
+            successes,
+            failures,
+        };
+        if open_ttl > 0 {
+            self.breaker
+                .apply_remote_open(Duration::from_millis(open_ttl as u64));
+        }
+        Ok(())
+    }
+
+    /// Syncs every `interval` until `shutdown`.
+    pub async fn run(self: Arc<Self>, shutdown: ShutdownSignal) {
+        let mut ticker = tokio::time::interval(self.config.interval);
+        while !shutdown.is_triggered() {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = shutdown.clone().wait() => break,
+            }
+            if let Err(e) = self.sync_once().await {
+                warn!("Circuit {} sync failed: {}", self.breaker.name(), e);
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Hooks on circuit state transitions.
+//!
+//! Every transition updates `GLOBAL_METRICS`: the `circuit_transitions`
+//! counter (labelled `circuit`, `from` and `to`) and the `circuit_state`
+//! gauge (0 closed, 1 half-open, 2 open). Listeners added to a breaker are
+//! called as well, e.g. `AuditListener` to record openings in the audit
+//! trail.
+
+use super::{CircuitState, WindowStats};
+use crate::messaging::{AuditEvent, MessageQueue, AUDIT_EVENTS};
+use crate::metrics::GLOBAL_METRICS;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitEvent {
+    pub name: String,
+    pub from: CircuitState,
+    pub to: CircuitState,
+    /// Epoch seconds.
+    pub at: f64,
+    /// The window as it stood when the circuit left `from`.
+    pub stats: WindowStats,
+}
+
+/// Called after each transition, outside the breaker's lock. Runs on the
+/// caller's path, so anything slow belongs on a spawned task.
+pub trait CircuitListener: Send + Sync {
+    fn on_transition(&self, event: &CircuitEvent);
+}
+
+impl<F> CircuitListener for F
+where
+    F: Fn(&CircuitEvent) + Send + Sync,
+{
+    fn on_transition(&self, event: &CircuitEvent) {
+        self(event)
+    }
+}
+
+pub(crate) fn state_gauge(state: CircuitState) -> f64 {
+    match state {
+        CircuitState::Closed => 0.0,
+        CircuitState::HalfOpen => 1.0,
+        CircuitState::Open => 2.0,
+    }
+}
+
+pub(crate) fn set_state_gauge(name: &str, state: CircuitState) {
+    GLOBAL_METRICS.set_gauge(
+        "circuit_state",
+        state_gauge(state),
+        Some(HashMap::from([("circuit".to_string(), name.to_string())])),
+    );
+}
+
+pub(crate) fn record_metrics(event: &CircuitEvent) {
+    GLOBAL_METRICS.increment(
+        "circuit_transitions",
+        1,
+        Some(HashMap::from([
+            ("circuit".to_string(), event.name.clone()),
+            ("from".to_string(), event.from.as_str().to_string()),
+            ("to".to_string(), event.to.as_str().to_string()),
+        ])),
+    );
+    set_state_gauge(&event.name, event.to);
+}
+
+/// Publishes each transition to `AUDIT_EVENTS` as `circuit.opened`,
+/// `circuit.half_opened` or `circuit.closed`. Needs a Tokio runtime;
+/// transitions outside one are only logged.
+pub struct AuditListener {
+    queue: Arc<dyn MessageQueue>,
+}
+
+impl AuditListener {
+    pub fn new(queue: Arc<dyn MessageQueue>) -> Self {
+        Self { queue }
+    }
+}
+
+impl CircuitListener for AuditListener {
+    fn on_transition(&self, event: &CircuitEvent) {
+        let action = match event.to {
+            CircuitState::Open => "circuit.opened",
+            CircuitState::HalfOpen => "circuit.half_opened",
+            CircuitState::Closed => "circuit.closed",
+        };
+        let audit = AuditEvent {
+            id: Uuid::new_v4().to_string(),
+            occurred_at: event.at,
+            actor: "circuit_breaker".to_string(),
+            action: action.to_string(),
+            resource: format!("circuit:{}", event.name),
+            account_id: None,
+            metadata: json!({
+                "from": event.from.as_str(),
+                "to": event.to.as_str(),
+                "successes": event.stats.successes,
+                "failures": event.stats.failures,
+            }),
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!("No runtime to audit circuit {} {}", event.name, action);
+            return;
+        };
+        let queue = self.queue.clone();
+        runtime.spawn(async move {
+            if let Err(e) = AUDIT_EVENTS.publish(queue.as_ref(), &audit).await {
+                warn!(
+                    "Failed to audit {} on {}: {}",
+                    audit.action, audit.resource, e
+                );
+            }
+        });
+    }
+}
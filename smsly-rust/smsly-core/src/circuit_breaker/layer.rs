@@ -0,0 +1,224 @@
+//! Circuit breaking as tower middleware.
+//!
+//! `CircuitBreakerLayer` wraps any `Service`, such as a `reqwest::Client`
+//! or a tower-based internal client, failing calls fast with
+//! `CircuitError::Open` while the circuit is open:
+//!
+//! ```
+//! # use smsly_core::circuit_breaker::{
+//! #     CircuitBreaker, CircuitBreakerConfig, CircuitBreakerLayer, ServerErrors,
+//! # };
+//! # use tower::ServiceBuilder;
+//! # let breaker = CircuitBreaker::new("billing", CircuitBreakerConfig::default());
+//! let client = ServiceBuilder::new()
+//!     .layer(CircuitBreakerLayer::new(breaker).with_classifier(ServerErrors))
+//!     .service(reqwest::Client::new());
+//! ```
+//!
+//! Axum routes need an infallible service, so they take
+//! `circuit_breaker_middleware` instead, which answers 503 with
+//! `Retry-After` while open:
+//!
+//! ```
+//! # use axum::{routing::post, Router};
+//! # use smsly_core::circuit_breaker::{
+//! #     circuit_breaker_middleware, CircuitBreaker, CircuitBreakerConfig,
+//! # };
+//! # let breaker = CircuitBreaker::new("messages", CircuitBreakerConfig::default());
+//! # let router: Router = Router::new().route("/v1/messages", post(|| async { "queued" }));
+//! let router = router.route_layer(axum::middleware::from_fn_with_state(
+//!     breaker,
+//!     circuit_breaker_middleware,
+//! ));
+//! ```
+
+use super::{CircuitBreaker, CircuitError, CircuitOpen};
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::future::BoxFuture;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// Decides which outcomes count against the circuit.
+pub trait Classify<Res, E> {
+    fn is_failure(&self, result: &Result<Res, E>) -> bool;
+}
+
+impl<F, Res, E> Classify<Res, E> for F
+where
+    F: Fn(&Result<Res, E>) -> bool,
+{
+    fn is_failure(&self, result: &Result<Res, E>) -> bool {
+        self(result)
+    }
+}
+
+/// Counts errors as failures and any response as a success.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AnyError;
+
+impl<Res, E> Classify<Res, E> for AnyError {
+    fn is_failure(&self, result: &Result<Res, E>) -> bool {
+        result.is_err()
+    }
+}
+
+/// Responses carrying an HTTP status.
+pub trait HttpStatus {
+    fn status(&self) -> StatusCode;
+}
+
+impl<B> HttpStatus for axum::http::Response<B> {
+    fn status(&self) -> StatusCode {
+        self.status()
+    }
+}
+
+impl HttpStatus for reqwest::Response {
+    fn status(&self) -> StatusCode {
+        self.status()
+    }
+}
+
+/// Counts errors, 5xx and 429 responses as failures; other 4xx are the
+/// caller's problem, not the target's.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServerErrors;
+
+impl<Res: HttpStatus, E> Classify<Res, E> for ServerErrors {
+    fn is_failure(&self, result: &Result<Res, E>) -> bool {
+        match result {
+            Ok(response) => is_server_error(response.status()),
+            Err(_) => true,
+        }
+    }
+}
+
+fn is_server_error(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerLayer<C = AnyError> {
+    breaker: CircuitBreaker,
+    classifier: C,
+}
+
+impl CircuitBreakerLayer {
+    pub fn new(breaker: CircuitBreaker) -> Self {
+        Self {
+            breaker,
+            classifier: AnyError,
+        }
+    }
+}
+
+impl<C> CircuitBreakerLayer<C> {
+    pub fn with_classifier<D>(self, classifier: D) -> CircuitBreakerLayer<D> {
+        CircuitBreakerLayer {
+            breaker: self.breaker,
+            classifier,
+        }
+    }
+}
+
+impl<S, C: Clone> Layer<S> for CircuitBreakerLayer<C> {
+    type Service = CircuitBreakerService<S, C>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CircuitBreakerService {
+            inner,
+            breaker: self.breaker.clone(),
+            classifier: self.classifier.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerService<S, C = AnyError> {
+    inner: S,
+    breaker: CircuitBreaker,
+    classifier: C,
+}
+
+impl<S, C> CircuitBreakerService<S, C> {
+    pub fn breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, C, Req> Service<Req> for CircuitBreakerService<S, C>
+where
+    S: Service<Req>,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+    S::Error: Send + 'static,
+    C: Classify<S::Response, S::Error> + Clone + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = CircuitError<S::Error>;
+    type Future = BoxFuture<'static, Result<S::Response, CircuitError<S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(CircuitError::Inner)
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        let permit = match self.breaker.try_acquire() {
+            Ok(permit) => permit,
+            Err(open) => return Box::pin(std::future::ready(Err(CircuitError::Open(open)))),
+        };
+        let future = self.inner.call(request);
+        let classifier = self.classifier.clone();
+        Box::pin(async move {
+            let result = future.await;
+            if classifier.is_failure(&result) {
+                permit.failure();
+            } else {
+                permit.success();
+            }
+            result.map_err(CircuitError::Inner)
+        })
+    }
+}
+
+impl IntoResponse for CircuitOpen {
+    fn into_response(self) -> Response {
+        let retry_after = self.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            axum::Json(serde_json::json!({ "error": self.to_string() })),
+        )
+            .into_response()
+    }
+}
+
+/// Fails requests fast with 503 while `breaker` is open, counting 5xx and
+/// 429 responses as failures. Install with
+/// `axum::middleware::from_fn_with_state(breaker, circuit_breaker_middleware)`.
+pub async fn circuit_breaker_middleware(
+    State(breaker): State<CircuitBreaker>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let permit = match breaker.try_acquire() {
+        Ok(permit) => permit,
+        Err(open) => return open.into_response(),
+    };
+    let response = next.run(request).await;
+    if is_server_error(response.status()) {
+        permit.failure();
+    } else {
+        permit.success();
+    }
+    response
+}
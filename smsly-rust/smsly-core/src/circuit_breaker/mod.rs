@@ -0,0 +1,739 @@
+//! Circuit breaking for calls to providers and downstream services.
+//!
+//! A `CircuitBreaker` counts outcomes over a sliding time window. Once at
+//! least `minimum_calls` calls in the window fail at `failure_rate` or
+//! more, the circuit opens and calls fail fast with `CircuitOpen` for
+//! `open_duration`. It then goes half-open, letting `half_open_max_calls`
+//! trial calls through: if they all succeed it closes, and any failure
+//! opens it again.
+//!
+//! Each breaker is local to its process; `CircuitSync` shares one across
+//! replicas through Redis. `SharedCircuits` keeps simpler
+//! consecutive-failure circuits for many targets in Redis alone. Transitions are counted in `GLOBAL_METRICS` and
+//! passed to any `CircuitListener`s, and a `CircuitRegistry` lists
+//! breakers for `/debug/circuits`.
+//!
+//! ```
+//! # use smsly_core::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitError};
+//! # async fn send(_to: &str, _body: &str) -> Result<String, std::io::Error> {
+//! #     Ok("SM1".to_string())
+//! # }
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! # let (to, body) = ("+15550100", "Your code is 1234");
+//! let breaker = CircuitBreaker::new("twilio", CircuitBreakerConfig::default());
+//! match breaker.call(send(to, body)).await {
+//!     Ok(sid) => println!("sent {}", sid),
+//!     // Fail over; the provider takes trial calls again after `retry_after`.
+//!     Err(CircuitError::Open(open)) => println!("{} open for {:?}", open.name, open.retry_after),
+//!     Err(CircuitError::Inner(e)) => println!("send failed: {}", e),
+//! }
+//! # }
+//! ```
+
+pub mod distributed;
+pub mod events;
+pub mod layer;
+pub mod policy;
+pub mod registry;
+pub mod shared;
+
+pub use distributed::{CircuitSync, SyncConfig};
+pub use events::{AuditListener, CircuitEvent, CircuitListener};
+pub use layer::{
+    circuit_breaker_middleware, AnyError, CircuitBreakerLayer, CircuitBreakerService, Classify,
+    ServerErrors,
+};
+pub use policy::CircuitPolicies;
+pub use registry::{create_circuit_debug_router, CircuitRegistry, CircuitSnapshot};
+pub use shared::{SharedCircuitConfig, SharedCircuits};
+
+use chrono::Utc;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Open => "open",
+            Self::HalfOpen => "half_open",
+        }
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Circuit {name} is open; retry in {retry_after:?}")]
+pub struct CircuitOpen {
+    pub name: String,
+    /// Until the circuit lets a trial call through.
+    pub retry_after: Duration,
+}
+
+#[derive(Error, Debug)]
+pub enum CircuitError<E> {
+    #[error(transparent)]
+    Open(CircuitOpen),
+    #[error("{0}")]
+    Inner(E),
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Share of failed calls in the window that opens the circuit, 0 to 1.
+    pub failure_rate: f64,
+    /// Calls the window needs before the failure rate counts, so a couple
+    /// of early failures don't open it.
+    pub minimum_calls: u32,
+    pub window: Duration,
+    /// Buckets the window is divided into; outcomes expire a bucket at a
+    /// time.
+    pub window_buckets: u32,
+    pub open_duration: Duration,
+    /// Concurrent trial calls while half-open, all of which must succeed
+    /// to close the circuit.
+    pub half_open_max_calls: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_rate: 0.5,
+            minimum_calls: 20,
+            window: Duration::from_secs(60),
+            window_buckets: 10,
+            open_duration: Duration::from_secs(30),
+            half_open_max_calls: 3,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn with_failure_rate(mut self, rate: f64) -> Self {
+        self.failure_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_minimum_calls(mut self, calls: u32) -> Self {
+        self.minimum_calls = calls.max(1);
+        self
+    }
+
+    pub fn with_window(mut self, window: Duration, buckets: u32) -> Self {
+        self.window = window;
+        self.window_buckets = buckets.max(1);
+        self
+    }
+
+    pub fn with_open_duration(mut self, duration: Duration) -> Self {
+        self.open_duration = duration;
+        self
+    }
+
+    pub fn with_half_open_max_calls(mut self, calls: u32) -> Self {
+        self.half_open_max_calls = calls.max(1);
+        self
+    }
+
+    pub(crate) fn bucket_width(&self) -> Duration {
+        (self.window / self.window_buckets.max(1)).max(Duration::from_millis(1))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    start: Instant,
+    successes: u32,
+    failures: u32,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: CircuitState,
+    /// Bumped on every transition, so calls started before one don't
+    /// count towards the next state.
+    generation: u64,
+    opened_at: Instant,
+    buckets: VecDeque<Bucket>,
+    trials_in_flight: u32,
+    trial_successes: u32,
+    /// Outcomes not yet pushed by a `CircuitSync`.
+    unsynced: WindowStats,
+    /// Opened by this replica since the last sync, rather than by another.
+    opened_locally: bool,
+    /// Transitions made under the lock, for listeners once it's released.
+    pending_events: Vec<CircuitEvent>,
+}
+
+/// Outcome totals over the current window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WindowStats {
+    pub successes: u32,
+    pub failures: u32,
+}
+
+impl WindowStats {
+    pub fn total(&self) -> u32 {
+        self.successes + self.failures
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        if self.total() == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.total() as f64
+        }
+    }
+}
+
+struct Shared {
+    name: String,
+    config: CircuitBreakerConfig,
+    inner: Mutex<Inner>,
+    listeners: RwLock<Vec<Arc<dyn CircuitListener>>>,
+}
+
+impl fmt::Debug for Shared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("name", &self.name)
+            .field("config", &self.config)
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Cheap to clone; clones share state.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    shared: Arc<Shared>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &str, config: CircuitBreakerConfig) -> Self {
+        events::set_state_gauge(name, CircuitState::Closed);
+        Self {
+            shared: Arc::new(Shared {
+                name: name.to_string(),
+                config,
+                inner: Mutex::new(Inner {
+                    state: CircuitState::Closed,
+                    generation: 0,
+                    opened_at: Instant::now(),
+                    buckets: VecDeque::new(),
+                    trials_in_flight: 0,
+                    trial_successes: 0,
+                    unsynced: WindowStats::default(),
+                    opened_locally: false,
+                    pending_events: Vec::new(),
+                }),
+                listeners: RwLock::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn with_listener(self, listener: Arc<dyn CircuitListener>) -> Self {
+        self.add_listener(listener);
+        self
+    }
+
+    /// Also applies to the breaker's clones.
+    pub fn add_listener(&self, listener: Arc<dyn CircuitListener>) {
+        self.shared
+            .listeners
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(listener);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.shared.name
+    }
+
+    pub fn config(&self) -> &CircuitBreakerConfig {
+        &self.shared.config
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.shared.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs `f` under the lock, then reports any transitions it made.
+    fn with_inner<R>(&self, f: impl FnOnce(&mut Inner) -> R) -> R {
+        let mut inner = self.lock();
+        let result = f(&mut inner);
+        let events = std::mem::take(&mut inner.pending_events);
+        drop(inner);
+        if !events.is_empty() {
+            let listeners = self
+                .shared
+                .listeners
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone();
+            for event in &events {
+                events::record_metrics(event);
+                for listener in &listeners {
+                    listener.on_transition(event);
+                }
+            }
+        }
+        result
+    }
+
+    /// The current state, moving an open circuit whose time is up to
+    /// half-open.
+    pub fn state(&self) -> CircuitState {
+        self.with_inner(|inner| {
+            self.expire_open(inner, Instant::now());
+            inner.state
+        })
+    }
+
+    pub fn window_stats(&self) -> WindowStats {
+        let mut inner = self.lock();
+        let now = Instant::now();
+        self.prune(&mut inner, now);
+        Self::stats(&inner)
+    }
+
+    /// Asks to make a call. Report its outcome on the permit; dropping the
+    /// permit without doing so (e.g. the call was cancelled) records
+    /// nothing.
+    pub fn try_acquire(&self) -> Result<CallPermit, CircuitOpen> {
+        self.with_inner(|inner| {
+            let now = Instant::now();
+            self.expire_open(inner, now);
+            let trial = match inner.state {
+                CircuitState::Closed => false,
+                CircuitState::Open => {
+                    return Err(self.open_error(inner, now));
+                }
+                CircuitState::HalfOpen => {
+                    if inner.trials_in_flight >= self.shared.config.half_open_max_calls {
+                        return Err(CircuitOpen {
+                            name: self.shared.name.clone(),
+                            retry_after: Duration::ZERO,
+                        });
+                    }
+                    inner.trials_in_flight += 1;
+                    true
+                }
+            };
+            Ok(CallPermit {
+                breaker: self.clone(),
+                generation: inner.generation,
+                trial,
+                recorded: false,
+            })
+        })
+    }
+
+    /// Runs `fut` if the circuit allows it, counting any `Err` as a
+    /// failure.
+    pub async fn call<F, T, E>(&self, fut: F) -> Result<T, CircuitError<E>>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        self.call_with(fut, |_| true).await
+    }
+
+    /// `call`, counting only errors for which `is_failure` holds, so e.g.
+    /// a rejected destination number doesn't count against a provider.
+    pub async fn call_with<F, T, E, P>(&self, fut: F, is_failure: P) -> Result<T, CircuitError<E>>
+    where
+        F: Future<Output = Result<T, E>>,
+        P: FnOnce(&E) -> bool,
+    {
+        let permit = self.try_acquire().map_err(CircuitError::Open)?;
+        match fut.await {
+            Ok(value) => {
+                permit.success();
+                Ok(value)
+            }
+            Err(e) => {
+                if is_failure(&e) {
+                    permit.failure();
+                } else {
+                    permit.success();
+                }
+                Err(CircuitError::Inner(e))
+            }
+        }
+    }
+
+    /// Closes the circuit and forgets the window.
+    pub fn reset(&self) {
+        self.with_inner(|inner| self.transition(inner, CircuitState::Closed, Instant::now()));
+    }
+
+    /// Opens the circuit for `open_duration`, e.g. when a provider
+    /// announces maintenance.
+    pub fn trip(&self) {
+        self.with_inner(|inner| self.transition(inner, CircuitState::Open, Instant::now()));
+    }
+
+    fn open_error(&self, inner: &Inner, now: Instant) -> CircuitOpen {
+        CircuitOpen {
+            name: self.shared.name.clone(),
+            retry_after: self
+                .shared
+                .config
+                .open_duration
+                .saturating_sub(now.duration_since(inner.opened_at)),
+        }
+    }
+
+    fn expire_open(&self, inner: &mut Inner, now: Instant) {
+        if inner.state == CircuitState::Open
+            && now.duration_since(inner.opened_at) >= self.shared.config.open_duration
+        {
+            self.transition(inner, CircuitState::HalfOpen, now);
+        }
+    }
+
+    fn transition(&self, inner: &mut Inner, to: CircuitState, now: Instant) {
+        if inner.state != to {
+            inner.pending_events.push(CircuitEvent {
+                name: self.shared.name.clone(),
+                from: inner.state,
+                to,
+                at: Utc::now().timestamp_millis() as f64 / 1000.0,
+                stats: Self::stats(inner),
+            });
+        }
+        inner.state = to;
+        inner.generation += 1;
+        inner.trials_in_flight = 0;
+        inner.trial_successes = 0;
+        match to {
+            CircuitState::Open => {
+                inner.opened_at = now;
+                inner.opened_locally = true;
+            }
+            CircuitState::Closed => inner.buckets.clear(),
+            CircuitState::HalfOpen => {}
+        }
+    }
+
+    fn prune(&self, inner: &mut Inner, now: Instant) {
+        let window = self.shared.config.window;
+        while inner
+            .buckets
+            .front()
+            .is_some_and(|b| now.duration_since(b.start) >= window)
+        {
+            inner.buckets.pop_front();
+        }
+    }
+
+    fn stats(inner: &Inner) -> WindowStats {
+        inner
+            .buckets
+            .iter()
+            .fold(WindowStats::default(), |acc, b| WindowStats {
+                successes: acc.successes + b.successes,
+                failures: acc.failures + b.failures,
+            })
+    }
+
+    /// Outcomes since the last call, and whether this replica opened the
+    /// circuit meanwhile.
+    pub(crate) fn take_unsynced(&self) -> (WindowStats, bool) {
+        let mut inner = self.lock();
+        let opened = std::mem::take(&mut inner.opened_locally) && inner.state == CircuitState::Open;
+        (std::mem::take(&mut inner.unsynced), opened)
+    }
+
+    /// Opens the circuit because another replica did, to reopen after
+    /// `remaining` as it will there.
+    pub(crate) fn apply_remote_open(&self, remaining: Duration) {
+        self.with_inner(|inner| {
+            if inner.state == CircuitState::Open {
+                return;
+            }
+            let now = Instant::now();
+            self.transition(inner, CircuitState::Open, now);
+            let elapsed = self.shared.config.open_duration.saturating_sub(remaining);
+            inner.opened_at = now.checked_sub(elapsed).unwrap_or(now);
+            inner.opened_locally = false;
+        })
+    }
+
+    fn record(&self, generation: u64, trial: bool, success: bool) {
+        self.with_inner(|inner| self.record_locked(inner, generation, trial, success));
+    }
+
+    fn record_locked(&self, inner: &mut Inner, generation: u64, trial: bool, success: bool) {
+        let now = Instant::now();
+        if trial {
+            if generation != inner.generation {
+                return;
+            }
+            inner.trials_in_flight = inner.trials_in_flight.saturating_sub(1);
+            if !success {
+                self.transition(inner, CircuitState::Open, now);
+                return;
+            }
+            inner.trial_successes += 1;
+            if inner.trial_successes >= self.shared.config.half_open_max_calls {
+                self.transition(inner, CircuitState::Closed, now);
+            }
+            return;
+        }
+
+        if inner.state != CircuitState::Closed || generation != inner.generation {
+            return;
+        }
+        self.prune(inner, now);
+        if success {
+            inner.unsynced.successes = inner.unsynced.successes.saturating_add(1);
+        } else {
+            inner.unsynced.failures = inner.unsynced.failures.saturating_add(1);
+        }
+        let width = self.shared.config.bucket_width();
+        match inner.buckets.back_mut() {
+            Some(bucket) if now.duration_since(bucket.start) < width => {
+                if success {
+                    bucket.successes += 1;
+                } else {
+                    bucket.failures += 1;
+                }
+            }
+            _ => inner.buckets.push_back(Bucket {
+                start: now,
+                successes: success as u32,
+                failures: !success as u32,
+            }),
+        }
+        if success {
+            return;
+        }
+        let stats = Self::stats(inner);
+        if stats.total() >= self.shared.config.minimum_calls
+            && stats.failure_rate() >= self.shared.config.failure_rate
+        {
+            self.transition(inner, CircuitState::Open, now);
+        }
+    }
+}
+
+/// Permission for one call; see `CircuitBreaker::try_acquire`.
+#[derive(Debug)]
+pub struct CallPermit {
+    breaker: CircuitBreaker,
+    generation: u64,
+    trial: bool,
+    recorded: bool,
+}
+
+impl CallPermit {
+    pub fn success(mut self) {
+        self.recorded = true;
+        self.breaker.record(self.generation, self.trial, true);
+    }
+
+    pub fn failure(mut self) {
+        self.recorded = true;
+        self.breaker.record(self.generation, self.trial, false);
+    }
+}
+
+impl Drop for CallPermit {
+    fn drop(&mut self) {
+        if self.recorded || !self.trial {
+            return;
+        }
+        let mut inner = self.breaker.lock();
+        if inner.generation == self.generation {
+            inner.trials_in_flight = inner.trials_in_flight.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    const OPEN: Duration = Duration::from_millis(30);
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(
+            "test",
+            CircuitBreakerConfig::default()
+                .with_failure_rate(0.5)
+                .with_minimum_calls(4)
+                .with_open_duration(OPEN)
+                .with_half_open_max_calls(2),
+        )
+    }
+
+    fn outcome(breaker: &CircuitBreaker, success: bool) {
+        let permit = breaker.try_acquire().unwrap();
+        if success {
+            permit.success()
+        } else {
+            permit.failure()
+        }
+    }
+
+    /// Opens `breaker` and waits out `OPEN` so it goes half-open.
+    fn half_open(breaker: &CircuitBreaker) {
+        breaker.trip();
+        sleep(OPEN);
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    }
+
+    #[test]
+    fn opens_at_the_failure_rate_once_the_window_has_minimum_calls() {
+        let breaker = breaker();
+        for _ in 0..3 {
+            outcome(&breaker, false);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        outcome(&breaker, true);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        outcome(&breaker, false);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let open = breaker.try_acquire().unwrap_err();
+        assert_eq!(open.name, "test");
+        assert!(open.retry_after > Duration::ZERO && open.retry_after <= OPEN);
+    }
+
+    #[test]
+    fn stays_closed_below_the_failure_rate() {
+        let breaker = breaker();
+        for success in [true, true, false, true, true, false, true] {
+            outcome(&breaker, success);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(
+            breaker.window_stats(),
+            WindowStats {
+                successes: 5,
+                failures: 2
+            }
+        );
+    }
+
+    #[test]
+    fn old_outcomes_leave_the_window() {
+        let breaker = CircuitBreaker::new(
+            "test",
+            CircuitBreakerConfig::default()
+                .with_minimum_calls(2)
+                .with_window(Duration::from_millis(20), 2),
+        );
+        outcome(&breaker, false);
+        sleep(Duration::from_millis(25));
+        assert_eq!(breaker.window_stats(), WindowStats::default());
+        outcome(&breaker, false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn half_open_closes_after_enough_trial_successes() {
+        let breaker = breaker();
+        half_open(&breaker);
+        let first = breaker.try_acquire().unwrap();
+        let second = breaker.try_acquire().unwrap();
+        // Both trial slots are taken.
+        assert_eq!(
+            breaker.try_acquire().unwrap_err().retry_after,
+            Duration::ZERO
+        );
+        first.success();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        second.success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.window_stats(), WindowStats::default());
+    }
+
+    #[test]
+    fn a_failed_trial_reopens() {
+        let breaker = breaker();
+        half_open(&breaker);
+        let first = breaker.try_acquire().unwrap();
+        let second = breaker.try_acquire().unwrap();
+        first.failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        // A trial from before the reopening doesn't count.
+        second.success();
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn dropped_trial_permits_free_their_slot() {
+        let breaker = breaker();
+        half_open(&breaker);
+        let first = breaker.try_acquire().unwrap();
+        drop(breaker.try_acquire().unwrap());
+        breaker.try_acquire().unwrap().success();
+        first.success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn outcomes_from_before_a_transition_are_ignored() {
+        let breaker = breaker();
+        let stale = breaker.try_acquire().unwrap();
+        breaker.trip();
+        breaker.reset();
+        stale.failure();
+        assert_eq!(breaker.window_stats(), WindowStats::default());
+    }
+
+    #[tokio::test]
+    async fn call_with_counts_only_failures() {
+        let breaker = breaker();
+        for _ in 0..4 {
+            let result = breaker
+                .call_with(async { Err::<(), _>("invalid number") }, |_| false)
+                .await;
+            assert!(matches!(result, Err(CircuitError::Inner("invalid number"))));
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.window_stats().successes, 4);
+
+        for _ in 0..4 {
+            let _ = breaker.call(async { Err::<(), _>("timeout") }).await;
+        }
+        assert!(matches!(
+            breaker.call(async { Ok::<_, ()>(()) }).await,
+            Err(CircuitError::Open(_))
+        ));
+    }
+
+    #[test]
+    fn listeners_see_each_transition() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let breaker = breaker().with_listener(Arc::new(move |event: &CircuitEvent| {
+            log.lock().unwrap().push((event.from, event.to))
+        }));
+        half_open(&breaker);
+        breaker.try_acquire().unwrap().success();
+        breaker.try_acquire().unwrap().success();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (CircuitState::Closed, CircuitState::Open),
+                (CircuitState::Open, CircuitState::HalfOpen),
+                (CircuitState::HalfOpen, CircuitState::Closed),
+            ]
+        );
+    }
+}
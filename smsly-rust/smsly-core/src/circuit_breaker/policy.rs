@@ -0,0 +1,92 @@
+use super::CircuitBreakerConfig;
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Breaker configs by target name, falling back to a default, so a
+/// provider and an internal service can be tuned separately.
+#[derive(Debug, Clone, Default)]
+pub struct CircuitPolicies {
+    pub default: CircuitBreakerConfig,
+    pub policies: HashMap<String, CircuitBreakerConfig>,
+}
+
+impl CircuitPolicies {
+    pub fn new(default: CircuitBreakerConfig) -> Self {
+        Self {
+            default,
+            policies: HashMap::new(),
+        }
+    }
+
+    /// Reads `CIRCUIT_DEFAULT_*` for the default policy, then a policy for
+    /// each name in the comma-separated `CIRCUIT_BREAKERS`, from
+    /// `CIRCUIT_<NAME>_*` over the default. `<NAME>` is upper-cased with
+    /// `-` and `.` as `_`, so `billing-service` reads
+    /// `CIRCUIT_BILLING_SERVICE_OPEN_SECS`. The settings are
+    /// `FAILURE_RATE`, `MINIMUM_CALLS`, `WINDOW_SECS`, `WINDOW_BUCKETS`,
+    /// `OPEN_SECS` and `HALF_OPEN_CALLS`.
+    pub fn from_env() -> Self {
+        let default = config_from_env("DEFAULT", CircuitBreakerConfig::default());
+        let mut policies = Self::new(default.clone());
+        for name in env::var("CIRCUIT_BREAKERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let config = config_from_env(&env_name(name), default.clone());
+            policies = policies.with_policy(name, config);
+        }
+        policies
+    }
+
+    pub fn with_policy(mut self, name: &str, config: CircuitBreakerConfig) -> Self {
+        self.policies.insert(name.to_string(), config);
+        self
+    }
+
+    /// The policy for `name`, or the default.
+    pub fn policy(&self, name: &str) -> CircuitBreakerConfig {
+        self.policies.get(name).unwrap_or(&self.default).clone()
+    }
+}
+
+fn env_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '-' | '.' => '_',
+            c => c.to_ascii_uppercase(),
+        })
+        .collect()
+}
+
+fn config_from_env(name: &str, mut config: CircuitBreakerConfig) -> CircuitBreakerConfig {
+    fn var<T: FromStr>(name: &str, setting: &str) -> Option<T> {
+        env::var(format!("CIRCUIT_{}_{}", name, setting))
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+    }
+
+    if let Some(rate) = var(name, "FAILURE_RATE") {
+        config = config.with_failure_rate(rate);
+    }
+    if let Some(calls) = var(name, "MINIMUM_CALLS") {
+        config = config.with_minimum_calls(calls);
+    }
+    let window = var(name, "WINDOW_SECS").map(Duration::from_secs);
+    let buckets = var(name, "WINDOW_BUCKETS");
+    if window.is_some() || buckets.is_some() {
+        let window = window.unwrap_or(config.window);
+        let buckets = buckets.unwrap_or(config.window_buckets);
+        config = config.with_window(window, buckets);
+    }
+    if let Some(secs) = var(name, "OPEN_SECS") {
+        config = config.with_open_duration(Duration::from_secs(secs));
+    }
+    if let Some(calls) = var(name, "HALF_OPEN_CALLS") {
+        config = config.with_half_open_max_calls(calls);
+    }
+    config
+}
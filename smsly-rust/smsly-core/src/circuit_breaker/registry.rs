@@ -0,0 +1,152 @@
+use super::{CircuitBreaker, CircuitBreakerConfig, CircuitListener, CircuitPolicies};
+use axum::{extract::State, routing::get, Json, Router};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitSnapshot {
+    pub name: String,
+    pub state: &'static str,
+    pub successes: u32,
+    pub failures: u32,
+    pub failure_rate: f64,
+}
+
+/// Breakers by name, so the process has one per dependency and they can
+/// all be listed. Listeners added here are attached to every breaker
+/// registered, before and after.
+#[derive(Default)]
+pub struct CircuitRegistry {
+    breakers: RwLock<HashMap<String, CircuitBreaker>>,
+    listeners: RwLock<Vec<Arc<dyn CircuitListener>>>,
+    policies: CircuitPolicies,
+}
+
+impl CircuitRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configs for breakers created by `breaker`.
+    pub fn with_policies(mut self, policies: CircuitPolicies) -> Self {
+        self.policies = policies;
+        self
+    }
+
+    pub fn policies(&self) -> &CircuitPolicies {
+        &self.policies
+    }
+
+    pub fn with_listener(self, listener: Arc<dyn CircuitListener>) -> Self {
+        self.add_listener(listener);
+        self
+    }
+
+    pub fn add_listener(&self, listener: Arc<dyn CircuitListener>) {
+        for breaker in self
+            .breakers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+        {
+            breaker.add_listener(listener.clone());
+        }
+        self.listeners
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(listener);
+    }
+
+    /// Adds `breaker`, replacing any registered under its name.
+    pub fn register(&self, breaker: CircuitBreaker) {
+        for listener in self
+            .listeners
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            breaker.add_listener(listener.clone());
+        }
+        self.breakers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(breaker.name().to_string(), breaker);
+    }
+
+    /// The breaker registered as `name`, creating it with `config` if there
+    /// is none.
+    pub fn get_or_create(&self, name: &str, config: CircuitBreakerConfig) -> CircuitBreaker {
+        if let Some(breaker) = self.get(name) {
+            return breaker;
+        }
+        let mut breakers = self.breakers.write().unwrap_or_else(|e| e.into_inner());
+        breakers
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let breaker = CircuitBreaker::new(name, config);
+                for listener in self
+                    .listeners
+                    .read()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .iter()
+                {
+                    breaker.add_listener(listener.clone());
+                }
+                breaker
+            })
+            .clone()
+    }
+
+    /// The breaker registered as `name`, creating it with its policy if
+    /// there is none.
+    pub fn breaker(&self, name: &str) -> CircuitBreaker {
+        match self.get(name) {
+            Some(breaker) => breaker,
+            None => self.get_or_create(name, self.policies.policy(name)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<CircuitBreaker> {
+        self.breakers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
+    }
+
+    /// Every breaker's current state, by name.
+    pub fn list(&self) -> Vec<CircuitSnapshot> {
+        let mut snapshots: Vec<_> = self
+            .breakers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .map(|breaker| {
+                let stats = breaker.window_stats();
+                CircuitSnapshot {
+                    name: breaker.name().to_string(),
+                    state: breaker.state().as_str(),
+                    successes: stats.successes,
+                    failures: stats.failures,
+                    failure_rate: stats.failure_rate(),
+                }
+            })
+            .collect();
+        snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+        snapshots
+    }
+}
+
+async fn circuits_handler(
+    State(registry): State<Arc<CircuitRegistry>>,
+) -> Json<Vec<CircuitSnapshot>> {
+    Json(registry.list())
+}
+
+/// `/debug/circuits` endpoint listing every registered breaker.
+pub fn create_circuit_debug_router(registry: Arc<CircuitRegistry>) -> Router {
+    Router::new()
+        .route("/debug/circuits", get(circuits_handler))
+        .with_state(registry)
+}
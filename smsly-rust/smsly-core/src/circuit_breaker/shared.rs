@@ -0,0 +1,137 @@
+//! Circuits kept only in Redis, for callers with many targets at once, such
+//! as one per customer callback URL, where a `CircuitBreaker` and
+//! `CircuitSync` per target would be too heavy.
+//!
+//! Each target's circuit opens after `failure_threshold` consecutive
+//! failures and stays open for `open_for`, for every replica. Once it
+//! closes, the count is still at or past the threshold, so the first trial
+//! failing reopens it straight away; a success resets it.
+
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+#[derive(Debug, Clone)]
+pub struct SharedCircuitConfig {
+    pub key_prefix: String,
+    /// Consecutive failures that open a target's circuit.
+    pub failure_threshold: u32,
+    pub open_for: Duration,
+    /// How long a failure count survives without further failures.
+    pub failures_ttl: Duration,
+}
+
+impl Default for SharedCircuitConfig {
+    fn default() -> Self {
+        Self {
+            key_prefix: "smsly:circuit:shared".to_string(),
+            failure_threshold: 5,
+            open_for: Duration::from_secs(60),
+            failures_ttl: Duration::from_secs(600),
+        }
+    }
+}
+
+impl SharedCircuitConfig {
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    pub fn with_open_for(mut self, open_for: Duration) -> Self {
+        self.open_for = open_for;
+        self
+    }
+
+    pub fn with_failures_ttl(mut self, ttl: Duration) -> Self {
+        self.failures_ttl = ttl;
+        self
+    }
+}
+
+pub struct SharedCircuits {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    config: SharedCircuitConfig,
+}
+
+impl SharedCircuits {
+    pub fn new(client: Client, config: SharedCircuitConfig) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &SharedCircuitConfig {
+        &self.config
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    /// Targets are hashed, so URLs and other long names make short keys.
+    fn key(&self, target: &str, suffix: &str) -> String {
+        let digest = hex::encode(Sha256::digest(target.as_bytes()));
+        format!("{}:{}:{}", self.config.key_prefix, &digest[..16], suffix)
+    }
+
+    /// How long `target`'s circuit stays open, if it is.
+    pub async fn open_for(&self, target: &str) -> RedisResult<Option<Duration>> {
+        let mut conn = self.conn().await?;
+        let ttl: i64 = redis::cmd("PTTL")
+            .arg(self.key(target, "open"))
+            .query_async(&mut conn)
+            .await?;
+        Ok((ttl > 0).then(|| Duration::from_millis(ttl as u64)))
+    }
+
+    pub async fn record_success(&self, target: &str) -> RedisResult<()> {
+        let mut conn = self.conn().await?;
+        redis::cmd("DEL")
+            .arg(self.key(target, "failures"))
+            .query_async(&mut conn)
+            .await
+    }
+
+    /// Counts a failure, opening the circuit at the threshold. True if this
+    /// failure opened it, including a failed trial reopening it; failures
+    /// while it is already open neither extend it nor return true.
+    pub async fn record_failure(&self, target: &str) -> RedisResult<bool> {
+        let mut conn = self.conn().await?;
+        let failures_key = self.key(target, "failures");
+        let (failures,): (u32,) = redis::pipe()
+            .cmd("INCR")
+            .arg(&failures_key)
+            .cmd("PEXPIRE")
+            .arg(&failures_key)
+            .arg(self.config.failures_ttl.as_millis() as u64)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        if failures < self.config.failure_threshold {
+            return Ok(false);
+        }
+        let opened: Option<String> = redis::cmd("SET")
+            .arg(self.key(target, "open"))
+            .arg(failures)
+            .arg("NX")
+            .arg("PX")
+            .arg(self.config.open_for.as_millis() as u64)
+            .query_async(&mut conn)
+            .await?;
+        Ok(opened.is_some())
+    }
+}
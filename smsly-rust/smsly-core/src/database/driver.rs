@@ -0,0 +1,271 @@
+use super::{DatabaseError, PoolConfig};
+use sqlx::postgres::PgPool;
+use std::env;
+use std::str::FromStr;
+
+#[cfg(feature = "mysql")]
+use sqlx::mysql::{MySqlConnectOptions, MySqlPool, MySqlPoolOptions};
+#[cfg(feature = "mysql")]
+use sqlx::ConnectOptions;
+
+/// Database backend. MySQL/MariaDB requires the `mysql` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DatabaseDriver {
+    #[default]
+    Postgres,
+    MySql,
+}
+
+impl FromStr for DatabaseDriver {
+    type Err = DatabaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "postgres" | "postgresql" | "pg" => Ok(DatabaseDriver::Postgres),
+            "mysql" | "mariadb" => Ok(DatabaseDriver::MySql),
+            other => Err(DatabaseError::UnsupportedDriver(other.to_string())),
+        }
+    }
+}
+
+impl DatabaseDriver {
+    /// Infers the driver from a URL scheme, ignoring SQLAlchemy suffixes
+    /// such as `+asyncpg`.
+    pub fn from_url(url: &str) -> Option<Self> {
+        let scheme = url.split("://").next()?;
+        scheme.split('+').next()?.parse().ok()
+    }
+
+    /// `DATABASE_DRIVER`, else the `DATABASE_URL` scheme, else Postgres.
+    pub fn from_env() -> Self {
+        env::var("DATABASE_DRIVER")
+            .ok()
+            .and_then(|d| d.parse().ok())
+            .or_else(|| {
+                env::var("DATABASE_URL")
+                    .ok()
+                    .and_then(|u| Self::from_url(&u))
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DatabaseDriver::Postgres => "postgres",
+            DatabaseDriver::MySql => "mysql",
+        }
+    }
+}
+
+/// A pool for either backend, for code that works on both (health checks,
+/// pool metrics, shutdown).
+#[derive(Debug, Clone)]
+pub enum DbPool {
+    Postgres(PgPool),
+    #[cfg(feature = "mysql")]
+    MySql(MySqlPool),
+}
+
+impl From<PgPool> for DbPool {
+    fn from(pool: PgPool) -> Self {
+        DbPool::Postgres(pool)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl From<MySqlPool> for DbPool {
+    fn from(pool: MySqlPool) -> Self {
+        DbPool::MySql(pool)
+    }
+}
+
+impl DbPool {
+    pub async fn connect(
+        driver: DatabaseDriver,
+        config: &PoolConfig,
+    ) -> Result<Self, DatabaseError> {
+        match driver {
+            DatabaseDriver::Postgres => Ok(DbPool::Postgres(
+                config
+                    .pool_options()
+                    .connect_with(config.connect_options(&config.url)?)
+                    .await?,
+            )),
+            #[cfg(feature = "mysql")]
+            DatabaseDriver::MySql => Ok(DbPool::MySql(
+                config
+                    .mysql_pool_options()
+                    .connect_with(config.mysql_connect_options(&config.url)?)
+                    .await?,
+            )),
+            #[cfg(not(feature = "mysql"))]
+            DatabaseDriver::MySql => Err(DatabaseError::UnsupportedDriver(
+                "mysql (build with the `mysql` feature)".to_string(),
+            )),
+        }
+    }
+
+    pub fn driver(&self) -> DatabaseDriver {
+        match self {
+            DbPool::Postgres(_) => DatabaseDriver::Postgres,
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(_) => DatabaseDriver::MySql,
+        }
+    }
+
+    pub fn as_postgres(&self) -> Option<&PgPool> {
+        match self {
+            DbPool::Postgres(pool) => Some(pool),
+            #[cfg(feature = "mysql")]
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "mysql")]
+    pub fn as_mysql(&self) -> Option<&MySqlPool> {
+        match self {
+            DbPool::MySql(pool) => Some(pool),
+            _ => None,
+        }
+    }
+
+    /// Runs `SELECT 1`.
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        match self {
+            DbPool::Postgres(pool) => sqlx::query("SELECT 1").execute(pool).await.map(|_| ()),
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(pool) => sqlx::query("SELECT 1").execute(pool).await.map(|_| ()),
+        }
+    }
+
+    /// Acquires and immediately releases a connection.
+    pub async fn probe_acquire(&self) -> Result<(), sqlx::Error> {
+        match self {
+            DbPool::Postgres(pool) => pool.acquire().await.map(drop),
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(pool) => pool.acquire().await.map(drop),
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        match self {
+            DbPool::Postgres(pool) => pool.size(),
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(pool) => pool.size(),
+        }
+    }
+
+    pub fn num_idle(&self) -> usize {
+        match self {
+            DbPool::Postgres(pool) => pool.num_idle(),
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(pool) => pool.num_idle(),
+        }
+    }
+
+    pub fn max_connections(&self) -> u32 {
+        match self {
+            DbPool::Postgres(pool) => pool.options().get_max_connections(),
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(pool) => pool.options().get_max_connections(),
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        match self {
+            DbPool::Postgres(pool) => pool.is_closed(),
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(pool) => pool.is_closed(),
+        }
+    }
+
+    pub async fn close(&self) {
+        match self {
+            DbPool::Postgres(pool) => pool.close().await,
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(pool) => pool.close().await,
+        }
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl PoolConfig {
+    pub fn mysql_pool_options(&self) -> MySqlPoolOptions {
+        let statement_timeout = self.statement_timeout;
+        MySqlPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .max_lifetime(self.max_lifetime)
+            .test_before_acquire(self.test_before_acquire)
+            .after_connect(move |conn, _| {
+                Box::pin(async move {
+                    // MySQL's equivalent of statement_timeout (SELECTs only).
+                    if let Some(timeout) = statement_timeout {
+                        sqlx::query(&format!(
+                            "SET SESSION max_execution_time = {}",
+                            timeout.as_millis()
+                        ))
+                        .execute(conn)
+                        .await?;
+                    }
+                    Ok(())
+                })
+            })
+    }
+
+    /// `ssl_mode` and `application_name` are Postgres-only; set TLS through
+    /// the URL's `ssl-mode` parameter instead.
+    pub fn mysql_connect_options(&self, url: &str) -> Result<MySqlConnectOptions, DatabaseError> {
+        let mut options = MySqlConnectOptions::from_str(url)?;
+        if !self.log_statements {
+            options = options.disable_statement_logging();
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl super::DatabaseManager {
+    /// MySQL counterpart of `DatabaseManager::connect`.
+    pub async fn connect_mysql(
+        &self,
+        name: &str,
+        config: &PoolConfig,
+    ) -> Result<MySqlPool, DatabaseError> {
+        if let Ok(pool) = self.get_mysql(name) {
+            return Ok(pool);
+        }
+
+        let pool = config
+            .mysql_pool_options()
+            .connect_with(config.mysql_connect_options(&config.url)?)
+            .await?;
+
+        let mut pools = self.mysql_pools.write().unwrap();
+        let pool = pools.entry(name.to_string()).or_insert(pool).clone();
+        tracing::info!(
+            "MySQL pool '{}' initialized with max_connections={}",
+            name,
+            config.max_connections
+        );
+        Ok(pool)
+    }
+
+    pub fn register_mysql(&self, name: &str, pool: MySqlPool) {
+        self.mysql_pools
+            .write()
+            .unwrap()
+            .insert(name.to_string(), pool);
+    }
+
+    pub fn get_mysql(&self, name: &str) -> Result<MySqlPool, DatabaseError> {
+        self.mysql_pools
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| DatabaseError::UnknownPool(name.to_string()))
+    }
+}
@@ -0,0 +1,75 @@
+use crate::metrics::GLOBAL_METRICS;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+pub struct QueryMetricNames;
+
+impl QueryMetricNames {
+    pub const QUERIES_TOTAL: &'static str = "db_queries";
+    pub const QUERY_DURATION: &'static str = "db_query_duration_seconds";
+    pub const QUERY_ERRORS_TOTAL: &'static str = "db_query_errors";
+}
+
+static SLOW_QUERY_THRESHOLD_MS: AtomicU64 = AtomicU64::new(500);
+
+/// Queries slower than this are logged at warn level (default 500ms).
+pub fn set_slow_query_threshold(threshold: Duration) {
+    SLOW_QUERY_THRESHOLD_MS.store(threshold.as_millis() as u64, Ordering::Relaxed);
+}
+
+pub fn slow_query_threshold() -> Duration {
+    Duration::from_millis(SLOW_QUERY_THRESHOLD_MS.load(Ordering::Relaxed))
+}
+
+/// Awaits `query`, recording its duration and outcome under `name`.
+///
+/// ```no_run
+/// # use smsly_core::database::instrument_query;
+/// # #[derive(sqlx::FromRow)]
+/// # struct Message {
+/// #     id: uuid::Uuid,
+/// # }
+/// # async fn get(pool: sqlx::PgPool, id: uuid::Uuid) -> Result<(), sqlx::Error> {
+/// let row = instrument_query(
+///     "messages.get_by_id",
+///     sqlx::query_as::<_, Message>("SELECT * FROM messages WHERE id = $1")
+///         .bind(id)
+///         .fetch_one(&pool),
+/// )
+/// .await?;
+/// # assert_eq!(row.id, id);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn instrument_query<T, E, F>(name: &str, query: F) -> Result<T, E>
+where
+    E: Display,
+    F: Future<Output = Result<T, E>>,
+{
+    let start = Instant::now();
+    let result = query.await;
+    let elapsed = start.elapsed();
+
+    let status = if result.is_ok() { "success" } else { "error" };
+    let labels = HashMap::from([("query".to_string(), name.to_string())]);
+    GLOBAL_METRICS.observe(
+        QueryMetricNames::QUERY_DURATION,
+        elapsed.as_secs_f64(),
+        Some(labels.clone()),
+    );
+    let mut status_labels = labels.clone();
+    status_labels.insert("status".to_string(), status.to_string());
+    GLOBAL_METRICS.increment(QueryMetricNames::QUERIES_TOTAL, 1, Some(status_labels));
+
+    if let Err(e) = &result {
+        GLOBAL_METRICS.increment(QueryMetricNames::QUERY_ERRORS_TOTAL, 1, Some(labels));
+        warn!("Query {} failed after {:?}: {}", name, elapsed, e);
+    } else if elapsed > slow_query_threshold() {
+        warn!("Slow query {} took {:?}", name, elapsed);
+    }
+    result
+}
@@ -0,0 +1,129 @@
+use super::DatabaseError;
+use crate::metrics::GLOBAL_METRICS;
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::postgres::{PgConnection, PgPool};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
+use tracing::info;
+
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    /// Versions applied by this run (or that would be, in dry-run mode).
+    pub applied: Vec<i64>,
+    /// Versions already present in `_sqlx_migrations`.
+    pub already_applied: Vec<i64>,
+    pub dry_run: bool,
+}
+
+/// Applies the migrations in `path` while holding the same Postgres advisory
+/// lock as `sqlx migrate`, so replicas starting together run them only once.
+pub async fn run_migrations(pool: &PgPool, path: &Path) -> Result<MigrationReport, DatabaseError> {
+    migrate(pool, path, false).await
+}
+
+/// Reports which migrations in `path` are pending without applying them.
+pub async fn plan_migrations(pool: &PgPool, path: &Path) -> Result<MigrationReport, DatabaseError> {
+    migrate(pool, path, true).await
+}
+
+async fn migrate(
+    pool: &PgPool,
+    path: &Path,
+    dry_run: bool,
+) -> Result<MigrationReport, DatabaseError> {
+    let migrator = Migrator::new(path).await?;
+    let mut conn = pool.acquire().await?;
+
+    let wait = Instant::now();
+    conn.lock().await?;
+    GLOBAL_METRICS.observe(
+        "db_migration_lock_wait_seconds",
+        wait.elapsed().as_secs_f64(),
+        None,
+    );
+
+    let result = apply_pending(&mut conn, &migrator, dry_run).await;
+    let unlocked = conn.unlock().await;
+    let report = result?;
+    unlocked?;
+    Ok(report)
+}
+
+async fn apply_pending(
+    conn: &mut PgConnection,
+    migrator: &Migrator,
+    dry_run: bool,
+) -> Result<MigrationReport, DatabaseError> {
+    conn.ensure_migrations_table().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrateError::Dirty(version).into());
+    }
+
+    let applied: HashMap<i64, Vec<u8>> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m.checksum.into_owned()))
+        .collect();
+    for version in applied.keys() {
+        if !migrator.version_exists(*version) {
+            return Err(MigrateError::VersionMissing(*version).into());
+        }
+    }
+
+    let mut report = MigrationReport {
+        dry_run,
+        ..Default::default()
+    };
+    for migration in migrator.iter() {
+        if migration.migration_type.is_down_migration() {
+            continue;
+        }
+        if let Some(checksum) = applied.get(&migration.version) {
+            if checksum.as_slice() != migration.checksum.as_ref() {
+                return Err(MigrateError::VersionMismatch(migration.version).into());
+            }
+            report.already_applied.push(migration.version);
+            continue;
+        }
+
+        if dry_run {
+            info!(
+                "Migration pending: {} {}",
+                migration.version, migration.description
+            );
+        } else {
+            let elapsed = conn.apply(migration).await?;
+            info!(
+                "Migration applied: {} {} in {:?}",
+                migration.version, migration.description, elapsed
+            );
+            let labels = HashMap::from([("version".to_string(), migration.version.to_string())]);
+            GLOBAL_METRICS.observe(
+                "db_migration_duration_seconds",
+                elapsed.as_secs_f64(),
+                Some(labels),
+            );
+            GLOBAL_METRICS.increment("db_migrations_applied", 1, None);
+        }
+        report.applied.push(migration.version);
+    }
+
+    let versions = report.already_applied.iter();
+    let latest = if dry_run {
+        versions.max()
+    } else {
+        versions.chain(&report.applied).max()
+    };
+    if let Some(latest) = latest {
+        GLOBAL_METRICS.set_gauge("db_schema_version", *latest as f64, None);
+    }
+    info!(
+        "Migrations {}: {} pending, {} already applied",
+        if dry_run { "planned" } else { "complete" },
+        report.applied.len(),
+        report.already_applied.len()
+    );
+    Ok(report)
+}
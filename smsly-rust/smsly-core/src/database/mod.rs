@@ -0,0 +1,441 @@
+use lazy_static::lazy_static;
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use sqlx::ConnectOptions;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::info;
+
+#[cfg(feature = "mysql")]
+use sqlx::mysql::MySqlPool;
+
+pub use sqlx::postgres::PgSslMode;
+
+pub mod driver;
+pub mod instrument;
+pub mod migrations;
+pub mod notify;
+pub mod outbox;
+pub mod pagination;
+pub mod partitions;
+pub mod pool_metrics;
+pub mod replicas;
+pub mod soft_delete;
+pub mod tenant;
+pub mod transaction;
+pub mod versioning;
+
+pub use driver::{DatabaseDriver, DbPool};
+pub use instrument::{
+    instrument_query, set_slow_query_threshold, slow_query_threshold, QueryMetricNames,
+};
+pub use migrations::{plan_migrations, run_migrations, MigrationReport};
+pub use notify::{notify, PgEvent, PgPubSub};
+pub use outbox::{
+    enqueue_outbox, OutboxConfig, OutboxMessage, OutboxPublisher, OutboxRelay, OUTBOX_SCHEMA_SQL,
+};
+pub use pagination::{KeysetCursor, KeysetPage, KeysetPagination, SortDirection};
+pub use partitions::{
+    attach_partition, detach_partition, list_partitions, PartitionManager, PartitionMonth,
+    PartitionReport, PartitionSpec,
+};
+pub use pool_metrics::{record_pool_metrics, PoolMetricNames};
+pub use replicas::ReplicaSet;
+pub use soft_delete::{purge_soft_deleted, restore, soft_delete, DeletedFilter, SoftDeletable};
+pub use tenant::{begin_for_tenant, TenantContext, TenantIsolation};
+pub use transaction::{
+    is_retryable_conflict, with_transaction, with_transaction_retries, IsolationLevel,
+    TransactionError, TransactionFuture,
+};
+pub use versioning::{Versioned, VersionedUpdate};
+
+/// Name of the pool managed by `create_async_engine` / `get_engine`.
+pub const DEFAULT_POOL: &str = "default";
+
+#[derive(Debug, Error)]
+pub enum DatabaseError {
+    #[error("Database pool '{0}' not initialized")]
+    UnknownPool(String),
+    #[error("Invalid tenant organization id: {0}")]
+    InvalidTenant(String),
+    #[error("Unsupported database driver: {0}")]
+    UnsupportedDriver(String),
+    #[error("Invalid SQL identifier: {0}")]
+    InvalidIdentifier(String),
+    #[error("Version conflict on {table} {id}: expected version {expected}")]
+    VersionConflict {
+        table: String,
+        id: String,
+        expected: i64,
+    },
+    #[error("Invalid pagination cursor: {0}")]
+    InvalidCursor(String),
+    #[error(transparent)]
+    Sqlx(#[from] sqlx::Error),
+    #[error(transparent)]
+    Migrate(#[from] sqlx::migrate::MigrateError),
+}
+
+/// Quotes a table or column name interpolated into SQL. Only plain
+/// lowercase identifiers are accepted.
+pub(crate) fn quote_ident(name: &str) -> Result<String, DatabaseError> {
+    if name.is_empty()
+        || name.len() > 63
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(DatabaseError::InvalidIdentifier(name.to_string()));
+    }
+    Ok(format!("\"{}\"", name))
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    pub idle_timeout: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
+    /// Sent as the `statement_timeout` session setting.
+    pub statement_timeout: Option<Duration>,
+    /// Overrides any `sslmode` in the URL.
+    pub ssl_mode: Option<PgSslMode>,
+    pub application_name: Option<String>,
+    /// Pings connections before handing them out.
+    pub test_before_acquire: bool,
+    /// Logs every statement through sqlx's statement logger (debug level).
+    pub log_statements: bool,
+}
+
+impl PoolConfig {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(600)),
+            max_lifetime: Some(Duration::from_secs(1800)),
+            statement_timeout: None,
+            ssl_mode: None,
+            application_name: None,
+            test_before_acquire: true,
+            log_statements: false,
+        }
+    }
+
+    pub fn with_max_connections(mut self, max_connections: u32) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    pub fn with_min_connections(mut self, min_connections: u32) -> Self {
+        self.min_connections = min_connections;
+        self
+    }
+
+    pub fn pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .max_lifetime(self.max_lifetime)
+            .test_before_acquire(self.test_before_acquire)
+    }
+
+    /// Connection options for `url` (the primary or a replica) with this
+    /// config's session settings applied.
+    pub fn connect_options(&self, url: &str) -> Result<PgConnectOptions, DatabaseError> {
+        let mut options = PgConnectOptions::from_str(url)?;
+        if let Some(mode) = self.ssl_mode {
+            options = options.ssl_mode(mode);
+        }
+        if let Some(name) = &self.application_name {
+            options = options.application_name(name);
+        }
+        if let Some(timeout) = self.statement_timeout {
+            options = options.options([("statement_timeout", timeout.as_millis().to_string())]);
+        }
+        if !self.log_statements {
+            options = options.disable_statement_logging();
+        }
+        Ok(options)
+    }
+}
+
+/// Named pools, so one service can talk to several databases
+/// (e.g. `default` and `analytics`). MySQL pools need the `mysql` feature.
+#[derive(Default)]
+pub struct DatabaseManager {
+    pools: RwLock<HashMap<String, PgPool>>,
+    #[cfg(feature = "mysql")]
+    mysql_pools: RwLock<HashMap<String, MySqlPool>>,
+    replica_sets: RwLock<HashMap<String, Arc<ReplicaSet>>>,
+}
+
+impl DatabaseManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connects `name` with `config`; an already registered pool is returned as is.
+    pub async fn connect(&self, name: &str, config: &PoolConfig) -> Result<PgPool, DatabaseError> {
+        if let Ok(pool) = self.get(name) {
+            return Ok(pool);
+        }
+
+        let pool = config
+            .pool_options()
+            .connect_with(config.connect_options(&config.url)?)
+            .await?;
+
+        let mut pools = self.pools.write().unwrap();
+        let pool = pools.entry(name.to_string()).or_insert(pool).clone();
+        info!(
+            "Database pool '{}' initialized with max_connections={}",
+            name, config.max_connections
+        );
+        Ok(pool)
+    }
+
+    /// Connects `name` with the given driver; see `DatabaseDriver::from_env`.
+    pub async fn connect_driver(
+        &self,
+        name: &str,
+        driver: DatabaseDriver,
+        config: &PoolConfig,
+    ) -> Result<DbPool, DatabaseError> {
+        match driver {
+            DatabaseDriver::Postgres => self.connect(name, config).await.map(DbPool::Postgres),
+            #[cfg(feature = "mysql")]
+            DatabaseDriver::MySql => self.connect_mysql(name, config).await.map(DbPool::MySql),
+            #[cfg(not(feature = "mysql"))]
+            DatabaseDriver::MySql => DbPool::connect(driver, config).await,
+        }
+    }
+
+    /// Connects `name` as the primary and routes its reads to `replica_urls`.
+    pub async fn connect_with_replicas(
+        &self,
+        name: &str,
+        config: &PoolConfig,
+        replica_urls: &[String],
+    ) -> Result<Arc<ReplicaSet>, DatabaseError> {
+        let primary = self.connect(name, config).await?;
+        let set = Arc::new(ReplicaSet::new(primary, replica_urls, config)?);
+        self.replica_sets
+            .write()
+            .unwrap()
+            .insert(name.to_string(), set.clone());
+        info!(
+            "Database pool '{}' routing reads to {} replica(s)",
+            name,
+            replica_urls.len()
+        );
+        Ok(set)
+    }
+
+    /// Pool for writes to `name` (its primary).
+    pub fn get_write(&self, name: &str) -> Result<PgPool, DatabaseError> {
+        self.get(name)
+    }
+
+    /// Pool for reads from `name`: a healthy replica if any are configured,
+    /// otherwise the primary.
+    pub fn get_read(&self, name: &str) -> Result<PgPool, DatabaseError> {
+        let set = self.replica_sets.read().unwrap().get(name).cloned();
+        match set {
+            Some(set) => Ok(set.get_read()),
+            None => self.get(name),
+        }
+    }
+
+    pub fn replica_set(&self, name: &str) -> Option<Arc<ReplicaSet>> {
+        self.replica_sets.read().unwrap().get(name).cloned()
+    }
+
+    /// Registers an externally built pool, replacing any pool with the same name.
+    pub fn register(&self, name: &str, pool: PgPool) {
+        self.pools.write().unwrap().insert(name.to_string(), pool);
+    }
+
+    pub fn get(&self, name: &str) -> Result<PgPool, DatabaseError> {
+        self.pools
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| DatabaseError::UnknownPool(name.to_string()))
+    }
+
+    /// Pool `name` for either driver.
+    pub fn get_pool(&self, name: &str) -> Result<DbPool, DatabaseError> {
+        if let Ok(pool) = self.get(name) {
+            return Ok(DbPool::Postgres(pool));
+        }
+        #[cfg(feature = "mysql")]
+        if let Ok(pool) = self.get_mysql(name) {
+            return Ok(DbPool::MySql(pool));
+        }
+        Err(DatabaseError::UnknownPool(name.to_string()))
+    }
+
+    /// Every registered pool plus read replicas, named `<pool>:replica<n>`.
+    pub fn all_pools(&self) -> Vec<(String, DbPool)> {
+        let mut pools: Vec<(String, DbPool)> = self
+            .pools
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, pool)| (name.clone(), DbPool::Postgres(pool.clone())))
+            .collect();
+        #[cfg(feature = "mysql")]
+        pools.extend(
+            self.mysql_pools
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(name, pool)| (name.clone(), DbPool::MySql(pool.clone()))),
+        );
+        for (name, set) in self.replica_sets.read().unwrap().iter() {
+            for (idx, pool) in set.replica_pools().into_iter().enumerate() {
+                pools.push((format!("{}:replica{}", name, idx), DbPool::Postgres(pool)));
+            }
+        }
+        pools
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        #[allow(unused_mut)]
+        let mut names: Vec<String> = self.pools.read().unwrap().keys().cloned().collect();
+        #[cfg(feature = "mysql")]
+        names.extend(self.mysql_pools.read().unwrap().keys().cloned());
+        names
+    }
+
+    pub async fn close(&self, name: &str) {
+        self.replica_sets.write().unwrap().remove(name);
+        let pool = self
+            .pools
+            .write()
+            .unwrap()
+            .remove(name)
+            .map(DbPool::Postgres);
+        #[cfg(feature = "mysql")]
+        let pool = pool.or_else(|| {
+            self.mysql_pools
+                .write()
+                .unwrap()
+                .remove(name)
+                .map(DbPool::MySql)
+        });
+        if let Some(pool) = pool {
+            pool.close().await;
+            info!("Database pool '{}' closed", name);
+        }
+    }
+
+    pub async fn close_all(&self) {
+        self.replica_sets.write().unwrap().clear();
+        #[allow(unused_mut)]
+        let mut pools: Vec<(String, DbPool)> = self
+            .pools
+            .write()
+            .unwrap()
+            .drain()
+            .map(|(name, pool)| (name, DbPool::Postgres(pool)))
+            .collect();
+        #[cfg(feature = "mysql")]
+        pools.extend(
+            self.mysql_pools
+                .write()
+                .unwrap()
+                .drain()
+                .map(|(name, pool)| (name, DbPool::MySql(pool))),
+        );
+        for (name, pool) in pools {
+            pool.close().await;
+            info!("Database pool '{}' closed", name);
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref DATABASE_MANAGER: DatabaseManager = DatabaseManager::new();
+}
+
+/// Interval of the pool gauge task started by `create_async_engine`.
+pub const POOL_METRICS_INTERVAL: Duration = Duration::from_secs(15);
+
+static POOL_METRICS_TASK: OnceLock<tokio::task::JoinHandle<()>> = OnceLock::new();
+
+/// Connects the default pool. As with the Python engine, `pool_size`
+/// connections are kept open and up to `max_overflow` more are opened under
+/// load; `pool_pre_ping` tests connections before use and `echo` logs SQL.
+pub async fn create_async_engine(
+    database_url: &str,
+    pool_size: u32,
+    max_overflow: u32,
+    pool_pre_ping: bool,
+    echo: bool,
+) -> Result<PgPool, DatabaseError> {
+    let mut config = PoolConfig::new(database_url)
+        .with_min_connections(pool_size)
+        .with_max_connections(pool_size + max_overflow);
+    config.test_before_acquire = pool_pre_ping;
+    config.log_statements = echo;
+    create_async_engine_with(&config).await
+}
+
+/// Connects the default pool from a full `PoolConfig`.
+pub async fn create_async_engine_with(config: &PoolConfig) -> Result<PgPool, DatabaseError> {
+    let pool = DATABASE_MANAGER.connect(DEFAULT_POOL, config).await?;
+    POOL_METRICS_TASK.get_or_init(|| DATABASE_MANAGER.spawn_pool_metrics(POOL_METRICS_INTERVAL));
+    Ok(pool)
+}
+
+/// Connects the default pool with `driver`, for services that may run
+/// against MySQL/MariaDB.
+pub async fn create_engine_for_driver(
+    driver: DatabaseDriver,
+    config: &PoolConfig,
+) -> Result<DbPool, DatabaseError> {
+    let pool = DATABASE_MANAGER
+        .connect_driver(DEFAULT_POOL, driver, config)
+        .await?;
+    POOL_METRICS_TASK.get_or_init(|| DATABASE_MANAGER.spawn_pool_metrics(POOL_METRICS_INTERVAL));
+    Ok(pool)
+}
+
+/// The default pool, whichever driver it uses.
+pub fn get_pool() -> Result<DbPool, DatabaseError> {
+    DATABASE_MANAGER.get_pool(DEFAULT_POOL)
+}
+
+pub fn get_engine() -> Result<PgPool, DatabaseError> {
+    DATABASE_MANAGER.get(DEFAULT_POOL)
+}
+
+/// Primary pool of the default database.
+pub fn get_write() -> Result<PgPool, DatabaseError> {
+    DATABASE_MANAGER.get_write(DEFAULT_POOL)
+}
+
+/// Read pool of the default database; see `DatabaseManager::get_read`.
+pub fn get_read() -> Result<PgPool, DatabaseError> {
+    DATABASE_MANAGER.get_read(DEFAULT_POOL)
+}
+
+pub async fn get_db() -> Result<sqlx::pool::PoolConnection<sqlx::Postgres>, DatabaseError> {
+    let pool = get_engine()?;
+    Ok(pool.acquire().await?)
+}
+
+pub async fn close_engine() {
+    DATABASE_MANAGER.close(DEFAULT_POOL).await;
+}
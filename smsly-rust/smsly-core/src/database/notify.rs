@@ -0,0 +1,109 @@
+use super::DatabaseError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlx::postgres::{PgListener, PgPool};
+use std::marker::PhantomData;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone)]
+pub enum PgEvent<T> {
+    Notification {
+        channel: String,
+        payload: T,
+    },
+    /// The listener reconnected; notifications sent while it was down are
+    /// lost, so subscribers caching state should reload it.
+    Reconnected,
+}
+
+/// Publishes a JSON payload with `pg_notify`. Postgres limits payloads to
+/// just under 8000 bytes, so send ids rather than whole records.
+pub async fn notify<T: Serialize>(
+    pool: &PgPool,
+    channel: &str,
+    payload: &T,
+) -> Result<(), DatabaseError> {
+    let payload = serde_json::to_string(payload).unwrap_or_default();
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(channel)
+        .bind(payload)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// `LISTEN`s on a set of channels and fans typed payloads out to a
+/// broadcast channel. Payloads that fail to deserialize are logged and skipped.
+pub struct PgPubSub<T> {
+    sender: broadcast::Sender<PgEvent<T>>,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T> PgPubSub<T>
+where
+    T: DeserializeOwned + Clone + Send + 'static,
+{
+    /// Connects a dedicated listener and starts relaying. `capacity` bounds how
+    /// far a slow subscriber may lag before it misses events.
+    pub async fn listen(
+        pool: &PgPool,
+        channels: &[&str],
+        capacity: usize,
+    ) -> Result<(Self, JoinHandle<()>), DatabaseError> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen_all(channels.iter().copied()).await?;
+        info!("Listening on Postgres channels: {}", channels.join(", "));
+
+        let (sender, _) = broadcast::channel(capacity);
+        let tx = sender.clone();
+        let handle = tokio::spawn(async move {
+            let mut backoff = Duration::from_millis(100);
+            loop {
+                // `try_recv` yields `None` when the connection drops; the next
+                // call reconnects and re-issues LISTEN for every channel.
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => {
+                        backoff = Duration::from_millis(100);
+                        match serde_json::from_str::<T>(notification.payload()) {
+                            Ok(payload) => {
+                                let _ = tx.send(PgEvent::Notification {
+                                    channel: notification.channel().to_string(),
+                                    payload,
+                                });
+                            }
+                            Err(e) => warn!(
+                                "Invalid payload on channel {}: {}",
+                                notification.channel(),
+                                e
+                            ),
+                        }
+                    }
+                    Ok(None) => {
+                        warn!("Postgres listener connection lost, reconnecting");
+                        let _ = tx.send(PgEvent::Reconnected);
+                    }
+                    Err(e) => {
+                        warn!("Postgres listener error: {}; retrying in {:?}", e, backoff);
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(Duration::from_secs(30));
+                    }
+                }
+            }
+        });
+
+        Ok((
+            Self {
+                sender,
+                _payload: PhantomData,
+            },
+            handle,
+        ))
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PgEvent<T>> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,323 @@
+use super::{quote_ident, DatabaseError};
+use crate::metrics::GLOBAL_METRICS;
+use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::postgres::{PgConnection, PgPool};
+use sqlx::Row;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Default outbox table; include this in a service migration.
+pub const OUTBOX_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS outbox (
+    id UUID PRIMARY KEY,
+    topic TEXT NOT NULL,
+    message_key TEXT,
+    payload JSONB NOT NULL,
+    headers JSONB NOT NULL DEFAULT '{}'::jsonb,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    available_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    published_at TIMESTAMPTZ,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT
+);
+CREATE INDEX IF NOT EXISTS outbox_pending_idx
+    ON outbox (available_at, created_at) WHERE published_at IS NULL;
+"#;
+
+#[derive(Debug, Clone)]
+pub struct OutboxMessage {
+    pub id: Uuid,
+    pub topic: String,
+    pub key: Option<String>,
+    pub payload: Value,
+    pub headers: HashMap<String, String>,
+    /// Delivery attempts made before this one.
+    pub attempts: i32,
+}
+
+impl OutboxMessage {
+    pub fn new(topic: &str, payload: Value) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            topic: topic.to_string(),
+            key: None,
+            payload,
+            headers: HashMap::new(),
+            attempts: 0,
+        }
+    }
+
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.key = Some(key.to_string());
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+/// Destination for relayed outbox rows, e.g. a queue producer.
+#[async_trait]
+pub trait OutboxPublisher: Send + Sync {
+    async fn publish(&self, message: &OutboxMessage) -> Result<(), String>;
+}
+
+/// Writes `message` to the outbox using the caller's transaction, so it is
+/// only relayed if the surrounding business write commits.
+pub async fn enqueue_outbox(
+    conn: &mut PgConnection,
+    message: &OutboxMessage,
+) -> Result<Uuid, DatabaseError> {
+    enqueue_outbox_into(conn, "outbox", message).await
+}
+
+pub async fn enqueue_outbox_into(
+    conn: &mut PgConnection,
+    table: &str,
+    message: &OutboxMessage,
+) -> Result<Uuid, DatabaseError> {
+    sqlx::query(&format!(
+        "INSERT INTO {} (id, topic, message_key, payload, headers) VALUES ($1, $2, $3, $4, $5)",
+        quote_ident(table)?
+    ))
+    .bind(message.id)
+    .bind(&message.topic)
+    .bind(&message.key)
+    .bind(&message.payload)
+    .bind(serde_json::to_value(&message.headers).unwrap_or_default())
+    .execute(conn)
+    .await?;
+    Ok(message.id)
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxConfig {
+    pub table: String,
+    pub batch_size: i64,
+    pub poll_interval: Duration,
+    /// Rows failing this many times are left for manual inspection.
+    pub max_attempts: i32,
+    /// First retry delay; doubles per attempt up to `max_retry_delay`.
+    pub retry_delay: Duration,
+    pub max_retry_delay: Duration,
+    /// A publish running longer than this counts as a failed attempt.
+    pub publish_timeout: Duration,
+    /// How long a batch may hold its row locks; rows not reached in time
+    /// are left for the next batch.
+    pub max_batch_duration: Duration,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            table: "outbox".to_string(),
+            batch_size: 100,
+            poll_interval: Duration::from_secs(1),
+            max_attempts: 10,
+            retry_delay: Duration::from_secs(1),
+            max_retry_delay: Duration::from_secs(300),
+            publish_timeout: Duration::from_secs(10),
+            max_batch_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Polls the outbox and publishes pending rows with at-least-once delivery:
+/// rows are locked with `FOR UPDATE SKIP LOCKED` so relays on several
+/// replicas share the work, and a row is only marked published after the
+/// publisher accepts it. A crash between the two republishes the row, so
+/// consumers must deduplicate on the message id.
+pub struct OutboxRelay {
+    pool: PgPool,
+    publisher: Arc<dyn OutboxPublisher>,
+    config: OutboxConfig,
+    table: String,
+}
+
+impl OutboxRelay {
+    /// Fails if `config.table` is not a plain lowercase identifier.
+    pub fn new(
+        pool: PgPool,
+        publisher: Arc<dyn OutboxPublisher>,
+        config: OutboxConfig,
+    ) -> Result<Self, DatabaseError> {
+        let table = quote_ident(&config.table)?;
+        Ok(Self {
+            pool,
+            publisher,
+            config,
+            table,
+        })
+    }
+
+    /// Relays one batch, returning how many rows were published.
+    pub async fn relay_once(&self) -> Result<usize, DatabaseError> {
+        let table = &self.table;
+        let started = Instant::now();
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query(&format!(
+            "SELECT id, topic, message_key, payload, headers, attempts FROM {} \
+             WHERE published_at IS NULL AND available_at <= now() AND attempts < $1 \
+             ORDER BY created_at LIMIT $2 FOR UPDATE SKIP LOCKED",
+            table
+        ))
+        .bind(self.config.max_attempts)
+        .bind(self.config.batch_size)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut published = 0;
+        for row in rows {
+            let remaining = self
+                .config
+                .max_batch_duration
+                .saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                break;
+            }
+            let message = OutboxMessage {
+                id: row.try_get("id")?,
+                topic: row.try_get("topic")?,
+                key: row.try_get("message_key")?,
+                payload: row.try_get("payload")?,
+                headers: serde_json::from_value(row.try_get("headers")?).unwrap_or_default(),
+                attempts: row.try_get("attempts")?,
+            };
+            let labels = Some(HashMap::from([(
+                "topic".to_string(),
+                message.topic.clone(),
+            )]));
+
+            let timeout = self.config.publish_timeout.min(remaining);
+            let outcome = tokio::time::timeout(timeout, self.publisher.publish(&message))
+                .await
+                .unwrap_or_else(|_| Err(format!("publish timed out after {:?}", timeout)));
+            match outcome {
+                Ok(()) => {
+                    sqlx::query(&format!(
+                        "UPDATE {} SET published_at = now(), attempts = attempts + 1, last_error = NULL WHERE id = $1",
+                        table
+                    ))
+                    .bind(message.id)
+                    .execute(&mut *tx)
+                    .await?;
+                    GLOBAL_METRICS.increment("outbox_published", 1, labels);
+                    published += 1;
+                }
+                Err(e) => {
+                    let delay = self.retry_delay(message.attempts);
+                    warn!(
+                        "Outbox publish failed for {} (attempt {}): {}",
+                        message.id,
+                        message.attempts + 1,
+                        e
+                    );
+                    if message.attempts + 1 >= self.config.max_attempts {
+                        error!(
+                            "Outbox message {} exhausted {} attempts",
+                            message.id, self.config.max_attempts
+                        );
+                    }
+                    sqlx::query(&format!(
+                        "UPDATE {} SET attempts = attempts + 1, last_error = $2, \
+                         available_at = now() + make_interval(secs => $3) WHERE id = $1",
+                        table
+                    ))
+                    .bind(message.id)
+                    .bind(&e)
+                    .bind(delay.as_secs_f64())
+                    .execute(&mut *tx)
+                    .await?;
+                    GLOBAL_METRICS.increment("outbox_publish_failures", 1, labels);
+                }
+            }
+        }
+        tx.commit().await?;
+        Ok(published)
+    }
+
+    /// Number of rows waiting to be published.
+    pub async fn pending(&self) -> Result<i64, DatabaseError> {
+        let count = sqlx::query_scalar(&format!(
+            "SELECT count(*) FROM {} WHERE published_at IS NULL",
+            self.table
+        ))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count)
+    }
+
+    /// Deletes rows published more than `older_than` ago.
+    pub async fn purge_published(&self, older_than: Duration) -> Result<u64, DatabaseError> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {} WHERE published_at < now() - make_interval(secs => $1)",
+            self.table
+        ))
+        .bind(older_than.as_secs_f64())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Relays until the task is aborted, draining full batches back to back
+    /// and sleeping `poll_interval` once the outbox is empty.
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        info!("Outbox relay started for table {}", self.config.table);
+        tokio::spawn(async move {
+            loop {
+                match self.relay_once().await {
+                    Ok(n) if n as i64 >= self.config.batch_size => continue,
+                    Ok(_) => {}
+                    Err(e) => error!("Outbox relay failed: {}", e),
+                }
+                if let Ok(pending) = self.pending().await {
+                    GLOBAL_METRICS.set_gauge("outbox_pending", pending as f64, None);
+                }
+                tokio::time::sleep(self.config.poll_interval).await;
+            }
+        })
+    }
+
+    fn retry_delay(&self, attempts: i32) -> Duration {
+        self.config
+            .retry_delay
+            .saturating_mul(1 << attempts.clamp(0, 16))
+            .min(self.config.max_retry_delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopPublisher;
+
+    #[async_trait]
+    impl OutboxPublisher for NoopPublisher {
+        async fn publish(&self, _message: &OutboxMessage) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn relay_rejects_unsafe_table_names() {
+        let pool = PgPool::connect_lazy("postgres://localhost/outbox").unwrap();
+        let config = OutboxConfig {
+            table: "outbox; DROP TABLE users".to_string(),
+            ..OutboxConfig::default()
+        };
+        let result = OutboxRelay::new(pool.clone(), Arc::new(NoopPublisher), config);
+        assert!(matches!(result, Err(DatabaseError::InvalidIdentifier(_))));
+
+        let relay =
+            OutboxRelay::new(pool, Arc::new(NoopPublisher), OutboxConfig::default()).unwrap();
+        assert_eq!(relay.table, "\"outbox\"");
+    }
+}
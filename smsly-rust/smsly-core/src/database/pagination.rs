@@ -0,0 +1,179 @@
+use super::DatabaseError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Serialize;
+use sqlx::postgres::Postgres;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::QueryBuilder;
+use uuid::Uuid;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortDirection {
+    /// Newest first.
+    #[default]
+    Desc,
+    Asc,
+}
+
+/// Position after the last row of a page, ordered by `(created_at, id)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeysetCursor {
+    pub created_at: OffsetDateTime,
+    pub id: Uuid,
+}
+
+impl KeysetCursor {
+    pub fn new(created_at: OffsetDateTime, id: Uuid) -> Self {
+        Self { created_at, id }
+    }
+
+    /// Opaque, URL-safe token for API responses.
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.created_at.unix_timestamp_nanos(),
+            self.id
+        ))
+    }
+
+    pub fn decode(token: &str) -> Result<Self, DatabaseError> {
+        let invalid = || DatabaseError::InvalidCursor(token.to_string());
+        let raw = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+        let (nanos, id) = raw.split_once(':').ok_or_else(invalid)?;
+        let nanos: i128 = nanos.parse().map_err(|_| invalid())?;
+        Ok(Self {
+            created_at: OffsetDateTime::from_unix_timestamp_nanos(nanos).map_err(|_| invalid())?,
+            id: Uuid::parse_str(id).map_err(|_| invalid())?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeysetPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+/// Keyset pagination over a `(created_at, id)` ordering. Stable under
+/// concurrent inserts and O(limit) regardless of depth, unlike OFFSET.
+///
+/// ```no_run
+/// # use smsly_core::database::{DatabaseError, KeysetCursor, KeysetPage, KeysetPagination};
+/// # use sqlx::types::time::OffsetDateTime;
+/// # use sqlx::QueryBuilder;
+/// # use uuid::Uuid;
+/// # #[derive(sqlx::FromRow)]
+/// # struct Message {
+/// #     id: Uuid,
+/// #     created_at: OffsetDateTime,
+/// # }
+/// # struct Params {
+/// #     limit: Option<i64>,
+/// #     cursor: Option<String>,
+/// # }
+/// # async fn list(
+/// #     pool: sqlx::PgPool,
+/// #     org_id: Uuid,
+/// #     params: Params,
+/// # ) -> Result<KeysetPage<Message>, DatabaseError> {
+/// let page = KeysetPagination::new(params.limit, params.cursor.as_deref())?;
+/// let mut qb = QueryBuilder::new("SELECT * FROM messages WHERE organization_id = ");
+/// qb.push_bind(org_id);
+/// page.push_filter(&mut qb, "created_at", "id");
+/// page.push_order_and_limit(&mut qb, "created_at", "id");
+/// let rows: Vec<Message> = qb.build_query_as().fetch_all(&pool).await?;
+/// let page = page.into_page(rows, |m| KeysetCursor::new(m.created_at, m.id));
+/// # Ok(page)
+/// # }
+/// ```
+///
+/// Column names are interpolated into SQL and must be trusted identifiers.
+#[derive(Debug, Clone)]
+pub struct KeysetPagination {
+    pub limit: i64,
+    pub cursor: Option<KeysetCursor>,
+    pub direction: SortDirection,
+}
+
+impl KeysetPagination {
+    /// Clamps `limit` to `1..=MAX_PAGE_SIZE` and decodes `cursor`.
+    pub fn new(limit: Option<i64>, cursor: Option<&str>) -> Result<Self, DatabaseError> {
+        Ok(Self {
+            limit: limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+            cursor: cursor
+                .filter(|c| !c.is_empty())
+                .map(KeysetCursor::decode)
+                .transpose()?,
+            direction: SortDirection::Desc,
+        })
+    }
+
+    pub fn with_direction(mut self, direction: SortDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Appends `AND (created_col, id_col) < (cursor)` (or `>` ascending).
+    /// The query must already have a `WHERE` clause; use `WHERE TRUE` if needed.
+    pub fn push_filter(
+        &self,
+        qb: &mut QueryBuilder<'_, Postgres>,
+        created_col: &str,
+        id_col: &str,
+    ) {
+        if let Some(cursor) = &self.cursor {
+            let op = match self.direction {
+                SortDirection::Desc => "<",
+                SortDirection::Asc => ">",
+            };
+            qb.push(format!(" AND ({}, {}) {} (", created_col, id_col, op));
+            qb.push_bind(cursor.created_at);
+            qb.push(", ");
+            qb.push_bind(cursor.id);
+            qb.push(")");
+        }
+    }
+
+    /// Appends `ORDER BY ... LIMIT limit + 1`; the extra row detects `has_more`.
+    pub fn push_order_and_limit(
+        &self,
+        qb: &mut QueryBuilder<'_, Postgres>,
+        created_col: &str,
+        id_col: &str,
+    ) {
+        let dir = match self.direction {
+            SortDirection::Desc => "DESC",
+            SortDirection::Asc => "ASC",
+        };
+        qb.push(format!(
+            " ORDER BY {} {}, {} {} LIMIT ",
+            created_col, dir, id_col, dir
+        ));
+        qb.push_bind(self.limit + 1);
+    }
+
+    /// Trims the look-ahead row and builds the next cursor from the last item.
+    pub fn into_page<T>(
+        &self,
+        mut rows: Vec<T>,
+        key: impl Fn(&T) -> KeysetCursor,
+    ) -> KeysetPage<T> {
+        let has_more = rows.len() as i64 > self.limit;
+        rows.truncate(self.limit as usize);
+        let next_cursor = if has_more {
+            rows.last().map(|row| key(row).encode())
+        } else {
+            None
+        };
+        KeysetPage {
+            items: rows,
+            next_cursor,
+            has_more,
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use super::{quote_ident, DatabaseError};
+use sqlx::postgres::{PgConnection, PgPool};
+use sqlx::types::time::OffsetDateTime;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Arbitrary key for the advisory lock serializing maintenance across replicas.
+const PARTITION_LOCK_KEY: i64 = 0x736d_736c_7970;
+
+/// A calendar month, the unit of partitioning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PartitionMonth {
+    pub year: i32,
+    pub month: u8,
+}
+
+impl PartitionMonth {
+    pub fn new(year: i32, month: u8) -> Self {
+        Self {
+            year,
+            month: month.clamp(1, 12),
+        }
+    }
+
+    pub fn current() -> Self {
+        let now = OffsetDateTime::now_utc();
+        Self::new(now.year(), u8::from(now.month()))
+    }
+
+    /// This month shifted by `months` (negative goes back).
+    pub fn offset(&self, months: i32) -> Self {
+        let index = self.year * 12 + (self.month as i32 - 1) + months;
+        Self::new(index.div_euclid(12), (index.rem_euclid(12) + 1) as u8)
+    }
+
+    /// Inclusive lower bound, e.g. `2026-10-01 00:00:00+00`.
+    pub fn start(&self) -> String {
+        format!("{:04}-{:02}-01 00:00:00+00", self.year, self.month)
+    }
+
+    /// Exclusive upper bound: the start of the next month.
+    pub fn end(&self) -> String {
+        self.offset(1).start()
+    }
+}
+
+/// A table range-partitioned by month on a timestamp column. Partitions are
+/// named `<table>_pYYYY_MM`; tables not following that pattern are ignored.
+#[derive(Debug, Clone)]
+pub struct PartitionSpec {
+    pub schema: String,
+    pub table: String,
+    /// Future months to keep created ahead of time.
+    pub premake: u32,
+    /// Months of data to keep, counting the current one; `None` keeps everything.
+    pub retention_months: Option<u32>,
+}
+
+impl PartitionSpec {
+    pub fn new(table: &str) -> Self {
+        Self {
+            schema: "public".to_string(),
+            table: table.to_string(),
+            premake: 3,
+            retention_months: None,
+        }
+    }
+
+    pub fn with_schema(mut self, schema: &str) -> Self {
+        self.schema = schema.to_string();
+        self
+    }
+
+    pub fn with_premake(mut self, months: u32) -> Self {
+        self.premake = months;
+        self
+    }
+
+    pub fn with_retention_months(mut self, months: u32) -> Self {
+        self.retention_months = Some(months);
+        self
+    }
+
+    pub fn partition_name(&self, month: PartitionMonth) -> String {
+        format!("{}_p{:04}_{:02}", self.table, month.year, month.month)
+    }
+
+    /// Inverse of `partition_name`.
+    pub fn parse_partition_name(&self, name: &str) -> Option<PartitionMonth> {
+        let suffix = name.strip_prefix(&self.table)?.strip_prefix("_p")?;
+        let (year, month) = suffix.split_once('_')?;
+        if year.len() != 4 || month.len() != 2 {
+            return None;
+        }
+        let month: u8 = month.parse().ok()?;
+        if !(1..=12).contains(&month) {
+            return None;
+        }
+        Some(PartitionMonth::new(year.parse().ok()?, month))
+    }
+
+    fn qualified(&self, name: &str) -> Result<String, DatabaseError> {
+        Ok(format!(
+            "{}.{}",
+            quote_ident(&self.schema)?,
+            quote_ident(name)?
+        ))
+    }
+
+    pub fn create_sql(&self, month: PartitionMonth) -> Result<String, DatabaseError> {
+        Ok(format!(
+            "CREATE TABLE IF NOT EXISTS {} PARTITION OF {} FOR VALUES FROM ('{}') TO ('{}')",
+            self.qualified(&self.partition_name(month))?,
+            self.qualified(&self.table)?,
+            month.start(),
+            month.end()
+        ))
+    }
+
+    pub fn attach_sql(&self, month: PartitionMonth) -> Result<String, DatabaseError> {
+        Ok(format!(
+            "ALTER TABLE {} ATTACH PARTITION {} FOR VALUES FROM ('{}') TO ('{}')",
+            self.qualified(&self.table)?,
+            self.qualified(&self.partition_name(month))?,
+            month.start(),
+            month.end()
+        ))
+    }
+
+    /// `CONCURRENTLY` avoids blocking writers but cannot run in a transaction.
+    pub fn detach_sql(
+        &self,
+        month: PartitionMonth,
+        concurrently: bool,
+    ) -> Result<String, DatabaseError> {
+        Ok(format!(
+            "ALTER TABLE {} DETACH PARTITION {}{}",
+            self.qualified(&self.table)?,
+            self.qualified(&self.partition_name(month))?,
+            if concurrently { " CONCURRENTLY" } else { "" }
+        ))
+    }
+
+    pub fn drop_sql(&self, month: PartitionMonth) -> Result<String, DatabaseError> {
+        Ok(format!(
+            "DROP TABLE IF EXISTS {}",
+            self.qualified(&self.partition_name(month))?
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PartitionReport {
+    pub created: Vec<String>,
+    pub dropped: Vec<String>,
+    /// Every DDL statement run (or that would be, in dry-run mode).
+    pub statements: Vec<String>,
+    pub dry_run: bool,
+    /// Another instance held the maintenance lock, so nothing was done.
+    pub skipped: bool,
+}
+
+/// Monthly partitions attached to `spec`'s parent table, oldest first.
+pub async fn list_partitions(
+    pool: &PgPool,
+    spec: &PartitionSpec,
+) -> Result<Vec<PartitionMonth>, DatabaseError> {
+    let names: Vec<String> = sqlx::query_scalar(
+        "SELECT child.relname::text FROM pg_inherits \
+         JOIN pg_class parent ON parent.oid = pg_inherits.inhparent \
+         JOIN pg_class child ON child.oid = pg_inherits.inhrelid \
+         JOIN pg_namespace ns ON ns.oid = parent.relnamespace \
+         WHERE ns.nspname = $1 AND parent.relname = $2",
+    )
+    .bind(&spec.schema)
+    .bind(&spec.table)
+    .fetch_all(pool)
+    .await?;
+    let mut months: Vec<PartitionMonth> = names
+        .iter()
+        .filter_map(|name| spec.parse_partition_name(name))
+        .collect();
+    months.sort();
+    Ok(months)
+}
+
+/// Creates the current month's partition plus `premake` future ones and,
+/// with a retention set, detaches and drops partitions older than it.
+///
+/// ```no_run
+/// # use smsly_core::database::{PartitionManager, PartitionSpec};
+/// # use std::time::Duration;
+/// # async fn start(pool: sqlx::PgPool) {
+/// let manager = PartitionManager::new(vec![
+///     PartitionSpec::new("messages").with_retention_months(13),
+///     PartitionSpec::new("audit_logs").with_premake(6),
+/// ]);
+/// manager.spawn(pool.clone(), Duration::from_secs(6 * 3600));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PartitionManager {
+    specs: Vec<PartitionSpec>,
+    dry_run: bool,
+}
+
+impl PartitionManager {
+    pub fn new(specs: Vec<PartitionSpec>) -> Self {
+        Self {
+            specs,
+            dry_run: false,
+        }
+    }
+
+    /// Plans the DDL into the report without executing it.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub async fn maintain(&self, pool: &PgPool) -> Result<PartitionReport, DatabaseError> {
+        self.maintain_at(pool, PartitionMonth::current()).await
+    }
+
+    /// `maintain` as if `now` were the current month.
+    pub async fn maintain_at(
+        &self,
+        pool: &PgPool,
+        now: PartitionMonth,
+    ) -> Result<PartitionReport, DatabaseError> {
+        let mut report = PartitionReport {
+            dry_run: self.dry_run,
+            ..Default::default()
+        };
+        let mut conn = pool.acquire().await?;
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(PARTITION_LOCK_KEY)
+            .fetch_one(&mut *conn)
+            .await?;
+        if !locked {
+            report.skipped = true;
+            return Ok(report);
+        }
+
+        let mut result = Ok(());
+        for spec in &self.specs {
+            result = self
+                .maintain_spec(pool, &mut conn, spec, now, &mut report)
+                .await;
+            if result.is_err() {
+                break;
+            }
+        }
+        let unlocked = sqlx::query("SELECT pg_advisory_unlock($1)")
+            .bind(PARTITION_LOCK_KEY)
+            .execute(&mut *conn)
+            .await;
+        result?;
+        unlocked?;
+
+        if !report.statements.is_empty() {
+            info!(
+                "Partition maintenance{}: created [{}], dropped [{}]",
+                if self.dry_run { " (dry run)" } else { "" },
+                report.created.join(", "),
+                report.dropped.join(", ")
+            );
+        }
+        Ok(report)
+    }
+
+    async fn maintain_spec(
+        &self,
+        pool: &PgPool,
+        conn: &mut PgConnection,
+        spec: &PartitionSpec,
+        now: PartitionMonth,
+        report: &mut PartitionReport,
+    ) -> Result<(), DatabaseError> {
+        let existing = list_partitions(pool, spec).await?;
+
+        for ahead in 0..=spec.premake as i32 {
+            let month = now.offset(ahead);
+            if existing.contains(&month) {
+                continue;
+            }
+            self.execute(conn, spec.create_sql(month)?, report).await?;
+            report.created.push(spec.partition_name(month));
+        }
+
+        if let Some(retention) = spec.retention_months {
+            let oldest_kept = now.offset(1 - retention.max(1) as i32);
+            for month in existing.into_iter().filter(|m| *m < oldest_kept) {
+                self.execute(conn, spec.detach_sql(month, false)?, report)
+                    .await?;
+                self.execute(conn, spec.drop_sql(month)?, report).await?;
+                report.dropped.push(spec.partition_name(month));
+            }
+        }
+        Ok(())
+    }
+
+    async fn execute(
+        &self,
+        conn: &mut PgConnection,
+        sql: String,
+        report: &mut PartitionReport,
+    ) -> Result<(), DatabaseError> {
+        if !self.dry_run {
+            sqlx::query(&sql).execute(&mut *conn).await?;
+        }
+        report.statements.push(sql);
+        Ok(())
+    }
+
+    /// Runs `maintain` on `interval` until the task is aborted.
+    pub fn spawn(self, pool: PgPool, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.maintain(&pool).await {
+                    warn!("Partition maintenance failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+/// Attaches an existing table as `month`'s partition of `spec`, e.g. after
+/// a bulk load or restoring an archived month.
+pub async fn attach_partition(
+    pool: &PgPool,
+    spec: &PartitionSpec,
+    month: PartitionMonth,
+) -> Result<(), DatabaseError> {
+    sqlx::query(&spec.attach_sql(month)?).execute(pool).await?;
+    info!("Attached partition {}", spec.partition_name(month));
+    Ok(())
+}
+
+/// Detaches `month`'s partition, leaving it as a standalone table for
+/// archiving.
+pub async fn detach_partition(
+    pool: &PgPool,
+    spec: &PartitionSpec,
+    month: PartitionMonth,
+    concurrently: bool,
+) -> Result<(), DatabaseError> {
+    sqlx::query(&spec.detach_sql(month, concurrently)?)
+        .execute(pool)
+        .await?;
+    info!("Detached partition {}", spec.partition_name(month));
+    Ok(())
+}
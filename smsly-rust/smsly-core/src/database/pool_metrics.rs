@@ -0,0 +1,63 @@
+use super::{DatabaseManager, DbPool};
+use crate::metrics::GLOBAL_METRICS;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+pub struct PoolMetricNames;
+
+impl PoolMetricNames {
+    pub const SIZE: &'static str = "db_pool_connections";
+    pub const IDLE: &'static str = "db_pool_idle_connections";
+    pub const IN_USE: &'static str = "db_pool_in_use_connections";
+    pub const MAX: &'static str = "db_pool_max_connections";
+    pub const ACQUIRE_WAIT: &'static str = "db_pool_acquire_wait_seconds";
+}
+
+/// Publishes size, idle, in-use and max gauges for `pool`, then times one
+/// `acquire()` to report how long callers currently wait for a connection.
+pub async fn record_pool_metrics(name: &str, pool: &DbPool) {
+    let labels = Some(HashMap::from([("pool".to_string(), name.to_string())]));
+    let size = pool.size() as f64;
+    let idle = pool.num_idle() as f64;
+    GLOBAL_METRICS.set_gauge(PoolMetricNames::SIZE, size, labels.clone());
+    GLOBAL_METRICS.set_gauge(PoolMetricNames::IDLE, idle, labels.clone());
+    GLOBAL_METRICS.set_gauge(PoolMetricNames::IN_USE, size - idle, labels.clone());
+    GLOBAL_METRICS.set_gauge(
+        PoolMetricNames::MAX,
+        pool.max_connections() as f64,
+        labels.clone(),
+    );
+
+    if pool.is_closed() {
+        return;
+    }
+    let start = Instant::now();
+    if pool.probe_acquire().await.is_ok() {
+        GLOBAL_METRICS.set_gauge(
+            PoolMetricNames::ACQUIRE_WAIT,
+            start.elapsed().as_secs_f64(),
+            labels,
+        );
+    }
+}
+
+impl DatabaseManager {
+    /// Records gauges for every registered pool and read replica.
+    pub async fn record_pool_metrics(&self) {
+        for (name, pool) in self.all_pools() {
+            record_pool_metrics(&name, &pool).await;
+        }
+    }
+
+    /// Runs `record_pool_metrics` on `interval` until the task is aborted.
+    pub fn spawn_pool_metrics(&'static self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.record_pool_metrics().await;
+            }
+        })
+    }
+}
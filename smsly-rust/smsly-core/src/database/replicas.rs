@@ -0,0 +1,149 @@
+use super::{DatabaseError, PoolConfig};
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::PgPool;
+use sqlx::Postgres;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+struct Replica {
+    label: String,
+    pool: PgPool,
+    healthy: AtomicBool,
+}
+
+/// A primary pool plus read replicas. Reads round-robin across healthy
+/// replicas and fall back to the primary when none are available.
+pub struct ReplicaSet {
+    primary: PgPool,
+    replicas: Vec<Replica>,
+    next: AtomicUsize,
+}
+
+impl ReplicaSet {
+    /// Replica pools connect lazily, so an unreachable replica does not block
+    /// startup; it is marked down on first failure.
+    pub fn new(
+        primary: PgPool,
+        replica_urls: &[String],
+        config: &PoolConfig,
+    ) -> Result<Self, DatabaseError> {
+        let mut replicas = Vec::with_capacity(replica_urls.len());
+        for url in replica_urls {
+            let pool = config
+                .pool_options()
+                .acquire_timeout(config.acquire_timeout.min(Duration::from_secs(3)))
+                .connect_lazy_with(config.connect_options(url)?);
+            replicas.push(Replica {
+                label: redact_url(url),
+                pool,
+                healthy: AtomicBool::new(true),
+            });
+        }
+        Ok(Self {
+            primary,
+            replicas,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn get_write(&self) -> PgPool {
+        self.primary.clone()
+    }
+
+    /// Next healthy replica, or the primary if every replica is down.
+    pub fn get_read(&self) -> PgPool {
+        self.next_healthy()
+            .map(|idx| self.replicas[idx].pool.clone())
+            .unwrap_or_else(|| self.primary.clone())
+    }
+
+    /// Acquires a read connection, marking replicas that fail to hand one out
+    /// as down and falling back to the primary.
+    pub async fn acquire_read(&self) -> Result<PoolConnection<Postgres>, DatabaseError> {
+        for _ in 0..self.replicas.len() {
+            let Some(idx) = self.next_healthy() else {
+                break;
+            };
+            match self.replicas[idx].pool.acquire().await {
+                Ok(conn) => return Ok(conn),
+                Err(e) => self.mark(idx, false, Some(&e)),
+            }
+        }
+        Ok(self.primary.acquire().await?)
+    }
+
+    pub fn replica_pools(&self) -> Vec<PgPool> {
+        self.replicas.iter().map(|r| r.pool.clone()).collect()
+    }
+
+    pub fn healthy_replicas(&self) -> usize {
+        self.replicas
+            .iter()
+            .filter(|r| r.healthy.load(Ordering::Relaxed))
+            .count()
+    }
+
+    /// Pings every replica, updating which ones receive reads.
+    pub async fn check_replicas(&self) {
+        for idx in 0..self.replicas.len() {
+            match sqlx::query("SELECT 1")
+                .execute(&self.replicas[idx].pool)
+                .await
+            {
+                Ok(_) => self.mark(idx, true, None),
+                Err(e) => self.mark(idx, false, Some(&e)),
+            }
+        }
+    }
+
+    /// Runs `check_replicas` on `interval` until the task is aborted.
+    pub fn spawn_health_checks(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.check_replicas().await;
+            }
+        })
+    }
+
+    fn next_healthy(&self) -> Option<usize> {
+        let len = self.replicas.len();
+        if len == 0 {
+            return None;
+        }
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&idx| self.replicas[idx].healthy.load(Ordering::Relaxed))
+    }
+
+    fn mark(&self, idx: usize, healthy: bool, error: Option<&sqlx::Error>) {
+        let replica = &self.replicas[idx];
+        if replica.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            if healthy {
+                info!("Read replica {} recovered", replica.label);
+            } else {
+                warn!(
+                    "Read replica {} marked down: {}",
+                    replica.label,
+                    error.map(|e| e.to_string()).unwrap_or_default()
+                );
+            }
+        }
+    }
+}
+
+/// Host and database of a connection URL, without credentials, for logs.
+fn redact_url(url: &str) -> String {
+    match url.rsplit_once('@') {
+        Some((_, host)) => host.to_string(),
+        None => url
+            .split_once("://")
+            .map(|(_, rest)| rest.to_string())
+            .unwrap_or_else(|| url.to_string()),
+    }
+}
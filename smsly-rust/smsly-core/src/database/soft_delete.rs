@@ -0,0 +1,94 @@
+use super::{quote_ident, DatabaseError};
+use sqlx::postgres::{PgConnection, Postgres};
+use sqlx::QueryBuilder;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A table whose rows are hidden by setting `deleted_at` rather than removed.
+/// The table needs `id uuid` and `deleted_at timestamptz NULL` columns.
+///
+/// ```no_run
+/// # use smsly_core::database::{soft_delete, DatabaseError, SoftDeletable};
+/// # struct Contact;
+/// impl SoftDeletable for Contact {
+///     const TABLE: &'static str = "contacts";
+/// }
+///
+/// # async fn delete(
+/// #     mut conn: sqlx::PgConnection,
+/// #     contact_id: uuid::Uuid,
+/// # ) -> Result<(), DatabaseError> {
+/// soft_delete::<Contact>(&mut conn, contact_id).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait SoftDeletable {
+    const TABLE: &'static str;
+}
+
+/// Which rows a listing query should see.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeletedFilter {
+    #[default]
+    Exclude,
+    Include,
+    /// Only soft-deleted rows, e.g. for a trash view.
+    Only,
+}
+
+impl DeletedFilter {
+    /// Appends the matching `AND deleted_at ...` condition. The query must
+    /// already have a `WHERE` clause; use `WHERE TRUE` if needed.
+    pub fn push(&self, qb: &mut QueryBuilder<'_, Postgres>, column: &str) {
+        match self {
+            DeletedFilter::Exclude => qb.push(format!(" AND {} IS NULL", column)),
+            DeletedFilter::Include => qb,
+            DeletedFilter::Only => qb.push(format!(" AND {} IS NOT NULL", column)),
+        };
+    }
+}
+
+/// Marks a row deleted. Returns `false` if it is missing or already deleted.
+pub async fn soft_delete<T: SoftDeletable>(
+    conn: &mut PgConnection,
+    id: Uuid,
+) -> Result<bool, DatabaseError> {
+    let result = sqlx::query(&format!(
+        "UPDATE {} SET deleted_at = now() WHERE id = $1 AND deleted_at IS NULL",
+        quote_ident(T::TABLE)?
+    ))
+    .bind(id)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Clears `deleted_at`. Returns `false` if the row is missing or not deleted.
+pub async fn restore<T: SoftDeletable>(
+    conn: &mut PgConnection,
+    id: Uuid,
+) -> Result<bool, DatabaseError> {
+    let result = sqlx::query(&format!(
+        "UPDATE {} SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL",
+        quote_ident(T::TABLE)?
+    ))
+    .bind(id)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Permanently removes rows soft-deleted more than `older_than` ago.
+pub async fn purge_soft_deleted<T: SoftDeletable>(
+    conn: &mut PgConnection,
+    older_than: Duration,
+) -> Result<u64, DatabaseError> {
+    let result = sqlx::query(&format!(
+        "DELETE FROM {} WHERE deleted_at < now() - make_interval(secs => $1)",
+        quote_ident(T::TABLE)?
+    ))
+    .bind(older_than.as_secs_f64())
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
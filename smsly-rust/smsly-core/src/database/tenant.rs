@@ -0,0 +1,85 @@
+use super::DatabaseError;
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+
+/// How tenant isolation is enforced for a connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TenantIsolation {
+    /// `search_path` is set to the tenant's schema (`<prefix><org id>`), then `public`.
+    Schema { prefix: String },
+    /// Only `app.organization_id` is set, for row-level security policies
+    /// using `current_setting('app.organization_id')`.
+    SessionSetting,
+}
+
+impl Default for TenantIsolation {
+    fn default() -> Self {
+        TenantIsolation::Schema {
+            prefix: "org_".to_string(),
+        }
+    }
+}
+
+/// The tenant a unit of work runs as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TenantContext {
+    pub organization_id: String,
+    pub user_id: Option<String>,
+}
+
+impl TenantContext {
+    pub fn new(organization_id: &str) -> Self {
+        Self {
+            organization_id: organization_id.to_string(),
+            user_id: None,
+        }
+    }
+
+    /// Schema name for this tenant; organization ids may only contain
+    /// ASCII alphanumerics, `_` and `-` (mapped to `_`).
+    pub fn schema_name(&self, prefix: &str) -> Result<String, DatabaseError> {
+        let id = &self.organization_id;
+        if id.is_empty()
+            || !id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(DatabaseError::InvalidTenant(id.clone()));
+        }
+        let schema = format!("{}{}", prefix, id.replace('-', "_")).to_lowercase();
+        // Postgres truncates identifiers longer than 63 bytes.
+        if schema.len() > 63 {
+            return Err(DatabaseError::InvalidTenant(id.clone()));
+        }
+        Ok(schema)
+    }
+}
+
+/// Begins a transaction scoped to `tenant`. Settings are applied with
+/// `SET LOCAL` semantics, so they end with the transaction and never leak to
+/// the next user of the pooled connection.
+pub async fn begin_for_tenant(
+    pool: &PgPool,
+    tenant: &TenantContext,
+    isolation: &TenantIsolation,
+) -> Result<Transaction<'static, Postgres>, DatabaseError> {
+    let search_path = match isolation {
+        TenantIsolation::Schema { prefix } => {
+            Some(format!("\"{}\", public", tenant.schema_name(prefix)?))
+        }
+        TenantIsolation::SessionSetting => None,
+    };
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "SELECT set_config('app.organization_id', $1, true), \
+                set_config('app.user_id', $2, true), \
+                CASE WHEN $3::text IS NOT NULL THEN set_config('search_path', $3, true) END",
+    )
+    .bind(&tenant.organization_id)
+    .bind(tenant.user_id.as_deref().unwrap_or(""))
+    .bind(search_path)
+    .execute(&mut *tx)
+    .await?;
+    Ok(tx)
+}
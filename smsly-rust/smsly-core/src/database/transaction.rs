@@ -0,0 +1,152 @@
+use super::DatabaseError;
+use crate::metrics::GLOBAL_METRICS;
+use rand::Rng;
+use sqlx::postgres::{PgPool, Postgres};
+use sqlx::Transaction;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tracing::warn;
+
+/// Future returned by a `with_transaction` body.
+pub type TransactionFuture<'c, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>;
+
+/// Attempts made by `with_transaction` before giving up on a conflict.
+pub const DEFAULT_TRANSACTION_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationLevel {
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    fn as_sql(self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "SET TRANSACTION ISOLATION LEVEL READ COMMITTED",
+            IsolationLevel::RepeatableRead => "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ",
+            IsolationLevel::Serializable => "SET TRANSACTION ISOLATION LEVEL SERIALIZABLE",
+        }
+    }
+}
+
+/// Errors a transaction body may return; lets `with_transaction` find the
+/// underlying database error to decide whether to retry.
+pub trait TransactionError: From<sqlx::Error> {
+    fn as_sqlx(&self) -> Option<&sqlx::Error>;
+}
+
+impl TransactionError for sqlx::Error {
+    fn as_sqlx(&self) -> Option<&sqlx::Error> {
+        Some(self)
+    }
+}
+
+impl TransactionError for DatabaseError {
+    fn as_sqlx(&self) -> Option<&sqlx::Error> {
+        match self {
+            DatabaseError::Sqlx(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// `serialization_failure` (40001) or `deadlock_detected` (40P01).
+pub fn is_retryable_conflict(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == "40001" || code == "40P01")
+}
+
+/// Runs `f` in a transaction at `isolation`, committing on `Ok` and rolling
+/// back on `Err`. Serialization failures and deadlocks are retried with
+/// jittered exponential backoff, so `f` must be safe to run more than once.
+///
+/// ```no_run
+/// # use smsly_core::database::{with_transaction, IsolationLevel};
+/// # async fn debit(pool: sqlx::PgPool, id: uuid::Uuid) -> Result<(), sqlx::Error> {
+/// with_transaction(&pool, IsolationLevel::Serializable, |tx| {
+///     Box::pin(async move {
+///         sqlx::query("UPDATE balances SET amount = amount - 1 WHERE id = $1")
+///             .bind(id)
+///             .execute(&mut **tx)
+///             .await?;
+///         Ok::<_, sqlx::Error>(())
+///     })
+/// })
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn with_transaction<T, E, F>(
+    pool: &PgPool,
+    isolation: IsolationLevel,
+    f: F,
+) -> Result<T, E>
+where
+    E: TransactionError,
+    F: for<'c> FnMut(&'c mut Transaction<'static, Postgres>) -> TransactionFuture<'c, T, E>,
+{
+    with_transaction_retries(pool, isolation, DEFAULT_TRANSACTION_ATTEMPTS, f).await
+}
+
+pub async fn with_transaction_retries<T, E, F>(
+    pool: &PgPool,
+    isolation: IsolationLevel,
+    max_attempts: u32,
+    mut f: F,
+) -> Result<T, E>
+where
+    E: TransactionError,
+    F: for<'c> FnMut(&'c mut Transaction<'static, Postgres>) -> TransactionFuture<'c, T, E>,
+{
+    let mut attempt = 1;
+    loop {
+        match run_once(pool, isolation, &mut f).await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < max_attempts && e.as_sqlx().is_some_and(is_retryable_conflict) => {
+                let delay = backoff_delay(attempt);
+                warn!(
+                    "Transaction conflict on attempt {}/{}, retrying in {:?}",
+                    attempt, max_attempts, delay
+                );
+                GLOBAL_METRICS.increment("db_transaction_retries", 1, None);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+async fn run_once<T, E, F>(pool: &PgPool, isolation: IsolationLevel, f: &mut F) -> Result<T, E>
+where
+    E: TransactionError,
+    F: for<'c> FnMut(&'c mut Transaction<'static, Postgres>) -> TransactionFuture<'c, T, E>,
+{
+    let mut tx = pool.begin().await?;
+    if isolation != IsolationLevel::ReadCommitted {
+        sqlx::query(isolation.as_sql()).execute(&mut *tx).await?;
+    }
+    match f(&mut tx).await {
+        Ok(value) => {
+            tx.commit().await?;
+            Ok(value)
+        }
+        Err(e) => {
+            // Dropping the transaction would also roll back, but without
+            // waiting for the server to acknowledge it.
+            let _ = tx.rollback().await;
+            Err(e)
+        }
+    }
+}
+
+/// 20ms doubling per attempt, capped at 1s, with up to 50% jitter.
+fn backoff_delay(attempt: u32) -> Duration {
+    let base = 20u64.saturating_mul(1 << attempt.min(6)).min(1000);
+    let jitter = rand::thread_rng().gen_range(0..=base / 2);
+    Duration::from_millis(base + jitter)
+}
@@ -0,0 +1,132 @@
+use super::{quote_ident, DatabaseError};
+use sqlx::postgres::{PgConnection, Postgres};
+use sqlx::{Encode, QueryBuilder, Type};
+use uuid::Uuid;
+
+/// A row guarded by optimistic locking through an integer `version` column
+/// (`bigint NOT NULL DEFAULT 1`).
+pub trait Versioned {
+    const TABLE: &'static str;
+
+    fn id(&self) -> Uuid;
+
+    fn version(&self) -> i64;
+}
+
+/// Compare-and-swap update: applies only if the row is still at the version
+/// the caller read, and bumps it. Otherwise fails with
+/// `DatabaseError::VersionConflict` so the caller can reload and retry.
+///
+/// ```no_run
+/// # use smsly_core::database::{DatabaseError, Versioned, VersionedUpdate};
+/// # use sqlx::types::time::OffsetDateTime;
+/// # use uuid::Uuid;
+/// # struct Campaign {
+/// #     id: Uuid,
+/// #     version: i64,
+/// # }
+/// # impl Versioned for Campaign {
+/// #     const TABLE: &'static str = "campaigns";
+/// #     fn id(&self) -> Uuid {
+/// #         self.id
+/// #     }
+/// #     fn version(&self) -> i64 {
+/// #         self.version
+/// #     }
+/// # }
+/// # async fn pause(
+/// #     mut conn: sqlx::PgConnection,
+/// #     campaign: Campaign,
+/// # ) -> Result<(), DatabaseError> {
+/// # let now = OffsetDateTime::now_utc();
+/// let version = VersionedUpdate::for_row(&campaign)
+///     .set("status", "paused")
+///     .set("paused_at", now)
+///     .execute(&mut conn)
+///     .await?;
+/// # assert!(version > campaign.version);
+/// # Ok(())
+/// # }
+/// ```
+pub struct VersionedUpdate<'a> {
+    qb: QueryBuilder<'a, Postgres>,
+    table: String,
+    id: Uuid,
+    expected: i64,
+    skip_deleted: bool,
+    invalid: Option<DatabaseError>,
+}
+
+impl<'a> VersionedUpdate<'a> {
+    pub fn new(table: &str, id: Uuid, expected_version: i64) -> Self {
+        let (qb, invalid) = match quote_ident(table) {
+            Ok(table) => (
+                QueryBuilder::new(format!("UPDATE {} SET version = version + 1", table)),
+                None,
+            ),
+            Err(e) => (QueryBuilder::new(""), Some(e)),
+        };
+        Self {
+            qb,
+            table: table.to_string(),
+            id,
+            expected: expected_version,
+            skip_deleted: false,
+            invalid,
+        }
+    }
+
+    pub fn for_row<T: Versioned>(row: &T) -> Self {
+        Self::new(T::TABLE, row.id(), row.version())
+    }
+
+    pub fn set<T>(mut self, column: &str, value: T) -> Self
+    where
+        T: 'a + Encode<'a, Postgres> + Type<Postgres> + Send,
+    {
+        match quote_ident(column) {
+            Ok(column) => {
+                self.qb.push(format!(", {} = ", column));
+                self.qb.push_bind(value);
+            }
+            Err(e) => {
+                self.invalid.get_or_insert(e);
+            }
+        }
+        self
+    }
+
+    /// Also sets `updated_at = now()`.
+    pub fn touch(mut self) -> Self {
+        self.qb.push(", updated_at = now()");
+        self
+    }
+
+    /// Treats a soft-deleted row as a conflict; see `SoftDeletable`.
+    pub fn excluding_deleted(mut self) -> Self {
+        self.skip_deleted = true;
+        self
+    }
+
+    /// Returns the row's new version.
+    pub async fn execute(mut self, conn: &mut PgConnection) -> Result<i64, DatabaseError> {
+        if let Some(e) = self.invalid {
+            return Err(e);
+        }
+        self.qb.push(" WHERE id = ");
+        self.qb.push_bind(self.id);
+        self.qb.push(" AND version = ");
+        self.qb.push_bind(self.expected);
+        if self.skip_deleted {
+            self.qb.push(" AND deleted_at IS NULL");
+        }
+        self.qb.push(" RETURNING version");
+
+        let version: Option<i64> = self.qb.build_query_scalar().fetch_optional(conn).await?;
+        version.ok_or(DatabaseError::VersionConflict {
+            table: self.table,
+            id: self.id.to_string(),
+            expected: self.expected,
+        })
+    }
+}
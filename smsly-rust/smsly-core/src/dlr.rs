@@ -0,0 +1,226 @@
+//! Delivery-report normalization.
+//!
+//! Every provider reports failures in its own vocabulary: Twilio's 300xx
+//! codes, Vonage's `err-code`, Plivo's `ErrorCode`, WhatsApp Cloud API
+//! errors, GSM MAP error numbers relayed by SMPP carriers, Infobip and
+//! MessageBird, and SES bounces for email. `normalize` maps them onto one
+//! `DlrReason` so retry and billing logic needn't know which provider
+//! carried the message.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DlrReason {
+    /// Handset off or out of coverage.
+    AbsentSubscriber,
+    /// The number isn't (or is no longer) assigned.
+    UnknownSubscriber,
+    /// Malformed, landline or otherwise unroutable number.
+    InvalidNumber,
+    /// Barred by the carrier or the subscriber's plan.
+    Blocked,
+    /// The recipient unsubscribed.
+    OptedOut,
+    /// Dropped by carrier content or sender filtering.
+    SpamFiltered,
+    /// Validity period ran out before the handset could be reached.
+    Expired,
+    /// Handset busy, memory full or similar.
+    HandsetError,
+    NetworkError,
+    /// Prepaid recipient without credit.
+    InsufficientFunds,
+    /// Failure on the provider's side (queue overflow, quota, account).
+    ProviderError,
+    Unknown,
+}
+
+impl DlrReason {
+    /// Whether sending the same message again later may succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            DlrReason::AbsentSubscriber
+                | DlrReason::Expired
+                | DlrReason::HandsetError
+                | DlrReason::NetworkError
+                | DlrReason::ProviderError
+        )
+    }
+
+    /// Whether the number itself is bad, so further sends to it should stop.
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            DlrReason::UnknownSubscriber | DlrReason::InvalidNumber | DlrReason::OptedOut
+        )
+    }
+}
+
+/// GSM 09.02 MAP error codes, relayed as-is by most SMSCs and by Infobip
+/// and MessageBird.
+const GSM_MAP: &[(&str, DlrReason)] = &[
+    ("1", DlrReason::UnknownSubscriber),
+    ("5", DlrReason::UnknownSubscriber),
+    ("6", DlrReason::AbsentSubscriber),
+    ("9", DlrReason::Blocked),
+    ("11", DlrReason::Blocked),
+    ("13", DlrReason::Blocked),
+    ("21", DlrReason::HandsetError),
+    ("27", DlrReason::AbsentSubscriber),
+    ("31", DlrReason::HandsetError),
+    ("32", DlrReason::HandsetError),
+    ("33", DlrReason::HandsetError),
+    ("34", DlrReason::NetworkError),
+    ("35", DlrReason::NetworkError),
+    ("36", DlrReason::NetworkError),
+];
+
+const TWILIO: &[(&str, DlrReason)] = &[
+    ("21211", DlrReason::InvalidNumber),
+    ("21408", DlrReason::Blocked),
+    ("21610", DlrReason::OptedOut),
+    ("21614", DlrReason::InvalidNumber),
+    ("30001", DlrReason::ProviderError),
+    ("30002", DlrReason::ProviderError),
+    ("30003", DlrReason::AbsentSubscriber),
+    ("30004", DlrReason::Blocked),
+    ("30005", DlrReason::UnknownSubscriber),
+    ("30006", DlrReason::InvalidNumber),
+    ("30007", DlrReason::SpamFiltered),
+    ("30008", DlrReason::Unknown),
+    ("30009", DlrReason::NetworkError),
+    ("30010", DlrReason::ProviderError),
+    ("30022", DlrReason::ProviderError),
+    ("30034", DlrReason::Blocked),
+];
+
+const PLIVO: &[(&str, DlrReason)] = &[
+    ("20", DlrReason::NetworkError),
+    ("30", DlrReason::SpamFiltered),
+    ("40", DlrReason::Blocked),
+    ("50", DlrReason::InvalidNumber),
+    ("70", DlrReason::UnknownSubscriber),
+    ("80", DlrReason::AbsentSubscriber),
+    ("90", DlrReason::NetworkError),
+    ("100", DlrReason::Blocked),
+    ("200", DlrReason::OptedOut),
+    ("300", DlrReason::ProviderError),
+    ("420", DlrReason::Expired),
+    ("900", DlrReason::ProviderError),
+];
+
+const WHATSAPP: &[(&str, DlrReason)] = &[
+    ("130429", DlrReason::ProviderError),
+    ("130472", DlrReason::SpamFiltered),
+    ("131000", DlrReason::ProviderError),
+    ("131026", DlrReason::InvalidNumber),
+    ("131049", DlrReason::SpamFiltered),
+    ("131050", DlrReason::OptedOut),
+    ("131053", DlrReason::ProviderError),
+    ("131056", DlrReason::ProviderError),
+];
+
+const VONAGE: &[(&str, DlrReason)] = &[
+    ("1", DlrReason::Unknown),
+    ("2", DlrReason::AbsentSubscriber),
+    ("3", DlrReason::UnknownSubscriber),
+    ("4", DlrReason::Blocked),
+    ("5", DlrReason::NetworkError),
+    ("6", DlrReason::SpamFiltered),
+    ("7", DlrReason::HandsetError),
+    ("8", DlrReason::NetworkError),
+    ("9", DlrReason::Blocked),
+    ("10", DlrReason::SpamFiltered),
+    ("11", DlrReason::InvalidNumber),
+    ("12", DlrReason::AbsentSubscriber),
+    ("13", DlrReason::Blocked),
+    ("14", DlrReason::Blocked),
+    ("15", DlrReason::InsufficientFunds),
+    ("16", DlrReason::ProviderError),
+    ("50", DlrReason::SpamFiltered),
+    ("99", DlrReason::Unknown),
+];
+
+/// SES bounce `bounceType:bounceSubType`, plus complaints.
+const SES: &[(&str, DlrReason)] = &[
+    ("Permanent:General", DlrReason::UnknownSubscriber),
+    ("Permanent:NoEmail", DlrReason::UnknownSubscriber),
+    ("Permanent:Suppressed", DlrReason::Blocked),
+    ("Permanent:OnAccountSuppressionList", DlrReason::Blocked),
+    ("Transient:General", DlrReason::NetworkError),
+    ("Transient:MailboxFull", DlrReason::HandsetError),
+    ("Transient:MessageTooLarge", DlrReason::HandsetError),
+    ("Transient:ContentRejected", DlrReason::SpamFiltered),
+    ("Transient:AttachmentRejected", DlrReason::SpamFiltered),
+    ("Undetermined:Undetermined", DlrReason::Unknown),
+    ("Complaint", DlrReason::OptedOut),
+];
+
+/// Fallback when only a status or description is available, e.g. an SMPP
+/// `stat:EXPIRED` or MessageBird's `statusReason`. Checked in order.
+const KEYWORDS: &[(&str, DlrReason)] = &[
+    ("expired", DlrReason::Expired),
+    ("opted out", DlrReason::OptedOut),
+    ("unsubscribed", DlrReason::OptedOut),
+    ("unknown subscriber", DlrReason::UnknownSubscriber),
+    ("absent", DlrReason::AbsentSubscriber),
+    ("unavailable subscriber", DlrReason::AbsentSubscriber),
+    ("unreachable", DlrReason::AbsentSubscriber),
+    ("invalid destination", DlrReason::InvalidNumber),
+    ("invalid number", DlrReason::InvalidNumber),
+    ("spam", DlrReason::SpamFiltered),
+    ("filter", DlrReason::SpamFiltered),
+    ("blocked", DlrReason::Blocked),
+    ("barred", DlrReason::Blocked),
+    ("carrier rejected", DlrReason::Blocked),
+    ("insufficient", DlrReason::InsufficientFunds),
+    ("capacity", DlrReason::ProviderError),
+    ("network", DlrReason::NetworkError),
+];
+
+/// Error-code dictionary for a provider (by adapter name).
+pub fn dictionary(provider: &str) -> &'static [(&'static str, DlrReason)] {
+    match provider.to_lowercase().as_str() {
+        "twilio" => TWILIO,
+        "plivo" => PLIVO,
+        "whatsapp" => WHATSAPP,
+        "vonage" => VONAGE,
+        "ses" | "email" => SES,
+        "smpp" | "infobip" | "messagebird" | "mock" => GSM_MAP,
+        _ => &[],
+    }
+}
+
+/// Normalized reason for a failure report, or `None` when there's nothing
+/// to classify (no code and no description). Codes missing from the
+/// provider's dictionary fall back to keywords in `description`, then to
+/// `Unknown`.
+pub fn normalize(
+    provider: &str,
+    error_code: Option<&str>,
+    description: Option<&str>,
+) -> Option<DlrReason> {
+    let code = error_code
+        .map(|code| code.trim().trim_start_matches('0'))
+        .filter(|code| !code.is_empty());
+    if code.is_none() && description.is_none() {
+        return None;
+    }
+    if let Some(reason) = code.and_then(|code| {
+        dictionary(provider)
+            .iter()
+            .find(|(known, _)| *known == code)
+            .map(|(_, reason)| *reason)
+    }) {
+        return Some(reason);
+    }
+    let description = description.unwrap_or_default().to_lowercase();
+    Some(
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| description.contains(keyword))
+            .map_or(DlrReason::Unknown, |(_, reason)| *reason),
+    )
+}
@@ -0,0 +1,380 @@
+use super::{ComponentHealth, HealthCheck};
+use async_trait::async_trait;
+use lapin::Connection;
+use reqwest::Url;
+use std::io;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::error;
+
+/// Fetches cluster metadata from the first reachable bootstrap broker.
+pub struct KafkaHealthCheck {
+    bootstrap_servers: Vec<String>,
+    critical: bool,
+}
+
+impl KafkaHealthCheck {
+    /// `bootstrap_servers` is a comma-separated `host:port` list, as in
+    /// `KAFKA_BOOTSTRAP_SERVERS`.
+    pub fn new(bootstrap_servers: &str) -> Self {
+        Self {
+            bootstrap_servers: bootstrap_servers
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            critical: true,
+        }
+    }
+
+    pub fn with_critical(mut self, critical: bool) -> Self {
+        self.critical = critical;
+        self
+    }
+
+    /// Metadata v0 for all topics; succeeds if the broker lists at least one broker.
+    async fn fetch_metadata(server: &str) -> io::Result<()> {
+        const CLIENT_ID: &[u8] = b"smsly-health";
+        let mut request = Vec::new();
+        request.extend_from_slice(&3i16.to_be_bytes()); // api_key: Metadata
+        request.extend_from_slice(&0i16.to_be_bytes()); // api_version
+        request.extend_from_slice(&1i32.to_be_bytes()); // correlation_id
+        request.extend_from_slice(&(CLIENT_ID.len() as i16).to_be_bytes());
+        request.extend_from_slice(CLIENT_ID);
+        request.extend_from_slice(&0i32.to_be_bytes()); // topics: all
+
+        let mut stream = TcpStream::connect(server).await?;
+        stream
+            .write_all(&(request.len() as i32).to_be_bytes())
+            .await?;
+        stream.write_all(&request).await?;
+
+        let size = stream.read_i32().await?;
+        let correlation_id = stream.read_i32().await?;
+        let brokers = stream.read_i32().await?;
+        if size < 8 || correlation_id != 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected metadata response",
+            ));
+        }
+        if brokers <= 0 {
+            return Err(io::Error::other("cluster reported no brokers"));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HealthCheck for KafkaHealthCheck {
+    fn name(&self) -> String {
+        "kafka".to_string()
+    }
+
+    fn critical(&self) -> bool {
+        self.critical
+    }
+
+    async fn check(&self) -> ComponentHealth {
+        let start = SystemTime::now();
+        let mut last_error = "no bootstrap servers configured".to_string();
+        for server in &self.bootstrap_servers {
+            match Self::fetch_metadata(server).await {
+                Ok(()) => return ComponentHealth::connected(start),
+                Err(e) => last_error = format!("{}: {}", server, e),
+            }
+        }
+        error!("Kafka health check failed: {}", last_error);
+        ComponentHealth::error(last_error)
+    }
+}
+
+/// Checks an existing RabbitMQ connection is open and can open a channel.
+pub struct RabbitMqHealthCheck {
+    connection: Arc<Connection>,
+    critical: bool,
+}
+
+impl RabbitMqHealthCheck {
+    pub fn new(connection: Arc<Connection>) -> Self {
+        Self {
+            connection,
+            critical: true,
+        }
+    }
+
+    pub fn with_critical(mut self, critical: bool) -> Self {
+        self.critical = critical;
+        self
+    }
+}
+
+#[async_trait]
+impl HealthCheck for RabbitMqHealthCheck {
+    fn name(&self) -> String {
+        "rabbitmq".to_string()
+    }
+
+    fn critical(&self) -> bool {
+        self.critical
+    }
+
+    async fn check(&self) -> ComponentHealth {
+        let start = SystemTime::now();
+        if !self.connection.status().connected() {
+            return ComponentHealth::error(format!(
+                "connection is {:?}",
+                self.connection.status().state()
+            ));
+        }
+        match self.connection.create_channel().await {
+            Ok(channel) => {
+                let _ = channel.close(200, "health check").await;
+                ComponentHealth::connected(start)
+            }
+            Err(e) => {
+                error!("RabbitMQ health check failed: {}", e);
+                ComponentHealth::error(e.to_string())
+            }
+        }
+    }
+}
+
+/// Opens a NATS connection and round-trips `PING`/`PONG`.
+pub struct NatsHealthCheck {
+    url: String,
+    critical: bool,
+}
+
+impl NatsHealthCheck {
+    /// `url` is a `nats://[user:pass@]host:port` server URL.
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            critical: true,
+        }
+    }
+
+    pub fn with_critical(mut self, critical: bool) -> Self {
+        self.critical = critical;
+        self
+    }
+
+    async fn ping(&self) -> io::Result<()> {
+        let url = Url::parse(&self.url)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing host"))?;
+        let port = url.port().unwrap_or(4222);
+
+        let mut connect = serde_json::json!({
+            "verbose": false,
+            "pedantic": false,
+            "name": "smsly-health",
+            "lang": "rust",
+        });
+        if !url.username().is_empty() {
+            connect["user"] = url.username().into();
+            connect["pass"] = url.password().unwrap_or_default().into();
+        }
+
+        let stream = TcpStream::connect((host, port)).await?;
+        let mut stream = BufReader::new(stream);
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        if !line.starts_with("INFO") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "server did not send INFO",
+            ));
+        }
+        stream
+            .get_mut()
+            .write_all(format!("CONNECT {}\r\nPING\r\n", connect).as_bytes())
+            .await?;
+        loop {
+            line.clear();
+            if stream.read_line(&mut line).await? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed",
+                ));
+            }
+            if line.starts_with("PONG") {
+                return Ok(());
+            }
+            if line.starts_with("-ERR") {
+                return Err(io::Error::other(line.trim().to_string()));
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for NatsHealthCheck {
+    fn name(&self) -> String {
+        "nats".to_string()
+    }
+
+    fn critical(&self) -> bool {
+        self.critical
+    }
+
+    async fn check(&self) -> ComponentHealth {
+        let start = SystemTime::now();
+        match self.ping().await {
+            Ok(()) => ComponentHealth::connected(start),
+            Err(e) => {
+                error!("NATS health check failed: {}", e);
+                ComponentHealth::error(e.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Serves one Metadata request, answering with `correlation_id` and
+    /// `brokers`.
+    async fn kafka_broker(correlation_id: i32, brokers: i32) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let size = stream.read_i32().await.unwrap();
+            let mut request = vec![0; size as usize];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[..8], [0, 3, 0, 0, 0, 0, 0, 1]);
+            assert_eq!(&request[10..22], b"smsly-health");
+            assert_eq!(request[22..], [0, 0, 0, 0]);
+
+            let mut response = Vec::new();
+            response.extend_from_slice(&correlation_id.to_be_bytes());
+            response.extend_from_slice(&brokers.to_be_bytes());
+            response.extend_from_slice(&[0; 16]);
+            stream
+                .write_all(&(response.len() as i32).to_be_bytes())
+                .await
+                .unwrap();
+            stream.write_all(&response).await.unwrap();
+        });
+        address
+    }
+
+    /// An address nothing listens on.
+    async fn closed_port() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn kafka_is_healthy_when_a_broker_answers_metadata() {
+        let servers = format!("{}, {}", closed_port().await, kafka_broker(1, 1).await);
+        let health = KafkaHealthCheck::new(&servers).check().await;
+        assert_eq!(health.status, "connected");
+    }
+
+    #[tokio::test]
+    async fn kafka_rejects_bad_metadata_responses() {
+        let health = KafkaHealthCheck::new(&kafka_broker(1, 0).await)
+            .check()
+            .await;
+        assert!(health.error.unwrap().contains("no brokers"));
+
+        let health = KafkaHealthCheck::new(&kafka_broker(2, 1).await)
+            .check()
+            .await;
+        assert!(health
+            .error
+            .unwrap()
+            .contains("unexpected metadata response"));
+
+        let health = KafkaHealthCheck::new(" , ").check().await;
+        assert_eq!(
+            health.error.as_deref(),
+            Some("no bootstrap servers configured")
+        );
+    }
+
+    /// Serves one NATS client, sending `greeting` first and `reply` after
+    /// its `PING`; returns the server URL with `credentials`.
+    async fn nats_server(
+        credentials: &str,
+        greeting: &'static str,
+        reply: &'static str,
+    ) -> (String, tokio::task::JoinHandle<serde_json::Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("nats://{}{}", credentials, listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            stream
+                .get_mut()
+                .write_all(greeting.as_bytes())
+                .await
+                .unwrap();
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            let connect =
+                serde_json::from_str(line.trim().strip_prefix("CONNECT ").unwrap()).unwrap();
+            line.clear();
+            stream.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "PING\r\n");
+            stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+            connect
+        });
+        (url, server)
+    }
+
+    const INFO: &str = "INFO {\"server_id\":\"test\",\"max_payload\":1048576}\r\n";
+
+    #[tokio::test]
+    async fn nats_is_healthy_on_pong() {
+        let (url, server) = nats_server("smsly:secret@", INFO, "+OK\r\nPONG\r\n").await;
+        let health = NatsHealthCheck::new(&url).check().await;
+        assert_eq!(health.status, "connected");
+
+        let connect = server.await.unwrap();
+        assert_eq!(connect["user"], "smsly");
+        assert_eq!(connect["pass"], "secret");
+        assert_eq!(connect["verbose"], false);
+    }
+
+    #[tokio::test]
+    async fn nats_reports_server_errors() {
+        let (url, _server) = nats_server("", INFO, "-ERR 'Authorization Violation'\r\n").await;
+        let health = NatsHealthCheck::new(&url).check().await;
+        assert_eq!(
+            health.error.as_deref(),
+            Some("-ERR 'Authorization Violation'")
+        );
+
+        let (url, _server) = nats_server("", INFO, "").await;
+        let health = NatsHealthCheck::new(&url).check().await;
+        assert_eq!(health.error.as_deref(), Some("connection closed"));
+    }
+
+    #[tokio::test]
+    async fn nats_needs_an_info_greeting() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("nats://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 400 Bad Request\r\n")
+                .await
+                .unwrap();
+        });
+        let health = NatsHealthCheck::new(&url).check().await;
+        assert_eq!(health.error.as_deref(), Some("server did not send INFO"));
+
+        let health = NatsHealthCheck::new("not a url").check().await;
+        assert_eq!(health.status, "error");
+    }
+}
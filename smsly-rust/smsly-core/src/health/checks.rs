@@ -0,0 +1,167 @@
+use super::ComponentHealth;
+use crate::adapters::BaseProviderAdapter;
+use crate::database::DbPool;
+use async_trait::async_trait;
+use redis::Client;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::Mutex;
+use tracing::error;
+
+/// A single component reported by the health router. Critical checks make the
+/// service unhealthy and fail readiness; non-critical ones only degrade it.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    fn name(&self) -> String;
+
+    fn critical(&self) -> bool {
+        true
+    }
+
+    async fn check(&self) -> ComponentHealth;
+}
+
+pub struct DatabaseHealthCheck {
+    pool: DbPool,
+}
+
+impl DatabaseHealthCheck {
+    /// Accepts a `PgPool`, a `MySqlPool` (with the `mysql` feature) or a `DbPool`.
+    pub fn new(pool: impl Into<DbPool>) -> Self {
+        Self { pool: pool.into() }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for DatabaseHealthCheck {
+    fn name(&self) -> String {
+        "database".to_string()
+    }
+
+    async fn check(&self) -> ComponentHealth {
+        let start = SystemTime::now();
+        match self.pool.ping().await {
+            Ok(_) => ComponentHealth::connected(start),
+            Err(e) => {
+                error!("Database health check failed: {}", e);
+                ComponentHealth::error(e.to_string())
+            }
+        }
+    }
+}
+
+pub struct RedisHealthCheck {
+    client: Client,
+}
+
+impl RedisHealthCheck {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for RedisHealthCheck {
+    fn name(&self) -> String {
+        "redis".to_string()
+    }
+
+    fn critical(&self) -> bool {
+        false
+    }
+
+    async fn check(&self) -> ComponentHealth {
+        let start = SystemTime::now();
+        match self.client.get_multiplexed_async_connection().await {
+            Ok(mut conn) => match redis::cmd("PING").query_async::<_, String>(&mut conn).await {
+                Ok(_) => ComponentHealth::connected(start),
+                Err(e) => {
+                    error!("Redis PING failed: {}", e);
+                    ComponentHealth::error(e.to_string())
+                }
+            },
+            Err(e) => {
+                error!("Redis connection failed: {}", e);
+                ComponentHealth::error(e.to_string())
+            }
+        }
+    }
+}
+
+/// Reuses the wrapped check's last result for `ttl`, so frequent probes don't
+/// hit the backing service every time. Concurrent callers wait on one refresh.
+pub struct CachedHealthCheck {
+    inner: Arc<dyn HealthCheck>,
+    ttl: Duration,
+    last: Mutex<Option<(Instant, ComponentHealth)>>,
+}
+
+impl CachedHealthCheck {
+    pub fn new(inner: Arc<dyn HealthCheck>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            last: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for CachedHealthCheck {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn critical(&self) -> bool {
+        self.inner.critical()
+    }
+
+    async fn check(&self) -> ComponentHealth {
+        let mut last = self.last.lock().await;
+        if let Some((at, result)) = last.as_ref() {
+            if at.elapsed() < self.ttl {
+                return result.clone();
+            }
+        }
+        let result = self.inner.check().await;
+        *last = Some((Instant::now(), result.clone()));
+        result
+    }
+}
+
+/// A registered provider's `health_check()`, reported as `provider:<name>`.
+/// Usually a live call to the provider's API, so wrap it in a
+/// `CachedHealthCheck`; a down provider only degrades the service.
+pub struct ProviderHealthCheck {
+    name: String,
+    adapter: Arc<Box<dyn BaseProviderAdapter>>,
+}
+
+impl ProviderHealthCheck {
+    pub fn new(name: impl Into<String>, adapter: Arc<Box<dyn BaseProviderAdapter>>) -> Self {
+        Self {
+            name: name.into(),
+            adapter,
+        }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for ProviderHealthCheck {
+    fn name(&self) -> String {
+        format!("provider:{}", self.name)
+    }
+
+    fn critical(&self) -> bool {
+        false
+    }
+
+    async fn check(&self) -> ComponentHealth {
+        let start = SystemTime::now();
+        if self.adapter.health_check().await {
+            ComponentHealth::connected(start)
+        } else {
+            ComponentHealth::error(format!("{} health check failed", self.name))
+        }
+    }
+}
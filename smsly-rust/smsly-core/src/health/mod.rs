@@ -0,0 +1,529 @@
+use crate::adapters::{BaseProviderAdapter, ProviderRegistry};
+use crate::database::DbPool;
+use crate::providers::signature::secret_matches;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use futures_util::future::join_all;
+use redis::Client;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+use tracing::warn;
+
+pub mod brokers;
+pub mod checks;
+pub mod monitor;
+pub mod notifier;
+pub mod startup;
+pub mod system;
+
+pub use brokers::{KafkaHealthCheck, NatsHealthCheck, RabbitMqHealthCheck};
+pub use checks::{
+    CachedHealthCheck, DatabaseHealthCheck, HealthCheck, ProviderHealthCheck, RedisHealthCheck,
+};
+pub use monitor::{HealthMonitor, HealthReport};
+pub use notifier::{HealthNotifier, WebhookFormat};
+pub use startup::StartupGate;
+pub use system::{SystemHealthCheck, SystemThresholds};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ComponentHealth {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ComponentHealth {
+    /// `connected` result with the latency measured since `start`.
+    pub fn connected(start: SystemTime) -> Self {
+        let duration = start.elapsed().unwrap_or_default().as_secs_f64() * 1000.0;
+        Self {
+            status: "connected".to_string(),
+            latency_ms: Some((duration * 100.0).round() / 100.0),
+            error: None,
+            details: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            status: "error".to_string(),
+            latency_ms: None,
+            error: Some(message.into()),
+            details: None,
+        }
+    }
+
+    /// The check did not finish within `after`.
+    pub fn timeout(after: Duration) -> Self {
+        Self {
+            status: "timeout".to_string(),
+            latency_ms: Some(after.as_secs_f64() * 1000.0),
+            error: Some(format!("check timed out after {}ms", after.as_millis())),
+            details: None,
+        }
+    }
+
+    /// Status only, for callers not allowed to see errors or latencies.
+    pub fn redacted(&self) -> Self {
+        Self {
+            status: self.status.clone(),
+            latency_ms: None,
+            error: None,
+            details: None,
+        }
+    }
+
+    pub fn is_failing(&self) -> bool {
+        matches!(self.status.as_str(), "error" | "timeout" | "degraded")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: HealthStatus,
+    pub service: String,
+    pub version: String,
+    pub components: HashMap<String, ComponentHealth>,
+    pub timestamp: f64,
+}
+
+#[derive(Clone)]
+pub struct HealthState {
+    pub service_name: String,
+    pub version: String,
+    pub checks: Vec<Arc<dyn HealthCheck>>,
+    /// Upper bound on a single component check.
+    pub check_timeout: Duration,
+    pub startup: Option<StartupGate>,
+    /// Registered SMS providers, reported as `provider:<name>` components.
+    pub providers: Option<Arc<ProviderRegistry>>,
+    /// How long a provider's result is reused; `Duration::ZERO` checks it
+    /// on every evaluation. Overridden per provider by `provider_ttls`.
+    pub provider_cache_ttl: Duration,
+    /// By `provider:<name>` component.
+    pub provider_ttls: HashMap<String, Duration>,
+    /// Latest report from a `HealthMonitor`; handlers check inline without one.
+    pub latest: Option<watch::Receiver<Option<HealthReport>>>,
+    /// Required in `X-Internal-Secret` for `/health?verbose=true`; verbose
+    /// output is refused when unset.
+    pub internal_secret: Option<String>,
+    provider_checks: ProviderChecks,
+}
+
+type ProviderChecks =
+    Arc<Mutex<HashMap<String, (Arc<Box<dyn BaseProviderAdapter>>, Arc<dyn HealthCheck>)>>>;
+
+pub const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Provider checks are usually billed or rate-limited API calls, so they
+/// are cached even without `with_cache_ttl`.
+pub const DEFAULT_PROVIDER_CACHE_TTL: Duration = Duration::from_secs(60);
+
+impl HealthState {
+    /// Runs one check, reporting `timeout` instead of blocking past `check_timeout`.
+    pub async fn run_check(&self, check: &dyn HealthCheck) -> ComponentHealth {
+        match tokio::time::timeout(self.check_timeout, check.check()).await {
+            Ok(health) => health,
+            Err(_) => {
+                warn!("Health check {} timed out", check.name());
+                ComponentHealth::timeout(self.check_timeout)
+            }
+        }
+    }
+
+    /// Runs every check and provider concurrently, returning the overall
+    /// status.
+    pub async fn evaluate(&self) -> HealthReport {
+        let mut components = HashMap::new();
+        let mut overall_status = HealthStatus::Healthy;
+        let mut critical_failure = None;
+
+        let results = join_all(
+            self.checks
+                .iter()
+                .map(|check| self.run_check(check.as_ref())),
+        )
+        .await;
+        for (check, h) in self.checks.iter().zip(results) {
+            if h.is_failing() {
+                if check.critical() {
+                    overall_status = HealthStatus::Unhealthy;
+                    critical_failure.get_or_insert_with(|| check.name());
+                } else if overall_status == HealthStatus::Healthy {
+                    overall_status = HealthStatus::Degraded;
+                }
+            }
+            components.insert(check.name(), h);
+        }
+
+        if let Some(registry) = &self.providers {
+            let checks = self.provider_checks(registry).await;
+            let results = join_all(checks.iter().map(|check| self.run_check(check.as_ref()))).await;
+            for (check, health) in checks.iter().zip(results) {
+                if health.is_failing() && overall_status == HealthStatus::Healthy {
+                    overall_status = HealthStatus::Degraded;
+                }
+                components.insert(check.name(), health);
+            }
+        }
+
+        HealthReport {
+            status: overall_status,
+            components,
+            critical_failure,
+            checked_at: unix_timestamp(),
+        }
+    }
+
+    /// The monitor's latest report if one is available, otherwise a live evaluation.
+    pub async fn report(&self) -> HealthReport {
+        if let Some(report) = self.latest.as_ref().and_then(|rx| rx.borrow().clone()) {
+            return report;
+        }
+        self.evaluate().await
+    }
+
+    /// The first failing critical check, from the monitor's latest report
+    /// if one is available. Otherwise only the critical checks run, so
+    /// readiness probes never call providers.
+    pub async fn critical_failure(&self) -> Option<String> {
+        if let Some(report) = self.latest.as_ref().and_then(|rx| rx.borrow().clone()) {
+            return report.critical_failure;
+        }
+        let critical: Vec<_> = self.checks.iter().filter(|c| c.critical()).collect();
+        let results = join_all(critical.iter().map(|check| self.run_check(check.as_ref()))).await;
+        critical
+            .iter()
+            .zip(results)
+            .find(|(_, health)| health.is_failing())
+            .map(|(check, _)| check.name())
+    }
+
+    /// A cached `ProviderHealthCheck` per registered adapter, replaced when
+    /// the adapter registered under a name changes.
+    async fn provider_checks(&self, registry: &ProviderRegistry) -> Vec<Arc<dyn HealthCheck>> {
+        let adapters = registry.adapters().await;
+        let mut cache = self
+            .provider_checks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        cache.retain(|name, _| adapters.iter().any(|(n, _)| n == name));
+        adapters
+            .into_iter()
+            .map(|(name, adapter)| {
+                if let Some((cached, check)) = cache.get(&name) {
+                    if Arc::ptr_eq(cached, &adapter) {
+                        return check.clone();
+                    }
+                }
+                let check: Arc<dyn HealthCheck> =
+                    Arc::new(ProviderHealthCheck::new(name.clone(), adapter.clone()));
+                let ttl = self
+                    .provider_ttls
+                    .get(&check.name())
+                    .copied()
+                    .unwrap_or(self.provider_cache_ttl);
+                let check = if ttl.is_zero() {
+                    check
+                } else {
+                    Arc::new(CachedHealthCheck::new(check, ttl)) as Arc<dyn HealthCheck>
+                };
+                cache.insert(name, (adapter, check.clone()));
+                check
+            })
+            .collect()
+    }
+}
+
+/// Assembles a health router from built-in and custom `HealthCheck`s.
+pub struct HealthRouterBuilder {
+    service_name: String,
+    version: String,
+    checks: Vec<Arc<dyn HealthCheck>>,
+    cache_ttl: Option<Duration>,
+    component_ttls: HashMap<String, Duration>,
+    check_timeout: Duration,
+    startup: Option<StartupGate>,
+    providers: Option<Arc<ProviderRegistry>>,
+    internal_secret: Option<String>,
+}
+
+impl HealthRouterBuilder {
+    pub fn new(service_name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            service_name: service_name.into(),
+            version: version.into(),
+            checks: Vec::new(),
+            cache_ttl: None,
+            component_ttls: HashMap::new(),
+            check_timeout: DEFAULT_CHECK_TIMEOUT,
+            startup: None,
+            providers: None,
+            internal_secret: None,
+        }
+    }
+
+    /// Allows `/health?verbose=true` for callers presenting this secret.
+    pub fn with_internal_secret(mut self, secret: impl Into<String>) -> Self {
+        let secret = secret.into();
+        self.internal_secret = (!secret.is_empty()).then_some(secret);
+        self
+    }
+
+    /// Includes each registered provider adapter in `/health`, caching each
+    /// result for `with_cache_ttl`, or `DEFAULT_PROVIDER_CACHE_TTL` without
+    /// one; override with `with_component_cache_ttl("provider:<name>", ..)`.
+    /// Providers never affect readiness.
+    pub fn with_providers(mut self, registry: Arc<ProviderRegistry>) -> Self {
+        self.providers = Some(registry);
+        self
+    }
+
+    /// Serves `/health/startup` from `gate` and holds readiness until it opens.
+    pub fn with_startup_gate(mut self, gate: StartupGate) -> Self {
+        self.startup = Some(gate);
+        self
+    }
+
+    /// Per-component check timeout; defaults to `DEFAULT_CHECK_TIMEOUT`.
+    pub fn with_check_timeout(mut self, timeout: Duration) -> Self {
+        self.check_timeout = timeout;
+        self
+    }
+
+    /// Caches every component's result for `ttl` (e.g. 5s) between probes.
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Overrides the cache TTL for one component; `Duration::ZERO` disables it.
+    pub fn with_component_cache_ttl(mut self, name: impl Into<String>, ttl: Duration) -> Self {
+        self.component_ttls.insert(name.into(), ttl);
+        self
+    }
+
+    pub fn with_database(self, pool: impl Into<DbPool>) -> Self {
+        self.with_check(DatabaseHealthCheck::new(pool))
+    }
+
+    pub fn with_redis(self, client: Client) -> Self {
+        self.with_check(RedisHealthCheck::new(client))
+    }
+
+    /// Adds the non-critical `system` component (memory, fds, runtime load).
+    pub fn with_system_resources(self, thresholds: SystemThresholds) -> Self {
+        self.with_check(SystemHealthCheck::new(thresholds))
+    }
+
+    pub fn with_check(mut self, check: impl HealthCheck + 'static) -> Self {
+        self.checks.push(Arc::new(check));
+        self
+    }
+
+    pub fn with_shared_check(mut self, check: Arc<dyn HealthCheck>) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    pub fn build_state(self) -> HealthState {
+        let provider_ttls = self
+            .component_ttls
+            .iter()
+            .filter(|(name, _)| name.starts_with("provider:"))
+            .map(|(name, ttl)| (name.clone(), *ttl))
+            .collect();
+        let checks = self
+            .checks
+            .into_iter()
+            .map(|check| {
+                let ttl = self
+                    .component_ttls
+                    .get(&check.name())
+                    .copied()
+                    .or(self.cache_ttl);
+                match ttl {
+                    Some(ttl) if !ttl.is_zero() => {
+                        Arc::new(CachedHealthCheck::new(check, ttl)) as Arc<dyn HealthCheck>
+                    }
+                    _ => check,
+                }
+            })
+            .collect();
+        HealthState {
+            service_name: self.service_name,
+            version: self.version,
+            checks,
+            check_timeout: self.check_timeout,
+            startup: self.startup,
+            providers: self.providers,
+            provider_cache_ttl: self.cache_ttl.unwrap_or(DEFAULT_PROVIDER_CACHE_TTL),
+            provider_ttls,
+            latest: None,
+            internal_secret: self.internal_secret,
+            provider_checks: Arc::default(),
+        }
+    }
+
+    pub fn build(self) -> Router {
+        Self::router(Arc::new(self.build_state()))
+    }
+
+    /// Like `build`, but probes serve the report of the returned monitor,
+    /// which must be started with `HealthMonitor::spawn`.
+    pub fn build_with_monitor(self, interval: Duration) -> (Router, Arc<HealthMonitor>) {
+        let (tx, rx) = watch::channel(None);
+        let mut state = self.build_state();
+        state.latest = Some(rx);
+        let state = Arc::new(state);
+        let monitor = Arc::new(HealthMonitor::with_sender(state.clone(), interval, tx));
+        (Self::router(state), monitor)
+    }
+
+    fn router(state: Arc<HealthState>) -> Router {
+        Router::new()
+            .route("/health", get(health_handler))
+            .route("/health/live", get(liveness_probe))
+            .route("/health/ready", get(readiness_probe))
+            .route("/health/startup", get(startup_probe))
+            .with_state(state)
+    }
+}
+
+pub fn create_health_router(
+    service_name: String,
+    version: String,
+    db_pool: Option<PgPool>,
+    redis_client: Option<Client>,
+) -> Router {
+    let mut builder = HealthRouterBuilder::new(service_name, version);
+    if let Some(pool) = db_pool {
+        builder = builder.with_database(pool);
+    }
+    if let Some(client) = redis_client {
+        builder = builder.with_redis(client);
+    }
+    builder.build()
+}
+
+fn unix_timestamp() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+#[derive(Debug, Deserialize)]
+struct HealthQuery {
+    #[serde(default)]
+    verbose: bool,
+}
+
+async fn health_handler(
+    State(state): State<Arc<HealthState>>,
+    Query(query): Query<HealthQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if query.verbose {
+        let provided = headers
+            .get("X-Internal-Secret")
+            .map(|h| h.as_bytes())
+            .unwrap_or_default();
+        let authorized = state
+            .internal_secret
+            .as_ref()
+            .is_some_and(|secret| secret_matches(provided, secret.as_bytes()));
+        if !authorized {
+            warn!("Rejected verbose health request without a valid internal secret");
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({"error": "Unauthorized", "detail": "Invalid internal secret"})),
+            )
+                .into_response();
+        }
+    }
+
+    let report = state.report().await;
+    let components = if query.verbose {
+        report.components
+    } else {
+        report
+            .components
+            .iter()
+            .map(|(name, health)| (name.clone(), health.redacted()))
+            .collect()
+    };
+
+    Json(HealthResponse {
+        status: report.status,
+        service: state.service_name.clone(),
+        version: state.version.clone(),
+        components,
+        timestamp: report.checked_at,
+    })
+    .into_response()
+}
+
+async fn liveness_probe() -> Json<serde_json::Value> {
+    Json(serde_json::json!({"status": "alive"}))
+}
+
+async fn startup_probe(State(state): State<Arc<HealthState>>) -> Response {
+    match &state.startup {
+        Some(gate) if !gate.is_started() => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "status": "starting",
+                "pending": gate.pending(),
+            })),
+        )
+            .into_response(),
+        _ => (
+            StatusCode::OK,
+            Json(serde_json::json!({"status": "started"})),
+        )
+            .into_response(),
+    }
+}
+
+async fn readiness_probe(State(state): State<Arc<HealthState>>) -> Response {
+    if state.startup.as_ref().is_some_and(|g| !g.is_started()) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({"status": "not_ready", "reason": "starting"})),
+        )
+            .into_response();
+    }
+    if let Some(component) = state.critical_failure().await {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "status": "not_ready",
+                "reason": format!("{}_unavailable", component),
+            })),
+        )
+            .into_response();
+    }
+    (StatusCode::OK, Json(serde_json::json!({"status": "ready"}))).into_response()
+}
@@ -0,0 +1,144 @@
+use super::{ComponentHealth, HealthState, HealthStatus};
+use crate::metrics::GLOBAL_METRICS;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Result of one full evaluation of every registered component.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub components: HashMap<String, ComponentHealth>,
+    /// First failing critical check, which fails readiness.
+    pub critical_failure: Option<String>,
+    pub checked_at: f64,
+}
+
+/// Runs the health checks on an interval and publishes the latest report, so
+/// probes read a cached result instead of checking inline.
+pub struct HealthMonitor {
+    state: Arc<HealthState>,
+    interval: Duration,
+    tx: watch::Sender<Option<HealthReport>>,
+}
+
+impl HealthMonitor {
+    pub fn new(state: Arc<HealthState>, interval: Duration) -> Self {
+        let (tx, _) = watch::channel(None);
+        Self::with_sender(state, interval, tx)
+    }
+
+    pub(crate) fn with_sender(
+        state: Arc<HealthState>,
+        interval: Duration,
+        tx: watch::Sender<Option<HealthReport>>,
+    ) -> Self {
+        Self {
+            state,
+            interval,
+            tx,
+        }
+    }
+
+    /// `None` until the first evaluation completes.
+    pub fn subscribe(&self) -> watch::Receiver<Option<HealthReport>> {
+        self.tx.subscribe()
+    }
+
+    pub fn latest(&self) -> Option<HealthReport> {
+        self.tx.borrow().clone()
+    }
+
+    /// Evaluates every check now and publishes the result.
+    pub async fn refresh(&self) -> HealthReport {
+        let report = self.state.evaluate().await;
+        let previous = self.tx.borrow().as_ref().map(|r| r.status.clone());
+        self.record(previous, &report);
+        self.tx.send_replace(Some(report.clone()));
+        report
+    }
+
+    fn record(&self, previous: Option<HealthStatus>, report: &HealthReport) {
+        let service = &self.state.service_name;
+        let labels = HashMap::from([("service".to_string(), service.clone())]);
+        GLOBAL_METRICS.set_gauge("health_status", status_value(&report.status), Some(labels));
+
+        let Some(previous) = previous else {
+            return;
+        };
+        if previous == report.status {
+            return;
+        }
+        let from = status_name(&previous);
+        let to = status_name(&report.status);
+        GLOBAL_METRICS.increment(
+            "health_status_transitions",
+            1,
+            Some(HashMap::from([
+                ("service".to_string(), service.clone()),
+                ("from".to_string(), from.to_string()),
+                ("to".to_string(), to.to_string()),
+            ])),
+        );
+        if report.status == HealthStatus::Healthy {
+            info!(
+                event = "health_status_changed",
+                service = %service,
+                from,
+                to,
+                "Service health recovered"
+            );
+        } else {
+            warn!(
+                event = "health_status_changed",
+                service = %service,
+                from,
+                to,
+                failing = ?failing_components(report),
+                "Service health changed"
+            );
+        }
+    }
+
+    /// Refreshes on the configured interval until the task is aborted.
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                self.refresh().await;
+            }
+        })
+    }
+}
+
+pub(crate) fn status_name(status: &HealthStatus) -> &'static str {
+    match status {
+        HealthStatus::Healthy => "healthy",
+        HealthStatus::Degraded => "degraded",
+        HealthStatus::Unhealthy => "unhealthy",
+    }
+}
+
+fn status_value(status: &HealthStatus) -> f64 {
+    match status {
+        HealthStatus::Healthy => 0.0,
+        HealthStatus::Degraded => 1.0,
+        HealthStatus::Unhealthy => 2.0,
+    }
+}
+
+fn failing_components(report: &HealthReport) -> Vec<&str> {
+    let mut failing: Vec<&str> = report
+        .components
+        .iter()
+        .filter(|(_, h)| h.is_failing())
+        .map(|(name, _)| name.as_str())
+        .collect();
+    failing.sort();
+    failing
+}
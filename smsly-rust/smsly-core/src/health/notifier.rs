@@ -0,0 +1,187 @@
+use super::monitor::{status_name, HealthReport};
+use super::HealthStatus;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// Payload shape for the alert webhook.
+#[derive(Debug, Clone)]
+pub enum WebhookFormat {
+    /// Slack incoming webhook (`{"text": ...}`).
+    Slack,
+    /// PagerDuty Events API v2; recoveries resolve the incident.
+    PagerDuty { routing_key: String },
+    /// Plain JSON with service, previous and current status.
+    Generic,
+}
+
+/// Posts a webhook when the overall status reported by a `HealthMonitor`
+/// changes. A new status must hold for `debounce` before it is announced, so
+/// a check that flaps within the window does not page anyone.
+pub struct HealthNotifier {
+    client: Client,
+    url: String,
+    format: WebhookFormat,
+    debounce: Duration,
+}
+
+impl HealthNotifier {
+    pub fn new(url: &str, format: WebhookFormat, debounce: Duration) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            url: url.to_string(),
+            format,
+            debounce,
+        }
+    }
+
+    /// Watches `reports` (from `HealthMonitor::subscribe`) until the monitor is dropped.
+    pub fn spawn(
+        self,
+        service: &str,
+        mut reports: watch::Receiver<Option<HealthReport>>,
+    ) -> JoinHandle<()> {
+        let service = service.to_string();
+        tokio::spawn(async move {
+            let mut announced = current_status(&reports);
+            while reports.changed().await.is_ok() {
+                let Some(mut pending) = current_status(&reports) else {
+                    continue;
+                };
+                let Some(from) = announced.clone() else {
+                    // First report establishes the baseline.
+                    announced = Some(pending);
+                    continue;
+                };
+                if pending == from {
+                    continue;
+                }
+
+                let mut deadline = Instant::now() + self.debounce;
+                loop {
+                    match tokio::time::timeout_at(deadline, reports.changed()).await {
+                        Ok(Ok(())) => {
+                            let Some(status) = current_status(&reports) else {
+                                continue;
+                            };
+                            if status == from {
+                                break;
+                            }
+                            if status != pending {
+                                pending = status;
+                                deadline = Instant::now() + self.debounce;
+                            }
+                        }
+                        Ok(Err(_)) => return,
+                        Err(_) => {
+                            let report = reports.borrow().clone();
+                            self.notify(&service, &from, &pending, report.as_ref())
+                                .await;
+                            announced = Some(pending);
+                            break;
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    async fn notify(
+        &self,
+        service: &str,
+        from: &HealthStatus,
+        to: &HealthStatus,
+        report: Option<&HealthReport>,
+    ) {
+        let payload = self.payload(service, from, to, report);
+        match self.client.post(&self.url).json(&payload).send().await {
+            Ok(response) if response.status().is_success() => {
+                info!(
+                    "Health webhook sent: {} {} -> {}",
+                    service,
+                    status_name(from),
+                    status_name(to)
+                );
+            }
+            Ok(response) => warn!("Health webhook rejected: HTTP {}", response.status()),
+            Err(e) => warn!("Health webhook failed: {}", e),
+        }
+    }
+
+    fn payload(
+        &self,
+        service: &str,
+        from: &HealthStatus,
+        to: &HealthStatus,
+        report: Option<&HealthReport>,
+    ) -> Value {
+        let failing: Vec<&str> = report
+            .map(|r| {
+                let mut names: Vec<&str> = r
+                    .components
+                    .iter()
+                    .filter(|(_, h)| h.is_failing())
+                    .map(|(name, _)| name.as_str())
+                    .collect();
+                names.sort();
+                names
+            })
+            .unwrap_or_default();
+        let summary = if failing.is_empty() {
+            format!(
+                "{} is {} (was {})",
+                service,
+                status_name(to),
+                status_name(from)
+            )
+        } else {
+            format!(
+                "{} is {} (was {}); failing: {}",
+                service,
+                status_name(to),
+                status_name(from),
+                failing.join(", ")
+            )
+        };
+
+        match &self.format {
+            WebhookFormat::Slack => json!({ "text": summary }),
+            WebhookFormat::PagerDuty { routing_key } => {
+                let (action, severity) = match to {
+                    HealthStatus::Healthy => ("resolve", "info"),
+                    HealthStatus::Degraded => ("trigger", "warning"),
+                    HealthStatus::Unhealthy => ("trigger", "critical"),
+                };
+                json!({
+                    "routing_key": routing_key,
+                    "event_action": action,
+                    "dedup_key": format!("{}-health", service),
+                    "payload": {
+                        "summary": summary,
+                        "source": service,
+                        "severity": severity,
+                        "custom_details": { "failing_components": failing },
+                    },
+                })
+            }
+            WebhookFormat::Generic => json!({
+                "service": service,
+                "previous_status": status_name(from),
+                "status": status_name(to),
+                "failing_components": failing,
+                "summary": summary,
+            }),
+        }
+    }
+}
+
+fn current_status(reports: &watch::Receiver<Option<HealthReport>>) -> Option<HealthStatus> {
+    reports.borrow().as_ref().map(|r| r.status.clone())
+}
@@ -0,0 +1,51 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, RwLock};
+use tracing::info;
+
+/// Tracks startup work (migrations, cache warmup, provider initialization).
+/// `/health/startup` and `/health/ready` fail until every step is complete.
+#[derive(Clone, Default)]
+pub struct StartupGate {
+    pending: Arc<RwLock<BTreeSet<String>>>,
+}
+
+impl StartupGate {
+    /// A gate waiting on the named steps; with no steps it is open immediately.
+    pub fn new<I, S>(steps: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            pending: Arc::new(RwLock::new(steps.into_iter().map(Into::into).collect())),
+        }
+    }
+
+    /// Adds a step that must complete before the service reports started.
+    pub fn register(&self, step: impl Into<String>) {
+        self.pending.write().unwrap().insert(step.into());
+    }
+
+    pub fn complete(&self, step: &str) {
+        let mut pending = self.pending.write().unwrap();
+        if pending.remove(step) {
+            info!("Startup step complete: {}", step);
+            if pending.is_empty() {
+                info!("Startup complete");
+            }
+        }
+    }
+
+    /// Opens the gate regardless of outstanding steps.
+    pub fn mark_started(&self) {
+        self.pending.write().unwrap().clear();
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.pending.read().unwrap().is_empty()
+    }
+
+    pub fn pending(&self) -> Vec<String> {
+        self.pending.read().unwrap().iter().cloned().collect()
+    }
+}
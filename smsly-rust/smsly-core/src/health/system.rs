@@ -0,0 +1,176 @@
+use super::{ComponentHealth, HealthCheck};
+use async_trait::async_trait;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// Thresholds above which the `system` component reports `degraded`.
+#[derive(Debug, Clone)]
+pub struct SystemThresholds {
+    /// Resident memory as a percentage of the cgroup limit (or host memory).
+    pub memory_percent: f64,
+    /// Open file descriptors as a percentage of the soft `RLIMIT_NOFILE`.
+    pub fd_percent: f64,
+    /// Time for a freshly spawned task to start running on the runtime.
+    pub scheduling_delay: Duration,
+}
+
+impl Default for SystemThresholds {
+    fn default() -> Self {
+        Self {
+            memory_percent: 90.0,
+            fd_percent: 80.0,
+            scheduling_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Reports process memory, open file descriptors and tokio runtime
+/// saturation. Never critical: it degrades the service rather than failing
+/// readiness. Memory and descriptor figures are read from `/proc` and are
+/// omitted on other platforms.
+#[derive(Default)]
+pub struct SystemHealthCheck {
+    thresholds: SystemThresholds,
+}
+
+impl SystemHealthCheck {
+    pub fn new(thresholds: SystemThresholds) -> Self {
+        Self { thresholds }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for SystemHealthCheck {
+    fn name(&self) -> String {
+        "system".to_string()
+    }
+
+    fn critical(&self) -> bool {
+        false
+    }
+
+    async fn check(&self) -> ComponentHealth {
+        let mut details = serde_json::Map::new();
+        let mut problems = Vec::new();
+
+        if let Some(rss) = resident_memory_bytes() {
+            details.insert("memory_rss_bytes".into(), rss.into());
+            if let Some(limit) = memory_limit_bytes() {
+                let percent = rss as f64 / limit as f64 * 100.0;
+                details.insert("memory_limit_bytes".into(), limit.into());
+                details.insert("memory_percent".into(), round2(percent).into());
+                if percent > self.thresholds.memory_percent {
+                    problems.push(format!("memory at {:.1}% of limit", percent));
+                }
+            }
+        }
+
+        if let Some(open) = open_fds() {
+            details.insert("open_fds".into(), open.into());
+            if let Some(limit) = fd_limit() {
+                let percent = open as f64 / limit as f64 * 100.0;
+                details.insert("fd_limit".into(), limit.into());
+                if percent > self.thresholds.fd_percent {
+                    problems.push(format!("{} of {} file descriptors open", open, limit));
+                }
+            }
+        }
+
+        let runtime = tokio::runtime::Handle::current().metrics();
+        details.insert("runtime_workers".into(), runtime.num_workers().into());
+        details.insert(
+            "runtime_alive_tasks".into(),
+            runtime.num_alive_tasks().into(),
+        );
+        details.insert(
+            "runtime_global_queue_depth".into(),
+            runtime.global_queue_depth().into(),
+        );
+        let delay = scheduling_delay().await;
+        details.insert(
+            "scheduling_delay_ms".into(),
+            round2(delay.as_secs_f64() * 1000.0).into(),
+        );
+        if delay > self.thresholds.scheduling_delay {
+            problems.push(format!("runtime scheduling delay {}ms", delay.as_millis()));
+        }
+
+        ComponentHealth {
+            status: if problems.is_empty() {
+                "ok"
+            } else {
+                "degraded"
+            }
+            .to_string(),
+            latency_ms: None,
+            error: (!problems.is_empty()).then(|| problems.join("; ")),
+            details: Some(details.into()),
+        }
+    }
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// How long a newly spawned task waits before it is polled.
+async fn scheduling_delay() -> Duration {
+    let spawned = Instant::now();
+    tokio::spawn(async move { spawned.elapsed() })
+        .await
+        .unwrap_or_default()
+}
+
+fn resident_memory_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))?
+        .split_whitespace()
+        .next()?
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// cgroup v2, then cgroup v1, then total host memory.
+fn memory_limit_bytes() -> Option<u64> {
+    for path in [
+        "/sys/fs/cgroup/memory.max",
+        "/sys/fs/cgroup/memory/memory.limit_in_bytes",
+    ] {
+        if let Some(limit) = fs::read_to_string(path)
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+        {
+            // cgroup v1 reports "unlimited" as a value near u64::MAX.
+            if limit < (1 << 60) {
+                return Some(limit);
+            }
+        }
+    }
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let kb = meminfo
+        .lines()
+        .find_map(|l| l.strip_prefix("MemTotal:"))?
+        .split_whitespace()
+        .next()?
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+fn open_fds() -> Option<usize> {
+    fs::read_dir("/proc/self/fd").ok().map(|d| d.count())
+}
+
+fn fd_limit() -> Option<u64> {
+    let limits = fs::read_to_string("/proc/self/limits").ok()?;
+    limits
+        .lines()
+        .find_map(|l| l.strip_prefix("Max open files"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
@@ -0,0 +1,427 @@
+//! Idempotency keys for send requests.
+//!
+//! Clients retry sends after timeouts without knowing whether the first
+//! attempt went out. With an `Idempotency-Key`, the first request for an
+//! (account, key) pair runs and its result is kept for a day; retries get
+//! that result back instead of sending again. A retry arriving while the
+//! first is still running is refused, as is the same key on a different
+//! request. `IdempotencyStore::execute` does this around any operation and
+//! `idempotency_middleware` around whole axum handlers.
+
+use crate::metrics::GLOBAL_METRICS;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use lazy_static::lazy_static;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult, Script};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+/// Set on responses replayed from a cached result.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+/// Keys longer than this are rejected rather than stored.
+pub const MAX_KEY_LEN: usize = 255;
+
+lazy_static! {
+    /// Returns the existing record, or claims the key with ARGV[1] for
+    /// ARGV[2] ms and returns nil.
+    static ref BEGIN: Script = Script::new(
+        r#"
+        local existing = redis.call("GET", KEYS[1])
+        if existing then
+            return existing
+        end
+        redis.call("SET", KEYS[1], ARGV[1], "PX", ARGV[2])
+        return false
+    "#
+    );
+}
+
+#[derive(Error, Debug)]
+pub enum IdempotencyError {
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    #[error("A request with this idempotency key is still in progress")]
+    InProgress,
+    #[error("Idempotency key was already used for a different request")]
+    Mismatch,
+    #[error("Invalid idempotency key: {0}")]
+    InvalidKey(String),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyConfig {
+    pub key_prefix: String,
+    /// How long completed results are replayed.
+    pub ttl: Duration,
+    /// How long a claimed key blocks retries if its request never
+    /// completes, e.g. the process died mid-send.
+    pub lock_ttl: Duration,
+    /// Largest request body the middleware buffers to fingerprint.
+    pub max_body_bytes: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            key_prefix: "smsly:idempotency".to_string(),
+            ttl: Duration::from_secs(24 * 3600),
+            lock_ttl: Duration::from_secs(60),
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl IdempotencyConfig {
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_lock_ttl(mut self, ttl: Duration) -> Self {
+        self.lock_ttl = ttl;
+        self
+    }
+
+    pub fn with_max_body_bytes(mut self, max: usize) -> Self {
+        self.max_body_bytes = max;
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    fingerprint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+}
+
+/// Outcome of claiming a key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Claim {
+    /// First use; run the request, then `complete` or `release`.
+    Started,
+    /// Already completed with this result.
+    Completed(Value),
+}
+
+/// The account whose keys a request uses, inserted into request extensions
+/// by the authentication layer ahead of `idempotency_middleware`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountId(pub String);
+
+pub struct IdempotencyStore {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    config: IdempotencyConfig,
+}
+
+impl IdempotencyStore {
+    pub fn new(client: Client, config: IdempotencyConfig) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            config,
+        }
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    fn key(&self, account_id: &str, key: &str) -> String {
+        format!("{}:{}:{}", self.config.key_prefix, account_id, key)
+    }
+
+    /// Claims `key` for `account_id`. `fingerprint` identifies the request,
+    /// e.g. a hash of its body, so a key reused for another request fails
+    /// with `Mismatch` instead of replaying the wrong result.
+    pub async fn begin(
+        &self,
+        account_id: &str,
+        key: &str,
+        fingerprint: &str,
+    ) -> Result<Claim, IdempotencyError> {
+        validate_key(key)?;
+        let record = serde_json::to_string(&Record {
+            fingerprint: fingerprint.to_string(),
+            result: None,
+        })?;
+        let mut conn = self.conn().await?;
+        let existing: Option<String> = BEGIN
+            .key(self.key(account_id, key))
+            .arg(record)
+            .arg(self.config.lock_ttl.as_millis() as u64)
+            .invoke_async(&mut conn)
+            .await?;
+        let Some(existing) = existing else {
+            return Ok(Claim::Started);
+        };
+        let existing: Record = serde_json::from_str(&existing)?;
+        if existing.fingerprint != fingerprint {
+            return Err(IdempotencyError::Mismatch);
+        }
+        match existing.result {
+            Some(result) => Ok(Claim::Completed(result)),
+            None => Err(IdempotencyError::InProgress),
+        }
+    }
+
+    /// Stores the result of a request started with `begin`.
+    pub async fn complete(
+        &self,
+        account_id: &str,
+        key: &str,
+        fingerprint: &str,
+        result: &Value,
+    ) -> Result<(), IdempotencyError> {
+        let record = serde_json::to_string(&Record {
+            fingerprint: fingerprint.to_string(),
+            result: Some(result.clone()),
+        })?;
+        let mut conn = self.conn().await?;
+        redis::cmd("SET")
+            .arg(self.key(account_id, key))
+            .arg(record)
+            .arg("PX")
+            .arg(self.config.ttl.as_millis() as u64)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Frees a key whose request failed so a retry can run it again.
+    pub async fn release(&self, account_id: &str, key: &str) -> Result<(), IdempotencyError> {
+        let mut conn = self.conn().await?;
+        redis::cmd("DEL")
+            .arg(self.key(account_id, key))
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Runs `operation` once per (account, key). Successful results are
+    /// stored and returned to later calls without running it again; on
+    /// error the key is released so the caller may retry. The outer error
+    /// is the idempotency layer's, the inner one the operation's.
+    pub async fn execute<T, E, F, Fut>(
+        &self,
+        account_id: &str,
+        key: &str,
+        fingerprint: &str,
+        operation: F,
+    ) -> Result<Result<T, E>, IdempotencyError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Claim::Completed(result) = self.begin(account_id, key, fingerprint).await? {
+            record_replay();
+            return Ok(Ok(serde_json::from_value(result)?));
+        }
+        match operation().await {
+            Ok(value) => {
+                let result = serde_json::to_value(&value)?;
+                if let Err(e) = self.complete(account_id, key, fingerprint, &result).await {
+                    // Already sent; a retry is held off until the lock expires.
+                    warn!("Storing idempotent result for {} failed: {}", key, e);
+                }
+                Ok(Ok(value))
+            }
+            Err(e) => {
+                if let Err(release_error) = self.release(account_id, key).await {
+                    warn!(
+                        "Releasing idempotency key {} failed: {}",
+                        key, release_error
+                    );
+                }
+                Ok(Err(e))
+            }
+        }
+    }
+}
+
+fn validate_key(key: &str) -> Result<(), IdempotencyError> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(IdempotencyError::InvalidKey(format!(
+            "must be 1-{} characters",
+            MAX_KEY_LEN
+        )));
+    }
+    if !key.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(IdempotencyError::InvalidKey(
+            "must be printable ASCII".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// SHA-256 over method, path and body, so a key is tied to one request.
+pub fn request_fingerprint(method: &str, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update(b" ");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+fn record_replay() {
+    GLOBAL_METRICS.increment("idempotent_replays", 1, None);
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// Makes requests carrying an `Idempotency-Key` header idempotent per
+/// `AccountId`; requests without one pass straight through. Responses
+/// other than 5xx are stored and replayed with `Idempotent-Replayed: true`;
+/// 5xx responses release the key so the client can retry.
+///
+/// Fails closed: if Redis is unreachable the request is refused with 503
+/// rather than risking a double send. Install on the send routes with
+/// `axum::middleware::from_fn_with_state(store, idempotency_middleware)`
+/// inside the layer that inserts `AccountId`.
+pub async fn idempotency_middleware(
+    State(store): State<Arc<IdempotencyStore>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string)
+    else {
+        return next.run(request).await;
+    };
+    let Some(AccountId(account_id)) = request.extensions().get::<AccountId>().cloned() else {
+        warn!("Idempotency-Key sent on a request without an AccountId");
+        return error_response(StatusCode::UNAUTHORIZED, "Unauthenticated");
+    };
+
+    let (parts, body) = request.into_parts();
+    let Ok(body) = to_bytes(body, store.config.max_body_bytes).await else {
+        return error_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large");
+    };
+    let fingerprint = request_fingerprint(parts.method.as_str(), parts.uri.path(), &body);
+
+    match store.begin(&account_id, &key, &fingerprint).await {
+        Ok(Claim::Started) => {}
+        Ok(Claim::Completed(result)) => {
+            record_replay();
+            return replay(result);
+        }
+        Err(e @ IdempotencyError::InvalidKey(_)) => {
+            return error_response(StatusCode::BAD_REQUEST, &e.to_string())
+        }
+        Err(e @ IdempotencyError::InProgress) => {
+            return error_response(StatusCode::CONFLICT, &e.to_string())
+        }
+        Err(e @ IdempotencyError::Mismatch) => {
+            return error_response(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string())
+        }
+        Err(e) => {
+            warn!("Idempotency check failed, refusing request: {}", e);
+            return error_response(StatusCode::SERVICE_UNAVAILABLE, "Service unavailable");
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if response.status().is_server_error() {
+        if let Err(e) = store.release(&account_id, &key).await {
+            warn!("Releasing idempotency key {} failed: {}", key, e);
+        }
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Reading response for idempotency key {} failed: {}", key, e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error");
+        }
+    };
+    let cached = CachedResponse {
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .get(axum::http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| HashMap::from([("content-type".to_string(), v.to_string())]))
+            .unwrap_or_default(),
+        body: BASE64.encode(&body),
+    };
+    match serde_json::to_value(&cached) {
+        Ok(result) => {
+            if let Err(e) = store
+                .complete(&account_id, &key, &fingerprint, &result)
+                .await
+            {
+                warn!("Storing idempotent response for {} failed: {}", key, e);
+            }
+        }
+        Err(e) => warn!("Encoding idempotent response for {} failed: {}", key, e),
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn replay(result: Value) -> Response {
+    let Ok(cached) = serde_json::from_value::<CachedResponse>(result) else {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error");
+    };
+    let body = BASE64.decode(cached.body).unwrap_or_default();
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = StatusCode::from_u16(cached.status).unwrap_or(StatusCode::OK);
+    for (name, value) in cached.headers {
+        if let (Ok(name), Ok(value)) = (
+            axum::http::HeaderName::try_from(name),
+            HeaderValue::try_from(value),
+        ) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+        .headers_mut()
+        .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
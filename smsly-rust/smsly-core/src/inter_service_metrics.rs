@@ -0,0 +1,166 @@
+use crate::metrics::GLOBAL_METRICS;
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::{Client, Method, RequestBuilder, Response};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub struct InterServiceMetricNames;
+
+impl InterServiceMetricNames {
+    pub const REQUESTS_TOTAL: &'static str = "inter_service_requests";
+    pub const REQUEST_DURATION: &'static str = "inter_service_request_duration_seconds";
+    pub const TARGET_REQUESTS_TOTAL: &'static str = "inter_service_target_requests";
+    pub const TARGET_ERRORS_TOTAL: &'static str = "inter_service_target_errors";
+    pub const TARGET_ERROR_RATE: &'static str = "inter_service_error_rate";
+}
+
+lazy_static! {
+    static ref UUID_RE: Regex =
+        Regex::new(r"(?i)[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}").unwrap();
+}
+
+/// Replaces UUIDs and numeric path segments with placeholders to keep label
+/// cardinality bounded.
+pub fn normalize_endpoint(endpoint: &str) -> String {
+    UUID_RE
+        .replace_all(endpoint, "{uuid}")
+        .split('/')
+        .map(|segment| {
+            if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+                "{id}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Maps an HTTP status code to the call status label used by `record_call`.
+pub fn status_from_code(code: u16) -> &'static str {
+    match code {
+        500.. => "server_error",
+        400..=499 => "client_error",
+        _ => "success",
+    }
+}
+
+/// Records one call to another service. `status` is `success`, `client_error`,
+/// `server_error`, `timeout`, `circuit_open` or `exception`; everything except
+/// `success` and `client_error` counts toward the target's error rate.
+pub fn record_call(target: &str, operation: &str, status: &str, duration: Duration) {
+    let labels = HashMap::from([
+        ("target".to_string(), target.to_string()),
+        ("operation".to_string(), operation.to_string()),
+        ("status".to_string(), status.to_string()),
+    ]);
+    GLOBAL_METRICS.increment(
+        InterServiceMetricNames::REQUESTS_TOTAL,
+        1,
+        Some(labels.clone()),
+    );
+    GLOBAL_METRICS.observe(
+        InterServiceMetricNames::REQUEST_DURATION,
+        duration.as_secs_f64(),
+        Some(labels),
+    );
+
+    let target_labels = Some(HashMap::from([("target".to_string(), target.to_string())]));
+    let requests = GLOBAL_METRICS.counter(
+        InterServiceMetricNames::TARGET_REQUESTS_TOTAL,
+        target_labels.clone(),
+    );
+    let errors = GLOBAL_METRICS.counter(
+        InterServiceMetricNames::TARGET_ERRORS_TOTAL,
+        target_labels.clone(),
+    );
+    requests.inc();
+    if !matches!(status, "success" | "client_error") {
+        errors.inc();
+    }
+    GLOBAL_METRICS.set_gauge(
+        InterServiceMetricNames::TARGET_ERROR_RATE,
+        errors.get() as f64 / requests.get().max(1) as f64,
+        target_labels,
+    );
+}
+
+/// Fraction of failed calls to `target` since startup (or the last reset).
+pub fn error_rate(target: &str) -> f64 {
+    let labels = Some(HashMap::from([("target".to_string(), target.to_string())]));
+    let requests = GLOBAL_METRICS
+        .counter(
+            InterServiceMetricNames::TARGET_REQUESTS_TOTAL,
+            labels.clone(),
+        )
+        .get();
+    let errors = GLOBAL_METRICS
+        .counter(InterServiceMetricNames::TARGET_ERRORS_TOTAL, labels)
+        .get();
+    if requests == 0 {
+        0.0
+    } else {
+        errors as f64 / requests as f64
+    }
+}
+
+/// `reqwest` wrapper bound to one downstream service that records every call
+/// through `record_call`.
+#[derive(Clone)]
+pub struct InstrumentedClient {
+    client: Client,
+    base_url: String,
+    service_name: String,
+}
+
+impl InstrumentedClient {
+    pub fn new(base_url: &str, service_name: &str, timeout: Duration) -> reqwest::Result<Self> {
+        Ok(Self {
+            client: Client::builder().timeout(timeout).build()?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            service_name: service_name.to_string(),
+        })
+    }
+
+    pub fn service_name(&self) -> &str {
+        &self.service_name
+    }
+
+    /// Sends `method path`, letting `build` add headers, query or body.
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        build: impl FnOnce(RequestBuilder) -> RequestBuilder,
+    ) -> reqwest::Result<Response> {
+        let operation = format!("{} {}", method, normalize_endpoint(path));
+        let url = format!("{}{}", self.base_url, path);
+        let start = Instant::now();
+
+        let result = build(self.client.request(method, url)).send().await;
+        let status = match &result {
+            Ok(response) => status_from_code(response.status().as_u16()),
+            Err(e) if e.is_timeout() => "timeout",
+            Err(_) => "exception",
+        };
+        record_call(&self.service_name, &operation, status, start.elapsed());
+        result
+    }
+
+    pub async fn get(&self, path: &str) -> reqwest::Result<Response> {
+        self.request(Method::GET, path, |r| r).await
+    }
+
+    pub async fn post_json<T: serde::Serialize + ?Sized>(
+        &self,
+        path: &str,
+        body: &T,
+    ) -> reqwest::Result<Response> {
+        self.request(Method::POST, path, |r| r.json(body)).await
+    }
+
+    pub async fn delete(&self, path: &str) -> reqwest::Result<Response> {
+        self.request(Method::DELETE, path, |r| r).await
+    }
+}
@@ -1,27 +1,40 @@
 pub mod adapters;
+pub mod archive;
+pub mod blocklist;
+pub mod callbacks;
+pub mod circuit_breaker;
 pub mod database;
+pub mod dlr;
 pub mod health;
+pub mod idempotency;
+pub mod inter_service_metrics;
+pub mod links;
+pub mod messaging;
 pub mod metrics;
+pub mod middleware;
+pub mod opt_out;
+pub mod providers;
+pub mod retry;
+pub mod segments;
+pub mod sender_id;
+pub mod shutdown;
+pub mod templates;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+pub mod whatsapp;
 
 // Placeholders for other modules
 pub mod admin_client {}
 pub mod api_keys {}
 pub mod audit {}
 pub mod auth_middleware {}
-pub mod circuit_breaker {}
 pub mod direct_access {}
 pub mod http {}
-pub mod inter_service_metrics {}
 pub mod internal_auth {}
-pub mod messaging {}
-pub mod middleware {}
 pub mod otp {}
 pub mod password {}
-pub mod providers {}
 pub mod rate_limit {}
-pub mod retry {}
 pub mod security_headers {}
 pub mod stalker_audit {}
 pub mod trust_engine {}
 pub mod vault {}
-pub mod whatsapp {}
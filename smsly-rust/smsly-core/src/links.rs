@@ -0,0 +1,435 @@
+//! Branded short links with click tracking.
+//!
+//! `LinkShortener::shorten_body` replaces each URL in an outbound body with
+//! a link on our domain, e.g. `https://smsly.link/aB3xK9q`, which also
+//! saves characters. Each link remembers the message and campaign it was
+//! sent in; `create_link_router` serves the redirects and records every
+//! visit, publishing a `LinkClick` and counting it towards the campaign.
+//! Visits from link-preview bots (which fetch a URL as soon as a message
+//! arrives on some devices) are recorded as previews, not clicks.
+
+use crate::messaging::{LinkClick, MessageQueue, LINK_CLICKS};
+use crate::metrics::GLOBAL_METRICS;
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult};
+use regex::Regex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use tracing::{error, warn};
+
+/// User agents of link-preview fetchers, matched case-insensitively.
+const PREVIEW_AGENTS: &[&str] = &[
+    "facebookexternalhit",
+    "whatsapp",
+    "slackbot",
+    "telegrambot",
+    "twitterbot",
+    "discordbot",
+    "linkedinbot",
+    "skypeuripreview",
+    "applebot",
+];
+const MAX_CODE_ATTEMPTS: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum LinkError {
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+    #[error("Could not allocate a unique short code")]
+    CodeExhausted,
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone)]
+pub struct LinkConfig {
+    /// Scheme and host of the short links, e.g. `https://smsly.link`.
+    pub base_url: String,
+    pub key_prefix: String,
+    /// How long links keep redirecting.
+    pub ttl: Duration,
+    pub code_length: usize,
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://smsly.link".to_string(),
+            key_prefix: "smsly:links".to_string(),
+            ttl: Duration::from_secs(90 * 86400),
+            code_length: 7,
+        }
+    }
+}
+
+impl LinkConfig {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_code_length(mut self, length: usize) -> Self {
+        self.code_length = length;
+        self
+    }
+}
+
+/// Who a link was sent to, for attributing its clicks.
+#[derive(Debug, Clone, Default)]
+pub struct LinkContext {
+    pub account_id: String,
+    pub message_id: Option<String>,
+    pub campaign_id: Option<String>,
+}
+
+impl LinkContext {
+    pub fn new(account_id: &str) -> Self {
+        Self {
+            account_id: account_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_message_id(mut self, message_id: &str) -> Self {
+        self.message_id = Some(message_id.to_string());
+        self
+    }
+
+    pub fn with_campaign_id(mut self, campaign_id: &str) -> Self {
+        self.campaign_id = Some(campaign_id.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShortLink {
+    pub code: String,
+    pub url: String,
+    pub account_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub campaign_id: Option<String>,
+    pub created_at: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShortenedBody {
+    pub body: String,
+    pub links: Vec<ShortLink>,
+}
+
+/// Click counts for a campaign. `unique_links` counts links clicked at
+/// least once, roughly the recipients who clicked (estimated with a
+/// HyperLogLog).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ClickStats {
+    pub links: u64,
+    pub clicks: u64,
+    pub unique_links: u64,
+    pub previews: u64,
+}
+
+fn now() -> f64 {
+    Utc::now().timestamp_millis() as f64 / 1000.0
+}
+
+fn is_preview_agent(user_agent: &str) -> bool {
+    let agent = user_agent.to_ascii_lowercase();
+    PREVIEW_AGENTS.iter().any(|bot| agent.contains(bot))
+}
+
+pub struct LinkShortener {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    queue: Option<Arc<dyn MessageQueue>>,
+    config: LinkConfig,
+    url_pattern: Regex,
+}
+
+impl LinkShortener {
+    pub fn new(client: Client, config: LinkConfig) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            queue: None,
+            config,
+            url_pattern: Regex::new(r#"https?://[^\s<>"]+"#).expect("valid URL pattern"),
+        }
+    }
+
+    /// Publishes each click on `LINK_CLICKS`.
+    pub fn with_queue(mut self, queue: Arc<dyn MessageQueue>) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    fn link_key(&self, code: &str) -> String {
+        format!("{}:code:{}", self.config.key_prefix, code)
+    }
+
+    fn campaign_key(&self, campaign_id: &str) -> String {
+        format!("{}:campaign:{}", self.config.key_prefix, campaign_id)
+    }
+
+    pub fn short_url(&self, code: &str) -> String {
+        format!("{}/{}", self.config.base_url, code)
+    }
+
+    fn new_code(&self) -> String {
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(self.config.code_length)
+            .map(char::from)
+            .collect()
+    }
+
+    pub async fn shorten(&self, url: &str, context: &LinkContext) -> Result<ShortLink, LinkError> {
+        let parsed =
+            Url::parse(url).map_err(|e| LinkError::InvalidUrl(format!("{}: {}", url, e)))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(LinkError::InvalidUrl(url.to_string()));
+        }
+        let mut conn = self.conn().await?;
+        for _ in 0..MAX_CODE_ATTEMPTS {
+            let link = ShortLink {
+                code: self.new_code(),
+                url: url.to_string(),
+                account_id: context.account_id.clone(),
+                message_id: context.message_id.clone(),
+                campaign_id: context.campaign_id.clone(),
+                created_at: now(),
+            };
+            let created: bool = redis::cmd("SET")
+                .arg(self.link_key(&link.code))
+                .arg(serde_json::to_string(&link)?)
+                .arg("NX")
+                .arg("EX")
+                .arg(self.config.ttl.as_secs())
+                .query_async::<_, Option<String>>(&mut conn)
+                .await?
+                .is_some();
+            if !created {
+                continue;
+            }
+            if let Some(campaign_id) = &link.campaign_id {
+                let key = self.campaign_key(campaign_id);
+                redis::pipe()
+                    .cmd("HINCRBY")
+                    .arg(&key)
+                    .arg("links")
+                    .arg(1)
+                    .ignore()
+                    .cmd("EXPIRE")
+                    .arg(&key)
+                    .arg(self.config.ttl.as_secs())
+                    .ignore()
+                    .query_async::<_, ()>(&mut conn)
+                    .await?;
+            }
+            return Ok(link);
+        }
+        Err(LinkError::CodeExhausted)
+    }
+
+    /// Replaces every `http(s)` URL in `body` with a short link, reusing one
+    /// link for repeats of the same URL. URLs already on our domain and
+    /// trailing punctuation are left alone.
+    pub async fn shorten_body(
+        &self,
+        body: &str,
+        context: &LinkContext,
+    ) -> Result<ShortenedBody, LinkError> {
+        let mut out = String::with_capacity(body.len());
+        let mut links: Vec<ShortLink> = Vec::new();
+        let mut by_url: HashMap<String, String> = HashMap::new();
+        let own_prefix = format!("{}/", self.config.base_url);
+        let mut last = 0;
+        for found in self.url_pattern.find_iter(body) {
+            let url = found
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}', '\'']);
+            if url.starts_with(&own_prefix) || Url::parse(url).is_err() {
+                continue;
+            }
+            let short = match by_url.get(url) {
+                Some(short) => short.clone(),
+                None => {
+                    let link = self.shorten(url, context).await?;
+                    let short = self.short_url(&link.code);
+                    by_url.insert(url.to_string(), short.clone());
+                    links.push(link);
+                    short
+                }
+            };
+            out.push_str(&body[last..found.start()]);
+            out.push_str(&short);
+            last = found.start() + url.len();
+        }
+        out.push_str(&body[last..]);
+        Ok(ShortenedBody { body: out, links })
+    }
+
+    pub async fn resolve(&self, code: &str) -> Result<Option<ShortLink>, LinkError> {
+        if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Ok(None);
+        }
+        let mut conn = self.conn().await?;
+        let raw: Option<String> = redis::cmd("GET")
+            .arg(self.link_key(code))
+            .query_async(&mut conn)
+            .await?;
+        raw.map(|raw| serde_json::from_str(&raw))
+            .transpose()
+            .map_err(LinkError::from)
+    }
+
+    /// Records a visit to `link` and returns the event.
+    pub async fn record_click(
+        &self,
+        link: &ShortLink,
+        user_agent: Option<&str>,
+    ) -> Result<LinkClick, LinkError> {
+        let preview = user_agent.is_some_and(is_preview_agent);
+        let click = LinkClick {
+            code: link.code.clone(),
+            url: link.url.clone(),
+            account_id: link.account_id.clone(),
+            message_id: link.message_id.clone(),
+            campaign_id: link.campaign_id.clone(),
+            clicked_at: now(),
+            user_agent: user_agent.map(str::to_string),
+            preview,
+        };
+        GLOBAL_METRICS.increment(
+            if preview {
+                "link_previews"
+            } else {
+                "link_clicks"
+            },
+            1,
+            None,
+        );
+
+        if let Some(campaign_id) = &link.campaign_id {
+            let key = self.campaign_key(campaign_id);
+            let mut pipe = redis::pipe();
+            pipe.cmd("HINCRBY")
+                .arg(&key)
+                .arg(if preview { "previews" } else { "clicks" })
+                .arg(1)
+                .ignore();
+            if !preview {
+                pipe.cmd("PFADD")
+                    .arg(format!("{}:clicked", key))
+                    .arg(&link.code)
+                    .ignore()
+                    .cmd("EXPIRE")
+                    .arg(format!("{}:clicked", key))
+                    .arg(self.config.ttl.as_secs())
+                    .ignore();
+            }
+            let mut conn = self.conn().await?;
+            pipe.query_async::<_, ()>(&mut conn).await?;
+        }
+
+        if let Some(queue) = &self.queue {
+            if let Err(e) = LINK_CLICKS.publish(queue.as_ref(), &click).await {
+                warn!("Failed to publish click on {}: {}", click.code, e);
+            }
+        }
+        Ok(click)
+    }
+
+    pub async fn campaign_stats(&self, campaign_id: &str) -> Result<ClickStats, LinkError> {
+        let key = self.campaign_key(campaign_id);
+        let mut conn = self.conn().await?;
+        let (counts, unique_links): (HashMap<String, u64>, u64) = redis::pipe()
+            .cmd("HGETALL")
+            .arg(&key)
+            .cmd("PFCOUNT")
+            .arg(format!("{}:clicked", key))
+            .query_async(&mut conn)
+            .await?;
+        let count = |field: &str| counts.get(field).copied().unwrap_or(0);
+        Ok(ClickStats {
+            links: count("links"),
+            clicks: count("clicks"),
+            unique_links,
+            previews: count("previews"),
+        })
+    }
+}
+
+async fn redirect_handler(
+    State(shortener): State<Arc<LinkShortener>>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let link = match shortener.resolve(&code).await {
+        Ok(Some(link)) => link,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            error!("Short link lookup for {} failed: {}", code, e);
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+    };
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok());
+    // A lost click beats a broken link.
+    if let Err(e) = shortener.record_click(&link, user_agent).await {
+        warn!("Failed to record click on {}: {}", code, e);
+    }
+    (
+        StatusCode::FOUND,
+        [
+            (header::LOCATION, link.url),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+    )
+        .into_response()
+}
+
+/// `GET /:code` redirecting short links, for the branded link domain.
+pub fn create_link_router(shortener: Arc<LinkShortener>) -> Router {
+    Router::new()
+        .route("/:code", get(redirect_handler))
+        .with_state(shortener)
+}
@@ -0,0 +1,553 @@
+use super::scheduler::Scheduler;
+use super::topics::{OutboundSend, OUTBOUND_SENDS};
+use super::{MessageQueue, QueueError};
+use crate::adapters::OutboundSms;
+use crate::metrics::GLOBAL_METRICS;
+use crate::providers::throttle::{OverflowPolicy, ProviderThrottle, ThrottleConfig};
+use crate::shutdown::ShutdownSignal;
+use crate::templates::{Template, TemplateError};
+use chrono::{DateTime, Duration as ChronoDuration, NaiveTime, Utc};
+use lazy_static::lazy_static;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult, Script};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+lazy_static! {
+    /// Sets status ARGV[1] if the current one is among ARGV[2..]; returns
+    /// the status afterwards.
+    static ref TRANSITION: Script = Script::new(
+        r#"
+        local current = redis.call("HGET", KEYS[1], "status")
+        if not current then
+            return false
+        end
+        for i = 2, #ARGV do
+            if current == ARGV[i] then
+                redis.call("HSET", KEYS[1], "status", ARGV[1])
+                return ARGV[1]
+            end
+        end
+        return current
+    "#
+    );
+}
+
+#[derive(Error, Debug)]
+pub enum CampaignError {
+    #[error("Campaign {0} not found")]
+    NotFound(String),
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    #[error(transparent)]
+    Queue(#[from] QueueError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignStatus {
+    Running,
+    Paused,
+    Cancelled,
+    Completed,
+}
+
+impl CampaignStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Paused => "paused",
+            Self::Cancelled => "cancelled",
+            Self::Completed => "completed",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "running" => Some(Self::Running),
+            "paused" => Some(Self::Paused),
+            "cancelled" => Some(Self::Cancelled),
+            "completed" => Some(Self::Completed),
+            _ => None,
+        }
+    }
+}
+
+/// Local times during which recipients aren't messaged, e.g. 21:00–08:00.
+/// Windows may wrap past midnight; `start == end` is no quiet time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end }
+    }
+
+    pub fn contains(&self, local: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= local && local < self.end
+        } else {
+            local >= self.start || local < self.end
+        }
+    }
+
+    /// When quiet hours next end for a recipient at `utc_offset_minutes`,
+    /// or `None` if `now` isn't within them.
+    pub fn next_allowed(
+        &self,
+        now: DateTime<Utc>,
+        utc_offset_minutes: i32,
+    ) -> Option<DateTime<Utc>> {
+        let offset = ChronoDuration::minutes(utc_offset_minutes as i64);
+        let local = now.naive_utc() + offset;
+        if !self.contains(local.time()) {
+            return None;
+        }
+        let mut end = local.date().and_time(self.end);
+        if end <= local {
+            end += ChronoDuration::days(1);
+        }
+        Some((end - offset).and_utc())
+    }
+}
+
+/// A campaign's message and pacing. The template is rendered per recipient
+/// with their variables.
+#[derive(Debug, Clone)]
+pub struct Campaign {
+    pub id: String,
+    pub account_id: String,
+    pub from: String,
+    pub template: Template,
+    /// Messages per second across every worker running the campaign.
+    pub rate_per_second: f64,
+    pub quiet_hours: Option<QuietHours>,
+    /// Offset used for recipients without their own.
+    pub default_utc_offset_minutes: i32,
+}
+
+impl Campaign {
+    pub fn new(id: &str, account_id: &str, from: &str, template: Template) -> Self {
+        Self {
+            id: id.to_string(),
+            account_id: account_id.to_string(),
+            from: from.to_string(),
+            template,
+            rate_per_second: 50.0,
+            quiet_hours: None,
+            default_utc_offset_minutes: 0,
+        }
+    }
+
+    pub fn with_rate(mut self, rate_per_second: f64) -> Self {
+        self.rate_per_second = rate_per_second;
+        self
+    }
+
+    pub fn with_quiet_hours(mut self, quiet_hours: QuietHours) -> Self {
+        self.quiet_hours = Some(quiet_hours);
+        self
+    }
+
+    pub fn with_default_utc_offset(mut self, minutes: i32) -> Self {
+        self.default_utc_offset_minutes = minutes;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recipient {
+    pub to: String,
+    /// ISO 3166 alpha-2, for routing.
+    pub country: String,
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    /// Recipient's current offset from UTC, resolved by the caller from
+    /// their timezone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utc_offset_minutes: Option<i32>,
+}
+
+impl Recipient {
+    pub fn new(to: &str, country: &str) -> Self {
+        Self {
+            to: to.to_string(),
+            country: country.to_string(),
+            variables: HashMap::new(),
+            utc_offset_minutes: None,
+        }
+    }
+
+    pub fn with_variable(mut self, name: &str, value: &str) -> Self {
+        self.variables.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_utc_offset(mut self, minutes: i32) -> Self {
+        self.utc_offset_minutes = Some(minutes);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CampaignProgress {
+    pub status: CampaignStatus,
+    pub total: u64,
+    /// Recipients processed so far, whatever the outcome.
+    pub processed: u64,
+    /// Published for sending now.
+    pub queued: u64,
+    /// Held by the scheduler until quiet hours end.
+    pub deferred: u64,
+    /// Template couldn't be rendered for them.
+    pub failed: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct CampaignConfig {
+    pub key_prefix: String,
+    /// Recipients handled between status checks and progress checkpoints.
+    pub chunk_size: usize,
+    /// How often a paused campaign checks whether it was resumed.
+    pub pause_poll: Duration,
+}
+
+impl Default for CampaignConfig {
+    fn default() -> Self {
+        Self {
+            key_prefix: "smsly:campaign".to_string(),
+            chunk_size: 500,
+            pause_poll: Duration::from_secs(1),
+        }
+    }
+}
+
+impl CampaignConfig {
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+}
+
+#[derive(Default)]
+struct ChunkCounts {
+    queued: i64,
+    deferred: i64,
+    failed: i64,
+}
+
+/// Sends campaigns through `OUTBOUND_SENDS` in chunks, throttled by a
+/// Redis token bucket per campaign, with recipients inside quiet hours
+/// handed to the `Scheduler` for when they end.
+///
+/// Progress lives in a Redis hash checkpointed after each chunk, so `run`
+/// picks up where a stopped or crashed run left off given the same
+/// recipient list in the same order. Message IDs are `<campaign>-<index>`,
+/// so the part of a chunk resent after a crash is deduplicated downstream.
+/// `pause`, `resume` and `cancel` take effect at the next chunk.
+pub struct CampaignRunner {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    queue: Arc<dyn MessageQueue>,
+    scheduler: Arc<Scheduler>,
+    config: CampaignConfig,
+}
+
+impl CampaignRunner {
+    pub fn new(
+        client: Client,
+        queue: Arc<dyn MessageQueue>,
+        scheduler: Arc<Scheduler>,
+        config: CampaignConfig,
+    ) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            queue,
+            scheduler,
+            config,
+        }
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    fn key(&self, campaign_id: &str) -> String {
+        format!("{}:{}", self.config.key_prefix, campaign_id)
+    }
+
+    pub async fn progress(&self, campaign_id: &str) -> Result<CampaignProgress, CampaignError> {
+        let mut conn = self.conn().await?;
+        let fields: HashMap<String, String> = redis::cmd("HGETALL")
+            .arg(self.key(campaign_id))
+            .query_async(&mut conn)
+            .await?;
+        let status = fields
+            .get("status")
+            .and_then(|s| CampaignStatus::parse(s))
+            .ok_or_else(|| CampaignError::NotFound(campaign_id.to_string()))?;
+        let count = |name: &str| fields.get(name).and_then(|v| v.parse().ok()).unwrap_or(0);
+        Ok(CampaignProgress {
+            status,
+            total: count("total"),
+            processed: count("offset"),
+            queued: count("queued"),
+            deferred: count("deferred"),
+            failed: count("failed"),
+        })
+    }
+
+    async fn transition(
+        &self,
+        campaign_id: &str,
+        to: CampaignStatus,
+        from: &[CampaignStatus],
+    ) -> Result<CampaignStatus, CampaignError> {
+        let mut conn = self.conn().await?;
+        let mut invocation = TRANSITION.key(self.key(campaign_id));
+        invocation.arg(to.as_str());
+        for status in from {
+            invocation.arg(status.as_str());
+        }
+        let status: Option<String> = invocation.invoke_async(&mut conn).await?;
+        status
+            .as_deref()
+            .and_then(CampaignStatus::parse)
+            .ok_or_else(|| CampaignError::NotFound(campaign_id.to_string()))
+    }
+
+    /// Returns the status afterwards; only a running campaign pauses.
+    pub async fn pause(&self, campaign_id: &str) -> Result<CampaignStatus, CampaignError> {
+        self.transition(
+            campaign_id,
+            CampaignStatus::Paused,
+            &[CampaignStatus::Running],
+        )
+        .await
+    }
+
+    pub async fn resume(&self, campaign_id: &str) -> Result<CampaignStatus, CampaignError> {
+        self.transition(
+            campaign_id,
+            CampaignStatus::Running,
+            &[CampaignStatus::Paused],
+        )
+        .await
+    }
+
+    /// Stops a running or paused campaign for good. Sends already deferred
+    /// to the scheduler still go out unless cancelled there.
+    pub async fn cancel(&self, campaign_id: &str) -> Result<CampaignStatus, CampaignError> {
+        self.transition(
+            campaign_id,
+            CampaignStatus::Cancelled,
+            &[CampaignStatus::Running, CampaignStatus::Paused],
+        )
+        .await
+    }
+
+    /// Waits out a pause; returns the status to act on.
+    async fn wait_while_paused(
+        &self,
+        campaign_id: &str,
+        shutdown: &ShutdownSignal,
+    ) -> Result<CampaignStatus, CampaignError> {
+        loop {
+            let status = self.progress(campaign_id).await?.status;
+            if status != CampaignStatus::Paused || shutdown.is_triggered() {
+                return Ok(status);
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(self.config.pause_poll) => {}
+                _ = shutdown.clone().wait() => {}
+            }
+        }
+    }
+
+    /// Sends `campaign` to `recipients`, starting or resuming it, until it
+    /// completes, is cancelled, or `shutdown`; returns the progress then.
+    pub async fn run(
+        &self,
+        campaign: &Campaign,
+        recipients: &[Recipient],
+        shutdown: ShutdownSignal,
+    ) -> Result<CampaignProgress, CampaignError> {
+        let key = self.key(&campaign.id);
+        let mut conn = self.conn().await?;
+        redis::pipe()
+            .atomic()
+            .hset_nx(&key, "status", CampaignStatus::Running.as_str())
+            .ignore()
+            .hset_nx(&key, "offset", 0)
+            .ignore()
+            .hset(&key, "total", recipients.len())
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        let mut progress = self.progress(&campaign.id).await?;
+        if progress.processed == 0 {
+            info!(
+                "Starting campaign {} to {} recipients",
+                campaign.id,
+                recipients.len()
+            );
+        } else {
+            info!(
+                "Resuming campaign {} at {}/{}",
+                campaign.id,
+                progress.processed,
+                recipients.len()
+            );
+        }
+
+        let throttle = ProviderThrottle::new(
+            self.client.clone(),
+            &campaign.id,
+            ThrottleConfig {
+                key_prefix: format!("{}:throttle", self.config.key_prefix),
+                ..ThrottleConfig::new(campaign.rate_per_second).with_overflow(
+                    OverflowPolicy::Queue {
+                        max_wait: Duration::from_secs(60),
+                    },
+                )
+            },
+        );
+
+        let mut offset = progress.processed as usize;
+        while offset < recipients.len() {
+            match self.wait_while_paused(&campaign.id, &shutdown).await? {
+                CampaignStatus::Running if !shutdown.is_triggered() => {}
+                _ => return self.progress(&campaign.id).await,
+            }
+
+            let end = (offset + self.config.chunk_size).min(recipients.len());
+            let mut counts = ChunkCounts::default();
+            for (index, recipient) in recipients[offset..end].iter().enumerate() {
+                self.send_one(campaign, offset + index, recipient, &throttle, &mut counts)
+                    .await?;
+            }
+            redis::pipe()
+                .atomic()
+                .hset(&key, "offset", end)
+                .ignore()
+                .hincr(&key, "queued", counts.queued)
+                .ignore()
+                .hincr(&key, "deferred", counts.deferred)
+                .ignore()
+                .hincr(&key, "failed", counts.failed)
+                .ignore()
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+            offset = end;
+        }
+
+        self.transition(
+            &campaign.id,
+            CampaignStatus::Completed,
+            &[CampaignStatus::Running],
+        )
+        .await?;
+        progress = self.progress(&campaign.id).await?;
+        info!(
+            "Campaign {} finished: {} queued, {} deferred, {} failed",
+            campaign.id, progress.queued, progress.deferred, progress.failed
+        );
+        Ok(progress)
+    }
+
+    async fn send_one(
+        &self,
+        campaign: &Campaign,
+        index: usize,
+        recipient: &Recipient,
+        throttle: &ProviderThrottle,
+        counts: &mut ChunkCounts,
+    ) -> Result<(), CampaignError> {
+        let body = match campaign.template.render(&recipient.variables) {
+            Ok(rendered) => rendered.text,
+            Err(e) => {
+                self.record_failure(campaign, recipient, index, &e);
+                counts.failed += 1;
+                return Ok(());
+            }
+        };
+        let mut message = OutboundSms::new(&recipient.to, &campaign.from, &body);
+        message.metadata = Some(HashMap::from([(
+            "campaign_id".to_string(),
+            Value::String(campaign.id.clone()),
+        )]));
+        let send = OutboundSend {
+            message_id: format!("{}-{}", campaign.id, index),
+            account_id: campaign.account_id.clone(),
+            country: recipient.country.clone(),
+            message,
+        };
+
+        let offset = recipient
+            .utc_offset_minutes
+            .unwrap_or(campaign.default_utc_offset_minutes);
+        let deferred_until = campaign
+            .quiet_hours
+            .and_then(|quiet| quiet.next_allowed(Utc::now(), offset));
+        if let Some(send_at) = deferred_until {
+            self.scheduler.schedule(&send, send_at).await?;
+            counts.deferred += 1;
+            self.record(campaign, "deferred");
+            return Ok(());
+        }
+
+        while let Err(e) = throttle.acquire().await {
+            warn!("Campaign {} throttled: {}", campaign.id, e);
+        }
+        let queue_message = OUTBOUND_SENDS.message(&send)?.with_id(&send.message_id);
+        self.queue
+            .publish(OUTBOUND_SENDS.name, &queue_message)
+            .await?;
+        counts.queued += 1;
+        self.record(campaign, "queued");
+        Ok(())
+    }
+
+    fn record_failure(
+        &self,
+        campaign: &Campaign,
+        recipient: &Recipient,
+        index: usize,
+        error: &TemplateError,
+    ) {
+        warn!(
+            "Campaign {} skipping recipient {} ({}): {}",
+            campaign.id, index, recipient.to, error
+        );
+        self.record(campaign, "failed");
+    }
+
+    fn record(&self, campaign: &Campaign, outcome: &str) {
+        GLOBAL_METRICS.increment(
+            "campaign_messages",
+            1,
+            Some(HashMap::from([
+                ("campaign".to_string(), campaign.id.clone()),
+                ("outcome".to_string(), outcome.to_string()),
+            ])),
+        );
+    }
+}
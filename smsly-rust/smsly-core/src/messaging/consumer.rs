@@ -0,0 +1,247 @@
+use super::dead_letter::{DeadLetter, DeadLetterQueue};
+use super::topics::Topic;
+use super::{Delivery, MessageQueue, QueueError, Subscription};
+use crate::metrics::GLOBAL_METRICS;
+use crate::shutdown::ShutdownSignal;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// When handled messages are acknowledged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitMode {
+    /// Each message as soon as its handler succeeds.
+    PerMessage,
+    /// The whole batch once every handler in it succeeded; one failure
+    /// leaves the batch pending, like a Kafka offset commit.
+    PerBatch,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsumerConfig {
+    pub batch_size: usize,
+    /// How long a poll waits for new messages.
+    pub wait: Duration,
+    /// Unacked deliveries idle this long are taken over from their consumer.
+    pub claim_after: Duration,
+    pub commit: CommitMode,
+    /// Deliveries failing this many times go to the dead-letter queue, if
+    /// the consumer has one; otherwise they're retried indefinitely.
+    pub max_attempts: u32,
+}
+
+impl Default for ConsumerConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 32,
+            wait: Duration::from_secs(5),
+            claim_after: Duration::from_secs(60),
+            commit: CommitMode::PerMessage,
+            max_attempts: 10,
+        }
+    }
+}
+
+impl ConsumerConfig {
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    pub fn with_claim_after(mut self, claim_after: Duration) -> Self {
+        self.claim_after = claim_after;
+        self
+    }
+
+    pub fn with_commit(mut self, commit: CommitMode) -> Self {
+        self.commit = commit;
+        self
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+}
+
+/// At-least-once consumer for a typed topic: a message is only acked after
+/// its handler returns `Ok`, and failed or abandoned ones are retried by
+/// whichever consumer in the group claims them after `claim_after`, so
+/// handlers must be idempotent on `QueueMessage::id`.
+///
+/// Payloads that don't decode as `T` are logged and acked, since retrying
+/// them can't succeed. With `with_dead_letters`, they and messages failing
+/// `max_attempts` times are moved to the dead-letter queue instead.
+pub struct TopicConsumer<T> {
+    queue: Arc<dyn MessageQueue>,
+    topic: Topic<T>,
+    subscription: Subscription,
+    config: ConsumerConfig,
+    dead_letters: Option<Arc<DeadLetterQueue>>,
+}
+
+impl<T: Serialize + DeserializeOwned> TopicConsumer<T> {
+    pub fn new(
+        queue: Arc<dyn MessageQueue>,
+        topic: Topic<T>,
+        group: &str,
+        consumer: &str,
+        config: ConsumerConfig,
+    ) -> Self {
+        Self {
+            queue,
+            subscription: Subscription::new(topic.name, group, consumer),
+            topic,
+            config,
+            dead_letters: None,
+        }
+    }
+
+    pub fn with_dead_letters(mut self, dead_letters: Arc<DeadLetterQueue>) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    pub fn subscription(&self) -> &Subscription {
+        &self.subscription
+    }
+
+    /// Handles one batch, claimed deliveries first, and returns how many
+    /// messages were handled successfully.
+    pub async fn poll_once<F, Fut, E>(&self, handler: &F) -> Result<usize, QueueError>
+    where
+        F: Fn(T, Delivery) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: Display,
+    {
+        let mut batch = self
+            .queue
+            .claim_pending(
+                &self.subscription,
+                self.config.claim_after,
+                self.config.batch_size,
+            )
+            .await?;
+        if batch.len() < self.config.batch_size {
+            // Don't block waiting for new messages while claimed ones wait.
+            let wait = if batch.is_empty() {
+                self.config.wait
+            } else {
+                Duration::ZERO
+            };
+            batch.extend(
+                self.queue
+                    .consume(
+                        &self.subscription,
+                        self.config.batch_size - batch.len(),
+                        wait,
+                    )
+                    .await?,
+            );
+        }
+
+        let mut handled = Vec::new();
+        let mut failed = false;
+        for delivery in batch {
+            let payload = match self.topic.decode(&delivery) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    error!(
+                        "Dropping undecodable message {} on {}: {}",
+                        delivery.message.id, self.topic.name, e
+                    );
+                    if !self.dead_letter(&delivery, &e.to_string()).await? {
+                        self.record("dropped");
+                    }
+                    self.queue.ack(&self.subscription, &delivery).await?;
+                    continue;
+                }
+            };
+            match handler(payload, delivery.clone()).await {
+                Ok(()) => {
+                    self.record("ok");
+                    if self.config.commit == CommitMode::PerMessage {
+                        self.queue.ack(&self.subscription, &delivery).await?;
+                    }
+                    handled.push(delivery);
+                }
+                Err(e) => {
+                    warn!(
+                        "Handler failed for {} on {} (delivery {}): {}",
+                        delivery.message.id, self.topic.name, delivery.delivery_count, e
+                    );
+                    self.record("error");
+                    if delivery.delivery_count >= self.config.max_attempts
+                        && self.dead_letter(&delivery, &e.to_string()).await?
+                    {
+                        self.queue.ack(&self.subscription, &delivery).await?;
+                    } else {
+                        failed = true;
+                    }
+                }
+            }
+        }
+
+        if self.config.commit == CommitMode::PerBatch && !failed {
+            for delivery in &handled {
+                self.queue.ack(&self.subscription, delivery).await?;
+            }
+        }
+        Ok(handled.len())
+    }
+
+    /// Polls until `shutdown`, finishing the batch in hand before returning.
+    pub async fn run<F, Fut, E>(&self, handler: F, shutdown: ShutdownSignal)
+    where
+        F: Fn(T, Delivery) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: Display,
+    {
+        while !shutdown.is_triggered() {
+            if let Err(e) = self.poll_once(&handler).await {
+                error!("Polling {} failed: {}", self.topic.name, e);
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                    _ = shutdown.clone().wait() => {}
+                }
+            }
+        }
+    }
+
+    /// Moves `delivery` to the dead-letter queue; false without one.
+    async fn dead_letter(&self, delivery: &Delivery, error: &str) -> Result<bool, QueueError> {
+        let Some(dead_letters) = &self.dead_letters else {
+            return Ok(false);
+        };
+        dead_letters
+            .push(&DeadLetter::new(&self.subscription.group, delivery, error))
+            .await?;
+        warn!(
+            "Dead-lettered {} on {} after {} deliveries",
+            delivery.message.id, self.topic.name, delivery.delivery_count
+        );
+        self.record("dead_lettered");
+        Ok(true)
+    }
+
+    fn record(&self, outcome: &str) {
+        GLOBAL_METRICS.increment(
+            "queue_messages_consumed",
+            1,
+            Some(HashMap::from([
+                ("topic".to_string(), self.topic.name.to_string()),
+                ("outcome".to_string(), outcome.to_string()),
+            ])),
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use super::{Delivery, MessageQueue, QueueError, QueueMessage};
+use crate::metrics::GLOBAL_METRICS;
+use chrono::Utc;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Header on replayed messages naming the provider to send through instead
+/// of the routed one, for the dispatch workers to honour.
+pub const PROVIDER_OVERRIDE_HEADER: &str = "Smsly-Provider";
+/// Header on replayed messages with the dead letter they came from.
+pub const REPLAYED_FROM_HEADER: &str = "Smsly-Replayed-From";
+
+/// A message that failed too often to keep retrying, with why.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub id: String,
+    pub topic: String,
+    /// Consumer group that gave up on it.
+    pub group: String,
+    pub message: QueueMessage,
+    /// The last handler error.
+    pub error: String,
+    pub attempts: u32,
+    pub failed_at: f64,
+}
+
+impl DeadLetter {
+    pub fn new(group: &str, delivery: &Delivery, error: &str) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            topic: delivery.topic.clone(),
+            group: group.to_string(),
+            message: delivery.message.clone(),
+            error: error.to_string(),
+            attempts: delivery.delivery_count,
+            failed_at: Utc::now().timestamp_millis() as f64 / 1000.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayOptions {
+    /// Publish to this topic instead of the original one.
+    pub topic: Option<String>,
+    /// Send through this provider instead of the routed one.
+    pub provider: Option<String>,
+}
+
+impl ReplayOptions {
+    pub fn to_topic(mut self, topic: &str) -> Self {
+        self.topic = Some(topic.to_string());
+        self
+    }
+
+    pub fn via_provider(mut self, provider: &str) -> Self {
+        self.provider = Some(provider.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReplayReport {
+    pub replayed: Vec<String>,
+    /// Dead letters that weren't found or failed to publish, with why;
+    /// those that failed to publish are kept.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Dead letters in Redis, indexed per topic by failure time. Entries stay
+/// until replayed or deleted. Depth per topic is exported as the
+/// `dead_letter_depth` gauge whenever it changes here.
+pub struct DeadLetterQueue {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    key_prefix: String,
+}
+
+impl DeadLetterQueue {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            key_prefix: "smsly:dlq".to_string(),
+        }
+    }
+
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    fn index_key(&self, topic: &str) -> String {
+        format!("{}:topic:{}", self.key_prefix, topic)
+    }
+
+    fn entry_key(&self, id: &str) -> String {
+        format!("{}:entry:{}", self.key_prefix, id)
+    }
+
+    fn topics_key(&self) -> String {
+        format!("{}:topics", self.key_prefix)
+    }
+
+    pub async fn push(&self, letter: &DeadLetter) -> Result<(), QueueError> {
+        let entry =
+            serde_json::to_string(letter).map_err(|e| QueueError::InvalidMessage(e.to_string()))?;
+        let mut conn = self.conn().await?;
+        redis::pipe()
+            .atomic()
+            .set(self.entry_key(&letter.id), entry)
+            .ignore()
+            .zadd(self.index_key(&letter.topic), &letter.id, letter.failed_at)
+            .ignore()
+            .sadd(self.topics_key(), &letter.topic)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        GLOBAL_METRICS.increment("dead_letters", 1, Some(topic_label(&letter.topic)));
+        self.depth(&letter.topic).await?;
+        Ok(())
+    }
+
+    /// Topics that have ever had dead letters.
+    pub async fn topics(&self) -> Result<Vec<String>, QueueError> {
+        let mut conn = self.conn().await?;
+        let mut topics: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(self.topics_key())
+            .query_async(&mut conn)
+            .await?;
+        topics.sort();
+        Ok(topics)
+    }
+
+    /// Number of dead letters on `topic`; also updates the gauge.
+    pub async fn depth(&self, topic: &str) -> Result<usize, QueueError> {
+        let mut conn = self.conn().await?;
+        let depth: usize = redis::cmd("ZCARD")
+            .arg(self.index_key(topic))
+            .query_async(&mut conn)
+            .await?;
+        GLOBAL_METRICS.set_gauge("dead_letter_depth", depth as f64, Some(topic_label(topic)));
+        Ok(depth)
+    }
+
+    /// Dead letters on `topic`, oldest first.
+    pub async fn list(
+        &self,
+        topic: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<DeadLetter>, QueueError> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.conn().await?;
+        let ids: Vec<String> = redis::cmd("ZRANGE")
+            .arg(self.index_key(topic))
+            .arg(offset)
+            .arg(offset + limit - 1)
+            .query_async(&mut conn)
+            .await?;
+        self.load(&mut conn, &ids).await
+    }
+
+    async fn load(
+        &self,
+        conn: &mut ConnectionManager,
+        ids: &[String],
+    ) -> Result<Vec<DeadLetter>, QueueError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys: Vec<String> = ids.iter().map(|id| self.entry_key(id)).collect();
+        let entries: Vec<Option<String>> = redis::cmd("MGET").arg(keys).query_async(conn).await?;
+        Ok(entries
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| match serde_json::from_str(&entry) {
+                Ok(letter) => Some(letter),
+                Err(e) => {
+                    warn!("Skipping unreadable dead letter: {}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<DeadLetter>, QueueError> {
+        let mut conn = self.conn().await?;
+        Ok(self
+            .load(&mut conn, &[id.to_string()])
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Returns false if there was no such dead letter.
+    pub async fn delete(&self, id: &str) -> Result<bool, QueueError> {
+        let Some(letter) = self.get(id).await? else {
+            return Ok(false);
+        };
+        self.remove(&letter).await?;
+        Ok(true)
+    }
+
+    async fn remove(&self, letter: &DeadLetter) -> Result<(), QueueError> {
+        let mut conn = self.conn().await?;
+        redis::pipe()
+            .atomic()
+            .del(self.entry_key(&letter.id))
+            .ignore()
+            .zrem(self.index_key(&letter.topic), &letter.id)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        self.depth(&letter.topic).await?;
+        Ok(())
+    }
+
+    /// Republishes the given dead letters and removes those published. The
+    /// original message ID is kept so consumers that already saw it can
+    /// deduplicate.
+    pub async fn replay(
+        &self,
+        queue: &dyn MessageQueue,
+        ids: &[String],
+        options: &ReplayOptions,
+    ) -> Result<ReplayReport, QueueError> {
+        let mut report = ReplayReport::default();
+        for id in ids {
+            let Some(letter) = self.get(id).await? else {
+                report.failed.push((id.clone(), "not found".to_string()));
+                continue;
+            };
+            let topic = options.topic.as_deref().unwrap_or(&letter.topic);
+            let mut message = letter
+                .message
+                .clone()
+                .with_header(REPLAYED_FROM_HEADER, &letter.id);
+            if let Some(provider) = &options.provider {
+                message = message.with_header(PROVIDER_OVERRIDE_HEADER, provider);
+            }
+            match queue.publish(topic, &message).await {
+                Ok(_) => {
+                    self.remove(&letter).await?;
+                    report.replayed.push(letter.id);
+                }
+                Err(e) => {
+                    warn!("Replaying dead letter {} failed: {}", letter.id, e);
+                    report.failed.push((letter.id, e.to_string()));
+                }
+            }
+        }
+        if !report.replayed.is_empty() {
+            info!("Replayed {} dead letters", report.replayed.len());
+        }
+        Ok(report)
+    }
+
+    /// Replays up to `limit` of the oldest dead letters on `topic`.
+    pub async fn replay_topic(
+        &self,
+        queue: &dyn MessageQueue,
+        topic: &str,
+        limit: usize,
+        options: &ReplayOptions,
+    ) -> Result<ReplayReport, QueueError> {
+        let ids: Vec<String> = self
+            .list(topic, 0, limit)
+            .await?
+            .into_iter()
+            .map(|letter| letter.id)
+            .collect();
+        self.replay(queue, &ids, options).await
+    }
+}
+
+fn topic_label(topic: &str) -> HashMap<String, String> {
+    HashMap::from([("topic".to_string(), topic.to_string())])
+}
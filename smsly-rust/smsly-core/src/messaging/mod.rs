@@ -0,0 +1,218 @@
+//! Broker-independent message queues, so the send pipeline and webhook
+//! processing can run on separate workers.
+//!
+//! There is no Kafka `MessageQueue` yet: it is to be built on rdkafka
+//! behind a `kafka` feature once the crate is vendored. Until then
+//! `Topic` and `TopicConsumer` run on the backends below.
+
+use crate::database::outbox::{OutboxMessage, OutboxPublisher};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+pub mod campaign;
+pub mod consumer;
+pub mod dead_letter;
+pub mod nats;
+pub mod rabbitmq;
+pub mod redis_streams;
+pub mod retry_queue;
+pub mod scheduler;
+#[cfg(feature = "aws")]
+pub mod sqs;
+pub mod topics;
+
+pub use campaign::{
+    Campaign, CampaignConfig, CampaignError, CampaignProgress, CampaignRunner, CampaignStatus,
+    QuietHours, Recipient,
+};
+pub use consumer::{CommitMode, ConsumerConfig, TopicConsumer};
+pub use dead_letter::{DeadLetter, DeadLetterQueue, ReplayOptions, ReplayReport};
+pub use nats::{NatsConfig, NatsQueue};
+pub use rabbitmq::{RabbitMqConfig, RabbitMqQueue};
+pub use redis_streams::{RedisStreamsConfig, RedisStreamsQueue};
+pub use retry_queue::{RetryDecision, RetryJob, RetryQueue, RetryQueueConfig};
+pub use scheduler::{ScheduledSend, Scheduler, SchedulerConfig};
+#[cfg(feature = "aws")]
+pub use sqs::{SqsConfig, SqsQueue};
+pub use topics::{
+    AuditEvent, LinkClick, OutboundSend, Topic, AUDIT_EVENTS, DELIVERY_REPORTS, LINK_CLICKS,
+    OUTBOUND_SENDS,
+};
+
+#[derive(Error, Debug)]
+pub enum QueueError {
+    #[error("Queue connection failed: {0}")]
+    Connection(String),
+    #[error("Queue backend error: {0}")]
+    Backend(String),
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+}
+
+impl From<lapin::Error> for QueueError {
+    fn from(e: lapin::Error) -> Self {
+        match e {
+            lapin::Error::IOError(_) | lapin::Error::InvalidConnectionState(_) => {
+                QueueError::Connection(e.to_string())
+            }
+            _ => QueueError::Backend(e.to_string()),
+        }
+    }
+}
+
+impl From<redis::RedisError> for QueueError {
+    fn from(e: redis::RedisError) -> Self {
+        if e.is_connection_refusal() || e.is_connection_dropped() || e.is_io_error() {
+            QueueError::Connection(e.to_string())
+        } else {
+            QueueError::Backend(e.to_string())
+        }
+    }
+}
+
+/// A message as published; `id` identifies it across redeliveries, so
+/// consumers deduplicate on it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueMessage {
+    pub id: String,
+    /// Partition or ordering key, where the backend has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub payload: Value,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+}
+
+impl QueueMessage {
+    pub fn new(payload: Value) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            key: None,
+            payload,
+            headers: HashMap::new(),
+        }
+    }
+
+    pub fn json<T: Serialize>(payload: &T) -> Result<Self, QueueError> {
+        serde_json::to_value(payload)
+            .map(Self::new)
+            .map_err(|e| QueueError::InvalidMessage(e.to_string()))
+    }
+
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = id.to_string();
+        self
+    }
+
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.key = Some(key.to_string());
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+/// Where and as whom to consume: consumers sharing a `group` split the
+/// topic's messages between them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Subscription {
+    pub topic: String,
+    pub group: String,
+    pub consumer: String,
+}
+
+impl Subscription {
+    pub fn new(topic: &str, group: &str, consumer: &str) -> Self {
+        Self {
+            topic: topic.to_string(),
+            group: group.to_string(),
+            consumer: consumer.to_string(),
+        }
+    }
+}
+
+/// A received message, pending until acked.
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    /// Backend handle used to ack this delivery (stream entry ID, delivery
+    /// tag, receipt handle, …); differs from `message.id`.
+    pub delivery_id: String,
+    pub topic: String,
+    pub message: QueueMessage,
+    /// 1 on first delivery.
+    pub delivery_count: u32,
+}
+
+impl Delivery {
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, QueueError> {
+        serde_json::from_value(self.message.payload.clone())
+            .map_err(|e| QueueError::InvalidMessage(e.to_string()))
+    }
+}
+
+/// At-least-once queue with consumer groups. A delivery that isn't acked
+/// stays pending and can be taken over with `claim_pending` once its
+/// consumer has sat on it for too long, e.g. after a crash.
+#[async_trait]
+pub trait MessageQueue: Send + Sync {
+    /// Publishes `message` and returns the backend's ID for it.
+    async fn publish(&self, topic: &str, message: &QueueMessage) -> Result<String, QueueError>;
+
+    /// Up to `max` new messages, waiting up to `wait` if there are none.
+    async fn consume(
+        &self,
+        subscription: &Subscription,
+        max: usize,
+        wait: Duration,
+    ) -> Result<Vec<Delivery>, QueueError>;
+
+    async fn ack(&self, subscription: &Subscription, delivery: &Delivery)
+        -> Result<(), QueueError>;
+
+    /// Takes over up to `max` deliveries left unacked by any consumer in the
+    /// group for at least `min_idle`.
+    async fn claim_pending(
+        &self,
+        subscription: &Subscription,
+        min_idle: Duration,
+        max: usize,
+    ) -> Result<Vec<Delivery>, QueueError>;
+}
+
+/// Relays outbox rows onto a queue, keeping the outbox ID as message ID.
+pub struct QueueOutboxPublisher {
+    queue: Arc<dyn MessageQueue>,
+}
+
+impl QueueOutboxPublisher {
+    pub fn new(queue: Arc<dyn MessageQueue>) -> Self {
+        Self { queue }
+    }
+}
+
+#[async_trait]
+impl OutboxPublisher for QueueOutboxPublisher {
+    async fn publish(&self, message: &OutboxMessage) -> Result<(), String> {
+        let queued = QueueMessage {
+            id: message.id.to_string(),
+            key: message.key.clone(),
+            payload: message.payload.clone(),
+            headers: message.headers.clone(),
+        };
+        self.queue
+            .publish(&message.topic, &queued)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,322 @@
+use super::NatsConfig;
+use crate::messaging::QueueError;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, Weak};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// A message received on a subscription.
+#[derive(Debug, Clone)]
+pub(crate) struct NatsMessage {
+    pub reply: Option<String>,
+    /// Status code from the header line, e.g. 404 when a pull has nothing.
+    pub status: Option<u16>,
+    pub headers: Vec<(String, String)>,
+    pub payload: Vec<u8>,
+}
+
+impl NatsMessage {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A client connection speaking the NATS text protocol. A reader task
+/// answers server pings and routes messages to subscriptions by sid. Once
+/// the connection drops it's closed for good and every subscription ends;
+/// the queue connects again.
+pub(crate) struct NatsConnection {
+    writer: Mutex<OwnedWriteHalf>,
+    subscriptions: StdMutex<HashMap<u64, mpsc::UnboundedSender<NatsMessage>>>,
+    next_sid: AtomicU64,
+    inbox_prefix: String,
+    closed: AtomicBool,
+    request_timeout: Duration,
+}
+
+impl NatsConnection {
+    pub async fn connect(config: &NatsConfig) -> Result<Arc<Self>, QueueError> {
+        let url = Url::parse(&config.url).map_err(|e| QueueError::Connection(e.to_string()))?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| QueueError::Connection("missing host".to_string()))?;
+        let port = url.port().unwrap_or(4222);
+
+        let mut connect = serde_json::json!({
+            "verbose": false,
+            "pedantic": false,
+            "headers": true,
+            "no_responders": true,
+            "name": config.name,
+            "lang": "rust",
+        });
+        if !url.username().is_empty() {
+            connect["user"] = url.username().into();
+            connect["pass"] = url.password().unwrap_or_default().into();
+        }
+
+        let stream = tokio::time::timeout(config.request_timeout, TcpStream::connect((host, port)))
+            .await
+            .map_err(|_| QueueError::Connection("connect timed out".to_string()))?
+            .map_err(io_error)?;
+        stream.set_nodelay(true).map_err(io_error)?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let mut line = String::new();
+        reader.read_line(&mut line).await.map_err(io_error)?;
+        if !line.starts_with("INFO") {
+            return Err(QueueError::Connection(
+                "server did not send INFO".to_string(),
+            ));
+        }
+        writer
+            .write_all(format!("CONNECT {}\r\nPING\r\n", connect).as_bytes())
+            .await
+            .map_err(io_error)?;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await.map_err(io_error)? == 0 {
+                return Err(QueueError::Connection("connection closed".to_string()));
+            }
+            if line.starts_with("PONG") {
+                break;
+            }
+            if line.starts_with("-ERR") {
+                return Err(QueueError::Connection(line.trim().to_string()));
+            }
+        }
+        info!("Connected to NATS at {}:{}", host, port);
+
+        let connection = Arc::new(Self {
+            writer: Mutex::new(writer),
+            subscriptions: StdMutex::new(HashMap::new()),
+            next_sid: AtomicU64::new(1),
+            inbox_prefix: format!("_INBOX.{}", Uuid::new_v4().simple()),
+            closed: AtomicBool::new(false),
+            request_timeout: config.request_timeout,
+        });
+        tokio::spawn(read_loop(Arc::downgrade(&connection), reader));
+        Ok(connection)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn close(&self) {
+        if !self.closed.swap(true, Ordering::SeqCst) {
+            // Dropping the senders ends every subscription.
+            self.subscriptions.lock().unwrap().clear();
+        }
+    }
+
+    pub fn new_inbox(&self) -> String {
+        format!("{}.{}", self.inbox_prefix, Uuid::new_v4().simple())
+    }
+
+    async fn write(&self, data: &[u8]) -> Result<(), QueueError> {
+        if self.is_closed() {
+            return Err(QueueError::Connection("connection closed".to_string()));
+        }
+        let mut writer = self.writer.lock().await;
+        if let Err(e) = writer.write_all(data).await {
+            drop(writer);
+            self.close();
+            return Err(io_error(e));
+        }
+        Ok(())
+    }
+
+    /// Publishes `payload`, with a header block when `headers` isn't empty.
+    pub async fn publish(
+        &self,
+        subject: &str,
+        reply: Option<&str>,
+        headers: &[(&str, &str)],
+        payload: &[u8],
+    ) -> Result<(), QueueError> {
+        let reply = reply.map(|r| format!(" {}", r)).unwrap_or_default();
+        let mut frame = Vec::with_capacity(payload.len() + 128);
+        if headers.is_empty() {
+            frame.extend_from_slice(
+                format!("PUB {}{} {}\r\n", subject, reply, payload.len()).as_bytes(),
+            );
+        } else {
+            let mut block = String::from("NATS/1.0\r\n");
+            for (name, value) in headers {
+                block.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            block.push_str("\r\n");
+            frame.extend_from_slice(
+                format!(
+                    "HPUB {}{} {} {}\r\n",
+                    subject,
+                    reply,
+                    block.len(),
+                    block.len() + payload.len()
+                )
+                .as_bytes(),
+            );
+            frame.extend_from_slice(block.as_bytes());
+        }
+        frame.extend_from_slice(payload);
+        frame.extend_from_slice(b"\r\n");
+        self.write(&frame).await
+    }
+
+    pub async fn subscribe(
+        &self,
+        subject: &str,
+    ) -> Result<(u64, mpsc::UnboundedReceiver<NatsMessage>), QueueError> {
+        let sid = self.next_sid.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscriptions.lock().unwrap().insert(sid, tx);
+        if let Err(e) = self
+            .write(format!("SUB {} {}\r\n", subject, sid).as_bytes())
+            .await
+        {
+            self.subscriptions.lock().unwrap().remove(&sid);
+            return Err(e);
+        }
+        Ok((sid, rx))
+    }
+
+    pub async fn unsubscribe(&self, sid: u64) {
+        self.subscriptions.lock().unwrap().remove(&sid);
+        let _ = self.write(format!("UNSUB {}\r\n", sid).as_bytes()).await;
+    }
+
+    /// Publishes with a fresh reply inbox and waits for the first reply.
+    pub async fn request(
+        &self,
+        subject: &str,
+        headers: &[(&str, &str)],
+        payload: &[u8],
+    ) -> Result<NatsMessage, QueueError> {
+        let inbox = self.new_inbox();
+        let (sid, mut replies) = self.subscribe(&inbox).await?;
+        let result = async {
+            self.publish(subject, Some(&inbox), headers, payload)
+                .await?;
+            match tokio::time::timeout(self.request_timeout, replies.recv()).await {
+                Ok(Some(reply)) if reply.status == Some(503) => Err(QueueError::Backend(format!(
+                    "no responders for {}",
+                    subject
+                ))),
+                Ok(Some(reply)) => Ok(reply),
+                Ok(None) => Err(QueueError::Connection("connection closed".to_string())),
+                Err(_) => Err(QueueError::Backend(format!(
+                    "request to {} timed out",
+                    subject
+                ))),
+            }
+        }
+        .await;
+        self.unsubscribe(sid).await;
+        result
+    }
+}
+
+fn io_error(e: std::io::Error) -> QueueError {
+    QueueError::Connection(e.to_string())
+}
+
+/// Splits an `HMSG` header block into its status code and headers.
+fn parse_headers(block: &[u8]) -> (Option<u16>, Vec<(String, String)>) {
+    let text = String::from_utf8_lossy(block);
+    let mut lines = text.split("\r\n");
+    // `NATS/1.0` or `NATS/1.0 404 No Messages`.
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok());
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+    (status, headers)
+}
+
+async fn read_loop(connection: Weak<NatsConnection>, reader: BufReader<OwnedReadHalf>) {
+    let mut reader = reader;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line).await;
+        let Some(connection) = connection.upgrade() else {
+            return;
+        };
+        match read {
+            Ok(0) | Err(_) => {
+                warn!("NATS connection lost");
+                connection.close();
+                return;
+            }
+            Ok(_) => {}
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.first().copied() {
+            Some("MSG") | Some("HMSG") => {
+                let with_headers = parts[0] == "HMSG";
+                // MSG <subject> <sid> [reply] <size>
+                // HMSG <subject> <sid> [reply] <header size> <total size>
+                let sizes = if with_headers { 2 } else { 1 };
+                if parts.len() < 3 + sizes || parts.len() > 4 + sizes {
+                    warn!("Malformed NATS frame: {}", line.trim());
+                    connection.close();
+                    return;
+                }
+                let reply = (parts.len() == 4 + sizes).then(|| parts[3].to_string());
+                let total: usize = parts[parts.len() - 1].parse().unwrap_or(0);
+                let header_size: usize = if with_headers {
+                    parts[parts.len() - 2].parse().unwrap_or(0)
+                } else {
+                    0
+                };
+                let mut data = vec![0; total + 2];
+                if reader.read_exact(&mut data).await.is_err() || header_size > total {
+                    connection.close();
+                    return;
+                }
+                data.truncate(total);
+                let payload = data.split_off(header_size);
+                let (status, headers) = if with_headers {
+                    parse_headers(&data)
+                } else {
+                    (None, Vec::new())
+                };
+                let sid: u64 = parts[2].parse().unwrap_or(0);
+                let message = NatsMessage {
+                    reply,
+                    status,
+                    headers,
+                    payload,
+                };
+                let subscriber = connection.subscriptions.lock().unwrap().get(&sid).cloned();
+                match subscriber {
+                    Some(tx) => {
+                        let _ = tx.send(message);
+                    }
+                    None => debug!("NATS message for unknown sid {}", sid),
+                }
+            }
+            Some("PING") => {
+                let _ = connection.write(b"PONG\r\n").await;
+            }
+            Some("-ERR") => warn!("NATS error: {}", line.trim()),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! JetStream over the plain NATS protocol, as `NatsHealthCheck` does, so no
+//! client library is needed.
+
+use super::{Delivery, MessageQueue, QueueError, QueueMessage, Subscription};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+mod connection;
+
+use connection::{NatsConnection, NatsMessage};
+
+/// Carries `QueueMessage::key`; JetStream has no partition key.
+const KEY_HEADER: &str = "Smsly-Message-Key";
+
+/// JetStream API error codes.
+const STREAM_NAME_IN_USE: i64 = 10058;
+const CONSUMER_ALREADY_EXISTS: i64 = 10148;
+
+#[derive(Debug, Clone)]
+pub struct NatsConfig {
+    /// `nats://[user:pass@]host:port`.
+    pub url: String,
+    pub name: String,
+    pub request_timeout: Duration,
+    /// A message ID seen again within this window is stored only once.
+    pub duplicate_window: Duration,
+    /// How long streams keep messages; `None` keeps them until limits hit.
+    pub max_age: Option<Duration>,
+    pub replicas: u32,
+    /// Unacked deliveries are redelivered after this long.
+    pub ack_wait: Duration,
+    pub max_deliver: Option<i64>,
+    pub max_ack_pending: i64,
+}
+
+impl NatsConfig {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            name: "smsly".to_string(),
+            request_timeout: Duration::from_secs(5),
+            duplicate_window: Duration::from_secs(600),
+            max_age: Some(Duration::from_secs(7 * 24 * 3600)),
+            replicas: 1,
+            ack_wait: Duration::from_secs(60),
+            max_deliver: Some(10),
+            max_ack_pending: 1000,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let mut config = Self::new(
+            &env::var("NATS_URL").unwrap_or_else(|_| "nats://localhost:4222".to_string()),
+        );
+        if let Ok(replicas) = env::var("NATS_STREAM_REPLICAS") {
+            config.replicas = replicas.parse().unwrap_or(1);
+        }
+        config
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_duplicate_window(mut self, window: Duration) -> Self {
+        self.duplicate_window = window;
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn with_replicas(mut self, replicas: u32) -> Self {
+        self.replicas = replicas;
+        self
+    }
+
+    pub fn with_ack_wait(mut self, ack_wait: Duration) -> Self {
+        self.ack_wait = ack_wait;
+        self
+    }
+
+    pub fn with_max_deliver(mut self, max_deliver: Option<i64>) -> Self {
+        self.max_deliver = max_deliver;
+        self
+    }
+}
+
+/// `MessageQueue` on NATS JetStream. Each topic is a stream of the same
+/// name (with `.` and wildcards replaced by `_`) capturing the topic as its
+/// subject, and each group a durable pull consumer on it.
+///
+/// `QueueMessage::id` is sent as `Nats-Msg-Id`, so republishing an ID within
+/// `duplicate_window` is dropped by the server; use the request's
+/// idempotency key as message ID to make retried publishes safe.
+///
+/// JetStream redelivers unacked messages after `ack_wait` on its own, so
+/// they come back through `consume` and `claim_pending` returns nothing.
+pub struct NatsQueue {
+    config: NatsConfig,
+    connection: Mutex<Option<Arc<NatsConnection>>>,
+    streams: StdMutex<HashSet<String>>,
+    consumers: StdMutex<HashSet<(String, String)>>,
+}
+
+impl NatsQueue {
+    pub fn new(config: NatsConfig) -> Self {
+        Self {
+            config,
+            connection: Mutex::new(None),
+            streams: StdMutex::new(HashSet::new()),
+            consumers: StdMutex::new(HashSet::new()),
+        }
+    }
+
+    async fn connection(&self) -> Result<Arc<NatsConnection>, QueueError> {
+        let mut connection = self.connection.lock().await;
+        if let Some(existing) = connection.as_ref() {
+            if !existing.is_closed() {
+                return Ok(existing.clone());
+            }
+        }
+        let fresh = NatsConnection::connect(&self.config).await?;
+        *connection = Some(fresh.clone());
+        Ok(fresh)
+    }
+
+    /// JetStream API call; the response may carry an `error` object.
+    async fn api(&self, subject: &str, body: &Value) -> Result<Value, QueueError> {
+        let reply = self
+            .connection()
+            .await?
+            .request(
+                &format!("$JS.API.{}", subject),
+                &[],
+                body.to_string().as_bytes(),
+            )
+            .await?;
+        serde_json::from_slice(&reply.payload).map_err(|e| QueueError::Backend(e.to_string()))
+    }
+
+    /// Creates `topic`'s stream, or updates it to the current settings.
+    pub async fn ensure_stream(&self, topic: &str) -> Result<(), QueueError> {
+        let stream = name_for(topic);
+        if self.streams.lock().unwrap().contains(&stream) {
+            return Ok(());
+        }
+        let config = json!({
+            "name": stream,
+            "subjects": [topic],
+            "retention": "limits",
+            "storage": "file",
+            "discard": "old",
+            "num_replicas": self.config.replicas,
+            "duplicate_window": nanos(self.config.duplicate_window),
+            "max_age": self.config.max_age.map(nanos).unwrap_or(0),
+        });
+        let mut response = self
+            .api(&format!("STREAM.CREATE.{}", stream), &config)
+            .await?;
+        if api_error(&response).is_some_and(|(code, _)| code == STREAM_NAME_IN_USE) {
+            response = self
+                .api(&format!("STREAM.UPDATE.{}", stream), &config)
+                .await?;
+        }
+        if let Some((_, description)) = api_error(&response) {
+            return Err(QueueError::Backend(format!(
+                "stream {}: {}",
+                stream, description
+            )));
+        }
+        self.streams.lock().unwrap().insert(stream);
+        Ok(())
+    }
+
+    /// Creates the group's durable pull consumer. An existing consumer with
+    /// different settings is kept as is.
+    pub async fn ensure_consumer(&self, subscription: &Subscription) -> Result<(), QueueError> {
+        let stream = name_for(&subscription.topic);
+        let durable = name_for(&subscription.group);
+        let id = (stream.clone(), durable.clone());
+        if self.consumers.lock().unwrap().contains(&id) {
+            return Ok(());
+        }
+        self.ensure_stream(&subscription.topic).await?;
+        let mut config = json!({
+            "durable_name": durable,
+            "ack_policy": "explicit",
+            "deliver_policy": "all",
+            "ack_wait": nanos(self.config.ack_wait),
+            "max_ack_pending": self.config.max_ack_pending,
+            "filter_subject": subscription.topic,
+        });
+        if let Some(max_deliver) = self.config.max_deliver {
+            config["max_deliver"] = max_deliver.into();
+        }
+        let response = self
+            .api(
+                &format!("CONSUMER.CREATE.{}.{}", stream, durable),
+                &json!({ "stream_name": stream, "config": config }),
+            )
+            .await?;
+        match api_error(&response) {
+            None => {}
+            Some((CONSUMER_ALREADY_EXISTS, description)) => {
+                warn!(
+                    "Keeping existing consumer {} on {}: {}",
+                    durable, stream, description
+                );
+            }
+            Some((_, description)) => {
+                return Err(QueueError::Backend(format!(
+                    "consumer {} on {}: {}",
+                    durable, stream, description
+                )));
+            }
+        }
+        self.consumers.lock().unwrap().insert(id);
+        Ok(())
+    }
+
+    /// Stops redelivery of a message that can never be handled.
+    pub async fn reject(&self, delivery: &Delivery) -> Result<(), QueueError> {
+        self.connection()
+            .await?
+            .publish(&delivery.delivery_id, None, &[], b"+TERM")
+            .await
+    }
+
+    /// Redelivers a message after `delay` instead of waiting for `ack_wait`.
+    pub async fn retry_after(
+        &self,
+        delivery: &Delivery,
+        delay: Duration,
+    ) -> Result<(), QueueError> {
+        let nak = format!("-NAK {}", json!({ "delay": nanos(delay) }));
+        self.connection()
+            .await?
+            .publish(&delivery.delivery_id, None, &[], nak.as_bytes())
+            .await
+    }
+}
+
+fn name_for(topic: &str) -> String {
+    topic.replace(['.', '*', '>', ' ', '/', '\\'], "_")
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos() as u64
+}
+
+fn api_error(response: &Value) -> Option<(i64, String)> {
+    let error = response.get("error")?;
+    Some((
+        error["err_code"].as_i64().unwrap_or_default(),
+        error["description"]
+            .as_str()
+            .unwrap_or("unknown error")
+            .to_string(),
+    ))
+}
+
+/// Times delivered, from the ack subject: `$JS.ACK.<stream>.<consumer>.
+/// <delivered>.…` or, with a domain and account hash, `$JS.ACK.<domain>.
+/// <hash>.<stream>.<consumer>.<delivered>.…`.
+fn delivered_count(ack_subject: &str) -> u32 {
+    let tokens: Vec<&str> = ack_subject.split('.').collect();
+    let index = if tokens.len() == 9 { 4 } else { 6 };
+    tokens
+        .get(index)
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(1)
+}
+
+fn decode(topic: &str, message: &NatsMessage, ack: &str) -> Result<Delivery, QueueError> {
+    let headers: HashMap<String, String> = message
+        .headers
+        .iter()
+        .filter(|(name, _)| !name.starts_with("Nats-") && !name.eq_ignore_ascii_case(KEY_HEADER))
+        .cloned()
+        .collect();
+    Ok(Delivery {
+        delivery_id: ack.to_string(),
+        topic: topic.to_string(),
+        message: QueueMessage {
+            id: message.header("Nats-Msg-Id").unwrap_or(ack).to_string(),
+            key: message.header(KEY_HEADER).map(str::to_string),
+            payload: serde_json::from_slice(&message.payload)
+                .map_err(|e| QueueError::InvalidMessage(e.to_string()))?,
+            headers,
+        },
+        delivery_count: delivered_count(ack),
+    })
+}
+
+#[async_trait]
+impl MessageQueue for NatsQueue {
+    async fn publish(&self, topic: &str, message: &QueueMessage) -> Result<String, QueueError> {
+        self.ensure_stream(topic).await?;
+        let payload = serde_json::to_vec(&message.payload)
+            .map_err(|e| QueueError::InvalidMessage(e.to_string()))?;
+        let mut headers: Vec<(&str, &str)> = vec![("Nats-Msg-Id", message.id.as_str())];
+        if let Some(key) = &message.key {
+            headers.push((KEY_HEADER, key.as_str()));
+        }
+        for (name, value) in &message.headers {
+            headers.push((name.as_str(), value.as_str()));
+        }
+        let reply = self
+            .connection()
+            .await?
+            .request(topic, &headers, &payload)
+            .await?;
+        let ack: Value = serde_json::from_slice(&reply.payload)
+            .map_err(|e| QueueError::Backend(e.to_string()))?;
+        if let Some((_, description)) = api_error(&ack) {
+            return Err(QueueError::Backend(description));
+        }
+        Ok(ack["seq"].as_u64().unwrap_or_default().to_string())
+    }
+
+    async fn consume(
+        &self,
+        subscription: &Subscription,
+        max: usize,
+        wait: Duration,
+    ) -> Result<Vec<Delivery>, QueueError> {
+        self.ensure_consumer(subscription).await?;
+        let connection = self.connection().await?;
+        let batch = max.max(1);
+        let request = if wait.is_zero() {
+            json!({ "batch": batch, "no_wait": true })
+        } else {
+            json!({ "batch": batch, "expires": nanos(wait) })
+        };
+        let inbox = connection.new_inbox();
+        let (sid, mut messages) = connection.subscribe(&inbox).await?;
+        let subject = format!(
+            "$JS.API.CONSUMER.MSG.NEXT.{}.{}",
+            name_for(&subscription.topic),
+            name_for(&subscription.group)
+        );
+        if let Err(e) = connection
+            .publish(&subject, Some(&inbox), &[], request.to_string().as_bytes())
+            .await
+        {
+            connection.unsubscribe(sid).await;
+            return Err(e);
+        }
+
+        // The server ends a short pull with a 404/408 status; the deadline
+        // only guards against a lost one.
+        let deadline = Instant::now() + wait + self.config.request_timeout;
+        let mut deliveries = Vec::new();
+        while deliveries.len() < batch {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let message = match tokio::time::timeout(remaining, messages.recv()).await {
+                Ok(Some(message)) => message,
+                Ok(None) | Err(_) => break,
+            };
+            match message.status {
+                Some(100) => continue,
+                Some(_) => break,
+                None => {}
+            }
+            let Some(ack) = message.reply.as_deref() else {
+                continue;
+            };
+            match decode(&subscription.topic, &message, ack) {
+                Ok(delivery) => deliveries.push(delivery),
+                Err(e) => {
+                    warn!(
+                        "Terminating unreadable message on {}: {}",
+                        subscription.topic, e
+                    );
+                    let _ = connection.publish(ack, None, &[], b"+TERM").await;
+                }
+            }
+        }
+        connection.unsubscribe(sid).await;
+        Ok(deliveries)
+    }
+
+    async fn ack(
+        &self,
+        _subscription: &Subscription,
+        delivery: &Delivery,
+    ) -> Result<(), QueueError> {
+        self.connection()
+            .await?
+            .publish(&delivery.delivery_id, None, &[], b"+ACK")
+            .await
+    }
+
+    async fn claim_pending(
+        &self,
+        _subscription: &Subscription,
+        _min_idle: Duration,
+        _max: usize,
+    ) -> Result<Vec<Delivery>, QueueError> {
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,467 @@
+use super::{Delivery, MessageQueue, QueueError, QueueMessage, Subscription};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use lapin::message::Delivery as AmqpDelivery;
+use lapin::options::{
+    BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicPublishOptions, BasicQosOptions,
+    ConfirmSelectOptions, ExchangeDeclareOptions, QueueBindOptions, QueueDeclareOptions,
+};
+use lapin::types::{AMQPValue, FieldTable, LongString, ShortString};
+use lapin::{BasicProperties, Channel, Connection, Consumer, ExchangeKind};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex as AsyncMutex, OnceCell};
+use tracing::warn;
+
+#[derive(Debug, Clone)]
+pub struct RabbitMqConfig {
+    /// Unacked deliveries the broker hands each consumer at once.
+    pub prefetch: u16,
+    /// Declare a dead-letter exchange and queue per group; rejected
+    /// deliveries land in `{topic}.{group}.dead`.
+    pub dead_letter: bool,
+    /// Use quorum queues, which track delivery counts across consumers.
+    pub quorum: bool,
+    /// Quorum queues dead-letter a message after this many deliveries.
+    pub delivery_limit: Option<u32>,
+}
+
+impl Default for RabbitMqConfig {
+    fn default() -> Self {
+        Self {
+            prefetch: 32,
+            dead_letter: true,
+            quorum: true,
+            delivery_limit: Some(10),
+        }
+    }
+}
+
+impl RabbitMqConfig {
+    pub fn with_prefetch(mut self, prefetch: u16) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+
+    pub fn with_dead_letter(mut self, dead_letter: bool) -> Self {
+        self.dead_letter = dead_letter;
+        self
+    }
+
+    /// Classic queues instead of quorum ones; `delivery_limit` is ignored.
+    pub fn classic(mut self) -> Self {
+        self.quorum = false;
+        self
+    }
+
+    pub fn with_delivery_limit(mut self, limit: Option<u32>) -> Self {
+        self.delivery_limit = limit;
+        self
+    }
+}
+
+struct Held {
+    delivery: Delivery,
+    since: Instant,
+}
+
+struct SubscriptionState {
+    channel: Channel,
+    consumer: AsyncMutex<Consumer>,
+    unacked: Mutex<HashMap<u64, Held>>,
+}
+
+/// `MessageQueue` on RabbitMQ. Each topic is a durable fanout exchange and
+/// each group a durable queue `{topic}.{group}` bound to it, so every group
+/// sees every message. Publishes wait for the broker's confirm and are
+/// mandatory: publishing to a topic no group is bound to fails instead of
+/// dropping the message, so declare subscriptions up front with
+/// `declare_subscription`.
+///
+/// RabbitMQ itself redelivers the messages of a consumer whose channel
+/// closes. `claim_pending` only hands back this process's own deliveries
+/// left unacked for `min_idle`, so failed handlers are retried.
+pub struct RabbitMqQueue {
+    connection: Arc<Connection>,
+    config: RabbitMqConfig,
+    publisher: OnceCell<Channel>,
+    exchanges: Mutex<HashSet<String>>,
+    subscriptions: AsyncMutex<HashMap<Subscription, Arc<SubscriptionState>>>,
+}
+
+impl RabbitMqQueue {
+    pub fn new(connection: Arc<Connection>, config: RabbitMqConfig) -> Self {
+        Self {
+            connection,
+            config,
+            publisher: OnceCell::new(),
+            exchanges: Mutex::new(HashSet::new()),
+            subscriptions: AsyncMutex::new(HashMap::new()),
+        }
+    }
+
+    fn queue_name(subscription: &Subscription) -> String {
+        format!("{}.{}", subscription.topic, subscription.group)
+    }
+
+    async fn publisher(&self) -> Result<&Channel, QueueError> {
+        self.publisher
+            .get_or_try_init(|| async {
+                let channel = self.connection.create_channel().await?;
+                channel
+                    .confirm_select(ConfirmSelectOptions::default())
+                    .await?;
+                Ok::<_, QueueError>(channel)
+            })
+            .await
+    }
+
+    async fn declare_exchange(&self, channel: &Channel, topic: &str) -> Result<(), QueueError> {
+        if self.exchanges.lock().unwrap().contains(topic) {
+            return Ok(());
+        }
+        channel
+            .exchange_declare(
+                topic,
+                ExchangeKind::Fanout,
+                ExchangeDeclareOptions {
+                    durable: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+        self.exchanges.lock().unwrap().insert(topic.to_string());
+        Ok(())
+    }
+
+    /// Declares the topic exchange, the group's queue and binding, and its
+    /// dead-letter exchange and queue when enabled. Idempotent, but queue
+    /// arguments can't change once declared.
+    pub async fn declare_subscription(
+        &self,
+        subscription: &Subscription,
+    ) -> Result<(), QueueError> {
+        let channel = self.connection.create_channel().await?;
+        let result = self.declare_on(&channel, subscription).await;
+        let _ = channel.close(200, "declared").await;
+        result
+    }
+
+    async fn declare_on(
+        &self,
+        channel: &Channel,
+        subscription: &Subscription,
+    ) -> Result<(), QueueError> {
+        self.declare_exchange(channel, &subscription.topic).await?;
+        let queue = Self::queue_name(subscription);
+        let durable = QueueDeclareOptions {
+            durable: true,
+            ..Default::default()
+        };
+
+        let mut arguments = FieldTable::default();
+        if self.config.quorum {
+            arguments.insert("x-queue-type".into(), long_string("quorum"));
+            if let Some(limit) = self.config.delivery_limit {
+                arguments.insert("x-delivery-limit".into(), AMQPValue::LongUInt(limit));
+            }
+        }
+        if self.config.dead_letter {
+            let dlx = format!("{}.dlx", subscription.topic);
+            let dead = format!("{}.dead", queue);
+            channel
+                .exchange_declare(
+                    &dlx,
+                    ExchangeKind::Direct,
+                    ExchangeDeclareOptions {
+                        durable: true,
+                        ..Default::default()
+                    },
+                    FieldTable::default(),
+                )
+                .await?;
+            channel
+                .queue_declare(&dead, durable, FieldTable::default())
+                .await?;
+            channel
+                .queue_bind(
+                    &dead,
+                    &dlx,
+                    &queue,
+                    QueueBindOptions::default(),
+                    FieldTable::default(),
+                )
+                .await?;
+            arguments.insert("x-dead-letter-exchange".into(), long_string(&dlx));
+            arguments.insert("x-dead-letter-routing-key".into(), long_string(&queue));
+        }
+
+        channel.queue_declare(&queue, durable, arguments).await?;
+        channel
+            .queue_bind(
+                &queue,
+                &subscription.topic,
+                "",
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn subscription(
+        &self,
+        subscription: &Subscription,
+    ) -> Result<Arc<SubscriptionState>, QueueError> {
+        let mut subscriptions = self.subscriptions.lock().await;
+        if let Some(state) = subscriptions.get(subscription) {
+            if state.channel.status().connected() {
+                return Ok(state.clone());
+            }
+        }
+        let channel = self.connection.create_channel().await?;
+        self.declare_on(&channel, subscription).await?;
+        channel
+            .basic_qos(self.config.prefetch, BasicQosOptions::default())
+            .await?;
+        let consumer = channel
+            .basic_consume(
+                &Self::queue_name(subscription),
+                &subscription.consumer,
+                BasicConsumeOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+        let state = Arc::new(SubscriptionState {
+            channel,
+            consumer: AsyncMutex::new(consumer),
+            unacked: Mutex::new(HashMap::new()),
+        });
+        subscriptions.insert(subscription.clone(), state.clone());
+        Ok(state)
+    }
+
+    /// Changes the prefetch of an open subscription, e.g. to slow a consumer
+    /// whose downstream provider is throttling.
+    pub async fn set_prefetch(
+        &self,
+        subscription: &Subscription,
+        prefetch: u16,
+    ) -> Result<(), QueueError> {
+        let state = self.subscription(subscription).await?;
+        state
+            .channel
+            .basic_qos(prefetch, BasicQosOptions::default())
+            .await?;
+        Ok(())
+    }
+
+    /// Rejects a delivery without requeueing, dead-lettering it when the
+    /// group has a dead-letter queue and dropping it otherwise.
+    pub async fn reject(
+        &self,
+        subscription: &Subscription,
+        delivery: &Delivery,
+    ) -> Result<(), QueueError> {
+        let state = self.subscription(subscription).await?;
+        let tag = delivery_tag(delivery)?;
+        state.unacked.lock().unwrap().remove(&tag);
+        state
+            .channel
+            .basic_nack(tag, BasicNackOptions::default())
+            .await?;
+        Ok(())
+    }
+}
+
+fn long_string(value: &str) -> AMQPValue {
+    AMQPValue::LongString(LongString::from(value))
+}
+
+fn delivery_tag(delivery: &Delivery) -> Result<u64, QueueError> {
+    delivery.delivery_id.parse().map_err(|_| {
+        QueueError::InvalidMessage(format!("bad delivery tag {}", delivery.delivery_id))
+    })
+}
+
+fn decode(topic: &str, delivery: &AmqpDelivery) -> Result<Delivery, QueueError> {
+    let properties = &delivery.properties;
+    let mut headers = HashMap::new();
+    let mut previous = None;
+    if let Some(table) = properties.headers() {
+        for (name, value) in table.inner() {
+            if name.as_str() == "x-delivery-count" {
+                previous = value
+                    .as_long_long_int()
+                    .or_else(|| value.as_long_int().map(i64::from));
+            } else if !name.as_str().starts_with("x-") {
+                if let Some(value) = value.as_long_string() {
+                    headers.insert(
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                    );
+                }
+            }
+        }
+    }
+    let delivery_count = match previous {
+        Some(previous) => previous.max(0) as u32 + 1,
+        None if delivery.redelivered => 2,
+        None => 1,
+    };
+    let routing_key = delivery.routing_key.as_str();
+    Ok(Delivery {
+        delivery_id: delivery.delivery_tag.to_string(),
+        topic: topic.to_string(),
+        message: QueueMessage {
+            id: properties
+                .message_id()
+                .as_ref()
+                .map(ShortString::to_string)
+                .unwrap_or_else(|| delivery.delivery_tag.to_string()),
+            key: (!routing_key.is_empty()).then(|| routing_key.to_string()),
+            payload: serde_json::from_slice(&delivery.data)
+                .map_err(|e| QueueError::InvalidMessage(e.to_string()))?,
+            headers,
+        },
+        delivery_count,
+    })
+}
+
+#[async_trait]
+impl MessageQueue for RabbitMqQueue {
+    async fn publish(&self, topic: &str, message: &QueueMessage) -> Result<String, QueueError> {
+        let channel = self.publisher().await?;
+        self.declare_exchange(channel, topic).await?;
+        let payload = serde_json::to_vec(&message.payload)
+            .map_err(|e| QueueError::InvalidMessage(e.to_string()))?;
+        let mut headers = FieldTable::default();
+        for (name, value) in &message.headers {
+            headers.insert(name.as_str().into(), long_string(value));
+        }
+        let properties = BasicProperties::default()
+            .with_message_id(message.id.as_str().into())
+            .with_content_type("application/json".into())
+            .with_delivery_mode(2)
+            .with_headers(headers);
+        let confirmation = channel
+            .basic_publish(
+                topic,
+                message.key.as_deref().unwrap_or_default(),
+                BasicPublishOptions {
+                    mandatory: true,
+                    ..Default::default()
+                },
+                &payload,
+                properties,
+            )
+            .await?
+            .await?;
+        if confirmation.is_nack() {
+            return Err(QueueError::Backend(format!(
+                "broker nacked message {}",
+                message.id
+            )));
+        }
+        if confirmation.take_message().is_some() {
+            return Err(QueueError::Backend(format!(
+                "no queue bound to {}; declare its subscriptions first",
+                topic
+            )));
+        }
+        Ok(message.id.clone())
+    }
+
+    async fn consume(
+        &self,
+        subscription: &Subscription,
+        max: usize,
+        wait: Duration,
+    ) -> Result<Vec<Delivery>, QueueError> {
+        let state = self.subscription(subscription).await?;
+        let mut consumer = state.consumer.lock().await;
+        let deadline = Instant::now() + wait;
+        let mut deliveries = Vec::new();
+        while deliveries.len() < max.max(1) {
+            // Only wait for the first delivery; take whatever else is buffered.
+            let remaining = if deliveries.is_empty() {
+                deadline.saturating_duration_since(Instant::now())
+            } else {
+                Duration::ZERO
+            };
+            let delivery = match tokio::time::timeout(remaining, consumer.next()).await {
+                Err(_) => break,
+                Ok(None) => {
+                    drop(consumer);
+                    self.subscriptions.lock().await.remove(subscription);
+                    return Err(QueueError::Connection("consumer cancelled".to_string()));
+                }
+                Ok(Some(delivery)) => delivery?,
+            };
+            match decode(&subscription.topic, &delivery) {
+                Ok(decoded) => {
+                    state.unacked.lock().unwrap().insert(
+                        delivery.delivery_tag,
+                        Held {
+                            delivery: decoded.clone(),
+                            since: Instant::now(),
+                        },
+                    );
+                    deliveries.push(decoded);
+                }
+                Err(e) => {
+                    warn!(
+                        "Rejecting unreadable message on {}: {}",
+                        subscription.topic, e
+                    );
+                    state
+                        .channel
+                        .basic_nack(delivery.delivery_tag, BasicNackOptions::default())
+                        .await?;
+                }
+            }
+        }
+        Ok(deliveries)
+    }
+
+    async fn ack(
+        &self,
+        subscription: &Subscription,
+        delivery: &Delivery,
+    ) -> Result<(), QueueError> {
+        let state = self.subscription(subscription).await?;
+        let tag = delivery_tag(delivery)?;
+        state.unacked.lock().unwrap().remove(&tag);
+        state
+            .channel
+            .basic_ack(tag, BasicAckOptions::default())
+            .await?;
+        Ok(())
+    }
+
+    async fn claim_pending(
+        &self,
+        subscription: &Subscription,
+        min_idle: Duration,
+        max: usize,
+    ) -> Result<Vec<Delivery>, QueueError> {
+        let Some(state) = self.subscriptions.lock().await.get(subscription).cloned() else {
+            return Ok(Vec::new());
+        };
+        let mut unacked = state.unacked.lock().unwrap();
+        let mut claimed = Vec::new();
+        for held in unacked.values_mut() {
+            if claimed.len() >= max {
+                break;
+            }
+            if held.since.elapsed() >= min_idle {
+                held.since = Instant::now();
+                held.delivery.delivery_count += 1;
+                claimed.push(held.delivery.clone());
+            }
+        }
+        Ok(claimed)
+    }
+}
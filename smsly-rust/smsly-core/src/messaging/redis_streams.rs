@@ -0,0 +1,298 @@
+use super::{Delivery, MessageQueue, QueueError, QueueMessage, Subscription};
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+/// Stream entries as returned by `XREADGROUP`/`XCLAIM`: ID and flat
+/// field/value list. Entries deleted while pending come back without fields.
+type StreamEntry = (String, Vec<Vec<u8>>);
+
+#[derive(Debug, Clone)]
+pub struct RedisStreamsConfig {
+    pub key_prefix: String,
+    /// Streams are trimmed to roughly this many entries on publish.
+    pub max_len: Option<usize>,
+}
+
+impl Default for RedisStreamsConfig {
+    fn default() -> Self {
+        Self {
+            key_prefix: "smsly:stream".to_string(),
+            max_len: Some(1_000_000),
+        }
+    }
+}
+
+impl RedisStreamsConfig {
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_max_len(mut self, max_len: Option<usize>) -> Self {
+        self.max_len = max_len;
+        self
+    }
+}
+
+/// `MessageQueue` on Redis Streams (Redis 6.2+), one stream per topic.
+/// Groups are created on first use and start from the beginning of the
+/// stream, so messages published before any worker started are not lost.
+///
+/// Blocking reads use their own connection rather than the shared one, which
+/// a `BLOCK` would stall for every other command.
+pub struct RedisStreamsQueue {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    config: RedisStreamsConfig,
+    groups: Mutex<HashSet<(String, String)>>,
+}
+
+impl RedisStreamsQueue {
+    pub fn new(client: Client, config: RedisStreamsConfig) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            config,
+            groups: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn key(&self, topic: &str) -> String {
+        format!("{}:{}", self.config.key_prefix, topic)
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    async fn ensure_group(&self, subscription: &Subscription) -> Result<(), QueueError> {
+        let id = (subscription.topic.clone(), subscription.group.clone());
+        if self.groups.lock().unwrap().contains(&id) {
+            return Ok(());
+        }
+        let created: RedisResult<()> = redis::cmd("XGROUP")
+            .arg("CREATE")
+            .arg(self.key(&subscription.topic))
+            .arg(&subscription.group)
+            .arg("0")
+            .arg("MKSTREAM")
+            .query_async(&mut self.conn().await?)
+            .await;
+        match created {
+            Ok(()) => {}
+            Err(e) if e.code() == Some("BUSYGROUP") => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.groups.lock().unwrap().insert(id);
+        Ok(())
+    }
+
+    /// Number of entries currently in `topic`'s stream.
+    pub async fn len(&self, topic: &str) -> Result<u64, QueueError> {
+        Ok(redis::cmd("XLEN")
+            .arg(self.key(topic))
+            .query_async(&mut self.conn().await?)
+            .await?)
+    }
+
+    /// Deliveries handed out to the group but not yet acked.
+    pub async fn pending_count(&self, subscription: &Subscription) -> Result<u64, QueueError> {
+        self.ensure_group(subscription).await?;
+        let (count, ..): (u64, Option<String>, Option<String>, redis::Value) =
+            redis::cmd("XPENDING")
+                .arg(self.key(&subscription.topic))
+                .arg(&subscription.group)
+                .query_async(&mut self.conn().await?)
+                .await?;
+        Ok(count)
+    }
+
+    fn deliveries(
+        &self,
+        subscription: &Subscription,
+        entries: Vec<StreamEntry>,
+        counts: &HashMap<String, u32>,
+    ) -> Vec<Delivery> {
+        entries
+            .into_iter()
+            .filter_map(|(entry_id, fields)| {
+                let message = match decode(&fields) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        // Left pending; acking would silently drop it.
+                        warn!(
+                            "Skipping unreadable entry {} on {}: {}",
+                            entry_id, subscription.topic, e
+                        );
+                        return None;
+                    }
+                };
+                Some(Delivery {
+                    delivery_count: counts.get(&entry_id).copied().unwrap_or(1),
+                    delivery_id: entry_id,
+                    topic: subscription.topic.clone(),
+                    message,
+                })
+            })
+            .collect()
+    }
+}
+
+fn decode(fields: &[Vec<u8>]) -> Result<QueueMessage, QueueError> {
+    let fields: HashMap<&[u8], &[u8]> = fields
+        .chunks_exact(2)
+        .map(|pair| (pair[0].as_slice(), pair[1].as_slice()))
+        .collect();
+    let text = |name: &str| {
+        fields
+            .get(name.as_bytes())
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+    };
+    let id = text("id").ok_or_else(|| QueueError::InvalidMessage("missing id".to_string()))?;
+    let payload = fields
+        .get(b"payload".as_slice())
+        .ok_or_else(|| QueueError::InvalidMessage("missing payload".to_string()))?;
+    Ok(QueueMessage {
+        id,
+        key: text("key"),
+        payload: serde_json::from_slice(payload)
+            .map_err(|e| QueueError::InvalidMessage(e.to_string()))?,
+        headers: match fields.get(b"headers".as_slice()) {
+            Some(headers) => serde_json::from_slice(headers)
+                .map_err(|e| QueueError::InvalidMessage(e.to_string()))?,
+            None => HashMap::new(),
+        },
+    })
+}
+
+#[async_trait]
+impl MessageQueue for RedisStreamsQueue {
+    async fn publish(&self, topic: &str, message: &QueueMessage) -> Result<String, QueueError> {
+        let payload = serde_json::to_vec(&message.payload)
+            .map_err(|e| QueueError::InvalidMessage(e.to_string()))?;
+        let mut cmd = redis::cmd("XADD");
+        cmd.arg(self.key(topic));
+        if let Some(max_len) = self.config.max_len {
+            cmd.arg("MAXLEN").arg("~").arg(max_len);
+        }
+        cmd.arg("*")
+            .arg("id")
+            .arg(&message.id)
+            .arg("payload")
+            .arg(payload);
+        if let Some(key) = &message.key {
+            cmd.arg("key").arg(key);
+        }
+        if !message.headers.is_empty() {
+            let headers = serde_json::to_vec(&message.headers)
+                .map_err(|e| QueueError::InvalidMessage(e.to_string()))?;
+            cmd.arg("headers").arg(headers);
+        }
+        Ok(cmd.query_async(&mut self.conn().await?).await?)
+    }
+
+    async fn consume(
+        &self,
+        subscription: &Subscription,
+        max: usize,
+        wait: Duration,
+    ) -> Result<Vec<Delivery>, QueueError> {
+        self.ensure_group(subscription).await?;
+        let mut cmd = redis::cmd("XREADGROUP");
+        cmd.arg("GROUP")
+            .arg(&subscription.group)
+            .arg(&subscription.consumer)
+            .arg("COUNT")
+            .arg(max.max(1));
+        let reply: Option<Vec<(String, Vec<StreamEntry>)>> = if wait.is_zero() {
+            cmd.arg("STREAMS")
+                .arg(self.key(&subscription.topic))
+                .arg(">");
+            cmd.query_async(&mut self.conn().await?).await?
+        } else {
+            cmd.arg("BLOCK")
+                .arg(wait.as_millis() as u64)
+                .arg("STREAMS")
+                .arg(self.key(&subscription.topic))
+                .arg(">");
+            let mut conn = self.client.get_multiplexed_async_connection().await?;
+            cmd.query_async(&mut conn).await?
+        };
+        let entries = reply
+            .into_iter()
+            .flatten()
+            .flat_map(|(_, entries)| entries)
+            .collect();
+        Ok(self.deliveries(subscription, entries, &HashMap::new()))
+    }
+
+    async fn ack(
+        &self,
+        subscription: &Subscription,
+        delivery: &Delivery,
+    ) -> Result<(), QueueError> {
+        redis::cmd("XACK")
+            .arg(self.key(&subscription.topic))
+            .arg(&subscription.group)
+            .arg(&delivery.delivery_id)
+            .query_async::<_, i64>(&mut self.conn().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn claim_pending(
+        &self,
+        subscription: &Subscription,
+        min_idle: Duration,
+        max: usize,
+    ) -> Result<Vec<Delivery>, QueueError> {
+        self.ensure_group(subscription).await?;
+        let key = self.key(&subscription.topic);
+        let min_idle_ms = min_idle.as_millis() as u64;
+        let mut conn = self.conn().await?;
+
+        // ID, consumer, idle ms, times delivered.
+        let pending: Vec<(String, String, u64, u32)> = redis::cmd("XPENDING")
+            .arg(&key)
+            .arg(&subscription.group)
+            .arg("IDLE")
+            .arg(min_idle_ms)
+            .arg("-")
+            .arg("+")
+            .arg(max.max(1))
+            .query_async(&mut conn)
+            .await?;
+        if pending.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut cmd = redis::cmd("XCLAIM");
+        cmd.arg(&key)
+            .arg(&subscription.group)
+            .arg(&subscription.consumer)
+            .arg(min_idle_ms);
+        for (id, ..) in &pending {
+            cmd.arg(id);
+        }
+        let entries: Vec<Option<StreamEntry>> = cmd.query_async(&mut conn).await?;
+        // XCLAIM bumps the delivery count for the entries it returns.
+        let counts = pending
+            .iter()
+            .map(|(id, _, _, count)| (id.clone(), count + 1))
+            .collect();
+        Ok(self.deliveries(
+            subscription,
+            entries.into_iter().flatten().collect(),
+            &counts,
+        ))
+    }
+}
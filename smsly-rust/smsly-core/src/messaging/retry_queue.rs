@@ -0,0 +1,388 @@
+use super::scheduler::{report_expired, MOVE_DUE};
+use super::topics::{OutboundSend, OUTBOUND_SENDS};
+use super::{MessageQueue, QueueError};
+use crate::adapters::SendResult;
+use crate::metrics::GLOBAL_METRICS;
+use crate::retry::{classify_send, RetryClass, RetryPolicy};
+use crate::shutdown::ShutdownSignal;
+use chrono::{DateTime, TimeZone, Utc};
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone)]
+pub struct RetryQueueConfig {
+    pub key_prefix: String,
+    /// Delays and attempt limits; `max_elapsed` counts from the first
+    /// failure.
+    pub policy: RetryPolicy,
+    pub poll_interval: Duration,
+    pub batch_size: usize,
+    /// Claimed retries not published within this are put back, e.g. after
+    /// a replica crashed mid-dispatch.
+    pub inflight_timeout: Duration,
+    /// How long a dispatched send's attempt count is kept for its next
+    /// failure; `complete` drops it sooner.
+    pub attempts_ttl: Duration,
+}
+
+impl Default for RetryQueueConfig {
+    fn default() -> Self {
+        Self {
+            key_prefix: "smsly:retry".to_string(),
+            policy: RetryPolicy::new("send_retry")
+                .with_max_attempts(6)
+                .with_backoff(Duration::from_secs(30), Duration::from_secs(1800), 3.0)
+                .with_max_elapsed(Some(Duration::from_secs(6 * 3600))),
+            poll_interval: Duration::from_secs(1),
+            batch_size: 100,
+            inflight_timeout: Duration::from_secs(60),
+            attempts_ttl: Duration::from_secs(24 * 3600),
+        }
+    }
+}
+
+impl RetryQueueConfig {
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+}
+
+/// A failed send and its retry history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryJob {
+    pub send: OutboundSend,
+    /// Failed attempts so far.
+    pub attempts: u32,
+    /// Epoch seconds.
+    pub first_failed_at: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetryDecision {
+    Scheduled {
+        attempt: u32,
+        retry_at: DateTime<Utc>,
+    },
+    /// Retrying can't help, e.g. the number is invalid.
+    Permanent,
+    /// Out of attempts or time.
+    Exhausted { attempts: u32 },
+}
+
+/// Holds failed sends in Redis and republishes them to `OUTBOUND_SENDS`
+/// with growing delays, so retries survive restarts and deploys. Sends
+/// are keyed by `message_id`; their attempt count carries over from one
+/// failure to the next until `complete` or `attempts_ttl`.
+///
+/// The send worker calls `retry_failed` on each failure and reports the
+/// failure itself when the decision is `Permanent` or `Exhausted`. Any
+/// number of replicas can run `run`: claiming due sends is atomic, and
+/// the queue message ID is the `message_id` for deduplication. Sends whose
+/// validity ran out while waiting are reported on `DELIVERY_REPORTS` as
+/// failed with reason `Expired` instead.
+pub struct RetryQueue {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    queue: Arc<dyn MessageQueue>,
+    config: RetryQueueConfig,
+}
+
+impl RetryQueue {
+    pub fn new(client: Client, queue: Arc<dyn MessageQueue>, config: RetryQueueConfig) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            queue,
+            config,
+        }
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    fn key(&self, suffix: &str) -> String {
+        format!("{}:{}", self.config.key_prefix, suffix)
+    }
+
+    fn job_key(&self, id: &str) -> String {
+        format!("{}:job:{}", self.config.key_prefix, id)
+    }
+
+    async fn load(
+        &self,
+        conn: &mut ConnectionManager,
+        id: &str,
+    ) -> Result<Option<RetryJob>, QueueError> {
+        let job: Option<String> = redis::cmd("GET")
+            .arg(self.job_key(id))
+            .query_async(conn)
+            .await?;
+        job.map(|job| serde_json::from_str(&job))
+            .transpose()
+            .map_err(|e| QueueError::InvalidMessage(e.to_string()))
+    }
+
+    /// Records a failed attempt at `send` through `provider` and schedules
+    /// the next one, unless `classify_send` says it's permanent or the
+    /// policy is out of attempts or time. A throttled send waits as long
+    /// as the provider asked, up to `max_retry_after`.
+    pub async fn retry_failed(
+        &self,
+        send: &OutboundSend,
+        provider: &str,
+        result: &SendResult,
+    ) -> Result<RetryDecision, QueueError> {
+        let class = classify_send(provider, result);
+        let mut conn = self.conn().await?;
+        if class == RetryClass::NotRetryable {
+            self.remove(&mut conn, &send.message_id).await?;
+            return Ok(RetryDecision::Permanent);
+        }
+
+        let now = Utc::now();
+        let mut job = match self.load(&mut conn, &send.message_id).await? {
+            Some(job) => job,
+            None => RetryJob {
+                send: send.clone(),
+                attempts: 0,
+                first_failed_at: now.timestamp_millis() as f64 / 1000.0,
+                last_provider: None,
+                last_error: None,
+            },
+        };
+        job.send = send.clone();
+        job.attempts += 1;
+        job.last_provider = Some(provider.to_string());
+        job.last_error = result.error_message.clone().or(result.error_code.clone());
+
+        let policy = &self.config.policy;
+        let delay = match class {
+            RetryClass::RetryAfter(wait) => wait.min(policy.max_retry_after),
+            _ => policy.delay(job.attempts),
+        };
+        let elapsed = Duration::from_secs_f64(
+            (now.timestamp_millis() as f64 / 1000.0 - job.first_failed_at).max(0.0),
+        );
+        if job.attempts >= policy.max_attempts
+            || policy.max_elapsed.is_some_and(|max| elapsed + delay > max)
+        {
+            warn!(
+                "Giving up on send {} after {} attempts",
+                send.message_id, job.attempts
+            );
+            GLOBAL_METRICS.increment("send_retries_exhausted", 1, None);
+            self.remove(&mut conn, &send.message_id).await?;
+            return Ok(RetryDecision::Exhausted {
+                attempts: job.attempts,
+            });
+        }
+
+        let retry_at = now + chrono::Duration::from_std(delay).unwrap_or_default();
+        let payload =
+            serde_json::to_string(&job).map_err(|e| QueueError::InvalidMessage(e.to_string()))?;
+        redis::pipe()
+            .atomic()
+            .set(self.job_key(&send.message_id), payload)
+            .ignore()
+            .zadd(
+                self.key("due"),
+                &send.message_id,
+                retry_at.timestamp_millis(),
+            )
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        GLOBAL_METRICS.increment("send_retries_scheduled", 1, None);
+        info!(
+            "Send {} failed on attempt {}, retrying at {}",
+            send.message_id, job.attempts, retry_at
+        );
+        Ok(RetryDecision::Scheduled {
+            attempt: job.attempts,
+            retry_at,
+        })
+    }
+
+    /// Forgets a send's retry history once it went through.
+    pub async fn complete(&self, message_id: &str) -> Result<(), QueueError> {
+        let mut conn = self.conn().await?;
+        self.remove(&mut conn, message_id).await
+    }
+
+    async fn remove(&self, conn: &mut ConnectionManager, id: &str) -> Result<(), QueueError> {
+        redis::pipe()
+            .atomic()
+            .del(self.job_key(id))
+            .ignore()
+            .zrem(self.key("due"), id)
+            .ignore()
+            .zrem(self.key("inflight"), id)
+            .ignore()
+            .query_async::<_, ()>(conn)
+            .await?;
+        Ok(())
+    }
+
+    /// The send's retry history and next attempt, if it has one due.
+    pub async fn get(
+        &self,
+        message_id: &str,
+    ) -> Result<Option<(RetryJob, DateTime<Utc>)>, QueueError> {
+        let mut conn = self.conn().await?;
+        let score: Option<i64> = redis::cmd("ZSCORE")
+            .arg(self.key("due"))
+            .arg(message_id)
+            .query_async(&mut conn)
+            .await?;
+        let Some(score) = score else {
+            return Ok(None);
+        };
+        Ok(self.load(&mut conn, message_id).await?.map(|job| {
+            (
+                job,
+                Utc.timestamp_millis_opt(score).single().unwrap_or_default(),
+            )
+        }))
+    }
+
+    pub async fn pending_count(&self) -> Result<usize, QueueError> {
+        let mut conn = self.conn().await?;
+        Ok(redis::cmd("ZCARD")
+            .arg(self.key("due"))
+            .query_async(&mut conn)
+            .await?)
+    }
+
+    /// Republishes one batch of due retries, returning how many were
+    /// published.
+    pub async fn dispatch_due(&self) -> Result<usize, QueueError> {
+        let mut conn = self.conn().await?;
+        let now = Utc::now().timestamp_millis();
+        let inflight_timeout = self.config.inflight_timeout.as_millis() as i64;
+
+        let recovered: Vec<String> = MOVE_DUE
+            .key(self.key("inflight"))
+            .key(self.key("due"))
+            .arg(now - inflight_timeout)
+            .arg(self.config.batch_size)
+            .arg(now)
+            .invoke_async(&mut conn)
+            .await?;
+        if !recovered.is_empty() {
+            warn!("Requeued {} stalled send retries", recovered.len());
+        }
+
+        let ids: Vec<String> = MOVE_DUE
+            .key(self.key("due"))
+            .key(self.key("inflight"))
+            .arg(now)
+            .arg(self.config.batch_size)
+            .arg(now)
+            .invoke_async(&mut conn)
+            .await?;
+
+        let mut published = 0;
+        for id in ids {
+            let result = match self.load(&mut conn, &id).await {
+                Ok(Some(job)) if job.send.message.is_expired() => {
+                    warn!("Send {} expired while waiting to retry", id);
+                    report_expired(&*self.queue, &id).await.map(|()| false)
+                }
+                Ok(Some(job)) => self.publish(&id, &job.send).await.map(|()| true),
+                Ok(None) => Err(QueueError::InvalidMessage("missing payload".to_string())),
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(keep) => {
+                    let mut pipe = redis::pipe();
+                    pipe.atomic().zrem(self.key("inflight"), &id).ignore();
+                    if keep {
+                        pipe.expire(self.job_key(&id), self.config.attempts_ttl.as_secs() as i64)
+                            .ignore();
+                        published += 1;
+                    } else {
+                        pipe.del(self.job_key(&id)).ignore();
+                    }
+                    pipe.query_async::<_, ()>(&mut conn).await?;
+                }
+                Err(QueueError::InvalidMessage(e)) => {
+                    error!("Dropping unreadable send retry {}: {}", id, e);
+                    self.remove(&mut conn, &id).await?;
+                }
+                Err(e) => {
+                    warn!("Publishing send retry {} failed: {}", id, e);
+                    let retry_at = now + self.config.poll_interval.as_millis().max(1000) as i64;
+                    redis::pipe()
+                        .atomic()
+                        .zrem(self.key("inflight"), &id)
+                        .ignore()
+                        .zadd(self.key("due"), &id, retry_at)
+                        .ignore()
+                        .query_async::<_, ()>(&mut conn)
+                        .await?;
+                }
+            }
+        }
+        if published > 0 {
+            GLOBAL_METRICS.increment("send_retries_dispatched", published as i64, None);
+        }
+        Ok(published)
+    }
+
+    async fn publish(&self, id: &str, send: &OutboundSend) -> Result<(), QueueError> {
+        let message = OUTBOUND_SENDS.message(send)?.with_id(id);
+        self.queue
+            .publish(OUTBOUND_SENDS.name, &message)
+            .await
+            .map(|_| ())
+    }
+
+    /// Dispatches every `poll_interval` until `shutdown`, straight away
+    /// again while a backlog remains.
+    pub async fn run(&self, shutdown: ShutdownSignal) {
+        while !shutdown.is_triggered() {
+            let mut backlog = false;
+            match self.dispatch_due().await {
+                Ok(published) => backlog = published >= self.config.batch_size,
+                Err(e) => error!("Dispatching send retries failed: {}", e),
+            }
+            if backlog {
+                continue;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(self.config.poll_interval) => {}
+                _ = shutdown.clone().wait() => {}
+            }
+        }
+    }
+}
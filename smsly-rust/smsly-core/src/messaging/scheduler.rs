@@ -0,0 +1,435 @@
+use super::topics::{OutboundSend, DELIVERY_REPORTS, OUTBOUND_SENDS};
+use super::{MessageQueue, QueueError};
+use crate::adapters::{MessageStatus, WebhookEvent};
+use crate::dlr::DlrReason;
+use crate::metrics::GLOBAL_METRICS;
+use crate::shutdown::ShutdownSignal;
+use chrono::{DateTime, TimeZone, Utc};
+use lazy_static::lazy_static;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult, Script};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+lazy_static! {
+    /// Moves up to ARGV[2] members scored at most ARGV[1] from one sorted
+    /// set to another with score ARGV[3], returning them.
+    pub(super) static ref MOVE_DUE: Script = Script::new(
+        r#"
+        local ids = redis.call("ZRANGEBYSCORE", KEYS[1], "-inf", ARGV[1], "LIMIT", 0, ARGV[2])
+        for _, id in ipairs(ids) do
+            redis.call("ZREM", KEYS[1], id)
+            redis.call("ZADD", KEYS[2], ARGV[3], id)
+        end
+        return ids
+    "#
+    );
+    /// Takes or renews the lock for token ARGV[1]; 1 if held afterwards.
+    static ref HOLD_LOCK: Script = Script::new(
+        r#"
+        local holder = redis.call("GET", KEYS[1])
+        if not holder then
+            redis.call("SET", KEYS[1], ARGV[1], "PX", ARGV[2])
+            return 1
+        end
+        if holder == ARGV[1] then
+            redis.call("PEXPIRE", KEYS[1], ARGV[2])
+            return 1
+        end
+        return 0
+    "#
+    );
+    static ref RELEASE_LOCK: Script = Script::new(
+        r#"
+        if redis.call("GET", KEYS[1]) == ARGV[1] then
+            return redis.call("DEL", KEYS[1])
+        end
+        return 0
+    "#
+    );
+    /// Only sends still waiting can be cancelled or moved; once claimed for
+    /// dispatch they're gone.
+    static ref CANCEL: Script = Script::new(
+        r#"
+        if redis.call("ZREM", KEYS[1], ARGV[1]) == 1 then
+            redis.call("DEL", KEYS[2])
+            return 1
+        end
+        return 0
+    "#
+    );
+    static ref RESCHEDULE: Script = Script::new(
+        r#"
+        if redis.call("ZSCORE", KEYS[1], ARGV[1]) then
+            redis.call("ZADD", KEYS[1], ARGV[2], ARGV[1])
+            return 1
+        end
+        return 0
+    "#
+    );
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    pub key_prefix: String,
+    pub poll_interval: Duration,
+    pub batch_size: usize,
+    /// Leader lock lifetime, renewed every poll; a new leader takes over
+    /// this long after the old one stops.
+    pub lock_ttl: Duration,
+    /// Claimed sends not published within this are put back, e.g. after
+    /// the leader crashed mid-dispatch.
+    pub inflight_timeout: Duration,
+    /// Delay before retrying a send whose publish failed.
+    pub retry_delay: Duration,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            key_prefix: "smsly:scheduled".to_string(),
+            poll_interval: Duration::from_secs(1),
+            batch_size: 100,
+            lock_ttl: Duration::from_secs(15),
+            inflight_timeout: Duration::from_secs(60),
+            retry_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl SchedulerConfig {
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_lock_ttl(mut self, ttl: Duration) -> Self {
+        self.lock_ttl = ttl;
+        self
+    }
+}
+
+/// A send waiting for its `send_at`.
+#[derive(Debug, Clone)]
+pub struct ScheduledSend {
+    pub send: OutboundSend,
+    pub send_at: DateTime<Utc>,
+}
+
+/// Holds sends with a `send_at` in Redis and publishes them to
+/// `OUTBOUND_SENDS` once due. Sends are keyed by `message_id`, so
+/// scheduling one again replaces it.
+///
+/// Any number of replicas can schedule and cancel; only the one holding the
+/// leader lock dispatches. Due sends are moved to an in-flight set before
+/// publishing and removed after, so a leader crash republishes rather than
+/// drops them; the queue message ID is the `message_id` for deduplication.
+/// Sends whose validity ran out while waiting are reported on
+/// `DELIVERY_REPORTS` as failed with reason `Expired` instead.
+pub struct Scheduler {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    queue: Arc<dyn MessageQueue>,
+    config: SchedulerConfig,
+    token: String,
+    leader: AtomicBool,
+}
+
+impl Scheduler {
+    pub fn new(client: Client, queue: Arc<dyn MessageQueue>, config: SchedulerConfig) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            queue,
+            config,
+            token: Uuid::new_v4().to_string(),
+            leader: AtomicBool::new(false),
+        }
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    fn key(&self, suffix: &str) -> String {
+        format!("{}:{}", self.config.key_prefix, suffix)
+    }
+
+    fn job_key(&self, id: &str) -> String {
+        format!("{}:job:{}", self.config.key_prefix, id)
+    }
+
+    pub async fn schedule(
+        &self,
+        send: &OutboundSend,
+        send_at: DateTime<Utc>,
+    ) -> Result<(), QueueError> {
+        let job =
+            serde_json::to_string(send).map_err(|e| QueueError::InvalidMessage(e.to_string()))?;
+        let mut conn = self.conn().await?;
+        redis::pipe()
+            .atomic()
+            .set(self.job_key(&send.message_id), job)
+            .ignore()
+            .zadd(
+                self.key("due"),
+                &send.message_id,
+                send_at.timestamp_millis(),
+            )
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns false if the send isn't waiting, e.g. it was already sent.
+    pub async fn cancel(&self, message_id: &str) -> Result<bool, QueueError> {
+        let mut conn = self.conn().await?;
+        let cancelled: i32 = CANCEL
+            .key(self.key("due"))
+            .key(self.job_key(message_id))
+            .arg(message_id)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(cancelled == 1)
+    }
+
+    /// Returns false if the send isn't waiting.
+    pub async fn reschedule(
+        &self,
+        message_id: &str,
+        send_at: DateTime<Utc>,
+    ) -> Result<bool, QueueError> {
+        let mut conn = self.conn().await?;
+        let moved: i32 = RESCHEDULE
+            .key(self.key("due"))
+            .arg(message_id)
+            .arg(send_at.timestamp_millis())
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(moved == 1)
+    }
+
+    /// The send if it's still waiting.
+    pub async fn get(&self, message_id: &str) -> Result<Option<ScheduledSend>, QueueError> {
+        let mut conn = self.conn().await?;
+        let (job, score): (Option<String>, Option<i64>) = redis::pipe()
+            .get(self.job_key(message_id))
+            .zscore(self.key("due"), message_id)
+            .query_async(&mut conn)
+            .await?;
+        let (Some(job), Some(score)) = (job, score) else {
+            return Ok(None);
+        };
+        Ok(Some(ScheduledSend {
+            send: serde_json::from_str(&job)
+                .map_err(|e| QueueError::InvalidMessage(e.to_string()))?,
+            send_at: Utc.timestamp_millis_opt(score).single().unwrap_or_default(),
+        }))
+    }
+
+    pub async fn pending_count(&self) -> Result<usize, QueueError> {
+        let mut conn = self.conn().await?;
+        Ok(redis::cmd("ZCARD")
+            .arg(self.key("due"))
+            .query_async(&mut conn)
+            .await?)
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.leader.load(Ordering::SeqCst)
+    }
+
+    /// Takes or renews the leader lock, returning whether this replica leads.
+    pub async fn hold_leadership(&self) -> Result<bool, QueueError> {
+        let mut conn = self.conn().await?;
+        let held: i32 = HOLD_LOCK
+            .key(self.key("leader"))
+            .arg(&self.token)
+            .arg(self.config.lock_ttl.as_millis() as u64)
+            .invoke_async(&mut conn)
+            .await?;
+        let held = held == 1;
+        if held != self.leader.swap(held, Ordering::SeqCst) {
+            if held {
+                info!("Scheduler {} became leader", self.token);
+            } else {
+                warn!("Scheduler {} lost leadership", self.token);
+            }
+        }
+        Ok(held)
+    }
+
+    pub async fn release_leadership(&self) -> Result<(), QueueError> {
+        let mut conn = self.conn().await?;
+        let _: i32 = RELEASE_LOCK
+            .key(self.key("leader"))
+            .arg(&self.token)
+            .invoke_async(&mut conn)
+            .await?;
+        self.leader.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Publishes one batch of due sends, returning how many were published.
+    /// Call only while leading; `run` does this.
+    pub async fn dispatch_due(&self) -> Result<usize, QueueError> {
+        let mut conn = self.conn().await?;
+        let now = Utc::now().timestamp_millis();
+        let inflight_timeout = self.config.inflight_timeout.as_millis() as i64;
+
+        let recovered: Vec<String> = MOVE_DUE
+            .key(self.key("inflight"))
+            .key(self.key("due"))
+            .arg(now - inflight_timeout)
+            .arg(self.config.batch_size)
+            .arg(now)
+            .invoke_async(&mut conn)
+            .await?;
+        if !recovered.is_empty() {
+            warn!("Requeued {} stalled scheduled sends", recovered.len());
+        }
+
+        let ids: Vec<String> = MOVE_DUE
+            .key(self.key("due"))
+            .key(self.key("inflight"))
+            .arg(now)
+            .arg(self.config.batch_size)
+            .arg(now)
+            .invoke_async(&mut conn)
+            .await?;
+
+        let mut published = 0;
+        for id in ids {
+            let job: Option<String> = redis::cmd("GET")
+                .arg(self.job_key(&id))
+                .query_async(&mut conn)
+                .await?;
+            let send = job.map(|job| serde_json::from_str::<OutboundSend>(&job));
+            let result = match send {
+                Some(Ok(send)) => self.publish_due(&id, &send).await,
+                Some(Err(e)) => Err(QueueError::InvalidMessage(e.to_string())),
+                None => Err(QueueError::InvalidMessage("missing payload".to_string())),
+            };
+
+            match result {
+                Ok(()) => {
+                    redis::pipe()
+                        .atomic()
+                        .del(self.job_key(&id))
+                        .ignore()
+                        .zrem(self.key("inflight"), &id)
+                        .ignore()
+                        .query_async::<_, ()>(&mut conn)
+                        .await?;
+                    published += 1;
+                }
+                Err(QueueError::InvalidMessage(e)) => {
+                    error!("Dropping unreadable scheduled send {}: {}", id, e);
+                    redis::pipe()
+                        .atomic()
+                        .del(self.job_key(&id))
+                        .ignore()
+                        .zrem(self.key("inflight"), &id)
+                        .ignore()
+                        .query_async::<_, ()>(&mut conn)
+                        .await?;
+                }
+                Err(e) => {
+                    warn!("Publishing scheduled send {} failed: {}", id, e);
+                    let retry_at = now + self.config.retry_delay.as_millis() as i64;
+                    redis::pipe()
+                        .atomic()
+                        .zrem(self.key("inflight"), &id)
+                        .ignore()
+                        .zadd(self.key("due"), &id, retry_at)
+                        .ignore()
+                        .query_async::<_, ()>(&mut conn)
+                        .await?;
+                }
+            }
+        }
+        if published > 0 {
+            GLOBAL_METRICS.increment("scheduled_sends_dispatched", published as i64, None);
+        }
+        Ok(published)
+    }
+
+    /// Publishes a due send, or its expiry as a failed delivery report if
+    /// its validity ran out while it waited.
+    async fn publish_due(&self, id: &str, send: &OutboundSend) -> Result<(), QueueError> {
+        if send.message.is_expired() {
+            warn!("Scheduled send {} expired before its send time", id);
+            return report_expired(&*self.queue, id).await;
+        }
+        let message = OUTBOUND_SENDS.message(send)?.with_id(id);
+        self.queue
+            .publish(OUTBOUND_SENDS.name, &message)
+            .await
+            .map(|_| ())
+    }
+
+    /// Competes for the leader lock every `poll_interval` and dispatches
+    /// while leading, until `shutdown`; then hands the lock over.
+    pub async fn run(&self, shutdown: ShutdownSignal) {
+        while !shutdown.is_triggered() {
+            let mut backlog = false;
+            match self.hold_leadership().await {
+                Ok(true) => match self.dispatch_due().await {
+                    Ok(published) => backlog = published >= self.config.batch_size,
+                    Err(e) => error!("Dispatching scheduled sends failed: {}", e),
+                },
+                Ok(false) => {}
+                Err(e) => {
+                    self.leader.store(false, Ordering::SeqCst);
+                    error!("Scheduler leader election failed: {}", e);
+                }
+            }
+            if backlog {
+                continue;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(self.config.poll_interval) => {}
+                _ = shutdown.clone().wait() => {}
+            }
+        }
+        if self.is_leader() {
+            if let Err(e) = self.release_leadership().await {
+                warn!("Releasing scheduler leadership failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Reports a send whose validity ran out while it waited as a failed
+/// delivery with reason `Expired`.
+pub(super) async fn report_expired(queue: &dyn MessageQueue, id: &str) -> Result<(), QueueError> {
+    GLOBAL_METRICS.increment("messages_expired", 1, None);
+    let report = WebhookEvent {
+        provider_message_id: id.to_string(),
+        status: MessageStatus::Failed,
+        timestamp: Some(Utc::now().timestamp_millis() as f64 / 1000.0),
+        error_code: Some("expired".to_string()),
+        error_message: Some("Validity period elapsed before sending".to_string()),
+        reason: Some(DlrReason::Expired),
+        raw_payload: None,
+    };
+    DELIVERY_REPORTS.publish(queue, &report).await.map(|_| ())
+}
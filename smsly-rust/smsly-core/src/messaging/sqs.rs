@@ -0,0 +1,451 @@
+use super::{Delivery, MessageQueue, QueueError, QueueMessage, Subscription};
+use crate::providers::http_client;
+use crate::providers::sns::{host_header, sign_v4, SigningScope};
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{Client, Url};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+const JSON_CONTENT_TYPE: &str = "application/x-amz-json-1.0";
+/// Message attributes carrying `QueueMessage::id` and `key`; headers are
+/// sent as the remaining attributes.
+const ID_ATTRIBUTE: &str = "smsly-message-id";
+const KEY_ATTRIBUTE: &str = "smsly-message-key";
+/// SQS caps batches at 10 entries and long polls at 20 seconds.
+const MAX_BATCH: usize = 10;
+const MAX_WAIT: Duration = Duration::from_secs(20);
+
+#[derive(Debug, Clone)]
+pub struct SqsConfig {
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    /// Defaults to `https://sqs.<region>.amazonaws.com/`.
+    pub endpoint: String,
+    /// Overrides the queue's default for received messages.
+    pub visibility_timeout: Option<Duration>,
+    /// Queue URL per topic; others are looked up by name with `GetQueueUrl`.
+    pub queue_urls: HashMap<String, String>,
+}
+
+impl SqsConfig {
+    pub fn new(region: &str, access_key_id: &str, secret_access_key: &str) -> Self {
+        Self {
+            region: region.to_string(),
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+            session_token: None,
+            endpoint: format!("https://sqs.{}.amazonaws.com/", region),
+            visibility_timeout: None,
+            queue_urls: HashMap::new(),
+        }
+    }
+
+    /// Reads the standard `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`,
+    /// `AWS_SESSION_TOKEN` and `AWS_REGION` (or `AWS_DEFAULT_REGION`).
+    pub fn from_env() -> Option<Self> {
+        let non_empty = |key: &str| env::var(key).ok().filter(|v| !v.is_empty());
+        let region = non_empty("AWS_REGION").or_else(|| non_empty("AWS_DEFAULT_REGION"))?;
+        let mut config = Self::new(
+            &region,
+            &non_empty("AWS_ACCESS_KEY_ID")?,
+            &non_empty("AWS_SECRET_ACCESS_KEY")?,
+        );
+        config.session_token = non_empty("AWS_SESSION_TOKEN");
+        if let Some(endpoint) = non_empty("AWS_SQS_ENDPOINT") {
+            config.endpoint = endpoint;
+        }
+        Some(config)
+    }
+
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_string();
+        self
+    }
+
+    pub fn with_visibility_timeout(mut self, timeout: Duration) -> Self {
+        self.visibility_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_queue_url(mut self, topic: &str, url: &str) -> Self {
+        self.queue_urls.insert(topic.to_string(), url.to_string());
+        self
+    }
+}
+
+/// `MessageQueue` on Amazon SQS through its JSON API, signed with SigV4.
+/// Each topic is one queue, named after the topic with `.` replaced by `-`
+/// unless mapped with `with_queue_url`. FIFO queues (`.fifo`) get the
+/// message ID as deduplication ID and its key as message group.
+///
+/// SQS has no consumer groups: every consumer of a queue competes for its
+/// messages, whatever its `Subscription::group`. To fan a topic out, publish
+/// through an SNS topic with one queue per group and map them explicitly.
+///
+/// Received messages reappear once their visibility timeout runs out
+/// without a delete, so `claim_pending` returns nothing; long handlers keep
+/// theirs hidden with `extend_visibility` or `spawn_visibility_heartbeat`.
+pub struct SqsQueue {
+    config: SqsConfig,
+    client: Client,
+    urls: Mutex<HashMap<String, String>>,
+}
+
+impl SqsQueue {
+    pub fn new(config: SqsConfig) -> Self {
+        Self {
+            urls: Mutex::new(config.queue_urls.clone()),
+            config,
+            client: http_client(),
+        }
+    }
+
+    async fn call(&self, action: &str, request: &Value) -> Result<Value, QueueError> {
+        let url =
+            Url::parse(&self.config.endpoint).map_err(|e| QueueError::Connection(e.to_string()))?;
+        let host = host_header(&url).map_err(QueueError::Connection)?;
+        let body = request.to_string();
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let scope = SigningScope {
+            region: &self.config.region,
+            service: "sqs",
+            access_key_id: &self.config.access_key_id,
+            secret_access_key: &self.config.secret_access_key,
+            session_token: self.config.session_token.as_deref(),
+        };
+        let authorization = sign_v4(
+            &scope,
+            "POST",
+            &host,
+            url.path(),
+            &amz_date,
+            JSON_CONTENT_TYPE,
+            &body,
+        );
+
+        let mut http = self
+            .client
+            .post(url)
+            .header("Content-Type", JSON_CONTENT_TYPE)
+            .header("X-Amz-Target", format!("AmazonSQS.{}", action))
+            .header("X-Amz-Date", &amz_date)
+            .header("Authorization", authorization);
+        if let Some(token) = &self.config.session_token {
+            http = http.header("X-Amz-Security-Token", token);
+        }
+        let response = http
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| QueueError::Connection(e.to_string()))?;
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        if status.is_success() {
+            return Ok(data);
+        }
+        let kind = data["__type"]
+            .as_str()
+            .and_then(|t| t.rsplit('#').next())
+            .unwrap_or("UnknownError");
+        Err(QueueError::Backend(format!(
+            "SQS {} failed ({}): {}",
+            action,
+            kind,
+            data["message"].as_str().unwrap_or_default()
+        )))
+    }
+
+    /// URL of `topic`'s queue, looked up once and cached.
+    pub async fn queue_url(&self, topic: &str) -> Result<String, QueueError> {
+        if let Some(url) = self.urls.lock().unwrap().get(topic) {
+            return Ok(url.clone());
+        }
+        let data = self
+            .call(
+                "GetQueueUrl",
+                &json!({ "QueueName": topic.replace('.', "-") }),
+            )
+            .await?;
+        let url = data["QueueUrl"]
+            .as_str()
+            .ok_or_else(|| QueueError::Backend("GetQueueUrl returned no URL".to_string()))?
+            .to_string();
+        self.urls
+            .lock()
+            .unwrap()
+            .insert(topic.to_string(), url.clone());
+        Ok(url)
+    }
+
+    fn entry(url: &str, message: &QueueMessage) -> Result<Map<String, Value>, QueueError> {
+        let string = |value: &str| json!({ "DataType": "String", "StringValue": value });
+        let mut attributes = Map::new();
+        attributes.insert(ID_ATTRIBUTE.to_string(), string(&message.id));
+        if let Some(key) = &message.key {
+            attributes.insert(KEY_ATTRIBUTE.to_string(), string(key));
+        }
+        for (name, value) in &message.headers {
+            attributes.insert(name.clone(), string(value));
+        }
+        let mut entry = Map::new();
+        entry.insert(
+            "MessageBody".to_string(),
+            serde_json::to_string(&message.payload)
+                .map_err(|e| QueueError::InvalidMessage(e.to_string()))?
+                .into(),
+        );
+        entry.insert("MessageAttributes".to_string(), attributes.into());
+        if url.ends_with(".fifo") {
+            entry.insert(
+                "MessageDeduplicationId".to_string(),
+                message.id.clone().into(),
+            );
+            entry.insert(
+                "MessageGroupId".to_string(),
+                message.key.as_deref().unwrap_or("default").into(),
+            );
+        }
+        Ok(entry)
+    }
+
+    /// Publishes in batches of 10 and returns the SQS message IDs in order.
+    /// Fails if any entry is rejected; the others are still sent.
+    pub async fn publish_batch(
+        &self,
+        topic: &str,
+        messages: &[QueueMessage],
+    ) -> Result<Vec<String>, QueueError> {
+        let url = self.queue_url(topic).await?;
+        let mut ids = Vec::with_capacity(messages.len());
+        let mut rejected = Vec::new();
+        for chunk in messages.chunks(MAX_BATCH) {
+            let entries = chunk
+                .iter()
+                .enumerate()
+                .map(|(i, message)| {
+                    let mut entry = Self::entry(&url, message)?;
+                    entry.insert("Id".to_string(), i.to_string().into());
+                    Ok(Value::Object(entry))
+                })
+                .collect::<Result<Vec<_>, QueueError>>()?;
+            let data = self
+                .call(
+                    "SendMessageBatch",
+                    &json!({ "QueueUrl": url, "Entries": entries }),
+                )
+                .await?;
+            let mut sent: HashMap<&str, &str> = HashMap::new();
+            for ok in data["Successful"].as_array().into_iter().flatten() {
+                if let (Some(id), Some(message_id)) = (ok["Id"].as_str(), ok["MessageId"].as_str())
+                {
+                    sent.insert(id, message_id);
+                }
+            }
+            for failed in data["Failed"].as_array().into_iter().flatten() {
+                rejected.push(format!(
+                    "{}: {}",
+                    failed["Code"].as_str().unwrap_or_default(),
+                    failed["Message"].as_str().unwrap_or_default()
+                ));
+            }
+            for i in 0..chunk.len() {
+                if let Some(message_id) = sent.get(i.to_string().as_str()) {
+                    ids.push(message_id.to_string());
+                }
+            }
+        }
+        if !rejected.is_empty() {
+            return Err(QueueError::Backend(format!(
+                "{} of {} messages rejected: {}",
+                rejected.len(),
+                messages.len(),
+                rejected.join("; ")
+            )));
+        }
+        Ok(ids)
+    }
+
+    /// Deletes handled deliveries in batches of 10.
+    pub async fn ack_batch(&self, topic: &str, deliveries: &[Delivery]) -> Result<(), QueueError> {
+        let url = self.queue_url(topic).await?;
+        for chunk in deliveries.chunks(MAX_BATCH) {
+            let entries: Vec<Value> = chunk
+                .iter()
+                .enumerate()
+                .map(|(i, d)| json!({ "Id": i.to_string(), "ReceiptHandle": d.delivery_id }))
+                .collect();
+            let data = self
+                .call(
+                    "DeleteMessageBatch",
+                    &json!({ "QueueUrl": url, "Entries": entries }),
+                )
+                .await?;
+            if let Some(failed) = data["Failed"].as_array().filter(|f| !f.is_empty()) {
+                return Err(QueueError::Backend(format!(
+                    "{} deletes failed: {}",
+                    failed.len(),
+                    failed[0]["Message"].as_str().unwrap_or_default()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps a delivery hidden for `timeout` from now; zero makes it visible
+    /// again immediately.
+    pub async fn extend_visibility(
+        &self,
+        delivery: &Delivery,
+        timeout: Duration,
+    ) -> Result<(), QueueError> {
+        let url = self.queue_url(&delivery.topic).await?;
+        self.call(
+            "ChangeMessageVisibility",
+            &json!({
+                "QueueUrl": url,
+                "ReceiptHandle": delivery.delivery_id,
+                "VisibilityTimeout": timeout.as_secs(),
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Extends the delivery's visibility to `timeout` every `timeout / 2`
+    /// until the task is aborted; abort it once the handler finishes.
+    pub fn spawn_visibility_heartbeat(
+        self: Arc<Self>,
+        delivery: Delivery,
+        timeout: Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(timeout / 2);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.extend_visibility(&delivery, timeout).await {
+                    warn!(
+                        "Could not extend visibility of {}: {}",
+                        delivery.message.id, e
+                    );
+                    return;
+                }
+            }
+        })
+    }
+}
+
+fn decode(topic: &str, message: &Value) -> Result<Delivery, QueueError> {
+    let receipt = message["ReceiptHandle"]
+        .as_str()
+        .ok_or_else(|| QueueError::InvalidMessage("missing receipt handle".to_string()))?;
+    let mut headers = HashMap::new();
+    let mut id = None;
+    let mut key = None;
+    if let Some(attributes) = message["MessageAttributes"].as_object() {
+        for (name, attribute) in attributes {
+            let Some(value) = attribute["StringValue"].as_str() else {
+                continue;
+            };
+            match name.as_str() {
+                ID_ATTRIBUTE => id = Some(value.to_string()),
+                KEY_ATTRIBUTE => key = Some(value.to_string()),
+                _ => {
+                    headers.insert(name.clone(), value.to_string());
+                }
+            }
+        }
+    }
+    let body = message["Body"].as_str().unwrap_or_default();
+    Ok(Delivery {
+        delivery_id: receipt.to_string(),
+        topic: topic.to_string(),
+        message: QueueMessage {
+            id: id
+                .or_else(|| message["MessageId"].as_str().map(str::to_string))
+                .unwrap_or_default(),
+            key,
+            payload: serde_json::from_str(body)
+                .map_err(|e| QueueError::InvalidMessage(e.to_string()))?,
+            headers,
+        },
+        delivery_count: message["Attributes"]["ApproximateReceiveCount"]
+            .as_str()
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(1),
+    })
+}
+
+#[async_trait]
+impl MessageQueue for SqsQueue {
+    async fn publish(&self, topic: &str, message: &QueueMessage) -> Result<String, QueueError> {
+        let url = self.queue_url(topic).await?;
+        let mut request = Self::entry(&url, message)?;
+        request.insert("QueueUrl".to_string(), url.into());
+        let data = self.call("SendMessage", &Value::Object(request)).await?;
+        Ok(data["MessageId"].as_str().unwrap_or_default().to_string())
+    }
+
+    async fn consume(
+        &self,
+        subscription: &Subscription,
+        max: usize,
+        wait: Duration,
+    ) -> Result<Vec<Delivery>, QueueError> {
+        let url = self.queue_url(&subscription.topic).await?;
+        let mut request = json!({
+            "QueueUrl": url,
+            "MaxNumberOfMessages": max.clamp(1, MAX_BATCH),
+            "WaitTimeSeconds": wait.min(MAX_WAIT).as_secs(),
+            "MessageAttributeNames": ["All"],
+            "MessageSystemAttributeNames": ["ApproximateReceiveCount"],
+        });
+        if let Some(timeout) = self.config.visibility_timeout {
+            request["VisibilityTimeout"] = timeout.as_secs().into();
+        }
+        let data = self.call("ReceiveMessage", &request).await?;
+        let mut deliveries = Vec::new();
+        for message in data["Messages"].as_array().into_iter().flatten() {
+            match decode(&subscription.topic, message) {
+                Ok(delivery) => deliveries.push(delivery),
+                // Left to reappear and eventually reach the redrive queue.
+                Err(e) => warn!(
+                    "Skipping unreadable message on {}: {}",
+                    subscription.topic, e
+                ),
+            }
+        }
+        Ok(deliveries)
+    }
+
+    async fn ack(
+        &self,
+        subscription: &Subscription,
+        delivery: &Delivery,
+    ) -> Result<(), QueueError> {
+        let url = self.queue_url(&subscription.topic).await?;
+        self.call(
+            "DeleteMessage",
+            &json!({ "QueueUrl": url, "ReceiptHandle": delivery.delivery_id }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn claim_pending(
+        &self,
+        _subscription: &Subscription,
+        _min_idle: Duration,
+        _max: usize,
+    ) -> Result<Vec<Delivery>, QueueError> {
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,101 @@
+use super::{Delivery, MessageQueue, QueueError, QueueMessage};
+use crate::adapters::{OutboundSms, WebhookEvent};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A topic name tied to the payload type carried on it, so producers and
+/// consumers can't disagree about the schema.
+pub struct Topic<T> {
+    pub name: &'static str,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Topic<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Topic<T> {}
+
+impl<T> fmt::Debug for Topic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Topic").field(&self.name).finish()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Topic<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _payload: PhantomData,
+        }
+    }
+
+    pub fn message(&self, payload: &T) -> Result<QueueMessage, QueueError> {
+        QueueMessage::json(payload)
+    }
+
+    pub fn decode(&self, delivery: &Delivery) -> Result<T, QueueError> {
+        delivery.json()
+    }
+
+    pub async fn publish(
+        &self,
+        queue: &dyn MessageQueue,
+        payload: &T,
+    ) -> Result<String, QueueError> {
+        queue.publish(self.name, &self.message(payload)?).await
+    }
+}
+
+/// A send accepted by the API, for the dispatch workers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboundSend {
+    pub message_id: String,
+    pub account_id: String,
+    /// ISO 3166 alpha-2 destination country, for routing.
+    pub country: String,
+    pub message: OutboundSms,
+}
+
+/// A security- or billing-relevant action, for the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: String,
+    pub occurred_at: f64,
+    /// User, API key or service that acted.
+    pub actor: String,
+    pub action: String,
+    pub resource: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub metadata: Value,
+}
+
+/// A visit to a short link, for analytics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkClick {
+    pub code: String,
+    pub url: String,
+    pub account_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub campaign_id: Option<String>,
+    pub clicked_at: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Fetched by a link-preview bot rather than opened by the recipient.
+    #[serde(default)]
+    pub preview: bool,
+}
+
+pub const OUTBOUND_SENDS: Topic<OutboundSend> = Topic::new("sms.outbound");
+pub const DELIVERY_REPORTS: Topic<WebhookEvent> = Topic::new("sms.dlr");
+pub const AUDIT_EVENTS: Topic<AuditEvent> = Topic::new("audit.events");
+pub const LINK_CLICKS: Topic<LinkClick> = Topic::new("links.clicks");
@@ -0,0 +1,373 @@
+use super::prometheus::{render_series, ExpositionFormat, CONTENT_TYPE};
+use super::{BucketHistogram, SimpleMetrics};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use redis::{AsyncCommands, Client, RedisResult};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+const FIELD_SEP: char = '\t';
+/// Missed flushes after which an instance's hashes expire.
+const TTL_FLUSHES: u32 = 4;
+
+#[derive(Debug, Clone)]
+pub struct ClusterConfig {
+    pub service: String,
+    pub instance: String,
+    pub key_prefix: String,
+    pub flush_interval: Duration,
+    /// Per-instance hashes expire if an instance stops flushing for this
+    /// long, so dead replicas drop out of the totals. Defaults to four flush
+    /// intervals.
+    pub key_ttl: Duration,
+}
+
+impl ClusterConfig {
+    /// Uses `HOSTNAME` (the pod name on Kubernetes) as the instance id, falling
+    /// back to a random id.
+    pub fn new(service: &str) -> Self {
+        let instance = env::var("HOSTNAME")
+            .ok()
+            .filter(|h| !h.is_empty())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let flush_interval = Duration::from_secs(15);
+        Self {
+            service: service.to_string(),
+            instance,
+            key_prefix: "smsly:metrics".to_string(),
+            flush_interval,
+            key_ttl: flush_interval * TTL_FLUSHES,
+        }
+    }
+
+    /// Also rescales `key_ttl` to four of the new intervals.
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self.key_ttl = interval * TTL_FLUSHES;
+        self
+    }
+
+    fn instances_key(&self) -> String {
+        format!("{}:{}:instances", self.key_prefix, self.service)
+    }
+
+    fn instance_key(&self, instance: &str, kind: &str) -> String {
+        format!("{}:{}:{}:{}", self.key_prefix, self.service, instance, kind)
+    }
+}
+
+/// Sums of every replica's flushed metrics.
+#[derive(Debug, Clone, Default)]
+pub struct ClusterMetrics {
+    pub instances: Vec<String>,
+    pub counters: Vec<(String, i64)>,
+    /// Gauges are summed across instances (e.g. total in-flight requests).
+    pub gauges: Vec<(String, f64)>,
+    pub histograms: Vec<(String, BucketHistogram)>,
+}
+
+impl ClusterMetrics {
+    pub fn render_prometheus(&self) -> String {
+        render_series(
+            &self.counters,
+            &self.gauges,
+            &self.histograms,
+            ExpositionFormat::Prometheus,
+        )
+    }
+}
+
+/// Periodically flushes local metric deltas to Redis hashes keyed by
+/// service + instance, and reads them back to build cluster-wide totals.
+pub struct ClusterAggregator {
+    client: Client,
+    config: ClusterConfig,
+    metrics: &'static SimpleMetrics,
+    last_counters: Mutex<HashMap<String, i64>>,
+    last_histograms: Mutex<HashMap<String, BucketHistogram>>,
+}
+
+impl ClusterAggregator {
+    pub fn new(client: Client, config: ClusterConfig, metrics: &'static SimpleMetrics) -> Self {
+        Self {
+            client,
+            config,
+            metrics,
+            last_counters: Mutex::new(HashMap::new()),
+            last_histograms: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &ClusterConfig {
+        &self.config
+    }
+
+    pub async fn flush(&self) -> RedisResult<()> {
+        let counters_key = self.config.instance_key(&self.config.instance, "counters");
+        let gauges_key = self.config.instance_key(&self.config.instance, "gauges");
+        let histograms_key = self
+            .config
+            .instance_key(&self.config.instance, "histograms");
+        let ttl = self.config.key_ttl.as_secs().max(1) as i64;
+
+        let mut pipe = redis::pipe();
+        pipe.sadd(self.config.instances_key(), &self.config.instance)
+            .ignore()
+            .expire(self.config.instances_key(), ttl)
+            .ignore();
+
+        let counters = self.metrics.counter_entries();
+        let histograms = self.metrics.histogram_entries();
+        {
+            let last = self.last_counters.lock().unwrap();
+            for (key, value) in &counters {
+                let delta = delta(*value, last.get(key).copied().unwrap_or(0));
+                if delta != 0 {
+                    pipe.hincr(&counters_key, key, delta).ignore();
+                }
+            }
+        }
+        for (key, value) in self.metrics.gauge_entries() {
+            pipe.hset(&gauges_key, key, value).ignore();
+        }
+        {
+            let last = self.last_histograms.lock().unwrap();
+            for (key, histogram) in &histograms {
+                let previous = last.get(key);
+                for (idx, count) in histogram.bucket_counts().iter().enumerate() {
+                    let before = previous
+                        .and_then(|p| p.bucket_counts().get(idx).copied())
+                        .unwrap_or(0);
+                    let delta = delta(*count as i64, before as i64);
+                    if delta != 0 {
+                        pipe.hincr(
+                            &histograms_key,
+                            format!("{}{}b{}", key, FIELD_SEP, idx),
+                            delta,
+                        )
+                        .ignore();
+                    }
+                }
+                let sum_delta = match previous {
+                    Some(p) if histogram.sum() >= p.sum() => histogram.sum() - p.sum(),
+                    _ => histogram.sum(),
+                };
+                pipe.cmd("HINCRBYFLOAT")
+                    .arg(&histograms_key)
+                    .arg(format!("{}{}sum", key, FIELD_SEP))
+                    .arg(sum_delta)
+                    .ignore();
+                let bounds = serde_json::to_string(histogram.bounds()).unwrap_or_default();
+                pipe.hset(
+                    &histograms_key,
+                    format!("{}{}bounds", key, FIELD_SEP),
+                    bounds,
+                )
+                .ignore();
+            }
+        }
+        for key in [&counters_key, &gauges_key, &histograms_key] {
+            pipe.expire(key, ttl).ignore();
+        }
+
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        pipe.query_async::<_, ()>(&mut conn).await?;
+
+        *self.last_counters.lock().unwrap() = counters.into_iter().collect();
+        *self.last_histograms.lock().unwrap() = histograms.into_iter().collect();
+        Ok(())
+    }
+
+    /// Flushes on the configured interval until the task is aborted.
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        info!(
+            "Cluster metrics flush started: service={} instance={}",
+            self.config.service, self.config.instance
+        );
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.config.flush_interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.flush().await {
+                    warn!("Cluster metrics flush failed: {}", e);
+                }
+            }
+        })
+    }
+
+    pub async fn aggregate(&self) -> RedisResult<ClusterMetrics> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let instances: Vec<String> = conn.smembers(self.config.instances_key()).await?;
+
+        let mut counters: HashMap<String, i64> = HashMap::new();
+        let mut gauges: HashMap<String, f64> = HashMap::new();
+        let mut histogram_parts: HashMap<String, HistogramParts> = HashMap::new();
+        let mut live = Vec::new();
+
+        for instance in instances {
+            let counter_hash: HashMap<String, i64> = conn
+                .hgetall(self.config.instance_key(&instance, "counters"))
+                .await?;
+            let gauge_hash: HashMap<String, f64> = conn
+                .hgetall(self.config.instance_key(&instance, "gauges"))
+                .await?;
+            let histogram_hash: HashMap<String, String> = conn
+                .hgetall(self.config.instance_key(&instance, "histograms"))
+                .await?;
+
+            if counter_hash.is_empty() && gauge_hash.is_empty() && histogram_hash.is_empty() {
+                conn.srem::<_, _, ()>(self.config.instances_key(), &instance)
+                    .await?;
+                continue;
+            }
+            live.push(instance);
+
+            for (key, value) in counter_hash {
+                *counters.entry(key).or_insert(0) += value;
+            }
+            for (key, value) in gauge_hash {
+                *gauges.entry(key).or_insert(0.0) += value;
+            }
+            merge_histograms(&mut histogram_parts, histogram_hash);
+        }
+
+        live.sort();
+        Ok(ClusterMetrics {
+            instances: live,
+            counters: counters.into_iter().collect(),
+            gauges: gauges.into_iter().collect(),
+            histograms: histogram_parts
+                .into_iter()
+                .map(|(key, h)| {
+                    (
+                        key,
+                        BucketHistogram::from_buckets(&h.bounds, &h.counts, h.sum),
+                    )
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Counter delta since the last flush; a smaller value means the local
+/// registry was reset, so the whole value is new.
+fn delta(current: i64, last: i64) -> i64 {
+    if current >= last {
+        current - last
+    } else {
+        current
+    }
+}
+
+/// Summed bucket counts for one histogram series across instances.
+struct HistogramParts {
+    bounds: Vec<f64>,
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+#[derive(Default)]
+struct RawHistogram {
+    bounds: Vec<f64>,
+    buckets: Vec<(usize, u64)>,
+    sum: f64,
+}
+
+fn merge_histograms(parts: &mut HashMap<String, HistogramParts>, hash: HashMap<String, String>) {
+    let mut raw: HashMap<String, RawHistogram> = HashMap::new();
+    for (field, value) in hash {
+        let Some((key, part)) = field.split_once(FIELD_SEP) else {
+            continue;
+        };
+        let entry = raw.entry(key.to_string()).or_default();
+        match part {
+            "bounds" => entry.bounds = serde_json::from_str(&value).unwrap_or_default(),
+            "sum" => entry.sum = value.parse().unwrap_or(0.0),
+            _ => {
+                if let (Some(idx), Ok(count)) = (
+                    part.strip_prefix('b').and_then(|i| i.parse::<usize>().ok()),
+                    value.parse::<u64>(),
+                ) {
+                    entry.buckets.push((idx, count));
+                }
+            }
+        }
+    }
+
+    for (key, histogram) in raw {
+        let mut counts = vec![0u64; histogram.bounds.len() + 1];
+        for (idx, count) in histogram.buckets {
+            if let Some(slot) = counts.get_mut(idx) {
+                *slot = count;
+            }
+        }
+        match parts.get_mut(&key) {
+            Some(existing) if existing.bounds == histogram.bounds => {
+                for (total, count) in existing.counts.iter_mut().zip(&counts) {
+                    *total += count;
+                }
+                existing.sum += histogram.sum;
+            }
+            Some(_) => warn!("Skipping histogram {} with mismatched buckets", key),
+            None => {
+                parts.insert(
+                    key,
+                    HistogramParts {
+                        bounds: histogram.bounds,
+                        counts,
+                        sum: histogram.sum,
+                    },
+                );
+            }
+        }
+    }
+}
+
+async fn cluster_metrics_handler(State(aggregator): State<Arc<ClusterAggregator>>) -> Response {
+    match aggregator.aggregate().await {
+        Ok(metrics) => (
+            [(header::CONTENT_TYPE, CONTENT_TYPE)],
+            metrics.render_prometheus(),
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Cluster metrics aggregation failed: {}", e);
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "cluster metrics unavailable",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// `/metrics/cluster` endpoint rendering totals across all replicas.
+pub fn create_cluster_metrics_router(aggregator: Arc<ClusterAggregator>) -> Router {
+    Router::new()
+        .route("/metrics/cluster", get(cluster_metrics_handler))
+        .with_state(aggregator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_ttl_follows_the_flush_interval() {
+        let config = ClusterConfig::new("sms");
+        assert_eq!(config.key_ttl, Duration::from_secs(60));
+
+        let config = config.with_flush_interval(Duration::from_secs(5));
+        assert_eq!(config.flush_interval, Duration::from_secs(5));
+        assert_eq!(config.key_ttl, Duration::from_secs(20));
+    }
+}
@@ -0,0 +1,133 @@
+use super::sharded::AtomicF64;
+use super::{BucketHistogram, Exemplar, SimpleMetrics, WindowedHistogram};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Pre-registered counter series returned by `SimpleMetrics::counter`.
+pub struct Counter<'a> {
+    metrics: &'a SimpleMetrics,
+    name: String,
+    labels: Option<HashMap<String, String>>,
+    cell: Arc<AtomicI64>,
+}
+
+impl<'a> Counter<'a> {
+    pub(crate) fn new(
+        metrics: &'a SimpleMetrics,
+        name: &str,
+        labels: Option<HashMap<String, String>>,
+        cell: Arc<AtomicI64>,
+    ) -> Self {
+        Self {
+            metrics,
+            name: name.to_string(),
+            labels,
+            cell,
+        }
+    }
+
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    /// Negative deltas are dropped; see `SimpleMetrics::increment`.
+    pub fn inc_by(&self, value: i64) {
+        if value < 0 {
+            return;
+        }
+        self.cell.fetch_add(value, Ordering::Relaxed);
+        self.metrics
+            .emit_counter(&self.name, value, self.labels.as_ref());
+    }
+
+    pub fn get(&self) -> i64 {
+        self.cell.load(Ordering::Relaxed)
+    }
+}
+
+/// Pre-registered gauge series returned by `SimpleMetrics::gauge`.
+pub struct Gauge<'a> {
+    metrics: &'a SimpleMetrics,
+    name: String,
+    labels: Option<HashMap<String, String>>,
+    cell: Arc<AtomicF64>,
+}
+
+impl<'a> Gauge<'a> {
+    pub(crate) fn new(
+        metrics: &'a SimpleMetrics,
+        name: &str,
+        labels: Option<HashMap<String, String>>,
+        cell: Arc<AtomicF64>,
+    ) -> Self {
+        Self {
+            metrics,
+            name: name.to_string(),
+            labels,
+            cell,
+        }
+    }
+
+    pub fn set(&self, value: f64) {
+        self.cell.store(value);
+        self.metrics
+            .emit_gauge(&self.name, value, self.labels.as_ref());
+    }
+
+    pub fn get(&self) -> f64 {
+        self.cell.load()
+    }
+}
+
+/// Pre-registered histogram series returned by `SimpleMetrics::histogram`.
+pub struct Histogram<'a> {
+    metrics: &'a SimpleMetrics,
+    name: String,
+    labels: Option<HashMap<String, String>>,
+    cell: Arc<Mutex<BucketHistogram>>,
+    window: Option<Arc<Mutex<WindowedHistogram>>>,
+}
+
+impl<'a> Histogram<'a> {
+    pub(crate) fn new(
+        metrics: &'a SimpleMetrics,
+        name: &str,
+        labels: Option<HashMap<String, String>>,
+        cell: Arc<Mutex<BucketHistogram>>,
+        window: Option<Arc<Mutex<WindowedHistogram>>>,
+    ) -> Self {
+        Self {
+            metrics,
+            name: name.to_string(),
+            labels,
+            cell,
+            window,
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        self.cell.lock().unwrap().observe(value);
+        if let Some(window) = &self.window {
+            window.lock().unwrap().observe(value);
+        }
+        self.metrics
+            .emit_histogram(&self.name, value, self.labels.as_ref());
+    }
+
+    pub fn observe_with_exemplar(&self, value: f64, exemplar: Exemplar) {
+        self.cell
+            .lock()
+            .unwrap()
+            .observe_with_exemplar(value, exemplar);
+        if let Some(window) = &self.window {
+            window.lock().unwrap().observe(value);
+        }
+        self.metrics
+            .emit_histogram(&self.name, value, self.labels.as_ref());
+    }
+
+    pub fn count(&self) -> u64 {
+        self.cell.lock().unwrap().count()
+    }
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default bucket boundaries in seconds, matching the Prometheus client defaults.
+pub const DEFAULT_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Links a single observation to a trace or request so slow buckets can be
+/// followed back to the request that landed in them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exemplar {
+    pub labels: HashMap<String, String>,
+    pub value: f64,
+    pub timestamp: f64,
+}
+
+impl Exemplar {
+    /// Exemplar carrying a single label such as `trace_id` or `request_id`.
+    /// `value` and `timestamp` are filled in when it is recorded.
+    pub fn with_label(key: &str, value: &str) -> Self {
+        Self {
+            labels: HashMap::from([(key.to_string(), value.to_string())]),
+            value: 0.0,
+            timestamp: 0.0,
+        }
+    }
+}
+
+/// Fixed-size histogram storing per-bucket counts plus a running count and sum.
+/// Memory use is bounded by the number of buckets, not the number of samples.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BucketHistogram {
+    bounds: Vec<f64>,
+    counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+    /// Most recent exemplar per bucket, aligned with `counts`.
+    #[serde(default)]
+    exemplars: Vec<Option<Exemplar>>,
+}
+
+impl BucketHistogram {
+    pub fn new(bounds: &[f64]) -> Self {
+        let mut bounds: Vec<f64> = bounds.iter().copied().filter(|b| b.is_finite()).collect();
+        bounds.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        bounds.dedup();
+        let counts = vec![0; bounds.len() + 1];
+        let exemplars = vec![None; counts.len()];
+        Self {
+            bounds,
+            counts,
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            exemplars,
+        }
+    }
+
+    /// Rebuilds a histogram from exported bucket counts, e.g. after aggregating
+    /// several instances. The observed range is approximated by the outer bounds.
+    pub fn from_buckets(bounds: &[f64], counts: &[u64], sum: f64) -> Self {
+        let mut histogram = Self::new(bounds);
+        for (slot, count) in histogram.counts.iter_mut().zip(counts) {
+            *slot = *count;
+        }
+        histogram.count = histogram.counts.iter().sum();
+        histogram.sum = sum;
+        if histogram.count > 0 {
+            histogram.min = histogram.bounds.first().copied().unwrap_or(0.0).min(0.0);
+            histogram.max = histogram.bounds.last().copied().unwrap_or(0.0);
+        }
+        histogram
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        self.record(value);
+    }
+
+    /// Observes `value` and stores `exemplar` as the latest one for its bucket.
+    pub fn observe_with_exemplar(&mut self, value: f64, mut exemplar: Exemplar) {
+        if let Some(idx) = self.record(value) {
+            exemplar.value = value;
+            exemplar.timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            self.exemplars[idx] = Some(exemplar);
+        }
+    }
+
+    fn record(&mut self, value: f64) -> Option<usize> {
+        if value.is_nan() {
+            return None;
+        }
+        let idx = self
+            .bounds
+            .iter()
+            .position(|b| value <= *b)
+            .unwrap_or(self.bounds.len());
+        self.counts[idx] += 1;
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        Some(idx)
+    }
+
+    /// Adds `other` into `self`. Returns `false` (leaving `self` untouched) if
+    /// the bucket layouts differ.
+    pub fn merge(&mut self, other: &BucketHistogram) -> bool {
+        if self.bounds != other.bounds {
+            return false;
+        }
+        for (total, count) in self.counts.iter_mut().zip(&other.counts) {
+            *total += count;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        for (mine, theirs) in self.exemplars.iter_mut().zip(&other.exemplars) {
+            let newer = match (&*mine, theirs) {
+                (Some(a), Some(b)) => b.timestamp > a.timestamp,
+                (None, Some(_)) => true,
+                _ => false,
+            };
+            if newer {
+                *mine = theirs.clone();
+            }
+        }
+        true
+    }
+
+    pub fn bounds(&self) -> &[f64] {
+        &self.bounds
+    }
+
+    /// Per-bucket (non-cumulative) counts; the final entry is the `+Inf` bucket.
+    pub fn bucket_counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Latest exemplar per bucket, aligned with `bucket_counts`.
+    pub fn exemplars(&self) -> &[Option<Exemplar>] {
+        &self.exemplars
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Estimates a percentile (0-100) by linear interpolation inside the bucket
+    /// that contains the target rank, clamped to the observed min/max.
+    pub fn percentile(&self, percentile: f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let rank = (self.count as f64 * percentile / 100.0).clamp(0.0, self.count as f64);
+        let mut cumulative = 0u64;
+        for (idx, bucket_count) in self.counts.iter().enumerate() {
+            if *bucket_count == 0 {
+                continue;
+            }
+            let next = cumulative + bucket_count;
+            if (next as f64) >= rank {
+                let lower = if idx == 0 {
+                    self.min
+                } else {
+                    self.bounds[idx - 1].max(self.min)
+                };
+                let upper = self
+                    .bounds
+                    .get(idx)
+                    .copied()
+                    .unwrap_or(self.max)
+                    .min(self.max);
+                let fraction = (rank - cumulative as f64) / *bucket_count as f64;
+                return lower + (upper - lower) * fraction;
+            }
+            cumulative = next;
+        }
+        self.max
+    }
+}
+
+impl Default for BucketHistogram {
+    fn default() -> Self {
+        Self::new(&DEFAULT_BUCKETS)
+    }
+}
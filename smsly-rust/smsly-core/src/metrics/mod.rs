@@ -0,0 +1,503 @@
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sharded::{AtomicF64, ShardedMap};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub mod cluster;
+mod handles;
+pub mod histogram;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod prometheus;
+mod sharded;
+pub mod sink;
+pub mod snapshot;
+pub mod window;
+
+pub use handles::{Counter, Gauge, Histogram};
+pub use histogram::{BucketHistogram, Exemplar, DEFAULT_BUCKETS};
+pub use sink::{MetricsSink, StatsdFlavor, StatsdSink};
+pub use smsly_macros::instrument_metric;
+pub use snapshot::{HistogramStats, MetricsSnapshot};
+pub use window::WindowedHistogram;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricLabels {
+    pub service: String,
+    pub environment: String,
+    pub version: String,
+}
+
+impl Default for MetricLabels {
+    fn default() -> Self {
+        Self {
+            service: "unknown".to_string(),
+            environment: "production".to_string(),
+            version: "1.0.0".to_string(),
+        }
+    }
+}
+
+pub struct SimpleMetrics {
+    labels: MetricLabels,
+    counters: ShardedMap<Arc<AtomicI64>>,
+    gauges: ShardedMap<Arc<AtomicF64>>,
+    histograms: ShardedMap<Arc<Mutex<BucketHistogram>>>,
+    bucket_config: RwLock<HashMap<String, Vec<f64>>>,
+    windows: ShardedMap<Arc<Mutex<WindowedHistogram>>>,
+    window_config: RwLock<HashMap<String, (Duration, usize)>>,
+    sinks: RwLock<Vec<Arc<dyn MetricsSink>>>,
+}
+
+impl SimpleMetrics {
+    pub fn new(labels: Option<MetricLabels>) -> Self {
+        Self {
+            labels: labels.unwrap_or_default(),
+            counters: ShardedMap::new(),
+            gauges: ShardedMap::new(),
+            histograms: ShardedMap::new(),
+            bucket_config: RwLock::new(HashMap::new()),
+            windows: ShardedMap::new(),
+            window_config: RwLock::new(HashMap::new()),
+            sinks: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn labels(&self) -> &MetricLabels {
+        &self.labels
+    }
+
+    /// Forwards every subsequent update to `sink` in addition to local storage.
+    pub fn add_sink(&self, sink: Arc<dyn MetricsSink>) {
+        self.sinks.write().unwrap().push(sink);
+    }
+
+    fn make_key(&self, name: &str, labels: &Option<HashMap<String, String>>) -> String {
+        if let Some(l) = labels {
+            let mut sorted_labels: Vec<_> = l.iter().collect();
+            sorted_labels.sort_by_key(|a| a.0);
+            let label_str: Vec<String> = sorted_labels
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            format!("{}{{{}}}", name, label_str.join(","))
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Counters are exported as monotonic sums, so negative deltas are dropped.
+    pub fn increment(&self, name: &str, value: i64, labels: Option<HashMap<String, String>>) {
+        if value < 0 {
+            return;
+        }
+        let key = self.make_key(name, &labels);
+        self.counters.with(
+            &key,
+            || Arc::new(AtomicI64::new(0)),
+            |c| c.fetch_add(value, Ordering::Relaxed),
+        );
+        self.emit_counter(name, value, labels.as_ref());
+    }
+
+    pub fn set_gauge(&self, name: &str, value: f64, labels: Option<HashMap<String, String>>) {
+        let key = self.make_key(name, &labels);
+        self.gauges
+            .with(&key, || Arc::new(AtomicF64::new(value)), |g| g.store(value));
+        self.emit_gauge(name, value, labels.as_ref());
+    }
+
+    /// Sets the bucket boundaries used for histogram `name`. Only series created
+    /// after this call pick up the new layout, so configure buckets at startup.
+    pub fn configure_buckets(&self, name: &str, bounds: &[f64]) {
+        let mut config = self.bucket_config.write().unwrap();
+        config.insert(name.to_string(), bounds.to_vec());
+    }
+
+    fn new_histogram(&self, name: &str) -> BucketHistogram {
+        let config = self.bucket_config.read().unwrap();
+        match config.get(name) {
+            Some(bounds) => BucketHistogram::new(bounds),
+            None => BucketHistogram::default(),
+        }
+    }
+
+    /// Makes `get_histogram_stats` for `name` report only samples from the last
+    /// `window`, tracked as `slots` rotating sub-histograms. Exports (snapshot,
+    /// Prometheus, OTLP) keep using the lifetime histogram.
+    pub fn configure_window(&self, name: &str, window: Duration, slots: usize) {
+        let mut config = self.window_config.write().unwrap();
+        config.insert(name.to_string(), (window, slots));
+    }
+
+    fn window_settings(&self, name: &str) -> Option<(Duration, usize)> {
+        self.window_config.read().unwrap().get(name).copied()
+    }
+
+    fn new_window(&self, name: &str, window: Duration, slots: usize) -> WindowedHistogram {
+        WindowedHistogram::new(self.new_histogram(name).bounds(), window, slots)
+    }
+
+    pub(crate) fn window_cell(
+        &self,
+        name: &str,
+        key: &str,
+    ) -> Option<Arc<Mutex<WindowedHistogram>>> {
+        let (window, slots) = self.window_settings(name)?;
+        Some(self.windows.with(
+            key,
+            || Arc::new(Mutex::new(self.new_window(name, window, slots))),
+            Arc::clone,
+        ))
+    }
+
+    pub fn observe(&self, name: &str, value: f64, labels: Option<HashMap<String, String>>) {
+        let key = self.make_key(name, &labels);
+        self.histograms.with(
+            &key,
+            || Arc::new(Mutex::new(self.new_histogram(name))),
+            |h| h.lock().unwrap().observe(value),
+        );
+        if let Some((window, slots)) = self.window_settings(name) {
+            self.windows.with(
+                &key,
+                || Arc::new(Mutex::new(self.new_window(name, window, slots))),
+                |w| w.lock().unwrap().observe(value),
+            );
+        }
+        self.emit_histogram(name, value, labels.as_ref());
+    }
+
+    /// Like `observe`, additionally storing `exemplar` (e.g. the current trace
+    /// id) as the latest exemplar of the bucket the value falls into.
+    pub fn observe_with_exemplar(
+        &self,
+        name: &str,
+        value: f64,
+        labels: Option<HashMap<String, String>>,
+        exemplar: Exemplar,
+    ) {
+        let key = self.make_key(name, &labels);
+        self.histograms.with(
+            &key,
+            || Arc::new(Mutex::new(self.new_histogram(name))),
+            |h| h.lock().unwrap().observe_with_exemplar(value, exemplar),
+        );
+        if let Some((window, slots)) = self.window_settings(name) {
+            self.windows.with(
+                &key,
+                || Arc::new(Mutex::new(self.new_window(name, window, slots))),
+                |w| w.lock().unwrap().observe(value),
+            );
+        }
+        self.emit_histogram(name, value, labels.as_ref());
+    }
+
+    /// Registers (or looks up) counter `name` and returns a handle whose label
+    /// key is resolved once, so updates skip `make_key` entirely.
+    pub fn counter(&self, name: &str, labels: Option<HashMap<String, String>>) -> Counter<'_> {
+        let key = self.make_key(name, &labels);
+        let cell = self
+            .counters
+            .with(&key, || Arc::new(AtomicI64::new(0)), Arc::clone);
+        Counter::new(self, name, labels, cell)
+    }
+
+    pub fn gauge(&self, name: &str, labels: Option<HashMap<String, String>>) -> Gauge<'_> {
+        let key = self.make_key(name, &labels);
+        let cell = self
+            .gauges
+            .with(&key, || Arc::new(AtomicF64::new(0.0)), Arc::clone);
+        Gauge::new(self, name, labels, cell)
+    }
+
+    pub fn histogram(&self, name: &str, labels: Option<HashMap<String, String>>) -> Histogram<'_> {
+        let key = self.make_key(name, &labels);
+        let cell = self.histograms.with(
+            &key,
+            || Arc::new(Mutex::new(self.new_histogram(name))),
+            Arc::clone,
+        );
+        let window = self.window_cell(name, &key);
+        Histogram::new(self, name, labels, cell, window)
+    }
+
+    pub(crate) fn emit_counter(
+        &self,
+        name: &str,
+        value: i64,
+        labels: Option<&HashMap<String, String>>,
+    ) {
+        for sink in self.sinks.read().unwrap().iter() {
+            sink.counter(name, value, labels);
+        }
+    }
+
+    pub(crate) fn emit_gauge(
+        &self,
+        name: &str,
+        value: f64,
+        labels: Option<&HashMap<String, String>>,
+    ) {
+        for sink in self.sinks.read().unwrap().iter() {
+            sink.gauge(name, value, labels);
+        }
+    }
+
+    pub(crate) fn emit_histogram(
+        &self,
+        name: &str,
+        value: f64,
+        labels: Option<&HashMap<String, String>>,
+    ) {
+        for sink in self.sinks.read().unwrap().iter() {
+            sink.histogram(name, value, labels);
+        }
+    }
+
+    pub fn counter_entries(&self) -> Vec<(String, i64)> {
+        let mut entries = Vec::new();
+        self.counters
+            .for_each(|k, v| entries.push((k.to_string(), v.load(Ordering::Relaxed))));
+        entries
+    }
+
+    pub fn gauge_entries(&self) -> Vec<(String, f64)> {
+        let mut entries = Vec::new();
+        self.gauges
+            .for_each(|k, v| entries.push((k.to_string(), v.load())));
+        entries
+    }
+
+    pub fn histogram_entries(&self) -> Vec<(String, BucketHistogram)> {
+        let mut entries = Vec::new();
+        self.histograms
+            .for_each(|k, v| entries.push((k.to_string(), v.lock().unwrap().clone())));
+        entries
+    }
+
+    /// Starts a timer that observes the elapsed seconds into histogram `name`
+    /// when the returned guard is dropped, including on early returns.
+    pub fn start_timer(
+        &self,
+        name: &str,
+        labels: Option<HashMap<String, String>>,
+    ) -> TimerGuard<'_> {
+        TimerGuard {
+            metrics: self,
+            name: name.to_string(),
+            labels,
+            start: Instant::now(),
+            armed: true,
+        }
+    }
+
+    /// Awaits `fut` and records its duration. A future that is dropped before
+    /// completion still records the time spent up to cancellation.
+    pub async fn time_async<F: Future>(
+        &self,
+        name: &str,
+        labels: Option<HashMap<String, String>>,
+        fut: F,
+    ) -> F::Output {
+        let _guard = self.start_timer(name, labels);
+        fut.await
+    }
+
+    pub fn get_histogram_stats(
+        &self,
+        name: &str,
+        labels: Option<HashMap<String, String>>,
+    ) -> HashMap<String, f64> {
+        let key = self.make_key(name, &labels);
+        let stats = if self.window_settings(name).is_some() {
+            self.windows
+                .get(&key, |w| HistogramStats::from(&w.lock().unwrap().merged()))
+        } else {
+            self.histograms
+                .get(&key, |h| HistogramStats::from(&*h.lock().unwrap()))
+        }
+        .unwrap_or_default();
+
+        HashMap::from([
+            ("count".to_string(), stats.count as f64),
+            ("sum".to_string(), stats.sum),
+            ("avg".to_string(), stats.avg),
+            ("p50".to_string(), stats.p50),
+            ("p95".to_string(), stats.p95),
+            ("p99".to_string(), stats.p99),
+        ])
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let mut histograms = HashMap::new();
+        self.histograms.for_each(|k, v| {
+            histograms.insert(k.to_string(), HistogramStats::from(&*v.lock().unwrap()));
+        });
+
+        MetricsSnapshot {
+            labels: self.labels.clone(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            counters: self.counter_entries().into_iter().collect(),
+            gauges: self.gauge_entries().into_iter().collect(),
+            histograms,
+        }
+    }
+
+    /// Zeroes every series in place. Series stay registered so typed handles
+    /// obtained earlier keep reporting into this registry.
+    pub fn reset(&self) {
+        self.counters.for_each(|_, c| c.store(0, Ordering::Relaxed));
+        self.gauges.for_each(|_, g| g.store(0.0));
+        self.histograms.for_each(|_, h| {
+            let mut h = h.lock().unwrap();
+            *h = BucketHistogram::new(h.bounds());
+        });
+        self.windows.for_each(|_, w| w.lock().unwrap().clear());
+    }
+}
+
+/// Splits a `make_key` string such as `name{a=1,b=2}` back into its parts.
+pub(crate) fn split_key(key: &str) -> (String, HashMap<String, String>) {
+    match key.split_once('{') {
+        Some((name, rest)) => {
+            let labels = rest
+                .trim_end_matches('}')
+                .split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            (name.to_string(), labels)
+        }
+        None => (key.to_string(), HashMap::new()),
+    }
+}
+
+lazy_static! {
+    pub static ref GLOBAL_METRICS: SimpleMetrics = SimpleMetrics::new(None);
+}
+
+pub fn track_metric(name: &str, metadata: HashMap<String, serde_json::Value>) {
+    let mut labels = HashMap::new();
+    let mut val = 1.0;
+
+    for (k, v) in metadata {
+        if let Some(n) = v.as_f64() {
+            if k == "duration_ms" || k == "latency" || k == "value" {
+                val = n;
+            }
+        }
+        if let Some(s) = v.as_str() {
+            labels.insert(k, s.to_string());
+        } else {
+            labels.insert(k, v.to_string());
+        }
+    }
+
+    GLOBAL_METRICS.observe(name, val, Some(labels));
+}
+
+pub struct Timer<'a> {
+    metrics: &'a SimpleMetrics,
+    name: String,
+    labels: Option<HashMap<String, String>>,
+    start: Option<SystemTime>,
+}
+
+impl<'a> Timer<'a> {
+    pub fn new(
+        metrics: &'a SimpleMetrics,
+        name: &str,
+        labels: Option<HashMap<String, String>>,
+    ) -> Self {
+        Self {
+            metrics,
+            name: name.to_string(),
+            labels,
+            start: None,
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.start = Some(SystemTime::now());
+    }
+
+    pub fn stop(&self) {
+        if let Some(start) = self.start {
+            let duration = start.elapsed().unwrap_or_default().as_secs_f64();
+            self.metrics
+                .observe(&self.name, duration, self.labels.clone());
+        }
+    }
+}
+
+pub struct TimerGuard<'a> {
+    metrics: &'a SimpleMetrics,
+    name: String,
+    labels: Option<HashMap<String, String>>,
+    start: Instant,
+    armed: bool,
+}
+
+impl TimerGuard<'_> {
+    pub fn elapsed_secs(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    /// Records the duration now and returns it in seconds.
+    pub fn stop(mut self) -> f64 {
+        self.record()
+    }
+
+    /// Drops the guard without recording anything.
+    pub fn discard(mut self) {
+        self.armed = false;
+    }
+
+    fn record(&mut self) -> f64 {
+        let duration = self.elapsed_secs();
+        if self.armed {
+            self.armed = false;
+            self.metrics
+                .observe(&self.name, duration, self.labels.take());
+        }
+        duration
+    }
+}
+
+impl Drop for TimerGuard<'_> {
+    fn drop(&mut self) {
+        self.record();
+    }
+}
+
+pub struct MetricNames;
+
+impl MetricNames {
+    pub const HTTP_REQUESTS_TOTAL: &'static str = "http_requests";
+    pub const HTTP_REQUEST_DURATION: &'static str = "http_request_duration_seconds";
+    pub const MESSAGES_SENT_TOTAL: &'static str = "smsly_messages_sent";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_counter_deltas_are_dropped() {
+        let metrics = SimpleMetrics::new(None);
+        metrics.increment("sent", 3, None);
+        metrics.increment("sent", -2, None);
+        let counter = metrics.counter("sent", None);
+        counter.inc_by(-5);
+        counter.inc();
+        assert_eq!(counter.get(), 4);
+    }
+}
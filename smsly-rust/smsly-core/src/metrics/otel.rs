@@ -0,0 +1,228 @@
+use super::{split_key, SimpleMetrics};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+#[derive(Error, Debug)]
+pub enum OtlpError {
+    #[error("OTLP request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("OTLP collector rejected metrics with status {0}")]
+    Rejected(u16),
+}
+
+#[derive(Debug, Clone)]
+pub struct OtlpConfig {
+    pub endpoint: String,
+    pub export_interval: Duration,
+    pub timeout: Duration,
+    pub headers: HashMap<String, String>,
+}
+
+impl OtlpConfig {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            export_interval: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+            headers: HashMap::new(),
+        }
+    }
+
+    /// Reads the standard `OTEL_EXPORTER_OTLP_*` variables. Returns `None` when no
+    /// endpoint is configured so callers can skip spawning the exporter.
+    pub fn from_env() -> Option<Self> {
+        let endpoint = env::var("OTEL_EXPORTER_OTLP_METRICS_ENDPOINT")
+            .or_else(|_| env::var("OTEL_EXPORTER_OTLP_ENDPOINT"))
+            .ok()
+            .filter(|e| !e.is_empty())?;
+
+        let mut config = Self::new(&endpoint);
+        if let Some(ms) = env::var("OTEL_METRIC_EXPORT_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            config.export_interval = Duration::from_millis(ms);
+        }
+        if let Some(ms) = env::var("OTEL_EXPORTER_OTLP_TIMEOUT")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            config.timeout = Duration::from_millis(ms);
+        }
+        if let Ok(raw) = env::var("OTEL_EXPORTER_OTLP_HEADERS") {
+            config.headers = parse_headers(&raw);
+        }
+        Some(config)
+    }
+
+    fn metrics_url(&self) -> String {
+        let base = self.endpoint.trim_end_matches('/');
+        if base.ends_with("/v1/metrics") {
+            base.to_string()
+        } else {
+            format!("{}/v1/metrics", base)
+        }
+    }
+}
+
+fn parse_headers(raw: &str) -> HashMap<String, String> {
+    raw.split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .filter(|(k, _)| !k.is_empty())
+        .collect()
+}
+
+/// Pushes the contents of a `SimpleMetrics` registry to an OpenTelemetry collector
+/// using the OTLP/HTTP JSON protocol.
+pub struct OtlpExporter {
+    client: reqwest::Client,
+    config: OtlpConfig,
+    metrics: &'static SimpleMetrics,
+    start_time: SystemTime,
+}
+
+impl OtlpExporter {
+    pub fn new(config: OtlpConfig, metrics: &'static SimpleMetrics) -> Result<Self, OtlpError> {
+        let client = reqwest::Client::builder().timeout(config.timeout).build()?;
+        Ok(Self {
+            client,
+            config,
+            metrics,
+            start_time: SystemTime::now(),
+        })
+    }
+
+    pub async fn export(&self) -> Result<(), OtlpError> {
+        let payload = self.build_payload();
+        let mut request = self.client.post(self.config.metrics_url()).json(&payload);
+        for (k, v) in &self.config.headers {
+            request = request.header(k, v);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(OtlpError::Rejected(response.status().as_u16()));
+        }
+        Ok(())
+    }
+
+    /// Runs `export` on the configured interval until the task is aborted.
+    pub fn spawn(self) -> JoinHandle<()> {
+        info!(
+            "OTLP metrics exporter started: endpoint={} interval={:?}",
+            self.config.endpoint, self.config.export_interval
+        );
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.config.export_interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.export().await {
+                    warn!("OTLP metrics export failed: {}", e);
+                }
+            }
+        })
+    }
+
+    fn build_payload(&self) -> Value {
+        let start = unix_nanos(self.start_time);
+        let now = unix_nanos(SystemTime::now());
+        let mut metrics = Vec::new();
+
+        for (key, value) in self.metrics.counter_entries() {
+            let (name, labels) = split_key(&key);
+            metrics.push(json!({
+                "name": name,
+                "sum": {
+                    "aggregationTemporality": 2,
+                    "isMonotonic": true,
+                    "dataPoints": [{
+                        "attributes": attributes(&labels),
+                        "startTimeUnixNano": start,
+                        "timeUnixNano": now,
+                        "asInt": value.to_string(),
+                    }],
+                },
+            }));
+        }
+
+        for (key, value) in self.metrics.gauge_entries() {
+            let (name, labels) = split_key(&key);
+            metrics.push(json!({
+                "name": name,
+                "gauge": {
+                    "dataPoints": [{
+                        "attributes": attributes(&labels),
+                        "timeUnixNano": now,
+                        "asDouble": value,
+                    }],
+                },
+            }));
+        }
+
+        for (key, histogram) in self.metrics.histogram_entries() {
+            let (name, labels) = split_key(&key);
+            metrics.push(json!({
+                "name": name,
+                "histogram": {
+                    "aggregationTemporality": 2,
+                    "dataPoints": [{
+                        "attributes": attributes(&labels),
+                        "startTimeUnixNano": start,
+                        "timeUnixNano": now,
+                        "count": histogram.count().to_string(),
+                        "sum": histogram.sum(),
+                        "bucketCounts": histogram
+                            .bucket_counts()
+                            .iter()
+                            .map(|c| c.to_string())
+                            .collect::<Vec<_>>(),
+                        "explicitBounds": histogram.bounds(),
+                    }],
+                },
+            }));
+        }
+
+        let labels = self.metrics.labels();
+        let resource = HashMap::from([
+            ("service.name".to_string(), labels.service.clone()),
+            ("service.version".to_string(), labels.version.clone()),
+            (
+                "deployment.environment".to_string(),
+                labels.environment.clone(),
+            ),
+        ]);
+
+        json!({
+            "resourceMetrics": [{
+                "resource": { "attributes": attributes(&resource) },
+                "scopeMetrics": [{
+                    "scope": { "name": "smsly-core", "version": env!("CARGO_PKG_VERSION") },
+                    "metrics": metrics,
+                }],
+            }],
+        })
+    }
+}
+
+fn attributes(labels: &HashMap<String, String>) -> Vec<Value> {
+    let mut sorted: Vec<_> = labels.iter().collect();
+    sorted.sort_by_key(|a| a.0);
+    sorted
+        .into_iter()
+        .map(|(k, v)| json!({ "key": k, "value": { "stringValue": v } }))
+        .collect()
+}
+
+fn unix_nanos(t: SystemTime) -> String {
+    t.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
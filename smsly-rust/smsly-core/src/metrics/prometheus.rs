@@ -0,0 +1,202 @@
+use super::{split_key, BucketHistogram, SimpleMetrics, GLOBAL_METRICS};
+use axum::{
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExpositionFormat {
+    /// Prometheus text format 0.0.4.
+    Prometheus,
+    /// OpenMetrics 1.0, which additionally carries histogram exemplars.
+    OpenMetrics,
+}
+
+/// Renders every series in `metrics` in the Prometheus text exposition format.
+/// Counters get the conventional `_total` suffix.
+pub fn render(metrics: &SimpleMetrics) -> String {
+    render_as(metrics, ExpositionFormat::Prometheus)
+}
+
+pub fn render_as(metrics: &SimpleMetrics, format: ExpositionFormat) -> String {
+    render_series(
+        &metrics.counter_entries(),
+        &metrics.gauge_entries(),
+        &metrics.histogram_entries(),
+        format,
+    )
+}
+
+struct Family {
+    kind: &'static str,
+    /// Lines per series, keyed by the rendered label set so output is stable.
+    series: BTreeMap<String, Vec<String>>,
+}
+
+fn family<'a>(
+    families: &'a mut BTreeMap<String, Family>,
+    name: &str,
+    kind: &'static str,
+) -> &'a mut BTreeMap<String, Vec<String>> {
+    &mut families
+        .entry(name.to_string())
+        .or_insert_with(|| Family {
+            kind,
+            series: BTreeMap::new(),
+        })
+        .series
+}
+
+pub(crate) fn render_series(
+    counters: &[(String, i64)],
+    gauges: &[(String, f64)],
+    histograms: &[(String, BucketHistogram)],
+    format: ExpositionFormat,
+) -> String {
+    let mut families: BTreeMap<String, Family> = BTreeMap::new();
+
+    for (key, value) in counters {
+        let (name, labels) = split_key(key);
+        let name = sanitize_name(name.trim_end_matches("_total"));
+        let label_str = format_labels(&labels, None);
+        let line = format!("{}_total{} {}", name, label_str, value);
+        let family_name = match format {
+            ExpositionFormat::Prometheus => format!("{}_total", name),
+            ExpositionFormat::OpenMetrics => name,
+        };
+        family(&mut families, &family_name, "counter")
+            .entry(label_str)
+            .or_default()
+            .push(line);
+    }
+
+    for (key, value) in gauges {
+        let (name, labels) = split_key(key);
+        let name = sanitize_name(&name);
+        let label_str = format_labels(&labels, None);
+        let line = format!("{}{} {}", name, label_str, value);
+        family(&mut families, &name, "gauge")
+            .entry(label_str)
+            .or_default()
+            .push(line);
+    }
+
+    for (key, histogram) in histograms {
+        let (name, labels) = split_key(key);
+        let name = sanitize_name(&name);
+        let label_str = format_labels(&labels, None);
+        let mut lines = Vec::new();
+        let mut cumulative = 0;
+        for (idx, count) in histogram.bucket_counts().iter().enumerate() {
+            cumulative += count;
+            let le = histogram
+                .bounds()
+                .get(idx)
+                .map(|b| b.to_string())
+                .unwrap_or_else(|| "+Inf".to_string());
+            let mut line = format!(
+                "{}_bucket{} {}",
+                name,
+                format_labels(&labels, Some(("le", &le))),
+                cumulative
+            );
+            if format == ExpositionFormat::OpenMetrics {
+                if let Some(Some(exemplar)) = histogram.exemplars().get(idx) {
+                    let _ = write!(
+                        line,
+                        " # {} {} {:.3}",
+                        format_labels(&exemplar.labels, None),
+                        exemplar.value,
+                        exemplar.timestamp
+                    );
+                }
+            }
+            lines.push(line);
+        }
+        lines.push(format!("{}_sum{} {}", name, label_str, histogram.sum()));
+        lines.push(format!("{}_count{} {}", name, label_str, histogram.count()));
+        family(&mut families, &name, "histogram").insert(label_str, lines);
+    }
+
+    let mut out = String::new();
+    for (name, family) in families {
+        let _ = writeln!(out, "# TYPE {} {}", name, family.kind);
+        for line in family.series.into_values().flatten() {
+            let _ = writeln!(out, "{}", line);
+        }
+    }
+    if format == ExpositionFormat::OpenMetrics {
+        out.push_str("# EOF\n");
+    }
+    out
+}
+
+fn format_labels(labels: &HashMap<String, String>, extra: Option<(&str, &str)>) -> String {
+    let mut pairs: Vec<_> = labels
+        .iter()
+        .map(|(k, v)| (sanitize_name(k), v.as_str()))
+        .collect();
+    pairs.sort();
+    if let Some((k, v)) = extra {
+        pairs.push((k.to_string(), v));
+    }
+    if pairs.is_empty() {
+        return String::new();
+    }
+    let body: Vec<String> = pairs
+        .into_iter()
+        .map(|(k, v)| {
+            let escaped = v
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", k, escaped)
+        })
+        .collect();
+    format!("{{{}}}", body.join(","))
+}
+
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+async fn metrics_handler(headers: HeaderMap) -> impl IntoResponse {
+    let wants_openmetrics = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("application/openmetrics-text"))
+        .unwrap_or(false);
+    if wants_openmetrics {
+        (
+            [(header::CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)],
+            render_as(&GLOBAL_METRICS, ExpositionFormat::OpenMetrics),
+        )
+    } else {
+        (
+            [(header::CONTENT_TYPE, CONTENT_TYPE)],
+            render(&GLOBAL_METRICS),
+        )
+    }
+}
+
+/// `/metrics` endpoint exposing `GLOBAL_METRICS` for Prometheus scraping. Scrapers
+/// that accept OpenMetrics also receive exemplars.
+pub fn create_metrics_router() -> Router {
+    Router::new().route("/metrics", get(metrics_handler))
+}
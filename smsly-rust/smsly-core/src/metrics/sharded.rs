@@ -0,0 +1,81 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+const SHARD_COUNT: usize = 32;
+
+/// String-keyed map split across independently locked shards. Lookups of existing
+/// series only take a shard read lock, so concurrent updates to different (or the
+/// same) series never serialize on a single mutex; values provide their own
+/// interior mutability (atomics or a per-series lock).
+pub(crate) struct ShardedMap<V> {
+    shards: Vec<RwLock<HashMap<String, V>>>,
+}
+
+impl<V> ShardedMap<V> {
+    pub(crate) fn new() -> Self {
+        Self {
+            shards: (0..SHARD_COUNT)
+                .map(|_| RwLock::new(HashMap::new()))
+                .collect(),
+        }
+    }
+
+    fn shard(&self, key: &str) -> &RwLock<HashMap<String, V>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARD_COUNT]
+    }
+
+    /// Runs `f` against the value for `key`, inserting `init()` first if missing.
+    pub(crate) fn with<R>(
+        &self,
+        key: &str,
+        init: impl FnOnce() -> V,
+        f: impl FnOnce(&V) -> R,
+    ) -> R {
+        let shard = self.shard(key);
+        {
+            let map = shard.read().unwrap();
+            if let Some(value) = map.get(key) {
+                return f(value);
+            }
+        }
+        let mut map = shard.write().unwrap();
+        let value = map.entry(key.to_string()).or_insert_with(init);
+        f(value)
+    }
+
+    pub(crate) fn get<R>(&self, key: &str, f: impl FnOnce(&V) -> R) -> Option<R> {
+        let map = self.shard(key).read().unwrap();
+        map.get(key).map(f)
+    }
+
+    pub(crate) fn for_each(&self, mut f: impl FnMut(&str, &V)) {
+        for shard in &self.shards {
+            let map = shard.read().unwrap();
+            for (k, v) in map.iter() {
+                f(k, v);
+            }
+        }
+    }
+}
+
+/// `f64` stored as raw bits in an `AtomicU64`.
+pub(crate) struct AtomicF64(AtomicU64);
+
+impl AtomicF64 {
+    pub(crate) fn new(value: f64) -> Self {
+        Self(AtomicU64::new(value.to_bits()))
+    }
+
+    pub(crate) fn load(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn store(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::net::UdpSocket;
+use tracing::debug;
+
+/// Receives every update made through `SimpleMetrics` so values can be forwarded
+/// to an external backend. Implementations must not block the caller.
+pub trait MetricsSink: Send + Sync {
+    fn counter(&self, name: &str, value: i64, labels: Option<&HashMap<String, String>>);
+    fn gauge(&self, name: &str, value: f64, labels: Option<&HashMap<String, String>>);
+    fn histogram(&self, name: &str, value: f64, labels: Option<&HashMap<String, String>>);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatsdFlavor {
+    /// Plain Etsy StatsD: labels are dropped.
+    Statsd,
+    /// Datadog agent: labels are sent as `|#key:value` tags.
+    DogStatsd,
+}
+
+/// Fire-and-forget UDP emitter for StatsD and DogStatsD agents. Histograms whose
+/// name ends in `_seconds` are sent as `ms` timings.
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: Option<String>,
+    flavor: StatsdFlavor,
+    constant_tags: Vec<String>,
+}
+
+impl StatsdSink {
+    pub fn new(addr: &str, prefix: Option<&str>, flavor: StatsdFlavor) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            prefix: prefix.map(|p| p.trim_end_matches('.').to_string()),
+            flavor,
+            constant_tags: Vec::new(),
+        })
+    }
+
+    /// Builds a DogStatsD sink from `DD_AGENT_HOST`/`DD_DOGSTATSD_PORT`, or a plain
+    /// StatsD sink from `STATSD_HOST`/`STATSD_PORT`. Returns `None` if neither is set.
+    pub fn from_env() -> Option<io::Result<Self>> {
+        let prefix = env::var("STATSD_PREFIX").ok();
+        if let Ok(host) = env::var("DD_AGENT_HOST") {
+            let port = env::var("DD_DOGSTATSD_PORT").unwrap_or_else(|_| "8125".to_string());
+            return Some(Self::new(
+                &format!("{}:{}", host, port),
+                prefix.as_deref(),
+                StatsdFlavor::DogStatsd,
+            ));
+        }
+        if let Ok(host) = env::var("STATSD_HOST") {
+            let port = env::var("STATSD_PORT").unwrap_or_else(|_| "8125".to_string());
+            return Some(Self::new(
+                &format!("{}:{}", host, port),
+                prefix.as_deref(),
+                StatsdFlavor::Statsd,
+            ));
+        }
+        None
+    }
+
+    /// Tags attached to every DogStatsD line, e.g. `service`, `env`, `version`.
+    pub fn with_constant_tags(mut self, tags: HashMap<String, String>) -> Self {
+        let mut tags: Vec<_> = tags.into_iter().collect();
+        tags.sort();
+        self.constant_tags = tags
+            .into_iter()
+            .map(|(k, v)| format!("{}:{}", sanitize(&k), sanitize(&v)))
+            .collect();
+        self
+    }
+
+    fn format_line(
+        &self,
+        name: &str,
+        value: &str,
+        kind: &str,
+        labels: Option<&HashMap<String, String>>,
+    ) -> String {
+        let mut line = match &self.prefix {
+            Some(prefix) => format!("{}.{}:{}|{}", prefix, sanitize(name), value, kind),
+            None => format!("{}:{}|{}", sanitize(name), value, kind),
+        };
+
+        if self.flavor == StatsdFlavor::DogStatsd {
+            let mut tags = self.constant_tags.clone();
+            if let Some(labels) = labels {
+                let mut sorted: Vec<_> = labels.iter().collect();
+                sorted.sort_by_key(|a| a.0);
+                tags.extend(
+                    sorted
+                        .into_iter()
+                        .map(|(k, v)| format!("{}:{}", sanitize(k), sanitize(v))),
+                );
+            }
+            if !tags.is_empty() {
+                line.push_str("|#");
+                line.push_str(&tags.join(","));
+            }
+        }
+        line
+    }
+
+    fn send(&self, line: String) {
+        if let Err(e) = self.socket.send(line.as_bytes()) {
+            debug!("StatsD send failed: {}", e);
+        }
+    }
+}
+
+impl MetricsSink for StatsdSink {
+    fn counter(&self, name: &str, value: i64, labels: Option<&HashMap<String, String>>) {
+        self.send(self.format_line(name, &value.to_string(), "c", labels));
+    }
+
+    fn gauge(&self, name: &str, value: f64, labels: Option<&HashMap<String, String>>) {
+        self.send(self.format_line(name, &value.to_string(), "g", labels));
+    }
+
+    fn histogram(&self, name: &str, value: f64, labels: Option<&HashMap<String, String>>) {
+        let line = if name.ends_with("_seconds") {
+            self.format_line(name, &(value * 1000.0).to_string(), "ms", labels)
+        } else {
+            let kind = match self.flavor {
+                StatsdFlavor::DogStatsd => "h",
+                StatsdFlavor::Statsd => "ms",
+            };
+            self.format_line(name, &value.to_string(), kind, labels)
+        };
+        self.send(line);
+    }
+}
+
+/// Replaces characters that are reserved in the StatsD line protocol.
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            ':' | '|' | '@' | '#' | ',' | '\n' => '_',
+            c => c,
+        })
+        .collect()
+}
@@ -0,0 +1,53 @@
+use super::{BucketHistogram, MetricLabels};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistogramStats {
+    pub count: u64,
+    pub sum: f64,
+    pub avg: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+impl From<&BucketHistogram> for HistogramStats {
+    fn from(histogram: &BucketHistogram) -> Self {
+        let count = histogram.count();
+        let sum = histogram.sum();
+        Self {
+            count,
+            sum,
+            avg: if count > 0 { sum / count as f64 } else { 0.0 },
+            p50: histogram.percentile(50.0),
+            p95: histogram.percentile(95.0),
+            p99: histogram.percentile(99.0),
+        }
+    }
+}
+
+/// Point-in-time copy of every series in a `SimpleMetrics`, keyed by the same
+/// `name{label=value,...}` strings used internally.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub labels: MetricLabels,
+    pub timestamp: f64,
+    pub counters: HashMap<String, i64>,
+    pub gauges: HashMap<String, f64>,
+    pub histograms: HashMap<String, HistogramStats>,
+}
+
+impl MetricsSnapshot {
+    pub fn counter(&self, key: &str) -> i64 {
+        self.counters.get(key).copied().unwrap_or(0)
+    }
+
+    pub fn gauge(&self, key: &str) -> Option<f64> {
+        self.gauges.get(key).copied()
+    }
+
+    pub fn histogram(&self, key: &str) -> Option<&HistogramStats> {
+        self.histograms.get(key)
+    }
+}
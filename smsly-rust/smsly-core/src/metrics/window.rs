@@ -0,0 +1,62 @@
+use super::BucketHistogram;
+use std::time::{Duration, Instant};
+
+/// Histogram over a trailing time window, kept as a ring of per-slot bucket
+/// histograms. Slots older than the window are cleared as time advances, so
+/// percentiles reflect only recent samples.
+#[derive(Debug, Clone)]
+pub struct WindowedHistogram {
+    slots: Vec<BucketHistogram>,
+    slot_duration: Duration,
+    current: usize,
+    current_start: Instant,
+}
+
+impl WindowedHistogram {
+    /// `window` is split into `slots` equal parts (minimum one).
+    pub fn new(bounds: &[f64], window: Duration, slots: usize) -> Self {
+        let slots = slots.max(1);
+        Self {
+            slots: vec![BucketHistogram::new(bounds); slots],
+            slot_duration: (window / slots as u32).max(Duration::from_millis(1)),
+            current: 0,
+            current_start: Instant::now(),
+        }
+    }
+
+    fn rotate(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.current_start);
+        let steps = (elapsed.as_nanos() / self.slot_duration.as_nanos()) as usize;
+        if steps == 0 {
+            return;
+        }
+        for _ in 0..steps.min(self.slots.len()) {
+            self.current = (self.current + 1) % self.slots.len();
+            let bounds = self.slots[self.current].bounds().to_vec();
+            self.slots[self.current] = BucketHistogram::new(&bounds);
+        }
+        self.current_start += self.slot_duration * steps as u32;
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        self.rotate(Instant::now());
+        self.slots[self.current].observe(value);
+    }
+
+    pub fn clear(&mut self) {
+        for slot in self.slots.iter_mut() {
+            *slot = BucketHistogram::new(slot.bounds());
+        }
+        self.current_start = Instant::now();
+    }
+
+    /// Merged view of every slot still inside the window.
+    pub fn merged(&mut self) -> BucketHistogram {
+        self.rotate(Instant::now());
+        let mut merged = BucketHistogram::new(self.slots[0].bounds());
+        for slot in &self.slots {
+            merged.merge(slot);
+        }
+        merged
+    }
+}
@@ -0,0 +1,66 @@
+use crate::metrics::{Exemplar, MetricNames, GLOBAL_METRICS};
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Records `HTTP_REQUESTS_TOTAL` and `HTTP_REQUEST_DURATION` for every request,
+/// labelled by method, matched route template and status class. Install with
+/// `Router::layer(axum::middleware::from_fn(metrics_middleware))` so the
+/// `MatchedPath` extension is available; unmatched requests use `unmatched`.
+/// The request's trace or request id is attached to the duration as an exemplar.
+pub async fn metrics_middleware(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let exemplar = request_exemplar(&request);
+
+    let response = next.run(request).await;
+
+    let labels = HashMap::from([
+        ("method".to_string(), method),
+        ("route".to_string(), route),
+        (
+            "status".to_string(),
+            format!("{}xx", response.status().as_u16() / 100),
+        ),
+    ]);
+    GLOBAL_METRICS.increment(MetricNames::HTTP_REQUESTS_TOTAL, 1, Some(labels.clone()));
+    let duration = start.elapsed().as_secs_f64();
+    match exemplar {
+        Some(exemplar) => GLOBAL_METRICS.observe_with_exemplar(
+            MetricNames::HTTP_REQUEST_DURATION,
+            duration,
+            Some(labels),
+            exemplar,
+        ),
+        None => GLOBAL_METRICS.observe(MetricNames::HTTP_REQUEST_DURATION, duration, Some(labels)),
+    }
+
+    response
+}
+
+/// Uses the W3C `traceparent` trace id when present, otherwise `X-Request-ID`.
+fn request_exemplar(request: &Request) -> Option<Exemplar> {
+    let headers = request.headers();
+    let trace_id = headers
+        .get("traceparent")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|v| v.split('-').nth(1))
+        .filter(|id| id.len() == 32);
+    if let Some(trace_id) = trace_id {
+        return Some(Exemplar::with_label("trace_id", trace_id));
+    }
+    headers
+        .get("X-Request-ID")
+        .and_then(|h| h.to_str().ok())
+        .map(|id| Exemplar::with_label("request_id", id))
+}
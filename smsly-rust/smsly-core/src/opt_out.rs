@@ -0,0 +1,373 @@
+//! Opt-out and opt-in keywords on inbound SMS.
+//!
+//! Carriers and regulators require that replying STOP (or the local
+//! equivalent: ARRET, BAJA, STOPP…) ends messaging from that sender, that
+//! the opt-out is confirmed with one final reply, and that START or similar
+//! undoes it. `KeywordEngine` recognises those replies per language,
+//! records them in an `OptOutStore`, and sends the confirmation back
+//! through the provider the reply came in on. The send path checks
+//! `is_opted_out` before dispatch.
+
+use crate::adapters::{InboundMessage, ProviderRegistry};
+use crate::metrics::GLOBAL_METRICS;
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::{Client, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+#[derive(Error, Debug)]
+pub enum OptOutError {
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    #[error("Opt-out store error: {0}")]
+    Store(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeywordAction {
+    OptOut,
+    OptIn,
+    Help,
+}
+
+/// Where opt-outs are kept. Numbers are passed normalised to digits.
+#[async_trait]
+pub trait OptOutStore: Send + Sync {
+    async fn opt_out(&self, account_id: &str, msisdn: &str) -> Result<(), OptOutError>;
+    async fn opt_in(&self, account_id: &str, msisdn: &str) -> Result<(), OptOutError>;
+    async fn is_opted_out(&self, account_id: &str, msisdn: &str) -> Result<bool, OptOutError>;
+}
+
+/// Opt-outs as one Redis set per account.
+pub struct RedisOptOutStore {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    key_prefix: String,
+}
+
+impl RedisOptOutStore {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            key_prefix: "smsly:optout".to_string(),
+        }
+    }
+
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    fn key(&self, account_id: &str) -> String {
+        format!("{}:{}", self.key_prefix, account_id)
+    }
+}
+
+#[async_trait]
+impl OptOutStore for RedisOptOutStore {
+    async fn opt_out(&self, account_id: &str, msisdn: &str) -> Result<(), OptOutError> {
+        let mut conn = self.conn().await?;
+        redis::cmd("SADD")
+            .arg(self.key(account_id))
+            .arg(msisdn)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn opt_in(&self, account_id: &str, msisdn: &str) -> Result<(), OptOutError> {
+        let mut conn = self.conn().await?;
+        redis::cmd("SREM")
+            .arg(self.key(account_id))
+            .arg(msisdn)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn is_opted_out(&self, account_id: &str, msisdn: &str) -> Result<bool, OptOutError> {
+        let mut conn = self.conn().await?;
+        Ok(redis::cmd("SISMEMBER")
+            .arg(self.key(account_id))
+            .arg(msisdn)
+            .query_async(&mut conn)
+            .await?)
+    }
+}
+
+/// Keywords and confirmation replies for one language. Keywords are
+/// matched on the whole message, case-insensitively and ignoring accents
+/// and surrounding punctuation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeywordSet {
+    /// ISO 639-1 code.
+    pub language: String,
+    pub opt_out: Vec<String>,
+    pub opt_in: Vec<String>,
+    pub help: Vec<String>,
+    /// Reply per action; actions without one get no reply.
+    pub replies: HashMap<KeywordAction, String>,
+}
+
+impl KeywordSet {
+    fn new(
+        language: &str,
+        opt_out: &[&str],
+        opt_in: &[&str],
+        help: &[&str],
+        replies: [(KeywordAction, &str); 3],
+    ) -> Self {
+        let words = |list: &[&str]| list.iter().map(|w| normalize(w)).collect();
+        Self {
+            language: language.to_string(),
+            opt_out: words(opt_out),
+            opt_in: words(opt_in),
+            help: words(help),
+            replies: replies
+                .into_iter()
+                .map(|(action, text)| (action, text.to_string()))
+                .collect(),
+        }
+    }
+
+    fn action(&self, keyword: &str) -> Option<KeywordAction> {
+        let contains = |list: &[String]| list.iter().any(|w| w == keyword);
+        if contains(&self.opt_out) {
+            Some(KeywordAction::OptOut)
+        } else if contains(&self.opt_in) {
+            Some(KeywordAction::OptIn)
+        } else if contains(&self.help) {
+            Some(KeywordAction::Help)
+        } else {
+            None
+        }
+    }
+}
+
+/// English, French, Spanish, German, Portuguese and Italian keywords with
+/// CTIA-style confirmations. English is checked first, so STOP in any
+/// language is confirmed in English.
+pub fn default_keyword_sets() -> Vec<KeywordSet> {
+    use KeywordAction::*;
+    vec![
+        KeywordSet::new(
+            "en",
+            &["STOP", "STOPALL", "UNSUBSCRIBE", "UNSUB", "CANCEL", "END", "QUIT", "OPTOUT", "REVOKE"],
+            &["START", "UNSTOP", "SUBSCRIBE", "YES"],
+            &["HELP", "INFO"],
+            [
+                (OptOut, "You have been unsubscribed and will receive no further messages. Reply START to resubscribe."),
+                (OptIn, "You have been resubscribed. Reply STOP to unsubscribe."),
+                (Help, "Reply STOP to unsubscribe or START to resubscribe."),
+            ],
+        ),
+        KeywordSet::new(
+            "fr",
+            &["ARRET", "ARRETER", "DESABONNER", "DESINSCRIRE", "STOPPER"],
+            &["DEMARRER", "ABONNER"],
+            &["AIDE"],
+            [
+                (OptOut, "Vous êtes désinscrit et ne recevrez plus de messages. Répondez DEMARRER pour vous réinscrire."),
+                (OptIn, "Vous êtes réinscrit. Répondez ARRET pour vous désinscrire."),
+                (Help, "Répondez ARRET pour vous désinscrire ou DEMARRER pour vous réinscrire."),
+            ],
+        ),
+        KeywordSet::new(
+            "es",
+            &["BAJA", "ALTO", "PARAR", "DETENER", "CANCELAR"],
+            &["ALTA", "INICIAR"],
+            &["AYUDA"],
+            [
+                (OptOut, "Te has dado de baja y no recibirás más mensajes. Responde ALTA para volver a suscribirte."),
+                (OptIn, "Te has vuelto a suscribir. Responde BAJA para darte de baja."),
+                (Help, "Responde BAJA para darte de baja o ALTA para volver a suscribirte."),
+            ],
+        ),
+        KeywordSet::new(
+            "de",
+            &["STOPP", "ABMELDEN", "ABBESTELLEN"],
+            &["ANMELDEN"],
+            &["HILFE"],
+            [
+                (OptOut, "Sie wurden abgemeldet und erhalten keine weiteren Nachrichten. Antworten Sie ANMELDEN, um sich wieder anzumelden."),
+                (OptIn, "Sie wurden wieder angemeldet. Antworten Sie STOPP, um sich abzumelden."),
+                (Help, "Antworten Sie STOPP zum Abmelden oder ANMELDEN zum Anmelden."),
+            ],
+        ),
+        KeywordSet::new(
+            "pt",
+            &["SAIR", "PARE", "DESCADASTRAR"],
+            &["VOLTAR", "RETORNAR"],
+            &["AJUDA"],
+            [
+                (OptOut, "Você cancelou a inscrição e não receberá mais mensagens. Responda VOLTAR para se inscrever novamente."),
+                (OptIn, "Sua inscrição foi reativada. Responda SAIR para cancelar."),
+                (Help, "Responda SAIR para cancelar ou VOLTAR para se inscrever novamente."),
+            ],
+        ),
+        KeywordSet::new(
+            "it",
+            &["ANNULLA", "BASTA", "DISISCRIVI", "FERMA"],
+            &["ISCRIVI"],
+            &["AIUTO"],
+            [
+                (OptOut, "Hai annullato l'iscrizione e non riceverai altri messaggi. Rispondi ISCRIVI per iscriverti di nuovo."),
+                (OptIn, "Ti sei iscritto di nuovo. Rispondi ANNULLA per annullare l'iscrizione."),
+                (Help, "Rispondi ANNULLA per annullare l'iscrizione o ISCRIVI per iscriverti di nuovo."),
+            ],
+        ),
+    ]
+}
+
+/// Uppercases, strips accents from Latin letters and drops everything but
+/// letters and digits, so "Arrêt." matches ARRET.
+fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' => {
+                'A'
+            }
+            'ç' | 'Ç' => 'C',
+            'è' | 'é' | 'ê' | 'ë' | 'È' | 'É' | 'Ê' | 'Ë' => 'E',
+            'ì' | 'í' | 'î' | 'ï' | 'Ì' | 'Í' | 'Î' | 'Ï' => 'I',
+            'ñ' | 'Ñ' => 'N',
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' => 'O',
+            'ù' | 'ú' | 'û' | 'ü' | 'Ù' | 'Ú' | 'Û' | 'Ü' => 'U',
+            c => c.to_ascii_uppercase(),
+        })
+        .filter(char::is_ascii_alphanumeric)
+        .collect()
+}
+
+fn normalize_msisdn(number: &str) -> String {
+    number.chars().filter(char::is_ascii_digit).collect()
+}
+
+/// A recognised keyword.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeywordMatch {
+    pub action: KeywordAction,
+    pub language: String,
+    /// Whether the confirmation went out.
+    pub replied: bool,
+}
+
+pub struct KeywordEngine {
+    store: Arc<dyn OptOutStore>,
+    registry: Arc<ProviderRegistry>,
+    keyword_sets: Vec<KeywordSet>,
+}
+
+impl KeywordEngine {
+    pub fn new(store: Arc<dyn OptOutStore>, registry: Arc<ProviderRegistry>) -> Self {
+        Self {
+            store,
+            registry,
+            keyword_sets: default_keyword_sets(),
+        }
+    }
+
+    /// Replaces the keyword sets, e.g. with an account's own wording.
+    pub fn with_keyword_sets(mut self, keyword_sets: Vec<KeywordSet>) -> Self {
+        self.keyword_sets = keyword_sets;
+        self
+    }
+
+    /// The action and language of `body` if it is exactly a keyword.
+    pub fn classify(&self, body: &str) -> Option<(KeywordAction, &KeywordSet)> {
+        let keyword = normalize(body);
+        if keyword.is_empty() {
+            return None;
+        }
+        self.keyword_sets
+            .iter()
+            .find_map(|set| set.action(&keyword).map(|action| (action, set)))
+    }
+
+    /// Handles `message`, received for `account_id` through `provider`:
+    /// records opt-outs and opt-ins, then replies from the number that was
+    /// texted. `None` if the message isn't a keyword. A failed reply is
+    /// logged but doesn't undo the opt-out.
+    pub async fn handle_inbound(
+        &self,
+        account_id: &str,
+        provider: &str,
+        message: &InboundMessage,
+    ) -> Result<Option<KeywordMatch>, OptOutError> {
+        let Some((action, set)) = self.classify(&message.body) else {
+            return Ok(None);
+        };
+        let msisdn = normalize_msisdn(&message.from);
+        match action {
+            KeywordAction::OptOut => self.store.opt_out(account_id, &msisdn).await?,
+            KeywordAction::OptIn => self.store.opt_in(account_id, &msisdn).await?,
+            KeywordAction::Help => {}
+        }
+        info!(
+            "{:?} keyword from {} for account {} ({})",
+            action, message.from, account_id, set.language
+        );
+        GLOBAL_METRICS.increment(
+            "opt_out_keywords",
+            1,
+            Some(HashMap::from([
+                (
+                    "action".to_string(),
+                    serde_json::to_value(action)
+                        .ok()
+                        .and_then(|v| v.as_str().map(str::to_string))
+                        .unwrap_or_default(),
+                ),
+                ("language".to_string(), set.language.clone()),
+            ])),
+        );
+
+        let mut replied = false;
+        if let Some(reply) = set.replies.get(&action) {
+            match self.registry.get(provider).await {
+                Ok(adapter) => {
+                    let result = adapter
+                        .send_sms(&message.from, &message.to, reply, None)
+                        .await;
+                    replied = result.success;
+                    if !result.success {
+                        warn!(
+                            "Keyword reply to {} via {} failed: {}",
+                            message.from,
+                            provider,
+                            result.error_message.unwrap_or_default()
+                        );
+                    }
+                }
+                Err(e) => warn!("Keyword reply to {} not sent: {}", message.from, e),
+            }
+        }
+        Ok(Some(KeywordMatch {
+            action,
+            language: set.language.clone(),
+            replied,
+        }))
+    }
+
+    /// Whether `msisdn` opted out of `account_id`'s messages. Callers
+    /// should not send when this errors.
+    pub async fn is_opted_out(&self, account_id: &str, msisdn: &str) -> Result<bool, OptOutError> {
+        self.store
+            .is_opted_out(account_id, &normalize_msisdn(msisdn))
+            .await
+    }
+}
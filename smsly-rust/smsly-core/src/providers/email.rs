@@ -0,0 +1,389 @@
+use super::signature::verify_basic_auth;
+use super::sns::{host_header, sign_v4, SigningScope};
+use super::{failed, http_client};
+use crate::adapters::{BaseProviderAdapter, MessageStatus, SendResult, WebhookEvent};
+use crate::dlr;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Method, Url};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use tracing::{error, info, warn};
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// For temporary (STS) credentials.
+    pub session_token: Option<String>,
+    /// Defaults to `https://email.<region>.amazonaws.com`.
+    pub endpoint: String,
+    /// Verified sender used when `from` is empty.
+    pub from_address: String,
+    /// Subject when the metadata has no `subject`.
+    pub default_subject: String,
+    /// Configuration set whose SNS destination publishes bounces and
+    /// complaints.
+    pub configuration_set: Option<String>,
+    /// Basic-auth credentials embedded in the SNS subscription URL; SNS
+    /// sends them on every notification. Without them every webhook is
+    /// rejected.
+    pub webhook_credentials: Option<(String, String)>,
+}
+
+impl EmailConfig {
+    pub fn new(
+        region: &str,
+        access_key_id: &str,
+        secret_access_key: &str,
+        from_address: &str,
+    ) -> Self {
+        Self {
+            region: region.to_string(),
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+            session_token: None,
+            endpoint: format!("https://email.{}.amazonaws.com", region),
+            from_address: from_address.to_string(),
+            default_subject: "Notification".to_string(),
+            configuration_set: None,
+            webhook_credentials: None,
+        }
+    }
+
+    pub fn with_default_subject(mut self, subject: &str) -> Self {
+        self.default_subject = subject.to_string();
+        self
+    }
+
+    pub fn with_configuration_set(mut self, name: &str) -> Self {
+        self.configuration_set = Some(name.to_string());
+        self
+    }
+
+    pub fn with_webhook_credentials(mut self, username: &str, password: &str) -> Self {
+        self.webhook_credentials = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Reads the standard AWS credential variables plus `SES_FROM_ADDRESS`,
+    /// `SES_CONFIGURATION_SET`, `SES_DEFAULT_SUBJECT` and
+    /// `SES_WEBHOOK_USERNAME` / `SES_WEBHOOK_PASSWORD`.
+    pub fn from_env() -> Option<Self> {
+        let non_empty = |key: &str| env::var(key).ok().filter(|v| !v.is_empty());
+        let region = non_empty("AWS_REGION").or_else(|| non_empty("AWS_DEFAULT_REGION"))?;
+        let mut config = Self::new(
+            &region,
+            &non_empty("AWS_ACCESS_KEY_ID")?,
+            &non_empty("AWS_SECRET_ACCESS_KEY")?,
+            &non_empty("SES_FROM_ADDRESS")?,
+        );
+        config.session_token = non_empty("AWS_SESSION_TOKEN");
+        config.configuration_set = non_empty("SES_CONFIGURATION_SET");
+        if let Some(subject) = non_empty("SES_DEFAULT_SUBJECT") {
+            config.default_subject = subject;
+        }
+        if let (Some(user), Some(pass)) = (
+            non_empty("SES_WEBHOOK_USERNAME"),
+            non_empty("SES_WEBHOOK_PASSWORD"),
+        ) {
+            config.webhook_credentials = Some((user, pass));
+        }
+        Some(config)
+    }
+}
+
+/// Email through the Amazon SES v2 API, so OTP and notification flows can
+/// fall back to email through the same registry. `send_sms` takes an email
+/// address as `to` and the text body; metadata keys `subject` and `html`
+/// fill in the rest.
+///
+/// Bounces and complaints arrive as SNS notifications from the
+/// configuration set's event destination and come back from
+/// `parse_webhook` as failed events with a normalized `reason`.
+pub struct EmailAdapter {
+    config: EmailConfig,
+    client: Client,
+}
+
+impl EmailAdapter {
+    pub fn new(config: EmailConfig) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+
+    pub async fn send_email(
+        &self,
+        to: &str,
+        from: &str,
+        subject: &str,
+        text: &str,
+        html: Option<&str>,
+    ) -> SendResult {
+        let mut body = json!({ "Text": { "Data": text, "Charset": "UTF-8" } });
+        if let Some(html) = html {
+            body["Html"] = json!({ "Data": html, "Charset": "UTF-8" });
+        }
+        let mut request = json!({
+            "FromEmailAddress": if from.is_empty() { self.config.from_address.as_str() } else { from },
+            "Destination": { "ToAddresses": [to] },
+            "Content": { "Simple": {
+                "Subject": { "Data": subject, "Charset": "UTF-8" },
+                "Body": body,
+            } },
+        });
+        if let Some(set) = &self.config.configuration_set {
+            request["ConfigurationSetName"] = json!(set);
+        }
+
+        let response = self
+            .call(Method::POST, "/v2/email/outbound-emails", Some(&request))
+            .await;
+        let (status, data) = match response {
+            Ok(response) => response,
+            Err(e) => {
+                error!("SES send failed: {}", e);
+                return failed(None, e, None);
+            }
+        };
+        if (200..300).contains(&status) {
+            SendResult {
+                success: true,
+                provider_message_id: data["MessageId"].as_str().map(str::to_string),
+                status: MessageStatus::Sent,
+                raw_response: Some(data),
+                segments: 1,
+                ..Default::default()
+            }
+        } else {
+            let message = data["message"]
+                .as_str()
+                .or(data["Message"].as_str())
+                .unwrap_or("Unknown error")
+                .to_string();
+            failed(Some(status.to_string()), message, Some(data))
+        }
+    }
+
+    /// Sends a signed JSON request and returns the HTTP status and body.
+    async fn call(
+        &self,
+        method: Method,
+        path: &str,
+        request: Option<&Value>,
+    ) -> Result<(u16, Value), String> {
+        let url = Url::parse(&format!(
+            "{}{}",
+            self.config.endpoint.trim_end_matches('/'),
+            path
+        ))
+        .map_err(|e| e.to_string())?;
+        let host = host_header(&url)?;
+        let body = request.map(Value::to_string).unwrap_or_default();
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let scope = SigningScope {
+            region: &self.config.region,
+            service: "ses",
+            access_key_id: &self.config.access_key_id,
+            secret_access_key: &self.config.secret_access_key,
+            session_token: self.config.session_token.as_deref(),
+        };
+        let authorization = sign_v4(
+            &scope,
+            method.as_str(),
+            &host,
+            url.path(),
+            &amz_date,
+            JSON_CONTENT_TYPE,
+            &body,
+        );
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("Content-Type", JSON_CONTENT_TYPE)
+            .header("X-Amz-Date", &amz_date)
+            .header("Authorization", authorization);
+        if let Some(token) = &self.config.session_token {
+            request = request.header("X-Amz-Security-Token", token);
+        }
+        let response = request.body(body).send().await.map_err(|e| e.to_string())?;
+        let status = response.status().as_u16();
+        Ok((status, response.json().await.unwrap_or(Value::Null)))
+    }
+
+    /// Confirms an SNS `SubscriptionConfirmation` by fetching its
+    /// `SubscribeURL`, which must be on `amazonaws.com`.
+    pub async fn confirm_subscription(&self, body: &[u8]) -> Result<(), String> {
+        let envelope: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+        if envelope["Type"] != "SubscriptionConfirmation" {
+            return Err("not a subscription confirmation".to_string());
+        }
+        let url = envelope["SubscribeURL"]
+            .as_str()
+            .and_then(|url| Url::parse(url).ok())
+            .filter(|url| {
+                url.scheme() == "https"
+                    && url
+                        .host_str()
+                        .is_some_and(|host| host.ends_with(".amazonaws.com"))
+            })
+            .ok_or_else(|| "missing or untrusted SubscribeURL".to_string())?;
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("confirmation returned {}", response.status()));
+        }
+        info!("SES notification subscription confirmed");
+        Ok(())
+    }
+}
+
+fn parse_timestamp(ts: &Value) -> Option<f64> {
+    ts.as_str()
+        .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+        .map(|dt| dt.timestamp() as f64)
+}
+
+/// Parses an SES notification, either the SNS envelope or the bare message.
+/// Handles both the notification (`notificationType`) and event publishing
+/// (`eventType`) formats.
+pub fn parse_notification(body: &[u8]) -> Result<WebhookEvent, String> {
+    let envelope: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let message = match &envelope["Message"] {
+        Value::String(inner) => serde_json::from_str(inner).map_err(|e| e.to_string())?,
+        _ => envelope,
+    };
+    let kind = message["notificationType"]
+        .as_str()
+        .or(message["eventType"].as_str())
+        .ok_or_else(|| "SES notification missing notificationType".to_string())?;
+    let provider_message_id = message["mail"]["messageId"]
+        .as_str()
+        .ok_or_else(|| "SES notification missing mail.messageId".to_string())?
+        .to_string();
+
+    let (status, error_code, error_message, timestamp) = match kind {
+        "Delivery" => (
+            MessageStatus::Delivered,
+            None,
+            None,
+            &message["delivery"]["timestamp"],
+        ),
+        "Bounce" => {
+            let bounce = &message["bounce"];
+            let code = format!(
+                "{}:{}",
+                bounce["bounceType"].as_str().unwrap_or("Undetermined"),
+                bounce["bounceSubType"].as_str().unwrap_or("Undetermined")
+            );
+            let diagnostic = bounce["bouncedRecipients"][0]["diagnosticCode"]
+                .as_str()
+                .map(str::to_string);
+            (
+                MessageStatus::Failed,
+                Some(code),
+                diagnostic,
+                &bounce["timestamp"],
+            )
+        }
+        "Complaint" => {
+            let complaint = &message["complaint"];
+            (
+                MessageStatus::Failed,
+                Some("Complaint".to_string()),
+                complaint["complaintFeedbackType"]
+                    .as_str()
+                    .map(str::to_string),
+                &complaint["timestamp"],
+            )
+        }
+        "Reject" => (
+            MessageStatus::Rejected,
+            Some("Reject".to_string()),
+            message["reject"]["reason"].as_str().map(str::to_string),
+            &message["mail"]["timestamp"],
+        ),
+        "Send" => (
+            MessageStatus::Sent,
+            None,
+            None,
+            &message["mail"]["timestamp"],
+        ),
+        other => return Err(format!("unhandled SES notification type {}", other)),
+    };
+
+    Ok(WebhookEvent {
+        provider_message_id,
+        status,
+        timestamp: parse_timestamp(timestamp),
+        reason: error_code
+            .as_deref()
+            .and_then(|code| dlr::normalize("ses", Some(code), error_message.as_deref())),
+        error_code,
+        error_message,
+        raw_payload: Some(message),
+    })
+}
+
+#[async_trait]
+impl BaseProviderAdapter for EmailAdapter {
+    fn name(&self) -> String {
+        "email".to_string()
+    }
+
+    fn supports_sms(&self) -> bool {
+        false
+    }
+
+    fn supports_email(&self) -> bool {
+        true
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let field = |key: &str| {
+            metadata
+                .as_ref()
+                .and_then(|m| m.get(key))
+                .and_then(Value::as_str)
+        };
+        let subject = field("subject").unwrap_or(&self.config.default_subject);
+        self.send_email(to, from, subject, body, field("html"))
+            .await
+    }
+
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, _body: &[u8]) -> bool {
+        let Some((user, pass)) = &self.config.webhook_credentials else {
+            warn!("SES webhook rejected: no webhook credentials configured");
+            return false;
+        };
+        verify_basic_auth(headers, user, pass)
+    }
+
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        parse_notification(body)
+    }
+
+    async fn health_check(&self) -> bool {
+        // GetAccount succeeds only with valid credentials.
+        self.call(Method::GET, "/v2/email/account", None)
+            .await
+            .is_ok_and(|(status, _)| (200..300).contains(&status))
+    }
+}
@@ -0,0 +1,355 @@
+use super::{failed, http_client};
+use crate::adapters::{BaseProviderAdapter, MessageStatus, SendResult};
+use crate::segments::count_segments;
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use reqwest::{Client, Method};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::Path;
+use tracing::{error, warn};
+
+lazy_static! {
+    static ref PLACEHOLDER: Regex = Regex::new(r"\{\{\s*([A-Za-z0-9_.]+)\s*\}\}").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyFormat {
+    #[default]
+    Json,
+    Form,
+}
+
+/// How to build the send request. Strings anywhere in `url`, `headers` and
+/// `body` may contain placeholders: `{{to}}`, `{{to_digits}}` (no leading
+/// `+`), `{{from}}`, `{{body}}`, `{{metadata.<key>}}` and `{{env.<VAR>}}`
+/// for credentials kept out of the config file. Values substituted into the
+/// URL are percent-encoded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestTemplate {
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// JSON template; for `form` it must be an object of scalar values.
+    #[serde(default)]
+    pub body: Option<Value>,
+    #[serde(default)]
+    pub body_format: BodyFormat,
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+/// Where to find things in the JSON response. Paths are dot-separated with
+/// numeric array indices, e.g. `messages.0.id`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResponseMapping {
+    /// Without it a 2xx status means success.
+    #[serde(default)]
+    pub success_path: Option<String>,
+    /// Values at `success_path` that mean success; when empty any truthy
+    /// value (`true`, non-zero, non-empty string) does.
+    #[serde(default)]
+    pub success_values: Vec<Value>,
+    #[serde(default)]
+    pub message_id_path: Option<String>,
+    #[serde(default)]
+    pub error_path: Option<String>,
+    #[serde(default)]
+    pub error_code_path: Option<String>,
+    #[serde(default)]
+    pub cost_path: Option<String>,
+}
+
+/// A provider defined entirely in JSON, for small aggregators with a plain
+/// "POST a message, get an id back" API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenericHttpConfig {
+    pub name: String,
+    pub request: RequestTemplate,
+    #[serde(default)]
+    pub response: ResponseMapping,
+    /// Polled by `health_check`; any 2xx is healthy. Placeholders limited
+    /// to `{{env.<VAR>}}`.
+    #[serde(default)]
+    pub health_url: Option<String>,
+}
+
+impl GenericHttpConfig {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_json(&json)
+            .map_err(|e| format!("Invalid provider config {}: {}", path.display(), e))
+    }
+}
+
+/// Values available to placeholders for one message.
+struct Context<'a> {
+    to: &'a str,
+    from: &'a str,
+    body: &'a str,
+    metadata: Option<&'a HashMap<String, Value>>,
+}
+
+impl Context<'_> {
+    fn resolve(&self, key: &str) -> Option<String> {
+        if let Some(var) = key.strip_prefix("env.") {
+            return env::var(var).ok();
+        }
+        if let Some(meta_key) = key.strip_prefix("metadata.") {
+            return self.metadata?.get(meta_key).map(|value| match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            });
+        }
+        match key {
+            "to" => Some(self.to.to_string()),
+            "to_digits" => Some(self.to.trim_start_matches('+').to_string()),
+            "from" => Some(self.from.to_string()),
+            "body" => Some(self.body.to_string()),
+            _ => None,
+        }
+    }
+
+    fn render(&self, template: &str, encode: bool) -> Result<String, String> {
+        let mut missing = None;
+        let rendered =
+            PLACEHOLDER.replace_all(template, |caps: &Captures| match self.resolve(&caps[1]) {
+                Some(value) if encode => percent_encode(&value),
+                Some(value) => value,
+                None => {
+                    missing.get_or_insert_with(|| caps[1].to_string());
+                    String::new()
+                }
+            });
+        match missing {
+            Some(key) => Err(format!("Unresolved placeholder: {}", key)),
+            None => Ok(rendered.into_owned()),
+        }
+    }
+
+    fn render_value(&self, template: &Value) -> Result<Value, String> {
+        Ok(match template {
+            Value::String(s) => Value::String(self.render(s, false)?),
+            Value::Array(items) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.render_value(item))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(key, value)| Ok((key.clone(), self.render_value(value)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            other => other.clone(),
+        })
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Looks up a dot-separated path such as `messages.0.id`.
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |current, segment| match current {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            Value::Object(map) => map.get(segment),
+            _ => None,
+        })
+}
+
+fn as_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+        Value::Null => false,
+    }
+}
+
+pub struct GenericHttpAdapter {
+    config: GenericHttpConfig,
+    client: Client,
+}
+
+impl GenericHttpAdapter {
+    pub fn new(config: GenericHttpConfig) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+
+    fn build_request(&self, ctx: &Context<'_>) -> Result<reqwest::RequestBuilder, String> {
+        let template = &self.config.request;
+        let method = Method::from_bytes(template.method.to_uppercase().as_bytes())
+            .map_err(|_| format!("Invalid HTTP method: {}", template.method))?;
+        let mut request = self
+            .client
+            .request(method, ctx.render(&template.url, true)?);
+        for (name, value) in &template.headers {
+            request = request.header(name, ctx.render(value, false)?);
+        }
+        if let Some(body) = &template.body {
+            let body = ctx.render_value(body)?;
+            request = match template.body_format {
+                BodyFormat::Json => request.json(&body),
+                BodyFormat::Form => {
+                    let fields: Vec<(String, String)> = body
+                        .as_object()
+                        .ok_or_else(|| "Form body template must be an object".to_string())?
+                        .iter()
+                        .filter_map(|(key, value)| as_string(value).map(|v| (key.clone(), v)))
+                        .collect();
+                    request.form(&fields)
+                }
+            };
+        }
+        Ok(request)
+    }
+
+    fn is_success(&self, http_ok: bool, data: &Value) -> bool {
+        let mapping = &self.config.response;
+        let Some(path) = &mapping.success_path else {
+            return http_ok;
+        };
+        match lookup(data, path) {
+            Some(value) if mapping.success_values.is_empty() => is_truthy(value),
+            Some(value) => mapping.success_values.contains(value),
+            None => false,
+        }
+    }
+}
+
+#[async_trait]
+impl BaseProviderAdapter for GenericHttpAdapter {
+    fn name(&self) -> String {
+        self.config.name.clone()
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let ctx = Context {
+            to,
+            from,
+            body,
+            metadata: metadata.as_ref(),
+        };
+        let request = match self.build_request(&ctx) {
+            Ok(request) => request,
+            Err(e) => {
+                error!("{} request template error: {}", self.config.name, e);
+                return failed(Some("template_error".to_string()), e, None);
+            }
+        };
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                error!("{} send failed: {}", self.config.name, e);
+                return failed(None, e.to_string(), None);
+            }
+        };
+
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        let data = serde_json::from_str(&text).unwrap_or_else(|_| json!({ "body": text }));
+        let mapping = &self.config.response;
+        let field = |path: &Option<String>| {
+            path.as_deref()
+                .and_then(|path| lookup(&data, path))
+                .and_then(as_string)
+        };
+
+        if self.is_success(status.is_success(), &data) {
+            return SendResult {
+                success: true,
+                provider_message_id: field(&mapping.message_id_path),
+                status: MessageStatus::Sent,
+                cost: field(&mapping.cost_path).and_then(|cost| cost.parse().ok()),
+                segments: count_segments(body),
+                raw_response: Some(data.clone()),
+                ..Default::default()
+            };
+        }
+        let code = field(&mapping.error_code_path).unwrap_or_else(|| status.as_u16().to_string());
+        let message = field(&mapping.error_path).unwrap_or_else(|| "Unknown error".to_string());
+        SendResult {
+            status: if status.is_client_error() {
+                MessageStatus::Rejected
+            } else {
+                MessageStatus::Failed
+            },
+            ..failed(Some(code), message, Some(data.clone()))
+        }
+    }
+
+    /// Configured providers have no signature scheme, so webhooks are refused.
+    async fn validate_webhook(&self, _headers: &HashMap<String, String>, _body: &[u8]) -> bool {
+        warn!(
+            "{} webhook rejected: generic adapters don't accept webhooks",
+            self.config.name
+        );
+        false
+    }
+
+    async fn health_check(&self) -> bool {
+        let Some(url) = &self.config.health_url else {
+            return true;
+        };
+        let ctx = Context {
+            to: "",
+            from: "",
+            body: "",
+            metadata: None,
+        };
+        let Ok(url) = ctx.render(url, false) else {
+            return false;
+        };
+        self.client
+            .get(url)
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,122 @@
+//! OAuth access tokens for Google APIs (RBM, FCM) from a service-account
+//! key, via the JWT-bearer grant.
+
+use super::signature::now_secs;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use reqwest::Client;
+use rsa::pkcs1v15::SigningKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::signature::{SignatureEncoding, Signer};
+use rsa::RsaPrivateKey;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::fs;
+use tokio::sync::Mutex;
+
+/// Refresh the access token this long before Google says it expires.
+const TOKEN_REFRESH_MARGIN_SECS: i64 = 60;
+
+/// The fields needed from a Google service-account key file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServiceAccountKey {
+    pub client_email: String,
+    /// PKCS#8 PEM.
+    pub private_key: String,
+    #[serde(default = "default_token_uri")]
+    pub token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    "https://oauth2.googleapis.com/token".to_string()
+}
+
+impl ServiceAccountKey {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid service account key: {}", e))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::from_json(&json)
+    }
+
+    /// Self-signed RS256 assertion for the OAuth JWT-bearer grant.
+    fn assertion(&self, scope: &str, now: i64) -> Result<String, String> {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(&self.private_key)
+            .map_err(|e| format!("invalid service account private key: {}", e))?;
+        let header = URL_SAFE_NO_PAD.encode(json!({ "alg": "RS256", "typ": "JWT" }).to_string());
+        let claims = URL_SAFE_NO_PAD.encode(
+            json!({
+                "iss": self.client_email,
+                "scope": scope,
+                "aud": self.token_uri,
+                "iat": now,
+                "exp": now + 3600,
+            })
+            .to_string(),
+        );
+        let signed = format!("{}.{}", header, claims);
+        let signature = SigningKey::<Sha256>::new(private_key).sign(signed.as_bytes());
+        Ok(format!(
+            "{}.{}",
+            signed,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        ))
+    }
+}
+
+/// Caches an access token for one key and scope, refreshing it shortly
+/// before expiry.
+pub struct GoogleTokenSource {
+    key: ServiceAccountKey,
+    scope: String,
+    /// Access token and its expiry (Unix seconds).
+    token: Mutex<Option<(String, i64)>>,
+}
+
+impl GoogleTokenSource {
+    pub fn new(key: ServiceAccountKey, scope: &str) -> Self {
+        Self {
+            key,
+            scope: scope.to_string(),
+            token: Mutex::new(None),
+        }
+    }
+
+    pub async fn access_token(&self, client: &Client) -> Result<String, String> {
+        let mut cached = self.token.lock().await;
+        let now = now_secs();
+        if let Some((token, expires_at)) = cached.as_ref() {
+            if *expires_at - TOKEN_REFRESH_MARGIN_SECS > now {
+                return Ok(token.clone());
+            }
+        }
+        let response = client
+            .post(&self.key.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &self.key.assertion(&self.scope, now)?),
+            ])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        let token = data["access_token"]
+            .as_str()
+            .filter(|_| status.is_success())
+            .ok_or_else(|| {
+                format!(
+                    "token request failed ({}): {}",
+                    status,
+                    data["error_description"].as_str().unwrap_or_default()
+                )
+            })?
+            .to_string();
+        let expires_in = data["expires_in"].as_i64().unwrap_or(3600);
+        *cached = Some((token.clone(), now + expires_in));
+        Ok(token)
+    }
+}
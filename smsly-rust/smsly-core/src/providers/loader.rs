@@ -0,0 +1,471 @@
+use super::google_auth::ServiceAccountKey;
+use super::mock::DlrScript;
+use super::push::{ApnsConfig, FcmConfig};
+use super::{
+    GenericHttpAdapter, GenericHttpConfig, InfobipAdapter, InfobipConfig, MessageBirdAdapter,
+    MessageBirdConfig, MockConfig, MockProvider, PlivoAdapter, PlivoConfig, PushAdapter,
+    PushConfig, RbmAdapter, RbmConfig, SmppAdapter, SmppConfig, TwilioAdapter, TwilioConfig,
+    ViberAdapter, ViberConfig, VonageAdapter, VonageConfig,
+};
+use crate::adapters::{BaseProviderAdapter, ProviderRegistry};
+use crate::database::{quote_ident, DatabaseError};
+use crate::whatsapp::{WhatsAppAdapter, WhatsAppConfig};
+use serde_json::Value;
+use sqlx::postgres::{PgListener, PgPool};
+use sqlx::Row;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+/// Provider table plus a trigger that notifies `provider_configs` on every
+/// change; include this in a service migration.
+pub const PROVIDER_CONFIGS_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS provider_configs (
+    name TEXT PRIMARY KEY,
+    provider_type TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    weight INTEGER NOT NULL DEFAULT 100,
+    config JSONB NOT NULL DEFAULT '{}'::jsonb,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE OR REPLACE FUNCTION notify_provider_configs() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('provider_configs', '');
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+DROP TRIGGER IF EXISTS provider_configs_notify ON provider_configs;
+CREATE TRIGGER provider_configs_notify
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON provider_configs
+    FOR EACH STATEMENT EXECUTE FUNCTION notify_provider_configs();
+"#;
+
+pub const PROVIDER_CONFIGS_CHANNEL: &str = "provider_configs";
+
+/// One row of the provider table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderRecord {
+    pub name: String,
+    pub provider_type: String,
+    pub enabled: bool,
+    pub weight: i32,
+    /// Type-specific settings. Any string value may instead be
+    /// `{"env": "VAR"}` to keep the secret in the environment.
+    pub config: Value,
+}
+
+pub type ProviderFactory =
+    Arc<dyn Fn(&ProviderRecord) -> Result<Box<dyn BaseProviderAdapter>, String> + Send + Sync>;
+
+struct Fields<'a>(&'a Value);
+
+impl Fields<'_> {
+    fn opt(&self, key: &str) -> Option<String> {
+        match &self.0[key] {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Object(map) => map
+                .get("env")
+                .and_then(Value::as_str)
+                .and_then(|var| env::var(var).ok()),
+            _ => None,
+        }
+        .filter(|v| !v.is_empty())
+    }
+
+    fn req(&self, key: &str) -> Result<String, String> {
+        self.opt(key).ok_or_else(|| format!("missing {}", key))
+    }
+}
+
+/// Builds the built-in adapters by `provider_type`: `twilio`, `plivo`,
+/// `vonage`, `messagebird`, `infobip`, `viber`, `smpp`, `generic_http`,
+/// `rbm`, `push`, `whatsapp`, `mock` and, with the `aws` feature, `sns` and `email`.
+pub fn build_adapter(record: &ProviderRecord) -> Result<Box<dyn BaseProviderAdapter>, String> {
+    let f = Fields(&record.config);
+    let adapter: Box<dyn BaseProviderAdapter> = match record.provider_type.as_str() {
+        "twilio" => {
+            let mut config = TwilioConfig::new(&f.req("account_sid")?, &f.req("auth_token")?);
+            config.messaging_service_sid = f.opt("messaging_service_sid");
+            config.webhook_url = f.opt("webhook_url");
+            if let Some(base_url) = f.opt("base_url") {
+                config.base_url = base_url;
+            }
+            Box::new(TwilioAdapter::new(config))
+        }
+        "plivo" => {
+            let mut config = PlivoConfig::new(&f.req("auth_id")?, &f.req("auth_token")?);
+            config.powerpack_uuid = f.opt("powerpack_uuid");
+            config.webhook_url = f.opt("webhook_url");
+            if let Some(base_url) = f.opt("base_url") {
+                config.base_url = base_url;
+            }
+            Box::new(PlivoAdapter::new(config))
+        }
+        "vonage" => {
+            let mut config = VonageConfig::new(&f.req("api_key")?, &f.req("api_secret")?);
+            config.signature_secret = f.opt("signature_secret");
+            if let Some(base_url) = f.opt("base_url") {
+                config.base_url = base_url;
+            }
+            Box::new(VonageAdapter::new(config))
+        }
+        "messagebird" => {
+            let mut config = MessageBirdConfig::new(&f.req("access_key")?);
+            config.signing_key = f.opt("signing_key");
+            if let Some(base_url) = f.opt("base_url") {
+                config.base_url = base_url;
+            }
+            Box::new(MessageBirdAdapter::new(config))
+        }
+        "infobip" => {
+            let mut config = InfobipConfig::new(&f.req("base_url")?, &f.req("api_key")?);
+            if let (Some(user), Some(pass)) = (f.opt("webhook_username"), f.opt("webhook_password"))
+            {
+                config = config.with_webhook_credentials(&user, &pass);
+            }
+            Box::new(InfobipAdapter::new(config))
+        }
+        "viber" => {
+            let mut config =
+                ViberConfig::new(&f.req("base_url")?, &f.req("api_key")?, &f.req("sender")?);
+            if let Some(secs) = f.opt("ttl_secs").and_then(|v| v.parse().ok()) {
+                config = config.with_ttl(Duration::from_secs(secs));
+            }
+            if let (Some(user), Some(pass)) = (f.opt("webhook_username"), f.opt("webhook_password"))
+            {
+                config = config.with_webhook_credentials(&user, &pass);
+            }
+            Box::new(ViberAdapter::new(config))
+        }
+        #[cfg(feature = "aws")]
+        "email" => {
+            let mut config = super::EmailConfig::new(
+                &f.req("region")?,
+                &f.req("access_key_id")?,
+                &f.req("secret_access_key")?,
+                &f.req("from_address")?,
+            );
+            config.session_token = f.opt("session_token");
+            config.configuration_set = f.opt("configuration_set");
+            if let Some(subject) = f.opt("default_subject") {
+                config.default_subject = subject;
+            }
+            if let (Some(user), Some(pass)) = (f.opt("webhook_username"), f.opt("webhook_password"))
+            {
+                config = config.with_webhook_credentials(&user, &pass);
+            }
+            if let Some(endpoint) = f.opt("endpoint") {
+                config.endpoint = endpoint;
+            }
+            Box::new(super::EmailAdapter::new(config))
+        }
+        #[cfg(feature = "aws")]
+        "sns" => {
+            let mut config = super::SnsConfig::new(
+                &f.req("region")?,
+                &f.req("access_key_id")?,
+                &f.req("secret_access_key")?,
+            );
+            config.session_token = f.opt("session_token");
+            config.default_sender_id = f.opt("sender_id");
+            if let Some(endpoint) = f.opt("endpoint") {
+                config.endpoint = endpoint;
+            }
+            Box::new(super::SnsAdapter::new(config))
+        }
+        "smpp" => {
+            let port = f.opt("port").and_then(|p| p.parse().ok()).unwrap_or(2775);
+            let mut config = SmppConfig::new(
+                &f.req("host")?,
+                port,
+                &f.req("system_id")?,
+                &f.opt("password").unwrap_or_default(),
+            )
+            .with_name(&record.name);
+            if let Some(system_type) = f.opt("system_type") {
+                config.system_type = system_type;
+            }
+            if let Some(window) = f.opt("window_size").and_then(|w| w.parse().ok()) {
+                config.window_size = window;
+            }
+            Box::new(SmppAdapter::new(config))
+        }
+        "rbm" => {
+            let mut config = RbmConfig::new(&f.req("agent_id")?, service_account_key(&f)?);
+            config.client_token = f.opt("client_token");
+            if let Some(base_url) = f.opt("base_url") {
+                config.base_url = base_url;
+            }
+            Box::new(RbmAdapter::new(config))
+        }
+        "whatsapp" => {
+            let mut config =
+                WhatsAppConfig::new(&f.req("access_token")?, &f.req("phone_number_id")?);
+            config.business_account_id = f.opt("business_account_id");
+            config.app_secret = f.opt("app_secret");
+            if let Some(api_version) = f.opt("api_version") {
+                config.api_version = api_version;
+            }
+            if let Some(base_url) = f.opt("base_url") {
+                config.base_url = base_url;
+            }
+            Box::new(WhatsAppAdapter::new(config))
+        }
+        "push" => {
+            let mut config = PushConfig::default();
+            if let Some(project_id) = f.opt("fcm_project_id") {
+                config = config.with_fcm(FcmConfig::new(&project_id, service_account_key(&f)?));
+            }
+            if let Some(team_id) = f.opt("apns_team_id") {
+                let mut apns = ApnsConfig::new(
+                    &team_id,
+                    &f.req("apns_key_id")?,
+                    &f.req("apns_private_key")?,
+                    &f.req("apns_topic")?,
+                );
+                if f.opt("apns_sandbox").is_some_and(|v| v == "true") {
+                    apns = apns.sandbox();
+                }
+                config = config.with_apns(apns);
+            }
+            if config.fcm.is_none() && config.apns.is_none() {
+                return Err("push needs fcm_project_id or apns_team_id".to_string());
+            }
+            Box::new(PushAdapter::new(config))
+        }
+        "mock" => {
+            let mut config = MockConfig::new().with_name(&record.name);
+            if let Some(ms) = f.opt("latency_ms").and_then(|v| v.parse().ok()) {
+                config.latency = Duration::from_millis(ms);
+            }
+            if let Some(rate) = f.opt("failure_rate").and_then(|v| v.parse().ok()) {
+                config = config.with_failure_rate(rate);
+            }
+            if let Some(ms) = f.opt("dlr_delay_ms").and_then(|v| v.parse().ok()) {
+                config.dlr = Some(DlrScript::delivered(Duration::from_millis(ms)));
+            }
+            if let Some(secret) = f.opt("webhook_secret") {
+                config.webhook_secret = secret;
+            }
+            Box::new(MockProvider::new(config))
+        }
+        "generic_http" => {
+            let mut config = record.config.clone();
+            config["name"] = Value::String(record.name.clone());
+            let config: GenericHttpConfig =
+                serde_json::from_value(config).map_err(|e| e.to_string())?;
+            Box::new(GenericHttpAdapter::new(config))
+        }
+        other => return Err(format!("unknown provider type {}", other)),
+    };
+    Ok(adapter)
+}
+
+/// A Google service account: the key file's JSON inline, or a string
+/// (e.g. `{"env": ...}`) holding it.
+fn service_account_key(f: &Fields) -> Result<ServiceAccountKey, String> {
+    match &f.0["service_account"] {
+        Value::Object(map) if !map.contains_key("env") => {
+            serde_json::from_value(Value::Object(map.clone())).map_err(|e| e.to_string())
+        }
+        _ => ServiceAccountKey::from_json(&f.req("service_account")?),
+    }
+}
+
+/// What a reload changed.
+#[derive(Debug, Clone, Default)]
+pub struct ReloadReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    /// Rows that couldn't be turned into an adapter, with the reason. The
+    /// previously loaded adapter for such a row, if any, stays in place.
+    pub failed: Vec<(String, String)>,
+}
+
+impl ReloadReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.updated.is_empty()
+            && self.removed.is_empty()
+            && self.failed.is_empty()
+    }
+}
+
+/// Keeps a `ProviderRegistry` in sync with the provider table. Rows are
+/// registered under their `name`; adapters registered in code under other
+/// names are left alone. A changed row replaces its adapter and closes the
+/// old one.
+pub struct ProviderLoader {
+    pool: PgPool,
+    registry: Arc<ProviderRegistry>,
+    table: String,
+    factory: ProviderFactory,
+    loaded: RwLock<HashMap<String, ProviderRecord>>,
+}
+
+impl ProviderLoader {
+    pub fn new(pool: PgPool, registry: Arc<ProviderRegistry>) -> Self {
+        Self {
+            pool,
+            registry,
+            table: "provider_configs".to_string(),
+            factory: Arc::new(build_adapter),
+            loaded: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_table(mut self, table: &str) -> Self {
+        self.table = table.to_string();
+        self
+    }
+
+    /// Replaces `build_adapter`, e.g. to add service-specific provider types.
+    pub fn with_factory(mut self, factory: ProviderFactory) -> Self {
+        self.factory = factory;
+        self
+    }
+
+    pub async fn fetch(&self) -> Result<Vec<ProviderRecord>, DatabaseError> {
+        let rows = sqlx::query(&format!(
+            "SELECT name, provider_type, enabled, weight, config FROM {}",
+            quote_ident(&self.table)?
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(ProviderRecord {
+                    name: row.try_get::<String, _>("name")?.to_lowercase(),
+                    provider_type: row.try_get::<String, _>("provider_type")?.to_lowercase(),
+                    enabled: row.try_get("enabled")?,
+                    weight: row.try_get("weight")?,
+                    config: row.try_get("config")?,
+                })
+            })
+            .collect()
+    }
+
+    pub async fn reload(&self) -> Result<ReloadReport, DatabaseError> {
+        let records = self.fetch().await?;
+        let mut report = ReloadReport::default();
+        let mut loaded = self.loaded.write().await;
+
+        let wanted: HashMap<&str, &ProviderRecord> = records
+            .iter()
+            .filter(|r| r.enabled)
+            .map(|r| (r.name.as_str(), r))
+            .collect();
+        let stale: Vec<String> = loaded
+            .keys()
+            .filter(|name| !wanted.contains_key(name.as_str()))
+            .cloned()
+            .collect();
+        for name in stale {
+            loaded.remove(&name);
+            if let Some(old) = self.registry.unregister(&name).await {
+                old.close().await;
+            }
+            report.removed.push(name);
+        }
+
+        for (name, record) in wanted {
+            let previous = loaded.get(name);
+            if previous == Some(record) {
+                continue;
+            }
+            let is_new = previous.is_none();
+            let adapter = match (self.factory)(record) {
+                Ok(adapter) => adapter,
+                Err(e) => {
+                    error!("Provider {} config invalid: {}", name, e);
+                    report.failed.push((name.to_string(), e));
+                    continue;
+                }
+            };
+            adapter.initialize().await;
+            let old = self.registry.get(name).await.ok();
+            self.registry.register_as(name, adapter).await;
+            if let Some(old) = old.filter(|_| !is_new) {
+                old.close().await;
+            }
+            loaded.insert(name.to_string(), record.clone());
+            if is_new {
+                report.added.push(name.to_string());
+            } else {
+                report.updated.push(name.to_string());
+            }
+        }
+
+        if !report.is_empty() {
+            info!(
+                "Provider configs reloaded: {} added, {} updated, {} removed, {} failed",
+                report.added.len(),
+                report.updated.len(),
+                report.removed.len(),
+                report.failed.len()
+            );
+        }
+        Ok(report)
+    }
+
+    /// Routing weight per loaded provider.
+    pub async fn weights(&self) -> HashMap<String, u32> {
+        self.loaded
+            .read()
+            .await
+            .iter()
+            .map(|(name, record)| (name.clone(), record.weight.max(0) as u32))
+            .collect()
+    }
+
+    /// Reloads on every `provider_configs` notification and at least every
+    /// `interval`, which also covers notifications missed while the listener
+    /// was reconnecting.
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut listener = match PgListener::connect_with(&self.pool).await {
+                Ok(mut listener) => match listener.listen(PROVIDER_CONFIGS_CHANNEL).await {
+                    Ok(()) => Some(listener),
+                    Err(e) => {
+                        warn!(
+                            "LISTEN {} failed, polling only: {}",
+                            PROVIDER_CONFIGS_CHANNEL, e
+                        );
+                        None
+                    }
+                },
+                Err(e) => {
+                    warn!("Provider config listener unavailable, polling only: {}", e);
+                    None
+                }
+            };
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                match listener.as_mut() {
+                    Some(l) => {
+                        tokio::select! {
+                            notification = l.recv() => {
+                                if let Err(e) = notification {
+                                    warn!("Provider config listener error: {}", e);
+                                }
+                            }
+                            _ = ticker.tick() => {}
+                        }
+                    }
+                    None => {
+                        ticker.tick().await;
+                    }
+                }
+                if let Err(e) = self.reload().await {
+                    error!("Provider config reload failed: {}", e);
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,361 @@
+use super::http_client;
+use super::infobip::InfobipConfig;
+use super::twilio::TwilioConfig;
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Client as RedisClient};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+pub const TWILIO_LOOKUP_BASE: &str = "https://lookups.twilio.com";
+
+#[derive(Error, Debug)]
+pub enum LookupError {
+    #[error("Lookup request failed: {0}")]
+    Request(String),
+    #[error("Lookup failed ({code}): {message}")]
+    Provider { code: String, message: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NumberType {
+    Mobile,
+    Landline,
+    Voip,
+    TollFree,
+    Unknown,
+}
+
+impl NumberType {
+    /// Whether the line can take SMS at all. VoIP numbers sometimes can, so
+    /// only landlines and toll-free numbers are ruled out.
+    pub fn receives_sms(&self) -> bool {
+        !matches!(self, NumberType::Landline | NumberType::TollFree)
+    }
+}
+
+/// What a lookup learned about a number. Fields the provider doesn't
+/// report are `None` rather than guessed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LookupResult {
+    pub msisdn: String,
+    /// Current network, e.g. `"310260"`; after porting this is the new one.
+    pub mccmnc: Option<String>,
+    pub ported: Option<bool>,
+    pub reachable: Option<bool>,
+    #[serde(rename = "type")]
+    pub number_type: NumberType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_response: Option<Value>,
+}
+
+impl LookupResult {
+    /// False only when the lookup positively says the number is a landline
+    /// or unreachable.
+    pub fn is_sendable(&self) -> bool {
+        self.number_type.receives_sms() && self.reachable != Some(false)
+    }
+}
+
+#[async_trait]
+pub trait NumberLookupProvider: Send + Sync {
+    fn name(&self) -> String;
+    async fn lookup(&self, msisdn: &str) -> Result<LookupResult, LookupError>;
+}
+
+/// Infobip Number Lookup, a live HLR query: network, porting and whether
+/// the handset is attached. HLRs only know mobile numbers, so anything
+/// else comes back as an unknown subscriber.
+pub struct InfobipLookup {
+    config: InfobipConfig,
+    client: Client,
+}
+
+impl InfobipLookup {
+    pub fn new(config: InfobipConfig) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+}
+
+#[async_trait]
+impl NumberLookupProvider for InfobipLookup {
+    fn name(&self) -> String {
+        "infobip".to_string()
+    }
+
+    async fn lookup(&self, msisdn: &str) -> Result<LookupResult, LookupError> {
+        let response = self
+            .client
+            .post(format!(
+                "{}/number/1/query",
+                self.config.base_url.trim_end_matches('/')
+            ))
+            .header("Authorization", format!("App {}", self.config.api_key))
+            .json(&json!({ "to": [msisdn.trim_start_matches('+')] }))
+            .send()
+            .await
+            .map_err(|e| LookupError::Request(e.to_string()))?;
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            let err = &data["requestError"]["serviceException"];
+            return Err(LookupError::Provider {
+                code: err["messageId"]
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| status.as_u16().to_string()),
+                message: err["text"].as_str().unwrap_or("Unknown error").to_string(),
+            });
+        }
+        let result = data["results"]
+            .get(0)
+            .ok_or_else(|| LookupError::Request("Missing from Infobip response".to_string()))?;
+
+        // Error group OK with status DELIVERED means the HLR answered for an
+        // attached handset; an unknown or absent subscriber is unreachable.
+        let error_group = result["error"]["groupName"].as_str().unwrap_or("OK");
+        let status_group = result["status"]["groupName"].as_str().unwrap_or_default();
+        let reachable = match (error_group, status_group) {
+            ("OK", "DELIVERED") => Some(true),
+            ("HANDSET_ERRORS" | "USER_ERRORS", _) | (_, "UNDELIVERABLE") => Some(false),
+            _ => None,
+        };
+        let unknown_subscriber = result["error"]["name"]
+            .as_str()
+            .is_some_and(|name| name.contains("UNKNOWN_SUBSCRIBER"));
+        let mccmnc = result["mccMnc"]
+            .as_str()
+            .filter(|m| !m.is_empty())
+            .map(str::to_string);
+        Ok(LookupResult {
+            msisdn: msisdn.to_string(),
+            number_type: if mccmnc.is_some() && !unknown_subscriber {
+                NumberType::Mobile
+            } else {
+                NumberType::Unknown
+            },
+            mccmnc,
+            ported: result["ported"].as_bool(),
+            reachable,
+            raw_response: Some(result.clone()),
+        })
+    }
+}
+
+/// Twilio Lookup v2 with line type intelligence and line status. Twilio
+/// doesn't report porting.
+pub struct TwilioLookup {
+    account_sid: String,
+    auth_token: String,
+    base_url: String,
+    client: Client,
+}
+
+impl TwilioLookup {
+    pub fn new(config: &TwilioConfig) -> Self {
+        Self {
+            account_sid: config.account_sid.clone(),
+            auth_token: config.auth_token.clone(),
+            base_url: TWILIO_LOOKUP_BASE.to_string(),
+            client: http_client(),
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.to_string();
+        self
+    }
+}
+
+fn twilio_number_type(line_type: &str) -> NumberType {
+    match line_type {
+        "mobile" => NumberType::Mobile,
+        "landline" => NumberType::Landline,
+        "fixedVoip" | "nonFixedVoip" => NumberType::Voip,
+        "tollFree" => NumberType::TollFree,
+        _ => NumberType::Unknown,
+    }
+}
+
+#[async_trait]
+impl NumberLookupProvider for TwilioLookup {
+    fn name(&self) -> String {
+        "twilio".to_string()
+    }
+
+    async fn lookup(&self, msisdn: &str) -> Result<LookupResult, LookupError> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/v2/PhoneNumbers/{}",
+                self.base_url.trim_end_matches('/'),
+                msisdn
+            ))
+            .query(&[("Fields", "line_type_intelligence,line_status")])
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .send()
+            .await
+            .map_err(|e| LookupError::Request(e.to_string()))?;
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            return Err(LookupError::Provider {
+                code: data["code"]
+                    .as_i64()
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| status.as_u16().to_string()),
+                message: data["message"]
+                    .as_str()
+                    .unwrap_or("Unknown error")
+                    .to_string(),
+            });
+        }
+
+        let line = &data["line_type_intelligence"];
+        let mccmnc = match (
+            line["mobile_country_code"].as_str(),
+            line["mobile_network_code"].as_str(),
+        ) {
+            (Some(mcc), Some(mnc)) => Some(format!("{}{}", mcc, mnc)),
+            _ => None,
+        };
+        // `valid: false` means the number isn't assigned at all.
+        let reachable = match data["line_status"]["status"].as_str() {
+            _ if data["valid"].as_bool() == Some(false) => Some(false),
+            Some("active" | "reachable") => Some(true),
+            Some("unreachable" | "inactive") => Some(false),
+            _ => None,
+        };
+        Ok(LookupResult {
+            msisdn: data["phone_number"].as_str().unwrap_or(msisdn).to_string(),
+            mccmnc,
+            ported: None,
+            reachable,
+            number_type: line["type"]
+                .as_str()
+                .map(twilio_number_type)
+                .unwrap_or(NumberType::Unknown),
+            raw_response: Some(data),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LookupCacheConfig {
+    pub ttl: Duration,
+    /// Unreachable numbers are rechecked sooner; handsets come back online.
+    pub negative_ttl: Duration,
+    pub key_prefix: String,
+}
+
+impl Default for LookupCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(7 * 24 * 3600),
+            negative_ttl: Duration::from_secs(3600),
+            key_prefix: "smsly:lookup".to_string(),
+        }
+    }
+}
+
+impl LookupCacheConfig {
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_negative_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_ttl = ttl;
+        self
+    }
+}
+
+/// Caches another provider's results in Redis, keyed by provider and
+/// number, since lookups are billed per query. If Redis is unreachable
+/// every lookup goes to the provider.
+pub struct CachedLookup {
+    inner: Box<dyn NumberLookupProvider>,
+    client: RedisClient,
+    conn: OnceCell<ConnectionManager>,
+    config: LookupCacheConfig,
+}
+
+impl CachedLookup {
+    pub fn new(
+        inner: Box<dyn NumberLookupProvider>,
+        client: RedisClient,
+        config: LookupCacheConfig,
+    ) -> Self {
+        Self {
+            inner,
+            client,
+            conn: OnceCell::new(),
+            config,
+        }
+    }
+
+    fn key(&self, msisdn: &str) -> String {
+        let digits: String = msisdn.chars().filter(char::is_ascii_digit).collect();
+        format!(
+            "{}:{}:{}",
+            self.config.key_prefix,
+            self.inner.name(),
+            digits
+        )
+    }
+
+    async fn conn(&self) -> redis::RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    async fn cached(&self, key: &str) -> redis::RedisResult<Option<LookupResult>> {
+        let json: Option<String> = self.conn().await?.get(key).await?;
+        Ok(json.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    async fn store(&self, key: &str, result: &LookupResult) -> redis::RedisResult<()> {
+        let ttl = if result.reachable == Some(false) {
+            self.config.negative_ttl
+        } else {
+            self.config.ttl
+        };
+        let json = serde_json::to_string(result).unwrap_or_default();
+        self.conn()
+            .await?
+            .set_ex(key, json, ttl.as_secs().max(1))
+            .await
+    }
+}
+
+#[async_trait]
+impl NumberLookupProvider for CachedLookup {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    async fn lookup(&self, msisdn: &str) -> Result<LookupResult, LookupError> {
+        let key = self.key(msisdn);
+        match self.cached(&key).await {
+            Ok(Some(result)) => return Ok(result),
+            Ok(None) => {}
+            Err(e) => warn!("Redis unavailable for lookup cache: {}", e),
+        }
+        let result = self.inner.lookup(msisdn).await?;
+        if let Err(e) = self.store(&key, &result).await {
+            warn!("Failed to cache lookup for {}: {}", key, e);
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,385 @@
+use super::url_guard::{check_url, guarded_client_with_redirects};
+use super::{failed, PROVIDER_TIMEOUT};
+use crate::adapters::{
+    BaseProviderAdapter, InboundMessage, OutboundSms, RcsContent, SendResult, WebhookEvent,
+};
+use async_trait::async_trait;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::warn;
+
+#[derive(Error, Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum MediaError {
+    #[error("MMS needs at least one media URL")]
+    Empty,
+    #[error("{count} media attachments exceed the limit of {max}")]
+    TooMany { count: usize, max: usize },
+    #[error("Media URL {url} must be public http(s)")]
+    InvalidUrl { url: String },
+    #[error("Media {url} unreachable: {reason}")]
+    Unreachable { url: String, reason: String },
+    #[error("Media {url} returned HTTP {status}")]
+    HttpStatus { url: String, status: u16 },
+    #[error("Media {url} has unsupported type {content_type}")]
+    UnsupportedType { url: String, content_type: String },
+    #[error("Media {url} is {size} bytes, over the {max} byte limit")]
+    TooLarge { url: String, size: u64, max: u64 },
+}
+
+impl MediaError {
+    /// Stable code for `SendResult::error_code`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            MediaError::Empty => "media_missing",
+            MediaError::TooMany { .. } => "media_too_many",
+            MediaError::InvalidUrl { .. } => "media_invalid_url",
+            MediaError::Unreachable { .. } | MediaError::HttpStatus { .. } => "media_unreachable",
+            MediaError::UnsupportedType { .. } => "media_unsupported_type",
+            MediaError::TooLarge { .. } => "media_too_large",
+        }
+    }
+}
+
+const MAX_REDIRECTS: usize = 5;
+
+/// What a provider accepts in one MMS.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaLimits {
+    pub max_items: usize,
+    /// Per attachment.
+    pub max_bytes: u64,
+    /// MIME types; `image/*` style wildcards match a whole family.
+    pub allowed_types: Vec<String>,
+}
+
+impl Default for MediaLimits {
+    /// Conservative carrier limits that most US MMS routes deliver intact.
+    fn default() -> Self {
+        Self {
+            max_items: 10,
+            max_bytes: 600 * 1024,
+            allowed_types: ["image/jpeg", "image/png", "image/gif"]
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+impl MediaLimits {
+    /// Published limits for providers that differ from the default.
+    pub fn for_provider(provider: &str) -> Self {
+        match provider {
+            // Twilio resizes JPEG, PNG and GIF up to 5 MB to fit the carrier.
+            "twilio" => Self {
+                max_bytes: 5 * 1024 * 1024,
+                ..Self::default()
+            },
+            "messagebird" => Self {
+                max_bytes: 1024 * 1024,
+                allowed_types: ["image/*", "video/*", "audio/*", "text/vcard"]
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+                ..Self::default()
+            },
+            // A single image per Viber message.
+            "viber" => Self {
+                max_items: 1,
+                max_bytes: 5 * 1024 * 1024,
+                allowed_types: vec!["image/jpeg".to_string(), "image/png".to_string()],
+            },
+            _ => Self::default(),
+        }
+    }
+
+    pub fn allows_type(&self, content_type: &str) -> bool {
+        // Drop parameters such as `; charset=...`.
+        let content_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        self.allowed_types
+            .iter()
+            .any(|allowed| match allowed.strip_suffix("/*") {
+                Some(family) => content_type.split('/').next() == Some(family),
+                None => *allowed == content_type,
+            })
+    }
+}
+
+/// An attachment as probed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MediaInfo {
+    pub url: String,
+    pub content_type: String,
+    /// `None` if the server reports no length; such media isn't rejected
+    /// on size.
+    pub size: Option<u64>,
+}
+
+/// Shrinks an oversized image to fit `max_bytes`, returning where the
+/// smaller copy is hosted. Plug in an image-processing service or library
+/// here; without one, oversized media is rejected.
+#[async_trait]
+pub trait MediaTranscoder: Send + Sync {
+    async fn transcode(&self, media: &MediaInfo, max_bytes: u64) -> Result<MediaInfo, MediaError>;
+}
+
+/// Probes MMS media before the provider sees it: reachability, MIME type
+/// and size, with a HEAD request (or a one-byte ranged GET where HEAD isn't
+/// allowed). Media URLs come from customers, so they are fetched through
+/// `url_guard`: public addresses only, each redirect checked again.
+pub struct MediaValidator {
+    client: Client,
+    limits: MediaLimits,
+    transcoder: Option<Arc<dyn MediaTranscoder>>,
+}
+
+impl MediaValidator {
+    pub fn new(limits: MediaLimits) -> Self {
+        Self {
+            client: guarded_client_with_redirects(PROVIDER_TIMEOUT, MAX_REDIRECTS),
+            limits,
+            transcoder: None,
+        }
+    }
+
+    pub fn with_transcoder(mut self, transcoder: Arc<dyn MediaTranscoder>) -> Self {
+        self.transcoder = Some(transcoder);
+        self
+    }
+
+    async fn probe(&self, url: &str) -> Result<MediaInfo, MediaError> {
+        if check_url(url).is_err() {
+            return Err(MediaError::InvalidUrl {
+                url: url.to_string(),
+            });
+        }
+        let unreachable = |e: reqwest::Error| MediaError::Unreachable {
+            url: url.to_string(),
+            reason: e.to_string(),
+        };
+        let mut response = self.client.head(url).send().await.map_err(unreachable)?;
+        let mut ranged = false;
+        if matches!(
+            response.status(),
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED | StatusCode::FORBIDDEN
+        ) {
+            // S3 presigned GET URLs and some CDNs refuse HEAD.
+            response = self
+                .client
+                .get(url)
+                .header(RANGE, "bytes=0-0")
+                .send()
+                .await
+                .map_err(unreachable)?;
+            ranged = response.status() == StatusCode::PARTIAL_CONTENT;
+        }
+        if !response.status().is_success() {
+            return Err(MediaError::HttpStatus {
+                url: url.to_string(),
+                status: response.status().as_u16(),
+            });
+        }
+        let headers = response.headers();
+        let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+        let size = if ranged {
+            // `bytes 0-0/12345`
+            header(CONTENT_RANGE)
+                .and_then(|range| range.rsplit('/').next())
+                .and_then(|total| total.parse().ok())
+        } else {
+            header(CONTENT_LENGTH).and_then(|len| len.parse().ok())
+        };
+        Ok(MediaInfo {
+            url: url.to_string(),
+            content_type: header(CONTENT_TYPE).unwrap_or_default().to_string(),
+            size,
+        })
+    }
+
+    async fn check(&self, url: &str) -> Result<MediaInfo, MediaError> {
+        let info = self.probe(url).await?;
+        if !self.limits.allows_type(&info.content_type) {
+            return Err(MediaError::UnsupportedType {
+                url: info.url,
+                content_type: info.content_type,
+            });
+        }
+        match info.size {
+            Some(size) if size > self.limits.max_bytes => match &self.transcoder {
+                Some(transcoder) if info.content_type.starts_with("image/") => {
+                    transcoder.transcode(&info, self.limits.max_bytes).await
+                }
+                _ => Err(MediaError::TooLarge {
+                    url: info.url,
+                    size,
+                    max: self.limits.max_bytes,
+                }),
+            },
+            Some(_) => Ok(info),
+            None => {
+                warn!("Media {} has no length; not checking its size", info.url);
+                Ok(info)
+            }
+        }
+    }
+
+    /// Checks every attachment, stopping at the first problem. On success
+    /// the result lists the URLs to send, with transcoded copies in place
+    /// of oversized originals.
+    pub async fn validate(&self, media_urls: &[String]) -> Result<Vec<MediaInfo>, MediaError> {
+        if media_urls.is_empty() {
+            return Err(MediaError::Empty);
+        }
+        if media_urls.len() > self.limits.max_items {
+            return Err(MediaError::TooMany {
+                count: media_urls.len(),
+                max: self.limits.max_items,
+            });
+        }
+        let mut checked = Vec::with_capacity(media_urls.len());
+        for url in media_urls {
+            checked.push(self.check(url).await?);
+        }
+        Ok(checked)
+    }
+}
+
+/// Validates media before `send_mms` reaches the wrapped adapter, so doomed
+/// sends fail fast without a provider API call. Failures come back with
+/// the `MediaError` code as `error_code` and the error as `raw_response`.
+pub struct MediaCheckedAdapter {
+    inner: Box<dyn BaseProviderAdapter>,
+    validator: MediaValidator,
+}
+
+impl MediaCheckedAdapter {
+    /// Uses `MediaLimits::for_provider` for the wrapped adapter.
+    pub fn new(inner: Box<dyn BaseProviderAdapter>) -> Self {
+        let limits = MediaLimits::for_provider(&inner.name());
+        Self::with_validator(inner, MediaValidator::new(limits))
+    }
+
+    pub fn with_validator(inner: Box<dyn BaseProviderAdapter>, validator: MediaValidator) -> Self {
+        Self { inner, validator }
+    }
+}
+
+#[async_trait]
+impl BaseProviderAdapter for MediaCheckedAdapter {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn supports_sms(&self) -> bool {
+        self.inner.supports_sms()
+    }
+
+    fn supports_mms(&self) -> bool {
+        self.inner.supports_mms()
+    }
+
+    fn supports_whatsapp(&self) -> bool {
+        self.inner.supports_whatsapp()
+    }
+
+    fn supports_rcs(&self) -> bool {
+        self.inner.supports_rcs()
+    }
+
+    fn supports_viber(&self) -> bool {
+        self.inner.supports_viber()
+    }
+
+    fn supports_email(&self) -> bool {
+        self.inner.supports_email()
+    }
+
+    fn supports_push(&self) -> bool {
+        self.inner.supports_push()
+    }
+
+    async fn initialize(&self) {
+        self.inner.initialize().await
+    }
+
+    async fn close(&self) {
+        self.inner.close().await
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        self.inner.send_sms(to, from, body, metadata).await
+    }
+
+    async fn send_sms_batch(&self, messages: Vec<OutboundSms>) -> Vec<SendResult> {
+        self.inner.send_sms_batch(messages).await
+    }
+
+    async fn send_mms(
+        &self,
+        to: &str,
+        from: &str,
+        text: Option<&str>,
+        media_urls: Vec<String>,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        match self.validator.validate(&media_urls).await {
+            Ok(media) => {
+                let media_urls = media.into_iter().map(|m| m.url).collect();
+                self.inner
+                    .send_mms(to, from, text, media_urls, metadata)
+                    .await
+            }
+            Err(e) => {
+                warn!("MMS to {} rejected before sending: {}", to, e);
+                failed(Some(e.code().to_string()), e.to_string(), Some(json!(e)))
+            }
+        }
+    }
+
+    async fn send_rcs(&self, to: &str, agent_id: &str, content: RcsContent) -> SendResult {
+        self.inner.send_rcs(to, agent_id, content).await
+    }
+
+    async fn send_whatsapp(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        self.inner.send_whatsapp(to, from, body, metadata).await
+    }
+
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+        self.inner.validate_webhook(headers, body).await
+    }
+
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        self.inner.parse_webhook(body).await
+    }
+
+    async fn parse_inbound(&self, body: &[u8]) -> Result<InboundMessage, String> {
+        self.inner.parse_inbound(body).await
+    }
+
+    async fn health_check(&self) -> bool {
+        self.inner.health_check().await
+    }
+}
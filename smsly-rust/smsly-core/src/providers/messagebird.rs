@@ -0,0 +1,297 @@
+use super::signature::{now_secs, verify_hs256_jwt};
+use super::{failed, header, http_client, webhook_fields};
+use crate::adapters::{
+    BaseProviderAdapter, InboundMessage, MessageStatus, SendResult, WebhookEvent,
+};
+use crate::dlr;
+use crate::segments::count_segments;
+use async_trait::async_trait;
+use chrono::DateTime;
+use reqwest::Client;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use tracing::{error, warn};
+
+pub const MESSAGEBIRD_API_BASE: &str = "https://rest.messagebird.com";
+
+/// Clock skew tolerated on the webhook JWT's `nbf`/`exp`.
+const JWT_LEEWAY_SECS: i64 = 60;
+
+#[derive(Debug, Clone)]
+pub struct MessageBirdConfig {
+    pub access_key: String,
+    /// Signing key for `MessageBird-Signature-JWT`. Without it every webhook
+    /// is rejected.
+    pub signing_key: Option<String>,
+    pub base_url: String,
+}
+
+impl MessageBirdConfig {
+    pub fn new(access_key: &str) -> Self {
+        Self {
+            access_key: access_key.to_string(),
+            signing_key: None,
+            base_url: MESSAGEBIRD_API_BASE.to_string(),
+        }
+    }
+
+    pub fn with_signing_key(mut self, key: &str) -> Self {
+        self.signing_key = Some(key.to_string());
+        self
+    }
+
+    /// Reads `MESSAGEBIRD_ACCESS_KEY` and `MESSAGEBIRD_SIGNING_KEY`.
+    pub fn from_env() -> Option<Self> {
+        let key = env::var("MESSAGEBIRD_ACCESS_KEY")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+        let mut config = Self::new(&key);
+        config.signing_key = env::var("MESSAGEBIRD_SIGNING_KEY")
+            .ok()
+            .filter(|v| !v.is_empty());
+        Some(config)
+    }
+}
+
+/// MessageBird (Bird) SMS API.
+pub struct MessageBirdAdapter {
+    config: MessageBirdConfig,
+    client: Client,
+}
+
+impl MessageBirdAdapter {
+    pub fn new(config: MessageBirdConfig) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.base_url.trim_end_matches('/'), path)
+    }
+
+    fn auth(&self) -> String {
+        format!("AccessKey {}", self.config.access_key)
+    }
+}
+
+pub fn map_status(status: &str) -> MessageStatus {
+    match status.to_lowercase().as_str() {
+        "sent" => MessageStatus::Sent,
+        "delivered" => MessageStatus::Delivered,
+        "expired" | "delivery_failed" => MessageStatus::Failed,
+        _ => MessageStatus::Pending,
+    }
+}
+
+/// Total price across recipients. MessageBird reports `price.amount` per
+/// recipient once the operator has priced the message, so it may be absent
+/// at submit time and only arrive with a status report.
+pub fn extract_cost(response: &Value) -> Option<f64> {
+    let prices: Vec<f64> = response["recipients"]["items"]
+        .as_array()?
+        .iter()
+        .filter_map(|item| item["price"]["amount"].as_f64())
+        .collect();
+    (!prices.is_empty()).then(|| prices.iter().sum())
+}
+
+#[async_trait]
+impl BaseProviderAdapter for MessageBirdAdapter {
+    fn name(&self) -> String {
+        "messagebird".to_string()
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let mut payload = json!({
+            "recipients": [to.trim_start_matches('+')],
+            "originator": from,
+            "body": body,
+            "datacoding": "auto",
+        });
+        if let Some(url) = metadata
+            .as_ref()
+            .and_then(|m| m.get("webhook_url"))
+            .and_then(Value::as_str)
+        {
+            payload["reportUrl"] = json!(url);
+        }
+        if let Some(reference) = metadata
+            .as_ref()
+            .and_then(|m| m.get("message_id"))
+            .and_then(Value::as_str)
+        {
+            payload["reference"] = json!(reference);
+        }
+
+        let response = self
+            .client
+            .post(self.url("/messages"))
+            .header("Authorization", self.auth())
+            .json(&payload)
+            .send()
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                error!("MessageBird send failed: {}", e);
+                return failed(None, e.to_string(), None);
+            }
+        };
+
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        if status.is_success() {
+            let item = &data["recipients"]["items"][0];
+            SendResult {
+                success: true,
+                provider_message_id: data["id"].as_str().map(str::to_string),
+                status: map_status(item["status"].as_str().unwrap_or("sent")),
+                cost: extract_cost(&data),
+                segments: item["messagePartCount"]
+                    .as_u64()
+                    .map_or_else(|| count_segments(body), |n| n as u32),
+                raw_response: Some(data),
+                ..Default::default()
+            }
+        } else {
+            let err = &data["errors"][0];
+            let code = match &err["code"] {
+                Value::Null => status.as_u16().to_string(),
+                code => code.to_string(),
+            };
+            let message = err["description"]
+                .as_str()
+                .unwrap_or("Unknown error")
+                .to_string();
+            SendResult {
+                // 2 (request not allowed), 9 (missing params), 10 (invalid params).
+                status: if matches!(code.as_str(), "2" | "9" | "10") {
+                    MessageStatus::Rejected
+                } else {
+                    MessageStatus::Failed
+                },
+                ..failed(Some(code), message, Some(data))
+            }
+        }
+    }
+
+    /// Checks `MessageBird-Signature-JWT`: signed with the signing key, within
+    /// `nbf`/`exp`, and bound to the body (`payload_hash`) and, when the
+    /// gateway forwards `X-Original-Url`, the URL (`url_hash`).
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+        let Some(key) = self.config.signing_key.as_deref() else {
+            warn!("MessageBird webhook rejected: no signing key configured");
+            return false;
+        };
+        let Some(claims) = header(headers, "MessageBird-Signature-JWT")
+            .and_then(|token| verify_hs256_jwt(key, token.trim()))
+        else {
+            return false;
+        };
+
+        let now = now_secs();
+        if claims["nbf"]
+            .as_i64()
+            .is_some_and(|nbf| nbf > now + JWT_LEEWAY_SECS)
+            || claims["exp"]
+                .as_i64()
+                .is_some_and(|exp| exp < now - JWT_LEEWAY_SECS)
+        {
+            return false;
+        }
+        let body_ok = match claims["payload_hash"].as_str() {
+            Some(hash) => hash.eq_ignore_ascii_case(&hex::encode(Sha256::digest(body))),
+            None => body.is_empty(),
+        };
+        let url_ok = match (
+            claims["url_hash"].as_str(),
+            header(headers, "X-Original-Url"),
+        ) {
+            (Some(hash), Some(url)) => hash.eq_ignore_ascii_case(&hex::encode(Sha256::digest(url))),
+            _ => true,
+        };
+        body_ok && url_ok
+    }
+
+    /// Parses a status report, sent as form fields (query string for GET
+    /// reports, forwarded as the body) or JSON.
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        let (fields, raw) = webhook_fields(body);
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+
+        let provider_message_id =
+            field("id").ok_or_else(|| "MessageBird status report missing id".to_string())?;
+        let error_code = field("statusErrorCode").filter(|code| code != "0");
+        let error_message = field("statusReason");
+        Ok(WebhookEvent {
+            provider_message_id,
+            status: map_status(&field("status").unwrap_or_default()),
+            timestamp: field("statusDatetime").and_then(|ts| {
+                DateTime::parse_from_rfc3339(&ts)
+                    .ok()
+                    .map(|dt| dt.timestamp() as f64)
+            }),
+            // statusReason is "successfully delivered" on success.
+            reason: error_code.as_ref().and_then(|code| {
+                dlr::normalize("messagebird", Some(code), error_message.as_deref())
+            }),
+            error_code,
+            error_message,
+            raw_payload: Some(raw),
+        })
+    }
+
+    /// The number's inbound forwarding webhook: `originator`, `recipient`
+    /// and `body` (older accounts send `message`).
+    async fn parse_inbound(&self, body: &[u8]) -> Result<InboundMessage, String> {
+        let (fields, raw) = webhook_fields(body);
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        let from = field("originator")
+            .ok_or_else(|| "MessageBird inbound missing originator".to_string())?;
+        Ok(InboundMessage {
+            provider_message_id: field("id").or_else(|| field("mid")),
+            from,
+            to: field("recipient").unwrap_or_default(),
+            body: field("body")
+                .or_else(|| field("message"))
+                .unwrap_or_default(),
+            media: Vec::new(),
+            timestamp: field("createdDatetime").and_then(|ts| {
+                DateTime::parse_from_rfc3339(&ts)
+                    .ok()
+                    .map(|dt| dt.timestamp() as f64)
+            }),
+            raw_payload: Some(raw),
+        })
+    }
+
+    async fn health_check(&self) -> bool {
+        self.client
+            .get(self.url("/balance"))
+            .header("Authorization", self.auth())
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+}
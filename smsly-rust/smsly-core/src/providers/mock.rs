@@ -0,0 +1,365 @@
+use super::signature::{hmac_sha256, verify_hmac_sha256, SignatureEncoding};
+use super::{failed, header, http_client};
+use crate::adapters::{
+    BaseProviderAdapter, InboundMessage, MessageStatus, SendResult, WebhookEvent,
+};
+use crate::dlr;
+use crate::segments::count_segments;
+use async_trait::async_trait;
+use rand::Rng;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Header carrying the hex HMAC-SHA256 of a mock DLR body.
+pub const MOCK_SIGNATURE_HEADER: &str = "X-Mock-Signature";
+
+/// A delivery report the mock sends `after` a successful submission.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DlrScript {
+    pub after: Duration,
+    pub status: MessageStatus,
+    pub error_code: Option<String>,
+}
+
+impl DlrScript {
+    pub fn delivered(after: Duration) -> Self {
+        Self {
+            after,
+            status: MessageStatus::Delivered,
+            error_code: None,
+        }
+    }
+
+    /// A failure report; `error_code` is looked up in the GSM MAP table, so
+    /// `"1"` reads as an unknown subscriber and `"27"` as absent.
+    pub fn failed(after: Duration, error_code: &str) -> Self {
+        Self {
+            after,
+            status: MessageStatus::Failed,
+            error_code: Some(error_code.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    pub name: String,
+    /// Delay before every send returns.
+    pub latency: Duration,
+    /// Share of sends (0.0-1.0) that fail outright.
+    pub failure_rate: f64,
+    /// Report for accepted messages; `None` sends no DLR.
+    pub dlr: Option<DlrScript>,
+    /// Per-recipient reports overriding `dlr`, keyed by the `to` number.
+    pub scripts: HashMap<String, DlrScript>,
+    /// Signs DLRs posted to `metadata["webhook_url"]`.
+    pub webhook_secret: String,
+    pub supports_mms: bool,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            name: "mock".to_string(),
+            latency: Duration::ZERO,
+            failure_rate: 0.0,
+            dlr: Some(DlrScript::delivered(Duration::from_secs(1))),
+            scripts: HashMap::new(),
+            webhook_secret: "mock-secret".to_string(),
+            supports_mms: true,
+        }
+    }
+}
+
+impl MockConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn with_failure_rate(mut self, rate: f64) -> Self {
+        self.failure_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_dlr(mut self, dlr: Option<DlrScript>) -> Self {
+        self.dlr = dlr;
+        self
+    }
+
+    pub fn with_script(mut self, to: &str, script: DlrScript) -> Self {
+        self.scripts.insert(to.to_string(), script);
+        self
+    }
+
+    pub fn with_webhook_secret(mut self, secret: &str) -> Self {
+        self.webhook_secret = secret.to_string();
+        self
+    }
+
+    /// Reads `MOCK_PROVIDER_LATENCY_MS`, `MOCK_PROVIDER_FAILURE_RATE` and
+    /// `MOCK_PROVIDER_DLR_DELAY_MS` (negative disables DLRs), for running a
+    /// service against the mock in staging.
+    pub fn from_env() -> Self {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let mut config = Self::default();
+        if let Some(ms) = var("MOCK_PROVIDER_LATENCY_MS").and_then(|v| v.parse().ok()) {
+            config.latency = Duration::from_millis(ms);
+        }
+        if let Some(rate) = var("MOCK_PROVIDER_FAILURE_RATE").and_then(|v| v.parse().ok()) {
+            config = config.with_failure_rate(rate);
+        }
+        if let Some(ms) = var("MOCK_PROVIDER_DLR_DELAY_MS").and_then(|v| v.parse::<i64>().ok()) {
+            config.dlr = u64::try_from(ms)
+                .ok()
+                .map(|ms| DlrScript::delivered(Duration::from_millis(ms)));
+        }
+        if let Some(secret) = var("MOCK_PROVIDER_WEBHOOK_SECRET") {
+            config.webhook_secret = secret;
+        }
+        config
+    }
+}
+
+/// A message the mock accepted or failed, for assertions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentMessage {
+    pub message_id: String,
+    pub to: String,
+    pub from: String,
+    pub body: String,
+    pub media_urls: Vec<String>,
+    pub success: bool,
+}
+
+/// Sandbox provider: sends nothing, costs nothing, and replays scripted
+/// DLRs. Reports go to `subscribe()` receivers and, when the send carries
+/// `metadata["webhook_url"]`, are POSTed there as signed JSON that
+/// `parse_webhook` understands, so a service's callback route is exercised
+/// too.
+pub struct MockProvider {
+    config: MockConfig,
+    client: Client,
+    sent: Mutex<Vec<SentMessage>>,
+    events: broadcast::Sender<WebhookEvent>,
+}
+
+impl MockProvider {
+    pub fn new(config: MockConfig) -> Self {
+        let (events, _) = broadcast::channel(1024);
+        Self {
+            config,
+            client: http_client(),
+            sent: Mutex::new(Vec::new()),
+            events,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<WebhookEvent> {
+        self.events.subscribe()
+    }
+
+    /// Every message sent so far, oldest first.
+    pub fn sent(&self) -> Vec<SentMessage> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn clear(&self) {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    /// Hex signature for a DLR body, as sent in `X-Mock-Signature`.
+    pub fn sign(&self, body: &[u8]) -> String {
+        hex::encode(hmac_sha256(self.config.webhook_secret.as_bytes(), body))
+    }
+
+    async fn deliver(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        media_urls: Vec<String>,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        if !self.config.latency.is_zero() {
+            tokio::time::sleep(self.config.latency).await;
+        }
+        let message_id = format!("mock-{}", Uuid::new_v4());
+        let success = rand::thread_rng().gen::<f64>() >= self.config.failure_rate;
+        self.sent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(SentMessage {
+                message_id: message_id.clone(),
+                to: to.to_string(),
+                from: from.to_string(),
+                body: body.to_string(),
+                media_urls,
+                success,
+            });
+        if !success {
+            return failed(
+                Some("mock_failure".to_string()),
+                "Simulated provider failure",
+                None,
+            );
+        }
+
+        if let Some(script) = self
+            .config
+            .scripts
+            .get(to)
+            .or(self.config.dlr.as_ref())
+            .cloned()
+        {
+            let webhook_url = metadata
+                .as_ref()
+                .and_then(|m| m.get("webhook_url"))
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            self.schedule(message_id.clone(), script, webhook_url);
+        }
+
+        SendResult {
+            success: true,
+            provider_message_id: Some(message_id.clone()),
+            status: MessageStatus::Sent,
+            raw_response: Some(json!({ "id": message_id, "to": to })),
+            cost: Some(0.0),
+            segments: count_segments(body),
+            ..Default::default()
+        }
+    }
+
+    fn schedule(&self, message_id: String, script: DlrScript, webhook_url: Option<String>) {
+        let payload = json!({
+            "id": message_id,
+            "status": script.status,
+            "error_code": script.error_code,
+        });
+        let body = payload.to_string();
+        let signature = self.sign(body.as_bytes());
+        let events = self.events.clone();
+        let client = self.client.clone();
+        let name = self.config.name.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(script.after).await;
+            if let Ok(event) = parse_event(&payload) {
+                // No receivers is fine; the webhook may be the only consumer.
+                let _ = events.send(event);
+            }
+            if let Some(url) = webhook_url {
+                let result = client
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .header(MOCK_SIGNATURE_HEADER, signature)
+                    .body(body)
+                    .send()
+                    .await;
+                if let Err(e) = result {
+                    warn!("{} DLR callback to {} failed: {}", name, url, e);
+                }
+            }
+        });
+    }
+}
+
+fn parse_event(payload: &Value) -> Result<WebhookEvent, String> {
+    let provider_message_id = payload["id"]
+        .as_str()
+        .ok_or_else(|| "Mock DLR missing id".to_string())?
+        .to_string();
+    let status: MessageStatus =
+        serde_json::from_value(payload["status"].clone()).map_err(|e| e.to_string())?;
+    let error_code = payload["error_code"].as_str().map(str::to_string);
+    Ok(WebhookEvent {
+        provider_message_id,
+        status,
+        timestamp: None,
+        reason: dlr::normalize("mock", error_code.as_deref(), None),
+        error_code,
+        error_message: None,
+        raw_payload: Some(payload.clone()),
+    })
+}
+
+#[async_trait]
+impl BaseProviderAdapter for MockProvider {
+    fn name(&self) -> String {
+        self.config.name.clone()
+    }
+
+    fn supports_mms(&self) -> bool {
+        self.config.supports_mms
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        self.deliver(to, from, body, Vec::new(), metadata).await
+    }
+
+    async fn send_mms(
+        &self,
+        to: &str,
+        from: &str,
+        text: Option<&str>,
+        media_urls: Vec<String>,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        if !self.config.supports_mms {
+            return failed(None, format!("{} does not support MMS", self.name()), None);
+        }
+        self.deliver(to, from, text.unwrap_or_default(), media_urls, metadata)
+            .await
+    }
+
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+        let Some(signature) = header(headers, MOCK_SIGNATURE_HEADER) else {
+            warn!("{} webhook missing {}", self.name(), MOCK_SIGNATURE_HEADER);
+            return false;
+        };
+        verify_hmac_sha256(
+            self.config.webhook_secret.as_bytes(),
+            body,
+            signature,
+            SignatureEncoding::Hex,
+        )
+    }
+
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        let payload: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+        parse_event(&payload)
+    }
+
+    /// Accepts `InboundMessage` JSON, e.g. `{"from", "to", "body"}`, so
+    /// tests can simulate replies with the same signing as DLRs.
+    async fn parse_inbound(&self, body: &[u8]) -> Result<InboundMessage, String> {
+        let payload: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+        let mut message: InboundMessage =
+            serde_json::from_value(payload.clone()).map_err(|e| e.to_string())?;
+        message.raw_payload = Some(payload);
+        Ok(message)
+    }
+}
@@ -0,0 +1,121 @@
+use crate::adapters::{MessageStatus, SendResult};
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[cfg(feature = "aws")]
+pub mod email;
+pub mod generic_http;
+pub mod google_auth;
+pub mod infobip;
+pub mod loader;
+pub mod lookup;
+pub mod media;
+pub mod messagebird;
+pub mod mock;
+pub mod plivo;
+pub mod push;
+pub mod rbm;
+pub mod routing;
+pub mod signature;
+pub mod smpp;
+#[cfg(feature = "aws")]
+pub mod sns;
+pub mod throttle;
+pub mod twilio;
+pub mod url_guard;
+pub mod viber;
+pub mod vonage;
+
+#[cfg(feature = "aws")]
+pub use email::{EmailAdapter, EmailConfig};
+pub use generic_http::{GenericHttpAdapter, GenericHttpConfig};
+pub use infobip::{InfobipAdapter, InfobipConfig};
+pub use loader::{ProviderLoader, ProviderRecord};
+pub use lookup::{
+    CachedLookup, InfobipLookup, LookupCacheConfig, LookupResult, NumberLookupProvider,
+    TwilioLookup,
+};
+pub use media::{MediaCheckedAdapter, MediaLimits, MediaValidator};
+pub use messagebird::{MessageBirdAdapter, MessageBirdConfig};
+pub use mock::{MockConfig, MockProvider};
+pub use plivo::{PlivoAdapter, PlivoConfig};
+pub use push::{PushAdapter, PushConfig};
+pub use rbm::{RbmAdapter, RbmConfig};
+pub use routing::{Channel, RoutingEngine, RoutingError};
+pub use smpp::{SmppAdapter, SmppConfig};
+#[cfg(feature = "aws")]
+pub use sns::{SnsAdapter, SnsConfig};
+pub use throttle::{ThrottleConfig, ThrottledAdapter};
+pub use twilio::{TwilioAdapter, TwilioConfig};
+pub use viber::{ViberAdapter, ViberConfig};
+pub use vonage::{VonageAdapter, VonageConfig};
+
+/// Timeout for provider API calls, matching the Python adapters.
+pub const PROVIDER_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub(crate) fn http_client() -> Client {
+    Client::builder()
+        .timeout(PROVIDER_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Case-insensitive header lookup; gateways forward headers with varying case.
+pub(crate) fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Decodes an `application/x-www-form-urlencoded` body, keeping repeated keys.
+pub(crate) fn parse_form(body: &[u8]) -> Vec<(String, String)> {
+    serde_urlencoded::from_bytes(body).unwrap_or_default()
+}
+
+/// Form fields as a JSON object for `raw_payload`; repeated keys keep the last value.
+pub(crate) fn form_to_json(params: &[(String, String)]) -> Value {
+    Value::Object(
+        params
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+            .collect(),
+    )
+}
+
+/// Webhook fields as strings, from a JSON or form-encoded body.
+pub(crate) fn webhook_fields(body: &[u8]) -> (Vec<(String, String)>, Value) {
+    if let Ok(Value::Object(map)) = serde_json::from_slice::<Value>(body) {
+        let fields = map
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                (key.clone(), value)
+            })
+            .collect();
+        return (fields, Value::Object(map));
+    }
+    let fields = parse_form(body);
+    let raw = form_to_json(&fields);
+    (fields, raw)
+}
+
+pub(crate) fn failed(
+    error_code: Option<String>,
+    error_message: impl Into<String>,
+    raw_response: Option<Value>,
+) -> SendResult {
+    SendResult {
+        success: false,
+        status: MessageStatus::Failed,
+        error_code,
+        error_message: Some(error_message.into()),
+        raw_response,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,293 @@
+use super::signature::{hmac_sha256, secret_matches};
+use super::{failed, form_to_json, header, http_client, parse_form};
+use crate::adapters::{
+    BaseProviderAdapter, InboundMedia, InboundMessage, MessageStatus, SendResult, WebhookEvent,
+};
+use crate::dlr;
+use crate::segments::count_segments;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tracing::error;
+
+pub const PLIVO_API_BASE: &str = "https://api.plivo.com/v1";
+
+#[derive(Debug, Clone)]
+pub struct PlivoConfig {
+    pub auth_id: String,
+    pub auth_token: String,
+    /// Sends from a Powerpack number pool instead of the `from` number.
+    pub powerpack_uuid: Option<String>,
+    /// Public URL Plivo posts callbacks to, used to verify signatures when
+    /// the gateway doesn't forward `X-Original-Url`.
+    pub webhook_url: Option<String>,
+    pub base_url: String,
+}
+
+impl PlivoConfig {
+    pub fn new(auth_id: &str, auth_token: &str) -> Self {
+        Self {
+            auth_id: auth_id.to_string(),
+            auth_token: auth_token.to_string(),
+            powerpack_uuid: None,
+            webhook_url: None,
+            base_url: PLIVO_API_BASE.to_string(),
+        }
+    }
+
+    pub fn with_powerpack(mut self, uuid: &str) -> Self {
+        self.powerpack_uuid = Some(uuid.to_string());
+        self
+    }
+
+    pub fn with_webhook_url(mut self, url: &str) -> Self {
+        self.webhook_url = Some(url.to_string());
+        self
+    }
+}
+
+/// Plivo Messaging API (SMS and MMS).
+pub struct PlivoAdapter {
+    config: PlivoConfig,
+    client: Client,
+}
+
+impl PlivoAdapter {
+    pub fn new(config: PlivoConfig) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+
+    fn account_url(&self) -> String {
+        format!(
+            "{}/Account/{}",
+            self.config.base_url.trim_end_matches('/'),
+            self.config.auth_id
+        )
+    }
+
+    async fn create_message(
+        &self,
+        from: &str,
+        mut payload: Value,
+        metadata: &Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        match &self.config.powerpack_uuid {
+            Some(uuid) => payload["powerpack_uuid"] = json!(uuid),
+            None => payload["src"] = json!(from),
+        }
+        if let Some(url) = metadata
+            .as_ref()
+            .and_then(|m| m.get("webhook_url"))
+            .and_then(Value::as_str)
+        {
+            payload["url"] = json!(url);
+            payload["method"] = json!("POST");
+        }
+        let response = self
+            .client
+            .post(format!("{}/Message/", self.account_url()))
+            .basic_auth(&self.config.auth_id, Some(&self.config.auth_token))
+            .json(&payload)
+            .send()
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Plivo send failed: {}", e);
+                return failed(None, e.to_string(), None);
+            }
+        };
+
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        if status.is_success() {
+            SendResult {
+                success: true,
+                // One UUID per message; Plivo concatenates long texts itself.
+                provider_message_id: data["message_uuid"]
+                    .get(0)
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                status: MessageStatus::Pending,
+                segments: payload["text"].as_str().map_or(1, count_segments),
+                raw_response: Some(data),
+                ..Default::default()
+            }
+        } else {
+            let message = match &data["error"] {
+                Value::String(message) => message.clone(),
+                Value::Null => "Unknown error".to_string(),
+                other => other.to_string(),
+            };
+            failed(Some(status.as_u16().to_string()), message, Some(data))
+        }
+    }
+
+    /// `X-Plivo-Signature-V3`: base64 HMAC-SHA256 of the signed URL (see
+    /// `signed_url`), a `.`, and the `X-Plivo-Signature-V3-Nonce` value.
+    pub fn signature(&self, url: &str, params: &[(String, String)], nonce: &str) -> String {
+        STANDARD.encode(hmac_sha256(
+            self.config.auth_token.as_bytes(),
+            format!("{}.{}", signed_url(url, params), nonce).as_bytes(),
+        ))
+    }
+}
+
+/// The URL as Plivo signs a POST callback: scheme, host and path, the query
+/// string re-sorted by name, then `.` and every POST parameter, sorted by
+/// name, as `name + value`.
+fn signed_url(url: &str, params: &[(String, String)]) -> String {
+    let (base, query) = url.split_once('?').unwrap_or((url, ""));
+    let mut signed = base.to_string();
+    let mut query = parse_form(query.as_bytes());
+    if !query.is_empty() {
+        query.sort();
+        let query: Vec<String> = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        signed.push('?');
+        signed.push_str(&query.join("&"));
+    }
+    let mut sorted: Vec<&(String, String)> = params.iter().collect();
+    sorted.sort();
+    signed.push('.');
+    for (key, value) in sorted {
+        signed.push_str(key);
+        signed.push_str(value);
+    }
+    signed
+}
+
+pub fn map_status(status: &str) -> MessageStatus {
+    match status.to_lowercase().as_str() {
+        "sent" => MessageStatus::Sent,
+        "delivered" | "read" => MessageStatus::Delivered,
+        "undelivered" | "failed" => MessageStatus::Failed,
+        "rejected" => MessageStatus::Rejected,
+        _ => MessageStatus::Pending,
+    }
+}
+
+#[async_trait]
+impl BaseProviderAdapter for PlivoAdapter {
+    fn name(&self) -> String {
+        "plivo".to_string()
+    }
+
+    fn supports_mms(&self) -> bool {
+        true
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let payload = json!({ "dst": to, "text": body, "type": "sms" });
+        self.create_message(from, payload, &metadata).await
+    }
+
+    async fn send_mms(
+        &self,
+        to: &str,
+        from: &str,
+        text: Option<&str>,
+        media_urls: Vec<String>,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let mut payload = json!({ "dst": to, "type": "mms", "media_urls": media_urls });
+        if let Some(text) = text {
+            payload["text"] = json!(text);
+        }
+        self.create_message(from, payload, &metadata).await
+    }
+
+    /// Requires the public callback URL, from `X-Original-Url` (set by the
+    /// gateway) or `PlivoConfig::webhook_url`. The signature header may list
+    /// several comma-separated signatures while an auth token is rotated;
+    /// any match is accepted.
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+        let (Some(signatures), Some(nonce)) = (
+            header(headers, "X-Plivo-Signature-V3"),
+            header(headers, "X-Plivo-Signature-V3-Nonce"),
+        ) else {
+            return false;
+        };
+        let Some(url) = header(headers, "X-Original-Url").or(self.config.webhook_url.as_deref())
+        else {
+            return false;
+        };
+        let expected = self.signature(url, &parse_form(body), nonce);
+        signatures
+            .split(',')
+            .any(|signature| secret_matches(signature.trim().as_bytes(), expected.as_bytes()))
+    }
+
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        let params = parse_form(body);
+        let field = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        let provider_message_id =
+            field("MessageUUID").ok_or_else(|| "Plivo callback missing MessageUUID".to_string())?;
+        let status = field("Status").unwrap_or_default();
+        // Successful reports carry `ErrorCode=000`.
+        let error_code = field("ErrorCode").filter(|code| !code.trim_start_matches('0').is_empty());
+        Ok(WebhookEvent {
+            provider_message_id,
+            status: map_status(&status),
+            timestamp: None,
+            reason: dlr::normalize("plivo", error_code.as_deref(), None),
+            error_code,
+            error_message: None,
+            raw_payload: Some(form_to_json(&params)),
+        })
+    }
+
+    /// Incoming-message webhook; MMS media arrive as `Media0`, `Media1`, ...
+    async fn parse_inbound(&self, body: &[u8]) -> Result<InboundMessage, String> {
+        let params = parse_form(body);
+        let field = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        let from = field("From").ok_or_else(|| "Plivo message missing From".to_string())?;
+        let media = (0..)
+            .map_while(|i| field(&format!("Media{}", i)))
+            .map(|url| InboundMedia {
+                url,
+                content_type: None,
+            })
+            .collect();
+        Ok(InboundMessage {
+            provider_message_id: field("MessageUUID"),
+            from,
+            to: field("To").unwrap_or_default(),
+            body: field("Text").unwrap_or_default(),
+            media,
+            timestamp: None,
+            raw_payload: Some(form_to_json(&params)),
+        })
+    }
+
+    async fn health_check(&self) -> bool {
+        self.client
+            .get(format!("{}/", self.account_url()))
+            .basic_auth(&self.config.auth_id, Some(&self.config.auth_token))
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+}
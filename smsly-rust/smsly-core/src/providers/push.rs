@@ -0,0 +1,434 @@
+use super::google_auth::{GoogleTokenSource, ServiceAccountKey};
+use super::signature::now_secs;
+use super::{failed, http_client};
+use crate::adapters::{BaseProviderAdapter, MessageStatus, SendResult};
+use async_trait::async_trait;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use reqwest::Client;
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::env;
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+pub const FCM_API_BASE: &str = "https://fcm.googleapis.com";
+pub const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+pub const APNS_PRODUCTION: &str = "https://api.push.apple.com";
+pub const APNS_SANDBOX: &str = "https://api.sandbox.push.apple.com";
+
+/// APNs rejects provider tokens older than an hour and throttles ones
+/// refreshed more often than every 20 minutes.
+const APNS_TOKEN_LIFETIME_SECS: i64 = 50 * 60;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PushNotification {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub body: String,
+    /// Custom key/value payload delivered to the app.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub data: HashMap<String, String>,
+}
+
+/// Where a device token came from. `to` strings carry it as a prefix
+/// (`fcm:<token>`, `apns:<token>`); a bare token goes to FCM if it's
+/// configured, else APNs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushPlatform {
+    Fcm,
+    Apns,
+}
+
+#[derive(Debug, Clone)]
+pub struct FcmConfig {
+    pub project_id: String,
+    pub service_account: ServiceAccountKey,
+    pub base_url: String,
+}
+
+impl FcmConfig {
+    pub fn new(project_id: &str, service_account: ServiceAccountKey) -> Self {
+        Self {
+            project_id: project_id.to_string(),
+            service_account,
+            base_url: FCM_API_BASE.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApnsConfig {
+    pub team_id: String,
+    pub key_id: String,
+    /// Contents of the `.p8` signing key (PKCS#8 PEM).
+    pub private_key: String,
+    /// App bundle id, sent as `apns-topic`.
+    pub topic: String,
+    pub base_url: String,
+}
+
+impl ApnsConfig {
+    pub fn new(team_id: &str, key_id: &str, private_key: &str, topic: &str) -> Self {
+        Self {
+            team_id: team_id.to_string(),
+            key_id: key_id.to_string(),
+            private_key: private_key.to_string(),
+            topic: topic.to_string(),
+            base_url: APNS_PRODUCTION.to_string(),
+        }
+    }
+
+    pub fn sandbox(mut self) -> Self {
+        self.base_url = APNS_SANDBOX.to_string();
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PushConfig {
+    pub fcm: Option<FcmConfig>,
+    pub apns: Option<ApnsConfig>,
+}
+
+impl PushConfig {
+    pub fn with_fcm(mut self, fcm: FcmConfig) -> Self {
+        self.fcm = Some(fcm);
+        self
+    }
+
+    pub fn with_apns(mut self, apns: ApnsConfig) -> Self {
+        self.apns = Some(apns);
+        self
+    }
+
+    /// FCM from `FCM_PROJECT_ID` and `FCM_SERVICE_ACCOUNT_FILE`; APNs from
+    /// `APNS_TEAM_ID`, `APNS_KEY_ID`, `APNS_KEY_FILE`, `APNS_TOPIC` and
+    /// `APNS_SANDBOX`. `None` unless at least one is complete.
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let mut config = Self::default();
+        if let (Some(project), Some(path)) =
+            (var("FCM_PROJECT_ID"), var("FCM_SERVICE_ACCOUNT_FILE"))
+        {
+            match ServiceAccountKey::from_file(&path) {
+                Ok(key) => config.fcm = Some(FcmConfig::new(&project, key)),
+                Err(e) => error!("FCM service account unusable: {}", e),
+            }
+        }
+        if let (Some(team), Some(key_id), Some(path), Some(topic)) = (
+            var("APNS_TEAM_ID"),
+            var("APNS_KEY_ID"),
+            var("APNS_KEY_FILE"),
+            var("APNS_TOPIC"),
+        ) {
+            match std::fs::read_to_string(&path) {
+                Ok(key) => {
+                    let mut apns = ApnsConfig::new(&team, &key_id, &key, &topic);
+                    if var("APNS_SANDBOX").is_some_and(|v| v == "true" || v == "1") {
+                        apns = apns.sandbox();
+                    }
+                    config.apns = Some(apns);
+                }
+                Err(e) => error!("APNs key {} unreadable: {}", path, e),
+            }
+        }
+        (config.fcm.is_some() || config.apns.is_some()).then_some(config)
+    }
+}
+
+/// Push notifications through FCM HTTP v1 and APNs (token-based auth), as
+/// the first step of a push → SMS fallback. `send_sms` takes a device token
+/// as `to` (see `PushPlatform`) and the notification body; metadata
+/// `title` and `data` (an object of strings) fill in the rest.
+///
+/// Neither service reports delivery, so there are no webhooks. A token
+/// the service no longer recognises fails with error code `unregistered`,
+/// and the caller should drop it.
+pub struct PushAdapter {
+    config: PushConfig,
+    client: Client,
+    fcm_auth: Option<GoogleTokenSource>,
+    /// APNs provider token and when it was issued (Unix seconds).
+    apns_token: Mutex<Option<(String, i64)>>,
+}
+
+impl PushAdapter {
+    pub fn new(config: PushConfig) -> Self {
+        let fcm_auth = config
+            .fcm
+            .as_ref()
+            .map(|fcm| GoogleTokenSource::new(fcm.service_account.clone(), FCM_SCOPE));
+        Self {
+            config,
+            // APNs only speaks HTTP/2, negotiated through ALPN.
+            client: http_client(),
+            fcm_auth,
+            apns_token: Mutex::new(None),
+        }
+    }
+
+    fn route<'a>(&self, to: &'a str) -> (PushPlatform, &'a str) {
+        if let Some(token) = to.strip_prefix("fcm:") {
+            (PushPlatform::Fcm, token)
+        } else if let Some(token) = to.strip_prefix("apns:") {
+            (PushPlatform::Apns, token)
+        } else if self.config.fcm.is_some() {
+            (PushPlatform::Fcm, to)
+        } else {
+            (PushPlatform::Apns, to)
+        }
+    }
+
+    pub async fn send_push(&self, to: &str, notification: &PushNotification) -> SendResult {
+        match self.route(to) {
+            (PushPlatform::Fcm, token) => self.send_fcm(token, notification).await,
+            (PushPlatform::Apns, token) => self.send_apns(token, notification).await,
+        }
+    }
+
+    async fn send_fcm(&self, token: &str, notification: &PushNotification) -> SendResult {
+        let (Some(config), Some(auth)) = (&self.config.fcm, &self.fcm_auth) else {
+            return failed(None, "FCM is not configured", None);
+        };
+        let access_token = match auth.access_token(&self.client).await {
+            Ok(token) => token,
+            Err(e) => {
+                error!("FCM authentication failed: {}", e);
+                return failed(Some("auth_failed".to_string()), e, None);
+            }
+        };
+        let mut message = json!({
+            "token": token,
+            "notification": { "body": notification.body },
+        });
+        if let Some(title) = &notification.title {
+            message["notification"]["title"] = json!(title);
+        }
+        if !notification.data.is_empty() {
+            message["data"] = json!(notification.data);
+        }
+        let response = self
+            .client
+            .post(format!(
+                "{}/v1/projects/{}/messages:send",
+                config.base_url.trim_end_matches('/'),
+                config.project_id
+            ))
+            .bearer_auth(access_token)
+            .json(&json!({ "message": message }))
+            .send()
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                error!("FCM send failed: {}", e);
+                return failed(None, e.to_string(), None);
+            }
+        };
+
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        if status.is_success() {
+            return SendResult {
+                success: true,
+                provider_message_id: data["name"].as_str().map(str::to_string),
+                status: MessageStatus::Sent,
+                raw_response: Some(data),
+                segments: 1,
+                ..Default::default()
+            };
+        }
+        let error = &data["error"];
+        let code = error["details"]
+            .as_array()
+            .and_then(|details| details.iter().find_map(|d| d["errorCode"].as_str()))
+            .or(error["status"].as_str())
+            .map(|code| match code {
+                "UNREGISTERED" => "unregistered".to_string(),
+                other => other.to_string(),
+            })
+            .unwrap_or_else(|| status.as_u16().to_string());
+        let message = error["message"]
+            .as_str()
+            .unwrap_or("Unknown error")
+            .to_string();
+        failed(Some(code), message, Some(data))
+    }
+
+    /// ES256 provider token, reused until it nears APNs' one-hour limit.
+    async fn apns_token(&self, config: &ApnsConfig) -> Result<String, String> {
+        let mut cached = self.apns_token.lock().await;
+        let now = now_secs();
+        if let Some((token, issued_at)) = cached.as_ref() {
+            if now - issued_at < APNS_TOKEN_LIFETIME_SECS {
+                return Ok(token.clone());
+            }
+        }
+        let der: String = config
+            .private_key
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        let der = STANDARD
+            .decode(der.trim())
+            .map_err(|e| format!("invalid APNs key: {}", e))?;
+        let rng = SystemRandom::new();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &der, &rng)
+            .map_err(|e| format!("invalid APNs key: {}", e))?;
+        let header =
+            URL_SAFE_NO_PAD.encode(json!({ "alg": "ES256", "kid": config.key_id }).to_string());
+        let claims =
+            URL_SAFE_NO_PAD.encode(json!({ "iss": config.team_id, "iat": now }).to_string());
+        let signed = format!("{}.{}", header, claims);
+        let signature = key
+            .sign(&rng, signed.as_bytes())
+            .map_err(|_| "APNs token signing failed".to_string())?;
+        let token = format!("{}.{}", signed, URL_SAFE_NO_PAD.encode(signature.as_ref()));
+        *cached = Some((token.clone(), now));
+        Ok(token)
+    }
+
+    async fn send_apns(&self, token: &str, notification: &PushNotification) -> SendResult {
+        let Some(config) = &self.config.apns else {
+            return failed(None, "APNs is not configured", None);
+        };
+        let jwt = match self.apns_token(config).await {
+            Ok(jwt) => jwt,
+            Err(e) => {
+                error!("APNs authentication failed: {}", e);
+                return failed(Some("auth_failed".to_string()), e, None);
+            }
+        };
+        let mut alert = json!({ "body": notification.body });
+        if let Some(title) = &notification.title {
+            alert["title"] = json!(title);
+        }
+        // Custom data sits beside `aps` at the top level.
+        let mut payload: Map<String, Value> = notification
+            .data
+            .iter()
+            .map(|(key, value)| (key.clone(), json!(value)))
+            .collect();
+        payload.insert("aps".to_string(), json!({ "alert": alert }));
+
+        let response = self
+            .client
+            .post(format!(
+                "{}/3/device/{}",
+                config.base_url.trim_end_matches('/'),
+                token
+            ))
+            .header("authorization", format!("bearer {}", jwt))
+            .header("apns-topic", &config.topic)
+            .header("apns-push-type", "alert")
+            .header("apns-priority", "10")
+            .json(&Value::Object(payload))
+            .send()
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                error!("APNs send failed: {}", e);
+                return failed(None, e.to_string(), None);
+            }
+        };
+
+        let status = response.status();
+        let apns_id = response
+            .headers()
+            .get("apns-id")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        if status.is_success() {
+            return SendResult {
+                success: true,
+                provider_message_id: apns_id,
+                status: MessageStatus::Sent,
+                segments: 1,
+                ..Default::default()
+            };
+        }
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        let reason = data["reason"]
+            .as_str()
+            .unwrap_or("Unknown error")
+            .to_string();
+        let code = match reason.as_str() {
+            "Unregistered" | "BadDeviceToken" => "unregistered".to_string(),
+            other => other.to_string(),
+        };
+        if status.as_u16() == 403 {
+            // ExpiredProviderToken and friends; mint a new one next time.
+            *self.apns_token.lock().await = None;
+        }
+        failed(Some(code), reason, Some(data))
+    }
+}
+
+#[async_trait]
+impl BaseProviderAdapter for PushAdapter {
+    fn name(&self) -> String {
+        "push".to_string()
+    }
+
+    fn supports_sms(&self) -> bool {
+        false
+    }
+
+    fn supports_push(&self) -> bool {
+        true
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        _from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let metadata = metadata.unwrap_or_default();
+        let notification = PushNotification {
+            title: metadata
+                .get("title")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            body: body.to_string(),
+            data: metadata
+                .get("data")
+                .and_then(Value::as_object)
+                .map(|data| {
+                    data.iter()
+                        .map(|(key, value)| {
+                            let value = match value {
+                                Value::String(s) => s.clone(),
+                                other => other.to_string(),
+                            };
+                            (key.clone(), value)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+        self.send_push(to, &notification).await
+    }
+
+    async fn health_check(&self) -> bool {
+        if let Some(auth) = &self.fcm_auth {
+            if let Err(e) = auth.access_token(&self.client).await {
+                warn!("FCM health check failed: {}", e);
+                return false;
+            }
+        }
+        if let Some(config) = &self.config.apns {
+            if let Err(e) = self.apns_token(config).await {
+                warn!("APNs health check failed: {}", e);
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,372 @@
+use super::google_auth::GoogleTokenSource;
+pub use super::google_auth::ServiceAccountKey;
+use super::signature::secret_matches;
+use super::{failed, header, http_client};
+use crate::adapters::{
+    BaseProviderAdapter, InboundMedia, InboundMessage, MessageStatus, RcsCard, RcsContent,
+    RcsSuggestion, SendResult, WebhookEvent,
+};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::DateTime;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde_json::{json, Value};
+use sha2::Sha512;
+use std::collections::HashMap;
+use std::env;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+pub const RBM_API_BASE: &str = "https://rcsbusinessmessaging.googleapis.com";
+pub const RBM_SCOPE: &str = "https://www.googleapis.com/auth/rcsbusinessmessaging";
+
+#[derive(Debug, Clone)]
+pub struct RbmConfig {
+    /// Agent used by `send_sms`, which has no agent parameter.
+    pub agent_id: String,
+    pub service_account: ServiceAccountKey,
+    /// Webhook client token from the agent's partner settings; without it
+    /// every webhook is rejected.
+    pub client_token: Option<String>,
+    pub base_url: String,
+}
+
+impl RbmConfig {
+    pub fn new(agent_id: &str, service_account: ServiceAccountKey) -> Self {
+        Self {
+            agent_id: agent_id.to_string(),
+            service_account,
+            client_token: None,
+            base_url: RBM_API_BASE.to_string(),
+        }
+    }
+
+    pub fn with_client_token(mut self, token: &str) -> Self {
+        self.client_token = Some(token.to_string());
+        self
+    }
+
+    /// Reads `RBM_AGENT_ID`, `RBM_SERVICE_ACCOUNT_FILE` (or the key itself in
+    /// `RBM_SERVICE_ACCOUNT_JSON`) and `RBM_CLIENT_TOKEN`.
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let agent_id = var("RBM_AGENT_ID")?;
+        let key = match (
+            var("RBM_SERVICE_ACCOUNT_JSON"),
+            var("RBM_SERVICE_ACCOUNT_FILE"),
+        ) {
+            (Some(json), _) => ServiceAccountKey::from_json(&json),
+            (None, Some(path)) => ServiceAccountKey::from_file(&path),
+            (None, None) => return None,
+        };
+        let key = key
+            .map_err(|e| error!("RBM service account unusable: {}", e))
+            .ok()?;
+        let mut config = Self::new(&agent_id, key);
+        config.client_token = var("RBM_CLIENT_TOKEN");
+        Some(config)
+    }
+}
+
+/// Google RCS Business Messaging. Plain `send_sms` goes out as an RCS text
+/// from the configured agent; numbers that aren't RCS-capable fail with
+/// error code `404`, so callers can fall back to SMS.
+pub struct RbmAdapter {
+    config: RbmConfig,
+    client: Client,
+    auth: GoogleTokenSource,
+}
+
+impl RbmAdapter {
+    pub fn new(config: RbmConfig) -> Self {
+        Self {
+            auth: GoogleTokenSource::new(config.service_account.clone(), RBM_SCOPE),
+            config,
+            client: http_client(),
+        }
+    }
+
+    async fn access_token(&self) -> Result<String, String> {
+        self.auth.access_token(&self.client).await
+    }
+
+    async fn send_content(&self, to: &str, agent_id: &str, content: &RcsContent) -> SendResult {
+        let token = match self.access_token().await {
+            Ok(token) => token,
+            Err(e) => {
+                error!("RBM authentication failed: {}", e);
+                return failed(Some("auth_failed".to_string()), e, None);
+            }
+        };
+        let message_id = Uuid::new_v4().to_string();
+        let response = self
+            .client
+            .post(format!(
+                "{}/v1/phones/{}/agentMessages",
+                self.config.base_url.trim_end_matches('/'),
+                to
+            ))
+            .query(&[("messageId", message_id.as_str()), ("agentId", agent_id)])
+            .bearer_auth(token)
+            .json(&json!({ "contentMessage": content_json(content) }))
+            .send()
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                error!("RBM send failed: {}", e);
+                return failed(None, e.to_string(), None);
+            }
+        };
+
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        if status.is_success() {
+            SendResult {
+                success: true,
+                provider_message_id: Some(message_id),
+                status: MessageStatus::Sent,
+                raw_response: Some(data),
+                segments: 1,
+                ..Default::default()
+            }
+        } else {
+            let message = data["error"]["message"]
+                .as_str()
+                .unwrap_or("Unknown error")
+                .to_string();
+            failed(Some(status.as_u16().to_string()), message, Some(data))
+        }
+    }
+}
+
+fn suggestion_json(suggestion: &RcsSuggestion) -> Value {
+    match suggestion {
+        RcsSuggestion::Reply {
+            text,
+            postback_data,
+        } => json!({ "reply": { "text": text, "postbackData": postback_data } }),
+        RcsSuggestion::OpenUrl {
+            text,
+            postback_data,
+            url,
+        } => json!({ "action": {
+            "text": text,
+            "postbackData": postback_data,
+            "openUrlAction": { "url": url },
+        } }),
+        RcsSuggestion::Dial {
+            text,
+            postback_data,
+            phone_number,
+        } => json!({ "action": {
+            "text": text,
+            "postbackData": postback_data,
+            "dialAction": { "phoneNumber": phone_number },
+        } }),
+    }
+}
+
+fn content_info(file_url: &str, thumbnail_url: Option<&str>) -> Value {
+    let mut info = json!({ "fileUrl": file_url });
+    if let Some(thumbnail_url) = thumbnail_url {
+        info["thumbnailUrl"] = json!(thumbnail_url);
+    }
+    info
+}
+
+fn suggestions_json(suggestions: &[RcsSuggestion]) -> Value {
+    Value::Array(suggestions.iter().map(suggestion_json).collect())
+}
+
+fn card_json(card: &RcsCard) -> Value {
+    let mut value = json!({});
+    if let Some(title) = &card.title {
+        value["title"] = json!(title);
+    }
+    if let Some(description) = &card.description {
+        value["description"] = json!(description);
+    }
+    if let Some(media) = &card.media {
+        value["media"] = json!({
+            "height": format!("{:?}", media.height).to_uppercase(),
+            "contentInfo": content_info(&media.file_url, media.thumbnail_url.as_deref()),
+        });
+    }
+    if !card.suggestions.is_empty() {
+        value["suggestions"] = suggestions_json(&card.suggestions);
+    }
+    value
+}
+
+/// RBM `contentMessage` for `content`.
+pub fn content_json(content: &RcsContent) -> Value {
+    let (mut value, suggestions) = match content {
+        RcsContent::Text { text, suggestions } => (json!({ "text": text }), suggestions),
+        RcsContent::File {
+            file_url,
+            thumbnail_url,
+            suggestions,
+        } => (
+            json!({ "contentInfo": content_info(file_url, thumbnail_url.as_deref()) }),
+            suggestions,
+        ),
+        RcsContent::RichCard { card, suggestions } => (
+            json!({ "richCard": { "standaloneCard": {
+                "cardOrientation": "VERTICAL",
+                "cardContent": card_json(card),
+            } } }),
+            suggestions,
+        ),
+        RcsContent::Carousel { cards, suggestions } => (
+            json!({ "richCard": { "carouselCard": {
+                "cardWidth": "MEDIUM",
+                "cardContents": cards.iter().map(card_json).collect::<Vec<_>>(),
+            } } }),
+            suggestions,
+        ),
+    };
+    if !suggestions.is_empty() {
+        value["suggestions"] = suggestions_json(suggestions);
+    }
+    value
+}
+
+/// RBM webhooks are Pub/Sub push envelopes with the event base64-encoded in
+/// `message.data`.
+fn decode_envelope(body: &[u8]) -> Result<Value, String> {
+    let envelope: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let data = envelope["message"]["data"]
+        .as_str()
+        .ok_or_else(|| "RBM webhook missing message.data".to_string())?;
+    let decoded = STANDARD.decode(data).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&decoded).map_err(|e| e.to_string())
+}
+
+pub fn map_event_type(event_type: &str) -> MessageStatus {
+    match event_type {
+        "DELIVERED" | "READ" => MessageStatus::Delivered,
+        _ => MessageStatus::Pending,
+    }
+}
+
+#[async_trait]
+impl BaseProviderAdapter for RbmAdapter {
+    fn name(&self) -> String {
+        "rbm".to_string()
+    }
+
+    fn supports_sms(&self) -> bool {
+        false
+    }
+
+    fn supports_rcs(&self) -> bool {
+        true
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        _from: &str,
+        body: &str,
+        _metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        self.send_content(to, &self.config.agent_id, &RcsContent::text(body))
+            .await
+    }
+
+    async fn send_rcs(&self, to: &str, agent_id: &str, content: RcsContent) -> SendResult {
+        self.send_content(to, agent_id, &content).await
+    }
+
+    /// `X-Goog-Signature`: base64 HMAC-SHA512 of the body keyed with the
+    /// webhook client token.
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+        let Some(token) = self.config.client_token.as_deref() else {
+            warn!("RBM webhook rejected: no client token configured");
+            return false;
+        };
+        let Some(provided) = header(headers, "X-Goog-Signature")
+            .and_then(|signature| STANDARD.decode(signature.trim()).ok())
+        else {
+            return false;
+        };
+        let mut mac =
+            Hmac::<Sha512>::new_from_slice(token.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(body);
+        secret_matches(&provided, &mac.finalize().into_bytes())
+    }
+
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        let event = decode_envelope(body)?;
+        let event_type = event["eventType"]
+            .as_str()
+            .ok_or_else(|| "RBM webhook is not a delivery event".to_string())?;
+        let provider_message_id = event["messageId"]
+            .as_str()
+            .ok_or_else(|| "RBM event missing messageId".to_string())?
+            .to_string();
+        Ok(WebhookEvent {
+            provider_message_id,
+            status: map_event_type(event_type),
+            timestamp: event["sendTime"].as_str().and_then(|ts| {
+                DateTime::parse_from_rfc3339(ts)
+                    .ok()
+                    .map(|dt| dt.timestamp() as f64)
+            }),
+            error_code: None,
+            error_message: None,
+            reason: None,
+            raw_payload: Some(event),
+        })
+    }
+
+    /// A user message: text, a tapped suggestion (its postback data is in
+    /// `raw_payload`) or a file.
+    async fn parse_inbound(&self, body: &[u8]) -> Result<InboundMessage, String> {
+        let event = decode_envelope(body)?;
+        let from = event["senderPhoneNumber"]
+            .as_str()
+            .ok_or_else(|| "RBM webhook is not a user message".to_string())?
+            .to_string();
+        let body = event["text"]
+            .as_str()
+            .or(event["suggestionResponse"]["text"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        let file = &event["userFile"]["payload"];
+        let media = file["fileUri"]
+            .as_str()
+            .map(|url| InboundMedia {
+                url: url.to_string(),
+                content_type: file["mimeType"].as_str().map(str::to_string),
+            })
+            .into_iter()
+            .collect();
+        Ok(InboundMessage {
+            provider_message_id: event["messageId"].as_str().map(str::to_string),
+            from,
+            to: event["agentId"]
+                .as_str()
+                .unwrap_or(&self.config.agent_id)
+                .to_string(),
+            body,
+            media,
+            timestamp: event["sendTime"].as_str().and_then(|ts| {
+                DateTime::parse_from_rfc3339(ts)
+                    .ok()
+                    .map(|dt| dt.timestamp() as f64)
+            }),
+            raw_payload: Some(event),
+        })
+    }
+
+    async fn health_check(&self) -> bool {
+        self.access_token()
+            .await
+            .map_err(|e| warn!("RBM health check failed: {}", e))
+            .is_ok()
+    }
+}
@@ -0,0 +1,442 @@
+use super::lookup::{NumberLookupProvider, NumberType};
+pub use crate::adapters::Channel;
+use crate::adapters::ProviderRegistry;
+use crate::metrics::GLOBAL_METRICS;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+#[derive(Error, Debug)]
+pub enum RoutingError {
+    #[error("No route to {to} for {channel}")]
+    NoRoute { to: String, channel: Channel },
+    #[error("Invalid rate card: {0}")]
+    InvalidRateCard(String),
+    #[error("{to} can't receive {channel}: {reason}")]
+    Undeliverable {
+        to: String,
+        channel: Channel,
+        reason: String,
+    },
+}
+
+/// Price for one provider, destination prefix and channel. Prefixes are
+/// E.164 digits without `+` (`44`, `447`, ...); the longest match wins.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rate {
+    pub provider: String,
+    pub prefix: String,
+    #[serde(default = "default_channel")]
+    pub channel: Channel,
+    pub price: f64,
+}
+
+fn default_channel() -> Channel {
+    Channel::Sms
+}
+
+/// Pins an account's traffic to a provider for a prefix, e.g. a customer
+/// with a contracted route. `channel: None` covers every channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteOverride {
+    pub prefix: String,
+    #[serde(default)]
+    pub channel: Option<Channel>,
+    pub provider: String,
+}
+
+/// How a `WeightedRoute` spreads traffic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Distribution {
+    /// Each message independently, with probability proportional to weight.
+    #[default]
+    Random,
+    /// Smooth weighted round-robin: exact proportions over every cycle of
+    /// the total weight, interleaved rather than in runs.
+    RoundRobin,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteWeight {
+    pub provider: String,
+    pub weight: u32,
+}
+
+/// Splits a prefix's traffic across equivalent providers, e.g. 70/30
+/// while migrating between them. It takes precedence over least-cost
+/// routing for that prefix; providers that are unusable are left out of
+/// the split, and if none are usable least-cost routing applies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeightedRoute {
+    pub prefix: String,
+    #[serde(default = "default_channel")]
+    pub channel: Channel,
+    pub providers: Vec<RouteWeight>,
+    #[serde(default)]
+    pub distribution: Distribution,
+}
+
+pub struct RoutingMetricNames;
+
+impl RoutingMetricNames {
+    /// Counter labelled by channel, prefix, provider and strategy
+    /// (`weighted`, `least_cost`), for comparing providers in a split.
+    pub const ROUTE_SELECTIONS_TOTAL: &'static str = "smsly_route_selections";
+}
+
+fn digits(number: &str) -> String {
+    number.chars().filter(char::is_ascii_digit).collect()
+}
+
+/// Least-cost routing over the providers in a registry. For each candidate
+/// provider the longest matching rate-card prefix sets its price; providers
+/// that are unhealthy, circuit-broken, unregistered or lack the channel are
+/// skipped, and the cheapest remaining one wins (ties go to the
+/// alphabetically first name).
+/// Prefixes covered by a `WeightedRoute` are split by weight instead.
+pub struct RoutingEngine {
+    registry: Arc<ProviderRegistry>,
+    rates: RwLock<Vec<Rate>>,
+    overrides: RwLock<HashMap<String, Vec<RouteOverride>>>,
+    health: RwLock<HashMap<String, bool>>,
+    lookup: Option<Arc<dyn NumberLookupProvider>>,
+    weighted: RwLock<Vec<WeightedRoute>>,
+    /// Smooth round-robin current weights, per channel and prefix.
+    round_robin: Mutex<HashMap<(Channel, String), Vec<i64>>>,
+}
+
+impl RoutingEngine {
+    pub fn new(registry: Arc<ProviderRegistry>) -> Self {
+        Self {
+            registry,
+            rates: RwLock::new(Vec::new()),
+            overrides: RwLock::new(HashMap::new()),
+            health: RwLock::new(HashMap::new()),
+            lookup: None,
+            weighted: RwLock::new(Vec::new()),
+            round_robin: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Looks up SMS and MMS destinations before routing them, refusing
+    /// landlines and numbers the network reports unreachable. Wrap the
+    /// provider in `CachedLookup`; lookup failures don't block sending.
+    pub fn with_lookup(mut self, lookup: Arc<dyn NumberLookupProvider>) -> Self {
+        self.lookup = Some(lookup);
+        self
+    }
+
+    async fn check_destination(&self, to: &str, channel: Channel) -> Result<(), RoutingError> {
+        let Some(lookup) = &self.lookup else {
+            return Ok(());
+        };
+        if !matches!(channel, Channel::Sms | Channel::Mms) {
+            return Ok(());
+        }
+        match lookup.lookup(to).await {
+            Ok(result) if !result.is_sendable() => Err(RoutingError::Undeliverable {
+                to: to.to_string(),
+                channel,
+                reason: match result.number_type {
+                    NumberType::Landline => "landline number",
+                    NumberType::TollFree => "toll-free number",
+                    _ => "unreachable",
+                }
+                .to_string(),
+            }),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                warn!("Number lookup for {} failed: {}", to, e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Replaces the rate cards wholesale.
+    pub async fn load_rates(&self, rates: Vec<Rate>) {
+        let rates = rates
+            .into_iter()
+            .map(|rate| Rate {
+                provider: rate.provider.to_lowercase(),
+                prefix: digits(&rate.prefix),
+                ..rate
+            })
+            .collect();
+        *self.rates.write().await = rates;
+    }
+
+    /// Loads rate cards from a JSON array of `Rate`.
+    pub async fn load_rates_json(&self, json: &str) -> Result<(), RoutingError> {
+        let rates: Vec<Rate> =
+            serde_json::from_str(json).map_err(|e| RoutingError::InvalidRateCard(e.to_string()))?;
+        if let Some(rate) = rates.iter().find(|r| !r.price.is_finite() || r.price < 0.0) {
+            return Err(RoutingError::InvalidRateCard(format!(
+                "{} {} has invalid price {}",
+                rate.provider, rate.prefix, rate.price
+            )));
+        }
+        self.load_rates(rates).await;
+        Ok(())
+    }
+
+    /// Replaces the weighted routes wholesale, resetting round-robin state.
+    pub async fn load_weighted_routes(&self, routes: Vec<WeightedRoute>) {
+        let routes = routes
+            .into_iter()
+            .map(|route| WeightedRoute {
+                prefix: digits(&route.prefix),
+                providers: route
+                    .providers
+                    .into_iter()
+                    .filter(|p| p.weight > 0)
+                    .map(|p| RouteWeight {
+                        provider: p.provider.to_lowercase(),
+                        ..p
+                    })
+                    .collect(),
+                ..route
+            })
+            .collect();
+        *self.weighted.write().await = routes;
+        self.round_robin
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// Loads weighted routes from a JSON array of `WeightedRoute`.
+    pub async fn load_weighted_routes_json(&self, json: &str) -> Result<(), RoutingError> {
+        let routes: Vec<WeightedRoute> =
+            serde_json::from_str(json).map_err(|e| RoutingError::InvalidRateCard(e.to_string()))?;
+        self.load_weighted_routes(routes).await;
+        Ok(())
+    }
+
+    pub async fn set_overrides(&self, account_id: &str, overrides: Vec<RouteOverride>) {
+        let overrides = overrides
+            .into_iter()
+            .map(|o| RouteOverride {
+                prefix: digits(&o.prefix),
+                provider: o.provider.to_lowercase(),
+                ..o
+            })
+            .collect();
+        self.overrides
+            .write()
+            .await
+            .insert(account_id.to_string(), overrides);
+    }
+
+    pub async fn clear_overrides(&self, account_id: &str) {
+        self.overrides.write().await.remove(account_id);
+    }
+
+    /// Marks a provider up or down; providers never reported are assumed up.
+    pub async fn set_health(&self, provider: &str, healthy: bool) {
+        self.health
+            .write()
+            .await
+            .insert(provider.to_lowercase(), healthy);
+    }
+
+    /// Runs every registered adapter's `health_check`.
+    pub async fn refresh_health(&self) {
+        for (name, adapter) in self.registry.adapters().await {
+            let healthy = adapter.health_check().await;
+            if !healthy {
+                warn!("Provider {} failed health check; routing around it", name);
+            }
+            self.set_health(&name, healthy).await;
+        }
+    }
+
+    async fn usable(&self, provider: &str, channel: Channel) -> bool {
+        if self.registry.circuit_open(provider) {
+            return false;
+        }
+        if !self
+            .health
+            .read()
+            .await
+            .get(provider)
+            .copied()
+            .unwrap_or(true)
+        {
+            return false;
+        }
+        match self.registry.get(provider).await {
+            Ok(adapter) => channel.supported_by(adapter.as_ref().as_ref()),
+            Err(_) => false,
+        }
+    }
+
+    fn pick_weighted(&self, route: &WeightedRoute, usable: &[&RouteWeight]) -> String {
+        match route.distribution {
+            Distribution::Random => {
+                let total: u32 = usable.iter().map(|p| p.weight).sum();
+                let mut ticket = rand::thread_rng().gen_range(0..total);
+                for candidate in usable {
+                    if ticket < candidate.weight {
+                        return candidate.provider.clone();
+                    }
+                    ticket -= candidate.weight;
+                }
+                usable[usable.len() - 1].provider.clone()
+            }
+            Distribution::RoundRobin => {
+                let mut state = self.round_robin.lock().unwrap_or_else(|e| e.into_inner());
+                // Indexed by position in `route.providers` so state survives
+                // a provider dropping out of `usable` for a while.
+                let current = state
+                    .entry((route.channel, route.prefix.clone()))
+                    .or_insert_with(|| vec![0; route.providers.len()]);
+                let mut total = 0i64;
+                let mut best: Option<usize> = None;
+                for (i, candidate) in route.providers.iter().enumerate() {
+                    if !usable.iter().any(|u| u.provider == candidate.provider) {
+                        continue;
+                    }
+                    current[i] += candidate.weight as i64;
+                    total += candidate.weight as i64;
+                    if best.is_none_or(|b| current[i] > current[b]) {
+                        best = Some(i);
+                    }
+                }
+                let best = best.unwrap_or(0);
+                current[best] -= total;
+                route.providers[best].provider.clone()
+            }
+        }
+    }
+
+    /// The weighted route for the longest matching prefix, if it has a
+    /// usable provider.
+    async fn route_weighted(&self, number: &str, channel: Channel) -> Option<(String, String)> {
+        let route = self
+            .weighted
+            .read()
+            .await
+            .iter()
+            .filter(|r| r.channel == channel && number.starts_with(&r.prefix))
+            .max_by_key(|r| r.prefix.len())
+            .cloned()?;
+        let mut usable = Vec::new();
+        for candidate in &route.providers {
+            if self.usable(&candidate.provider, channel).await {
+                usable.push(candidate);
+            }
+        }
+        if usable.is_empty() {
+            warn!(
+                "No usable provider in weighted route {} for {}; using least-cost",
+                route.prefix, channel
+            );
+            return None;
+        }
+        Some((self.pick_weighted(&route, &usable), route.prefix.clone()))
+    }
+
+    fn record_selection(channel: Channel, prefix: &str, provider: &str, strategy: &str) {
+        let labels = HashMap::from([
+            ("channel".to_string(), channel.to_string()),
+            ("prefix".to_string(), prefix.to_string()),
+            ("provider".to_string(), provider.to_string()),
+            ("strategy".to_string(), strategy.to_string()),
+        ]);
+        GLOBAL_METRICS.increment(RoutingMetricNames::ROUTE_SELECTIONS_TOTAL, 1, Some(labels));
+    }
+
+    /// Provider for `to` on `channel`: a weighted split if one covers the
+    /// number, otherwise the cheapest usable provider.
+    pub async fn route(&self, to: &str, channel: Channel) -> Result<String, RoutingError> {
+        self.check_destination(to, channel).await?;
+        let number = digits(to);
+        if let Some((provider, prefix)) = self.route_weighted(&number, channel).await {
+            debug!(
+                "Routing {} via {} (weighted, prefix {})",
+                channel, provider, prefix
+            );
+            Self::record_selection(channel, &prefix, &provider, "weighted");
+            return Ok(provider);
+        }
+        // Best (longest prefix) rate per provider.
+        let mut best: HashMap<String, &Rate> = HashMap::new();
+        let rates = self.rates.read().await;
+        for rate in rates
+            .iter()
+            .filter(|r| r.channel == channel && number.starts_with(&r.prefix))
+        {
+            best.entry(rate.provider.clone())
+                .and_modify(|current| {
+                    if rate.prefix.len() > current.prefix.len() {
+                        *current = rate;
+                    }
+                })
+                .or_insert(rate);
+        }
+
+        // Clone out so the lock isn't held across the registry lookups below.
+        let mut candidates: Vec<Rate> = best.into_values().cloned().collect();
+        drop(rates);
+        candidates.sort_by(|a, b| {
+            a.price
+                .total_cmp(&b.price)
+                .then_with(|| a.provider.cmp(&b.provider))
+        });
+        for rate in candidates {
+            if self.usable(&rate.provider, channel).await {
+                debug!(
+                    "Routing {} via {} at {} (prefix {})",
+                    channel, rate.provider, rate.price, rate.prefix
+                );
+                Self::record_selection(channel, &rate.prefix, &rate.provider, "least_cost");
+                return Ok(rate.provider.clone());
+            }
+        }
+        Err(RoutingError::NoRoute {
+            to: to.to_string(),
+            channel,
+        })
+    }
+
+    /// Like `route`, but an account override for the longest matching prefix
+    /// wins while its provider is usable.
+    pub async fn route_for_account(
+        &self,
+        account_id: &str,
+        to: &str,
+        channel: Channel,
+    ) -> Result<String, RoutingError> {
+        let number = digits(to);
+        let pinned = self
+            .overrides
+            .read()
+            .await
+            .get(account_id)
+            .and_then(|overrides| {
+                overrides
+                    .iter()
+                    .filter(|o| {
+                        o.channel.is_none_or(|c| c == channel) && number.starts_with(&o.prefix)
+                    })
+                    .max_by_key(|o| o.prefix.len())
+                    .map(|o| o.provider.clone())
+            });
+        if let Some(provider) = pinned {
+            if self.usable(&provider, channel).await {
+                self.check_destination(to, channel).await?;
+                return Ok(provider);
+            }
+            warn!(
+                "Override provider {} unusable for account {}; falling back to least-cost",
+                provider, account_id
+            );
+        }
+        self.route(to, channel).await
+    }
+}
@@ -0,0 +1,130 @@
+//! Building blocks for `validate_webhook`. Every comparison against a
+//! provided signature or credential is constant-time.
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha1::Sha1;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::header;
+
+/// How a provider transmits a signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureEncoding {
+    Hex,
+    Base64,
+}
+
+impl SignatureEncoding {
+    fn decode(&self, signature: &str) -> Option<Vec<u8>> {
+        let signature = signature.trim();
+        match self {
+            SignatureEncoding::Hex => hex::decode(signature).ok(),
+            SignatureEncoding::Base64 => STANDARD.decode(signature).ok(),
+        }
+    }
+}
+
+pub fn hmac_sha1(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub fn verify_hmac_sha1(
+    key: &[u8],
+    data: &[u8],
+    signature: &str,
+    encoding: SignatureEncoding,
+) -> bool {
+    encoding
+        .decode(signature)
+        .is_some_and(|provided| secret_matches(&provided, &hmac_sha1(key, data)))
+}
+
+pub fn verify_hmac_sha256(
+    key: &[u8],
+    data: &[u8],
+    signature: &str,
+    encoding: SignatureEncoding,
+) -> bool {
+    encoding
+        .decode(signature)
+        .is_some_and(|provided| secret_matches(&provided, &hmac_sha256(key, data)))
+}
+
+pub(crate) fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Whether a signed Unix timestamp (seconds) is within `tolerance` of now,
+/// in either direction. Guards against replayed callbacks.
+pub fn within_tolerance(timestamp: i64, tolerance: Duration) -> bool {
+    (now_secs() - timestamp).unsigned_abs() <= tolerance.as_secs()
+}
+
+/// Checks `Authorization: Basic ...` for providers that authenticate
+/// callbacks with credentials embedded in the callback URL.
+pub fn verify_basic_auth(
+    headers: &HashMap<String, String>,
+    username: &str,
+    password: &str,
+) -> bool {
+    let expected = format!(
+        "Basic {}",
+        STANDARD.encode(format!("{}:{}", username, password))
+    );
+    header(headers, "Authorization")
+        .is_some_and(|provided| secret_matches(provided.trim().as_bytes(), expected.as_bytes()))
+}
+
+/// Verifies an HS256 JWT against `secret` and returns its claims. Callers
+/// check provider-specific claims such as `payload_hash`.
+pub fn verify_hs256_jwt(secret: &str, token: &str) -> Option<Value> {
+    let mut parts = token.splitn(3, '.');
+    let (header_b64, claims_b64, signature_b64) = (parts.next()?, parts.next()?, parts.next()?);
+    let signature = URL_SAFE_NO_PAD.decode(signature_b64).ok()?;
+    let signed = format!("{}.{}", header_b64, claims_b64);
+    if !secret_matches(
+        &signature,
+        &hmac_sha256(secret.as_bytes(), signed.as_bytes()),
+    ) {
+        return None;
+    }
+    let claims = URL_SAFE_NO_PAD.decode(claims_b64).ok()?;
+    serde_json::from_slice(&claims).ok()
+}
+
+/// Whether a JWT's `iat` is within `tolerance` of now and its `exp`, if
+/// any, hasn't passed, so a captured token can't be replayed later.
+/// Tokens without `iat` are rejected.
+pub fn jwt_is_fresh(claims: &Value, tolerance: Duration) -> bool {
+    let now = now_secs();
+    claims["iat"]
+        .as_i64()
+        .is_some_and(|iat| within_tolerance(iat, tolerance))
+        && claims["exp"].as_i64().is_none_or(|exp| exp >= now)
+}
+
+/// Constant-time comparison for shared secrets (webhooks, internal probes).
+pub fn secret_matches(provided: &[u8], expected: &[u8]) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .iter()
+            .zip(expected)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
@@ -0,0 +1,626 @@
+//! SMPP 3.4 client for carrier routes without an HTTP API.
+
+use super::failed;
+use crate::adapters::{
+    expired_result, remaining_validity, BaseProviderAdapter, InboundMessage, MessageStatus,
+    OutboundSms, SendResult, WebhookEvent,
+};
+use crate::dlr::{self, DlrReason};
+use crate::segments;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc, Mutex};
+use tracing::{error, warn};
+
+pub mod pdu;
+mod session;
+
+use pdu::{Address, DeliverSm, SubmitSm};
+pub use session::SmppSession;
+
+#[derive(Error, Debug)]
+pub enum SmppError {
+    #[error("SMPP I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("SMPP bind rejected with status {0:#010x}")]
+    BindFailed(u32),
+    #[error("Unexpected SMPP PDU {0:#010x}")]
+    UnexpectedPdu(u32),
+    #[error("SMPP response timed out")]
+    Timeout,
+    #[error("SMPP session closed")]
+    Closed,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmppConfig {
+    /// Adapter name, so several SMSC binds can be registered side by side.
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub system_id: String,
+    pub password: String,
+    pub system_type: String,
+    /// Maximum unacknowledged requests in flight.
+    pub window_size: usize,
+    pub enquire_link_interval: Duration,
+    pub response_timeout: Duration,
+}
+
+impl SmppConfig {
+    pub fn new(host: &str, port: u16, system_id: &str, password: &str) -> Self {
+        Self {
+            name: "smpp".to_string(),
+            host: host.to_string(),
+            port,
+            system_id: system_id.to_string(),
+            password: password.to_string(),
+            system_type: String::new(),
+            window_size: 10,
+            enquire_link_interval: Duration::from_secs(30),
+            response_timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_window_size(mut self, window_size: usize) -> Self {
+        self.window_size = window_size;
+        self
+    }
+
+    pub fn with_enquire_link_interval(mut self, interval: Duration) -> Self {
+        self.enquire_link_interval = interval;
+        self
+    }
+
+    /// Reads `SMPP_HOST`, `SMPP_PORT` (default 2775), `SMPP_SYSTEM_ID`,
+    /// `SMPP_PASSWORD` and optionally `SMPP_SYSTEM_TYPE` / `SMPP_WINDOW_SIZE`.
+    pub fn from_env() -> Option<Self> {
+        let non_empty = |key: &str| env::var(key).ok().filter(|v| !v.is_empty());
+        let port = non_empty("SMPP_PORT")
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(2775);
+        let mut config = Self::new(
+            &non_empty("SMPP_HOST")?,
+            port,
+            &non_empty("SMPP_SYSTEM_ID")?,
+            &non_empty("SMPP_PASSWORD").unwrap_or_default(),
+        );
+        if let Some(system_type) = non_empty("SMPP_SYSTEM_TYPE") {
+            config.system_type = system_type;
+        }
+        if let Some(window) = non_empty("SMPP_WINDOW_SIZE").and_then(|w| w.parse().ok()) {
+            config.window_size = window;
+        }
+        Some(config)
+    }
+}
+
+/// Something the SMSC pushed to us over `deliver_sm`.
+#[derive(Debug, Clone)]
+pub enum SmppEvent {
+    DeliveryReceipt(WebhookEvent),
+    /// Mobile-originated message; SMPP binds carry no message id or media.
+    Inbound(InboundMessage),
+}
+
+const DATA_CODING_DEFAULT: u8 = 0x00;
+const DATA_CODING_UCS2: u8 = 0x08;
+
+/// Splits a message into `short_message` payloads: GSM-7 (unpacked, one
+/// septet per octet) when the text allows it, otherwise UCS-2. Concatenated
+/// parts carry an 8-bit-reference UDH.
+pub fn segment(text: &str, reference: u8) -> (u8, Vec<Vec<u8>>) {
+    let (data_coding, units, single, multi) = match segments::encode_gsm7(text) {
+        Some(units) => (DATA_CODING_DEFAULT, units, 160, 153),
+        None => (DATA_CODING_UCS2, segments::encode_ucs2(text), 140, 134),
+    };
+
+    let total_len: usize = units.iter().map(Vec::len).sum();
+    if total_len <= single {
+        return (data_coding, vec![units.concat()]);
+    }
+
+    let mut parts: Vec<Vec<u8>> = vec![Vec::new()];
+    for unit in units {
+        let current = parts.last_mut().expect("parts is never empty");
+        if current.len() + unit.len() > multi {
+            parts.push(unit);
+        } else {
+            current.extend(unit);
+        }
+    }
+    let total = parts.len().min(255) as u8;
+    let parts = parts
+        .into_iter()
+        .take(255)
+        .enumerate()
+        .map(|(idx, payload)| {
+            let mut part = vec![0x05, 0x00, 0x03, reference, total, idx as u8 + 1];
+            part.extend(payload);
+            part
+        })
+        .collect();
+    (data_coding, parts)
+}
+
+fn decode_text(data_coding: u8, payload: &[u8]) -> String {
+    if data_coding == DATA_CODING_UCS2 {
+        let units: Vec<u16> = payload
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        segments::decode_gsm7(payload)
+    }
+}
+
+/// Maps a DLR `stat:` value.
+pub fn map_status(stat: &str) -> MessageStatus {
+    match stat.to_uppercase().as_str() {
+        "DELIVRD" => MessageStatus::Delivered,
+        "ACCEPTD" | "ENROUTE" => MessageStatus::Sent,
+        "EXPIRED" | "DELETED" | "UNDELIV" => MessageStatus::Failed,
+        "REJECTD" => MessageStatus::Rejected,
+        _ => MessageStatus::Pending,
+    }
+}
+
+/// `message_state` TLV values (SMPP 3.4 section 5.2.28).
+fn map_message_state(state: u8) -> MessageStatus {
+    match state {
+        1 | 6 => MessageStatus::Sent,
+        2 => MessageStatus::Delivered,
+        3..=5 => MessageStatus::Failed,
+        8 => MessageStatus::Rejected,
+        _ => MessageStatus::Pending,
+    }
+}
+
+/// Parses the de-facto receipt text
+/// `id:IIII sub:001 dlvrd:001 submit date:... done date:... stat:DELIVRD err:000 text:...`.
+pub fn parse_receipt_text(text: &str) -> Result<WebhookEvent, String> {
+    // `text:` is free-form and always last; keys before it are single words
+    // except the two dates.
+    let head = text.split(" text:").next().unwrap_or(text);
+    let mut fields = HashMap::new();
+    for key in [
+        "id",
+        "sub",
+        "dlvrd",
+        "submit date",
+        "done date",
+        "stat",
+        "err",
+    ] {
+        let needle = format!("{}:", key);
+        let Some(start) = head
+            .match_indices(&needle)
+            .find(|(idx, _)| *idx == 0 || head.as_bytes()[idx - 1] == b' ')
+            .map(|(idx, _)| idx + needle.len())
+        else {
+            continue;
+        };
+        let value = head[start..].split(' ').next().unwrap_or_default();
+        fields.insert(key, value.to_string());
+    }
+
+    let provider_message_id = fields
+        .get("id")
+        .filter(|id| !id.is_empty())
+        .cloned()
+        .ok_or_else(|| "SMPP receipt missing id".to_string())?;
+    let stat = fields.get("stat").cloned().unwrap_or_default();
+    let status = map_status(&stat);
+    let error_code = fields
+        .get("err")
+        .filter(|err| !err.trim_start_matches('0').is_empty())
+        .cloned();
+    let error_message =
+        (matches!(status, MessageStatus::Failed | MessageStatus::Rejected)).then(|| stat.clone());
+    Ok(WebhookEvent {
+        provider_message_id,
+        reason: dlr::normalize("smpp", error_code.as_deref(), error_message.as_deref()),
+        error_message,
+        status,
+        timestamp: fields
+            .get("done date")
+            .and_then(|date| parse_receipt_date(date)),
+        error_code,
+        raw_payload: Some(json!({ "receipt": text })),
+    })
+}
+
+/// Receipt dates are `YYMMDDhhmm` or `YYMMDDhhmmss`, in SMSC local time
+/// which in practice is almost always UTC.
+fn parse_receipt_date(date: &str) -> Option<f64> {
+    let padded = match date.len() {
+        10 => format!("{}00", date),
+        12 => date.to_string(),
+        _ => return None,
+    };
+    chrono::NaiveDateTime::parse_from_str(&padded, "%y%m%d%H%M%S")
+        .ok()
+        .map(|dt| dt.and_utc().timestamp() as f64)
+}
+
+fn event_for(deliver: DeliverSm) -> SmppEvent {
+    let text = decode_text(deliver.data_coding, &deliver.short_message);
+    if !deliver.is_delivery_receipt() {
+        return SmppEvent::Inbound(InboundMessage {
+            provider_message_id: None,
+            raw_payload: Some(json!({
+                "source_addr": deliver.source,
+                "destination_addr": deliver.destination,
+                "data_coding": deliver.data_coding,
+            })),
+            from: deliver.source,
+            to: deliver.destination,
+            body: text,
+            media: Vec::new(),
+            timestamp: None,
+        });
+    }
+    let mut event = parse_receipt_text(&text).unwrap_or_else(|_| WebhookEvent {
+        provider_message_id: String::new(),
+        status: MessageStatus::Pending,
+        timestamp: None,
+        error_code: None,
+        error_message: None,
+        reason: None,
+        raw_payload: Some(json!({ "receipt": text })),
+    });
+    // The TLVs are authoritative when present.
+    if let Some(id) = deliver.tlv(pdu::TLV_RECEIPTED_MESSAGE_ID) {
+        event.provider_message_id = String::from_utf8_lossy(id)
+            .trim_end_matches('\0')
+            .to_string();
+    }
+    if let Some(&[state]) = deliver.tlv(pdu::TLV_MESSAGE_STATE) {
+        event.status = map_message_state(state);
+        if state == 3 {
+            event.reason.get_or_insert(DlrReason::Expired);
+        }
+    }
+    SmppEvent::DeliveryReceipt(event)
+}
+
+/// `submit_sm_resp` statuses worth distinguishing; everything else fails.
+fn status_for(command_status: u32) -> MessageStatus {
+    match command_status {
+        pdu::ESME_RINVSRCADR | pdu::ESME_RINVDSTADR => MessageStatus::Rejected,
+        _ => MessageStatus::Failed,
+    }
+}
+
+/// Submits every part of one message; the result carries the first part's
+/// id, with all of them in `raw_response`. The message's remaining validity
+/// goes out as `validity_period`, and one already expired isn't submitted.
+async fn submit(
+    session: &SmppSession,
+    to: &str,
+    from: &str,
+    body: &str,
+    metadata: Option<&HashMap<String, Value>>,
+    reference: u8,
+) -> SendResult {
+    let validity_period = remaining_validity(metadata);
+    if validity_period == Some(Duration::ZERO) {
+        return expired_result();
+    }
+    let (data_coding, parts) = segment(body, reference);
+    let esm_class = if parts.len() > 1 { pdu::ESM_UDHI } else { 0 };
+    let mut message_ids = Vec::with_capacity(parts.len());
+    for short_message in parts.iter() {
+        let submit = SubmitSm {
+            source: Address::parse(from),
+            destination: Address::parse(to),
+            esm_class,
+            registered_delivery: 1,
+            data_coding,
+            validity_period,
+            short_message: short_message.clone(),
+        };
+        let resp = match session.request(pdu::SUBMIT_SM, submit.encode()).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                error!("SMPP submit_sm failed: {}", e);
+                return failed(
+                    None,
+                    e.to_string(),
+                    Some(json!({ "message_ids": message_ids })),
+                );
+            }
+        };
+        if resp.command_status != pdu::ESME_ROK {
+            return SendResult {
+                status: status_for(resp.command_status),
+                ..failed(
+                    Some(format!("{:#010x}", resp.command_status)),
+                    format!(
+                        "submit_sm rejected for part {} of {}",
+                        message_ids.len() + 1,
+                        parts.len()
+                    ),
+                    Some(json!({ "message_ids": message_ids })),
+                )
+            };
+        }
+        message_ids.push(pdu::submit_sm_resp_message_id(&resp.body).unwrap_or_default());
+    }
+
+    SendResult {
+        success: true,
+        provider_message_id: message_ids.first().cloned(),
+        status: MessageStatus::Sent,
+        segments: parts.len() as u32,
+        raw_response: Some(json!({ "message_ids": message_ids })),
+        ..Default::default()
+    }
+}
+
+/// SMPP transceiver bind exposed as a provider adapter. The session binds
+/// lazily on first use and rebinds after the connection drops. Delivery
+/// receipts and mobile-originated messages arrive over the bind rather than
+/// HTTP; consume them with `subscribe`.
+pub struct SmppAdapter {
+    config: SmppConfig,
+    session: Mutex<Option<Arc<SmppSession>>>,
+    events: broadcast::Sender<SmppEvent>,
+    reference: AtomicU8,
+}
+
+impl SmppAdapter {
+    pub fn new(config: SmppConfig) -> Self {
+        let (events, _) = broadcast::channel(1024);
+        Self {
+            config,
+            session: Mutex::new(None),
+            events,
+            reference: AtomicU8::new(0),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SmppEvent> {
+        self.events.subscribe()
+    }
+
+    /// The bound session, binding a new one if there is none or it closed.
+    pub async fn session(&self) -> Result<Arc<SmppSession>, SmppError> {
+        let mut guard = self.session.lock().await;
+        if let Some(session) = guard.as_ref().filter(|s| !s.is_closed()) {
+            return Ok(session.clone());
+        }
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let session = SmppSession::bind(&self.config, tx).await?;
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            while let Some(deliver) = rx.recv().await {
+                let _ = events.send(event_for(deliver));
+            }
+        });
+        *guard = Some(session.clone());
+        Ok(session)
+    }
+}
+
+#[async_trait]
+impl BaseProviderAdapter for SmppAdapter {
+    fn name(&self) -> String {
+        self.config.name.clone()
+    }
+
+    async fn initialize(&self) {
+        if let Err(e) = self.session().await {
+            warn!("SMPP bind to {} failed: {}", self.config.host, e);
+        }
+    }
+
+    async fn close(&self) {
+        if let Some(session) = self.session.lock().await.take() {
+            session.unbind().await;
+        }
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let session = match self.session().await {
+            Ok(session) => session,
+            Err(e) => {
+                error!("SMPP bind to {} failed: {}", self.config.host, e);
+                return failed(None, e.to_string(), None);
+            }
+        };
+
+        let reference = self.reference.fetch_add(1, Ordering::Relaxed);
+        submit(&session, to, from, body, metadata.as_ref(), reference).await
+    }
+
+    /// Submits concurrently so the whole window stays in use rather than
+    /// waiting out a round trip per message.
+    async fn send_sms_batch(&self, messages: Vec<OutboundSms>) -> Vec<SendResult> {
+        let session = match self.session().await {
+            Ok(session) => session,
+            Err(e) => {
+                error!("SMPP bind to {} failed: {}", self.config.host, e);
+                return vec![failed(None, e.to_string(), None); messages.len()];
+            }
+        };
+        let handles: Vec<_> = messages
+            .into_iter()
+            .map(|message| {
+                let session = session.clone();
+                let reference = self.reference.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(async move {
+                    submit(
+                        &session,
+                        &message.to,
+                        &message.from,
+                        &message.body,
+                        message.metadata.as_ref(),
+                        reference,
+                    )
+                    .await
+                })
+            })
+            .collect();
+        let mut results = Vec::with_capacity(handles.len());
+        for handle in handles {
+            results.push(
+                handle
+                    .await
+                    .unwrap_or_else(|e| failed(None, e.to_string(), None)),
+            );
+        }
+        results
+    }
+
+    /// Receipts arrive over the bind, never as webhooks.
+    async fn validate_webhook(&self, _headers: &HashMap<String, String>, _body: &[u8]) -> bool {
+        false
+    }
+
+    /// Parses receipt text relayed from elsewhere, e.g. an SMPP gateway that
+    /// forwards `deliver_sm` over HTTP.
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        parse_receipt_text(&String::from_utf8_lossy(body))
+    }
+
+    async fn health_check(&self) -> bool {
+        self.session().await.is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_gsm7_text_is_one_unpacked_part() {
+        let (data_coding, parts) = segment("Your code is 1234", 9);
+        assert_eq!(data_coding, DATA_CODING_DEFAULT);
+        assert_eq!(parts, vec![b"Your code is 1234".to_vec()]);
+        assert_eq!(decode_text(data_coding, &parts[0]), "Your code is 1234");
+    }
+
+    #[test]
+    fn long_text_gets_concatenation_headers() {
+        let (_, parts) = segment(&"a".repeat(161), 9);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0][..6], [0x05, 0x00, 0x03, 9, 2, 1]);
+        assert_eq!(parts[0].len(), 6 + 153);
+        assert_eq!(parts[1][..6], [0x05, 0x00, 0x03, 9, 2, 2]);
+        assert_eq!(parts[1].len(), 6 + 8);
+    }
+
+    #[test]
+    fn escaped_characters_are_not_split_across_parts() {
+        let text = format!("{}€{}", "a".repeat(152), "a".repeat(10));
+        let (data_coding, parts) = segment(&text, 1);
+        assert_eq!(parts[0].len(), 6 + 152);
+        let joined: Vec<u8> = parts.iter().flat_map(|p| p[6..].to_vec()).collect();
+        assert_eq!(decode_text(data_coding, &joined), text);
+    }
+
+    #[test]
+    fn non_gsm_text_uses_ucs2() {
+        let (data_coding, parts) = segment("Olá ✓", 1);
+        assert_eq!(data_coding, DATA_CODING_UCS2);
+        assert_eq!(parts[0].len(), 10);
+        assert_eq!(decode_text(data_coding, &parts[0]), "Olá ✓");
+
+        let (_, parts) = segment(&"✓".repeat(71), 1);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].len(), 6 + 134);
+    }
+
+    #[test]
+    fn parses_receipt_text() {
+        let event = parse_receipt_text(
+            "id:0a1b2c sub:001 dlvrd:001 submit date:2410150930 \
+             done date:241015093105 stat:DELIVRD err:000 text:Your code is",
+        )
+        .unwrap();
+        assert_eq!(event.provider_message_id, "0a1b2c");
+        assert_eq!(event.status, MessageStatus::Delivered);
+        assert_eq!(event.error_code, None);
+        assert_eq!(event.error_message, None);
+        assert_eq!(event.timestamp, Some(1_728_984_665.0));
+
+        let event = parse_receipt_text("id:9 stat:UNDELIV err:00B done date:2410150931").unwrap();
+        assert_eq!(event.status, MessageStatus::Failed);
+        assert_eq!(event.error_code.as_deref(), Some("00B"));
+        assert_eq!(event.error_message.as_deref(), Some("UNDELIV"));
+        assert_eq!(event.timestamp, Some(1_728_984_660.0));
+
+        assert!(parse_receipt_text("sub:001 stat:DELIVRD").is_err());
+        // `text:` is free-form and mustn't be read as fields.
+        let event = parse_receipt_text("id:1 stat:ACCEPTD text:id:2 stat:DELIVRD").unwrap();
+        assert_eq!(event.provider_message_id, "1");
+        assert_eq!(event.status, MessageStatus::Sent);
+    }
+
+    #[test]
+    fn receipt_tlvs_override_the_text() {
+        let deliver = DeliverSm {
+            esm_class: pdu::ESM_DELIVERY_RECEIPT,
+            short_message: b"id:text-id stat:DELIVRD err:000".to_vec(),
+            tlvs: vec![
+                (pdu::TLV_RECEIPTED_MESSAGE_ID, b"tlv-id\0".to_vec()),
+                (pdu::TLV_MESSAGE_STATE, vec![3]),
+            ],
+            ..DeliverSm::default()
+        };
+        let SmppEvent::DeliveryReceipt(event) = event_for(deliver) else {
+            panic!("expected a delivery receipt");
+        };
+        assert_eq!(event.provider_message_id, "tlv-id");
+        assert_eq!(event.status, MessageStatus::Failed);
+        assert_eq!(event.reason, Some(DlrReason::Expired));
+    }
+
+    #[test]
+    fn other_deliver_sm_is_inbound() {
+        let deliver = DeliverSm {
+            source: "15550100".to_string(),
+            destination: "12345".to_string(),
+            short_message: b"STOP".to_vec(),
+            ..DeliverSm::default()
+        };
+        let SmppEvent::Inbound(message) = event_for(deliver) else {
+            panic!("expected an inbound message");
+        };
+        assert_eq!(
+            (
+                message.from.as_str(),
+                message.to.as_str(),
+                message.body.as_str()
+            ),
+            ("15550100", "12345", "STOP")
+        );
+    }
+
+    #[test]
+    fn maps_submit_statuses() {
+        assert_eq!(status_for(pdu::ESME_RINVDSTADR), MessageStatus::Rejected);
+        assert_eq!(status_for(pdu::ESME_RTHROTTLED), MessageStatus::Failed);
+        assert_eq!(map_message_state(2), MessageStatus::Delivered);
+        assert_eq!(map_status("rejectd"), MessageStatus::Rejected);
+    }
+}
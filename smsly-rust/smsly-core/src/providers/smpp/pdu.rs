@@ -0,0 +1,430 @@
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Set on every response `command_id`.
+pub const RESPONSE_BIT: u32 = 0x8000_0000;
+
+pub const GENERIC_NACK: u32 = 0x8000_0000;
+pub const BIND_TRANSCEIVER: u32 = 0x0000_0009;
+pub const BIND_TRANSCEIVER_RESP: u32 = 0x8000_0009;
+pub const SUBMIT_SM: u32 = 0x0000_0004;
+pub const SUBMIT_SM_RESP: u32 = 0x8000_0004;
+pub const DELIVER_SM: u32 = 0x0000_0005;
+pub const DELIVER_SM_RESP: u32 = 0x8000_0005;
+pub const UNBIND: u32 = 0x0000_0006;
+pub const UNBIND_RESP: u32 = 0x8000_0006;
+pub const ENQUIRE_LINK: u32 = 0x0000_0015;
+pub const ENQUIRE_LINK_RESP: u32 = 0x8000_0015;
+
+pub const ESME_ROK: u32 = 0x0000_0000;
+pub const ESME_RINVCMDID: u32 = 0x0000_0003;
+pub const ESME_RINVSRCADR: u32 = 0x0000_000A;
+pub const ESME_RINVDSTADR: u32 = 0x0000_000B;
+pub const ESME_RMSGQFUL: u32 = 0x0000_0014;
+pub const ESME_RTHROTTLED: u32 = 0x0000_0058;
+
+/// TLV tags used in delivery receipts.
+pub const TLV_RECEIPTED_MESSAGE_ID: u16 = 0x001E;
+pub const TLV_MESSAGE_STATE: u16 = 0x0427;
+
+pub const INTERFACE_VERSION: u8 = 0x34;
+
+/// `esm_class` bits.
+pub const ESM_UDHI: u8 = 0x40;
+pub const ESM_DELIVERY_RECEIPT: u8 = 0x04;
+
+/// Largest PDU accepted from the SMSC; real PDUs are well under 1 KiB.
+const MAX_PDU_LEN: u32 = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pdu {
+    pub command_id: u32,
+    pub command_status: u32,
+    pub sequence: u32,
+    pub body: Vec<u8>,
+}
+
+impl Pdu {
+    pub fn new(command_id: u32, sequence: u32, body: Vec<u8>) -> Self {
+        Self {
+            command_id,
+            command_status: ESME_ROK,
+            sequence,
+            body,
+        }
+    }
+
+    /// Empty-bodied response to `request`, e.g. `enquire_link_resp`.
+    pub fn response_to(request: &Pdu, command_status: u32) -> Self {
+        Self {
+            command_id: request.command_id | RESPONSE_BIT,
+            command_status,
+            sequence: request.sequence,
+            body: Vec::new(),
+        }
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.command_id & RESPONSE_BIT != 0
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(16 + self.body.len());
+        out.extend_from_slice(&(16 + self.body.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.command_id.to_be_bytes());
+        out.extend_from_slice(&self.command_status.to_be_bytes());
+        out.extend_from_slice(&self.sequence.to_be_bytes());
+        out.extend_from_slice(&self.body);
+        out
+    }
+
+    pub async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let len = reader.read_u32().await?;
+        if !(16..=MAX_PDU_LEN).contains(&len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid PDU length {}", len),
+            ));
+        }
+        let command_id = reader.read_u32().await?;
+        let command_status = reader.read_u32().await?;
+        let sequence = reader.read_u32().await?;
+        let mut body = vec![0; len as usize - 16];
+        reader.read_exact(&mut body).await?;
+        Ok(Self {
+            command_id,
+            command_status,
+            sequence,
+            body,
+        })
+    }
+}
+
+/// Type of number / numbering plan indicator for an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub ton: u8,
+    pub npi: u8,
+    pub addr: String,
+}
+
+impl Address {
+    /// `+E.164` becomes international/ISDN, digits stay unknown/ISDN and
+    /// anything else is an alphanumeric sender ID.
+    pub fn parse(addr: &str) -> Self {
+        if let Some(number) = addr.strip_prefix('+') {
+            Self {
+                ton: 1,
+                npi: 1,
+                addr: number.to_string(),
+            }
+        } else if !addr.is_empty() && addr.chars().all(|c| c.is_ascii_digit()) {
+            Self {
+                ton: 0,
+                npi: 1,
+                addr: addr.to_string(),
+            }
+        } else {
+            Self {
+                ton: 5,
+                npi: 0,
+                addr: addr.to_string(),
+            }
+        }
+    }
+}
+
+fn put_cstring(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(value.as_bytes());
+    out.push(0);
+}
+
+pub fn bind_transceiver(system_id: &str, password: &str, system_type: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_cstring(&mut body, system_id);
+    put_cstring(&mut body, password);
+    put_cstring(&mut body, system_type);
+    body.push(INTERFACE_VERSION);
+    body.push(0); // addr_ton
+    body.push(0); // addr_npi
+    put_cstring(&mut body, ""); // address_range
+    body
+}
+
+#[derive(Debug, Clone)]
+pub struct SubmitSm {
+    pub source: Address,
+    pub destination: Address,
+    pub esm_class: u8,
+    pub registered_delivery: u8,
+    pub data_coding: u8,
+    /// How long the SMSC keeps retrying delivery; its default when `None`.
+    pub validity_period: Option<Duration>,
+    pub short_message: Vec<u8>,
+}
+
+/// SMPP relative time, `YYMMDDhhmmss000R`. Whole days only go up to 99,
+/// so longer periods are capped there.
+pub fn relative_time(period: Duration) -> String {
+    let secs = period.as_secs().clamp(1, 99 * 86_400 + 86_399);
+    format!(
+        "0000{:02}{:02}{:02}{:02}000R",
+        secs / 86_400,
+        secs % 86_400 / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+impl SubmitSm {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(64 + self.short_message.len());
+        put_cstring(&mut body, ""); // service_type
+        body.push(self.source.ton);
+        body.push(self.source.npi);
+        put_cstring(&mut body, &self.source.addr);
+        body.push(self.destination.ton);
+        body.push(self.destination.npi);
+        put_cstring(&mut body, &self.destination.addr);
+        body.push(self.esm_class);
+        body.push(0); // protocol_id
+        body.push(0); // priority_flag
+        put_cstring(&mut body, ""); // schedule_delivery_time
+        put_cstring(
+            &mut body,
+            &self.validity_period.map(relative_time).unwrap_or_default(),
+        );
+        body.push(self.registered_delivery);
+        body.push(0); // replace_if_present_flag
+        body.push(self.data_coding);
+        body.push(0); // sm_default_msg_id
+        body.push(self.short_message.len() as u8);
+        body.extend_from_slice(&self.short_message);
+        body
+    }
+}
+
+/// Cursor over a PDU body.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        let value = *self.buf.get(self.pos)?;
+        self.pos += 1;
+        Some(value)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.buf.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(slice)
+    }
+
+    fn cstring(&mut self) -> Option<String> {
+        let end = self.buf[self.pos..].iter().position(|b| *b == 0)?;
+        let value = String::from_utf8_lossy(&self.buf[self.pos..self.pos + end]).into_owned();
+        self.pos += end + 1;
+        Some(value)
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.len().saturating_sub(self.pos)
+    }
+}
+
+/// `message_id` from a `submit_sm_resp` body.
+pub fn submit_sm_resp_message_id(body: &[u8]) -> Option<String> {
+    Reader::new(body).cstring()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DeliverSm {
+    pub source: String,
+    pub destination: String,
+    pub esm_class: u8,
+    pub data_coding: u8,
+    pub short_message: Vec<u8>,
+    pub tlvs: Vec<(u16, Vec<u8>)>,
+}
+
+impl DeliverSm {
+    pub fn decode(body: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(body);
+        r.cstring()?; // service_type
+        r.u8()?;
+        r.u8()?;
+        let source = r.cstring()?;
+        r.u8()?;
+        r.u8()?;
+        let destination = r.cstring()?;
+        let esm_class = r.u8()?;
+        r.u8()?; // protocol_id
+        r.u8()?; // priority_flag
+        r.cstring()?; // schedule_delivery_time
+        r.cstring()?; // validity_period
+        r.u8()?; // registered_delivery
+        r.u8()?; // replace_if_present_flag
+        let data_coding = r.u8()?;
+        r.u8()?; // sm_default_msg_id
+        let len = r.u8()? as usize;
+        let short_message = r.bytes(len)?.to_vec();
+        let mut tlvs = Vec::new();
+        while r.remaining() >= 4 {
+            let tag = r.u16()?;
+            let len = r.u16()? as usize;
+            tlvs.push((tag, r.bytes(len)?.to_vec()));
+        }
+        Some(Self {
+            source,
+            destination,
+            esm_class,
+            data_coding,
+            short_message,
+            tlvs,
+        })
+    }
+
+    pub fn is_delivery_receipt(&self) -> bool {
+        self.esm_class & 0x3C == ESM_DELIVERY_RECEIPT
+    }
+
+    pub fn tlv(&self, tag: u16) -> Option<&[u8]> {
+        self.tlvs
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, value)| value.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pdus_round_trip() {
+        let pdu = Pdu::new(SUBMIT_SM, 7, vec![1, 2, 3]);
+        let bytes = pdu.encode();
+        assert_eq!(&bytes[..4], &19u32.to_be_bytes());
+        assert_eq!(Pdu::read(&mut bytes.as_slice()).await.unwrap(), pdu);
+
+        let resp = Pdu::response_to(&pdu, ESME_RTHROTTLED);
+        assert!(resp.is_response() && !pdu.is_response());
+        assert_eq!(resp.command_id, SUBMIT_SM_RESP);
+        assert_eq!(resp.sequence, 7);
+        assert_eq!(
+            Pdu::read(&mut resp.encode().as_slice()).await.unwrap(),
+            resp
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_lengths() {
+        let mut short = Pdu::new(ENQUIRE_LINK, 1, Vec::new()).encode();
+        short[..4].copy_from_slice(&15u32.to_be_bytes());
+        assert!(Pdu::read(&mut short.as_slice()).await.is_err());
+
+        let mut huge = short.clone();
+        huge[..4].copy_from_slice(&(MAX_PDU_LEN + 1).to_be_bytes());
+        assert!(Pdu::read(&mut huge.as_slice()).await.is_err());
+
+        let truncated = Pdu::new(SUBMIT_SM, 1, vec![0; 10]).encode();
+        assert!(Pdu::read(&mut &truncated[..20]).await.is_err());
+    }
+
+    #[test]
+    fn encodes_bind_transceiver() {
+        assert_eq!(
+            bind_transceiver("smsly", "secret", ""),
+            b"smsly\0secret\0\0\x34\0\0\0".to_vec()
+        );
+    }
+
+    #[test]
+    fn classifies_addresses() {
+        assert_eq!(
+            Address::parse("+447700900123"),
+            Address {
+                ton: 1,
+                npi: 1,
+                addr: "447700900123".to_string()
+            }
+        );
+        assert_eq!(
+            (Address::parse("12345").ton, Address::parse("12345").npi),
+            (0, 1)
+        );
+        assert_eq!(
+            (Address::parse("SMSLY").ton, Address::parse("SMSLY").npi),
+            (5, 0)
+        );
+        assert_eq!(Address::parse("").ton, 5);
+    }
+
+    #[test]
+    fn formats_relative_validity() {
+        assert_eq!(relative_time(Duration::from_secs(5400)), "000000013000000R");
+        assert_eq!(
+            relative_time(Duration::from_secs(90_061)),
+            "000001010101000R"
+        );
+        assert_eq!(relative_time(Duration::ZERO), "000000000001000R");
+        assert_eq!(
+            relative_time(Duration::from_secs(200 * 86_400)),
+            "000099235959000R"
+        );
+    }
+
+    /// `submit_sm` and `deliver_sm` share a body layout, so one decodes the
+    /// other.
+    #[test]
+    fn submit_sm_decodes_as_deliver_sm() {
+        let submit = SubmitSm {
+            source: Address::parse("SMSLY"),
+            destination: Address::parse("+15550100"),
+            esm_class: ESM_UDHI,
+            registered_delivery: 1,
+            data_coding: 0x08,
+            validity_period: Some(Duration::from_secs(3600)),
+            short_message: vec![0x00, 0x48, 0x00, 0x69],
+        };
+        let mut body = submit.encode();
+        body.extend_from_slice(&TLV_MESSAGE_STATE.to_be_bytes());
+        body.extend_from_slice(&1u16.to_be_bytes());
+        body.push(2);
+
+        let deliver = DeliverSm::decode(&body).unwrap();
+        assert_eq!(deliver.source, "SMSLY");
+        assert_eq!(deliver.destination, "15550100");
+        assert_eq!(deliver.esm_class, ESM_UDHI);
+        assert_eq!(deliver.data_coding, 0x08);
+        assert_eq!(deliver.short_message, submit.short_message);
+        assert_eq!(deliver.tlv(TLV_MESSAGE_STATE), Some(&[2][..]));
+        assert_eq!(deliver.tlv(TLV_RECEIPTED_MESSAGE_ID), None);
+        assert!(!deliver.is_delivery_receipt());
+
+        // Cut inside short_message.
+        assert!(DeliverSm::decode(&body[..body.len() - 7]).is_none());
+        assert!(DeliverSm::decode(b"").is_none());
+    }
+
+    #[test]
+    fn reads_the_submit_sm_resp_message_id() {
+        assert_eq!(
+            submit_sm_resp_message_id(b"0a1b2c\0").as_deref(),
+            Some("0a1b2c")
+        );
+        assert_eq!(submit_sm_resp_message_id(b"unterminated"), None);
+    }
+}
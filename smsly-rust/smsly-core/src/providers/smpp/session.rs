@@ -0,0 +1,347 @@
+use super::pdu::{self, DeliverSm, Pdu};
+use super::{SmppConfig, SmppError};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, Weak};
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, Mutex, Semaphore};
+use tracing::{debug, info, warn};
+
+/// A bound transceiver session. Requests are pipelined up to the configured
+/// window; a reader task routes responses by sequence number and hands
+/// `deliver_sm` PDUs to `incoming`, and a keepalive task sends `enquire_link`.
+/// Once the connection drops the session is closed for good; the adapter
+/// binds a new one.
+pub struct SmppSession {
+    writer: Mutex<OwnedWriteHalf>,
+    pending: StdMutex<HashMap<u32, oneshot::Sender<Pdu>>>,
+    sequence: AtomicU32,
+    window: Semaphore,
+    closed: AtomicBool,
+    config: SmppConfig,
+}
+
+impl SmppSession {
+    pub async fn bind(
+        config: &SmppConfig,
+        incoming: mpsc::UnboundedSender<DeliverSm>,
+    ) -> Result<Arc<Self>, SmppError> {
+        let addr = format!("{}:{}", config.host, config.port);
+        let stream = tokio::time::timeout(config.response_timeout, TcpStream::connect(&addr))
+            .await
+            .map_err(|_| SmppError::Timeout)??;
+        stream.set_nodelay(true)?;
+        let (mut reader, mut writer) = stream.into_split();
+
+        let bind = Pdu::new(
+            pdu::BIND_TRANSCEIVER,
+            1,
+            pdu::bind_transceiver(&config.system_id, &config.password, &config.system_type),
+        );
+        writer.write_all(&bind.encode()).await?;
+        let resp = tokio::time::timeout(config.response_timeout, Pdu::read(&mut reader))
+            .await
+            .map_err(|_| SmppError::Timeout)??;
+        if resp.command_id != pdu::BIND_TRANSCEIVER_RESP {
+            return Err(SmppError::UnexpectedPdu(resp.command_id));
+        }
+        if resp.command_status != pdu::ESME_ROK {
+            return Err(SmppError::BindFailed(resp.command_status));
+        }
+        info!("SMPP bound to {} as {}", addr, config.system_id);
+
+        let session = Arc::new(Self {
+            writer: Mutex::new(writer),
+            pending: StdMutex::new(HashMap::new()),
+            sequence: AtomicU32::new(2),
+            window: Semaphore::new(config.window_size.max(1)),
+            closed: AtomicBool::new(false),
+            config: config.clone(),
+        });
+        tokio::spawn(read_loop(Arc::downgrade(&session), reader, incoming));
+        tokio::spawn(keepalive(Arc::downgrade(&session)));
+        Ok(session)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn next_sequence(&self) -> u32 {
+        // Sequence numbers are 1..=0x7FFFFFFF.
+        let seq = self.sequence.fetch_add(1, Ordering::SeqCst) & 0x7FFF_FFFF;
+        seq.max(1)
+    }
+
+    async fn write(&self, pdu: &Pdu) -> Result<(), SmppError> {
+        let mut writer = self.writer.lock().await;
+        if let Err(e) = writer.write_all(&pdu.encode()).await {
+            self.close();
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Sends a request and waits for its response, holding a window slot
+    /// until it arrives or `response_timeout` passes.
+    pub async fn request(&self, command_id: u32, body: Vec<u8>) -> Result<Pdu, SmppError> {
+        if self.is_closed() {
+            return Err(SmppError::Closed);
+        }
+        let _permit = self.window.acquire().await.map_err(|_| SmppError::Closed)?;
+        let seq = self.next_sequence();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(seq, tx);
+
+        if let Err(e) = self.write(&Pdu::new(command_id, seq, body)).await {
+            self.pending.lock().unwrap().remove(&seq);
+            return Err(e);
+        }
+        match tokio::time::timeout(self.config.response_timeout, rx).await {
+            Ok(Ok(resp)) => Ok(resp),
+            Ok(Err(_)) => Err(SmppError::Closed),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&seq);
+                Err(SmppError::Timeout)
+            }
+        }
+    }
+
+    /// Unbinds politely; the SMSC closes the connection afterwards.
+    pub async fn unbind(&self) {
+        if !self.is_closed() {
+            let _ = self.request(pdu::UNBIND, Vec::new()).await;
+        }
+        self.close();
+    }
+
+    fn close(&self) {
+        if !self.closed.swap(true, Ordering::SeqCst) {
+            // Dropping the senders fails every in-flight request with `Closed`.
+            self.pending.lock().unwrap().clear();
+            self.window.close();
+        }
+    }
+}
+
+async fn read_loop(
+    session: Weak<SmppSession>,
+    mut reader: OwnedReadHalf,
+    incoming: mpsc::UnboundedSender<DeliverSm>,
+) {
+    loop {
+        let result = Pdu::read(&mut reader).await;
+        let Some(session) = session.upgrade() else {
+            return;
+        };
+        let pdu = match result {
+            Ok(pdu) => pdu,
+            Err(e) => {
+                warn!("SMPP connection to {} lost: {}", session.config.host, e);
+                session.close();
+                return;
+            }
+        };
+
+        if pdu.is_response() {
+            let waiter = session.pending.lock().unwrap().remove(&pdu.sequence);
+            match waiter {
+                Some(waiter) => {
+                    let _ = waiter.send(pdu);
+                }
+                None => debug!("SMPP response for unknown sequence {}", pdu.sequence),
+            }
+            continue;
+        }
+
+        let reply = match pdu.command_id {
+            pdu::DELIVER_SM => {
+                match DeliverSm::decode(&pdu.body) {
+                    Some(deliver) => {
+                        let _ = incoming.send(deliver);
+                    }
+                    None => warn!("Malformed deliver_sm from {}", session.config.host),
+                }
+                // deliver_sm_resp carries an empty message_id.
+                let mut resp = Pdu::response_to(&pdu, pdu::ESME_ROK);
+                resp.body.push(0);
+                resp
+            }
+            pdu::ENQUIRE_LINK => Pdu::response_to(&pdu, pdu::ESME_ROK),
+            pdu::UNBIND => {
+                let _ = session.write(&Pdu::response_to(&pdu, pdu::ESME_ROK)).await;
+                info!("SMPP unbound by {}", session.config.host);
+                session.close();
+                return;
+            }
+            _ => Pdu {
+                command_id: pdu::GENERIC_NACK,
+                command_status: pdu::ESME_RINVCMDID,
+                sequence: pdu.sequence,
+                body: Vec::new(),
+            },
+        };
+        if session.write(&reply).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn keepalive(session: Weak<SmppSession>) {
+    let Some(interval) = session.upgrade().map(|s| s.config.enquire_link_interval) else {
+        return;
+    };
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let Some(session) = session.upgrade() else {
+            return;
+        };
+        if session.is_closed() {
+            return;
+        }
+        if let Err(e) = session.request(pdu::ENQUIRE_LINK, Vec::new()).await {
+            warn!("SMPP enquire_link to {} failed: {}", session.config.host, e);
+            session.close();
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    async fn smsc() -> (TcpListener, SmppConfig) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        (
+            listener,
+            SmppConfig::new("127.0.0.1", port, "smsly", "secret"),
+        )
+    }
+
+    /// Reads the bind and answers it with `status`.
+    async fn accept_bind(listener: &TcpListener, status: u32) -> TcpStream {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let bind = Pdu::read(&mut stream).await.unwrap();
+        assert_eq!(bind.command_id, pdu::BIND_TRANSCEIVER);
+        assert_eq!(bind.body, pdu::bind_transceiver("smsly", "secret", ""));
+        let mut resp = Pdu::response_to(&bind, status);
+        resp.body = b"SMSC\0".to_vec();
+        stream.write_all(&resp.encode()).await.unwrap();
+        stream
+    }
+
+    #[tokio::test]
+    async fn exchanges_pdus_with_the_smsc() {
+        let (listener, config) = smsc().await;
+        let (done, replied) = oneshot::channel();
+        let smsc = tokio::spawn(async move {
+            let mut stream = accept_bind(&listener, pdu::ESME_ROK).await;
+
+            let submit = Pdu::read(&mut stream).await.unwrap();
+            assert_eq!((submit.command_id, submit.sequence), (pdu::SUBMIT_SM, 2));
+            let mut resp = Pdu::response_to(&submit, pdu::ESME_ROK);
+            resp.body = b"msg-1\0".to_vec();
+            stream.write_all(&resp.encode()).await.unwrap();
+
+            let mut deliver = Vec::new();
+            deliver.extend_from_slice(b"\0\x01\x0115550100\0\0\x0012345\0");
+            deliver.extend_from_slice(b"\0\0\0\0\0\0\0\0\0\x04STOP");
+            let deliver = Pdu::new(pdu::DELIVER_SM, 77, deliver);
+            stream.write_all(&deliver.encode()).await.unwrap();
+            let resp = Pdu::read(&mut stream).await.unwrap();
+            assert_eq!((resp.command_id, resp.sequence), (pdu::DELIVER_SM_RESP, 77));
+            assert_eq!(resp.body, b"\0");
+
+            stream
+                .write_all(&Pdu::new(pdu::ENQUIRE_LINK, 78, Vec::new()).encode())
+                .await
+                .unwrap();
+            let resp = Pdu::read(&mut stream).await.unwrap();
+            assert_eq!(
+                (resp.command_id, resp.sequence),
+                (pdu::ENQUIRE_LINK_RESP, 78)
+            );
+
+            stream
+                .write_all(&Pdu::new(0x0000_0103, 79, Vec::new()).encode())
+                .await
+                .unwrap();
+            let nack = Pdu::read(&mut stream).await.unwrap();
+            assert_eq!(
+                (nack.command_id, nack.command_status),
+                (pdu::GENERIC_NACK, pdu::ESME_RINVCMDID)
+            );
+            done.send(()).unwrap();
+
+            let unbind = Pdu::read(&mut stream).await.unwrap();
+            assert_eq!(unbind.command_id, pdu::UNBIND);
+            stream
+                .write_all(&Pdu::response_to(&unbind, pdu::ESME_ROK).encode())
+                .await
+                .unwrap();
+        });
+
+        let (tx, mut incoming) = mpsc::unbounded_channel();
+        let session = SmppSession::bind(&config, tx).await.unwrap();
+        let resp = session
+            .request(pdu::SUBMIT_SM, b"body".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(resp.command_id, pdu::SUBMIT_SM_RESP);
+        assert_eq!(
+            pdu::submit_sm_resp_message_id(&resp.body).as_deref(),
+            Some("msg-1")
+        );
+
+        let deliver = incoming.recv().await.unwrap();
+        assert_eq!(
+            (deliver.source.as_str(), deliver.short_message.as_slice()),
+            ("15550100", &b"STOP"[..])
+        );
+
+        replied.await.unwrap();
+        session.unbind().await;
+        assert!(session.is_closed());
+        assert!(matches!(
+            session.request(pdu::SUBMIT_SM, Vec::new()).await,
+            Err(SmppError::Closed)
+        ));
+        smsc.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejected_binds_fail() {
+        let (listener, config) = smsc().await;
+        let smsc = tokio::spawn(async move { accept_bind(&listener, 0x0000_000E).await });
+        let (tx, _incoming) = mpsc::unbounded_channel();
+        assert!(matches!(
+            SmppSession::bind(&config, tx).await,
+            Err(SmppError::BindFailed(0x0000_000E))
+        ));
+        smsc.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn in_flight_requests_fail_when_the_connection_drops() {
+        let (listener, config) = smsc().await;
+        let smsc = tokio::spawn(async move {
+            let mut stream = accept_bind(&listener, pdu::ESME_ROK).await;
+            Pdu::read(&mut stream).await.unwrap();
+        });
+        let (tx, _incoming) = mpsc::unbounded_channel();
+        let session = SmppSession::bind(&config, tx).await.unwrap();
+        assert!(matches!(
+            session.request(pdu::SUBMIT_SM, Vec::new()).await,
+            Err(SmppError::Closed)
+        ));
+        assert!(session.is_closed());
+        smsc.await.unwrap();
+    }
+}
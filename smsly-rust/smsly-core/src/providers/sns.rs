@@ -0,0 +1,437 @@
+use super::signature::hmac_sha256;
+use super::{failed, http_client};
+use crate::adapters::{BaseProviderAdapter, MessageStatus, SendResult};
+use crate::segments::count_segments;
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{Client, Url};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use tracing::error;
+
+const SNS_API_VERSION: &str = "2010-03-31";
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded; charset=utf-8";
+
+#[derive(Debug, Clone)]
+pub struct SnsConfig {
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// For temporary (STS) credentials.
+    pub session_token: Option<String>,
+    /// Defaults to `https://sns.<region>.amazonaws.com/`; override for
+    /// VPC endpoints or LocalStack.
+    pub endpoint: String,
+    /// Used when the metadata has no `sender_id`.
+    pub default_sender_id: Option<String>,
+}
+
+impl SnsConfig {
+    pub fn new(region: &str, access_key_id: &str, secret_access_key: &str) -> Self {
+        Self {
+            region: region.to_string(),
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+            session_token: None,
+            endpoint: format!("https://sns.{}.amazonaws.com/", region),
+            default_sender_id: None,
+        }
+    }
+
+    /// Reads the standard `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`,
+    /// `AWS_SESSION_TOKEN` and `AWS_REGION` (or `AWS_DEFAULT_REGION`).
+    pub fn from_env() -> Option<Self> {
+        let non_empty = |key: &str| env::var(key).ok().filter(|v| !v.is_empty());
+        let region = non_empty("AWS_REGION").or_else(|| non_empty("AWS_DEFAULT_REGION"))?;
+        let mut config = Self::new(
+            &region,
+            &non_empty("AWS_ACCESS_KEY_ID")?,
+            &non_empty("AWS_SECRET_ACCESS_KEY")?,
+        );
+        config.session_token = non_empty("AWS_SESSION_TOKEN");
+        config.default_sender_id = non_empty("AWS_SNS_SENDER_ID");
+        Some(config)
+    }
+}
+
+/// SMS through Amazon SNS `Publish`, signed with SigV4.
+///
+/// Metadata keys: `sender_id`, `sms_type` (`transactional` or `promotional`),
+/// `origination_number` and `max_price` (USD).
+pub struct SnsAdapter {
+    config: SnsConfig,
+    client: Client,
+}
+
+impl SnsAdapter {
+    pub fn new(config: SnsConfig) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+
+    /// POSTs a signed Query API call and returns the HTTP status and XML body.
+    async fn call(&self, params: &[(String, String)]) -> Result<(u16, String), String> {
+        let url = Url::parse(&self.config.endpoint).map_err(|e| e.to_string())?;
+        let host = host_header(&url)?;
+        let body = serde_urlencoded::to_string(params).map_err(|e| e.to_string())?;
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let scope = SigningScope {
+            region: &self.config.region,
+            service: "sns",
+            access_key_id: &self.config.access_key_id,
+            secret_access_key: &self.config.secret_access_key,
+            session_token: self.config.session_token.as_deref(),
+        };
+        let authorization = sign_v4(
+            &scope,
+            "POST",
+            &host,
+            url.path(),
+            &amz_date,
+            FORM_CONTENT_TYPE,
+            &body,
+        );
+
+        let mut request = self
+            .client
+            .post(url)
+            .header("Content-Type", FORM_CONTENT_TYPE)
+            .header("X-Amz-Date", &amz_date)
+            .header("Authorization", authorization);
+        if let Some(token) = &self.config.session_token {
+            request = request.header("X-Amz-Security-Token", token);
+        }
+        let response = request.body(body).send().await.map_err(|e| e.to_string())?;
+        let status = response.status().as_u16();
+        Ok((status, response.text().await.unwrap_or_default()))
+    }
+}
+
+/// Credentials and service scope for `sign_v4`.
+pub(crate) struct SigningScope<'a> {
+    pub region: &'a str,
+    pub service: &'a str,
+    pub access_key_id: &'a str,
+    pub secret_access_key: &'a str,
+    pub session_token: Option<&'a str>,
+}
+
+/// `Host` header value for an endpoint URL, with the port when explicit.
+pub(crate) fn host_header(url: &Url) -> Result<String, String> {
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => Ok(format!("{}:{}", host, port)),
+        (Some(host), None) => Ok(host.to_string()),
+        (None, _) => Err(format!("Invalid AWS endpoint: {}", url)),
+    }
+}
+
+/// Builds the SigV4 `Authorization` header for a request with no query
+/// string.
+pub(crate) fn sign_v4(
+    scope: &SigningScope,
+    method: &str,
+    host: &str,
+    path: &str,
+    amz_date: &str,
+    content_type: &str,
+    body: &str,
+) -> String {
+    sign_v4_with_headers(
+        scope,
+        method,
+        host,
+        path,
+        amz_date,
+        &[("content-type", content_type)],
+        &hex::encode(Sha256::digest(body)),
+    )
+}
+
+/// `sign_v4` for requests signing extra headers (lowercase names) or with
+/// a precomputed payload hash, as S3 requires.
+pub(crate) fn sign_v4_with_headers(
+    scope: &SigningScope,
+    method: &str,
+    host: &str,
+    path: &str,
+    amz_date: &str,
+    extra_headers: &[(&str, &str)],
+    payload_hash: &str,
+) -> String {
+    let date = &amz_date[..8];
+    let mut headers: Vec<(&str, String)> = extra_headers
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect();
+    headers.push(("host", host.to_string()));
+    headers.push(("x-amz-date", amz_date.to_string()));
+    if let Some(token) = scope.session_token {
+        headers.push(("x-amz-security-token", token.to_string()));
+    }
+    headers.sort_by(|a, b| a.0.cmp(b.0));
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method,
+        if path.is_empty() { "/" } else { path },
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+
+    let credential_scope = format!("{}/{}/{}/aws4_request", date, scope.region, scope.service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        credential_scope,
+        hex::encode(Sha256::digest(canonical_request))
+    );
+    let mut key = hmac_sha256(
+        format!("AWS4{}", scope.secret_access_key).as_bytes(),
+        date.as_bytes(),
+    );
+    for part in [scope.region, scope.service, "aws4_request"] {
+        key = hmac_sha256(&key, part.as_bytes());
+    }
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        scope.access_key_id,
+        credential_scope,
+        signed_headers,
+        hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()))
+    )
+}
+
+/// Text of the first `<tag>` in an SNS XML response.
+fn xml_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(&xml[start..end])
+}
+
+fn string_attribute(params: &mut Vec<(String, String)>, name: &str, value: &str) {
+    let idx = params
+        .iter()
+        .filter(|(key, _)| key.ends_with(".Name"))
+        .count()
+        + 1;
+    let prefix = format!("MessageAttributes.entry.{}", idx);
+    params.push((format!("{}.Name", prefix), name.to_string()));
+    params.push((format!("{}.Value.DataType", prefix), "String".to_string()));
+    params.push((format!("{}.Value.StringValue", prefix), value.to_string()));
+}
+
+#[async_trait]
+impl BaseProviderAdapter for SnsAdapter {
+    fn name(&self) -> String {
+        "sns".to_string()
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let meta = |key: &str| {
+            metadata
+                .as_ref()
+                .and_then(|m| m.get(key))
+                .and_then(Value::as_str)
+                .filter(|v| !v.is_empty())
+        };
+        let mut params = vec![
+            ("Action".to_string(), "Publish".to_string()),
+            ("Version".to_string(), SNS_API_VERSION.to_string()),
+            ("PhoneNumber".to_string(), to.to_string()),
+            ("Message".to_string(), body.to_string()),
+        ];
+
+        // An alphanumeric `from` is a sender ID; a number is an origination number.
+        let sender_id = meta("sender_id")
+            .or_else(|| (!from.starts_with('+') && !from.is_empty()).then_some(from))
+            .or(self.config.default_sender_id.as_deref());
+        if let Some(sender_id) = sender_id {
+            string_attribute(&mut params, "AWS.SNS.SMS.SenderID", sender_id);
+        }
+        if let Some(number) =
+            meta("origination_number").or_else(|| from.starts_with('+').then_some(from))
+        {
+            string_attribute(&mut params, "AWS.MM.SMS.OriginationNumber", number);
+        }
+        if let Some(sms_type) = meta("sms_type") {
+            let sms_type = match sms_type.to_lowercase().as_str() {
+                "transactional" => "Transactional",
+                "promotional" => "Promotional",
+                other => {
+                    return SendResult {
+                        status: MessageStatus::Rejected,
+                        ..failed(
+                            Some("InvalidParameter".to_string()),
+                            format!("Unknown sms_type: {}", other),
+                            None,
+                        )
+                    }
+                }
+            };
+            string_attribute(&mut params, "AWS.SNS.SMS.SMSType", sms_type);
+        }
+        if let Some(max_price) = meta("max_price") {
+            string_attribute(&mut params, "AWS.SNS.SMS.MaxPrice", max_price);
+        }
+
+        let (status, xml) = match self.call(&params).await {
+            Ok(response) => response,
+            Err(e) => {
+                error!("SNS publish failed: {}", e);
+                return failed(None, e, None);
+            }
+        };
+        if (200..300).contains(&status) {
+            return SendResult {
+                success: true,
+                provider_message_id: xml_text(&xml, "MessageId").map(str::to_string),
+                status: MessageStatus::Sent,
+                raw_response: Some(json!({ "body": xml })),
+                segments: count_segments(body),
+                ..Default::default()
+            };
+        }
+
+        let code = xml_text(&xml, "Code").unwrap_or("Unknown").to_string();
+        let message = xml_text(&xml, "Message")
+            .unwrap_or("Unknown error")
+            .to_string();
+        SendResult {
+            status: match code.as_str() {
+                "InvalidParameter"
+                | "InvalidParameterValue"
+                | "OptedOut"
+                | "AuthorizationError" => MessageStatus::Rejected,
+                _ => MessageStatus::Failed,
+            },
+            ..failed(Some(code), message, Some(json!({ "body": xml })))
+        }
+    }
+
+    /// SNS reports SMS delivery through CloudWatch Logs, not webhooks.
+    async fn validate_webhook(&self, _headers: &HashMap<String, String>, _body: &[u8]) -> bool {
+        false
+    }
+
+    async fn health_check(&self) -> bool {
+        let params = [
+            ("Action".to_string(), "GetSMSAttributes".to_string()),
+            ("Version".to_string(), SNS_API_VERSION.to_string()),
+        ];
+        matches!(self.call(&params).await, Ok((status, _)) if (200..300).contains(&status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The credentials and request of the AWS SigV4 test suite.
+    const SCOPE: SigningScope<'static> = SigningScope {
+        region: "us-east-1",
+        service: "service",
+        access_key_id: "AKIDEXAMPLE",
+        secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+        session_token: None,
+    };
+    const HOST: &str = "example.amazonaws.com";
+    const AMZ_DATE: &str = "20150830T123600Z";
+    const CREDENTIAL: &str = "Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request";
+
+    #[test]
+    fn signs_the_get_vanilla_vector() {
+        let empty_hash = hex::encode(Sha256::digest(""));
+        assert_eq!(
+            sign_v4_with_headers(&SCOPE, "GET", HOST, "/", AMZ_DATE, &[], &empty_hash),
+            format!(
+                "AWS4-HMAC-SHA256 {}, SignedHeaders=host;x-amz-date, \
+                 Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31",
+                CREDENTIAL
+            )
+        );
+    }
+
+    #[test]
+    fn signs_the_post_vanilla_vector() {
+        let empty_hash = hex::encode(Sha256::digest(""));
+        assert_eq!(
+            sign_v4_with_headers(&SCOPE, "POST", HOST, "", AMZ_DATE, &[], &empty_hash),
+            format!(
+                "AWS4-HMAC-SHA256 {}, SignedHeaders=host;x-amz-date, \
+                 Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b",
+                CREDENTIAL
+            )
+        );
+    }
+
+    #[test]
+    fn signs_the_form_post_vector() {
+        assert_eq!(
+            sign_v4(
+                &SCOPE,
+                "POST",
+                HOST,
+                "/",
+                AMZ_DATE,
+                "application/x-www-form-urlencoded",
+                "Param1=value1",
+            ),
+            format!(
+                "AWS4-HMAC-SHA256 {}, SignedHeaders=content-type;host;x-amz-date, \
+                 Signature=ff11897932ad3f4e8b18135d722051e5ac45fc38421b1da7b9d196a0fe09473a",
+                CREDENTIAL
+            )
+        );
+    }
+
+    #[test]
+    fn session_tokens_are_signed() {
+        let scope = SigningScope {
+            session_token: Some("token"),
+            ..SCOPE
+        };
+        let authorization = sign_v4(&scope, "POST", HOST, "/", AMZ_DATE, FORM_CONTENT_TYPE, "");
+        assert!(authorization
+            .contains("SignedHeaders=content-type;host;x-amz-date;x-amz-security-token,"));
+    }
+
+    #[test]
+    fn host_header_keeps_explicit_ports() {
+        let url = Url::parse("http://localhost:4566/").unwrap();
+        assert_eq!(host_header(&url).unwrap(), "localhost:4566");
+        let url = Url::parse("https://sns.us-east-1.amazonaws.com").unwrap();
+        assert_eq!(host_header(&url).unwrap(), "sns.us-east-1.amazonaws.com");
+    }
+
+    #[test]
+    fn reads_xml_text_and_numbers_attributes() {
+        let xml = "<PublishResult><MessageId>abc-123</MessageId></PublishResult>";
+        assert_eq!(xml_text(xml, "MessageId"), Some("abc-123"));
+        assert_eq!(xml_text(xml, "Code"), None);
+
+        let mut params = Vec::new();
+        string_attribute(&mut params, "AWS.SNS.SMS.SenderID", "SMSLY");
+        string_attribute(&mut params, "AWS.SNS.SMS.SMSType", "Transactional");
+        assert_eq!(params[3].0, "MessageAttributes.entry.2.Name");
+        assert_eq!(params[5].1, "Transactional");
+    }
+}
@@ -0,0 +1,285 @@
+use super::failed;
+use crate::adapters::{BaseProviderAdapter, InboundMessage, RcsContent, SendResult, WebhookEvent};
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use redis::aio::ConnectionManager;
+use redis::{Client, Script};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+lazy_static! {
+    /// Refills by elapsed time (Redis server clock, so workers agree), takes
+    /// one token if available and returns how many ms until one would be.
+    static ref TOKEN_BUCKET: Script = Script::new(
+        r#"
+        local rate = tonumber(ARGV[1])
+        local capacity = tonumber(ARGV[2])
+        local time = redis.call("TIME")
+        local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+        local state = redis.call("HMGET", KEYS[1], "tokens", "ts")
+        local tokens = tonumber(state[1]) or capacity
+        local ts = tonumber(state[2]) or now
+        tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate / 1000)
+        local wait = 0
+        if tokens >= 1 then
+            tokens = tokens - 1
+        else
+            wait = math.ceil((1 - tokens) * 1000 / rate)
+        end
+        redis.call("HSET", KEYS[1], "tokens", tostring(tokens), "ts", now)
+        redis.call("PEXPIRE", KEYS[1], math.ceil(capacity * 1000 / rate) + 1000)
+        return wait
+    "#
+    );
+}
+
+#[derive(Error, Debug)]
+pub enum ThrottleError {
+    #[error("Provider {provider} is over its {tps} msg/s limit")]
+    Exceeded { provider: String, tps: f64 },
+}
+
+/// What to do with a send when the bucket is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for a token, giving up after `max_wait`.
+    Queue {
+        max_wait: Duration,
+    },
+    Reject,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThrottleConfig {
+    /// Sustained messages per second.
+    pub tps: f64,
+    /// Bucket capacity; defaults to one second's worth.
+    pub burst: u32,
+    pub overflow: OverflowPolicy,
+    pub key_prefix: String,
+}
+
+impl ThrottleConfig {
+    pub fn new(tps: f64) -> Self {
+        Self {
+            tps,
+            burst: tps.ceil().max(1.0) as u32,
+            overflow: OverflowPolicy::Queue {
+                max_wait: Duration::from_secs(5),
+            },
+            key_prefix: "smsly:throttle".to_string(),
+        }
+    }
+
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+}
+
+/// Token bucket shared by every worker through Redis, keyed by provider.
+/// If Redis is unreachable sends are let through, as with the API rate
+/// limiter, rather than stalling all traffic on one route.
+pub struct ProviderThrottle {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    provider: String,
+    config: ThrottleConfig,
+}
+
+impl ProviderThrottle {
+    pub fn new(client: Client, provider: &str, config: ThrottleConfig) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            provider: provider.to_lowercase(),
+            config,
+        }
+    }
+
+    fn key(&self) -> String {
+        format!("{}:{}", self.config.key_prefix, self.provider)
+    }
+
+    /// Takes a token; `Ok(Some(wait))` when the bucket is empty.
+    async fn try_take(&self) -> redis::RedisResult<Option<Duration>> {
+        let conn = self
+            .conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await?;
+        let wait_ms: u64 = TOKEN_BUCKET
+            .key(self.key())
+            .arg(self.config.tps)
+            .arg(self.config.burst)
+            .invoke_async(&mut conn.clone())
+            .await?;
+        Ok((wait_ms > 0).then(|| Duration::from_millis(wait_ms)))
+    }
+
+    /// Waits for (or, under `Reject`, demands) a send slot.
+    pub async fn acquire(&self) -> Result<(), ThrottleError> {
+        let exceeded = || ThrottleError::Exceeded {
+            provider: self.provider.clone(),
+            tps: self.config.tps,
+        };
+        let started = Instant::now();
+        loop {
+            let wait = match self.try_take().await {
+                Ok(None) => return Ok(()),
+                Ok(Some(wait)) => wait,
+                Err(e) => {
+                    warn!("Redis unavailable for {} throttle: {}", self.provider, e);
+                    return Ok(());
+                }
+            };
+            match self.config.overflow {
+                OverflowPolicy::Reject => return Err(exceeded()),
+                OverflowPolicy::Queue { max_wait } => {
+                    if started.elapsed() + wait > max_wait {
+                        return Err(exceeded());
+                    }
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+}
+
+/// Wraps an adapter so every send first takes a token from its provider's
+/// bucket. Throttled sends come back failed with error code `throttled`.
+pub struct ThrottledAdapter {
+    inner: Box<dyn BaseProviderAdapter>,
+    throttle: ProviderThrottle,
+}
+
+impl ThrottledAdapter {
+    pub fn new(
+        inner: Box<dyn BaseProviderAdapter>,
+        client: Client,
+        config: ThrottleConfig,
+    ) -> Self {
+        let throttle = ProviderThrottle::new(client, &inner.name(), config);
+        Self { inner, throttle }
+    }
+
+    fn throttled(err: ThrottleError) -> SendResult {
+        failed(Some("throttled".to_string()), err.to_string(), None)
+    }
+}
+
+#[async_trait]
+impl BaseProviderAdapter for ThrottledAdapter {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn supports_sms(&self) -> bool {
+        self.inner.supports_sms()
+    }
+
+    fn supports_mms(&self) -> bool {
+        self.inner.supports_mms()
+    }
+
+    fn supports_whatsapp(&self) -> bool {
+        self.inner.supports_whatsapp()
+    }
+
+    fn supports_rcs(&self) -> bool {
+        self.inner.supports_rcs()
+    }
+
+    fn supports_viber(&self) -> bool {
+        self.inner.supports_viber()
+    }
+
+    fn supports_email(&self) -> bool {
+        self.inner.supports_email()
+    }
+
+    fn supports_push(&self) -> bool {
+        self.inner.supports_push()
+    }
+
+    async fn initialize(&self) {
+        self.inner.initialize().await
+    }
+
+    async fn close(&self) {
+        self.inner.close().await
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        if let Err(e) = self.throttle.acquire().await {
+            return Self::throttled(e);
+        }
+        self.inner.send_sms(to, from, body, metadata).await
+    }
+
+    async fn send_mms(
+        &self,
+        to: &str,
+        from: &str,
+        text: Option<&str>,
+        media_urls: Vec<String>,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        if let Err(e) = self.throttle.acquire().await {
+            return Self::throttled(e);
+        }
+        self.inner
+            .send_mms(to, from, text, media_urls, metadata)
+            .await
+    }
+
+    async fn send_rcs(&self, to: &str, agent_id: &str, content: RcsContent) -> SendResult {
+        if let Err(e) = self.throttle.acquire().await {
+            return Self::throttled(e);
+        }
+        self.inner.send_rcs(to, agent_id, content).await
+    }
+
+    async fn send_whatsapp(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        if let Err(e) = self.throttle.acquire().await {
+            return Self::throttled(e);
+        }
+        self.inner.send_whatsapp(to, from, body, metadata).await
+    }
+
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+        self.inner.validate_webhook(headers, body).await
+    }
+
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        self.inner.parse_webhook(body).await
+    }
+
+    async fn parse_inbound(&self, body: &[u8]) -> Result<InboundMessage, String> {
+        self.inner.parse_inbound(body).await
+    }
+
+    async fn health_check(&self) -> bool {
+        self.inner.health_check().await
+    }
+}
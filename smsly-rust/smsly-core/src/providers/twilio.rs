@@ -0,0 +1,373 @@
+use super::signature::{hmac_sha1, verify_hmac_sha1, SignatureEncoding};
+use super::{failed, form_to_json, header, http_client, parse_form};
+use crate::adapters::{
+    expired_result, remaining_validity, BaseProviderAdapter, InboundMedia, InboundMessage,
+    MessageStatus, SendResult, WebhookEvent,
+};
+use crate::dlr;
+use crate::segments::count_segments;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::error;
+
+pub const TWILIO_API_BASE: &str = "https://api.twilio.com/2010-04-01";
+
+/// Twilio attaches at most 10 media to an inbound MMS.
+const MAX_INBOUND_MEDIA: usize = 10;
+
+/// Longest `ValidityPeriod` Twilio accepts, in seconds.
+const MAX_VALIDITY_SECS: u64 = 36_000;
+
+#[derive(Debug, Clone)]
+pub struct TwilioConfig {
+    pub account_sid: String,
+    pub auth_token: String,
+    /// Sends through a Messaging Service instead of the `from` number.
+    pub messaging_service_sid: Option<String>,
+    /// Public URL Twilio posts status callbacks to, used to verify signatures
+    /// when the gateway doesn't forward `X-Original-Url`.
+    pub webhook_url: Option<String>,
+    pub base_url: String,
+}
+
+impl TwilioConfig {
+    pub fn new(account_sid: &str, auth_token: &str) -> Self {
+        Self {
+            account_sid: account_sid.to_string(),
+            auth_token: auth_token.to_string(),
+            messaging_service_sid: None,
+            webhook_url: None,
+            base_url: TWILIO_API_BASE.to_string(),
+        }
+    }
+
+    pub fn with_messaging_service(mut self, sid: &str) -> Self {
+        self.messaging_service_sid = Some(sid.to_string());
+        self
+    }
+
+    pub fn with_webhook_url(mut self, url: &str) -> Self {
+        self.webhook_url = Some(url.to_string());
+        self
+    }
+}
+
+/// Twilio Programmable Messaging (SMS, MMS and WhatsApp senders).
+pub struct TwilioAdapter {
+    config: TwilioConfig,
+    client: Client,
+}
+
+impl TwilioAdapter {
+    pub fn new(config: TwilioConfig) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+
+    fn account_url(&self) -> String {
+        format!(
+            "{}/Accounts/{}",
+            self.config.base_url.trim_end_matches('/'),
+            self.config.account_sid
+        )
+    }
+
+    fn sender_params(
+        &self,
+        from: &str,
+        metadata: &Option<HashMap<String, Value>>,
+    ) -> Vec<(String, String)> {
+        let mut params = Vec::new();
+        match &self.config.messaging_service_sid {
+            Some(sid) => params.push(("MessagingServiceSid".to_string(), sid.clone())),
+            None => params.push(("From".to_string(), from.to_string())),
+        }
+        if let Some(url) = metadata
+            .as_ref()
+            .and_then(|m| m.get("webhook_url"))
+            .and_then(Value::as_str)
+        {
+            params.push(("StatusCallback".to_string(), url.to_string()));
+        }
+        if let Some(validity) = remaining_validity(metadata.as_ref()) {
+            let secs = validity.as_secs_f64().ceil() as u64;
+            params.push((
+                "ValidityPeriod".to_string(),
+                secs.clamp(1, MAX_VALIDITY_SECS).to_string(),
+            ));
+        }
+        params
+    }
+
+    async fn create_message(&self, params: Vec<(String, String)>) -> SendResult {
+        let response = self
+            .client
+            .post(format!("{}/Messages.json", self.account_url()))
+            .basic_auth(&self.config.account_sid, Some(&self.config.auth_token))
+            .form(&params)
+            .send()
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Twilio send failed: {}", e);
+                return failed(None, e.to_string(), None);
+            }
+        };
+
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        if status.is_success() {
+            SendResult {
+                success: true,
+                provider_message_id: data["sid"].as_str().map(str::to_string),
+                status: map_status(data["status"].as_str().unwrap_or_default()),
+                cost: parse_price(&data["price"]),
+                segments: parse_segments(&data["num_segments"]).unwrap_or_else(|| {
+                    let body = params.iter().find(|(key, _)| key == "Body");
+                    body.map_or(1, |(_, body)| count_segments(body))
+                }),
+                raw_response: Some(data),
+                ..Default::default()
+            }
+        } else {
+            let code = match &data["code"] {
+                Value::Null => status.as_u16().to_string(),
+                code => code.to_string(),
+            };
+            let message = data["message"]
+                .as_str()
+                .unwrap_or("Unknown error")
+                .to_string();
+            failed(Some(code), message, Some(data))
+        }
+    }
+
+    /// `X-Twilio-Signature`: base64 HMAC-SHA1 over the callback URL followed by
+    /// every POST parameter, sorted by name, as `name + value`.
+    pub fn signature(&self, url: &str, params: &[(String, String)]) -> String {
+        STANDARD.encode(hmac_sha1(
+            self.config.auth_token.as_bytes(),
+            signed_payload(url, params).as_bytes(),
+        ))
+    }
+}
+
+fn signed_payload(url: &str, params: &[(String, String)]) -> String {
+    let mut sorted: Vec<&(String, String)> = params.iter().collect();
+    sorted.sort();
+    sorted
+        .into_iter()
+        .fold(url.to_string(), |mut payload, (key, value)| {
+            payload.push_str(key);
+            payload.push_str(value);
+            payload
+        })
+}
+
+pub fn map_status(status: &str) -> MessageStatus {
+    match status.to_lowercase().as_str() {
+        "sent" => MessageStatus::Sent,
+        "delivered" | "read" => MessageStatus::Delivered,
+        "undelivered" | "failed" => MessageStatus::Failed,
+        "canceled" => MessageStatus::Rejected,
+        _ => MessageStatus::Pending,
+    }
+}
+
+/// Twilio reports prices as negative strings (`"-0.00750"`), often only
+/// once the message has been sent.
+fn parse_price(price: &Value) -> Option<f64> {
+    price
+        .as_str()
+        .and_then(|p| p.parse::<f64>().ok())
+        .map(f64::abs)
+}
+
+fn parse_segments(segments: &Value) -> Option<u32> {
+    segments
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .or_else(|| segments.as_u64().map(|s| s as u32))
+}
+
+#[async_trait]
+impl BaseProviderAdapter for TwilioAdapter {
+    fn name(&self) -> String {
+        "twilio".to_string()
+    }
+
+    fn supports_mms(&self) -> bool {
+        true
+    }
+
+    fn supports_whatsapp(&self) -> bool {
+        true
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        if remaining_validity(metadata.as_ref()) == Some(Duration::ZERO) {
+            return expired_result();
+        }
+        let mut params = vec![
+            ("To".to_string(), to.to_string()),
+            ("Body".to_string(), body.to_string()),
+        ];
+        params.extend(self.sender_params(from, &metadata));
+        self.create_message(params).await
+    }
+
+    async fn send_mms(
+        &self,
+        to: &str,
+        from: &str,
+        text: Option<&str>,
+        media_urls: Vec<String>,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        if remaining_validity(metadata.as_ref()) == Some(Duration::ZERO) {
+            return expired_result();
+        }
+        let mut params = vec![("To".to_string(), to.to_string())];
+        params.extend(self.sender_params(from, &metadata));
+        if let Some(text) = text {
+            params.push(("Body".to_string(), text.to_string()));
+        }
+        // Twilio accepts one MediaUrl parameter per attachment.
+        params.extend(
+            media_urls
+                .into_iter()
+                .map(|url| ("MediaUrl".to_string(), url)),
+        );
+        self.create_message(params).await
+    }
+
+    /// WhatsApp senders and recipients are addressed as `whatsapp:<number>`.
+    async fn send_whatsapp(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        if remaining_validity(metadata.as_ref()) == Some(Duration::ZERO) {
+            return expired_result();
+        }
+        let whatsapp = |number: &str| {
+            if number.starts_with("whatsapp:") {
+                number.to_string()
+            } else {
+                format!("whatsapp:{}", number)
+            }
+        };
+        let mut params = vec![
+            ("To".to_string(), whatsapp(to)),
+            ("Body".to_string(), body.to_string()),
+        ];
+        params.extend(self.sender_params(&whatsapp(from), &metadata));
+        self.create_message(params).await
+    }
+
+    /// Requires the public callback URL, from `X-Original-Url` (set by the
+    /// gateway) or `TwilioConfig::webhook_url`.
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+        let Some(signature) = header(headers, "X-Twilio-Signature") else {
+            return false;
+        };
+        let Some(url) = header(headers, "X-Original-Url").or(self.config.webhook_url.as_deref())
+        else {
+            return false;
+        };
+        verify_hmac_sha1(
+            self.config.auth_token.as_bytes(),
+            signed_payload(url, &parse_form(body)).as_bytes(),
+            signature,
+            SignatureEncoding::Base64,
+        )
+    }
+
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        let params = parse_form(body);
+        let field = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        let provider_message_id = field("MessageSid")
+            .or_else(|| field("SmsSid"))
+            .ok_or_else(|| "Twilio callback missing MessageSid".to_string())?;
+        let status = field("MessageStatus")
+            .or_else(|| field("SmsStatus"))
+            .unwrap_or_default();
+
+        let error_code = field("ErrorCode");
+        let error_message = field("ErrorMessage");
+        Ok(WebhookEvent {
+            provider_message_id,
+            status: map_status(&status),
+            timestamp: None,
+            reason: dlr::normalize("twilio", error_code.as_deref(), error_message.as_deref()),
+            error_code,
+            error_message,
+            raw_payload: Some(form_to_json(&params)),
+        })
+    }
+
+    /// Incoming-message webhook; MMS media arrive as `MediaUrl{n}` and
+    /// `MediaContentType{n}`.
+    async fn parse_inbound(&self, body: &[u8]) -> Result<InboundMessage, String> {
+        let params = parse_form(body);
+        let field = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        let from = field("From").ok_or_else(|| "Twilio message missing From".to_string())?;
+        let num_media: usize = field("NumMedia")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(0)
+            .min(MAX_INBOUND_MEDIA);
+        let media = (0..num_media)
+            .filter_map(|i| {
+                Some(InboundMedia {
+                    url: field(&format!("MediaUrl{}", i))?,
+                    content_type: field(&format!("MediaContentType{}", i)),
+                })
+            })
+            .collect();
+        Ok(InboundMessage {
+            provider_message_id: field("MessageSid").or_else(|| field("SmsSid")),
+            from,
+            to: field("To").unwrap_or_default(),
+            body: field("Body").unwrap_or_default(),
+            media,
+            timestamp: None,
+            raw_payload: Some(form_to_json(&params)),
+        })
+    }
+
+    async fn health_check(&self) -> bool {
+        self.client
+            .get(format!("{}.json", self.account_url()))
+            .basic_auth(&self.config.account_sid, Some(&self.config.auth_token))
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+}
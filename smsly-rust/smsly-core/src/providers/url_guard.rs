@@ -0,0 +1,165 @@
+//! Guards for requests to customer-supplied URLs (status callbacks, MMS
+//! media), so they can't be pointed at loopback, private networks or cloud
+//! metadata endpoints.
+//!
+//! `check_url` rejects bad schemes and non-public IP literals up front.
+//! Hostnames are checked after DNS resolution by `guarded_client`'s
+//! resolver, which connects only to public addresses, so a name that
+//! resolves to `169.254.169.254` (or is rebound to it later) fails at
+//! connect time. `guarded_client` doesn't follow redirects;
+//! `guarded_client_with_redirects` checks each hop like the first.
+//! Requests sent through a configured HTTP proxy are resolved by the proxy
+//! instead.
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Url};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum UrlGuardError {
+    #[error("Invalid URL {0}")]
+    Invalid(String),
+    #[error("URL scheme {0} is not allowed; use http or https")]
+    Scheme(String),
+    #[error("{0} is not a public address")]
+    NotPublic(String),
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Shared address space (carrier-grade NAT).
+        || (a == 100 && (64..128).contains(&b))
+        // IETF protocol assignments.
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking.
+        || (a == 198 && (18..20).contains(&b))
+        // Reserved.
+        || a >= 240)
+}
+
+/// Whether `ip` is routable on the public internet. IPv6 addresses that
+/// embed an IPv4 one (mapped, NAT64, 6to4) are judged by that address.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(v4);
+    }
+    let segments = ip.segments();
+    let embedded = |hi: u16, lo: u16| Ipv4Addr::from(((hi as u32) << 16) | lo as u32);
+    match segments {
+        // NAT64.
+        [0x64, 0xff9b, 0, 0, 0, 0, hi, lo] => return is_public_ipv4(embedded(hi, lo)),
+        // 6to4.
+        [0x2002, hi, lo, ..] => return is_public_ipv4(embedded(hi, lo)),
+        // Deprecated IPv4-compatible.
+        [0, 0, 0, 0, 0, 0, hi, lo] if !ip.is_loopback() && !ip.is_unspecified() => {
+            return is_public_ipv4(embedded(hi, lo))
+        }
+        _ => {}
+    }
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local.
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local.
+        || (segments[0] & 0xffc0) == 0xfe80
+        // Documentation.
+        || (segments[0] == 0x2001 && segments[1] == 0xdb8))
+}
+
+/// Parses `url`, requiring http(s) and, for IP literals, a public address.
+/// Hostnames are checked when `guarded_client` resolves them.
+pub fn check_url(url: &str) -> Result<Url, UrlGuardError> {
+    let parsed = Url::parse(url).map_err(|_| UrlGuardError::Invalid(url.to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(UrlGuardError::Scheme(parsed.scheme().to_string()));
+    }
+    // IP literals, including forms like `http://2130706433/`, come back
+    // normalized; IPv6 ones in brackets.
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| UrlGuardError::Invalid(url.to_string()))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_lowercase();
+    let public = match host.parse::<IpAddr>() {
+        Ok(ip) => is_public_ip(ip),
+        Err(_) => host != "localhost" && !host.ends_with(".localhost"),
+    };
+    if !public {
+        return Err(UrlGuardError::NotPublic(host));
+    }
+    Ok(parsed)
+}
+
+/// Resolves with the system resolver and keeps only public addresses,
+/// failing when none are left.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(Box::new(UrlGuardError::NotPublic(host)) as _);
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// HTTP client for customer-supplied URLs: public addresses only, no
+/// redirects. Check URLs with `check_url` before sending too, as IP
+/// literals skip the resolver.
+pub fn guarded_client(timeout: Duration) -> Client {
+    build_client(timeout, Policy::none())
+}
+
+/// `guarded_client`, following up to `max_redirects` redirects that pass
+/// `check_url`.
+pub fn guarded_client_with_redirects(timeout: Duration, max_redirects: usize) -> Client {
+    let policy = Policy::custom(move |attempt| {
+        if attempt.previous().len() > max_redirects {
+            attempt.error("too many redirects")
+        } else if let Err(e) = check_url(attempt.url().as_str()) {
+            attempt.error(e)
+        } else {
+            attempt.follow()
+        }
+    });
+    build_client(timeout, policy)
+}
+
+fn build_client(timeout: Duration, redirects: Policy) -> Client {
+    Client::builder()
+        .timeout(timeout)
+        .redirect(redirects)
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+        .expect("Failed to build HTTP client")
+}
@@ -0,0 +1,269 @@
+use super::infobip::{parse_delivery_reports, parse_inbound_messages, result_for};
+use super::signature::verify_basic_auth;
+use super::{failed, http_client};
+use crate::adapters::{BaseProviderAdapter, InboundMessage, SendResult, WebhookEvent};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// Viber accepts TTLs between 30 seconds and 14 days.
+const MIN_TTL: Duration = Duration::from_secs(30);
+const MAX_TTL: Duration = Duration::from_secs(14 * 24 * 3600);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ViberContent {
+    Text {
+        text: String,
+    },
+    /// An image, optionally with a caption.
+    Image {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ViberConfig {
+    /// Infobip account base URL; Viber Business Messages are only sold
+    /// through partners and this adapter speaks Infobip's Viber API.
+    pub base_url: String,
+    pub api_key: String,
+    /// Approved Viber business sender name, used when `from` is empty.
+    pub sender: String,
+    /// How long Viber keeps trying before the message expires, e.g. so an
+    /// SMS fallback can go out in time.
+    pub ttl: Duration,
+    /// Basic-auth credentials embedded in the delivery webhook URL, as for
+    /// `InfobipConfig`. Without them every webhook is rejected.
+    pub webhook_credentials: Option<(String, String)>,
+}
+
+impl ViberConfig {
+    pub fn new(base_url: &str, api_key: &str, sender: &str) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            api_key: api_key.to_string(),
+            sender: sender.to_string(),
+            ttl: Duration::from_secs(24 * 3600),
+            webhook_credentials: None,
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl.clamp(MIN_TTL, MAX_TTL);
+        self
+    }
+
+    pub fn with_webhook_credentials(mut self, username: &str, password: &str) -> Self {
+        self.webhook_credentials = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Reads `VIBER_SENDER`, `VIBER_TTL_SECS` and the Infobip account from
+    /// `VIBER_BASE_URL`/`VIBER_API_KEY`, falling back to `INFOBIP_BASE_URL`/
+    /// `INFOBIP_API_KEY`. Webhook credentials come from
+    /// `VIBER_WEBHOOK_USERNAME`/`VIBER_WEBHOOK_PASSWORD`.
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let base_url = var("VIBER_BASE_URL").or_else(|| var("INFOBIP_BASE_URL"))?;
+        let api_key = var("VIBER_API_KEY").or_else(|| var("INFOBIP_API_KEY"))?;
+        let mut config = Self::new(&base_url, &api_key, &var("VIBER_SENDER")?);
+        if let Some(secs) = var("VIBER_TTL_SECS").and_then(|v| v.parse().ok()) {
+            config = config.with_ttl(Duration::from_secs(secs));
+        }
+        if let (Some(user), Some(pass)) =
+            (var("VIBER_WEBHOOK_USERNAME"), var("VIBER_WEBHOOK_PASSWORD"))
+        {
+            config.webhook_credentials = Some((user, pass));
+        }
+        Some(config)
+    }
+}
+
+/// Viber Business Messages. `send_sms` sends a Viber text, so the adapter
+/// can sit in a `[Viber, Sms]` fallback chain (see
+/// `ProviderRegistry::send_with_fallback`); recipients without Viber come
+/// back undeliverable in the delivery report.
+pub struct ViberAdapter {
+    config: ViberConfig,
+    client: Client,
+}
+
+impl ViberAdapter {
+    pub fn new(config: ViberConfig) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+
+    fn message_json(
+        &self,
+        to: &str,
+        from: &str,
+        content: &ViberContent,
+        metadata: Option<&HashMap<String, Value>>,
+    ) -> Value {
+        let content = match content {
+            ViberContent::Text { text } => json!({ "type": "TEXT", "text": text }),
+            ViberContent::Image { url, text } => {
+                let mut image = json!({ "type": "IMAGE", "mediaUrl": url });
+                if let Some(text) = text {
+                    image["text"] = json!(text);
+                }
+                image
+            }
+        };
+        let mut message = json!({
+            "sender": if from.is_empty() { self.config.sender.as_str() } else { from },
+            "destinations": [{ "to": to.trim_start_matches('+') }],
+            "content": content,
+            "options": {
+                "validityPeriod": { "amount": self.config.ttl.as_secs(), "timeUnit": "SECONDS" },
+            },
+        });
+        if let Some(url) = metadata
+            .and_then(|m| m.get("webhook_url"))
+            .and_then(Value::as_str)
+        {
+            message["webhooks"] = json!({ "delivery": { "url": url } });
+        }
+        message
+    }
+
+    pub async fn send_viber(
+        &self,
+        to: &str,
+        from: &str,
+        content: ViberContent,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let message = self.message_json(to, from, &content, metadata.as_ref());
+        let response = self
+            .client
+            .post(format!(
+                "{}/viber/2/messages",
+                self.config.base_url.trim_end_matches('/')
+            ))
+            .header("Authorization", format!("App {}", self.config.api_key))
+            .json(&json!({ "messages": [message] }))
+            .send()
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Viber send failed: {}", e);
+                return failed(None, e.to_string(), None);
+            }
+        };
+
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            let err = &data["requestError"]["serviceException"];
+            let code = err["messageId"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| status.as_u16().to_string());
+            let message = err["text"].as_str().unwrap_or("Unknown error").to_string();
+            return failed(Some(code), message, Some(data));
+        }
+        match data["messages"].get(0) {
+            // Viber bills per message, not per segment.
+            Some(result) => result_for(result, 1),
+            None => failed(None, "Missing from Infobip response", Some(data)),
+        }
+    }
+}
+
+#[async_trait]
+impl BaseProviderAdapter for ViberAdapter {
+    fn name(&self) -> String {
+        "viber".to_string()
+    }
+
+    fn supports_sms(&self) -> bool {
+        false
+    }
+
+    fn supports_viber(&self) -> bool {
+        true
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let content = ViberContent::Text {
+            text: body.to_string(),
+        };
+        self.send_viber(to, from, content, metadata).await
+    }
+
+    /// Sends the first image with `text` as its caption; Viber messages
+    /// carry a single image.
+    async fn send_mms(
+        &self,
+        to: &str,
+        from: &str,
+        text: Option<&str>,
+        media_urls: Vec<String>,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let Some(url) = media_urls.into_iter().next() else {
+            return failed(None, "Viber image message needs a media URL", None);
+        };
+        let content = ViberContent::Image {
+            url,
+            text: text.map(str::to_string),
+        };
+        self.send_viber(to, from, content, metadata).await
+    }
+
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, _body: &[u8]) -> bool {
+        let Some((user, pass)) = &self.config.webhook_credentials else {
+            warn!("Viber webhook rejected: no webhook credentials configured");
+            return false;
+        };
+        verify_basic_auth(headers, user, pass)
+    }
+
+    /// Viber delivery reports use the Infobip report format.
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        parse_delivery_reports(body)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Viber webhook has no results".to_string())
+    }
+
+    /// Viber messages from users, in Infobip's inbound format.
+    async fn parse_inbound(&self, body: &[u8]) -> Result<InboundMessage, String> {
+        parse_inbound_messages(body)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Viber webhook has no results".to_string())
+    }
+
+    async fn health_check(&self) -> bool {
+        self.client
+            .get(format!(
+                "{}/account/1/balance",
+                self.config.base_url.trim_end_matches('/')
+            ))
+            .header("Authorization", format!("App {}", self.config.api_key))
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,331 @@
+use super::signature::{
+    jwt_is_fresh, verify_hmac_sha256, verify_hs256_jwt, within_tolerance, SignatureEncoding,
+};
+use super::{failed, header, http_client, webhook_fields};
+use crate::adapters::{
+    expired_result, remaining_validity, BaseProviderAdapter, InboundMessage, MessageStatus,
+    SendResult, WebhookEvent,
+};
+use crate::dlr;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use reqwest::Client;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+use tracing::{error, warn};
+
+pub const VONAGE_API_BASE: &str = "https://rest.nexmo.com";
+
+/// Bounds Vonage accepts for `ttl`, in milliseconds.
+const MIN_TTL_MS: u64 = 20_000;
+const MAX_TTL_MS: u64 = 604_800_000;
+
+/// Allowed clock skew on a signed callback's `timestamp` or JWT `iat`.
+const SIGNATURE_TOLERANCE: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone)]
+pub struct VonageConfig {
+    pub api_key: String,
+    pub api_secret: String,
+    /// Secret for signed webhooks. Without it every webhook is rejected.
+    pub signature_secret: Option<String>,
+    pub base_url: String,
+}
+
+impl VonageConfig {
+    pub fn new(api_key: &str, api_secret: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            api_secret: api_secret.to_string(),
+            signature_secret: None,
+            base_url: VONAGE_API_BASE.to_string(),
+        }
+    }
+
+    pub fn with_signature_secret(mut self, secret: &str) -> Self {
+        self.signature_secret = Some(secret.to_string());
+        self
+    }
+
+    /// Reads `VONAGE_API_KEY`, `VONAGE_API_SECRET` and `VONAGE_SIGNATURE_SECRET`;
+    /// `None` unless both credentials are set.
+    pub fn from_env() -> Option<Self> {
+        let key = env::var("VONAGE_API_KEY").ok().filter(|v| !v.is_empty())?;
+        let secret = env::var("VONAGE_API_SECRET")
+            .ok()
+            .filter(|v| !v.is_empty())?;
+        let mut config = Self::new(&key, &secret);
+        config.signature_secret = env::var("VONAGE_SIGNATURE_SECRET")
+            .ok()
+            .filter(|v| !v.is_empty());
+        Some(config)
+    }
+}
+
+/// Vonage (Nexmo) SMS API.
+pub struct VonageAdapter {
+    config: VonageConfig,
+    client: Client,
+}
+
+impl VonageAdapter {
+    pub fn new(config: VonageConfig) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+
+    /// Signed webhooks carry `Authorization: Bearer <JWT>` (HS256 with the
+    /// signature secret) whose `payload_hash` claim is the body's SHA-256.
+    /// `iat` must be within five minutes of now.
+    fn verify_jwt(&self, secret: &str, token: &str, body: &[u8]) -> bool {
+        let Some(claims) = verify_hs256_jwt(secret, token) else {
+            return false;
+        };
+        if !jwt_is_fresh(&claims, SIGNATURE_TOLERANCE) {
+            return false;
+        }
+        match claims["payload_hash"].as_str() {
+            Some(hash) => hash.eq_ignore_ascii_case(&hex::encode(Sha256::digest(body))),
+            // GET callbacks have no body to bind the token to.
+            None => body.is_empty(),
+        }
+    }
+
+    /// SMS API signed callbacks carry a `sig` parameter: HMAC-SHA256 over
+    /// `&key=value` pairs sorted by key, with `&` and `=` in values replaced
+    /// by `_`. The signed `timestamp` must be within five minutes of now.
+    fn verify_sig(&self, secret: &str, params: &[(String, String)]) -> bool {
+        let field = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let Some(sig) = field("sig") else {
+            return false;
+        };
+        if !field("timestamp")
+            .and_then(|ts| ts.parse().ok())
+            .is_some_and(|ts| within_tolerance(ts, SIGNATURE_TOLERANCE))
+        {
+            return false;
+        }
+        let mut sorted: Vec<&(String, String)> =
+            params.iter().filter(|(key, _)| key != "sig").collect();
+        sorted.sort();
+        let payload: String = sorted
+            .into_iter()
+            .map(|(key, value)| format!("&{}={}", key, value.replace(['&', '='], "_")))
+            .collect();
+        verify_hmac_sha256(
+            secret.as_bytes(),
+            payload.as_bytes(),
+            sig,
+            SignatureEncoding::Hex,
+        )
+    }
+}
+
+/// Maps the `status` of a submitted message (`"0"` is success).
+pub fn map_error_code(code: &str) -> MessageStatus {
+    match code {
+        "0" => MessageStatus::Sent,
+        // Throttled, internal error, quota and bind limits: worth retrying.
+        "1" | "5" | "9" | "10" => MessageStatus::Failed,
+        _ => MessageStatus::Rejected,
+    }
+}
+
+/// Maps a delivery receipt `status`.
+pub fn map_status(status: &str) -> MessageStatus {
+    match status.to_lowercase().as_str() {
+        "delivered" => MessageStatus::Delivered,
+        "accepted" => MessageStatus::Sent,
+        "expired" | "failed" => MessageStatus::Failed,
+        "rejected" => MessageStatus::Rejected,
+        _ => MessageStatus::Pending,
+    }
+}
+
+#[async_trait]
+impl BaseProviderAdapter for VonageAdapter {
+    fn name(&self) -> String {
+        "vonage".to_string()
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        let validity = remaining_validity(metadata.as_ref());
+        if validity == Some(Duration::ZERO) {
+            return expired_result();
+        }
+        let mut params = vec![
+            ("api_key", self.config.api_key.clone()),
+            ("api_secret", self.config.api_secret.clone()),
+            ("to", to.trim_start_matches('+').to_string()),
+            ("from", from.trim_start_matches('+').to_string()),
+            ("text", body.to_string()),
+            (
+                "type",
+                if body.is_ascii() { "text" } else { "unicode" }.to_string(),
+            ),
+        ];
+        if let Some(url) = metadata
+            .as_ref()
+            .and_then(|m| m.get("webhook_url"))
+            .and_then(Value::as_str)
+        {
+            params.push(("callback", url.to_string()));
+        }
+        if let Some(validity) = validity {
+            let ttl = (validity.as_millis() as u64).clamp(MIN_TTL_MS, MAX_TTL_MS);
+            params.push(("ttl", ttl.to_string()));
+        }
+
+        let response = self
+            .client
+            .post(format!(
+                "{}/sms/json",
+                self.config.base_url.trim_end_matches('/')
+            ))
+            .form(&params)
+            .send()
+            .await;
+        let data: Value = match response {
+            Ok(response) => response.json().await.unwrap_or(Value::Null),
+            Err(e) => {
+                error!("Vonage send failed: {}", e);
+                return failed(None, e.to_string(), None);
+            }
+        };
+
+        // One entry per segment; the first failure decides the result.
+        let messages = data["messages"].as_array().cloned().unwrap_or_default();
+        let failure = messages.iter().find(|m| m["status"].as_str() != Some("0"));
+        match (messages.first(), failure) {
+            (Some(first), None) => SendResult {
+                success: true,
+                provider_message_id: first["message-id"].as_str().map(str::to_string),
+                status: MessageStatus::Sent,
+                cost: Some(
+                    messages
+                        .iter()
+                        .filter_map(|m| m["message-price"].as_str()?.parse::<f64>().ok())
+                        .sum(),
+                ),
+                segments: messages.len() as u32,
+                raw_response: Some(data),
+                ..Default::default()
+            },
+            (_, failure) => {
+                let code = failure
+                    .and_then(|m| m["status"].as_str())
+                    .unwrap_or("unknown")
+                    .to_string();
+                let message = failure
+                    .and_then(|m| m["error-text"].as_str())
+                    .unwrap_or("Unknown error")
+                    .to_string();
+                SendResult {
+                    status: map_error_code(&code),
+                    ..failed(Some(code), message, Some(data))
+                }
+            }
+        }
+    }
+
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+        let Some(secret) = self.config.signature_secret.as_deref() else {
+            warn!("Vonage webhook rejected: no signature secret configured");
+            return false;
+        };
+        if let Some(token) =
+            header(headers, "Authorization").and_then(|v| v.strip_prefix("Bearer "))
+        {
+            return self.verify_jwt(secret, token.trim(), body);
+        }
+        let (fields, _) = webhook_fields(body);
+        self.verify_sig(secret, &fields)
+    }
+
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        let (fields, raw) = webhook_fields(body);
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        let provider_message_id = field("messageId")
+            .or_else(|| field("message-id"))
+            .or_else(|| field("message_uuid"))
+            .ok_or_else(|| "Vonage DLR missing messageId".to_string())?;
+        let error_code = field("err-code").filter(|code| code != "0");
+
+        Ok(WebhookEvent {
+            provider_message_id,
+            status: map_status(&field("status").unwrap_or_default()),
+            timestamp: field("message-timestamp").and_then(|ts| {
+                NaiveDateTime::parse_from_str(&ts, "%Y-%m-%d %H:%M:%S")
+                    .ok()
+                    .map(|dt| dt.and_utc().timestamp() as f64)
+            }),
+            reason: dlr::normalize("vonage", error_code.as_deref(), None),
+            error_code,
+            error_message: None,
+            raw_payload: Some(raw),
+        })
+    }
+
+    /// The SMS API's inbound webhook (`msisdn`, `to`, `text`), sent as a
+    /// query string, form or JSON.
+    async fn parse_inbound(&self, body: &[u8]) -> Result<InboundMessage, String> {
+        let (fields, raw) = webhook_fields(body);
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        let from = field("msisdn").ok_or_else(|| "Vonage inbound missing msisdn".to_string())?;
+        Ok(InboundMessage {
+            provider_message_id: field("messageId"),
+            from,
+            to: field("to").unwrap_or_default(),
+            body: field("text").unwrap_or_default(),
+            media: Vec::new(),
+            timestamp: field("message-timestamp").and_then(|ts| {
+                NaiveDateTime::parse_from_str(&ts, "%Y-%m-%d %H:%M:%S")
+                    .ok()
+                    .map(|dt| dt.and_utc().timestamp() as f64)
+            }),
+            raw_payload: Some(raw),
+        })
+    }
+
+    async fn health_check(&self) -> bool {
+        self.client
+            .get(format!(
+                "{}/account/get-balance",
+                self.config.base_url.trim_end_matches('/')
+            ))
+            .query(&[
+                ("api_key", &self.config.api_key),
+                ("api_secret", &self.config.api_secret),
+            ])
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,163 @@
+//! Retry budgets, so that retries can't multiply an outage's load.
+//!
+//! Each successful call deposits `ratio` tokens and each retry withdraws
+//! one, so while a target is down retries are capped at roughly `ratio`
+//! of the traffic that was succeeding before. A trickle of
+//! `min_retries_per_sec` keeps retries possible for targets with little
+//! traffic.
+
+use crate::metrics::GLOBAL_METRICS;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+#[derive(Debug, Clone)]
+pub struct RetryBudgetConfig {
+    /// Retries earned per successful call.
+    pub ratio: f64,
+    pub min_retries_per_sec: f64,
+    /// Most tokens saved up, bounding the burst of retries after a long
+    /// healthy stretch.
+    pub max_tokens: f64,
+}
+
+impl Default for RetryBudgetConfig {
+    fn default() -> Self {
+        Self {
+            ratio: 0.2,
+            min_retries_per_sec: 1.0,
+            max_tokens: 100.0,
+        }
+    }
+}
+
+impl RetryBudgetConfig {
+    pub fn with_ratio(mut self, ratio: f64) -> Self {
+        self.ratio = ratio.max(0.0);
+        self
+    }
+
+    pub fn with_min_retries_per_sec(mut self, rate: f64) -> Self {
+        self.min_retries_per_sec = rate.max(0.0);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, tokens: f64) -> Self {
+        self.max_tokens = tokens.max(1.0);
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// The budget for one target; share it between every retry policy
+/// calling that target.
+#[derive(Debug)]
+pub struct RetryBudget {
+    target: String,
+    config: RetryBudgetConfig,
+    bucket: Mutex<Bucket>,
+}
+
+impl RetryBudget {
+    /// Starts with up to ten tokens, so retries work before the first
+    /// successes come in.
+    pub fn new(target: &str, config: RetryBudgetConfig) -> Self {
+        Self {
+            target: target.to_string(),
+            bucket: Mutex::new(Bucket {
+                tokens: config.max_tokens.min(10.0),
+                refilled_at: Instant::now(),
+            }),
+            config,
+        }
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn with_bucket<R>(&self, f: impl FnOnce(&mut Bucket) -> R) -> R {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens =
+            (bucket.tokens + elapsed * self.config.min_retries_per_sec).min(self.config.max_tokens);
+        bucket.refilled_at = now;
+        let result = f(&mut bucket);
+        GLOBAL_METRICS.set_gauge(
+            "retry_budget_tokens",
+            bucket.tokens,
+            Some(HashMap::from([("target".to_string(), self.target.clone())])),
+        );
+        result
+    }
+
+    /// Records a successful call.
+    pub fn deposit(&self) {
+        self.with_bucket(|bucket| {
+            bucket.tokens = (bucket.tokens + self.config.ratio).min(self.config.max_tokens)
+        });
+    }
+
+    /// Takes a token for one retry, or refuses it if the budget is spent.
+    pub fn try_withdraw(&self) -> bool {
+        let allowed = self.with_bucket(|bucket| {
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                true
+            } else {
+                false
+            }
+        });
+        if !allowed {
+            GLOBAL_METRICS.increment(
+                "retry_budget_exhausted",
+                1,
+                Some(HashMap::from([("target".to_string(), self.target.clone())])),
+            );
+        }
+        allowed
+    }
+
+    pub fn tokens(&self) -> f64 {
+        self.with_bucket(|bucket| bucket.tokens)
+    }
+}
+
+/// Budgets by target, created on first use.
+#[derive(Debug, Default)]
+pub struct RetryBudgets {
+    config: RetryBudgetConfig,
+    budgets: RwLock<HashMap<String, Arc<RetryBudget>>>,
+}
+
+impl RetryBudgets {
+    pub fn new(config: RetryBudgetConfig) -> Self {
+        Self {
+            config,
+            budgets: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, target: &str) -> Arc<RetryBudget> {
+        if let Some(budget) = self
+            .budgets
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(target)
+        {
+            return budget.clone();
+        }
+        self.budgets
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(target.to_string())
+            .or_insert_with(|| Arc::new(RetryBudget::new(target, self.config.clone())))
+            .clone()
+    }
+}
@@ -0,0 +1,213 @@
+use super::http::provider_throttle;
+use crate::adapters::SendResult;
+use crate::circuit_breaker::CircuitError;
+use crate::database::{is_retryable_conflict, DatabaseError};
+use crate::dlr::{self, DlrReason};
+use crate::messaging::QueueError;
+use crate::providers::smpp::SmppError;
+use crate::providers::throttle::ThrottleError;
+use crate::whatsapp::{WhatsAppError, WhatsAppErrorKind};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+/// Whether an error is worth another attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryClass {
+    /// Transient; retry after the usual backoff.
+    Retryable,
+    /// Permanent, e.g. an invalid destination number; retrying only
+    /// repeats the failure.
+    NotRetryable,
+    /// The target said when to come back.
+    RetryAfter(Duration),
+}
+
+impl RetryClass {
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, RetryClass::NotRetryable)
+    }
+}
+
+pub trait ClassifyRetry {
+    fn retry_class(&self) -> RetryClass;
+}
+
+impl<E: ClassifyRetry> ClassifyRetry for CircuitError<E> {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            CircuitError::Open(open) => RetryClass::RetryAfter(open.retry_after),
+            CircuitError::Inner(e) => e.retry_class(),
+        }
+    }
+}
+
+/// 408, 429 and 5xx other than 501 and 505 are transient; anything else
+/// is the request's fault.
+pub fn classify_status(status: StatusCode) -> RetryClass {
+    match status {
+        StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS => RetryClass::Retryable,
+        StatusCode::NOT_IMPLEMENTED | StatusCode::HTTP_VERSION_NOT_SUPPORTED => {
+            RetryClass::NotRetryable
+        }
+        status if status.is_server_error() => RetryClass::Retryable,
+        _ => RetryClass::NotRetryable,
+    }
+}
+
+impl ClassifyRetry for reqwest::Error {
+    fn retry_class(&self) -> RetryClass {
+        if let Some(status) = self.status() {
+            return classify_status(status);
+        }
+        if self.is_timeout() || self.is_connect() || self.is_request() || self.is_body() {
+            RetryClass::Retryable
+        } else {
+            RetryClass::NotRetryable
+        }
+    }
+}
+
+impl ClassifyRetry for redis::RedisError {
+    fn retry_class(&self) -> RetryClass {
+        use redis::ErrorKind;
+
+        if self.is_timeout()
+            || self.is_connection_dropped()
+            || self.is_connection_refusal()
+            || self.is_io_error()
+        {
+            return RetryClass::Retryable;
+        }
+        match self.kind() {
+            ErrorKind::TryAgain
+            | ErrorKind::BusyLoadingError
+            | ErrorKind::ClusterDown
+            | ErrorKind::MasterDown
+            | ErrorKind::ReadOnly
+            | ErrorKind::ClusterConnectionNotFound => RetryClass::Retryable,
+            _ => RetryClass::NotRetryable,
+        }
+    }
+}
+
+/// SQLSTATEs worth retrying besides serialization failures and deadlocks:
+/// connection exceptions (08), too many connections, lock timeouts and
+/// server shutdown.
+fn is_transient_sqlstate(code: &str) -> bool {
+    code.starts_with("08") || matches!(code, "53300" | "55P03" | "57P01" | "57P02" | "57P03")
+}
+
+impl ClassifyRetry for sqlx::Error {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::WorkerCrashed => {
+                RetryClass::Retryable
+            }
+            sqlx::Error::Database(_) if is_retryable_conflict(self) => RetryClass::Retryable,
+            sqlx::Error::Database(e) => match e.code() {
+                Some(code) if is_transient_sqlstate(&code) => RetryClass::Retryable,
+                _ => RetryClass::NotRetryable,
+            },
+            _ => RetryClass::NotRetryable,
+        }
+    }
+}
+
+impl ClassifyRetry for DatabaseError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            DatabaseError::Sqlx(e) => e.retry_class(),
+            _ => RetryClass::NotRetryable,
+        }
+    }
+}
+
+impl ClassifyRetry for QueueError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            QueueError::Connection(_) | QueueError::Backend(_) => RetryClass::Retryable,
+            QueueError::InvalidMessage(_) => RetryClass::NotRetryable,
+        }
+    }
+}
+
+impl ClassifyRetry for WhatsAppError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            WhatsAppError::Request(_) => RetryClass::Retryable,
+            WhatsAppError::Api { code, .. } if self.kind() == WhatsAppErrorKind::RateLimited => {
+                provider_throttle("whatsapp", &code.to_string())
+                    .map_or(RetryClass::Retryable, RetryClass::RetryAfter)
+            }
+            e if e.is_retryable() => RetryClass::Retryable,
+            _ => RetryClass::NotRetryable,
+        }
+    }
+}
+
+impl ClassifyRetry for SmppError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            SmppError::Io(_) | SmppError::Timeout | SmppError::Closed => RetryClass::Retryable,
+            SmppError::BindFailed(_) | SmppError::UnexpectedPdu(_) => RetryClass::NotRetryable,
+        }
+    }
+}
+
+impl ClassifyRetry for ThrottleError {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            ThrottleError::Exceeded { tps, .. } if *tps > 0.0 => {
+                RetryClass::RetryAfter(Duration::from_secs_f64(1.0 / tps))
+            }
+            ThrottleError::Exceeded { .. } => RetryClass::Retryable,
+        }
+    }
+}
+
+impl ClassifyRetry for DlrReason {
+    fn retry_class(&self) -> RetryClass {
+        if self.is_retryable() {
+            RetryClass::Retryable
+        } else {
+            RetryClass::NotRetryable
+        }
+    }
+}
+
+/// Classifies a failed send through `provider`, reading its error code
+/// with `dlr::normalize`. Recipient problems (invalid, unknown or opted-out
+/// numbers) and expired messages are permanent, and the provider's
+/// throttle codes ask for a `provider_throttle` wait; anything else,
+/// including errors without a code, may go through on a retry or another
+/// provider.
+pub fn classify_send(provider: &str, result: &SendResult) -> RetryClass {
+    if result.success {
+        return RetryClass::NotRetryable;
+    }
+    match result.error_code.as_deref() {
+        Some("expired") => return RetryClass::NotRetryable,
+        Some("throttled") => return RetryClass::Retryable,
+        Some(code) => {
+            if let Some(wait) = provider_throttle(provider, code) {
+                return RetryClass::RetryAfter(wait);
+            }
+        }
+        None => {}
+    }
+    match dlr::normalize(
+        provider,
+        result.error_code.as_deref(),
+        result.error_message.as_deref(),
+    ) {
+        Some(reason) if reason.is_permanent() => RetryClass::NotRetryable,
+        _ => RetryClass::Retryable,
+    }
+}
+
+/// `classify_send` without provider-specific codes.
+impl ClassifyRetry for SendResult {
+    fn retry_class(&self) -> RetryClass {
+        classify_send("", self)
+    }
+}
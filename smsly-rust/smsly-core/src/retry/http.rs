@@ -0,0 +1,187 @@
+//! Throttle awareness for HTTP calls and provider sends.
+
+use super::classify::{classify_status, ClassifyRetry, RetryClass};
+use super::{retry_with, RetryPolicy};
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderName, RETRY_AFTER};
+use reqwest::{Response, StatusCode};
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+/// Throttle error codes in `SendResult::error_code` that don't say how
+/// long to wait, with how long to wait for each.
+const PROVIDER_THROTTLES: &[(&str, &str, Duration)] = &[
+    ("twilio", "20429", Duration::from_secs(1)),
+    ("vonage", "1", Duration::from_secs(1)),
+    ("whatsapp", "4", Duration::from_secs(60)),
+    ("whatsapp", "80007", Duration::from_secs(60)),
+    ("whatsapp", "130429", Duration::from_secs(1)),
+    // Meta allows about one message every six seconds to the same user.
+    ("whatsapp", "131056", Duration::from_secs(6)),
+];
+
+/// How long `provider` asks to wait after failing a send with
+/// `error_code`, if that code is a throttle.
+pub fn provider_throttle(provider: &str, error_code: &str) -> Option<Duration> {
+    PROVIDER_THROTTLES
+        .iter()
+        .find(|(name, code, _)| *name == provider && *code == error_code)
+        .map(|(_, _, wait)| *wait)
+}
+
+/// Parses a `Retry-After` value: delay seconds, or an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return (secs.is_finite() && secs >= 0.0).then(|| Duration::from_secs_f64(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// The wait a response asks for in `Retry-After`, or failing that the
+/// `RateLimit-Reset` delay some APIs send instead.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let reset = HeaderName::from_static("ratelimit-reset");
+    [RETRY_AFTER, reset].iter().find_map(|name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after)
+    })
+}
+
+/// `classify_status`, honouring the wait a 429 or 503 asks for.
+pub fn classify_response(response: &Response) -> RetryClass {
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+        if let Some(wait) = retry_after(response.headers()) {
+            return RetryClass::RetryAfter(wait);
+        }
+    }
+    classify_status(status)
+}
+
+enum HttpFailure {
+    Transport(reqwest::Error),
+    Status(Response),
+}
+
+impl fmt::Display for HttpFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpFailure::Transport(e) => write!(f, "{}", e),
+            HttpFailure::Status(response) => {
+                write!(f, "{} from {}", response.status(), response.url())
+            }
+        }
+    }
+}
+
+impl ClassifyRetry for HttpFailure {
+    fn retry_class(&self) -> RetryClass {
+        match self {
+            HttpFailure::Transport(e) => e.retry_class(),
+            HttpFailure::Status(response) => classify_response(response),
+        }
+    }
+}
+
+/// Sends the request `send` builds until it gets a response that isn't
+/// worth retrying, waiting as `Retry-After` asks on 429 and 503. Once
+/// `policy` gives up the last response is returned as is, so callers
+/// still see its status and body.
+///
+/// ```no_run
+/// # use smsly_core::retry::{retry_http, RetryPolicy};
+/// # async fn example() -> Result<(), reqwest::Error> {
+/// # let (policy, client) = (RetryPolicy::new("callbacks.post"), reqwest::Client::new());
+/// # let (url, body) = ("https://example.com/hook", serde_json::json!({}));
+/// let response = retry_http(&policy, || client.post(url).json(&body).send()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn retry_http<F, Fut>(
+    policy: &RetryPolicy,
+    mut send: F,
+) -> Result<Response, reqwest::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Response, reqwest::Error>>,
+{
+    let result = retry_with(
+        policy,
+        || {
+            let sent = send();
+            async move {
+                match sent.await {
+                    Ok(response) if classify_response(&response).is_retryable() => {
+                        Err(HttpFailure::Status(response))
+                    }
+                    Ok(response) => Ok(response),
+                    Err(e) => Err(HttpFailure::Transport(e)),
+                }
+            }
+        },
+        HttpFailure::retry_class,
+    )
+    .await;
+    match result {
+        Ok(response) | Err(HttpFailure::Status(response)) => Ok(response),
+        Err(HttpFailure::Transport(e)) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn parses_delay_seconds() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after(" 1.5 "),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(parse_retry_after("NaN"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn parses_http_dates() {
+        let at = (Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        let wait = parse_retry_after(&at).unwrap();
+        assert!(wait > Duration::from_secs(25) && wait <= Duration::from_secs(30));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn falls_back_to_ratelimit_reset() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert("ratelimit-reset", HeaderValue::from_static("7"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("2"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn knows_provider_throttle_codes() {
+        assert_eq!(
+            provider_throttle("whatsapp", "131056"),
+            Some(Duration::from_secs(6))
+        );
+        assert_eq!(provider_throttle("twilio", "21211"), None);
+        assert_eq!(provider_throttle("vonage", "20429"), None);
+    }
+}
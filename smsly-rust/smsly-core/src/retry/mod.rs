@@ -0,0 +1,433 @@
+//! Retrying fallible async operations.
+//!
+//! `retry_async` reruns an operation with exponentially growing delays
+//! until it succeeds, `max_attempts` is reached or the next delay would
+//! overrun `max_elapsed`. Each delay is drawn uniformly between zero and
+//! the exponential step ("full jitter"), so callers failing together
+//! don't retry together.
+//!
+//! `retry_classified` retries only errors whose `ClassifyRetry` says so,
+//! waiting as long as the target asked when it did, up to
+//! `max_retry_after`; `retry_http` does the same for HTTP responses with
+//! `Retry-After`. Each such throttle is counted in `retry_throttled` and
+//! its wait observed in `retry_after_seconds`.
+//!
+//! A policy given a `RetryBudget` only retries while the target's budget
+//! allows, so retries stay a fraction of traffic during an outage.
+//!
+//! Every attempt is counted in `retry_attempts`, labelled with the
+//! policy's `operation` and the attempt's `outcome`: `success`, `retried`,
+//! `gave_up` or `over_budget`.
+//!
+//! ```
+//! # use smsly_core::retry::{retry_async, RetryPolicy};
+//! # struct Billing;
+//! # impl Billing {
+//! #     async fn charge(&self, _account: &str, _amount: u64) -> Result<String, String> {
+//! #         Ok("receipt".to_string())
+//! #     }
+//! # }
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), String> {
+//! # let (billing, account, amount) = (Billing, "acct-1", 100);
+//! let policy = RetryPolicy::new("billing.charge").with_max_attempts(4);
+//! let receipt = retry_async(&policy, || billing.charge(&account, amount)).await?;
+//! # assert_eq!(receipt, "receipt");
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod budget;
+pub mod classify;
+pub mod http;
+
+pub use budget::{RetryBudget, RetryBudgetConfig, RetryBudgets};
+pub use classify::{classify_send, classify_status, ClassifyRetry, RetryClass};
+pub use http::{classify_response, parse_retry_after, provider_throttle, retry_after, retry_http};
+
+use crate::metrics::GLOBAL_METRICS;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Names the operation in logs and metrics.
+    pub operation: String,
+    /// Including the first.
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    /// Gives up rather than sleep past this since the first attempt.
+    pub max_elapsed: Option<Duration>,
+    /// Draw each delay from zero up to the exponential step; without it
+    /// the step is used as is.
+    pub jitter: bool,
+    /// Longest wait honoured when a target says when to retry.
+    pub max_retry_after: Duration,
+    /// Shared with other policies calling the same target.
+    pub budget: Option<Arc<RetryBudget>>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            operation: "default".to_string(),
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+            max_elapsed: Some(Duration::from_secs(30)),
+            jitter: true,
+            max_retry_after: Duration::from_secs(60),
+            budget: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(operation: &str) -> Self {
+        Self {
+            operation: operation.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// The delay before the first retry, growing by `multiplier` per retry
+    /// up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration, multiplier: f64) -> Self {
+        self.initial_delay = initial;
+        self.max_delay = max.max(initial);
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    pub fn with_max_elapsed(mut self, max_elapsed: Option<Duration>) -> Self {
+        self.max_elapsed = max_elapsed;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_max_retry_after(mut self, max: Duration) -> Self {
+        self.max_retry_after = max;
+        self
+    }
+
+    pub fn with_budget(mut self, budget: Arc<RetryBudget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// The exponential step before retry number `retry`, counting from 1.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .powi(retry.saturating_sub(1).min(i32::MAX as u32) as i32);
+        let step = self.initial_delay.as_secs_f64() * factor;
+        if !step.is_finite() || step >= self.max_delay.as_secs_f64() {
+            self.max_delay
+        } else {
+            Duration::from_secs_f64(step)
+        }
+    }
+
+    /// The delay to sleep before retry number `retry`.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let step = self.backoff(retry);
+        if self.jitter && !step.is_zero() {
+            rand::thread_rng().gen_range(Duration::ZERO..=step)
+        } else {
+            step
+        }
+    }
+}
+
+fn record_throttle(operation: &str, wait: Duration) {
+    let labels = HashMap::from([("operation".to_string(), operation.to_string())]);
+    GLOBAL_METRICS.increment("retry_throttled", 1, Some(labels.clone()));
+    GLOBAL_METRICS.observe("retry_after_seconds", wait.as_secs_f64(), Some(labels));
+}
+
+fn record_attempt(operation: &str, outcome: &str) {
+    GLOBAL_METRICS.increment(
+        "retry_attempts",
+        1,
+        Some(HashMap::from([
+            ("operation".to_string(), operation.to_string()),
+            ("outcome".to_string(), outcome.to_string()),
+        ])),
+    );
+}
+
+/// Runs `op` until it succeeds or `policy` gives up, returning the last
+/// error. Every error is retried; see `retry_async_if`.
+pub async fn retry_async<T, E, F, Fut>(policy: &RetryPolicy, op: F) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    retry_with(policy, op, |_| RetryClass::Retryable).await
+}
+
+/// `retry_async`, retrying per the error's `ClassifyRetry`. A
+/// `RetryAfter` replaces the backoff, up to `max_retry_after`.
+pub async fn retry_classified<T, E, F, Fut>(policy: &RetryPolicy, op: F) -> Result<T, E>
+where
+    E: Display + ClassifyRetry,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    retry_with(policy, op, E::retry_class).await
+}
+
+/// `retry_async`, retrying only errors for which `is_retryable` holds.
+pub async fn retry_async_if<T, E, F, Fut, P>(
+    policy: &RetryPolicy,
+    op: F,
+    is_retryable: P,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    P: Fn(&E) -> bool,
+{
+    retry_with(policy, op, |e| {
+        if is_retryable(e) {
+            RetryClass::Retryable
+        } else {
+            RetryClass::NotRetryable
+        }
+    })
+    .await
+}
+
+/// `retry_async`, classifying each error with `classify`.
+pub async fn retry_with<T, E, F, Fut, C>(
+    policy: &RetryPolicy,
+    mut op: F,
+    classify: C,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    C: Fn(&E) -> RetryClass,
+{
+    let start = Instant::now();
+    let mut attempt = 1;
+    loop {
+        let error = match op().await {
+            Ok(value) => {
+                if let Some(budget) = &policy.budget {
+                    budget.deposit();
+                }
+                record_attempt(&policy.operation, "success");
+                return Ok(value);
+            }
+            Err(e) => e,
+        };
+        let class = classify(&error);
+        let delay = match class {
+            RetryClass::RetryAfter(wait) => {
+                record_throttle(&policy.operation, wait);
+                wait.min(policy.max_retry_after)
+            }
+            _ => policy.delay(attempt),
+        };
+        let out_of_time = policy
+            .max_elapsed
+            .is_some_and(|max| start.elapsed() + delay > max);
+        if attempt >= policy.max_attempts || out_of_time || !class.is_retryable() {
+            record_attempt(&policy.operation, "gave_up");
+            debug!(
+                "{} failed on attempt {}/{}, giving up: {}",
+                policy.operation, attempt, policy.max_attempts, error
+            );
+            return Err(error);
+        }
+        if let Some(budget) = policy.budget.as_ref().filter(|b| !b.try_withdraw()) {
+            record_attempt(&policy.operation, "over_budget");
+            warn!(
+                "{} failed on attempt {}, not retrying: {} retry budget spent: {}",
+                policy.operation,
+                attempt,
+                budget.target(),
+                error
+            );
+            return Err(error);
+        }
+        record_attempt(&policy.operation, "retried");
+        warn!(
+            "{} failed on attempt {}/{}, retrying in {:?}: {}",
+            policy.operation, attempt, policy.max_attempts, delay, error
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy::new("test").with_backoff(
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+            2.0,
+        )
+    }
+
+    /// Fails `failures` times, then succeeds, counting calls.
+    async fn flaky(calls: &AtomicU32, failures: u32) -> Result<u32, String> {
+        let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if call <= failures {
+            Err(format!("failure {}", call))
+        } else {
+            Ok(call)
+        }
+    }
+
+    #[test]
+    fn backoff_grows_then_caps() {
+        let policy = policy();
+        let ms = Duration::from_millis;
+        assert_eq!(policy.backoff(0), ms(100));
+        assert_eq!(policy.backoff(1), ms(100));
+        assert_eq!(policy.backoff(2), ms(200));
+        assert_eq!(policy.backoff(3), ms(400));
+        assert_eq!(policy.backoff(4), ms(800));
+        assert_eq!(policy.backoff(5), ms(1000));
+        assert_eq!(policy.backoff(64), ms(1000));
+        assert_eq!(policy.backoff(u32::MAX), ms(1000));
+    }
+
+    #[test]
+    fn backoff_builder_keeps_bounds_sane() {
+        let policy = RetryPolicy::new("test").with_backoff(
+            Duration::from_secs(1),
+            Duration::from_millis(10),
+            0.5,
+        );
+        assert_eq!(policy.max_delay, Duration::from_secs(1));
+        assert_eq!(policy.multiplier, 1.0);
+        assert_eq!(policy.backoff(10), Duration::from_secs(1));
+        assert_eq!(
+            RetryPolicy::new("test").with_max_attempts(0).max_attempts,
+            1
+        );
+    }
+
+    #[test]
+    fn full_jitter_stays_within_the_step() {
+        let policy = policy();
+        for retry in 1..=6 {
+            let step = policy.backoff(retry);
+            let delays: Vec<Duration> = (0..200).map(|_| policy.delay(retry)).collect();
+            assert!(delays.iter().all(|d| *d <= step));
+            assert!(delays.iter().any(|d| *d < step / 2));
+        }
+        let fixed = policy.with_jitter(false);
+        assert_eq!(fixed.delay(3), fixed.backoff(3));
+    }
+
+    #[tokio::test]
+    async fn retries_until_success() {
+        let policy = policy()
+            .with_backoff(Duration::ZERO, Duration::ZERO, 2.0)
+            .with_max_attempts(5);
+        let calls = AtomicU32::new(0);
+        let result = retry_async(&policy, || flaky(&calls, 2)).await;
+        assert_eq!(result, Ok(3));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts_with_the_last_error() {
+        let policy = policy()
+            .with_backoff(Duration::ZERO, Duration::ZERO, 2.0)
+            .with_max_attempts(4);
+        let calls = AtomicU32::new(0);
+        let result = retry_async(&policy, || flaky(&calls, u32::MAX)).await;
+        assert_eq!(result, Err("failure 4".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_permanent_errors() {
+        let calls = AtomicU32::new(0);
+        let result = retry_async_if(&policy(), || flaky(&calls, u32::MAX), |_| false).await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_rather_than_sleep_past_max_elapsed() {
+        let policy = policy()
+            .with_jitter(false)
+            .with_max_elapsed(Some(Duration::from_millis(20)));
+        let calls = AtomicU32::new(0);
+        let start = Instant::now();
+        assert!(retry_async(&policy, || flaky(&calls, u32::MAX))
+            .await
+            .is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_capped_by_max_retry_after() {
+        let policy = policy()
+            .with_max_attempts(2)
+            .with_max_retry_after(Duration::from_millis(1));
+        let calls = AtomicU32::new(0);
+        let start = Instant::now();
+        let result = retry_with(
+            &policy,
+            || flaky(&calls, 1),
+            |_| RetryClass::RetryAfter(Duration::from_secs(60)),
+        )
+        .await;
+        assert_eq!(result, Ok(2));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn spent_budget_stops_retries() {
+        let budget = Arc::new(RetryBudget::new(
+            "test",
+            RetryBudgetConfig::default()
+                .with_ratio(0.0)
+                .with_min_retries_per_sec(0.0)
+                .with_max_tokens(1.0),
+        ));
+        let policy = policy()
+            .with_backoff(Duration::ZERO, Duration::ZERO, 2.0)
+            .with_max_attempts(5)
+            .with_budget(budget.clone());
+        let calls = AtomicU32::new(0);
+        assert!(retry_async(&policy, || flaky(&calls, u32::MAX))
+            .await
+            .is_err());
+        // One token, so one retry.
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(budget.tokens() < 1.0);
+    }
+}
@@ -0,0 +1,191 @@
+//! SMS encoding detection and segment counting (3GPP TS 23.038).
+//!
+//! A message that fits the GSM 03.38 alphabet goes out as GSM-7: 160
+//! septets in a single SMS, 153 per part once concatenated (the UDH takes
+//! 7). Anything else needs UCS-2: 70 UTF-16 code units, 67 per part.
+//! Extension-table characters (`€`, `[`, `{`, ...) cost two septets and a
+//! surrogate pair two code units; neither is split across parts.
+
+use serde::{Deserialize, Serialize};
+
+/// The GSM 03.38 default alphabet, indexed by septet. 0x1B is the escape to
+/// the extension table and never matches a real character.
+const GSM7_BASIC: [char; 128] = [
+    // 0x00
+    '@', '£', '$', '¥', 'è', 'é', 'ù', 'ì', 'ò', 'Ç', '\n', 'Ø', 'ø', '\r', 'Å', 'å',
+    // 0x10
+    'Δ', '_', 'Φ', 'Γ', 'Λ', 'Ω', 'Π', 'Ψ', 'Σ', 'Θ', 'Ξ', '\u{1B}', 'Æ', 'æ', 'ß', 'É',
+    // 0x20
+    ' ', '!', '"', '#', '¤', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    // 0x30
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    // 0x40
+    '¡', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    // 0x50
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', 'Ä', 'Ö', 'Ñ', 'Ü', '§',
+    // 0x60
+    '¿', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    // 0x70
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'ä', 'ö', 'ñ', 'ü', 'à',
+];
+
+/// Extension table entries, sent as `0x1B` followed by the septet.
+const GSM7_EXTENSION: [(char, u8); 10] = [
+    ('\u{0C}', 0x0A),
+    ('^', 0x14),
+    ('{', 0x28),
+    ('}', 0x29),
+    ('\\', 0x2F),
+    ('[', 0x3C),
+    ('~', 0x3D),
+    (']', 0x3E),
+    ('|', 0x40),
+    ('€', 0x65),
+];
+
+pub const GSM7_ESCAPE: u8 = 0x1B;
+
+pub const GSM7_SINGLE: u32 = 160;
+pub const GSM7_MULTIPART: u32 = 153;
+pub const UCS2_SINGLE: u32 = 70;
+pub const UCS2_MULTIPART: u32 = 67;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    Gsm7,
+    Ucs2,
+}
+
+/// Septets for `c` in GSM-7 (unpacked, escape included), or `None` if it
+/// isn't in the alphabet.
+pub fn gsm7_septets(c: char) -> Option<Vec<u8>> {
+    if c != '\u{1B}' {
+        if let Some(idx) = GSM7_BASIC.iter().position(|g| *g == c) {
+            return Some(vec![idx as u8]);
+        }
+    }
+    GSM7_EXTENSION
+        .iter()
+        .find(|(g, _)| *g == c)
+        .map(|(_, septet)| vec![GSM7_ESCAPE, *septet])
+}
+
+pub fn is_gsm7(c: char) -> bool {
+    gsm7_septets(c).is_some()
+}
+
+/// Unpacked GSM-7 septets per character, or `None` if any character needs
+/// UCS-2.
+pub fn encode_gsm7(text: &str) -> Option<Vec<Vec<u8>>> {
+    text.chars().map(gsm7_septets).collect()
+}
+
+/// Decodes unpacked GSM-7 septets; unknown escapes decode as a space, per
+/// the spec's fallback.
+pub fn decode_gsm7(septets: &[u8]) -> String {
+    let mut out = String::with_capacity(septets.len());
+    let mut iter = septets.iter();
+    while let Some(&septet) = iter.next() {
+        if septet == GSM7_ESCAPE {
+            let Some(&ext) = iter.next() else {
+                break;
+            };
+            out.push(
+                GSM7_EXTENSION
+                    .iter()
+                    .find(|(_, s)| *s == ext)
+                    .map_or(' ', |(c, _)| *c),
+            );
+        } else {
+            out.push(GSM7_BASIC.get(septet as usize).copied().unwrap_or('?'));
+        }
+    }
+    out
+}
+
+/// Big-endian UTF-16 bytes per character.
+pub fn encode_ucs2(text: &str) -> Vec<Vec<u8>> {
+    text.chars()
+        .map(|c| {
+            let mut buf = [0u16; 2];
+            c.encode_utf16(&mut buf)
+                .iter()
+                .flat_map(|u| u.to_be_bytes())
+                .collect()
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentInfo {
+    pub encoding: Encoding,
+    /// Septets for GSM-7, UTF-16 code units for UCS-2.
+    pub units: u32,
+    pub segments: u32,
+    /// Capacity of each part: 160/70 for a single SMS, 153/67 when concatenated.
+    pub per_segment: u32,
+    /// Units still free in the last part.
+    pub remaining: u32,
+    /// Distinct characters that forced UCS-2, in order of appearance.
+    pub non_gsm_chars: Vec<char>,
+}
+
+impl SegmentInfo {
+    /// Pre-send estimate at a per-segment rate, as carriers bill.
+    pub fn estimate_cost(&self, price_per_segment: f64) -> f64 {
+        self.segments as f64 * price_per_segment
+    }
+}
+
+/// Number of parts the unit sizes pack into, never splitting a character.
+fn pack(sizes: impl Iterator<Item = u32> + Clone, single: u32, multi: u32) -> (u32, u32, u32) {
+    let total: u32 = sizes.clone().sum();
+    if total <= single {
+        return (1, single, single - total);
+    }
+    let mut parts = 1;
+    let mut used = 0;
+    for size in sizes {
+        if used + size > multi {
+            parts += 1;
+            used = 0;
+        }
+        used += size;
+    }
+    (parts, multi, multi - used)
+}
+
+pub fn analyze(text: &str) -> SegmentInfo {
+    let mut non_gsm_chars = Vec::new();
+    for c in text.chars().filter(|c| !is_gsm7(*c)) {
+        if !non_gsm_chars.contains(&c) {
+            non_gsm_chars.push(c);
+        }
+    }
+
+    let (encoding, sizes, single, multi): (_, Vec<u32>, _, _) = if non_gsm_chars.is_empty() {
+        let sizes = text
+            .chars()
+            .map(|c| gsm7_septets(c).map_or(1, |s| s.len() as u32))
+            .collect();
+        (Encoding::Gsm7, sizes, GSM7_SINGLE, GSM7_MULTIPART)
+    } else {
+        let sizes = text.chars().map(|c| c.len_utf16() as u32).collect();
+        (Encoding::Ucs2, sizes, UCS2_SINGLE, UCS2_MULTIPART)
+    };
+    let (segments, per_segment, remaining) = pack(sizes.iter().copied(), single, multi);
+    SegmentInfo {
+        encoding,
+        units: sizes.iter().sum(),
+        segments,
+        per_segment,
+        remaining,
+        non_gsm_chars,
+    }
+}
+
+/// Segments a message will be billed as; an empty message still sends one.
+pub fn count_segments(text: &str) -> u32 {
+    analyze(text).segments
+}
@@ -0,0 +1,238 @@
+//! Sender ID rules by destination country.
+//!
+//! Whether an SMS may go out under an alphanumeric sender ("ACME"), and
+//! how long it may be, depends on the destination market. Some markets
+//! only deliver from numbers, some require alphanumeric senders to be
+//! registered with carriers first, and India caps headers at six
+//! characters. `validate_sender` checks a sender against those rules and
+//! normalizes it before dispatch; `SenderRewrites` swaps in a permitted
+//! sender where the requested one is refused.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// GSM 03.40 limit for an alphanumeric originating address.
+pub const MAX_ALPHANUMERIC_LEN: usize = 11;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SenderError {
+    #[error("Sender ID is empty")]
+    Empty,
+    #[error("Sender ID {0:?} contains characters carriers reject")]
+    InvalidCharacters(String),
+    #[error("Sender ID {sender:?} is longer than {max} characters")]
+    TooLong { sender: String, max: usize },
+    #[error("Alphanumeric sender IDs aren't delivered in {country}")]
+    AlphanumericNotAllowed { sender: String, country: String },
+    #[error("Sender number {0:?} is not a valid short code or E.164 number")]
+    InvalidNumber(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SenderKind {
+    Alphanumeric,
+    /// 3–8 digit short code.
+    ShortCode,
+    /// Full E.164 number.
+    LongCode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountryRules {
+    /// False in numeric-only markets.
+    pub alphanumeric: bool,
+    pub max_alphanumeric_len: usize,
+    /// Alphanumeric senders must be registered with carriers beforehand;
+    /// unregistered ones are dropped or overwritten.
+    pub registration_required: bool,
+}
+
+const DEFAULT_RULES: CountryRules = CountryRules {
+    alphanumeric: true,
+    max_alphanumeric_len: MAX_ALPHANUMERIC_LEN,
+    registration_required: false,
+};
+
+const NUMERIC_ONLY: CountryRules = CountryRules {
+    alphanumeric: false,
+    max_alphanumeric_len: 0,
+    registration_required: false,
+};
+
+const REGISTERED: CountryRules = CountryRules {
+    alphanumeric: true,
+    max_alphanumeric_len: MAX_ALPHANUMERIC_LEN,
+    registration_required: true,
+};
+
+/// Markets that differ from `DEFAULT_RULES`, by ISO 3166 alpha-2 code.
+const COUNTRY_RULES: &[(&str, CountryRules)] = &[
+    ("US", NUMERIC_ONLY),
+    ("CA", NUMERIC_ONLY),
+    ("PR", NUMERIC_ONLY),
+    ("CN", NUMERIC_ONLY),
+    ("BR", NUMERIC_ONLY),
+    ("MX", NUMERIC_ONLY),
+    // DLT headers: six characters, registered per entity.
+    (
+        "IN",
+        CountryRules {
+            alphanumeric: true,
+            max_alphanumeric_len: 6,
+            registration_required: true,
+        },
+    ),
+    ("AE", REGISTERED),
+    ("SA", REGISTERED),
+    ("EG", REGISTERED),
+    ("KW", REGISTERED),
+    ("QA", REGISTERED),
+    ("OM", REGISTERED),
+    ("BH", REGISTERED),
+    ("JO", REGISTERED),
+    ("TR", REGISTERED),
+    ("ID", REGISTERED),
+    ("PH", REGISTERED),
+    ("TH", REGISTERED),
+    ("VN", REGISTERED),
+];
+
+/// Rules for a destination country; unlisted countries get the defaults.
+pub fn rules(country: &str) -> CountryRules {
+    COUNTRY_RULES
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(country))
+        .map(|(_, rules)| *rules)
+        .unwrap_or(DEFAULT_RULES)
+}
+
+/// A sender that passed validation, in the form providers expect.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NormalizedSender {
+    /// Trimmed alphanumeric ID, short code digits, or `+` and E.164 digits.
+    pub value: String,
+    pub kind: SenderKind,
+    /// The caller must check the sender is registered for this country.
+    pub registration_required: bool,
+}
+
+/// Letters, digits, space and `+ - _ &`, as carriers accept in the
+/// alphanumeric originating address.
+fn alphanumeric_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, ' ' | '+' | '-' | '_' | '&')
+}
+
+/// Validates `from` for delivery to `country` (ISO 3166 alpha-2).
+/// Senders of digits only, optionally with `+` and common separators, are
+/// numbers; anything with a letter is alphanumeric.
+pub fn validate_sender(from: &str, country: &str) -> Result<NormalizedSender, SenderError> {
+    let sender = from.trim();
+    if sender.is_empty() {
+        return Err(SenderError::Empty);
+    }
+    let rules = rules(country);
+
+    if !sender.chars().any(|c| c.is_ascii_alphabetic()) {
+        if !sender
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '+' | ' ' | '-' | '(' | ')' | '.'))
+        {
+            return Err(SenderError::InvalidCharacters(sender.to_string()));
+        }
+        let digits: String = sender.chars().filter(char::is_ascii_digit).collect();
+        let international = sender.starts_with('+');
+        let (value, kind) = if !international && (3..=8).contains(&digits.len()) {
+            (digits, SenderKind::ShortCode)
+        } else if (8..=15).contains(&digits.len()) && !digits.starts_with('0') {
+            (format!("+{}", digits), SenderKind::LongCode)
+        } else {
+            return Err(SenderError::InvalidNumber(sender.to_string()));
+        };
+        return Ok(NormalizedSender {
+            value,
+            kind,
+            registration_required: false,
+        });
+    }
+
+    if !sender.chars().all(alphanumeric_char) {
+        return Err(SenderError::InvalidCharacters(sender.to_string()));
+    }
+    if !rules.alphanumeric {
+        return Err(SenderError::AlphanumericNotAllowed {
+            sender: sender.to_string(),
+            country: country.to_uppercase(),
+        });
+    }
+    if sender.chars().count() > rules.max_alphanumeric_len {
+        return Err(SenderError::TooLong {
+            sender: sender.to_string(),
+            max: rules.max_alphanumeric_len,
+        });
+    }
+    Ok(NormalizedSender {
+        value: sender.to_string(),
+        kind: SenderKind::Alphanumeric,
+        registration_required: rules.registration_required,
+    })
+}
+
+/// A sender replaced to suit the destination market.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SenderRewrite {
+    pub original: String,
+    pub rewritten: String,
+    /// Why the original was refused.
+    pub reason: String,
+}
+
+/// Replacement senders for destinations that refuse the requested one,
+/// e.g. a local long code for an alphanumeric ID in a numeric-only market.
+/// Only market rules trigger a rewrite (alphanumeric not allowed or too
+/// long); malformed senders still fail.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SenderRewrites {
+    /// Replacement sender by ISO 3166 alpha-2 code.
+    #[serde(default)]
+    pub fallbacks: HashMap<String, String>,
+}
+
+impl SenderRewrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fallback(mut self, country: &str, sender: &str) -> Self {
+        self.fallbacks
+            .insert(country.to_uppercase(), sender.to_string());
+        self
+    }
+
+    /// Validates `from` for `country`, substituting the country's fallback
+    /// if the market refuses it. The rewrite, if any, is returned alongside.
+    pub fn apply(
+        &self,
+        from: &str,
+        country: &str,
+    ) -> Result<(NormalizedSender, Option<SenderRewrite>), SenderError> {
+        let err = match validate_sender(from, country) {
+            Ok(sender) => return Ok((sender, None)),
+            Err(
+                err @ (SenderError::AlphanumericNotAllowed { .. } | SenderError::TooLong { .. }),
+            ) => err,
+            Err(err) => return Err(err),
+        };
+        let Some(fallback) = self.fallbacks.get(&country.to_uppercase()) else {
+            return Err(err);
+        };
+        let sender = validate_sender(fallback, country)?;
+        let rewrite = SenderRewrite {
+            original: from.to_string(),
+            rewritten: sender.value.clone(),
+            reason: err.to_string(),
+        };
+        Ok((sender, Some(rewrite)))
+    }
+}
@@ -0,0 +1,164 @@
+use crate::database::DATABASE_MANAGER;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+type Hook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// Resolves once shutdown has started; cheap to clone into servers and loops.
+///
+/// ```no_run
+/// # use smsly_core::shutdown::ShutdownCoordinator;
+/// # use std::time::Duration;
+/// # async fn serve(app: axum::Router) -> std::io::Result<()> {
+/// # let coordinator = ShutdownCoordinator::new(Duration::from_secs(30));
+/// # let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
+/// axum::serve(listener, app)
+///     .with_graceful_shutdown(coordinator.signal().wait())
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    rx: watch::Receiver<bool>,
+}
+
+impl ShutdownSignal {
+    pub fn is_triggered(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    pub async fn wait(mut self) {
+        let _ = self.rx.wait_for(|triggered| *triggered).await;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    pub completed: Vec<String>,
+    /// Hooks still running (or never started) when the deadline passed.
+    pub timed_out: Vec<String>,
+}
+
+/// Runs registered teardown hooks on SIGTERM/SIGINT within an overall deadline.
+/// Hooks run one at a time in reverse registration order, so register
+/// long-lived resources (database pools) before the work that uses them
+/// (in-flight sends, audit buffers).
+pub struct ShutdownCoordinator {
+    hooks: Mutex<Vec<(String, Hook)>>,
+    trigger: watch::Sender<bool>,
+    deadline: Duration,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(deadline: Duration) -> Self {
+        let (trigger, _) = watch::channel(false);
+        Self {
+            hooks: Mutex::new(Vec::new()),
+            trigger,
+            deadline,
+        }
+    }
+
+    pub fn register<F, Fut>(&self, name: &str, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks
+            .lock()
+            .unwrap()
+            .push((name.to_string(), Box::new(move || Box::pin(hook()))));
+    }
+
+    /// Registers closing every pool in `DATABASE_MANAGER`.
+    pub fn register_database_pools(&self) {
+        self.register("database", || async { DATABASE_MANAGER.close_all().await });
+    }
+
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal {
+            rx: self.trigger.subscribe(),
+        }
+    }
+
+    /// Waits for SIGTERM or SIGINT, then runs `shutdown`.
+    pub async fn run_on_signal(&self) -> ShutdownReport {
+        wait_for_os_signal().await;
+        self.shutdown().await
+    }
+
+    /// Notifies every `ShutdownSignal` and runs the hooks. Calling it again
+    /// after the hooks have run is a no-op.
+    pub async fn shutdown(&self) -> ShutdownReport {
+        self.trigger.send_replace(true);
+        let hooks: Vec<(String, Hook)> = self.hooks.lock().unwrap().drain(..).collect();
+        if hooks.is_empty() {
+            return ShutdownReport::default();
+        }
+        info!(
+            "Shutting down: {} hook(s), deadline {:?}",
+            hooks.len(),
+            self.deadline
+        );
+
+        let deadline = Instant::now() + self.deadline;
+        let mut report = ShutdownReport::default();
+        let mut remaining = hooks.into_iter().rev();
+        for (name, hook) in remaining.by_ref() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match tokio::time::timeout(left, hook()).await {
+                Ok(()) => {
+                    info!("Shutdown hook completed: {}", name);
+                    report.completed.push(name);
+                }
+                Err(_) => {
+                    error!("Shutdown hook timed out: {}", name);
+                    report.timed_out.push(name);
+                    break;
+                }
+            }
+        }
+        report.timed_out.extend(remaining.map(|(name, _)| name));
+        if !report.timed_out.is_empty() {
+            warn!(
+                "Shutdown deadline exceeded; skipped: {}",
+                report.timed_out.join(", ")
+            );
+        }
+        report
+    }
+}
+
+async fn wait_for_os_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received SIGINT"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
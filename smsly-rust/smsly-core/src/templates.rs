@@ -0,0 +1,292 @@
+//! Message templates with named placeholders.
+//!
+//! A template body like `Your code is {{code}}, valid for {{minutes}}
+//! minutes` is parsed once when defined; rendering fills each placeholder
+//! from the message's variables and fails if any is missing, rather than
+//! sending a literal `{{code}}`. Budgets are checked on the rendered text,
+//! since a long name or a single emoji in a variable can push a message
+//! into more segments or into UCS-2.
+
+use crate::segments::{analyze, Encoding, SegmentInfo};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("Template {0} not found")]
+    NotFound(String),
+    #[error("Invalid template: {0}")]
+    Invalid(String),
+    #[error("Undefined template variables: {}", .0.join(", "))]
+    UndefinedVariables(Vec<String>),
+    #[error("Rendered message is {length} characters, over the {max} limit")]
+    TooLong { length: usize, max: usize },
+    #[error("Rendered message needs {segments} segments, over the {max} limit")]
+    TooManySegments { segments: u32, max: u32 },
+    #[error("Rendered message needs UCS-2 for {0:?}")]
+    NotGsm7(Vec<char>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Variable(String),
+}
+
+/// Template as stored: the body plus its budgets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TemplateDef {
+    id: String,
+    body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_segments: Option<u32>,
+    #[serde(default)]
+    require_gsm7: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "TemplateDef", into = "TemplateDef")]
+pub struct Template {
+    pub id: String,
+    pub body: String,
+    /// Characters allowed after rendering.
+    pub max_length: Option<usize>,
+    /// Segments allowed after rendering.
+    pub max_segments: Option<u32>,
+    /// Refuse renders that would need UCS-2.
+    pub require_gsm7: bool,
+    parts: Vec<Part>,
+}
+
+impl TryFrom<TemplateDef> for Template {
+    type Error = TemplateError;
+
+    fn try_from(def: TemplateDef) -> Result<Self, Self::Error> {
+        Ok(Self {
+            parts: parse(&def.body)?,
+            id: def.id,
+            body: def.body,
+            max_length: def.max_length,
+            max_segments: def.max_segments,
+            require_gsm7: def.require_gsm7,
+        })
+    }
+}
+
+impl From<Template> for TemplateDef {
+    fn from(template: Template) -> Self {
+        Self {
+            id: template.id,
+            body: template.body,
+            max_length: template.max_length,
+            max_segments: template.max_segments,
+            require_gsm7: template.require_gsm7,
+        }
+    }
+}
+
+/// Splits `body` into text and `{{name}}` placeholders. Names are ASCII
+/// letters, digits and `_`, not starting with a digit; whitespace inside
+/// the braces is ignored. `{{{{` is a literal `{{`.
+fn parse(body: &str) -> Result<Vec<Part>, TemplateError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        text.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+        if let Some(after) = rest.strip_prefix("{{") {
+            text.push_str("{{");
+            rest = after;
+            continue;
+        }
+        let end = rest
+            .find("}}")
+            .ok_or_else(|| TemplateError::Invalid("unclosed placeholder".to_string()))?;
+        let name = rest[..end].trim();
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(TemplateError::Invalid(format!(
+                "bad placeholder name {:?}",
+                name
+            )));
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(std::mem::take(&mut text)));
+        }
+        parts.push(Part::Variable(name.to_string()));
+        rest = &rest[end + 2..];
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+/// A rendered message with its segment breakdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rendered {
+    pub text: String,
+    pub segments: SegmentInfo,
+}
+
+impl Template {
+    pub fn new(id: &str, body: &str) -> Result<Self, TemplateError> {
+        Ok(Self {
+            id: id.to_string(),
+            body: body.to_string(),
+            max_length: None,
+            max_segments: None,
+            require_gsm7: false,
+            parts: parse(body)?,
+        })
+    }
+
+    pub fn with_max_length(mut self, max: usize) -> Self {
+        self.max_length = Some(max);
+        self
+    }
+
+    pub fn with_max_segments(mut self, max: u32) -> Self {
+        self.max_segments = Some(max);
+        self
+    }
+
+    pub fn with_require_gsm7(mut self, require: bool) -> Self {
+        self.require_gsm7 = require;
+        self
+    }
+
+    /// Distinct placeholder names, in order of first use.
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for part in &self.parts {
+            if let Part::Variable(name) = part {
+                if !names.contains(&name.as_str()) {
+                    names.push(name.as_str());
+                }
+            }
+        }
+        names
+    }
+
+    /// Fills the placeholders from `variables` and checks the result
+    /// against the template's budgets. Extra variables are ignored.
+    pub fn render(&self, variables: &HashMap<String, String>) -> Result<Rendered, TemplateError> {
+        let missing: Vec<String> = self
+            .variables()
+            .into_iter()
+            .filter(|name| !variables.contains_key(*name))
+            .map(str::to_string)
+            .collect();
+        if !missing.is_empty() {
+            return Err(TemplateError::UndefinedVariables(missing));
+        }
+
+        let mut text = String::with_capacity(self.body.len());
+        for part in &self.parts {
+            match part {
+                Part::Text(s) => text.push_str(s),
+                Part::Variable(name) => text.push_str(&variables[name]),
+            }
+        }
+
+        let length = text.chars().count();
+        if let Some(max) = self.max_length.filter(|max| length > *max) {
+            return Err(TemplateError::TooLong { length, max });
+        }
+        let segments = analyze(&text);
+        if self.require_gsm7 && segments.encoding != Encoding::Gsm7 {
+            return Err(TemplateError::NotGsm7(segments.non_gsm_chars));
+        }
+        if let Some(max) = self.max_segments.filter(|max| segments.segments > *max) {
+            return Err(TemplateError::TooManySegments {
+                segments: segments.segments,
+                max,
+            });
+        }
+        Ok(Rendered { text, segments })
+    }
+}
+
+/// Templates by ID, shared by the services rendering them. Load from
+/// config or the database with `load_json`; replacing a template takes
+/// effect on the next render.
+#[derive(Debug, Default)]
+pub struct TemplateStore {
+    templates: RwLock<HashMap<String, Template>>,
+}
+
+impl TemplateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, template: Template) {
+        self.templates
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(template.id.clone(), template);
+    }
+
+    pub fn remove(&self, id: &str) -> Option<Template> {
+        self.templates
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Template> {
+        self.templates
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .templates
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Adds templates from a JSON array, replacing any with the same ID.
+    /// Nothing is added if any template is invalid.
+    pub fn load_json(&self, json: &str) -> Result<usize, TemplateError> {
+        let templates: Vec<Template> =
+            serde_json::from_str(json).map_err(|e| TemplateError::Invalid(e.to_string()))?;
+        let count = templates.len();
+        let mut stored = self.templates.write().unwrap_or_else(|e| e.into_inner());
+        for template in templates {
+            stored.insert(template.id.clone(), template);
+        }
+        Ok(count)
+    }
+
+    pub fn render(
+        &self,
+        id: &str,
+        variables: &HashMap<String, String>,
+    ) -> Result<Rendered, TemplateError> {
+        let templates = self.templates.read().unwrap_or_else(|e| e.into_inner());
+        templates
+            .get(id)
+            .ok_or_else(|| TemplateError::NotFound(id.to_string()))?
+            .render(variables)
+    }
+}
@@ -0,0 +1,440 @@
+//! Contract tests for `BaseProviderAdapter` implementations (feature
+//! `testkit`).
+//!
+//! `MockServer` stands in for a provider's API in the style of wiremock:
+//! it records every request and answers with scripted responses. An
+//! `AdapterContract` pairs an adapter constructor with the provider's
+//! fixtures, and `run_contract` checks the behavior every adapter owes
+//! its callers:
+//!
+//! - a successful send returns `success` with the provider's message id;
+//! - a rejected send maps to `MessageStatus::Failed` with the provider's
+//!   error code, and an unreachable API fails rather than panics;
+//! - unsigned or tampered webhooks are rejected;
+//! - delivery reports and inbound messages parse, and garbage doesn't.
+//!
+//! ```
+//! # use serde_json::json;
+//! # use smsly_core::providers::{TwilioAdapter, TwilioConfig};
+//! # use smsly_core::testkit::{assert_contract, AdapterContract, MockResponse};
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! #     twilio_contract().await;
+//! # }
+//! // In a #[tokio::test]:
+//! async fn twilio_contract() {
+//!     let contract = AdapterContract::new(|base_url| {
+//!         let mut config = TwilioConfig::new("AC123", "secret");
+//!         config.base_url = base_url.to_string();
+//!         Box::new(TwilioAdapter::new(config))
+//!     })
+//!     .send_success(MockResponse::json(201, json!({ "sid": "SM1", "status": "queued" })), "SM1")
+//!     .send_failure(MockResponse::json(400, json!({ "code": 21211 })), "21211");
+//!     assert_contract(&contract).await;
+//! }
+//! ```
+
+use crate::adapters::{BaseProviderAdapter, MessageStatus};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+/// A scripted HTTP response.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: Value,
+    pub headers: Vec<(String, String)>,
+}
+
+impl MockResponse {
+    pub fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            body,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// A request the mock server received.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    /// Lowercased names.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    pub fn json(&self) -> Option<Value> {
+        serde_json::from_slice(&self.body).ok()
+    }
+
+    pub fn form(&self) -> Vec<(String, String)> {
+        serde_urlencoded::from_bytes(&self.body).unwrap_or_default()
+    }
+}
+
+struct ServerState {
+    default: MockResponse,
+    /// Exact-path responses, e.g. for an OAuth token endpoint.
+    mounted: HashMap<String, MockResponse>,
+    requests: Vec<RecordedRequest>,
+}
+
+type SharedState = Arc<Mutex<ServerState>>;
+
+/// Local HTTP server answering every path with the default response unless
+/// one is mounted for it. Stops when dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: SharedState,
+    handle: JoinHandle<()>,
+}
+
+async fn handle(
+    State(state): State<SharedState>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    state.requests.push(RecordedRequest {
+        method: method.to_string(),
+        path: uri.path().to_string(),
+        query: uri.query().map(str::to_string),
+        headers: headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_string(), value.to_str().ok()?.to_string()))
+            })
+            .collect(),
+        body: body.to_vec(),
+    });
+    let response = state
+        .mounted
+        .get(uri.path())
+        .unwrap_or(&state.default)
+        .clone();
+    let mut http = (
+        StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        axum::Json(response.body),
+    )
+        .into_response();
+    for (name, value) in response.headers {
+        if let (Ok(name), Ok(value)) = (
+            axum::http::HeaderName::from_bytes(name.as_bytes()),
+            axum::http::HeaderValue::from_str(&value),
+        ) {
+            http.headers_mut().insert(name, value);
+        }
+    }
+    http
+}
+
+impl MockServer {
+    /// Binds an ephemeral port on 127.0.0.1. Answers `200 {}` until told
+    /// otherwise.
+    pub async fn start() -> Self {
+        let state = Arc::new(Mutex::new(ServerState {
+            default: MockResponse::json(200, Value::Object(Default::default())),
+            mounted: HashMap::new(),
+            requests: Vec::new(),
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock server");
+        let addr = listener.local_addr().expect("mock server address");
+        let app = Router::new().fallback(handle).with_state(state.clone());
+        let handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Self {
+            addr,
+            state,
+            handle,
+        }
+    }
+
+    pub fn uri(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ServerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Response for every path without a mounted one.
+    pub fn respond_with(&self, response: MockResponse) {
+        self.state().default = response;
+    }
+
+    pub fn mount(&self, path: &str, response: MockResponse) {
+        self.state().mounted.insert(path.to_string(), response);
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state().requests.clone()
+    }
+
+    pub fn clear_requests(&self) {
+        self.state().requests.clear();
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// A webhook body with the headers that make it valid.
+#[derive(Debug, Clone)]
+pub struct WebhookFixture {
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl WebhookFixture {
+    pub fn new(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            headers: HashMap::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+pub type AdapterFactory = Box<dyn Fn(&str) -> Box<dyn BaseProviderAdapter> + Send + Sync>;
+
+/// One adapter's fixtures. Checks whose fixtures are missing are skipped.
+pub struct AdapterContract {
+    /// Builds the adapter against an API base URL.
+    pub build: AdapterFactory,
+    pub to: String,
+    pub from: String,
+    /// OAuth or similar endpoints the adapter calls before sending.
+    pub mounts: Vec<(String, MockResponse)>,
+    pub send_success: Option<(MockResponse, String)>,
+    pub send_failure: Option<(MockResponse, String)>,
+    /// A signed delivery report and the message id and status it carries.
+    pub dlr: Option<(WebhookFixture, String, MessageStatus)>,
+    /// A signed inbound message and its sender and text.
+    pub inbound: Option<(WebhookFixture, String, String)>,
+}
+
+impl AdapterContract {
+    pub fn new(
+        build: impl Fn(&str) -> Box<dyn BaseProviderAdapter> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            build: Box::new(build),
+            to: "+447700900123".to_string(),
+            from: "+447700900456".to_string(),
+            mounts: Vec::new(),
+            send_success: None,
+            send_failure: None,
+            dlr: None,
+            inbound: None,
+        }
+    }
+
+    pub fn with_addresses(mut self, to: &str, from: &str) -> Self {
+        self.to = to.to_string();
+        self.from = from.to_string();
+        self
+    }
+
+    pub fn mount(mut self, path: &str, response: MockResponse) -> Self {
+        self.mounts.push((path.to_string(), response));
+        self
+    }
+
+    /// The provider's accepted-send response and the id it should yield.
+    pub fn send_success(mut self, response: MockResponse, message_id: &str) -> Self {
+        self.send_success = Some((response, message_id.to_string()));
+        self
+    }
+
+    /// A rejected-send response and the error code it should map to.
+    pub fn send_failure(mut self, response: MockResponse, error_code: &str) -> Self {
+        self.send_failure = Some((response, error_code.to_string()));
+        self
+    }
+
+    pub fn dlr(mut self, fixture: WebhookFixture, message_id: &str, status: MessageStatus) -> Self {
+        self.dlr = Some((fixture, message_id.to_string(), status));
+        self
+    }
+
+    pub fn inbound(mut self, fixture: WebhookFixture, from: &str, body: &str) -> Self {
+        self.inbound = Some((fixture, from.to_string(), body.to_string()));
+        self
+    }
+}
+
+/// Runs every check the fixtures allow and returns what failed, one line
+/// per violation.
+pub async fn run_contract(contract: &AdapterContract) -> Vec<String> {
+    let mut violations = Vec::new();
+    let server = MockServer::start().await;
+    for (path, response) in &contract.mounts {
+        server.mount(path, response.clone());
+    }
+    let adapter = (contract.build)(&server.uri());
+    let name = adapter.name();
+
+    let mut calls_api = false;
+    if let Some((response, message_id)) = &contract.send_success {
+        server.respond_with(response.clone());
+        server.clear_requests();
+        let result = adapter
+            .send_sms(&contract.to, &contract.from, "contract test", None)
+            .await;
+        if !result.success || result.status == MessageStatus::Failed {
+            violations.push(format!(
+                "{}: accepted send reported failure: {:?}",
+                name, result
+            ));
+        }
+        if result.provider_message_id.as_deref() != Some(message_id) {
+            violations.push(format!(
+                "{}: accepted send returned id {:?}, expected {}",
+                name, result.provider_message_id, message_id
+            ));
+        }
+        calls_api = !server.requests().is_empty();
+    }
+
+    if let Some((response, error_code)) = &contract.send_failure {
+        server.respond_with(response.clone());
+        let result = adapter
+            .send_sms(&contract.to, &contract.from, "contract test", None)
+            .await;
+        if result.success || result.status != MessageStatus::Failed {
+            violations.push(format!(
+                "{}: rejected send reported success: {:?}",
+                name, result
+            ));
+        }
+        if result.error_code.as_deref() != Some(error_code) {
+            violations.push(format!(
+                "{}: rejected send mapped to error code {:?}, expected {}",
+                name, result.error_code, error_code
+            ));
+        }
+    }
+
+    // Nothing listens on port 1. Skipped for adapters that don't send over
+    // HTTP (SMPP, the mock provider).
+    if calls_api {
+        let unreachable = (contract.build)("http://127.0.0.1:1");
+        let result = unreachable
+            .send_sms(&contract.to, &contract.from, "contract test", None)
+            .await;
+        if result.success || result.status != MessageStatus::Failed {
+            violations.push(format!(
+                "{}: send to an unreachable API reported {:?}",
+                name, result.status
+            ));
+        }
+    }
+
+    for (kind, fixture) in [
+        ("delivery report", contract.dlr.as_ref().map(|d| &d.0)),
+        ("inbound message", contract.inbound.as_ref().map(|i| &i.0)),
+    ] {
+        let Some(fixture) = fixture else {
+            continue;
+        };
+        if !adapter
+            .validate_webhook(&fixture.headers, &fixture.body)
+            .await
+        {
+            violations.push(format!("{}: valid {} webhook rejected", name, kind));
+        }
+        if adapter
+            .validate_webhook(&HashMap::new(), &fixture.body)
+            .await
+        {
+            violations.push(format!("{}: unsigned {} webhook accepted", name, kind));
+        }
+        let mut tampered = fixture.body.clone();
+        tampered.extend_from_slice(b" ");
+        // Header-only schemes (basic auth) can't detect body changes.
+        if !fixture
+            .headers
+            .keys()
+            .any(|h| h.eq_ignore_ascii_case("authorization"))
+            && adapter.validate_webhook(&fixture.headers, &tampered).await
+        {
+            violations.push(format!("{}: tampered {} webhook accepted", name, kind));
+        }
+    }
+
+    if let Some((fixture, message_id, status)) = &contract.dlr {
+        match adapter.parse_webhook(&fixture.body).await {
+            Ok(event) => {
+                if &event.provider_message_id != message_id || event.status != *status {
+                    violations.push(format!(
+                        "{}: delivery report parsed as {} {:?}, expected {} {:?}",
+                        name, event.provider_message_id, event.status, message_id, status
+                    ));
+                }
+            }
+            Err(e) => violations.push(format!("{}: delivery report didn't parse: {}", name, e)),
+        }
+        if adapter.parse_webhook(b"not a webhook").await.is_ok() {
+            violations.push(format!("{}: garbage parsed as a delivery report", name));
+        }
+    }
+
+    if let Some((fixture, from, body)) = &contract.inbound {
+        match adapter.parse_inbound(&fixture.body).await {
+            Ok(message) => {
+                if &message.from != from || &message.body != body {
+                    violations.push(format!(
+                        "{}: inbound message parsed as {} {:?}, expected {} {:?}",
+                        name, message.from, message.body, from, body
+                    ));
+                }
+            }
+            Err(e) => violations.push(format!("{}: inbound message didn't parse: {}", name, e)),
+        }
+        if adapter.parse_inbound(b"not a webhook").await.is_ok() {
+            violations.push(format!("{}: garbage parsed as an inbound message", name));
+        }
+    }
+
+    violations
+}
+
+/// Panics with every violation, for use in `#[tokio::test]`s.
+pub async fn assert_contract(contract: &AdapterContract) {
+    let violations = run_contract(contract).await;
+    assert!(
+        violations.is_empty(),
+        "adapter contract violated:\n  {}",
+        violations.join("\n  ")
+    );
+}
@@ -0,0 +1,753 @@
+use super::session::SessionTracker;
+use super::webhook::{self, WhatsAppEvent};
+use crate::adapters::{
+    expired_result, remaining_validity, BaseProviderAdapter, InboundMessage, MessageStatus,
+    SendResult, WebhookEvent,
+};
+use crate::providers::{failed, header, http_client};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{error, warn};
+
+pub const GRAPH_API_BASE: &str = "https://graph.facebook.com";
+pub const DEFAULT_API_VERSION: &str = "v21.0";
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum WhatsAppError {
+    #[error("WhatsApp request failed: {0}")]
+    Request(String),
+    #[error("WhatsApp API error {code}: {message}")]
+    Api {
+        code: i64,
+        subcode: Option<i64>,
+        message: String,
+        /// `error_data.details`, usually the most specific explanation.
+        details: Option<String>,
+        fbtrace_id: Option<String>,
+    },
+    #[error("Invalid WhatsApp response: {0}")]
+    InvalidResponse(String),
+    /// Refused locally: no open 24-hour session, so only templates may go.
+    #[error("No open WhatsApp session with {user}; send a template")]
+    SessionClosed { user: String },
+}
+
+/// What a Cloud API error code means for the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WhatsAppErrorKind {
+    /// Throughput, pair or spam rate limits; back off and retry.
+    RateLimited,
+    /// Free-form message more than 24 hours after the user last wrote.
+    OutsideSessionWindow,
+    /// The recipient isn't on WhatsApp or can't receive this message.
+    Undeliverable,
+    TemplateNotFound,
+    /// Template parameters don't match its definition.
+    TemplateMismatch,
+    InvalidParameter,
+    /// Access token expired or lacks permission.
+    Unauthorized,
+    /// Account or number locked, restricted or unregistered.
+    AccountRestricted,
+    /// Transient failure on Meta's side.
+    ServiceUnavailable,
+    Other,
+}
+
+impl WhatsAppError {
+    /// Cloud API error codes, grouped.
+    pub fn kind(&self) -> WhatsAppErrorKind {
+        let code = match self {
+            WhatsAppError::Api { code, .. } => code,
+            WhatsAppError::SessionClosed { .. } => return WhatsAppErrorKind::OutsideSessionWindow,
+            _ => return WhatsAppErrorKind::ServiceUnavailable,
+        };
+        match code {
+            4 | 80007 | 130429 | 131048 | 131056 => WhatsAppErrorKind::RateLimited,
+            131047 => WhatsAppErrorKind::OutsideSessionWindow,
+            131026 | 131021 => WhatsAppErrorKind::Undeliverable,
+            132001 => WhatsAppErrorKind::TemplateNotFound,
+            132000 | 132012 | 132015 | 132016 => WhatsAppErrorKind::TemplateMismatch,
+            100 | 131008 | 131009 | 131051 | 131052 | 131053 => WhatsAppErrorKind::InvalidParameter,
+            0 | 3 | 10 | 190 | 200..=299 => WhatsAppErrorKind::Unauthorized,
+            368 | 131031 | 133010 | 133016 => WhatsAppErrorKind::AccountRestricted,
+            1 | 2 | 131000 | 131016 | 133004 => WhatsAppErrorKind::ServiceUnavailable,
+            _ => WhatsAppErrorKind::Other,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            WhatsAppErrorKind::RateLimited | WhatsAppErrorKind::ServiceUnavailable
+        )
+    }
+
+    /// As a failed `SendResult`, with the Cloud API code as `error_code`
+    /// (`session_closed` for `SessionClosed`).
+    pub fn into_send_result(self) -> SendResult {
+        let code = match &self {
+            WhatsAppError::Api { code, .. } => Some(code.to_string()),
+            WhatsAppError::SessionClosed { .. } => Some("session_closed".to_string()),
+            _ => None,
+        };
+        let message = match &self {
+            WhatsAppError::Api {
+                message,
+                details: Some(details),
+                ..
+            } => format!("{}: {}", message, details),
+            other => other.to_string(),
+        };
+        let raw = match &self {
+            WhatsAppError::Api { .. } | WhatsAppError::SessionClosed { .. } => {
+                Some(json!({ "kind": self.kind() }))
+            }
+            _ => None,
+        };
+        failed(code, message, raw)
+    }
+
+    fn from_response(status: u16, data: &Value) -> Self {
+        let err = &data["error"];
+        match err["code"].as_i64() {
+            Some(code) => WhatsAppError::Api {
+                code,
+                subcode: err["error_subcode"].as_i64(),
+                message: err["message"]
+                    .as_str()
+                    .unwrap_or("Unknown error")
+                    .to_string(),
+                details: err["error_data"]["details"].as_str().map(str::to_string),
+                fbtrace_id: err["fbtrace_id"].as_str().map(str::to_string),
+            },
+            None => WhatsAppError::InvalidResponse(format!("HTTP {}: {}", status, data)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WhatsAppConfig {
+    pub access_token: String,
+    /// The sending number's ID, not the number itself.
+    pub phone_number_id: String,
+    /// WhatsApp Business Account, needed for templates and number status.
+    pub business_account_id: Option<String>,
+    /// Meta app secret that signs webhooks (`X-Hub-Signature-256`).
+    pub app_secret: Option<String>,
+    pub api_version: String,
+    pub base_url: String,
+}
+
+impl WhatsAppConfig {
+    pub fn new(access_token: &str, phone_number_id: &str) -> Self {
+        Self {
+            access_token: access_token.to_string(),
+            phone_number_id: phone_number_id.to_string(),
+            business_account_id: None,
+            app_secret: None,
+            api_version: DEFAULT_API_VERSION.to_string(),
+            base_url: GRAPH_API_BASE.to_string(),
+        }
+    }
+
+    pub fn with_business_account(mut self, business_account_id: &str) -> Self {
+        self.business_account_id = Some(business_account_id.to_string());
+        self
+    }
+
+    pub fn with_app_secret(mut self, app_secret: &str) -> Self {
+        self.app_secret = Some(app_secret.to_string());
+        self
+    }
+
+    pub fn with_api_version(mut self, api_version: &str) -> Self {
+        self.api_version = api_version.to_string();
+        self
+    }
+
+    /// From `WHATSAPP_ACCESS_TOKEN` and `WHATSAPP_PHONE_NUMBER_ID`, with
+    /// optional `WHATSAPP_BUSINESS_ACCOUNT_ID`, `WHATSAPP_APP_SECRET` and
+    /// `WHATSAPP_API_VERSION`.
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let mut config = Self::new(
+            &var("WHATSAPP_ACCESS_TOKEN")?,
+            &var("WHATSAPP_PHONE_NUMBER_ID")?,
+        );
+        config.business_account_id = var("WHATSAPP_BUSINESS_ACCOUNT_ID");
+        config.app_secret = var("WHATSAPP_APP_SECRET");
+        if let Some(version) = var("WHATSAPP_API_VERSION") {
+            config.api_version = version;
+        }
+        Some(config)
+    }
+}
+
+/// Media by uploaded ID or public link.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MediaObject {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    /// Not allowed on audio or stickers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    /// Documents only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+}
+
+impl MediaObject {
+    pub fn id(id: &str) -> Self {
+        Self {
+            id: Some(id.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn link(url: &str) -> Self {
+        Self {
+            link: Some(url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_caption(mut self, caption: &str) -> Self {
+        self.caption = Some(caption.to_string());
+        self
+    }
+
+    pub fn with_filename(mut self, filename: &str) -> Self {
+        self.filename = Some(filename.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Sticker,
+}
+
+impl MediaKind {
+    /// Guessed from a URL's file extension; unrecognized files go as
+    /// documents, which accept any type.
+    pub fn from_url(url: &str) -> Self {
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        let extension = path.rsplit('.').next().unwrap_or_default().to_lowercase();
+        match extension.as_str() {
+            "jpg" | "jpeg" | "png" => MediaKind::Image,
+            "mp4" | "3gp" => MediaKind::Video,
+            "aac" | "amr" | "mp3" | "m4a" | "ogg" | "opus" => MediaKind::Audio,
+            "webp" => MediaKind::Sticker,
+            _ => MediaKind::Document,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateLanguage {
+    /// e.g. `en_US`.
+    pub code: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Currency {
+    pub fallback_value: String,
+    /// ISO 4217.
+    pub code: String,
+    /// Amount times 1000.
+    pub amount_1000: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DateTime {
+    pub fallback_value: String,
+}
+
+/// A value substituted into a template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TemplateParameter {
+    Text {
+        text: String,
+    },
+    Currency {
+        currency: Currency,
+    },
+    DateTime {
+        date_time: DateTime,
+    },
+    Image {
+        image: MediaObject,
+    },
+    Video {
+        video: MediaObject,
+    },
+    Document {
+        document: MediaObject,
+    },
+    /// Quick-reply button payload.
+    Payload {
+        payload: String,
+    },
+}
+
+impl TemplateParameter {
+    pub fn text(text: &str) -> Self {
+        TemplateParameter::Text {
+            text: text.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentType {
+    Header,
+    Body,
+    Button,
+}
+
+/// Parameters for one part of a template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateComponent {
+    #[serde(rename = "type")]
+    pub component_type: ComponentType,
+    /// Buttons only: `quick_reply` or `url`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub_type: Option<String>,
+    /// Buttons only: position of the button, from 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+    pub parameters: Vec<TemplateParameter>,
+}
+
+impl TemplateComponent {
+    pub fn header(parameters: Vec<TemplateParameter>) -> Self {
+        Self {
+            component_type: ComponentType::Header,
+            sub_type: None,
+            index: None,
+            parameters,
+        }
+    }
+
+    pub fn body(parameters: Vec<TemplateParameter>) -> Self {
+        Self {
+            component_type: ComponentType::Body,
+            sub_type: None,
+            index: None,
+            parameters,
+        }
+    }
+
+    pub fn button(sub_type: &str, index: usize, parameters: Vec<TemplateParameter>) -> Self {
+        Self {
+            component_type: ComponentType::Button,
+            sub_type: Some(sub_type.to_string()),
+            index: Some(index.to_string()),
+            parameters,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    pub language: TemplateLanguage,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<TemplateComponent>,
+}
+
+impl Template {
+    pub fn new(name: &str, language: &str, components: Vec<TemplateComponent>) -> Self {
+        Self {
+            name: name.to_string(),
+            language: TemplateLanguage {
+                code: language.to_string(),
+            },
+            components,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextBody {
+    pub body: String,
+    /// Render a preview for the first URL in `body`.
+    #[serde(default)]
+    pub preview_url: bool,
+}
+
+/// The body of an outbound message, serialized as the Cloud API's `type`
+/// plus the matching object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageContent {
+    Text { text: TextBody },
+    Template { template: Template },
+    Image { image: MediaObject },
+    Video { video: MediaObject },
+    Audio { audio: MediaObject },
+    Document { document: MediaObject },
+    Sticker { sticker: MediaObject },
+}
+
+impl MessageContent {
+    pub fn text(body: &str) -> Self {
+        MessageContent::Text {
+            text: TextBody {
+                body: body.to_string(),
+                preview_url: false,
+            },
+        }
+    }
+
+    pub fn media(kind: MediaKind, media: MediaObject) -> Self {
+        match kind {
+            MediaKind::Image => MessageContent::Image { image: media },
+            MediaKind::Video => MessageContent::Video { video: media },
+            MediaKind::Audio => MessageContent::Audio { audio: media },
+            MediaKind::Document => MessageContent::Document { document: media },
+            MediaKind::Sticker => MessageContent::Sticker { sticker: media },
+        }
+    }
+
+    /// Template messages are the only kind allowed outside the 24-hour
+    /// customer service window.
+    pub fn is_template(&self) -> bool {
+        matches!(self, MessageContent::Template { .. })
+    }
+}
+
+/// An accepted outbound message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentMessage {
+    /// `wamid.…`, which status webhooks refer to.
+    pub message_id: String,
+    /// The recipient's WhatsApp ID, if Meta resolved one.
+    pub wa_id: Option<String>,
+    pub raw_response: Value,
+}
+
+/// Meta's WhatsApp Business Cloud API, for one business phone number.
+pub struct WhatsAppClient {
+    config: WhatsAppConfig,
+    client: Client,
+}
+
+impl WhatsAppClient {
+    pub fn new(config: WhatsAppConfig) -> Self {
+        Self {
+            config,
+            client: http_client(),
+        }
+    }
+
+    pub fn config(&self) -> &WhatsAppConfig {
+        &self.config
+    }
+
+    /// Graph API URL for `path`, e.g. `{phone_number_id}/messages`.
+    pub fn url(&self, path: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            self.config.base_url.trim_end_matches('/'),
+            self.config.api_version,
+            path.trim_start_matches('/')
+        )
+    }
+
+    pub(crate) fn http(&self) -> &Client {
+        &self.client
+    }
+
+    pub(crate) fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        request.bearer_auth(&self.config.access_token)
+    }
+
+    /// Sends `request` and returns the JSON body, mapping Graph API errors.
+    pub(crate) async fn execute(&self, request: RequestBuilder) -> Result<Value, WhatsAppError> {
+        self.send_json(self.authorized(request)).await
+    }
+
+    /// As `execute`, for requests that carry their own credentials.
+    pub(crate) async fn send_json(&self, request: RequestBuilder) -> Result<Value, WhatsAppError> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| WhatsAppError::Request(e.to_string()))?;
+        let status = response.status();
+        let data: Value = response.json().await.unwrap_or(Value::Null);
+        if status.is_success() {
+            Ok(data)
+        } else {
+            Err(WhatsAppError::from_response(status.as_u16(), &data))
+        }
+    }
+
+    /// Sends `content` to `to` (E.164, with or without `+`).
+    pub async fn send(
+        &self,
+        to: &str,
+        content: &MessageContent,
+    ) -> Result<SentMessage, WhatsAppError> {
+        let mut payload = serde_json::to_value(content)
+            .map_err(|e| WhatsAppError::InvalidResponse(e.to_string()))?;
+        payload["messaging_product"] = json!("whatsapp");
+        payload["recipient_type"] = json!("individual");
+        payload["to"] = json!(to.trim_start_matches('+'));
+        let data = self
+            .execute(
+                self.client
+                    .post(self.url(&format!("{}/messages", self.config.phone_number_id)))
+                    .json(&payload),
+            )
+            .await?;
+        let message_id = data["messages"][0]["id"]
+            .as_str()
+            .ok_or_else(|| WhatsAppError::InvalidResponse("missing message id".to_string()))?
+            .to_string();
+        Ok(SentMessage {
+            message_id,
+            wa_id: data["contacts"][0]["wa_id"].as_str().map(str::to_string),
+            raw_response: data,
+        })
+    }
+
+    pub async fn send_text(&self, to: &str, body: &str) -> Result<SentMessage, WhatsAppError> {
+        self.send(to, &MessageContent::text(body)).await
+    }
+
+    pub async fn send_template(
+        &self,
+        to: &str,
+        template: Template,
+    ) -> Result<SentMessage, WhatsAppError> {
+        self.send(to, &MessageContent::Template { template }).await
+    }
+
+    pub async fn send_media(
+        &self,
+        to: &str,
+        kind: MediaKind,
+        media: MediaObject,
+    ) -> Result<SentMessage, WhatsAppError> {
+        self.send(to, &MessageContent::media(kind, media)).await
+    }
+
+    /// Whether the access token can read the phone number.
+    pub async fn health_check(&self) -> bool {
+        self.execute(
+            self.client
+                .get(self.url(&self.config.phone_number_id))
+                .query(&[("fields", "id")]),
+        )
+        .await
+        .is_ok()
+    }
+}
+
+fn send_result(result: Result<SentMessage, WhatsAppError>) -> SendResult {
+    match result {
+        Ok(sent) => SendResult {
+            success: true,
+            provider_message_id: Some(sent.message_id),
+            status: MessageStatus::Pending,
+            // Conversation-based pricing; no per-segment billing.
+            segments: 1,
+            raw_response: Some(sent.raw_response),
+            ..Default::default()
+        },
+        Err(e) => {
+            error!("WhatsApp send failed: {}", e);
+            e.into_send_result()
+        }
+    }
+}
+
+/// The Cloud API as a provider adapter. `send_sms` sends text, or the
+/// template in `metadata["whatsapp_template"]` (a `Template` as JSON) when
+/// given; `send_mms` sends the first attachment with `text` as caption.
+/// The sender is always the configured phone number. Webhooks need
+/// `WhatsAppConfig::app_secret`; `parse_webhook` and `parse_inbound` return
+/// the first status or message in a delivery, see `webhook::parse_payload`
+/// for all of them.
+///
+/// With a `SessionTracker`, free-form sends outside the 24-hour window fail
+/// locally with error code `session_closed` instead of reaching Meta, and
+/// `parse_inbound` opens windows for the messages it sees.
+///
+/// The Cloud API takes no per-message TTL, so messages past their
+/// `expires_at` fail locally with error code `expired`; for delivery-side
+/// expiry, set `NewTemplate::message_send_ttl_seconds` on the template.
+pub struct WhatsAppAdapter {
+    client: Arc<WhatsAppClient>,
+    sessions: Option<Arc<SessionTracker>>,
+}
+
+impl WhatsAppAdapter {
+    pub fn new(config: WhatsAppConfig) -> Self {
+        Self::with_client(Arc::new(WhatsAppClient::new(config)))
+    }
+
+    /// Shares `client` with, e.g., a `TemplateCatalog`.
+    pub fn with_client(client: Arc<WhatsAppClient>) -> Self {
+        Self {
+            client,
+            sessions: None,
+        }
+    }
+
+    pub fn with_sessions(mut self, sessions: Arc<SessionTracker>) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    pub fn client(&self) -> &Arc<WhatsAppClient> {
+        &self.client
+    }
+
+    /// Sends `content`, enforcing the session window when tracked.
+    async fn send_tracked(&self, to: &str, content: &MessageContent) -> SendResult {
+        let Some(sessions) = &self.sessions else {
+            return send_result(self.client.send(to, content).await);
+        };
+        if !content.is_template() && !sessions.can_send_freeform(to).await {
+            return send_result(Err(WhatsAppError::SessionClosed {
+                user: to.to_string(),
+            }));
+        }
+        let result = self.client.send(to, content).await;
+        if let Err(e) = &result {
+            if e.kind() == WhatsAppErrorKind::OutsideSessionWindow {
+                if let Err(e) = sessions.close(to).await {
+                    warn!("Failed to close WhatsApp session for {}: {}", to, e);
+                }
+            }
+        }
+        send_result(result)
+    }
+}
+
+#[async_trait]
+impl BaseProviderAdapter for WhatsAppAdapter {
+    fn name(&self) -> String {
+        "whatsapp".to_string()
+    }
+
+    fn supports_sms(&self) -> bool {
+        false
+    }
+
+    fn supports_whatsapp(&self) -> bool {
+        true
+    }
+
+    async fn send_sms(
+        &self,
+        to: &str,
+        _from: &str,
+        body: &str,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        if remaining_validity(metadata.as_ref()) == Some(Duration::ZERO) {
+            return expired_result();
+        }
+        let template = metadata.as_ref().and_then(|m| m.get("whatsapp_template"));
+        let content = match template {
+            Some(template) => match serde_json::from_value(template.clone()) {
+                Ok(template) => MessageContent::Template { template },
+                Err(e) => {
+                    return failed(None, format!("Invalid whatsapp_template: {}", e), None);
+                }
+            },
+            None => MessageContent::text(body),
+        };
+        self.send_tracked(to, &content).await
+    }
+
+    async fn send_mms(
+        &self,
+        to: &str,
+        _from: &str,
+        text: Option<&str>,
+        media_urls: Vec<String>,
+        metadata: Option<HashMap<String, Value>>,
+    ) -> SendResult {
+        if remaining_validity(metadata.as_ref()) == Some(Duration::ZERO) {
+            return expired_result();
+        }
+        let Some(url) = media_urls.first() else {
+            return failed(None, "WhatsApp media message needs a media URL", None);
+        };
+        let kind = MediaKind::from_url(url);
+        let mut media = MediaObject::link(url);
+        if let Some(text) = text.filter(|_| !matches!(kind, MediaKind::Audio | MediaKind::Sticker))
+        {
+            media = media.with_caption(text);
+        }
+        self.send_tracked(to, &MessageContent::media(kind, media))
+            .await
+    }
+
+    async fn validate_webhook(&self, headers: &HashMap<String, String>, body: &[u8]) -> bool {
+        let Some(app_secret) = &self.client.config().app_secret else {
+            warn!("WhatsApp webhook rejected: no app secret configured");
+            return false;
+        };
+        header(headers, "X-Hub-Signature-256")
+            .is_some_and(|signature| webhook::verify_signature(app_secret, signature, body))
+    }
+
+    async fn parse_webhook(&self, body: &[u8]) -> Result<WebhookEvent, String> {
+        let raw: Value = serde_json::from_slice(body).unwrap_or(Value::Null);
+        webhook::parse_payload(body)?
+            .into_iter()
+            .find_map(|event| match event {
+                WhatsAppEvent::Status { update, .. } => {
+                    Some(update.to_webhook_event(Some(raw.clone())))
+                }
+                _ => None,
+            })
+            .ok_or_else(|| "WhatsApp webhook has no status".to_string())
+    }
+
+    async fn parse_inbound(&self, body: &[u8]) -> Result<InboundMessage, String> {
+        let raw: Value = serde_json::from_slice(body).unwrap_or(Value::Null);
+        let events = webhook::parse_payload(body)?;
+        if let Some(sessions) = &self.sessions {
+            sessions.record_events(&events).await;
+        }
+        events
+            .into_iter()
+            .find_map(|event| match event {
+                WhatsAppEvent::Message {
+                    display_phone_number,
+                    message,
+                    ..
+                } => Some(message.to_inbound_message(&display_phone_number, Some(raw.clone()))),
+                _ => None,
+            })
+            .ok_or_else(|| "WhatsApp webhook has no message".to_string())
+    }
+
+    async fn health_check(&self) -> bool {
+        self.client.health_check().await
+    }
+}
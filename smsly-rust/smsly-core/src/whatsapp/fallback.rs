@@ -0,0 +1,243 @@
+use super::webhook::WhatsAppEvent;
+use crate::adapters::{
+    Channel, MessageStatus, OutboundSms, ProviderRegistry, SendResult, WebhookEvent,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+/// Cloud API codes for recipients that can't be reached on WhatsApp at all.
+const NOT_ON_WHATSAPP: &[&str] = &["131026", "131021"];
+
+#[derive(Debug, Clone)]
+pub struct FallbackPolicy {
+    /// How long to wait for WhatsApp to report delivery before resending.
+    pub delivery_timeout: Duration,
+    /// Channels tried, in order, once WhatsApp gives up.
+    pub fallback_channels: Vec<Channel>,
+}
+
+impl Default for FallbackPolicy {
+    fn default() -> Self {
+        Self {
+            delivery_timeout: Duration::from_secs(60),
+            fallback_channels: vec![Channel::Sms],
+        }
+    }
+}
+
+impl FallbackPolicy {
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.delivery_timeout = timeout;
+        self
+    }
+
+    pub fn with_fallback_channels(mut self, channels: Vec<Channel>) -> Self {
+        self.fallback_channels = channels;
+        self
+    }
+}
+
+/// Why a message left WhatsApp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackReason {
+    /// No WhatsApp adapter could take the message for this country.
+    Unavailable,
+    /// The recipient has no WhatsApp account.
+    NotOnWhatsApp,
+    /// The send was refused outright.
+    Rejected,
+    /// WhatsApp accepted the message but reported it failed.
+    Failed,
+    /// No delivery report within `delivery_timeout`.
+    Timeout,
+}
+
+/// The one status the caller sees for a message, whichever channel carried
+/// it. `result` is the final attempt: for a delivered WhatsApp message its
+/// status is `Delivered`; after a fallback it's the fallback send.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallbackOutcome {
+    pub channel: Channel,
+    pub provider: String,
+    pub result: SendResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_reason: Option<FallbackReason>,
+    /// Set if WhatsApp accepted the message, even if it was later resent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub whatsapp_message_id: Option<String>,
+}
+
+impl FallbackOutcome {
+    pub fn status(&self) -> MessageStatus {
+        self.result.status.clone()
+    }
+}
+
+/// Sends over WhatsApp first and falls back to SMS (per `FallbackPolicy`)
+/// through the registry when the recipient isn't on WhatsApp, the send is
+/// refused, or no delivery report arrives in time.
+///
+/// WhatsApp status webhooks must be passed to `handle_status` or
+/// `handle_events`, or every accepted message runs into the timeout and is
+/// resent.
+pub struct ChannelFallback {
+    registry: Arc<ProviderRegistry>,
+    policy: FallbackPolicy,
+    pending: Mutex<HashMap<String, oneshot::Sender<WebhookEvent>>>,
+}
+
+impl ChannelFallback {
+    pub fn new(registry: Arc<ProviderRegistry>, policy: FallbackPolicy) -> Self {
+        Self {
+            registry,
+            policy,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn policy(&self) -> &FallbackPolicy {
+        &self.policy
+    }
+
+    /// Sends `message` and resolves once it's delivered on WhatsApp or handed
+    /// to a fallback channel, which can take up to `delivery_timeout`.
+    /// `None` if no channel could carry it.
+    pub async fn send(&self, country: &str, message: &OutboundSms) -> Option<FallbackOutcome> {
+        let attempt = self
+            .registry
+            .send_with_fallback(&[Channel::Whatsapp], country, message)
+            .await;
+
+        let (whatsapp, reason) = match attempt {
+            None => (None, FallbackReason::Unavailable),
+            Some((_, _, result)) if !result.success => {
+                let reason = if is_not_on_whatsapp(result.error_code.as_deref()) {
+                    FallbackReason::NotOnWhatsApp
+                } else {
+                    FallbackReason::Rejected
+                };
+                (None, reason)
+            }
+            Some((channel, provider, result)) => {
+                let Some(message_id) = result.provider_message_id.clone() else {
+                    // Nothing to match delivery reports against.
+                    return Some(FallbackOutcome {
+                        channel,
+                        provider,
+                        result,
+                        fallback_reason: None,
+                        whatsapp_message_id: None,
+                    });
+                };
+                match self.await_delivery(&message_id).await {
+                    Some(event) if event.status == MessageStatus::Delivered => {
+                        let mut result = result;
+                        result.status = MessageStatus::Delivered;
+                        return Some(FallbackOutcome {
+                            channel,
+                            provider,
+                            result,
+                            fallback_reason: None,
+                            whatsapp_message_id: Some(message_id),
+                        });
+                    }
+                    Some(event) => {
+                        let reason = if is_not_on_whatsapp(event.error_code.as_deref()) {
+                            FallbackReason::NotOnWhatsApp
+                        } else {
+                            FallbackReason::Failed
+                        };
+                        (Some((channel, provider, result, message_id)), reason)
+                    }
+                    None => (
+                        Some((channel, provider, result, message_id)),
+                        FallbackReason::Timeout,
+                    ),
+                }
+            }
+        };
+
+        info!(
+            "WhatsApp message to {} falling back ({:?})",
+            message.to, reason
+        );
+        let whatsapp_message_id = whatsapp.as_ref().map(|(.., id)| id.clone());
+        match self
+            .registry
+            .send_with_fallback(&self.policy.fallback_channels, country, message)
+            .await
+        {
+            Some((channel, provider, result)) => Some(FallbackOutcome {
+                channel,
+                provider,
+                result,
+                fallback_reason: Some(reason),
+                whatsapp_message_id,
+            }),
+            None => {
+                warn!("No fallback channel for message to {}", message.to);
+                // Report the WhatsApp attempt, which may still be delivered.
+                whatsapp.map(|(channel, provider, result, id)| FallbackOutcome {
+                    channel,
+                    provider,
+                    result,
+                    fallback_reason: Some(reason),
+                    whatsapp_message_id: Some(id),
+                })
+            }
+        }
+    }
+
+    async fn await_delivery(&self, message_id: &str) -> Option<WebhookEvent> {
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(message_id.to_string(), tx);
+        match tokio::time::timeout(self.policy.delivery_timeout, rx).await {
+            Ok(Ok(event)) => Some(event),
+            _ => {
+                self.pending.lock().unwrap().remove(message_id);
+                None
+            }
+        }
+    }
+
+    /// Feeds a WhatsApp delivery report to the send waiting on it. Returns
+    /// whether one was; `sent` and other interim statuses are ignored.
+    pub fn handle_status(&self, event: &WebhookEvent) -> bool {
+        if !matches!(
+            event.status,
+            MessageStatus::Delivered | MessageStatus::Failed | MessageStatus::Rejected
+        ) {
+            return false;
+        }
+        let waiter = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(&event.provider_message_id);
+        match waiter {
+            Some(tx) => tx.send(event.clone()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// `handle_status` for every status update in a webhook delivery.
+    pub fn handle_events(&self, events: &[WhatsAppEvent]) {
+        for event in events {
+            if let WhatsAppEvent::Status { update, .. } = event {
+                self.handle_status(&update.to_webhook_event(None));
+            }
+        }
+    }
+}
+
+fn is_not_on_whatsapp(code: Option<&str>) -> bool {
+    code.is_some_and(|code| NOT_ON_WHATSAPP.contains(&code))
+}
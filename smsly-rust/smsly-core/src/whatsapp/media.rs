@@ -0,0 +1,411 @@
+use super::client::{
+    MediaKind, MediaObject, MessageContent, SentMessage, WhatsAppClient, WhatsAppError,
+};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::warn;
+
+/// Media IDs expire 30 days after upload; renew a day early.
+pub const MEDIA_ID_TTL: Duration = Duration::from_secs(29 * 24 * 3600);
+
+/// Attempts per resumable upload before giving up.
+const RESUME_ATTEMPTS: usize = 3;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MediaError {
+    #[error("Media file is empty")]
+    Empty,
+    #[error("{kind:?} media is {size} bytes, over the {max} byte limit")]
+    TooLarge {
+        kind: MediaKind,
+        size: u64,
+        max: u64,
+    },
+    #[error("{kind:?} media can't be {mime_type}")]
+    UnsupportedType { kind: MediaKind, mime_type: String },
+    #[error("Downloaded media {media_id} doesn't match its checksum")]
+    ChecksumMismatch { media_id: String },
+    #[error(transparent)]
+    Api(#[from] WhatsAppError),
+}
+
+/// Cloud API size limit and accepted MIME types per kind. An empty list
+/// accepts any type.
+pub fn limits(kind: MediaKind) -> (u64, &'static [&'static str]) {
+    const MB: u64 = 1024 * 1024;
+    match kind {
+        MediaKind::Image => (5 * MB, &["image/jpeg", "image/png"]),
+        MediaKind::Video => (16 * MB, &["video/mp4", "video/3gpp"]),
+        MediaKind::Audio => (
+            16 * MB,
+            &[
+                "audio/aac",
+                "audio/amr",
+                "audio/mpeg",
+                "audio/mp4",
+                "audio/ogg",
+            ],
+        ),
+        MediaKind::Document => (100 * MB, &[]),
+        // Animated stickers may be 500 KB, static ones 100 KB.
+        MediaKind::Sticker => (500 * 1024, &["image/webp"]),
+    }
+}
+
+/// Checks a file against the limits for `kind` before it's uploaded or
+/// after it's downloaded.
+pub fn validate(kind: MediaKind, size: u64, mime_type: &str) -> Result<(), MediaError> {
+    if size == 0 {
+        return Err(MediaError::Empty);
+    }
+    let (max, types) = limits(kind);
+    if size > max {
+        return Err(MediaError::TooLarge { kind, size, max });
+    }
+    // Drop parameters such as `; codecs=opus`.
+    let base = mime_type.split(';').next().unwrap_or_default().trim();
+    if !types.is_empty() && !types.iter().any(|t| t.eq_ignore_ascii_case(base)) {
+        return Err(MediaError::UnsupportedType {
+            kind,
+            mime_type: mime_type.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// A file to upload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalMedia {
+    pub kind: MediaKind,
+    pub mime_type: String,
+    /// Shown to recipients for documents.
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl LocalMedia {
+    pub fn new(kind: MediaKind, mime_type: &str, data: Vec<u8>) -> Self {
+        Self {
+            kind,
+            mime_type: mime_type.to_string(),
+            filename: None,
+            data,
+        }
+    }
+
+    pub fn with_filename(mut self, filename: &str) -> Self {
+        self.filename = Some(filename.to_string());
+        self
+    }
+
+    /// Hex SHA-256 of the contents, which keys cached media IDs.
+    pub fn digest(&self) -> String {
+        hex::encode(Sha256::digest(&self.data))
+    }
+
+    fn validate(&self) -> Result<(), MediaError> {
+        validate(self.kind, self.data.len() as u64, &self.mime_type)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadedMedia {
+    pub media_id: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Uploads and downloads WhatsApp media. Uploaded IDs are remembered by
+/// content hash and reused until they near expiry, then the file is
+/// uploaded again; `send` also re-uploads once if Meta rejects a cached ID.
+pub struct MediaManager {
+    client: Arc<WhatsAppClient>,
+    ttl: Duration,
+    ids: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl MediaManager {
+    pub fn new(client: Arc<WhatsAppClient>) -> Self {
+        Self {
+            client,
+            ttl: MEDIA_ID_TTL,
+            ids: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn ids(&self) -> std::sync::MutexGuard<'_, HashMap<String, (String, Instant)>> {
+        self.ids.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Uploads `media` for use in outbound messages and returns its ID.
+    pub async fn upload(&self, media: &LocalMedia) -> Result<String, MediaError> {
+        media.validate()?;
+        let boundary = format!("smsly-{}", uuid::Uuid::new_v4().simple());
+        let data = self
+            .client
+            .execute(
+                self.client
+                    .http()
+                    .post(
+                        self.client
+                            .url(&format!("{}/media", self.client.config().phone_number_id)),
+                    )
+                    .header(
+                        CONTENT_TYPE,
+                        format!("multipart/form-data; boundary={}", boundary),
+                    )
+                    .body(multipart_body(&boundary, media)),
+            )
+            .await?;
+        let id = data["id"]
+            .as_str()
+            .ok_or_else(|| WhatsAppError::InvalidResponse("missing media id".to_string()))?
+            .to_string();
+        self.ids()
+            .insert(media.digest(), (id.clone(), Instant::now()));
+        Ok(id)
+    }
+
+    /// A usable ID for `media`: the cached one while fresh, otherwise a new
+    /// upload.
+    pub async fn media_id(&self, media: &LocalMedia) -> Result<String, MediaError> {
+        let cached = self
+            .ids()
+            .get(&media.digest())
+            .filter(|(_, uploaded)| uploaded.elapsed() < self.ttl)
+            .map(|(id, _)| id.clone());
+        match cached {
+            Some(id) => Ok(id),
+            None => self.upload(media).await,
+        }
+    }
+
+    /// Drops the cached ID for `media` so the next use uploads it again.
+    pub fn forget(&self, media: &LocalMedia) {
+        self.ids().remove(&media.digest());
+    }
+
+    /// Sends `media` to `to` by ID, uploading it if needed.
+    pub async fn send(
+        &self,
+        to: &str,
+        media: &LocalMedia,
+        caption: Option<&str>,
+    ) -> Result<SentMessage, MediaError> {
+        let content = |id: &str| {
+            let mut object = MediaObject::id(id);
+            if !matches!(media.kind, MediaKind::Audio | MediaKind::Sticker) {
+                object.caption = caption.map(str::to_string);
+            }
+            if media.kind == MediaKind::Document {
+                object.filename = media.filename.clone();
+            }
+            MessageContent::media(media.kind, object)
+        };
+        let id = self.media_id(media).await?;
+        match self.client.send(to, &content(&id)).await {
+            Err(e) if rejects_media_id(&e) => {
+                warn!("WhatsApp media {} rejected ({}); uploading again", id, e);
+                self.forget(media);
+                let id = self.upload(media).await?;
+                Ok(self.client.send(to, &content(&id)).await?)
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// Fetches media by ID, such as an inbound attachment. Its size is
+    /// checked against the limits for `kind` before downloading, and the
+    /// contents against Meta's checksum after.
+    pub async fn download(
+        &self,
+        media_id: &str,
+        kind: Option<MediaKind>,
+    ) -> Result<DownloadedMedia, MediaError> {
+        let info = self
+            .client
+            .execute(self.client.http().get(self.client.url(media_id)))
+            .await?;
+        let url = info["url"]
+            .as_str()
+            .ok_or_else(|| WhatsAppError::InvalidResponse("missing media url".to_string()))?;
+        let mime_type = info["mime_type"].as_str().unwrap_or_default().to_string();
+        let declared = match &info["file_size"] {
+            Value::String(size) => size.parse().ok(),
+            size => size.as_u64(),
+        };
+        if let (Some(kind), Some(size)) = (kind, declared) {
+            validate(kind, size, &mime_type)?;
+        }
+
+        // The URL is short-lived and needs the same access token.
+        let response = self
+            .client
+            .authorized(self.client.http().get(url))
+            .send()
+            .await
+            .map_err(|e| WhatsAppError::Request(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(WhatsAppError::InvalidResponse(format!(
+                "media download returned HTTP {}",
+                response.status()
+            ))
+            .into());
+        }
+        let data = response
+            .bytes()
+            .await
+            .map_err(|e| WhatsAppError::Request(e.to_string()))?
+            .to_vec();
+        if let Some(kind) = kind {
+            validate(kind, data.len() as u64, &mime_type)?;
+        }
+        if let Some(expected) = info["sha256"].as_str() {
+            if !hex::encode(Sha256::digest(&data)).eq_ignore_ascii_case(expected) {
+                return Err(MediaError::ChecksumMismatch {
+                    media_id: media_id.to_string(),
+                });
+            }
+        }
+        Ok(DownloadedMedia {
+            media_id: media_id.to_string(),
+            mime_type,
+            data,
+        })
+    }
+
+    /// Uploads through the Resumable Upload API under the Meta app
+    /// `app_id`, returning the file handle template definitions take for
+    /// header examples. An interrupted transfer resumes from the offset
+    /// Meta reports.
+    pub async fn upload_resumable(
+        &self,
+        app_id: &str,
+        media: &LocalMedia,
+    ) -> Result<String, MediaError> {
+        media.validate()?;
+        let session = self
+            .client
+            .execute(
+                self.client
+                    .http()
+                    .post(self.client.url(&format!("{}/uploads", app_id)))
+                    .query(&[
+                        (
+                            "file_name",
+                            media.filename.clone().unwrap_or_else(|| media.digest()),
+                        ),
+                        ("file_length", media.data.len().to_string()),
+                        ("file_type", media.mime_type.clone()),
+                    ]),
+            )
+            .await?;
+        let upload_id = session["id"]
+            .as_str()
+            .ok_or_else(|| WhatsAppError::InvalidResponse("missing upload id".to_string()))?;
+        self.resume_upload(upload_id, media).await
+    }
+
+    /// Continues an upload session from wherever Meta has it.
+    pub async fn resume_upload(
+        &self,
+        upload_id: &str,
+        media: &LocalMedia,
+    ) -> Result<String, MediaError> {
+        let auth = format!("OAuth {}", self.client.config().access_token);
+        let url = self.client.url(upload_id);
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let status = self
+                .client
+                .send_json(self.client.http().get(&url).header(AUTHORIZATION, &auth))
+                .await?;
+            let offset =
+                (status["file_offset"].as_u64().unwrap_or(0) as usize).min(media.data.len());
+            let result = self
+                .client
+                .send_json(
+                    self.client
+                        .http()
+                        .post(&url)
+                        .header(AUTHORIZATION, &auth)
+                        .header("file_offset", offset.to_string())
+                        .body(media.data[offset..].to_vec()),
+                )
+                .await;
+            match result {
+                Ok(data) => {
+                    return data["h"].as_str().map(str::to_string).ok_or_else(|| {
+                        WhatsAppError::InvalidResponse("missing upload handle".to_string()).into()
+                    })
+                }
+                Err(WhatsAppError::Request(e)) if attempt < RESUME_ATTEMPTS => {
+                    warn!(
+                        "Upload {} interrupted at {}: {}; resuming",
+                        upload_id, offset, e
+                    );
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Whether a send failed because the media ID is unknown or expired.
+fn rejects_media_id(error: &WhatsAppError) -> bool {
+    match error {
+        WhatsAppError::Api { code: 131053, .. } => true,
+        WhatsAppError::Api {
+            code: 100,
+            message,
+            details,
+            ..
+        } => {
+            let text = format!("{} {}", message, details.as_deref().unwrap_or_default());
+            text.to_lowercase().contains("media")
+        }
+        _ => false,
+    }
+}
+
+fn multipart_body(boundary: &str, media: &LocalMedia) -> Vec<u8> {
+    let filename = media
+        .filename
+        .clone()
+        .unwrap_or_else(|| format!("{:?}", media.kind).to_lowercase())
+        .replace('"', "");
+    let mut body = Vec::with_capacity(media.data.len() + 512);
+    for (name, value) in [
+        ("messaging_product", "whatsapp"),
+        ("type", media.mime_type.as_str()),
+    ] {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                boundary, name, value
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+            boundary, filename, media.mime_type
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(&media.data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
@@ -0,0 +1,21 @@
+//! WhatsApp Business messaging through Meta's Cloud API.
+
+pub mod client;
+pub mod fallback;
+pub mod media;
+pub mod quality;
+pub mod session;
+pub mod templates;
+pub mod webhook;
+
+pub use client::{
+    MediaKind, MediaObject, MessageContent, SentMessage, Template, TemplateComponent,
+    TemplateParameter, WhatsAppAdapter, WhatsAppClient, WhatsAppConfig, WhatsAppError,
+    WhatsAppErrorKind,
+};
+pub use fallback::{ChannelFallback, FallbackOutcome, FallbackPolicy, FallbackReason};
+pub use media::{LocalMedia, MediaManager};
+pub use quality::{MessagingTier, NumberQuality, QualityMonitor, QualityRating};
+pub use session::SessionTracker;
+pub use templates::{TemplateCatalog, TemplateDefinition, TemplateError, TemplateStatus};
+pub use webhook::{InboundContent, StatusUpdate, WhatsAppEvent};
@@ -0,0 +1,287 @@
+use super::client::{WhatsAppClient, WhatsAppError};
+use crate::health::{ComponentHealth, HealthCheck};
+use crate::metrics::GLOBAL_METRICS;
+use crate::providers::signature::now_secs;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+const FIELDS: &str =
+    "id,display_phone_number,verified_name,quality_rating,messaging_limit_tier,status";
+
+/// Meta's rating of recent messages from a number; it drops with blocks and
+/// reports and, at red, the number is flagged and its tier may be lowered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum QualityRating {
+    Green,
+    Yellow,
+    Red,
+    #[serde(other)]
+    Unknown,
+}
+
+impl QualityRating {
+    fn gauge(self) -> f64 {
+        match self {
+            QualityRating::Green => 0.0,
+            QualityRating::Yellow => 1.0,
+            QualityRating::Red => 2.0,
+            QualityRating::Unknown => -1.0,
+        }
+    }
+}
+
+/// How many unique users a number may start conversations with per day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessagingTier {
+    #[serde(rename = "TIER_50")]
+    Tier50,
+    #[serde(rename = "TIER_250")]
+    Tier250,
+    #[serde(rename = "TIER_1K")]
+    Tier1K,
+    #[serde(rename = "TIER_10K")]
+    Tier10K,
+    #[serde(rename = "TIER_100K")]
+    Tier100K,
+    #[serde(rename = "TIER_UNLIMITED")]
+    Unlimited,
+    #[serde(other)]
+    Unknown,
+}
+
+impl MessagingTier {
+    /// `None` for unlimited or unknown tiers.
+    pub fn daily_limit(self) -> Option<u32> {
+        match self {
+            MessagingTier::Tier50 => Some(50),
+            MessagingTier::Tier250 => Some(250),
+            MessagingTier::Tier1K => Some(1_000),
+            MessagingTier::Tier10K => Some(10_000),
+            MessagingTier::Tier100K => Some(100_000),
+            MessagingTier::Unlimited | MessagingTier::Unknown => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NumberQuality {
+    #[serde(rename = "id")]
+    pub phone_number_id: String,
+    #[serde(default)]
+    pub display_phone_number: Option<String>,
+    #[serde(default)]
+    pub verified_name: Option<String>,
+    #[serde(default = "unknown_rating")]
+    pub quality_rating: QualityRating,
+    #[serde(default)]
+    pub messaging_limit_tier: Option<MessagingTier>,
+    /// `CONNECTED`, `FLAGGED`, `RESTRICTED`, …
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub checked_at: i64,
+}
+
+fn unknown_rating() -> QualityRating {
+    QualityRating::Unknown
+}
+
+impl NumberQuality {
+    /// Red, or flagged or restricted by Meta; campaigns on the number should
+    /// be paused until it recovers.
+    pub fn is_low_quality(&self) -> bool {
+        self.quality_rating == QualityRating::Red
+            || matches!(self.status.as_deref(), Some("FLAGGED" | "RESTRICTED"))
+    }
+}
+
+/// Polls quality rating and messaging tier for the client's phone number,
+/// or every number of the business account when one is configured, and
+/// publishes the latest snapshot keyed by phone number ID.
+///
+/// Also a non-critical `HealthCheck` (`whatsapp_quality`), degraded while
+/// any number is low quality, and records `whatsapp_quality_rating`
+/// (0 green, 1 yellow, 2 red) and `whatsapp_messaging_limit` gauges.
+pub struct QualityMonitor {
+    client: Arc<WhatsAppClient>,
+    interval: Duration,
+    tx: watch::Sender<HashMap<String, NumberQuality>>,
+}
+
+impl QualityMonitor {
+    pub fn new(client: Arc<WhatsAppClient>, interval: Duration) -> Self {
+        let (tx, _) = watch::channel(HashMap::new());
+        Self {
+            client,
+            interval,
+            tx,
+        }
+    }
+
+    /// Empty until the first successful poll.
+    pub fn subscribe(&self) -> watch::Receiver<HashMap<String, NumberQuality>> {
+        self.tx.subscribe()
+    }
+
+    pub fn latest(&self) -> HashMap<String, NumberQuality> {
+        self.tx.borrow().clone()
+    }
+
+    /// Whether sending from `phone_number_id` should be paused. Numbers not
+    /// polled yet aren't flagged.
+    pub fn is_flagged(&self, phone_number_id: &str) -> bool {
+        self.tx
+            .borrow()
+            .get(phone_number_id)
+            .is_some_and(NumberQuality::is_low_quality)
+    }
+
+    async fn fetch(&self) -> Result<Vec<NumberQuality>, WhatsAppError> {
+        let config = self.client.config();
+        let Some(account) = &config.business_account_id else {
+            let data = self
+                .client
+                .execute(
+                    self.client
+                        .http()
+                        .get(self.client.url(&config.phone_number_id))
+                        .query(&[("fields", FIELDS)]),
+                )
+                .await?;
+            return parse_number(data).map(|number| vec![number]);
+        };
+
+        let mut url = self.client.url(&format!("{}/phone_numbers", account));
+        let mut query = vec![("fields", FIELDS)];
+        let mut numbers = Vec::new();
+        loop {
+            let data = self
+                .client
+                .execute(self.client.http().get(&url).query(&query))
+                .await?;
+            if let Some(page) = data["data"].as_array() {
+                for number in page {
+                    numbers.push(parse_number(number.clone())?);
+                }
+            }
+            match data["paging"]["next"].as_str() {
+                Some(next) => {
+                    url = next.to_string();
+                    query.clear();
+                }
+                None => return Ok(numbers),
+            }
+        }
+    }
+
+    /// Polls now and publishes the result; the previous snapshot is kept if
+    /// the poll fails.
+    pub async fn refresh(&self) -> Result<HashMap<String, NumberQuality>, WhatsAppError> {
+        let numbers = self.fetch().await.inspect_err(|e| {
+            error!("WhatsApp quality poll failed: {}", e);
+        })?;
+        let previous = self.latest();
+        let mut snapshot = HashMap::new();
+        for mut number in numbers {
+            number.checked_at = now_secs();
+            record(previous.get(&number.phone_number_id), &number);
+            snapshot.insert(number.phone_number_id.clone(), number);
+        }
+        self.tx.send_replace(snapshot.clone());
+        Ok(snapshot)
+    }
+
+    /// Refreshes on the configured interval until the task is aborted.
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                let _ = self.refresh().await;
+            }
+        })
+    }
+}
+
+fn parse_number(data: Value) -> Result<NumberQuality, WhatsAppError> {
+    serde_json::from_value(data).map_err(|e| WhatsAppError::InvalidResponse(e.to_string()))
+}
+
+fn record(previous: Option<&NumberQuality>, number: &NumberQuality) {
+    let labels = Some(HashMap::from([(
+        "phone_number_id".to_string(),
+        number.phone_number_id.clone(),
+    )]));
+    GLOBAL_METRICS.set_gauge(
+        "whatsapp_quality_rating",
+        number.quality_rating.gauge(),
+        labels.clone(),
+    );
+    if let Some(limit) = number
+        .messaging_limit_tier
+        .and_then(MessagingTier::daily_limit)
+    {
+        GLOBAL_METRICS.set_gauge("whatsapp_messaging_limit", limit as f64, labels);
+    }
+
+    let Some(previous) = previous else {
+        return;
+    };
+    if previous.quality_rating != number.quality_rating
+        || previous.messaging_limit_tier != number.messaging_limit_tier
+    {
+        if number.is_low_quality() {
+            warn!(
+                phone_number_id = %number.phone_number_id,
+                rating = ?number.quality_rating,
+                tier = ?number.messaging_limit_tier,
+                "WhatsApp number flagged for low quality"
+            );
+        } else {
+            info!(
+                phone_number_id = %number.phone_number_id,
+                rating = ?number.quality_rating,
+                tier = ?number.messaging_limit_tier,
+                "WhatsApp number quality changed"
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for QualityMonitor {
+    fn name(&self) -> String {
+        "whatsapp_quality".to_string()
+    }
+
+    fn critical(&self) -> bool {
+        false
+    }
+
+    async fn check(&self) -> ComponentHealth {
+        let snapshot = self.latest();
+        let mut details = Map::new();
+        let mut flagged = Vec::new();
+        for (id, number) in &snapshot {
+            details.insert(id.clone(), serde_json::to_value(number).unwrap_or_default());
+            if number.is_low_quality() {
+                flagged.push(id.as_str());
+            }
+        }
+        flagged.sort();
+        ComponentHealth {
+            status: if flagged.is_empty() { "ok" } else { "degraded" }.to_string(),
+            latency_ms: None,
+            error: (!flagged.is_empty()).then(|| format!("low quality: {}", flagged.join(", "))),
+            details: Some(details.into()),
+        }
+    }
+}
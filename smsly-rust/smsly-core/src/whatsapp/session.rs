@@ -0,0 +1,145 @@
+use super::webhook::WhatsAppEvent;
+use crate::providers::signature::now_secs;
+use lazy_static::lazy_static;
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Client, Script};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+/// Meta's customer service window: free-form messages are allowed for 24
+/// hours after the user's last message.
+pub const SESSION_WINDOW: Duration = Duration::from_secs(24 * 3600);
+
+lazy_static! {
+    /// Stores the inbound timestamp unless a later one is already there,
+    /// since webhooks can arrive out of order.
+    static ref RECORD_INBOUND: Script = Script::new(
+        r#"
+        local current = tonumber(redis.call("GET", KEYS[1]))
+        if current == nil or current < tonumber(ARGV[1]) then
+            redis.call("SET", KEYS[1], ARGV[1], "EX", ARGV[2])
+        end
+        return 0
+    "#
+    );
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub window: Duration,
+    pub key_prefix: String,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            window: SESSION_WINDOW,
+            key_prefix: "smsly:wa:session".to_string(),
+        }
+    }
+}
+
+/// Tracks the 24-hour customer service window per user of one business
+/// number, from the user's last inbound message. Outside the window only
+/// templates may be sent; free-form sends there fail with error 131047
+/// and count against the number's quality.
+///
+/// If Redis is unreachable the window is treated as closed, so callers
+/// fall back to templates rather than risk the number.
+pub struct SessionTracker {
+    client: Client,
+    conn: OnceCell<ConnectionManager>,
+    phone_number_id: String,
+    config: SessionConfig,
+}
+
+impl SessionTracker {
+    pub fn new(client: Client, phone_number_id: &str, config: SessionConfig) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            phone_number_id: phone_number_id.to_string(),
+            config,
+        }
+    }
+
+    fn key(&self, user: &str) -> String {
+        format!(
+            "{}:{}:{}",
+            self.config.key_prefix,
+            self.phone_number_id,
+            user.trim_start_matches('+')
+        )
+    }
+
+    async fn conn(&self) -> redis::RedisResult<ConnectionManager> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+
+    /// Opens (or extends) the window from a message `user` sent at
+    /// `timestamp` (Unix seconds).
+    pub async fn record_inbound(&self, user: &str, timestamp: i64) -> redis::RedisResult<()> {
+        let remaining = self.config.window.as_secs() as i64 - (now_secs() - timestamp);
+        if remaining <= 0 {
+            return Ok(());
+        }
+        RECORD_INBOUND
+            .key(self.key(user))
+            .arg(timestamp)
+            .arg(remaining)
+            .invoke_async(&mut self.conn().await?)
+            .await
+    }
+
+    /// Records every inbound message to this number in a webhook delivery.
+    pub async fn record_events(&self, events: &[WhatsAppEvent]) {
+        for event in events {
+            let WhatsAppEvent::Message {
+                phone_number_id,
+                message,
+                ..
+            } = event
+            else {
+                continue;
+            };
+            if *phone_number_id != self.phone_number_id {
+                continue;
+            }
+            let timestamp = message.timestamp.unwrap_or_else(now_secs);
+            if let Err(e) = self.record_inbound(&message.from, timestamp).await {
+                warn!(
+                    "Failed to record WhatsApp session for {}: {}",
+                    message.from, e
+                );
+            }
+        }
+    }
+
+    /// When the window with `user` closes (Unix seconds), if it's open.
+    pub async fn window_closes_at(&self, user: &str) -> redis::RedisResult<Option<i64>> {
+        let last: Option<i64> = self.conn().await?.get(self.key(user)).await?;
+        Ok(last
+            .map(|last| last + self.config.window.as_secs() as i64)
+            .filter(|closes| *closes > now_secs()))
+    }
+
+    /// Whether a non-template message may be sent to `user` now.
+    pub async fn can_send_freeform(&self, user: &str) -> bool {
+        match self.window_closes_at(user).await {
+            Ok(closes) => closes.is_some(),
+            Err(e) => {
+                warn!("Redis unavailable for WhatsApp sessions: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Forgets the window, e.g. after Meta rejects a send with 131047.
+    pub async fn close(&self, user: &str) -> redis::RedisResult<()> {
+        self.conn().await?.del(self.key(user)).await
+    }
+}
@@ -0,0 +1,399 @@
+use super::client::{
+    ComponentType, SentMessage, Template, TemplateComponent, WhatsAppClient, WhatsAppError,
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+lazy_static! {
+    static ref PLACEHOLDER: Regex = Regex::new(r"\{\{\s*([A-Za-z0-9_]+)\s*\}\}").unwrap();
+}
+
+/// How long definitions are trusted before the catalog refetches them.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum TemplateError {
+    #[error("WhatsApp business account ID not configured")]
+    MissingBusinessAccount,
+    #[error("Template {name} ({language}) not found")]
+    NotFound { name: String, language: String },
+    #[error("Template {name} is {status:?}, not approved")]
+    NotApproved {
+        name: String,
+        status: TemplateStatus,
+    },
+    #[error("Template {name} {component} expects {expected} parameters, got {actual}")]
+    ParameterCount {
+        name: String,
+        component: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error(transparent)]
+    Api(#[from] WhatsAppError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TemplateCategory {
+    Marketing,
+    Utility,
+    Authentication,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TemplateStatus {
+    Approved,
+    Pending,
+    Rejected,
+    /// Paused by Meta after poor recipient feedback.
+    Paused,
+    Disabled,
+    InAppeal,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateButton {
+    /// `QUICK_REPLY`, `URL`, `PHONE_NUMBER`, `COPY_CODE`, `OTP`, ...
+    #[serde(rename = "type")]
+    pub button_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// A part of a template as defined, with `{{1}}` style placeholders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateDefinitionComponent {
+    /// `HEADER`, `BODY`, `FOOTER` or `BUTTONS`.
+    #[serde(rename = "type")]
+    pub component_type: String,
+    /// Headers only: `TEXT`, `IMAGE`, `VIDEO`, `DOCUMENT` or `LOCATION`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<TemplateButton>,
+}
+
+/// A template as registered with the business account.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateDefinition {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub language: String,
+    pub status: TemplateStatus,
+    pub category: TemplateCategory,
+    #[serde(default)]
+    pub components: Vec<TemplateDefinitionComponent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejected_reason: Option<String>,
+}
+
+/// Number of distinct placeholders, `{{1}}` or named `{{order_id}}`.
+pub fn variable_count(text: &str) -> usize {
+    PLACEHOLDER
+        .captures_iter(text)
+        .map(|c| c[1].to_string())
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+impl TemplateDefinition {
+    /// Parameters each part takes when sending, keyed by component type
+    /// and button index. Parts without placeholders are omitted, as are
+    /// quick-reply payloads, which are optional.
+    pub fn expected_parameters(&self) -> HashMap<(ComponentType, Option<usize>), usize> {
+        let mut expected = HashMap::new();
+        for component in &self.components {
+            let text = component.text.as_deref().unwrap_or_default();
+            match component.component_type.to_uppercase().as_str() {
+                "HEADER" => {
+                    let count = match component.format.as_deref().unwrap_or("TEXT") {
+                        "TEXT" => variable_count(text),
+                        "IMAGE" | "VIDEO" | "DOCUMENT" => 1,
+                        _ => 0,
+                    };
+                    expected.insert((ComponentType::Header, None), count);
+                }
+                "BODY" => {
+                    expected.insert((ComponentType::Body, None), variable_count(text));
+                }
+                "BUTTONS" => {
+                    for (index, button) in component.buttons.iter().enumerate() {
+                        let count = match button.button_type.to_uppercase().as_str() {
+                            "URL" => variable_count(button.url.as_deref().unwrap_or_default()),
+                            "COPY_CODE" | "OTP" => 1,
+                            _ => continue,
+                        };
+                        expected.insert((ComponentType::Button, Some(index)), count);
+                    }
+                }
+                _ => {}
+            }
+        }
+        expected.retain(|_, count| *count > 0);
+        expected
+    }
+
+    /// Checks `components` supply exactly the parameters this template
+    /// takes, so a mismatch fails here rather than as Meta error 132000.
+    pub fn validate(&self, components: &[TemplateComponent]) -> Result<(), TemplateError> {
+        let mut provided: HashMap<(ComponentType, Option<usize>), usize> = HashMap::new();
+        for component in components {
+            if component.sub_type.as_deref() == Some("quick_reply") {
+                continue;
+            }
+            let index = component.index.as_deref().and_then(|i| i.parse().ok());
+            *provided
+                .entry((component.component_type, index))
+                .or_default() += component.parameters.len();
+        }
+        let expected = self.expected_parameters();
+        for key in expected.keys().chain(provided.keys()) {
+            let expected = expected.get(key).copied().unwrap_or_default();
+            let actual = provided.get(key).copied().unwrap_or_default();
+            if expected != actual {
+                let component = match key {
+                    (_, Some(index)) => format!("button {}", index),
+                    (kind, None) => format!("{:?}", kind).to_lowercase(),
+                };
+                return Err(TemplateError::ParameterCount {
+                    name: self.name.clone(),
+                    component,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A template to submit for review.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewTemplate {
+    /// Lowercase letters, digits and underscores.
+    pub name: String,
+    pub language: String,
+    pub category: TemplateCategory,
+    pub components: Vec<TemplateDefinitionComponent>,
+    /// How long Meta keeps trying to deliver messages sent with this
+    /// template; the Cloud API has no per-message TTL. Authentication and
+    /// utility templates only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_send_ttl_seconds: Option<u32>,
+}
+
+struct CachedTemplates {
+    loaded: Instant,
+    by_name: HashMap<(String, String), TemplateDefinition>,
+}
+
+/// Creates, lists and caches the business account's message templates.
+pub struct TemplateCatalog {
+    client: Arc<WhatsAppClient>,
+    ttl: Duration,
+    cache: RwLock<Option<CachedTemplates>>,
+}
+
+impl TemplateCatalog {
+    pub fn new(client: Arc<WhatsAppClient>) -> Self {
+        Self {
+            client,
+            ttl: DEFAULT_CACHE_TTL,
+            cache: RwLock::new(None),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn templates_url(&self) -> Result<String, TemplateError> {
+        let account = self
+            .client
+            .config()
+            .business_account_id
+            .as_deref()
+            .ok_or(TemplateError::MissingBusinessAccount)?;
+        Ok(self.client.url(&format!("{}/message_templates", account)))
+    }
+
+    /// Submits a template for review; returns its ID and initial status.
+    pub async fn create(
+        &self,
+        template: &NewTemplate,
+    ) -> Result<(String, TemplateStatus), TemplateError> {
+        let url = self.templates_url()?;
+        let data = self
+            .client
+            .execute(self.client.http().post(url).json(template))
+            .await?;
+        self.invalidate().await;
+        let status =
+            serde_json::from_value(data["status"].clone()).unwrap_or(TemplateStatus::Pending);
+        Ok((data["id"].as_str().unwrap_or_default().to_string(), status))
+    }
+
+    /// Every template in the account, following pagination. `name`
+    /// narrows the listing to one template's languages.
+    pub async fn list(&self, name: Option<&str>) -> Result<Vec<TemplateDefinition>, TemplateError> {
+        let mut url = self.templates_url()?;
+        let mut query = vec![
+            (
+                "fields",
+                "id,name,language,status,category,components,rejected_reason".to_string(),
+            ),
+            ("limit", "100".to_string()),
+        ];
+        if let Some(name) = name {
+            query.push(("name", name.to_string()));
+        }
+        let mut templates = Vec::new();
+        loop {
+            let data = self
+                .client
+                .execute(self.client.http().get(&url).query(&query))
+                .await?;
+            if let Some(page) = data["data"].as_array() {
+                templates.extend(
+                    page.iter().filter_map(|t| {
+                        serde_json::from_value::<TemplateDefinition>(t.clone()).ok()
+                    }),
+                );
+            }
+            // `next` already carries the query and cursor.
+            match data["paging"]["next"].as_str() {
+                Some(next) => {
+                    url = next.to_string();
+                    query.clear();
+                }
+                None => return Ok(templates),
+            }
+        }
+    }
+
+    /// Current review status, fetched fresh rather than from the cache.
+    pub async fn status(
+        &self,
+        name: &str,
+        language: &str,
+    ) -> Result<TemplateStatus, TemplateError> {
+        let template = self
+            .list(Some(name))
+            .await?
+            .into_iter()
+            .find(|t| t.name == name && t.language == language)
+            .ok_or_else(|| TemplateError::NotFound {
+                name: name.to_string(),
+                language: language.to_string(),
+            })?;
+        Ok(template.status)
+    }
+
+    /// Reloads every definition into the cache.
+    pub async fn refresh(&self) -> Result<(), TemplateError> {
+        let by_name = self
+            .list(None)
+            .await?
+            .into_iter()
+            .map(|t| ((t.name.clone(), t.language.clone()), t))
+            .collect();
+        *self.cache.write().await = Some(CachedTemplates {
+            loaded: Instant::now(),
+            by_name,
+        });
+        Ok(())
+    }
+
+    pub async fn invalidate(&self) {
+        *self.cache.write().await = None;
+    }
+
+    /// The cached definition, refreshing the cache when stale or when the
+    /// template isn't in it (it may have been created since).
+    pub async fn get(
+        &self,
+        name: &str,
+        language: &str,
+    ) -> Result<TemplateDefinition, TemplateError> {
+        let key = (name.to_string(), language.to_string());
+        let lookup = |cache: &Option<CachedTemplates>| {
+            cache
+                .as_ref()
+                .filter(|cache| cache.loaded.elapsed() < self.ttl)
+                .and_then(|cache| cache.by_name.get(&key).cloned())
+        };
+        if let Some(template) = lookup(&*self.cache.read().await) {
+            return Ok(template);
+        }
+        self.refresh().await?;
+        lookup(&*self.cache.read().await).ok_or_else(|| TemplateError::NotFound {
+            name: name.to_string(),
+            language: language.to_string(),
+        })
+    }
+
+    /// Starts a template send, checked against the cached definition
+    /// before anything reaches Meta.
+    pub fn send_template(
+        &self,
+        name: &str,
+        language: &str,
+        components: Vec<TemplateComponent>,
+    ) -> TemplateSend<'_> {
+        TemplateSend {
+            catalog: self,
+            template: Template::new(name, language, components),
+        }
+    }
+}
+
+/// A template message being assembled; see `TemplateCatalog::send_template`.
+pub struct TemplateSend<'a> {
+    catalog: &'a TemplateCatalog,
+    template: Template,
+}
+
+impl TemplateSend<'_> {
+    pub fn with_component(mut self, component: TemplateComponent) -> Self {
+        self.template.components.push(component);
+        self
+    }
+
+    /// The template, once it's approved and its parameters match.
+    pub async fn build(self) -> Result<Template, TemplateError> {
+        let definition = self
+            .catalog
+            .get(&self.template.name, &self.template.language.code)
+            .await?;
+        if definition.status != TemplateStatus::Approved {
+            return Err(TemplateError::NotApproved {
+                name: definition.name,
+                status: definition.status,
+            });
+        }
+        definition.validate(&self.template.components)?;
+        Ok(self.template)
+    }
+
+    pub async fn send(self, to: &str) -> Result<SentMessage, TemplateError> {
+        let client = Arc::clone(&self.catalog.client);
+        let template = self.build().await?;
+        Ok(client.send_template(to, template).await?)
+    }
+}
@@ -0,0 +1,371 @@
+use super::client::MediaKind;
+use crate::adapters::{InboundMedia, InboundMessage, MessageStatus, WebhookEvent};
+use crate::dlr;
+use crate::providers::signature::{secret_matches, verify_hmac_sha256, SignatureEncoding};
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::routing::{get, MethodRouter};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// Checks `X-Hub-Signature-256` (`sha256=` and a hex HMAC-SHA256 of the
+/// raw body, keyed by the app secret).
+pub fn verify_signature(app_secret: &str, signature: &str, body: &[u8]) -> bool {
+    let Some(hex) = signature.trim().strip_prefix("sha256=") else {
+        return false;
+    };
+    verify_hmac_sha256(app_secret.as_bytes(), body, hex, SignatureEncoding::Hex)
+}
+
+/// The subscription handshake: Meta calls the webhook URL with
+/// `hub.mode=subscribe`, our `hub.verify_token` and a `hub.challenge` to
+/// echo back. Returns the challenge if the token matches.
+pub fn verify_subscription(params: &HashMap<String, String>, verify_token: &str) -> Option<String> {
+    let mode = params.get("hub.mode")?;
+    let token = params.get("hub.verify_token")?;
+    (mode == "subscribe" && secret_matches(token.as_bytes(), verify_token.as_bytes()))
+        .then(|| params.get("hub.challenge").cloned())
+        .flatten()
+}
+
+/// GET handler answering the subscription handshake, for mounting next to
+/// the POST handler: `.route("/webhooks/whatsapp", subscription_route(token).post(...))`.
+pub fn subscription_route<S>(verify_token: &str) -> MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let verify_token = verify_token.to_string();
+    get(
+        move |Query(params): Query<HashMap<String, String>>| async move {
+            match verify_subscription(&params, &verify_token) {
+                Some(challenge) => (StatusCode::OK, challenge),
+                None => {
+                    warn!("WhatsApp webhook verification rejected");
+                    (StatusCode::FORBIDDEN, String::new())
+                }
+            }
+        },
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookError {
+    pub code: i64,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+/// A delivery status for one of our messages.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusUpdate {
+    /// `wamid.…` as returned when sending.
+    pub message_id: String,
+    pub recipient_id: String,
+    /// `sent`, `delivered`, `read` or `failed`.
+    pub status: String,
+    pub timestamp: Option<i64>,
+    /// Billing category of the conversation, e.g. `marketing` or `service`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing_category: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<WebhookError>,
+}
+
+impl StatusUpdate {
+    pub fn message_status(&self) -> MessageStatus {
+        match self.status.as_str() {
+            "sent" => MessageStatus::Sent,
+            "delivered" | "read" => MessageStatus::Delivered,
+            "failed" => MessageStatus::Failed,
+            _ => MessageStatus::Pending,
+        }
+    }
+
+    /// As the provider-independent delivery report.
+    pub fn to_webhook_event(&self, raw_payload: Option<Value>) -> WebhookEvent {
+        let error = self.errors.first();
+        let error_code = error.map(|e| e.code.to_string());
+        let error_message = error.map(|e| match &e.details {
+            Some(details) => format!("{}: {}", e.title, details),
+            None => e.title.clone(),
+        });
+        WebhookEvent {
+            provider_message_id: self.message_id.clone(),
+            status: self.message_status(),
+            timestamp: self.timestamp.map(|ts| ts as f64),
+            reason: dlr::normalize("whatsapp", error_code.as_deref(), error_message.as_deref()),
+            error_code,
+            error_message,
+            raw_payload,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContactPhone {
+    pub phone: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wa_id: Option<String>,
+}
+
+/// A contact card the user shared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedContact {
+    pub name: String,
+    #[serde(default)]
+    pub phones: Vec<ContactPhone>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InboundContent {
+    Text {
+        body: String,
+    },
+    /// An emoji reaction to one of our messages; an empty emoji removes it.
+    Reaction {
+        message_id: String,
+        emoji: String,
+    },
+    Contacts {
+        contacts: Vec<SharedContact>,
+    },
+    /// Fetch with `MediaManager::download`.
+    Media {
+        kind: MediaKind,
+        media_id: String,
+        mime_type: Option<String>,
+        caption: Option<String>,
+    },
+    Location {
+        latitude: f64,
+        longitude: f64,
+        name: Option<String>,
+    },
+    /// A template quick-reply or interactive button/list reply.
+    Reply {
+        payload: String,
+        text: String,
+    },
+    Unsupported {
+        message_type: String,
+    },
+}
+
+/// A message from a user to one of our numbers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InboundWhatsApp {
+    pub message_id: String,
+    /// The user's WhatsApp ID (their number, without `+`).
+    pub from: String,
+    pub profile_name: Option<String>,
+    pub timestamp: Option<i64>,
+    /// The message this one replies to, if any.
+    pub context_message_id: Option<String>,
+    pub content: InboundContent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WhatsAppEvent {
+    Status {
+        /// Our business number the status concerns.
+        phone_number_id: String,
+        update: StatusUpdate,
+    },
+    Message {
+        phone_number_id: String,
+        /// Display form of our number, e.g. `15550001234`.
+        display_phone_number: String,
+        message: InboundWhatsApp,
+    },
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+/// Unix seconds, sent as a string.
+fn unix(value: &Value) -> Option<i64> {
+    value.as_str().and_then(|ts| ts.parse().ok())
+}
+
+fn inbound_content(message: &Value) -> InboundContent {
+    let message_type = message["type"].as_str().unwrap_or_default();
+    let media_kind = match message_type {
+        "image" => Some(MediaKind::Image),
+        "video" => Some(MediaKind::Video),
+        "audio" => Some(MediaKind::Audio),
+        "document" => Some(MediaKind::Document),
+        "sticker" => Some(MediaKind::Sticker),
+        _ => None,
+    };
+    if let Some(kind) = media_kind {
+        let media = &message[message_type];
+        return InboundContent::Media {
+            kind,
+            media_id: text(&media["id"]).unwrap_or_default(),
+            mime_type: text(&media["mime_type"]),
+            caption: text(&media["caption"]),
+        };
+    }
+    match message_type {
+        "text" => InboundContent::Text {
+            body: text(&message["text"]["body"]).unwrap_or_default(),
+        },
+        "reaction" => InboundContent::Reaction {
+            message_id: text(&message["reaction"]["message_id"]).unwrap_or_default(),
+            emoji: text(&message["reaction"]["emoji"]).unwrap_or_default(),
+        },
+        "contacts" => InboundContent::Contacts {
+            contacts: message["contacts"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|contact| SharedContact {
+                    name: text(&contact["name"]["formatted_name"]).unwrap_or_default(),
+                    phones: contact["phones"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .map(|phone| ContactPhone {
+                            phone: text(&phone["phone"]).unwrap_or_default(),
+                            wa_id: text(&phone["wa_id"]),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        },
+        "location" => InboundContent::Location {
+            latitude: message["location"]["latitude"].as_f64().unwrap_or_default(),
+            longitude: message["location"]["longitude"]
+                .as_f64()
+                .unwrap_or_default(),
+            name: text(&message["location"]["name"]),
+        },
+        "button" => InboundContent::Reply {
+            payload: text(&message["button"]["payload"]).unwrap_or_default(),
+            text: text(&message["button"]["text"]).unwrap_or_default(),
+        },
+        "interactive" => {
+            let interactive = &message["interactive"];
+            let reply = match interactive["type"].as_str() {
+                Some("list_reply") => &interactive["list_reply"],
+                _ => &interactive["button_reply"],
+            };
+            InboundContent::Reply {
+                payload: text(&reply["id"]).unwrap_or_default(),
+                text: text(&reply["title"]).unwrap_or_default(),
+            }
+        }
+        other => InboundContent::Unsupported {
+            message_type: other.to_string(),
+        },
+    }
+}
+
+/// Every event in a webhook POST body. One delivery may batch several
+/// statuses and messages across numbers.
+pub fn parse_payload(body: &[u8]) -> Result<Vec<WhatsAppEvent>, String> {
+    let payload: Value =
+        serde_json::from_slice(body).map_err(|e| format!("Invalid WhatsApp webhook: {}", e))?;
+    if payload["object"] != "whatsapp_business_account" {
+        return Err(format!("Unexpected webhook object {}", payload["object"]));
+    }
+    let mut events = Vec::new();
+    let changes = payload["entry"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|entry| entry["changes"].as_array().into_iter().flatten())
+        .filter(|change| change["field"] == "messages");
+    for change in changes {
+        let value = &change["value"];
+        let phone_number_id = text(&value["metadata"]["phone_number_id"]).unwrap_or_default();
+        let display_phone_number =
+            text(&value["metadata"]["display_phone_number"]).unwrap_or_default();
+        let profile_names: HashMap<String, String> = value["contacts"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|c| Some((text(&c["wa_id"])?, text(&c["profile"]["name"])?)))
+            .collect();
+
+        for status in value["statuses"].as_array().into_iter().flatten() {
+            events.push(WhatsAppEvent::Status {
+                phone_number_id: phone_number_id.clone(),
+                update: StatusUpdate {
+                    message_id: text(&status["id"]).unwrap_or_default(),
+                    recipient_id: text(&status["recipient_id"]).unwrap_or_default(),
+                    status: text(&status["status"]).unwrap_or_default(),
+                    timestamp: unix(&status["timestamp"]),
+                    pricing_category: text(&status["pricing"]["category"]),
+                    errors: status["errors"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .map(|e| WebhookError {
+                            code: e["code"].as_i64().unwrap_or_default(),
+                            title: text(&e["title"]).unwrap_or_default(),
+                            details: text(&e["error_data"]["details"]),
+                        })
+                        .collect(),
+                },
+            });
+        }
+        for message in value["messages"].as_array().into_iter().flatten() {
+            let from = text(&message["from"]).unwrap_or_default();
+            events.push(WhatsAppEvent::Message {
+                phone_number_id: phone_number_id.clone(),
+                display_phone_number: display_phone_number.clone(),
+                message: InboundWhatsApp {
+                    message_id: text(&message["id"]).unwrap_or_default(),
+                    profile_name: profile_names.get(&from).cloned(),
+                    from,
+                    timestamp: unix(&message["timestamp"]),
+                    context_message_id: text(&message["context"]["id"]),
+                    content: inbound_content(message),
+                },
+            });
+        }
+    }
+    Ok(events)
+}
+
+impl InboundWhatsApp {
+    /// As the provider-independent inbound message. Text-like content
+    /// becomes the body; media `url`s are media IDs for
+    /// `MediaManager::download`.
+    pub fn to_inbound_message(&self, to: &str, raw_payload: Option<Value>) -> InboundMessage {
+        let (body, media) = match &self.content {
+            InboundContent::Text { body } => (body.clone(), Vec::new()),
+            InboundContent::Reply { text, .. } => (text.clone(), Vec::new()),
+            InboundContent::Reaction { emoji, .. } => (emoji.clone(), Vec::new()),
+            InboundContent::Media {
+                media_id,
+                mime_type,
+                caption,
+                ..
+            } => (
+                caption.clone().unwrap_or_default(),
+                vec![InboundMedia {
+                    url: media_id.clone(),
+                    content_type: mime_type.clone(),
+                }],
+            ),
+            _ => (String::new(), Vec::new()),
+        };
+        InboundMessage {
+            provider_message_id: Some(self.message_id.clone()),
+            from: format!("+{}", self.from.trim_start_matches('+')),
+            to: format!("+{}", to.trim_start_matches('+')),
+            body,
+            media,
+            timestamp: self.timestamp.map(|ts| ts as f64),
+            raw_payload,
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, ItemFn, LitStr, ReturnType, Type};
+
+/// Times an async fn into `GLOBAL_METRICS` and counts its outcome.
+///
+/// ```
+/// # use smsly_core::metrics::{instrument_metric, GLOBAL_METRICS};
+/// # struct SendResult;
+/// # #[derive(Debug)]
+/// # struct AdapterError;
+/// # struct Adapter;
+/// # impl Adapter {
+/// #[instrument_metric(name = "provider_send", labels(provider = "twilio"))]
+/// async fn send(&self, to: &str) -> Result<SendResult, AdapterError> {
+/// #     if to.is_empty() {
+/// #         return Err(AdapterError);
+/// #     }
+/// #     Ok(SendResult)
+/// }
+/// # }
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// #     assert!(Adapter.send("").await.is_err());
+/// #     let counters = GLOBAL_METRICS.counter_entries();
+/// #     assert!(counters.iter().any(|(key, n)| key.contains("provider_send") && *n == 1));
+/// # }
+/// ```
+///
+/// Records `<name>_duration_seconds` (histogram) and `<name>` (counter) with an
+/// `outcome` label of `success` or `failure`. Functions returning `Result` fail
+/// on `Err`; any other return type always counts as success. `name` defaults to
+/// the function name.
+#[proc_macro_attribute]
+pub fn instrument_metric(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut name: Option<LitStr> = None;
+    let mut labels: Vec<(String, LitStr)> = Vec::new();
+
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("labels") {
+            meta.parse_nested_meta(|label| {
+                let key = label
+                    .path
+                    .get_ident()
+                    .ok_or_else(|| label.error("label key must be an identifier"))?
+                    .to_string();
+                labels.push((key, label.value()?.parse()?));
+                Ok(())
+            })
+        } else {
+            Err(meta.error("expected `name` or `labels(...)`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+
+    let func = parse_macro_input!(item as ItemFn);
+    if func.sig.asyncness.is_none() {
+        return syn::Error::new_spanned(
+            func.sig.fn_token,
+            "instrument_metric requires an async fn",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let name = name.unwrap_or_else(|| LitStr::new(&func.sig.ident.to_string(), Span::call_site()));
+    let duration_name = LitStr::new(&format!("{}_duration_seconds", name.value()), name.span());
+    let label_keys = labels.iter().map(|(k, _)| k);
+    let label_values = labels.iter().map(|(_, v)| v);
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = func;
+    // Annotating the result type lets `?` inside the body infer its error type.
+    let (result_binding, outcome) = match &sig.output {
+        ReturnType::Type(_, ty) if returns_result(&sig.output) => (
+            quote!(__result: #ty),
+            quote!(if __result.is_ok() {
+                "success"
+            } else {
+                "failure"
+            }),
+        ),
+        _ => (quote!(__result), quote!("success")),
+    };
+
+    quote! {
+        #(#attrs)*
+        #vis #sig {
+            let __labels: ::std::collections::HashMap<::std::string::String, ::std::string::String> =
+                ::std::collections::HashMap::from([
+                    #((#label_keys.to_string(), #label_values.to_string()),)*
+                ]);
+            let __timer = ::smsly_core::metrics::GLOBAL_METRICS
+                .start_timer(#duration_name, Some(__labels.clone()));
+            let #result_binding = async move #block.await;
+            __timer.stop();
+            let mut __labels = __labels;
+            __labels.insert("outcome".to_string(), #outcome.to_string());
+            ::smsly_core::metrics::GLOBAL_METRICS.increment(#name, 1, Some(__labels));
+            __result
+        }
+    }
+    .into()
+}
+
+fn returns_result(output: &ReturnType) -> bool {
+    match output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .map(|s| s.ident == "Result")
+                .unwrap_or(false),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
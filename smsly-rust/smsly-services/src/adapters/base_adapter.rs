@@ -1,7 +1,9 @@
 use crate::config::Settings;
 use serde_json::Value;
+use smsly_core::inter_service_metrics::record_call;
 use smsly_core::metrics::track_metric;
 use std::collections::HashMap;
+use std::time::Duration;
 
 pub struct BaseAdapter {
     pub service_name: String,
@@ -23,11 +25,13 @@ impl BaseAdapter {
         }
     }
 
+    /// `status` is a `record_call` status (`success`, `client_error`,
+    /// `server_error`, `timeout`, `exception`, ...).
     pub fn track_request(
         &self,
         operation: &str,
         provider: &str,
-        success: bool,
+        status: &str,
         duration: f64,
         metadata: Option<HashMap<String, Value>>,
     ) {
@@ -41,11 +45,18 @@ impl BaseAdapter {
             Value::String(operation.to_string()),
         );
         meta.insert("provider".to_string(), Value::String(provider.to_string()));
-        meta.insert("success".to_string(), Value::Bool(success));
+        meta.insert("success".to_string(), Value::Bool(status == "success"));
+        meta.insert("status".to_string(), Value::String(status.to_string()));
         if let Some(n) = serde_json::Number::from_f64(duration * 1000.0) {
             meta.insert("duration_ms".to_string(), Value::Number(n));
         }
 
         track_metric("adapter.request", meta);
+        record_call(
+            &self.service_name,
+            operation,
+            status,
+            Duration::from_secs_f64(duration.max(0.0)),
+        );
     }
 }
@@ -48,8 +48,15 @@ impl SMSAdapter {
         };
 
         let duration = start.elapsed().unwrap_or_default().as_secs_f64();
+        // Neither path gets a response back when it fails: the microservice
+        // or the legacy service is unavailable.
+        let status = if result.success {
+            "success"
+        } else {
+            "exception"
+        };
         self.base
-            .track_request("send_sms", &result.provider, result.success, duration, None);
+            .track_request("send_sms", &result.provider, status, duration, None);
 
         result
     }
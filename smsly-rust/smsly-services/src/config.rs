@@ -1,8 +1,110 @@
+use smsly_core::circuit_breaker::{CircuitPolicies, CircuitRegistry};
+use smsly_core::database::{DatabaseDriver, PgSslMode, PoolConfig};
+use smsly_core::providers::{VonageAdapter, VonageConfig};
 use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+fn env_parse<T: FromStr>(key: &str) -> Option<T> {
+    env::var(key).ok().and_then(|v| v.trim().parse().ok())
+}
+
+fn env_flag(key: &str, default: bool) -> bool {
+    env::var(key)
+        .map(|v| v.to_lowercase() == "true" || v == "1")
+        .unwrap_or(default)
+}
+
+#[derive(Clone, Debug)]
+pub struct DatabaseConfig {
+    pub driver: DatabaseDriver,
+    pub url: String,
+    pub pool_size: u32,
+    pub max_overflow: u32,
+    pub acquire_timeout: Duration,
+    pub idle_timeout: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
+    pub statement_timeout: Option<Duration>,
+    pub ssl_mode: Option<PgSslMode>,
+    pub application_name: Option<String>,
+    pub pool_pre_ping: bool,
+    pub echo: bool,
+}
+
+/// Shared .env files carry SQLAlchemy URLs (`postgresql+asyncpg://`,
+/// `mysql+aiomysql://`); sqlx wants the bare scheme.
+fn strip_sqlalchemy_dialect(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) => match scheme.split_once('+') {
+            Some((scheme, _)) => format!("{}://{}", scheme, rest),
+            None => url.to_string(),
+        },
+        None => url.to_string(),
+    }
+}
+
+impl DatabaseConfig {
+    pub fn from_env() -> Self {
+        Self {
+            driver: DatabaseDriver::from_env(),
+            url: strip_sqlalchemy_dialect(&env::var("DATABASE_URL").unwrap_or_default()),
+            pool_size: env_parse("DATABASE_POOL_SIZE").unwrap_or(10),
+            max_overflow: env_parse("DATABASE_MAX_OVERFLOW").unwrap_or(20),
+            acquire_timeout: Duration::from_secs(
+                env_parse("DATABASE_ACQUIRE_TIMEOUT_SECS").unwrap_or(30),
+            ),
+            idle_timeout: Some(Duration::from_secs(
+                env_parse("DATABASE_IDLE_TIMEOUT_SECS").unwrap_or(600),
+            ))
+            .filter(|d| !d.is_zero()),
+            max_lifetime: Some(Duration::from_secs(
+                env_parse("DATABASE_MAX_LIFETIME_SECS").unwrap_or(1800),
+            ))
+            .filter(|d| !d.is_zero()),
+            statement_timeout: env_parse("DATABASE_STATEMENT_TIMEOUT_MS")
+                .map(Duration::from_millis),
+            ssl_mode: env_parse("DATABASE_SSL_MODE"),
+            application_name: env::var("DATABASE_APPLICATION_NAME")
+                .or_else(|_| env::var("SERVICE_NAME"))
+                .ok()
+                .filter(|v| !v.is_empty()),
+            pool_pre_ping: env_flag("DATABASE_POOL_PRE_PING", true),
+            echo: env_flag("DATABASE_ECHO", false),
+        }
+    }
+
+    pub fn pool_config(&self) -> PoolConfig {
+        let mut config = PoolConfig::new(&self.url)
+            .with_min_connections(self.pool_size)
+            .with_max_connections(self.pool_size + self.max_overflow);
+        config.acquire_timeout = self.acquire_timeout;
+        config.idle_timeout = self.idle_timeout;
+        config.max_lifetime = self.max_lifetime;
+        config.statement_timeout = self.statement_timeout;
+        config.ssl_mode = self.ssl_mode;
+        config.application_name = self.application_name.clone();
+        config.test_before_acquire = self.pool_pre_ping;
+        config.log_statements = self.echo;
+        config
+    }
+}
 
 #[derive(Clone)]
 pub struct Settings {
     pub internal_api_secret: String,
+    pub otel_exporter_otlp_endpoint: Option<String>,
+    pub otel_metric_export_interval_ms: u64,
+    pub database: DatabaseConfig,
+    pub vonage: Option<VonageConfig>,
+    /// From `CIRCUIT_DEFAULT_*`, and `CIRCUIT_<NAME>_*` for each target in
+    /// `CIRCUIT_BREAKERS`; see `CircuitPolicies::from_env`.
+    pub circuit_breakers: CircuitPolicies,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Settings {
@@ -11,6 +113,16 @@ impl Settings {
 
         Self {
             internal_api_secret: env::var("INTERNAL_API_SECRET").unwrap_or_default(),
+            otel_exporter_otlp_endpoint: env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            otel_metric_export_interval_ms: env::var("OTEL_METRIC_EXPORT_INTERVAL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60_000),
+            database: DatabaseConfig::from_env(),
+            vonage: VonageConfig::from_env(),
+            circuit_breakers: CircuitPolicies::from_env(),
         }
     }
 
@@ -28,4 +140,31 @@ impl Settings {
         .map(|v| v.to_lowercase() == "true" || v == "1")
         .unwrap_or(true)
     }
+
+    /// Vonage adapter when `VONAGE_API_KEY` and `VONAGE_API_SECRET` are set.
+    pub fn vonage_adapter(&self) -> Option<VonageAdapter> {
+        self.vonage.clone().map(VonageAdapter::new)
+    }
+
+    /// Registry creating breakers with the configured policies.
+    pub fn circuit_registry(&self) -> CircuitRegistry {
+        CircuitRegistry::new().with_policies(self.circuit_breakers.clone())
+    }
+
+    /// `OtlpConfig::from_env`, so `OTEL_EXPORTER_OTLP_METRICS_ENDPOINT` wins
+    /// over the generic endpoint.
+    #[cfg(feature = "otel")]
+    pub fn otlp_config(&self) -> Option<smsly_core::metrics::otel::OtlpConfig> {
+        use smsly_core::metrics::otel::OtlpConfig;
+
+        let mut config = OtlpConfig::from_env().or_else(|| {
+            self.otel_exporter_otlp_endpoint
+                .as_deref()
+                .filter(|e| !e.is_empty())
+                .map(OtlpConfig::new)
+        })?;
+        config.export_interval =
+            std::time::Duration::from_millis(self.otel_metric_export_interval_ms);
+        Some(config)
+    }
 }
@@ -7,9 +7,10 @@ use axum::{
     Json,
 };
 use constant_time_eq::constant_time_eq;
-use redis::{AsyncCommands, Client, Script};
+use redis::{Client, Script};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use smsly_core::database::TenantContext;
 use std::sync::Arc;
 use tracing::warn;
 
@@ -24,6 +25,18 @@ pub struct InternalContext {
     pub is_internal: bool,
 }
 
+impl InternalContext {
+    /// Tenant scope for `smsly_core::database::begin_for_tenant`; `None` when
+    /// the caller is not acting for an organization.
+    pub fn tenant(&self) -> Option<TenantContext> {
+        let organization_id = self.organization_id.as_deref().filter(|o| !o.is_empty())?;
+        Some(TenantContext {
+            organization_id: organization_id.to_string(),
+            user_id: self.user_id.clone(),
+        })
+    }
+}
+
 fn default_account_type() -> String {
     "casual".to_string()
 }